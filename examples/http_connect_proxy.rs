@@ -1,4 +1,5 @@
-//! An example to showcase how one can build an unauthenticated http proxy server.
+//! An example to showcase how one can build an unauthenticated http proxy server,
+//! using the [`ForwardProxyService`] to serve both plain http requests and CONNECT requests.
 //!
 //! This example also demonstrates how one can define their own username label parser,
 //! next to the built-in username label parsers.
@@ -6,7 +7,7 @@
 //! # Run the example
 //!
 //! ```sh
-//! cargo run --example http_connect_proxy --features=http-full
+//! cargo run --example http_connect_proxy --features=http-full,proxy
 //! ```
 //!
 //! # Expected output
@@ -59,30 +60,23 @@
 use rama::{
     context::Extensions,
     http::{
-        client::HttpClient,
-        layer::{
-            proxy_auth::ProxyAuthLayer,
-            remove_header::{RemoveRequestHeaderLayer, RemoveResponseHeaderLayer},
-            trace::TraceLayer,
-            upgrade::{UpgradeLayer, Upgraded},
-        },
-        matcher::{DomainMatcher, HttpMatcher, MethodMatcher},
+        layer::{proxy_auth::ProxyAuthLayer, trace::TraceLayer},
+        matcher::{DomainMatcher, HttpMatcher},
+        proxy::ForwardProxyService,
         response::Json,
         server::HttpServer,
         service::web::{extract::Path, match_service},
-        Body, IntoResponse, Request, Response, StatusCode,
+        Request, StatusCode,
     },
     layer::HijackLayer,
-    net::http::RequestContext,
     net::stream::layer::http::BodyLimitLayer,
     net::{address::Domain, user::Basic},
     rt::Executor,
-    service::service_fn,
-    tcp::{client::default_tcp_connect, server::TcpListener, utils::is_connection_error},
+    tcp::server::TcpListener,
     username::{
         UsernameLabelParser, UsernameLabelState, UsernameLabels, UsernameOpaqueLabelParser,
     },
-    Context, Layer, Service,
+    Context, Layer,
 };
 use serde::Deserialize;
 use serde_json::json;
@@ -143,15 +137,10 @@ async fn main() {
                             _ => StatusCode::NOT_FOUND,
                         })
                     ),
-                    UpgradeLayer::new(
-                        MethodMatcher::CONNECT,
-                        service_fn(http_connect_accept),
-                        service_fn(http_connect_proxy),
-                    ),
-                    RemoveResponseHeaderLayer::hop_by_hop(),
-                    RemoveRequestHeaderLayer::hop_by_hop(),
                 )
-            .layer(service_fn(http_plain_proxy)));
+            // serves plain http requests, removing the hop-by-hop headers,
+            // as well as CONNECT requests, tunnelling the upgraded connection to its target
+            .layer(ForwardProxyService::new()));
 
             tcp_service.serve_graceful(guard, (
                 // protect the http proxy from too large bodies, both from request and response end
@@ -165,66 +154,6 @@ async fn main() {
         .expect("graceful shutdown");
 }
 
-async fn http_connect_accept<S>(
-    mut ctx: Context<S>,
-    req: Request,
-) -> Result<(Response, Context<S>, Request), Response>
-where
-    S: Clone + Send + Sync + 'static,
-{
-    match ctx.get_or_try_insert_with_ctx::<RequestContext, _>(|ctx| (ctx, &req).try_into()) {
-        Ok(request_ctx) => tracing::info!("accept CONNECT to {}", request_ctx.authority),
-        Err(err) => {
-            tracing::error!(err = %err, "error extracting authority");
-            return Err(StatusCode::BAD_REQUEST.into_response());
-        }
-    }
-
-    Ok((StatusCode::OK.into_response(), ctx, req))
-}
-
-async fn http_connect_proxy<S>(ctx: Context<S>, mut upgraded: Upgraded) -> Result<(), Infallible>
-where
-    S: Clone + Send + Sync + 'static,
-{
-    let authority = ctx // assumption validated by `http_connect_accept`
-        .get::<RequestContext>()
-        .unwrap()
-        .authority
-        .clone();
-    tracing::info!("CONNECT to {authority}");
-    let (mut stream, _) = match default_tcp_connect(&ctx, authority).await {
-        Ok(stream) => stream,
-        Err(err) => {
-            tracing::error!(error = %err, "error connecting to host");
-            return Ok(());
-        }
-    };
-    if let Err(err) = tokio::io::copy_bidirectional(&mut upgraded, &mut stream).await {
-        if !is_connection_error(&err) {
-            tracing::error!(error = %err, "error copying data");
-        }
-    }
-    Ok(())
-}
-
-async fn http_plain_proxy<S>(ctx: Context<S>, req: Request) -> Result<Response, Infallible>
-where
-    S: Clone + Send + Sync + 'static,
-{
-    let client = HttpClient::default();
-    match client.serve(ctx, req).await {
-        Ok(resp) => Ok(resp),
-        Err(err) => {
-            tracing::error!(error = %err, "error in client request");
-            Ok(Response::builder()
-                .status(StatusCode::INTERNAL_SERVER_ERROR)
-                .body(Body::empty())
-                .unwrap())
-        }
-    }
-}
-
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum Priority {
     High,
@@ -0,0 +1,135 @@
+//! An example to show how to serve gRPC methods over h2,
+//! using the [`Grpc`] endpoints, [`WebService`] and [`HttpServer`] from Rama.
+//!
+//! [`Grpc`]: crate::http::grpc::server::Grpc
+//! [`WebService`]: crate::http::service::web::WebService
+//! [`HttpServer`]: crate::http::server::HttpServer
+//!
+//! This example will create a server that listens on `127.0.0.1:62018`.
+//!
+//! # Run the example
+//!
+//! ```sh
+//! cargo run --example http_grpc --features=grpc,http-full
+//! ```
+//!
+//! # Expected output
+//!
+//! The server will start and listen on `:62018`. You can use `grpcurl`
+//! (without reflection, so providing the proto definition) to call its methods:
+//!
+//! ```proto
+//! syntax = "proto3";
+//! package math;
+//!
+//! message Number {
+//!   int64 value = 1;
+//! }
+//!
+//! service Math {
+//!   rpc Double(Number) returns (Number);
+//!   rpc CountTo(Number) returns (stream Number);
+//! }
+//! ```
+//!
+//! ```sh
+//! grpcurl -plaintext -proto math.proto -d '{"value": 21}' 127.0.0.1:62018 math.Math/Double
+//! grpcurl -plaintext -proto math.proto -d '{"value": 3}' 127.0.0.1:62018 math.Math/CountTo
+//! ```
+//!
+//! You should see a response with the value `42`,
+//! respectively a stream of the values `1`, `2` and `3`.
+
+use rama::{
+    http::{
+        grpc::{self, server::Grpc, CompressionEncoding, ProstCodec, Status},
+        layer::trace::TraceLayer,
+        server::HttpServer,
+        service::web::WebService,
+    },
+    rt::Executor,
+    service::service_fn,
+    Layer,
+};
+use std::time::Duration;
+use tracing::level_filters::LevelFilter;
+use tracing_subscriber::{fmt, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct Number {
+    #[prost(int64, tag = "1")]
+    value: i64,
+}
+
+type NumberCodec = ProstCodec<Number, Number>;
+
+async fn double(req: grpc::Request<Number>) -> Result<grpc::Response<Number>, Status> {
+    let value = req.into_inner().value;
+    let value = value
+        .checked_mul(2)
+        .ok_or_else(|| Status::out_of_range("number too large to double"))?;
+    Ok(grpc::Response::new(Number { value }))
+}
+
+async fn count_to(
+    req: grpc::Request<Number>,
+) -> Result<
+    grpc::Response<impl futures::Stream<Item = Result<Number, Status>> + Send + 'static>,
+    Status,
+> {
+    let n = req.into_inner().value;
+    Ok(grpc::Response::new(futures::stream::unfold(
+        1,
+        move |value| async move {
+            if value > n {
+                return None;
+            }
+            // count slowly, so deadlines of clients can expire
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            Some((Ok(Number { value }), value + 1))
+        },
+    )))
+}
+
+#[tokio::main]
+async fn main() {
+    tracing_subscriber::registry()
+        .with(fmt::layer())
+        .with(
+            EnvFilter::builder()
+                .with_default_directive(LevelFilter::DEBUG.into())
+                .from_env_lossy(),
+        )
+        .init();
+
+    let graceful = rama::graceful::Shutdown::default();
+
+    graceful.spawn_task_fn(|guard| async move {
+        let exec = Executor::graceful(guard);
+        HttpServer::h2(exec)
+            .listen(
+                "127.0.0.1:62018",
+                TraceLayer::new_for_http().layer(
+                    WebService::default()
+                        .post(
+                            "/math.Math/Double",
+                            Grpc::new(NumberCodec::new())
+                                .accept_compressed(CompressionEncoding::Gzip)
+                                .send_compressed(CompressionEncoding::Gzip)
+                                .unary(service_fn(double)),
+                        )
+                        .post(
+                            "/math.Math/CountTo",
+                            Grpc::new(NumberCodec::new()).server_streaming(service_fn(count_to)),
+                        ),
+                ),
+            )
+            .await
+            .expect("failed to run service");
+    });
+
+    graceful
+        .shutdown_with_limit(Duration::from_secs(30))
+        .await
+        .expect("graceful shutdown");
+}
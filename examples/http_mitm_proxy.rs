@@ -20,13 +20,13 @@
 //! # Run the example
 //!
 //! ```sh
-//! cargo run --example http_mitm_proxy --features=http-full,rustls
+//! cargo run --example http_mitm_proxy --features=http-full,proxy,rustls
 //! ```
 //!
 //! Or alternatively run it using boring (ssl)
 //!
 //! ```sh
-//! cargo run --example http_mitm_proxy --features=http-full,boring
+//! cargo run --example http_mitm_proxy --features=http-full,proxy,boring
 //! ```
 //!
 //! # Expected output
@@ -37,11 +37,19 @@
 //! curl -v -x http://127.0.0.1:62017 --proxy-user 'john:secret' http://www.example.com/
 //! curl -k -v -x http://127.0.0.1:62017 --proxy-user 'john:secret' https://www.example.com/
 //! ```
+//!
+//! CONNECT requests are served by the [`ForwardProxyService`], which hands off
+//! the upgraded connection to the MITM service and accounts its traffic.
+//!
+//! WebSockets are relayed as well, be it bootstrapped over http/1.1
+//! or over h2 using the extended CONNECT protocol (RFC 8441),
+//! translating the handshake in case the upstream server speaks another http version.
 
 use rama::{
     error::{BoxError, ErrorContext, OpaqueError},
     http::{
         client::HttpClient,
+        core::upgrade::OnUpgrade,
         layer::{
             map_response_body::MapResponseBodyLayer,
             proxy_auth::ProxyAuthLayer,
@@ -49,14 +57,14 @@ use rama::{
             required_header::AddRequiredRequestHeadersLayer,
             trace::TraceLayer,
             traffic_writer::{self, RequestWriterLayer},
-            upgrade::{UpgradeLayer, Upgraded},
+            upgrade::{UpgradeLayer, Upgraded, WebSocketMatcher},
         },
         matcher::MethodMatcher,
+        proxy::ForwardProxyService,
         server::HttpServer,
         Body, IntoResponse, Request, Response, StatusCode,
     },
-    layer::ConsumeErrLayer,
-    net::http::RequestContext,
+    layer::{ConsumeErrLayer, HijackLayer},
     net::stream::layer::http::BodyLimitLayer,
     net::tls::{
         client::{ClientConfig, ClientHelloExtension, ServerVerifyMode},
@@ -114,10 +122,11 @@ async fn main() -> Result<(), BoxError> {
                 // See [`ProxyAuthLayer::with_labels`] for more information,
                 // e.g. can also be used to extract upstream proxy filters
                 ProxyAuthLayer::new(Basic::new("john", "secret")),
-                UpgradeLayer::new(
+                // CONNECT requests are accepted by the forward proxy,
+                // handing off the upgraded connection to the MITM service
+                HijackLayer::new(
                     MethodMatcher::CONNECT,
-                    service_fn(http_connect_accept),
-                    service_fn(http_connect_proxy),
+                    ForwardProxyService::new().mitm(service_fn(http_connect_proxy)),
                 ),
             )
                 .layer(http_mitm_service),
@@ -143,23 +152,6 @@ async fn main() -> Result<(), BoxError> {
     Ok(())
 }
 
-async fn http_connect_accept(
-    mut ctx: Context,
-    req: Request,
-) -> Result<(Response, Context, Request), Response> {
-    match ctx.get_or_try_insert_with_ctx::<RequestContext, _>(|ctx| (ctx, &req).try_into()) {
-        Ok(request_ctx) => {
-            tracing::info!("accept CONNECT to {}", request_ctx.authority);
-        }
-        Err(err) => {
-            tracing::error!(err = %err, "error extracting authority");
-            return Err(StatusCode::BAD_REQUEST.into_response());
-        }
-    }
-
-    Ok((StatusCode::OK.into_response(), ctx, req))
-}
-
 async fn http_connect_proxy(ctx: Context, upgraded: Upgraded) -> Result<(), Infallible> {
     // In the past we deleted the request context here, as such:
     // ```
@@ -193,6 +185,13 @@ fn new_http_mitm_proxy(
     (
         MapResponseBodyLayer::new(Body::new),
         TraceLayer::new_for_http(),
+        // websocket handshakes are forwarded as-is (hop-by-hop headers included),
+        // after which the upgraded streams are relayed
+        UpgradeLayer::new(
+            WebSocketMatcher::new(),
+            service_fn(http_mitm_websocket_accept),
+            service_fn(http_mitm_websocket_relay),
+        ),
         RemoveResponseHeaderLayer::hop_by_hop(),
         RemoveRequestHeaderLayer::hop_by_hop(),
         ConsumeErrLayer::default(),
@@ -208,6 +207,75 @@ async fn http_mitm_proxy(ctx: Context, req: Request) -> Result<Response, Infalli
     // This function will receive all requests going through this proxy,
     // be it sent via HTTP or HTTPS, both are equally visible. Hence... MITM
 
+    let client = new_mitm_http_client();
+    match client.serve(ctx, req).await {
+        Ok(resp) => Ok(resp),
+        Err(err) => {
+            tracing::error!(error = ?err, "error in client request");
+            Ok(Response::builder()
+                .status(StatusCode::INTERNAL_SERVER_ERROR)
+                .body(Body::empty())
+                .unwrap())
+        }
+    }
+}
+
+async fn http_mitm_websocket_accept(
+    ctx: Context,
+    mut req: Request,
+) -> Result<(Response, Context, Request), Response> {
+    // the downstream upgrade is kept aside, as the request itself is forwarded upstream
+    let Some(downstream_upgrade) = req.extensions_mut().remove::<OnUpgrade>() else {
+        return Err(StatusCode::BAD_REQUEST.into_response());
+    };
+
+    let client = new_mitm_http_client();
+    let mut resp = match client.serve(ctx.clone(), req).await {
+        Ok(resp) => resp,
+        Err(err) => {
+            tracing::error!(error = ?err, "error in client websocket handshake");
+            return Err(StatusCode::BAD_GATEWAY.into_response());
+        }
+    };
+
+    // no upgrade means that the upstream server refused the websocket
+    let Some(upstream_upgrade) = resp.extensions_mut().remove::<OnUpgrade>() else {
+        return Err(resp);
+    };
+
+    let mut ctx = ctx;
+    ctx.insert(upstream_upgrade);
+
+    let mut downstream_req = Request::default();
+    downstream_req.extensions_mut().insert(downstream_upgrade);
+
+    Ok((resp, ctx, downstream_req))
+}
+
+async fn http_mitm_websocket_relay(
+    ctx: Context,
+    mut downstream: Upgraded,
+) -> Result<(), Infallible> {
+    let Some(upstream_upgrade) = ctx.get::<OnUpgrade>().cloned() else {
+        tracing::error!("missing upstream websocket upgrade");
+        return Ok(());
+    };
+
+    match upstream_upgrade.await {
+        Ok(mut upstream) => {
+            if let Err(err) = tokio::io::copy_bidirectional(&mut downstream, &mut upstream).await {
+                tracing::debug!(error = %err, "websocket relay closed with error");
+            }
+        }
+        Err(err) => {
+            tracing::error!(error = %err, "upstream websocket upgrade failed");
+        }
+    }
+
+    Ok(())
+}
+
+fn new_mitm_http_client() -> HttpClient {
     // NOTE: use a custom connector (layers) in case you wish to add custom features,
     // such as upstream proxies or other configurations
     let mut client = HttpClient::default();
@@ -221,16 +289,7 @@ async fn http_mitm_proxy(ctx: Context, req: Request) -> Result<Response, Infalli
         ]),
         ..Default::default()
     });
-    match client.serve(ctx, req).await {
-        Ok(resp) => Ok(resp),
-        Err(err) => {
-            tracing::error!(error = ?err, "error in client request");
-            Ok(Response::builder()
-                .status(StatusCode::INTERNAL_SERVER_ERROR)
-                .body(Body::empty())
-                .unwrap())
-        }
-    }
+    client
 }
 
 // NOTE: for a production service you ideally use
@@ -1,12 +1,13 @@
 //! This example demonstrates how to create an https proxy.
 //!
 //! This proxy example does not perform any TLS termination on the actual proxied traffic.
-//! It is an adoptation of the `http_connect_proxy` example with tls termination for the incoming connections.
+//! It is an adoptation of the `http_connect_proxy` example with tls termination for the incoming connections,
+//! using the [`ForwardProxyService`] to serve both plain http requests and CONNECT requests.
 //!
 //! # Run the example
 //!
 //! ```sh
-//! cargo run --example https_connect_proxy --features=http-full,rustls
+//! cargo run --example https_connect_proxy --features=http-full,proxy,rustls
 //! ```
 //!
 //! # Expected output
@@ -26,31 +27,22 @@
 use rama::{
     graceful::Shutdown,
     http::{
-        client::HttpClient,
-        layer::{
-            proxy_auth::ProxyAuthLayer,
-            trace::TraceLayer,
-            upgrade::{UpgradeLayer, Upgraded},
-        },
-        matcher::MethodMatcher,
+        layer::{proxy_auth::ProxyAuthLayer, trace::TraceLayer},
+        proxy::ForwardProxyService,
         server::HttpServer,
-        Body, IntoResponse, Request, Response, StatusCode,
     },
-    net::http::RequestContext,
     net::stream::layer::http::BodyLimitLayer,
     net::tls::{
         server::{SelfSignedData, ServerAuth, ServerConfig},
-        ApplicationProtocol, SecureTransport,
+        ApplicationProtocol,
     },
     net::user::Basic,
     rt::Executor,
-    service::service_fn,
-    tcp::{client::default_tcp_connect, server::TcpListener, utils::is_connection_error},
+    tcp::server::TcpListener,
     tls::std::server::TlsAcceptorLayer,
-    Context, Layer, Service,
+    Layer,
 };
 
-use std::convert::Infallible;
 use std::time::Duration;
 use tracing::metadata::LevelFilter;
 use tracing_subscriber::{fmt, prelude::*, EnvFilter};
@@ -96,13 +88,8 @@ async fn main() {
                 // See [`ProxyAuthLayer::with_labels`] for more information,
                 // e.g. can also be used to extract upstream proxy filter
                 ProxyAuthLayer::new(Basic::new("john", "secret")),
-                UpgradeLayer::new(
-                    MethodMatcher::CONNECT,
-                    service_fn(http_connect_accept),
-                    service_fn(http_connect_proxy),
-                ),
             )
-                .layer(service_fn(http_plain_proxy)),
+                .layer(ForwardProxyService::new()),
         );
 
         tcp_service
@@ -123,70 +110,3 @@ async fn main() {
         .await
         .expect("graceful shutdown");
 }
-
-async fn http_connect_accept<S>(
-    mut ctx: Context<S>,
-    req: Request,
-) -> Result<(Response, Context<S>, Request), Response>
-where
-    S: Clone + Send + Sync + 'static,
-{
-    match ctx.get_or_try_insert_with_ctx::<RequestContext, _>(|ctx| (ctx, &req).try_into()) {
-        Ok(request_ctx) => tracing::info!("accept CONNECT to {}", request_ctx.authority),
-        Err(err) => {
-            tracing::error!(err = %err, "error extracting authority");
-            return Err(StatusCode::BAD_REQUEST.into_response());
-        }
-    }
-
-    tracing::info!(
-        "proxy secure transport ingress: {:?}",
-        ctx.get::<SecureTransport>()
-    );
-
-    Ok((StatusCode::OK.into_response(), ctx, req))
-}
-
-async fn http_connect_proxy<S>(ctx: Context<S>, mut upgraded: Upgraded) -> Result<(), Infallible>
-where
-    S: Clone + Send + Sync + 'static,
-{
-    let authority = ctx // assumption validated by `http_connect_accept`
-        .get::<RequestContext>()
-        .unwrap()
-        .authority
-        .clone();
-    tracing::info!("CONNECT to {authority}");
-    let (mut stream, _) = match default_tcp_connect(&ctx, authority).await {
-        Ok(stream) => stream,
-        Err(err) => {
-            tracing::error!(error = %err, "error connecting to host");
-            return Ok(());
-        }
-    };
-    if let Err(err) = tokio::io::copy_bidirectional(&mut upgraded, &mut stream).await {
-        if !is_connection_error(&err) {
-            tracing::error!(error = %err, "error copying data");
-        }
-    }
-    Ok(())
-}
-
-async fn http_plain_proxy<S>(ctx: Context<S>, req: Request) -> Result<Response, Infallible>
-where
-    S: Clone + Send + Sync + 'static,
-{
-    let client = HttpClient::default();
-    let uri = req.uri().clone();
-    tracing::debug!(uri = %req.uri(), "proxy connect plain text request");
-    match client.serve(ctx, req).await {
-        Ok(resp) => Ok(resp),
-        Err(err) => {
-            tracing::error!(error = %err, uri = %uri, "error in client request");
-            Ok(Response::builder()
-                .status(StatusCode::INTERNAL_SERVER_ERROR)
-                .body(Body::empty())
-                .unwrap())
-        }
-    }
-}
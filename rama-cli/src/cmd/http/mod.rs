@@ -24,8 +24,8 @@ use rama::{
     net::{
         address::ProxyAddress,
         tls::{
-            client::{ClientConfig, ClientHelloExtension, ServerVerifyMode},
-            ApplicationProtocol,
+            client::{ClientConfig, ClientHelloExtension, ServerVerifyMode, SpkiPin, SpkiPinSet},
+            ApplicationProtocol, DataEncoding,
         },
         user::ProxyCredential,
     },
@@ -33,7 +33,7 @@ use rama::{
     service::service_fn,
     Context, Layer, Service,
 };
-use std::{io::IsTerminal, time::Duration};
+use std::{io::IsTerminal, sync::Arc, time::Duration};
 use terminal_prompt::Terminal;
 use tokio::sync::oneshot;
 use tracing::level_filters::LevelFilter;
@@ -89,6 +89,17 @@ pub struct CliCommandHttp {
     /// skip Tls certificate verification
     insecure: bool,
 
+    #[arg(long, conflicts_with = "insecure")]
+    /// the (PEM) file path of the CA certificate(s) to verify the server with,
+    /// instead of the default root store
+    cacert: Option<String>,
+
+    #[arg(long, conflicts_with = "insecure")]
+    /// pin the public key of the server (or one of its chain),
+    /// as the SHA-256 digest of its SPKI in the `sha256//<base64>` format
+    /// (can be specified multiple times)
+    pin: Vec<String>,
+
     #[arg(long)]
     /// the desired tls version to use (automatically defined by default, choices are: 1.2, 1.3)
     tls: Option<String>,
@@ -344,11 +355,33 @@ where
     let server_verify_mode = if cfg.insecure {
         Some(ServerVerifyMode::Disable)
     } else {
-        None
+        let root_store = match cfg.cacert.as_deref() {
+            Some(path) => {
+                let pem = std::fs::read_to_string(path).context("read CA cert file")?;
+                Some(Arc::new(DataEncoding::Pem(
+                    pem.try_into().context("CA cert file cannot be empty")?,
+                )))
+            }
+            None => None,
+        };
+        if cfg.pin.is_empty() {
+            root_store.map(ServerVerifyMode::RootStore)
+        } else {
+            let pins = cfg
+                .pin
+                .iter()
+                .map(|pin| pin.parse::<SpkiPin>())
+                .collect::<Result<Vec<_>, _>>()
+                .context("parse public key pin")?;
+            Some(ServerVerifyMode::Pinned {
+                pins: Arc::new(SpkiPinSet::new(pins)),
+                root_store,
+            })
+        }
     };
 
     inner_client.set_tls_config(ClientConfig {
-        server_verify_mode,
+        server_verify_mode: server_verify_mode.clone(),
         extensions: Some(vec![
             ClientHelloExtension::ApplicationLayerProtocolNegotiation(vec![
                 ApplicationProtocol::HTTP_2,
@@ -3,26 +3,14 @@
 use clap::Args;
 use rama::{
     error::BoxError,
-    http::{
-        client::HttpClient,
-        layer::{
-            remove_header::{RemoveRequestHeaderLayer, RemoveResponseHeaderLayer},
-            trace::TraceLayer,
-            upgrade::{UpgradeLayer, Upgraded},
-        },
-        matcher::MethodMatcher,
-        server::HttpServer,
-        Body, IntoResponse, Request, Response, StatusCode,
-    },
+    http::{layer::trace::TraceLayer, proxy::ForwardProxyService, server::HttpServer},
     layer::{limit::policy::ConcurrentPolicy, LimitLayer, TimeoutLayer},
-    net::http::RequestContext,
     net::stream::layer::http::BodyLimitLayer,
     rt::Executor,
-    service::service_fn,
-    tcp::{client::default_tcp_connect, server::TcpListener, utils::is_connection_error},
-    Context, Layer, Service,
+    tcp::server::TcpListener,
+    Layer,
 };
-use std::{convert::Infallible, time::Duration};
+use std::time::Duration;
 use tracing::level_filters::LevelFilter;
 use tracing_subscriber::{fmt, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
 
@@ -69,19 +57,8 @@ pub async fn run(cfg: CliCommandProxy) -> Result<(), BoxError> {
             .expect("bind proxy to 127.0.0.1:62001");
 
         let exec = Executor::graceful(guard.clone());
-        let http_service = HttpServer::auto(exec).service(
-            (
-                TraceLayer::new_for_http(),
-                UpgradeLayer::new(
-                    MethodMatcher::CONNECT,
-                    service_fn(http_connect_accept),
-                    service_fn(http_connect_proxy),
-                ),
-                RemoveResponseHeaderLayer::hop_by_hop(),
-                RemoveRequestHeaderLayer::hop_by_hop(),
-            )
-                .layer(service_fn(http_plain_proxy)),
-        );
+        let http_service = HttpServer::auto(exec)
+            .service(TraceLayer::new_for_http().layer(ForwardProxyService::new()));
 
         let tcp_service_builder = (
             // protect the http proxy from too large bodies, both from request and response end
@@ -101,63 +78,3 @@ pub async fn run(cfg: CliCommandProxy) -> Result<(), BoxError> {
 
     Ok(())
 }
-
-async fn http_connect_accept<S>(
-    mut ctx: Context<S>,
-    req: Request,
-) -> Result<(Response, Context<S>, Request), Response>
-where
-    S: Clone + Send + Sync + 'static,
-{
-    match ctx.get_or_try_insert_with_ctx::<RequestContext, _>(|ctx| (ctx, &req).try_into()) {
-        Ok(request_ctx) => tracing::info!("accept CONNECT to {}", request_ctx.authority),
-        Err(err) => {
-            tracing::error!(err = %err, "error extracting authority");
-            return Err(StatusCode::BAD_REQUEST.into_response());
-        }
-    }
-
-    Ok((StatusCode::OK.into_response(), ctx, req))
-}
-
-async fn http_connect_proxy<S>(ctx: Context<S>, mut upgraded: Upgraded) -> Result<(), Infallible>
-where
-    S: Clone + Send + Sync + 'static,
-{
-    let authority = ctx // assumption validated by `http_connect_accept`
-        .get::<RequestContext>()
-        .unwrap()
-        .authority
-        .clone();
-    tracing::info!("CONNECT to {authority}");
-    let (mut stream, _) = match default_tcp_connect(&ctx, authority).await {
-        Ok(stream) => stream,
-        Err(err) => {
-            tracing::error!(error = %err, "error connecting to host");
-            return Ok(());
-        }
-    };
-    if let Err(err) = tokio::io::copy_bidirectional(&mut upgraded, &mut stream).await {
-        if !is_connection_error(&err) {
-            tracing::error!(error = %err, "error copying data");
-        }
-    }
-    Ok(())
-}
-
-async fn http_plain_proxy<S>(ctx: Context<S>, req: Request) -> Result<Response, Infallible>
-where
-    S: Clone + Send + Sync + 'static,
-{
-    let client = HttpClient::default();
-    match client.serve(ctx, req).await {
-        Ok(resp) => Ok(resp),
-        Err(err) => {
-            tracing::error!(error = %err, "error in client request");
-            Ok(Response::builder()
-                .status(StatusCode::INTERNAL_SERVER_ERROR)
-                .body(Body::empty())
-                .unwrap())
-        }
-    }
-}
@@ -0,0 +1,839 @@
+use crate::{DnsLookup, DnsResolver, HttpsRecord};
+use rama_core::error::BoxError;
+use rama_net::address::Domain;
+use std::{
+    collections::{BTreeSet, HashMap},
+    fmt,
+    future::Future,
+    net::{Ipv4Addr, Ipv6Addr},
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+    time::{Duration, Instant},
+};
+use tokio::sync::OnceCell;
+
+/// A [`DnsResolver`] which caches the lookups of the wrapped [`DnsResolver`].
+///
+/// Records are cached for their time-to-live (TTL) as reported by the wrapped resolver
+/// (see [`DnsResolver::ipv4_lookup_with_ttl`]), clamped between the configured
+/// minimum and maximum TTL. Resolvers which are not aware of TTLs are cached for the default TTL.
+///
+/// Failed lookups are cached as well, for the (configurable) negative TTL,
+/// such that failing domains do not hit the wrapped resolver over and over again.
+///
+/// Concurrent lookups of the same domain and record type are coalesced
+/// into a single lookup of the wrapped resolver. Optionally records can be prefetched
+/// in the background when they are looked up shortly before expiring,
+/// such that popular domains are never resolved in the foreground once cached.
+///
+/// Clones of a [`CachingDns`] share the same cache.
+pub struct CachingDns<R> {
+    resolver: Arc<R>,
+    ipv4: Arc<RecordCache<Ipv4Addr>>,
+    ipv6: Arc<RecordCache<Ipv6Addr>>,
+    https: Arc<RecordCache<HttpsRecord>>,
+    config: CacheConfig,
+}
+
+impl<R: fmt::Debug> fmt::Debug for CachingDns<R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CachingDns")
+            .field("resolver", &self.resolver)
+            .field("config", &self.config)
+            .finish()
+    }
+}
+
+impl<R> Clone for CachingDns<R> {
+    fn clone(&self) -> Self {
+        Self {
+            resolver: self.resolver.clone(),
+            ipv4: self.ipv4.clone(),
+            ipv6: self.ipv6.clone(),
+            https: self.https.clone(),
+            config: self.config,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct CacheConfig {
+    min_ttl: Duration,
+    max_ttl: Duration,
+    default_ttl: Duration,
+    negative_ttl: Duration,
+    prefetch: Option<Duration>,
+    max_entries: usize,
+}
+
+impl CacheConfig {
+    fn ttl(&self, ttl: Option<Duration>) -> Duration {
+        ttl.unwrap_or(self.default_ttl)
+            .max(self.min_ttl)
+            .min(self.max_ttl)
+    }
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            min_ttl: Duration::ZERO,
+            max_ttl: Duration::from_secs(24 * 60 * 60),
+            default_ttl: Duration::from_secs(60),
+            negative_ttl: Duration::from_secs(5),
+            prefetch: None,
+            max_entries: 10_000,
+        }
+    }
+}
+
+impl<R> CachingDns<R> {
+    /// Create a new [`CachingDns`] caching the lookups of the given [`DnsResolver`].
+    pub fn new(resolver: R) -> Self {
+        Self {
+            resolver: Arc::new(resolver),
+            ipv4: Default::default(),
+            ipv6: Default::default(),
+            https: Default::default(),
+            config: CacheConfig::default(),
+        }
+    }
+
+    /// Set the minimum duration records are cached for,
+    /// regardless of their time-to-live (TTL).
+    ///
+    /// By default records are cached no shorter than their TTL.
+    pub fn with_min_ttl(mut self, ttl: Duration) -> Self {
+        self.config.min_ttl = ttl;
+        self
+    }
+
+    /// Set the minimum duration records are cached for,
+    /// regardless of their time-to-live (TTL).
+    ///
+    /// By default records are cached no shorter than their TTL.
+    pub fn set_min_ttl(&mut self, ttl: Duration) -> &mut Self {
+        self.config.min_ttl = ttl;
+        self
+    }
+
+    /// Set the maximum duration records are cached for,
+    /// regardless of their time-to-live (TTL).
+    ///
+    /// Defaults to one day. It takes precedence over the minimum TTL.
+    pub fn with_max_ttl(mut self, ttl: Duration) -> Self {
+        self.config.max_ttl = ttl;
+        self
+    }
+
+    /// Set the maximum duration records are cached for,
+    /// regardless of their time-to-live (TTL).
+    ///
+    /// Defaults to one day. It takes precedence over the minimum TTL.
+    pub fn set_max_ttl(&mut self, ttl: Duration) -> &mut Self {
+        self.config.max_ttl = ttl;
+        self
+    }
+
+    /// Set the duration records are cached for in case
+    /// their time-to-live (TTL) is not known by the wrapped resolver.
+    ///
+    /// Defaults to one minute.
+    pub fn with_default_ttl(mut self, ttl: Duration) -> Self {
+        self.config.default_ttl = ttl;
+        self
+    }
+
+    /// Set the duration records are cached for in case
+    /// their time-to-live (TTL) is not known by the wrapped resolver.
+    ///
+    /// Defaults to one minute.
+    pub fn set_default_ttl(&mut self, ttl: Duration) -> &mut Self {
+        self.config.default_ttl = ttl;
+        self
+    }
+
+    /// Set the duration failed lookups are cached for.
+    ///
+    /// Defaults to five seconds, use [`Duration::ZERO`] to disable negative caching.
+    pub fn with_negative_ttl(mut self, ttl: Duration) -> Self {
+        self.config.negative_ttl = ttl;
+        self
+    }
+
+    /// Set the duration failed lookups are cached for.
+    ///
+    /// Defaults to five seconds, use [`Duration::ZERO`] to disable negative caching.
+    pub fn set_negative_ttl(&mut self, ttl: Duration) -> &mut Self {
+        self.config.negative_ttl = ttl;
+        self
+    }
+
+    /// Prefetch cached records in the background when they are looked up
+    /// within the given duration before they expire.
+    ///
+    /// Prefetching is disabled by default.
+    pub fn with_prefetch(mut self, before_expiry: Duration) -> Self {
+        self.config.prefetch = Some(before_expiry);
+        self
+    }
+
+    /// Prefetch cached records in the background when they are looked up
+    /// within the given duration before they expire.
+    ///
+    /// Prefetching is disabled by default.
+    pub fn set_prefetch(&mut self, before_expiry: Duration) -> &mut Self {
+        self.config.prefetch = Some(before_expiry);
+        self
+    }
+
+    /// Set the maximum amount of domains cached per record type.
+    ///
+    /// When full, expired entries are evicted first, followed by
+    /// the entry that expires the soonest. Defaults to `10_000`.
+    pub fn with_max_entries(mut self, max: usize) -> Self {
+        self.config.max_entries = max;
+        self
+    }
+
+    /// Set the maximum amount of domains cached per record type.
+    ///
+    /// When full, expired entries are evicted first, followed by
+    /// the entry that expires the soonest. Defaults to `10_000`.
+    pub fn set_max_entries(&mut self, max: usize) -> &mut Self {
+        self.config.max_entries = max;
+        self
+    }
+
+    /// Remove all cached lookups.
+    pub fn clear(&self) {
+        self.ipv4.lock().clear();
+        self.ipv6.lock().clear();
+        self.https.lock().clear();
+    }
+}
+
+impl<R> CachingDns<R>
+where
+    R: DnsResolver<Error: Into<BoxError>>,
+{
+    async fn lookup<T, F, Fut>(
+        &self,
+        cache: &Arc<RecordCache<T>>,
+        domain: Domain,
+        resolve: F,
+    ) -> Result<DnsLookup<T>, DnsCacheError>
+    where
+        T: Clone + Send + Sync + 'static,
+        F: FnOnce(Arc<R>, Domain) -> Fut,
+        Fut: Future<Output = Result<DnsLookup<T>, R::Error>> + Send + 'static,
+    {
+        let now = Instant::now();
+        let cell = {
+            let mut state = cache.lock();
+            let state = &mut *state;
+            if let Some(entry) = state.entries.get(&domain).filter(|e| e.expires_at > now) {
+                let entry = entry.clone();
+                if entry.prefetch_at.is_some_and(|at| at <= now)
+                    && !state.in_flight.contains_key(&domain)
+                {
+                    if let Ok(handle) = tokio::runtime::Handle::try_current() {
+                        let cell = Arc::new(OnceCell::new());
+                        state.in_flight.insert(domain.clone(), cell.clone());
+                        let lookup = resolve(self.resolver.clone(), domain.clone());
+                        let (cache, config) = (cache.clone(), self.config);
+                        handle.spawn(async move {
+                            cell.get_or_init(|| store(config, &cache, &cell, domain, lookup))
+                                .await;
+                        });
+                    }
+                }
+                return entry.into_lookup(now, true);
+            }
+            state.in_flight.entry(domain.clone()).or_default().clone()
+        };
+
+        cell.get_or_init(|| {
+            store(
+                self.config,
+                cache,
+                &cell,
+                domain.clone(),
+                resolve(self.resolver.clone(), domain),
+            )
+        })
+        .await
+        .clone()
+        .into_lookup(Instant::now(), false)
+    }
+}
+
+/// Resolve the lookup and store its result in the cache,
+/// completing the in-flight lookup for all waiting (coalesced) lookups.
+///
+/// A failed lookup does not replace a cached positive entry which is still valid,
+/// such that a failing prefetch does not evict records which can still be served.
+/// Its next prefetch is postponed for the negative TTL instead (capped at its expiry),
+/// such that a failing resolver is not hit by a prefetch for every lookup.
+async fn store<T, E>(
+    config: CacheConfig,
+    cache: &RecordCache<T>,
+    cell: &Arc<OnceCell<CacheEntry<T>>>,
+    domain: Domain,
+    lookup: impl Future<Output = Result<DnsLookup<T>, E>>,
+) -> CacheEntry<T>
+where
+    T: Clone,
+    E: Into<BoxError>,
+{
+    let result = lookup.await;
+    let now = Instant::now();
+    let entry = match result {
+        Ok(lookup) => {
+            let ttl = config.ttl(lookup.ttl);
+            CacheEntry {
+                result: Ok(lookup.records),
+                expires_at: now + ttl,
+                prefetch_at: config
+                    .prefetch
+                    .filter(|before| *before < ttl)
+                    .map(|before| now + (ttl - before)),
+            }
+        }
+        Err(err) => CacheEntry {
+            result: Err(DnsCacheError::new(err.into())),
+            expires_at: now + config.negative_ttl.min(config.max_ttl),
+            prefetch_at: None,
+        },
+    };
+
+    let mut state = cache.lock();
+    if state
+        .in_flight
+        .get(&domain)
+        .is_some_and(|in_flight| Arc::ptr_eq(in_flight, cell))
+    {
+        state.in_flight.remove(&domain);
+    }
+    if entry.result.is_err() {
+        if let Some(cached) = state
+            .entries
+            .get_mut(&domain)
+            .filter(|cached| cached.result.is_ok() && cached.expires_at > now)
+        {
+            let retry_at = (now + config.negative_ttl).min(cached.expires_at);
+            cached.prefetch_at = cached.prefetch_at.map(|_| retry_at);
+            return cached.clone();
+        }
+    }
+    if entry.expires_at > now {
+        state.insert(domain, entry.clone(), config.max_entries);
+    }
+    entry
+}
+
+impl<R> DnsResolver for CachingDns<R>
+where
+    R: DnsResolver<Error: Into<BoxError>>,
+{
+    type Error = DnsCacheError;
+
+    async fn ipv4_lookup(&self, domain: Domain) -> Result<Vec<Ipv4Addr>, Self::Error> {
+        Ok(self.ipv4_lookup_with_ttl(domain).await?.records)
+    }
+
+    async fn ipv6_lookup(&self, domain: Domain) -> Result<Vec<Ipv6Addr>, Self::Error> {
+        Ok(self.ipv6_lookup_with_ttl(domain).await?.records)
+    }
+
+    async fn https_lookup(&self, domain: Domain) -> Result<Vec<HttpsRecord>, Self::Error> {
+        Ok(self.https_lookup_with_ttl(domain).await?.records)
+    }
+
+    async fn ipv4_lookup_with_ttl(
+        &self,
+        domain: Domain,
+    ) -> Result<DnsLookup<Ipv4Addr>, Self::Error> {
+        self.lookup(&self.ipv4, domain, |resolver, domain| async move {
+            resolver.ipv4_lookup_with_ttl(domain).await
+        })
+        .await
+    }
+
+    async fn ipv6_lookup_with_ttl(
+        &self,
+        domain: Domain,
+    ) -> Result<DnsLookup<Ipv6Addr>, Self::Error> {
+        self.lookup(&self.ipv6, domain, |resolver, domain| async move {
+            resolver.ipv6_lookup_with_ttl(domain).await
+        })
+        .await
+    }
+
+    async fn https_lookup_with_ttl(
+        &self,
+        domain: Domain,
+    ) -> Result<DnsLookup<HttpsRecord>, Self::Error> {
+        self.lookup(&self.https, domain, |resolver, domain| async move {
+            resolver.https_lookup_with_ttl(domain).await
+        })
+        .await
+    }
+}
+
+#[derive(Debug)]
+struct RecordCache<T> {
+    state: Mutex<CacheState<T>>,
+}
+
+impl<T> Default for RecordCache<T> {
+    fn default() -> Self {
+        Self {
+            state: Mutex::new(CacheState {
+                entries: HashMap::new(),
+                expiry: BTreeSet::new(),
+                in_flight: HashMap::new(),
+            }),
+        }
+    }
+}
+
+impl<T> RecordCache<T> {
+    fn lock(&self) -> MutexGuard<'_, CacheState<T>> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[derive(Debug)]
+struct CacheState<T> {
+    entries: HashMap<Domain, CacheEntry<T>>,
+    /// The cached domains ordered by expiry, to evict without scanning all entries.
+    expiry: BTreeSet<(Instant, Domain)>,
+    in_flight: HashMap<Domain, Arc<OnceCell<CacheEntry<T>>>>,
+}
+
+impl<T> CacheState<T> {
+    fn insert(&mut self, domain: Domain, entry: CacheEntry<T>, max_entries: usize) {
+        if max_entries == 0 {
+            return;
+        }
+        if let Some(previous) = self.entries.get(&domain) {
+            self.expiry.remove(&(previous.expires_at, domain.clone()));
+        } else if self.entries.len() >= max_entries {
+            // expired entries are always the first to expire
+            if let Some((_, oldest)) = self.expiry.pop_first() {
+                self.entries.remove(&oldest);
+            }
+        }
+        self.expiry.insert((entry.expires_at, domain.clone()));
+        self.entries.insert(domain, entry);
+    }
+
+    fn clear(&mut self) {
+        self.entries.clear();
+        self.expiry.clear();
+    }
+}
+
+#[derive(Debug, Clone)]
+struct CacheEntry<T> {
+    result: Result<Vec<T>, DnsCacheError>,
+    expires_at: Instant,
+    prefetch_at: Option<Instant>,
+}
+
+impl<T> CacheEntry<T> {
+    fn into_lookup(self, now: Instant, cached: bool) -> Result<DnsLookup<T>, DnsCacheError> {
+        match self.result {
+            Ok(records) => Ok(DnsLookup::new(
+                records,
+                self.expires_at.saturating_duration_since(now),
+            )),
+            Err(mut err) => {
+                err.cached = cached;
+                Err(err)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+/// Error returned by [`CachingDns`] for a failed lookup.
+///
+/// The error of the wrapped resolver is shared between all lookups
+/// which were coalesced or served from the (negative) cache.
+pub struct DnsCacheError {
+    inner: Arc<BoxError>,
+    cached: bool,
+}
+
+impl DnsCacheError {
+    fn new(err: BoxError) -> Self {
+        Self {
+            inner: Arc::new(err),
+            cached: false,
+        }
+    }
+
+    /// Returns `true` if this error was served from the (negative) cache,
+    /// instead of being the result of a lookup by the wrapped resolver.
+    pub fn is_cached(&self) -> bool {
+        self.cached
+    }
+}
+
+impl fmt::Display for DnsCacheError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.cached {
+            write!(f, "cached dns lookup error: {}", self.inner)
+        } else {
+            write!(f, "dns lookup error: {}", self.inner)
+        }
+    }
+}
+
+impl std::error::Error for DnsCacheError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&**self.inner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DenyAllDns, InMemoryDns};
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+    #[derive(Debug)]
+    struct CountingDns<R> {
+        inner: R,
+        ttl: Option<Duration>,
+        delay: Duration,
+        lookups: AtomicUsize,
+    }
+
+    impl<R> CountingDns<R> {
+        fn new(inner: R) -> Self {
+            Self {
+                inner,
+                ttl: None,
+                delay: Duration::ZERO,
+                lookups: AtomicUsize::new(0),
+            }
+        }
+
+        fn lookups(&self) -> usize {
+            self.lookups.load(Ordering::SeqCst)
+        }
+    }
+
+    impl<R: DnsResolver> DnsResolver for CountingDns<R> {
+        type Error = R::Error;
+
+        async fn ipv4_lookup(&self, domain: Domain) -> Result<Vec<Ipv4Addr>, Self::Error> {
+            self.lookups.fetch_add(1, Ordering::SeqCst);
+            tokio::time::sleep(self.delay).await;
+            self.inner.ipv4_lookup(domain).await
+        }
+
+        async fn ipv6_lookup(&self, domain: Domain) -> Result<Vec<Ipv6Addr>, Self::Error> {
+            self.lookups.fetch_add(1, Ordering::SeqCst);
+            tokio::time::sleep(self.delay).await;
+            self.inner.ipv6_lookup(domain).await
+        }
+
+        async fn ipv4_lookup_with_ttl(
+            &self,
+            domain: Domain,
+        ) -> Result<DnsLookup<Ipv4Addr>, Self::Error> {
+            let records = self.ipv4_lookup(domain).await?;
+            Ok(DnsLookup {
+                records,
+                ttl: self.ttl,
+            })
+        }
+    }
+
+    fn example_dns() -> InMemoryDns {
+        let mut dns = InMemoryDns::new();
+        dns.insert_address(
+            Domain::from_static("example.com"),
+            Ipv4Addr::new(127, 0, 0, 1),
+        );
+        dns
+    }
+
+    #[tokio::test]
+    async fn test_caching_dns_caches_lookups() {
+        let resolver = Arc::new(CountingDns::new(example_dns()));
+        let dns = CachingDns::new(resolver.clone());
+
+        for _ in 0..3 {
+            let lookup = dns
+                .ipv4_lookup_with_ttl(Domain::from_static("example.com"))
+                .await
+                .unwrap();
+            assert_eq!(lookup.records, vec![Ipv4Addr::new(127, 0, 0, 1)]);
+            assert!(lookup.ttl.unwrap() <= Duration::from_secs(60));
+        }
+        assert_eq!(resolver.lookups(), 1);
+
+        // different record types are cached separately
+        assert!(dns
+            .ipv6_lookup(Domain::from_static("example.com"))
+            .await
+            .is_err());
+        assert_eq!(resolver.lookups(), 2);
+
+        dns.clear();
+        dns.ipv4_lookup(Domain::from_static("example.com"))
+            .await
+            .unwrap();
+        assert_eq!(resolver.lookups(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_caching_dns_ttl_clamping() {
+        let mut resolver = CountingDns::new(example_dns());
+        resolver.ttl = Some(Duration::from_secs(3600));
+        let resolver = Arc::new(resolver);
+
+        let dns = CachingDns::new(resolver.clone()).with_max_ttl(Duration::from_secs(10));
+        let lookup = dns
+            .ipv4_lookup_with_ttl(Domain::from_static("example.com"))
+            .await
+            .unwrap();
+        assert!(lookup.ttl.unwrap() <= Duration::from_secs(10));
+
+        let dns = CachingDns::new(resolver.clone())
+            .with_min_ttl(Duration::from_secs(7200))
+            .with_max_ttl(Duration::from_secs(7200 * 2));
+        let lookup = dns
+            .ipv4_lookup_with_ttl(Domain::from_static("example.com"))
+            .await
+            .unwrap();
+        assert!(lookup.ttl.unwrap() > Duration::from_secs(3600));
+    }
+
+    #[tokio::test]
+    async fn test_caching_dns_expiry() {
+        let mut resolver = CountingDns::new(example_dns());
+        resolver.ttl = Some(Duration::from_millis(20));
+        let resolver = Arc::new(resolver);
+        let dns = CachingDns::new(resolver.clone());
+
+        dns.ipv4_lookup(Domain::from_static("example.com"))
+            .await
+            .unwrap();
+        dns.ipv4_lookup(Domain::from_static("example.com"))
+            .await
+            .unwrap();
+        assert_eq!(resolver.lookups(), 1);
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        dns.ipv4_lookup(Domain::from_static("example.com"))
+            .await
+            .unwrap();
+        assert_eq!(resolver.lookups(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_caching_dns_negative_caching() {
+        let resolver = Arc::new(CountingDns::new(DenyAllDns::new()));
+        let dns = CachingDns::new(resolver.clone());
+
+        let err = dns
+            .ipv4_lookup(Domain::from_static("example.com"))
+            .await
+            .unwrap_err();
+        assert!(!err.is_cached());
+        let err = dns
+            .ipv4_lookup(Domain::from_static("example.com"))
+            .await
+            .unwrap_err();
+        assert!(err.is_cached());
+        assert_eq!(resolver.lookups(), 1);
+
+        let dns = CachingDns::new(resolver.clone()).with_negative_ttl(Duration::ZERO);
+        for _ in 0..2 {
+            let err = dns
+                .ipv4_lookup(Domain::from_static("example.com"))
+                .await
+                .unwrap_err();
+            assert!(!err.is_cached());
+        }
+        assert_eq!(resolver.lookups(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_caching_dns_coalesces_concurrent_lookups() {
+        let mut resolver = CountingDns::new(example_dns());
+        resolver.delay = Duration::from_millis(20);
+        let resolver = Arc::new(resolver);
+        let dns = CachingDns::new(resolver.clone());
+
+        let lookups: Vec<_> = (0..8)
+            .map(|_| {
+                let dns = dns.clone();
+                tokio::spawn(
+                    async move { dns.ipv4_lookup(Domain::from_static("example.com")).await },
+                )
+            })
+            .collect();
+        for lookup in lookups {
+            assert_eq!(
+                lookup.await.unwrap().unwrap(),
+                vec![Ipv4Addr::new(127, 0, 0, 1)]
+            );
+        }
+        assert_eq!(resolver.lookups(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_caching_dns_prefetch() {
+        let mut resolver = CountingDns::new(example_dns());
+        resolver.ttl = Some(Duration::from_millis(200));
+        let resolver = Arc::new(resolver);
+        let dns = CachingDns::new(resolver.clone()).with_prefetch(Duration::from_millis(100));
+
+        dns.ipv4_lookup(Domain::from_static("example.com"))
+            .await
+            .unwrap();
+        // not yet within the prefetch window
+        dns.ipv4_lookup(Domain::from_static("example.com"))
+            .await
+            .unwrap();
+        assert_eq!(resolver.lookups(), 1);
+
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        // served from cache, triggers a background refresh
+        dns.ipv4_lookup(Domain::from_static("example.com"))
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(resolver.lookups(), 2);
+
+        // refreshed entry is valid beyond the original expiry
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        dns.ipv4_lookup(Domain::from_static("example.com"))
+            .await
+            .unwrap();
+        assert_eq!(resolver.lookups(), 2);
+    }
+
+    #[derive(Debug, Default)]
+    struct FlakyDns {
+        fail: AtomicBool,
+    }
+
+    impl DnsResolver for FlakyDns {
+        type Error = BoxError;
+
+        async fn ipv4_lookup(&self, _domain: Domain) -> Result<Vec<Ipv4Addr>, Self::Error> {
+            if self.fail.load(Ordering::SeqCst) {
+                Err("lookup failed".into())
+            } else {
+                Ok(vec![Ipv4Addr::new(127, 0, 0, 1)])
+            }
+        }
+
+        async fn ipv6_lookup(&self, _domain: Domain) -> Result<Vec<Ipv6Addr>, Self::Error> {
+            Err("lookup failed".into())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_caching_dns_failed_prefetch_keeps_records() {
+        let mut resolver = CountingDns::new(FlakyDns::default());
+        resolver.ttl = Some(Duration::from_millis(200));
+        let resolver = Arc::new(resolver);
+        let dns = CachingDns::new(resolver.clone()).with_prefetch(Duration::from_millis(100));
+
+        dns.ipv4_lookup(Domain::from_static("example.com"))
+            .await
+            .unwrap();
+
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        resolver.inner.fail.store(true, Ordering::SeqCst);
+        // served from cache, triggers a background refresh which fails
+        dns.ipv4_lookup(Domain::from_static("example.com"))
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(resolver.lookups(), 2);
+
+        // the records remain cached until they expire
+        assert_eq!(
+            dns.ipv4_lookup(Domain::from_static("example.com"))
+                .await
+                .unwrap(),
+            vec![Ipv4Addr::new(127, 0, 0, 1)]
+        );
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(dns
+            .ipv4_lookup(Domain::from_static("example.com"))
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_caching_dns_failed_prefetch_backoff() {
+        let mut resolver = CountingDns::new(FlakyDns::default());
+        resolver.ttl = Some(Duration::from_millis(400));
+        let resolver = Arc::new(resolver);
+        let dns = CachingDns::new(resolver.clone())
+            .with_prefetch(Duration::from_millis(300))
+            .with_negative_ttl(Duration::from_millis(100));
+
+        dns.ipv4_lookup(Domain::from_static("example.com"))
+            .await
+            .unwrap();
+
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        resolver.inner.fail.store(true, Ordering::SeqCst);
+        // served from cache, triggers a background refresh which fails
+        dns.ipv4_lookup(Domain::from_static("example.com"))
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(resolver.lookups(), 2);
+
+        // no new refresh is triggered until the negative ttl has passed
+        for _ in 0..5 {
+            dns.ipv4_lookup(Domain::from_static("example.com"))
+                .await
+                .unwrap();
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(resolver.lookups(), 2);
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        dns.ipv4_lookup(Domain::from_static("example.com"))
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(resolver.lookups(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_caching_dns_max_entries() {
+        let mut inner = InMemoryDns::new();
+        for domain in ["a.example.com", "b.example.com", "c.example.com"] {
+            inner.insert_address(Domain::from_static(domain), Ipv4Addr::new(127, 0, 0, 1));
+        }
+        let resolver = Arc::new(CountingDns::new(inner));
+        let dns = CachingDns::new(resolver.clone()).with_max_entries(2);
+
+        for domain in ["a.example.com", "b.example.com", "c.example.com"] {
+            dns.ipv4_lookup(Domain::from_static(domain)).await.unwrap();
+        }
+        assert_eq!(dns.ipv4.lock().entries.len(), 2);
+        assert_eq!(dns.ipv4.lock().expiry.len(), 2);
+        assert_eq!(resolver.lookups(), 3);
+
+        dns.ipv4_lookup(Domain::from_static("c.example.com"))
+            .await
+            .unwrap();
+        assert_eq!(resolver.lookups(), 3);
+    }
+}
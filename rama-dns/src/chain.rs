@@ -3,7 +3,7 @@ use std::net::{Ipv4Addr, Ipv6Addr};
 use rama_core::error::BoxError;
 use rama_net::address::Domain;
 
-use crate::DnsResolver;
+use crate::{DnsLookup, DnsResolver, HttpsRecord};
 
 macro_rules! dns_resolver_chain_impl {
     () => {
@@ -28,6 +28,59 @@ macro_rules! dns_resolver_chain_impl {
             }
             Err(errors)
         }
+
+        async fn https_lookup(&self, domain: Domain) -> Result<Vec<HttpsRecord>, Self::Error> {
+            let mut errors = Vec::new();
+            for resolver in self {
+                match resolver.https_lookup(domain.clone()).await {
+                    Ok(records) => return Ok(records),
+                    Err(err) => errors.push(err.into()),
+                }
+            }
+            Err(errors)
+        }
+
+        async fn ipv4_lookup_with_ttl(
+            &self,
+            domain: Domain,
+        ) -> Result<DnsLookup<Ipv4Addr>, Self::Error> {
+            let mut errors = Vec::new();
+            for resolver in self {
+                match resolver.ipv4_lookup_with_ttl(domain.clone()).await {
+                    Ok(lookup) => return Ok(lookup),
+                    Err(err) => errors.push(err.into()),
+                }
+            }
+            Err(errors)
+        }
+
+        async fn ipv6_lookup_with_ttl(
+            &self,
+            domain: Domain,
+        ) -> Result<DnsLookup<Ipv6Addr>, Self::Error> {
+            let mut errors = Vec::new();
+            for resolver in self {
+                match resolver.ipv6_lookup_with_ttl(domain.clone()).await {
+                    Ok(lookup) => return Ok(lookup),
+                    Err(err) => errors.push(err.into()),
+                }
+            }
+            Err(errors)
+        }
+
+        async fn https_lookup_with_ttl(
+            &self,
+            domain: Domain,
+        ) -> Result<DnsLookup<HttpsRecord>, Self::Error> {
+            let mut errors = Vec::new();
+            for resolver in self {
+                match resolver.https_lookup_with_ttl(domain.clone()).await {
+                    Ok(lookup) => return Ok(lookup),
+                    Err(err) => errors.push(err.into()),
+                }
+            }
+            Err(errors)
+        }
     };
 }
 
@@ -166,4 +219,20 @@ mod tests {
             .await
             .is_err());
     }
+
+    #[tokio::test]
+    async fn test_chain_err_ok_https() {
+        let v = vec![Either::B(DenyAllDns::new()), Either::A(InMemoryDns::new())];
+        let result = v
+            .https_lookup(Domain::from_static("example.com"))
+            .await
+            .unwrap();
+        assert!(result.is_empty());
+
+        let v = vec![DenyAllDns::new(), DenyAllDns::new()];
+        assert!(v
+            .https_lookup(Domain::from_static("example.com"))
+            .await
+            .is_err());
+    }
 }
@@ -1,4 +1,4 @@
-use crate::DnsResolver;
+use crate::{DnsResolver, HttpsRecord};
 use rama_net::address::Domain;
 use rama_utils::macros::error::static_str_error;
 use std::net::{Ipv4Addr, Ipv6Addr};
@@ -32,4 +32,8 @@ impl DnsResolver for DenyAllDns {
     async fn ipv6_lookup(&self, _domain: Domain) -> Result<Vec<Ipv6Addr>, Self::Error> {
         Err(DnsDeniedError)
     }
+
+    async fn https_lookup(&self, _domain: Domain) -> Result<Vec<HttpsRecord>, Self::Error> {
+        Err(DnsDeniedError)
+    }
 }
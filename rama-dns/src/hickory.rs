@@ -1,8 +1,14 @@
 //! dns using the [`hickory_resolver`] crate
 
-use crate::DnsResolver;
+use crate::{DnsLookup, DnsResolver, HttpsRecord};
 use hickory_resolver::{
-    proto::rr::rdata::{A, AAAA},
+    proto::rr::{
+        rdata::{
+            svcb::{Alpn, EchConfig, SvcParamKey, SvcParamValue, SVCB},
+            A, AAAA,
+        },
+        RData, RecordType,
+    },
     Name, TokioAsyncResolver,
 };
 use rama_core::error::{ErrorContext, OpaqueError};
@@ -10,6 +16,7 @@ use rama_net::address::Domain;
 use std::{
     net::{Ipv4Addr, Ipv6Addr},
     sync::{Arc, OnceLock},
+    time::{Duration, Instant},
 };
 
 pub use hickory_resolver::config;
@@ -104,31 +111,126 @@ impl DnsResolver for HickoryDns {
     type Error = OpaqueError;
 
     async fn ipv4_lookup(&self, domain: Domain) -> Result<Vec<Ipv4Addr>, Self::Error> {
+        Ok(self.ipv4_lookup_with_ttl(domain).await?.records)
+    }
+
+    async fn ipv6_lookup(&self, domain: Domain) -> Result<Vec<Ipv6Addr>, Self::Error> {
+        Ok(self.ipv6_lookup_with_ttl(domain).await?.records)
+    }
+
+    async fn https_lookup(&self, domain: Domain) -> Result<Vec<HttpsRecord>, Self::Error> {
+        Ok(self.https_lookup_with_ttl(domain).await?.records)
+    }
+
+    async fn ipv4_lookup_with_ttl(
+        &self,
+        domain: Domain,
+    ) -> Result<DnsLookup<Ipv4Addr>, Self::Error> {
         let name = fqdn_from_domain(domain)?;
-        Ok(self
+        let lookup = self
             .0
             .ipv4_lookup(name)
             .await
-            .context("lookup IPv4 address(es)")?
-            .into_iter()
-            .map(|A(ip)| ip)
-            .collect())
+            .context("lookup IPv4 address(es)")?;
+        let ttl = ttl_until(lookup.valid_until());
+        Ok(DnsLookup::new(
+            lookup.into_iter().map(|A(ip)| ip).collect(),
+            ttl,
+        ))
     }
 
-    async fn ipv6_lookup(&self, domain: Domain) -> Result<Vec<Ipv6Addr>, Self::Error> {
+    async fn ipv6_lookup_with_ttl(
+        &self,
+        domain: Domain,
+    ) -> Result<DnsLookup<Ipv6Addr>, Self::Error> {
         let name = fqdn_from_domain(domain)?;
-        Ok(self
+        let lookup = self
             .0
             .ipv6_lookup(name)
             .await
-            .context("lookup IPv6 address(es)")?
-            .into_iter()
-            .map(|AAAA(ip)| ip)
-            .collect())
+            .context("lookup IPv6 address(es)")?;
+        let ttl = ttl_until(lookup.valid_until());
+        Ok(DnsLookup::new(
+            lookup.into_iter().map(|AAAA(ip)| ip).collect(),
+            ttl,
+        ))
+    }
+
+    async fn https_lookup_with_ttl(
+        &self,
+        domain: Domain,
+    ) -> Result<DnsLookup<HttpsRecord>, Self::Error> {
+        let name = fqdn_from_domain(domain)?;
+        let lookup = self
+            .0
+            .lookup(name, RecordType::HTTPS)
+            .await
+            .context("lookup HTTPS record(s)")?;
+        Ok(DnsLookup::new(
+            lookup
+                .iter()
+                .filter_map(|rdata| match rdata {
+                    RData::HTTPS(https) => Some(https_record_from_svcb(&https.0)),
+                    _ => None,
+                })
+                .collect(),
+            ttl_until(lookup.valid_until()),
+        ))
+    }
+}
+
+/// Remaining time-to-live of a hickory lookup which is valid until the given instant.
+fn ttl_until(valid_until: Instant) -> Duration {
+    valid_until.saturating_duration_since(Instant::now())
+}
+
+pub(crate) fn https_record_from_svcb(svcb: &SVCB) -> HttpsRecord {
+    let target = svcb.target_name();
+    let mut record = HttpsRecord {
+        priority: svcb.svc_priority(),
+        target: (!target.is_root())
+            .then(|| target.to_utf8().trim_end_matches('.').parse().ok())
+            .flatten(),
+        alpn: Vec::new(),
+        port: None,
+        ech_config_list: None,
+    };
+    for (_, value) in svcb.svc_params() {
+        match value {
+            SvcParamValue::Alpn(alpn) => record.alpn.extend(alpn.0.iter().cloned()),
+            SvcParamValue::Port(port) => record.port = Some(*port),
+            SvcParamValue::EchConfig(ech_config) => {
+                record.ech_config_list = Some(ech_config.0.clone())
+            }
+            _ => (),
+        }
+    }
+    record
+}
+
+/// Create the SVCB data of an HTTPS record, the inverse of [`https_record_from_svcb`].
+pub(crate) fn svcb_from_https_record(record: HttpsRecord) -> Result<SVCB, OpaqueError> {
+    let target = match record.target {
+        Some(target) => fqdn_from_domain(target)?,
+        None => Name::root(),
+    };
+    let mut params = Vec::new();
+    if !record.alpn.is_empty() {
+        params.push((SvcParamKey::Alpn, SvcParamValue::Alpn(Alpn(record.alpn))));
+    }
+    if let Some(port) = record.port {
+        params.push((SvcParamKey::Port, SvcParamValue::Port(port)));
+    }
+    if let Some(ech_config_list) = record.ech_config_list {
+        params.push((
+            SvcParamKey::EchConfig,
+            SvcParamValue::EchConfig(EchConfig(ech_config_list)),
+        ));
     }
+    Ok(SVCB::new(record.priority, target, params))
 }
 
-fn fqdn_from_domain(domain: Domain) -> Result<Name, OpaqueError> {
+pub(crate) fn fqdn_from_domain(domain: Domain) -> Result<Name, OpaqueError> {
     let mut name = Name::from_utf8(domain).context("try to consume a Domain as a Dns Name")?;
     name.set_fqdn(true);
     Ok(name)
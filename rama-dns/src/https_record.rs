@@ -0,0 +1,20 @@
+use rama_net::address::Domain;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// A (DNS) HTTPS record, as defined in RFC 9460.
+///
+/// Only the parameters relevant to rama clients are exposed.
+pub struct HttpsRecord {
+    /// Priority of the record, `0` indicates an alias record.
+    pub priority: u16,
+    /// Target name of the service, `None` in case it
+    /// is the same as the name of the record (`.`).
+    pub target: Option<Domain>,
+    /// ALPN protocol identifiers supported by the service.
+    pub alpn: Vec<String>,
+    /// Alternative port of the service, if any.
+    pub port: Option<u16>,
+    /// Raw (encoded) `ECHConfigList` of the service, if any,
+    /// which can be used to offer Encrypted Client Hello (ECH).
+    pub ech_config_list: Option<Vec<u8>>,
+}
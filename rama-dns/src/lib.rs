@@ -41,6 +41,56 @@ pub trait DnsResolver: Send + Sync + 'static {
         &self,
         domain: Domain,
     ) -> impl Future<Output = Result<Vec<Ipv6Addr>, Self::Error>> + Send + '_;
+
+    /// Resolve the 'HTTPS' records accessible by this resolver for the given [`Domain`].
+    ///
+    /// Resolvers which do not support HTTPS records resolve to no records by default.
+    fn https_lookup(
+        &self,
+        domain: Domain,
+    ) -> impl Future<Output = Result<Vec<HttpsRecord>, Self::Error>> + Send + '_ {
+        let _ = domain;
+        async { Ok(Vec::new()) }
+    }
+
+    /// Resolve the 'A' records accessible by this resolver for the given [`Domain`],
+    /// together with their time-to-live (TTL).
+    ///
+    /// Resolvers which are not aware of TTLs resolve using [`DnsResolver::ipv4_lookup`]
+    /// without a TTL by default.
+    fn ipv4_lookup_with_ttl(
+        &self,
+        domain: Domain,
+    ) -> impl Future<Output = Result<DnsLookup<Ipv4Addr>, Self::Error>> + Send + '_ {
+        let lookup = self.ipv4_lookup(domain);
+        async move { lookup.await.map(Into::into) }
+    }
+
+    /// Resolve the 'AAAA' records accessible by this resolver for the given [`Domain`],
+    /// together with their time-to-live (TTL).
+    ///
+    /// Resolvers which are not aware of TTLs resolve using [`DnsResolver::ipv6_lookup`]
+    /// without a TTL by default.
+    fn ipv6_lookup_with_ttl(
+        &self,
+        domain: Domain,
+    ) -> impl Future<Output = Result<DnsLookup<Ipv6Addr>, Self::Error>> + Send + '_ {
+        let lookup = self.ipv6_lookup(domain);
+        async move { lookup.await.map(Into::into) }
+    }
+
+    /// Resolve the 'HTTPS' records accessible by this resolver for the given [`Domain`],
+    /// together with their time-to-live (TTL).
+    ///
+    /// Resolvers which are not aware of TTLs resolve using [`DnsResolver::https_lookup`]
+    /// without a TTL by default.
+    fn https_lookup_with_ttl(
+        &self,
+        domain: Domain,
+    ) -> impl Future<Output = Result<DnsLookup<HttpsRecord>, Self::Error>> + Send + '_ {
+        let lookup = self.https_lookup(domain);
+        async move { lookup.await.map(Into::into) }
+    }
 }
 
 impl<R: DnsResolver> DnsResolver for Arc<R> {
@@ -59,6 +109,34 @@ impl<R: DnsResolver> DnsResolver for Arc<R> {
     ) -> impl Future<Output = Result<Vec<Ipv6Addr>, Self::Error>> + Send + '_ {
         (**self).ipv6_lookup(domain)
     }
+
+    fn https_lookup(
+        &self,
+        domain: Domain,
+    ) -> impl Future<Output = Result<Vec<HttpsRecord>, Self::Error>> + Send + '_ {
+        (**self).https_lookup(domain)
+    }
+
+    fn ipv4_lookup_with_ttl(
+        &self,
+        domain: Domain,
+    ) -> impl Future<Output = Result<DnsLookup<Ipv4Addr>, Self::Error>> + Send + '_ {
+        (**self).ipv4_lookup_with_ttl(domain)
+    }
+
+    fn ipv6_lookup_with_ttl(
+        &self,
+        domain: Domain,
+    ) -> impl Future<Output = Result<DnsLookup<Ipv6Addr>, Self::Error>> + Send + '_ {
+        (**self).ipv6_lookup_with_ttl(domain)
+    }
+
+    fn https_lookup_with_ttl(
+        &self,
+        domain: Domain,
+    ) -> impl Future<Output = Result<DnsLookup<HttpsRecord>, Self::Error>> + Send + '_ {
+        (**self).https_lookup_with_ttl(domain)
+    }
 }
 
 impl<R: DnsResolver<Error: Into<BoxError>>> DnsResolver for Option<R> {
@@ -77,8 +155,53 @@ impl<R: DnsResolver<Error: Into<BoxError>>> DnsResolver for Option<R> {
             None => Err(DomainNotMappedErr.into()),
         }
     }
+
+    async fn https_lookup(&self, domain: Domain) -> Result<Vec<HttpsRecord>, Self::Error> {
+        match self {
+            Some(d) => d.https_lookup(domain).await.map_err(Into::into),
+            None => Err(DomainNotMappedErr.into()),
+        }
+    }
+
+    async fn ipv4_lookup_with_ttl(
+        &self,
+        domain: Domain,
+    ) -> Result<DnsLookup<Ipv4Addr>, Self::Error> {
+        match self {
+            Some(d) => d.ipv4_lookup_with_ttl(domain).await.map_err(Into::into),
+            None => Err(DomainNotMappedErr.into()),
+        }
+    }
+
+    async fn ipv6_lookup_with_ttl(
+        &self,
+        domain: Domain,
+    ) -> Result<DnsLookup<Ipv6Addr>, Self::Error> {
+        match self {
+            Some(d) => d.ipv6_lookup_with_ttl(domain).await.map_err(Into::into),
+            None => Err(DomainNotMappedErr.into()),
+        }
+    }
+
+    async fn https_lookup_with_ttl(
+        &self,
+        domain: Domain,
+    ) -> Result<DnsLookup<HttpsRecord>, Self::Error> {
+        match self {
+            Some(d) => d.https_lookup_with_ttl(domain).await.map_err(Into::into),
+            None => Err(DomainNotMappedErr.into()),
+        }
+    }
 }
 
+mod https_record;
+#[doc(inline)]
+pub use https_record::HttpsRecord;
+
+mod lookup;
+#[doc(inline)]
+pub use lookup::DnsLookup;
+
 pub mod hickory;
 #[doc(inline)]
 pub use hickory::HickoryDns;
@@ -93,4 +216,12 @@ pub use deny_all::{DenyAllDns, DnsDeniedError};
 
 pub mod chain;
 
+mod cache;
+#[doc(inline)]
+pub use cache::{CachingDns, DnsCacheError};
+
+pub mod wire;
+
+pub mod server;
+
 mod variant;
@@ -0,0 +1,30 @@
+use std::time::Duration;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// The records resolved by a [`DnsResolver`] lookup,
+/// together with the time-to-live (TTL) of those records.
+///
+/// [`DnsResolver`]: crate::DnsResolver
+pub struct DnsLookup<T> {
+    /// The resolved records.
+    pub records: Vec<T>,
+    /// The duration for which the records may be cached,
+    /// `None` in case it is not known by the resolver.
+    pub ttl: Option<Duration>,
+}
+
+impl<T> DnsLookup<T> {
+    /// Create a new [`DnsLookup`] for the given records and their time-to-live (TTL).
+    pub fn new(records: Vec<T>, ttl: Duration) -> Self {
+        Self {
+            records,
+            ttl: Some(ttl),
+        }
+    }
+}
+
+impl<T> From<Vec<T>> for DnsLookup<T> {
+    fn from(records: Vec<T>) -> Self {
+        Self { records, ttl: None }
+    }
+}
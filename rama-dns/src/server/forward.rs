@@ -0,0 +1,296 @@
+use crate::{
+    hickory::svcb_from_https_record,
+    wire::{self, Message, OpCode, RData, Record, RecordType, ResponseCode, ResponseError},
+    DnsLookup, DnsResolver, DomainNotMappedErr,
+};
+use hickory_resolver::{
+    error::{ResolveError, ResolveErrorKind},
+    proto::rr::rdata::{A, AAAA, HTTPS},
+};
+use rama_core::{error::BoxError, Context, Service};
+use rama_net::address::Domain;
+use std::{convert::Infallible, error::Error, time::Duration};
+
+#[derive(Debug, Clone)]
+/// A DNS [`Service`] which answers 'A', 'AAAA' and 'HTTPS' queries using a [`DnsResolver`],
+/// e.g. a [`HickoryDns`] resolver to forward queries to upstream DNS servers,
+/// or an [`InMemoryDns`] to serve a fixed set of domains.
+///
+/// Queries of other record types are answered without records (`NOERROR`).
+/// Lookup errors which indicate that the domain does not exist are answered with `NXDOMAIN`,
+/// those of a domain without records of the queried type without records (`NOERROR`),
+/// and all other lookup errors with `SERVFAIL`.
+///
+/// [`HickoryDns`]: crate::HickoryDns
+/// [`InMemoryDns`]: crate::InMemoryDns
+pub struct ForwardService<R> {
+    resolver: R,
+    default_ttl: Duration,
+}
+
+impl<R> ForwardService<R> {
+    /// Create a new [`ForwardService`] which answers queries using the given [`DnsResolver`].
+    pub fn new(resolver: R) -> Self {
+        Self {
+            resolver,
+            default_ttl: Duration::from_secs(60),
+        }
+    }
+
+    /// Set the time-to-live (TTL) of the answered records,
+    /// in case the [`DnsResolver`] does not return one for its lookups.
+    ///
+    /// Defaults to 1 minute.
+    pub fn with_default_ttl(mut self, ttl: Duration) -> Self {
+        self.default_ttl = ttl;
+        self
+    }
+
+    /// Set the time-to-live (TTL) of the answered records,
+    /// in case the [`DnsResolver`] does not return one for its lookups.
+    ///
+    /// Defaults to 1 minute.
+    pub fn set_default_ttl(&mut self, ttl: Duration) -> &mut Self {
+        self.default_ttl = ttl;
+        self
+    }
+
+    fn answers<T>(
+        &self,
+        name: &wire::Name,
+        lookup: DnsLookup<T>,
+        rdata: impl Fn(T) -> Option<RData>,
+    ) -> Vec<Record> {
+        let ttl = lookup.ttl.unwrap_or(self.default_ttl);
+        let ttl = u32::try_from(ttl.as_secs()).unwrap_or(u32::MAX);
+        lookup
+            .records
+            .into_iter()
+            .filter_map(|record| Some(Record::from_rdata(name.clone(), ttl, rdata(record)?)))
+            .collect()
+    }
+}
+
+impl<State, R> Service<State, Message> for ForwardService<R>
+where
+    State: Clone + Send + Sync + 'static,
+    R: DnsResolver<Error: Into<BoxError>>,
+{
+    type Response = Message;
+    type Error = Infallible;
+
+    async fn serve(
+        &self,
+        _ctx: Context<State>,
+        query: Message,
+    ) -> Result<Self::Response, Self::Error> {
+        if query.op_code() != OpCode::Query {
+            return Ok(wire::response_message(&query, ResponseCode::NotImp));
+        }
+        let [question] = query.queries() else {
+            return Ok(wire::response_message(&query, ResponseCode::FormErr));
+        };
+
+        let mut name = question.name().clone();
+        name.set_fqdn(false);
+        let Ok(domain) = Domain::try_from(name.to_utf8()) else {
+            return Ok(wire::response_message(&query, ResponseCode::Refused));
+        };
+
+        let name = question.name();
+        let answers = match question.query_type() {
+            RecordType::A => self
+                .resolver
+                .ipv4_lookup_with_ttl(domain)
+                .await
+                .map(|lookup| self.answers(name, lookup, |ip| Some(RData::A(A(ip)))))
+                .map_err(Into::into),
+            RecordType::AAAA => self
+                .resolver
+                .ipv6_lookup_with_ttl(domain)
+                .await
+                .map(|lookup| self.answers(name, lookup, |ip| Some(RData::AAAA(AAAA(ip)))))
+                .map_err(Into::into),
+            RecordType::HTTPS => self
+                .resolver
+                .https_lookup_with_ttl(domain)
+                .await
+                .map(|lookup| {
+                    self.answers(name, lookup, |record| {
+                        let svcb = svcb_from_https_record(record).ok()?;
+                        Some(RData::HTTPS(HTTPS(svcb)))
+                    })
+                })
+                .map_err(Into::into),
+            _ => Ok(Vec::new()),
+        };
+
+        let mut response = match answers {
+            Ok(answers) => {
+                let mut response = wire::response_message(&query, ResponseCode::NoError);
+                response.add_answers(answers);
+                response
+            }
+            Err(err) => wire::response_message(&query, lookup_error_response_code(&*err)),
+        };
+        response.set_recursion_available(true);
+        Ok(response)
+    }
+}
+
+/// The response code of a failed lookup, which is `NXDOMAIN` in case the domain does not exist
+/// and `NOERROR` in case it has no records of the queried type, as reported by the (wrapped)
+/// resolver. All other lookup errors result in `SERVFAIL`.
+fn lookup_error_response_code(err: &(dyn Error + 'static)) -> ResponseCode {
+    let mut source = Some(err);
+    while let Some(err) = source {
+        let response_code = if err.is::<DomainNotMappedErr>() {
+            Some(ResponseCode::NXDomain)
+        } else if let Some(err) = err.downcast_ref::<ResponseError>() {
+            Some(err.response_code())
+        } else if let Some(err) = err.downcast_ref::<ResolveError>() {
+            match err.kind() {
+                ResolveErrorKind::NoRecordsFound { response_code, .. } => Some(*response_code),
+                _ => None,
+            }
+        } else {
+            None
+        };
+        match response_code {
+            Some(code @ (ResponseCode::NXDomain | ResponseCode::NoError)) => return code,
+            Some(_) => return ResponseCode::ServFail,
+            // static str errors are their own source
+            None => {
+                source = err
+                    .source()
+                    .filter(|source| !std::ptr::addr_eq(*source, err))
+            }
+        }
+    }
+    ResponseCode::ServFail
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        hickory::https_record_from_svcb, CachingDns, DenyAllDns, HttpsRecord, InMemoryDns,
+    };
+    use rama_core::error::{ErrorContext, OpaqueError};
+    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+    async fn query<R: DnsResolver<Error: Into<BoxError>>>(
+        service: &ForwardService<R>,
+        domain: &'static str,
+        record_type: RecordType,
+    ) -> Message {
+        let query = wire::query_message(3, Domain::from_static(domain), record_type).unwrap();
+        service
+            .serve(Context::<()>::default(), query)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_forward_service() {
+        let mut dns = InMemoryDns::new();
+        dns.insert(
+            Domain::from_static("example.com"),
+            vec![IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1))],
+        );
+        let service = ForwardService::new(dns).with_default_ttl(Duration::from_secs(30));
+
+        let response = query(&service, "example.com", RecordType::A).await;
+        assert_eq!(response.id(), 3);
+        assert_eq!(response.response_code(), ResponseCode::NoError);
+        assert!(response.recursion_available());
+        assert_eq!(response.answers().len(), 1);
+        assert_eq!(response.answers()[0].ttl(), 30);
+        assert_eq!(
+            response.answers()[0].data(),
+            Some(&RData::A(A(Ipv4Addr::new(127, 0, 0, 1))))
+        );
+
+        // other record types are answered without records
+        let response = query(&service, "example.com", RecordType::MX).await;
+        assert_eq!(response.response_code(), ResponseCode::NoError);
+        assert!(response.answers().is_empty());
+
+        let response = query(&service, "missing.example.com", RecordType::A).await;
+        assert_eq!(response.response_code(), ResponseCode::NXDomain);
+        assert!(response.answers().is_empty());
+    }
+
+    #[derive(Debug)]
+    struct HttpsDns;
+
+    impl DnsResolver for HttpsDns {
+        type Error = OpaqueError;
+
+        async fn ipv4_lookup(&self, _domain: Domain) -> Result<Vec<Ipv4Addr>, Self::Error> {
+            Err(ResponseError::new(ResponseCode::NoError)).context("no A records found")
+        }
+
+        async fn ipv6_lookup(&self, _domain: Domain) -> Result<Vec<Ipv6Addr>, Self::Error> {
+            Err(OpaqueError::from_display("connection refused"))
+        }
+
+        async fn https_lookup(&self, _domain: Domain) -> Result<Vec<HttpsRecord>, Self::Error> {
+            Ok(vec![HttpsRecord {
+                priority: 1,
+                target: None,
+                alpn: vec!["h2".to_owned()],
+                port: Some(8443),
+                ech_config_list: None,
+            }])
+        }
+    }
+
+    #[tokio::test]
+    async fn test_forward_service_https() {
+        let service = ForwardService::new(HttpsDns);
+        let response = query(&service, "example.com", RecordType::HTTPS).await;
+        assert_eq!(response.response_code(), ResponseCode::NoError);
+        assert_eq!(response.answers().len(), 1);
+        let Some(RData::HTTPS(https)) = response.answers()[0].data() else {
+            panic!("unexpected answer: {:?}", response.answers()[0]);
+        };
+        let record = https_record_from_svcb(&https.0);
+        assert_eq!(record.priority, 1);
+        assert_eq!(record.target, None);
+        assert_eq!(record.alpn, vec!["h2".to_owned()]);
+        assert_eq!(record.port, Some(8443));
+    }
+
+    #[tokio::test]
+    async fn test_forward_service_lookup_error_response_code() {
+        let service = ForwardService::new(HttpsDns);
+        // domain without records of the queried type
+        let response = query(&service, "example.com", RecordType::A).await;
+        assert_eq!(response.response_code(), ResponseCode::NoError);
+        assert!(response.answers().is_empty());
+        // other lookup errors
+        let response = query(&service, "example.com", RecordType::AAAA).await;
+        assert_eq!(response.response_code(), ResponseCode::ServFail);
+
+        // errors of the wrapped resolver of a cache
+        let mut dns = InMemoryDns::new();
+        dns.insert_address(
+            Domain::from_static("example.com"),
+            Ipv4Addr::new(127, 0, 0, 1),
+        );
+        let service = ForwardService::new(CachingDns::new(dns));
+        for _ in 0..2 {
+            let response = query(&service, "missing.example.com", RecordType::A).await;
+            assert_eq!(response.response_code(), ResponseCode::NXDomain);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_forward_service_lookup_error() {
+        let service = ForwardService::new(DenyAllDns::new());
+        let response = query(&service, "example.com", RecordType::AAAA).await;
+        assert_eq!(response.response_code(), ResponseCode::ServFail);
+        assert!(response.answers().is_empty());
+    }
+}
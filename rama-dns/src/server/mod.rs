@@ -0,0 +1,547 @@
+//! DNS server support, serving DNS queries using rama [`Service`]s.
+//!
+//! Each query [`Message`] received by a [`DnsServer`] is served as a request
+//! by its inner [`Service`], which returns the response [`Message`]:
+//!
+//! - [`ZoneService`]: answers authoritatively for the [`Zone`]s it serves,
+//!   e.g. to map domains to local stand-ins in test environments;
+//! - [`ForwardService`]: answers using any [`DnsResolver`].
+//!
+//! Queries are served over UDP using [`DnsServer::serve_udp`],
+//! and over streams such as TCP by using the [`DnsServer`] as a stream [`Service`].
+//!
+//! [`DnsResolver`]: crate::DnsResolver
+
+use crate::wire::{self, Message, MessageType, ResponseCode};
+use rama_core::{
+    error::{BoxError, ErrorContext, ErrorExt},
+    Context, Service,
+};
+use rama_net::stream::{SocketInfo, Stream};
+use std::{fmt, io, pin::pin, sync::Arc, time::Duration};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::UdpSocket,
+    sync::Semaphore,
+};
+
+mod zone;
+#[doc(inline)]
+pub use zone::{RefuseService, Zone, ZoneService};
+
+mod forward;
+#[doc(inline)]
+pub use forward::ForwardService;
+
+/// Maximum size of a DNS message over UDP for clients which do not advertise
+/// a larger size using EDNS, as defined in [RFC 1035].
+///
+/// [RFC 1035]: https://datatracker.ietf.org/doc/html/rfc1035
+const MIN_UDP_PAYLOAD: u16 = 512;
+
+/// Default duration after which idle stream connections are closed,
+/// within the range of a few seconds recommended by [RFC 7766].
+///
+/// [RFC 7766]: https://datatracker.ietf.org/doc/html/rfc7766#section-6.2.3
+const DEFAULT_STREAM_IDLE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Default maximum number of UDP queries served concurrently by [`DnsServer::serve_udp`].
+const DEFAULT_MAX_UDP_QUERIES: usize = 1024;
+
+/// A DNS server, serving DNS queries using the inner [`Service`].
+///
+/// Queries are served over UDP using [`DnsServer::serve_udp`].
+/// Streams (e.g. TCP streams accepted by a `TcpListener`) are served by using
+/// the [`DnsServer`] itself as a [`Service`], where queries are framed
+/// using a two byte length prefix, as defined in [RFC 1035].
+///
+/// Queries which cannot be served by the inner service are answered with `SERVFAIL`,
+/// and malformed queries received over a stream with `FORMERR`.
+///
+/// [RFC 1035]: https://datatracker.ietf.org/doc/html/rfc1035
+pub struct DnsServer<S> {
+    service: Arc<S>,
+    stream_idle_timeout: Duration,
+    max_udp_queries: usize,
+}
+
+impl<S: fmt::Debug> fmt::Debug for DnsServer<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DnsServer")
+            .field("service", &self.service)
+            .field("stream_idle_timeout", &self.stream_idle_timeout)
+            .field("max_udp_queries", &self.max_udp_queries)
+            .finish()
+    }
+}
+
+impl<S> Clone for DnsServer<S> {
+    fn clone(&self) -> Self {
+        Self {
+            service: self.service.clone(),
+            stream_idle_timeout: self.stream_idle_timeout,
+            max_udp_queries: self.max_udp_queries,
+        }
+    }
+}
+
+impl<S> DnsServer<S> {
+    /// Create a new [`DnsServer`] which serves DNS queries using the given [`Service`].
+    pub fn new(service: S) -> Self {
+        Self {
+            service: Arc::new(service),
+            stream_idle_timeout: DEFAULT_STREAM_IDLE_TIMEOUT,
+            max_udp_queries: DEFAULT_MAX_UDP_QUERIES,
+        }
+    }
+
+    /// Set the duration after which a stream is closed
+    /// when no (complete) query is received on it.
+    ///
+    /// Defaults to 10 seconds.
+    pub fn with_stream_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.stream_idle_timeout = timeout;
+        self
+    }
+
+    /// Set the duration after which a stream is closed
+    /// when no (complete) query is received on it.
+    ///
+    /// Defaults to 10 seconds.
+    pub fn set_stream_idle_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.stream_idle_timeout = timeout;
+        self
+    }
+
+    /// Set the maximum number of queries served concurrently by [`DnsServer::serve_udp`].
+    /// Queries received while this many queries are in flight are dropped.
+    ///
+    /// Defaults to 1024.
+    pub fn with_max_udp_queries(mut self, max: usize) -> Self {
+        self.max_udp_queries = max;
+        self
+    }
+
+    /// Set the maximum number of queries served concurrently by [`DnsServer::serve_udp`].
+    /// Queries received while this many queries are in flight are dropped.
+    ///
+    /// Defaults to 1024.
+    pub fn set_max_udp_queries(&mut self, max: usize) -> &mut Self {
+        self.max_udp_queries = max;
+        self
+    }
+
+    /// Serve the DNS queries received on the given [`UdpSocket`],
+    /// until the socket fails or the graceful shutdown guard
+    /// of the [`Context`]'s executor is cancelled.
+    ///
+    /// Each query is served in its own task, with the [`SocketInfo`]
+    /// of the client inserted in the [`Context`]. Responses which are larger than
+    /// the payload size supported by the client are truncated.
+    ///
+    /// Queries received while the maximum number of queries are in flight
+    /// (see [`DnsServer::with_max_udp_queries`]) are dropped,
+    /// as clients retry unanswered queries over UDP.
+    pub async fn serve_udp<State>(
+        &self,
+        ctx: Context<State>,
+        socket: UdpSocket,
+    ) -> Result<(), BoxError>
+    where
+        State: Clone + Send + Sync + 'static,
+        S: Service<State, Message, Response = Message, Error: Into<BoxError>>,
+    {
+        let socket = Arc::new(socket);
+        let in_flight = Arc::new(Semaphore::new(self.max_udp_queries));
+        let local_addr = socket.local_addr().ok();
+        let guard = ctx.executor().guard().cloned();
+        let mut cancelled = pin!(async {
+            match &guard {
+                Some(guard) => guard.cancelled().await,
+                None => std::future::pending().await,
+            }
+        });
+
+        let mut buf = vec![0; wire::MAX_MESSAGE_SIZE];
+        loop {
+            let (len, peer_addr) = tokio::select! {
+                _ = cancelled.as_mut() => return Ok(()),
+                result = socket.recv_from(&mut buf) => match result {
+                    Ok(received) => received,
+                    // reported on some platforms for a previous response
+                    // which could not be delivered to its client
+                    Err(err) if err.kind() == io::ErrorKind::ConnectionReset => continue,
+                    Err(err) => return Err(err.into()),
+                },
+            };
+
+            // malformed datagrams are dropped, as there is no query to respond to
+            let Ok(query) = wire::decode_message(&buf[..len]) else {
+                continue;
+            };
+            // queries are dropped when overloaded, the client retries them later
+            let Ok(permit) = in_flight.clone().try_acquire_owned() else {
+                continue;
+            };
+
+            let mut ctx = ctx.clone();
+            ctx.insert(SocketInfo::new(local_addr, peer_addr));
+            let executor = ctx.executor().clone();
+            let service = self.service.clone();
+            let socket = socket.clone();
+
+            executor.spawn_task(async move {
+                let _permit = permit;
+                let max_payload = usize::from(query.max_payload().max(MIN_UDP_PAYLOAD));
+                let response = serve_query(service.as_ref(), ctx, query).await;
+                let Ok(mut bytes) = wire::encode_message(&response) else {
+                    return;
+                };
+                if bytes.len() > max_payload {
+                    let Ok(truncated) = wire::encode_message(&truncate_response(&response)) else {
+                        return;
+                    };
+                    bytes = truncated;
+                }
+                let _ = socket.send_to(&bytes, peer_addr).await;
+            });
+        }
+    }
+
+    /// Serve the DNS queries received on the given [`Stream`], e.g. a TCP stream,
+    /// until the client closes the stream or it is idle for longer than the
+    /// stream idle timeout (see [`DnsServer::with_stream_idle_timeout`]).
+    ///
+    /// Queries and responses are framed using a two byte length prefix,
+    /// and served one after the other.
+    pub async fn serve_stream<State, IO>(
+        &self,
+        ctx: Context<State>,
+        stream: IO,
+    ) -> Result<(), BoxError>
+    where
+        State: Clone + Send + Sync + 'static,
+        S: Service<State, Message, Response = Message, Error: Into<BoxError>>,
+        IO: Stream,
+    {
+        let mut stream = pin!(stream);
+        loop {
+            let read = async {
+                let len = stream.read_u16().await?;
+                let mut buf = vec![0; usize::from(len)];
+                stream.read_exact(&mut buf).await?;
+                Ok::<_, io::Error>(buf)
+            };
+            let buf = match tokio::time::timeout(self.stream_idle_timeout, read).await {
+                Ok(Ok(buf)) => buf,
+                Ok(Err(err)) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(()),
+                Ok(Err(err)) => return Err(err.context("DnsServer: read dns query").into()),
+                Err(_) => return Ok(()),
+            };
+
+            let response = match wire::decode_message(&buf) {
+                Ok(query) => serve_query(self.service.as_ref(), ctx.clone(), query).await,
+                Err(_) => format_error_response(&buf),
+            };
+
+            let bytes = wire::encode_message(&response)?;
+            let mut buf = Vec::with_capacity(bytes.len() + 2);
+            // encoded messages never exceed the maximum message size
+            buf.extend_from_slice(&(bytes.len() as u16).to_be_bytes());
+            buf.extend_from_slice(&bytes);
+            stream
+                .write_all(&buf)
+                .await
+                .context("DnsServer: write dns response")?;
+            stream
+                .flush()
+                .await
+                .context("DnsServer: write dns response")?;
+        }
+    }
+}
+
+impl<State, S, IO> Service<State, IO> for DnsServer<S>
+where
+    State: Clone + Send + Sync + 'static,
+    S: Service<State, Message, Response = Message, Error: Into<BoxError>>,
+    IO: Stream,
+{
+    type Response = ();
+    type Error = BoxError;
+
+    async fn serve(&self, ctx: Context<State>, stream: IO) -> Result<Self::Response, Self::Error> {
+        self.serve_stream(ctx, stream).await
+    }
+}
+
+/// Serve a single query using the given service,
+/// answering with `SERVFAIL` in case the service failed to do so.
+async fn serve_query<State, S>(service: &S, ctx: Context<State>, query: Message) -> Message
+where
+    S: Service<State, Message, Response = Message>,
+{
+    let server_failure = wire::response_message(&query, ResponseCode::ServFail);
+    match service.serve(ctx, query).await {
+        Ok(response) if response.message_type() == MessageType::Response => response,
+        _ => server_failure,
+    }
+}
+
+/// Create a `FORMERR` response for a malformed query,
+/// using the message id of the query if available.
+fn format_error_response(query: &[u8]) -> Message {
+    let id = match query {
+        [high, low, ..] => u16::from_be_bytes([*high, *low]),
+        _ => 0,
+    };
+    let mut response = Message::new();
+    response
+        .set_id(id)
+        .set_message_type(MessageType::Response)
+        .set_response_code(ResponseCode::FormErr);
+    response
+}
+
+/// Create a copy of the response without any records,
+/// flagged as truncated such that the client retries over a stream transport.
+fn truncate_response(response: &Message) -> Message {
+    let mut truncated = Message::new();
+    truncated
+        .set_id(response.id())
+        .set_message_type(MessageType::Response)
+        .set_op_code(response.op_code())
+        .set_authoritative(response.authoritative())
+        .set_truncated(true)
+        .set_recursion_desired(response.recursion_desired())
+        .set_recursion_available(response.recursion_available())
+        .set_checking_disabled(response.checking_disabled())
+        .set_response_code(response.response_code())
+        .add_queries(response.queries().to_vec());
+    truncated
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wire::{RData, Record, RecordType};
+    use rama_net::address::Domain;
+    use std::{
+        net::{Ipv4Addr, SocketAddr},
+        str::FromStr,
+    };
+
+    fn zone_service() -> ZoneService {
+        let mut zone = Zone::new(Domain::from_static("example.com")).unwrap();
+        zone.insert_address(
+            Domain::from_static("www.example.com"),
+            Ipv4Addr::new(127, 0, 0, 1).into(),
+        )
+        .unwrap();
+        let name = wire::Name::from_str("many.example.com.").unwrap();
+        for i in 0..64 {
+            zone.insert_record(Record::from_rdata(
+                name.clone(),
+                60,
+                RData::A(Ipv4Addr::new(10, 0, 0, i).into()),
+            ));
+        }
+        ZoneService::new().with_zone(zone)
+    }
+
+    #[tokio::test]
+    async fn test_dns_server_udp() {
+        let server = DnsServer::new(zone_service());
+        let socket = UdpSocket::bind(SocketAddr::from(([127, 0, 0, 1], 0)))
+            .await
+            .unwrap();
+        let server_addr = socket.local_addr().unwrap();
+        tokio::spawn(async move { server.serve_udp(Context::<()>::default(), socket).await });
+
+        let client = UdpSocket::bind(SocketAddr::from(([127, 0, 0, 1], 0)))
+            .await
+            .unwrap();
+        client.connect(server_addr).await.unwrap();
+        let mut buf = vec![0; wire::MAX_MESSAGE_SIZE];
+
+        let query =
+            wire::query_message(1, Domain::from_static("www.example.com"), RecordType::A).unwrap();
+        client
+            .send(&wire::encode_message(&query).unwrap())
+            .await
+            .unwrap();
+        let len = client.recv(&mut buf).await.unwrap();
+        let response = wire::decode_message(&buf[..len]).unwrap();
+        assert_eq!(response.id(), 1);
+        assert_eq!(response.response_code(), ResponseCode::NoError);
+        assert!(response.authoritative());
+        assert_eq!(response.answers().len(), 1);
+
+        let query =
+            wire::query_message(2, Domain::from_static("many.example.com"), RecordType::A).unwrap();
+        client
+            .send(&wire::encode_message(&query).unwrap())
+            .await
+            .unwrap();
+        let len = client.recv(&mut buf).await.unwrap();
+        let response = wire::decode_message(&buf[..len]).unwrap();
+        assert_eq!(response.id(), 2);
+        assert!(response.truncated());
+        assert!(response.answers().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_dns_server_udp_max_queries() {
+        let service = rama_core::service::service_fn(|query: Message| async move {
+            if query.id() == 1 {
+                tokio::time::sleep(Duration::from_millis(200)).await;
+            }
+            Ok::<_, std::convert::Infallible>(wire::response_message(&query, ResponseCode::NoError))
+        });
+        let server = DnsServer::new(service).with_max_udp_queries(1);
+        let socket = UdpSocket::bind(SocketAddr::from(([127, 0, 0, 1], 0)))
+            .await
+            .unwrap();
+        let server_addr = socket.local_addr().unwrap();
+        tokio::spawn(async move { server.serve_udp(Context::<()>::default(), socket).await });
+
+        let client = UdpSocket::bind(SocketAddr::from(([127, 0, 0, 1], 0)))
+            .await
+            .unwrap();
+        client.connect(server_addr).await.unwrap();
+        let mut buf = vec![0; wire::MAX_MESSAGE_SIZE];
+
+        for id in [1, 2] {
+            let query =
+                wire::query_message(id, Domain::from_static("example.com"), RecordType::A).unwrap();
+            client
+                .send(&wire::encode_message(&query).unwrap())
+                .await
+                .unwrap();
+        }
+
+        // the second query is dropped while the first one is in flight
+        let len = client.recv(&mut buf).await.unwrap();
+        assert_eq!(wire::decode_message(&buf[..len]).unwrap().id(), 1);
+        assert!(
+            tokio::time::timeout(Duration::from_millis(100), client.recv(&mut buf))
+                .await
+                .is_err()
+        );
+
+        let query =
+            wire::query_message(3, Domain::from_static("example.com"), RecordType::A).unwrap();
+        client
+            .send(&wire::encode_message(&query).unwrap())
+            .await
+            .unwrap();
+        let len = client.recv(&mut buf).await.unwrap();
+        assert_eq!(wire::decode_message(&buf[..len]).unwrap().id(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_dns_server_stream() {
+        let server = DnsServer::new(zone_service());
+        let (mut client, stream) = tokio::io::duplex(wire::MAX_MESSAGE_SIZE);
+        let handle =
+            tokio::spawn(async move { server.serve(Context::<()>::default(), stream).await });
+
+        for (id, domain, code, answers) in [
+            (1, "www.example.com", ResponseCode::NoError, 1),
+            (2, "many.example.com", ResponseCode::NoError, 64),
+            (3, "missing.example.com", ResponseCode::NXDomain, 0),
+            (4, "example.org", ResponseCode::Refused, 0),
+        ] {
+            let query =
+                wire::query_message(id, Domain::from_static(domain), RecordType::A).unwrap();
+            let bytes = wire::encode_message(&query).unwrap();
+            client
+                .write_all(&(bytes.len() as u16).to_be_bytes())
+                .await
+                .unwrap();
+            client.write_all(&bytes).await.unwrap();
+
+            let len = client.read_u16().await.unwrap();
+            let mut buf = vec![0; usize::from(len)];
+            client.read_exact(&mut buf).await.unwrap();
+            let response = wire::decode_message(&buf).unwrap();
+            assert_eq!(response.id(), id);
+            assert_eq!(response.response_code(), code);
+            assert_eq!(response.answers().len(), answers);
+        }
+
+        drop(client);
+        handle.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_dns_server_stream_malformed_query() {
+        let server = DnsServer::new(zone_service());
+        let (mut client, stream) = tokio::io::duplex(wire::MAX_MESSAGE_SIZE);
+        let handle =
+            tokio::spawn(async move { server.serve(Context::<()>::default(), stream).await });
+
+        let garbage = [0, 9, 1, 2, 3];
+        client
+            .write_all(&(garbage.len() as u16).to_be_bytes())
+            .await
+            .unwrap();
+        client.write_all(&garbage).await.unwrap();
+
+        let len = client.read_u16().await.unwrap();
+        let mut buf = vec![0; usize::from(len)];
+        client.read_exact(&mut buf).await.unwrap();
+        let response = wire::decode_message(&buf).unwrap();
+        assert_eq!(response.id(), 9);
+        assert_eq!(response.response_code(), ResponseCode::FormErr);
+
+        // the connection remains usable
+        let query =
+            wire::query_message(10, Domain::from_static("www.example.com"), RecordType::A).unwrap();
+        let bytes = wire::encode_message(&query).unwrap();
+        client
+            .write_all(&(bytes.len() as u16).to_be_bytes())
+            .await
+            .unwrap();
+        client.write_all(&bytes).await.unwrap();
+        let len = client.read_u16().await.unwrap();
+        let mut buf = vec![0; usize::from(len)];
+        client.read_exact(&mut buf).await.unwrap();
+        let response = wire::decode_message(&buf).unwrap();
+        assert_eq!(response.id(), 10);
+        assert_eq!(response.response_code(), ResponseCode::NoError);
+
+        drop(client);
+        handle.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_dns_server_stream_idle_timeout() {
+        let server =
+            DnsServer::new(zone_service()).with_stream_idle_timeout(Duration::from_millis(50));
+        let (mut client, stream) = tokio::io::duplex(wire::MAX_MESSAGE_SIZE);
+        let handle =
+            tokio::spawn(async move { server.serve(Context::<()>::default(), stream).await });
+
+        // an incomplete query does not keep the connection open
+        client.write_all(&[0, 32, 0]).await.unwrap();
+        tokio::time::timeout(Duration::from_secs(1), handle)
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        let mut buf = Vec::new();
+        assert_eq!(client.read_to_end(&mut buf).await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_dns_server_service_failure() {
+        let service =
+            rama_core::service::service_fn(|_query: Message| async { Err::<Message, _>("oops") });
+        let query =
+            wire::query_message(5, Domain::from_static("example.com"), RecordType::A).unwrap();
+        let response = serve_query(&service, Context::<()>::default(), query).await;
+        assert_eq!(response.id(), 5);
+        assert_eq!(response.response_code(), ResponseCode::ServFail);
+    }
+}
@@ -0,0 +1,459 @@
+use crate::{
+    hickory::fqdn_from_domain,
+    wire::{self, Message, Name, OpCode, Query, RData, Record, RecordType, ResponseCode},
+};
+use hickory_resolver::proto::rr::rdata::{A, AAAA, CNAME};
+use rama_core::{error::OpaqueError, Context, Service};
+use rama_net::address::Domain;
+use std::{collections::HashMap, convert::Infallible, net::IpAddr, time::Duration};
+
+/// Maximum amount of CNAME records followed within a [`Zone`] to answer a query.
+const MAX_CNAME_CHAIN: usize = 8;
+
+#[derive(Debug, Clone)]
+/// A DNS zone, containing the records served authoritatively by a [`ZoneService`]
+/// for its origin and all domains below it.
+///
+/// Records with a wildcard name (e.g. `*.example.com`) are used to answer
+/// queries for names which have no records of their own. Records of which the name
+/// is not part of the zone are never served.
+pub struct Zone {
+    origin: Name,
+    ttl: Duration,
+    records: HashMap<Name, Vec<Record>>,
+    soa: Option<Record>,
+}
+
+impl Zone {
+    /// Create a new empty [`Zone`] for the given origin [`Domain`].
+    pub fn new(origin: Domain) -> Result<Self, OpaqueError> {
+        Ok(Self {
+            origin: fqdn_from_domain(origin)?.to_lowercase(),
+            ttl: Duration::from_secs(300),
+            records: HashMap::new(),
+            soa: None,
+        })
+    }
+
+    /// Returns the origin of the [`Zone`].
+    pub fn origin(&self) -> &Name {
+        &self.origin
+    }
+
+    /// Set the time-to-live (TTL) of the records inserted using [`Zone::insert_address`].
+    ///
+    /// Defaults to 5 minutes.
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// Set the time-to-live (TTL) of the records inserted using [`Zone::insert_address`].
+    ///
+    /// Defaults to 5 minutes.
+    pub fn set_ttl(&mut self, ttl: Duration) -> &mut Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// Inserts a [`Record`] in the [`Zone`].
+    ///
+    /// A 'SOA' record replaces the existing one, if any, and is added
+    /// to the authority section of negative answers.
+    pub fn insert_record(&mut self, record: Record) -> &mut Self {
+        if record.record_type() == RecordType::SOA {
+            self.soa = Some(record);
+        } else {
+            self.records
+                .entry(record.name().to_lowercase())
+                .or_default()
+                .push(record);
+        }
+        self
+    }
+
+    /// Inserts an 'A' or 'AAAA' record, for the given IP address, in the [`Zone`].
+    pub fn insert_address(
+        &mut self,
+        domain: Domain,
+        address: IpAddr,
+    ) -> Result<&mut Self, OpaqueError> {
+        let name = fqdn_from_domain(domain)?;
+        let rdata = match address {
+            IpAddr::V4(ip) => RData::A(A(ip)),
+            IpAddr::V6(ip) => RData::AAAA(AAAA(ip)),
+        };
+        let ttl = u32::try_from(self.ttl.as_secs()).unwrap_or(u32::MAX);
+        Ok(self.insert_record(Record::from_rdata(name, ttl, rdata)))
+    }
+
+    /// Create the authoritative response for the given query.
+    fn answer(&self, query: &Message, question: &Query) -> Message {
+        let mut response = wire::response_message(query, ResponseCode::NoError);
+        response.set_authoritative(true);
+        match self.lookup(question.name(), question.query_type()) {
+            Some(answers) if !answers.is_empty() => {
+                response.add_answers(answers);
+                return response;
+            }
+            Some(_) => (),
+            None => {
+                response.set_response_code(ResponseCode::NXDomain);
+            }
+        }
+        if let Some(soa) = &self.soa {
+            response.add_name_server(soa.clone());
+        }
+        response
+    }
+
+    /// Returns the answer records for the given name and type,
+    /// following CNAME records within the zone,
+    /// or `None` in case the name does not exist in the zone.
+    fn lookup(&self, name: &Name, record_type: RecordType) -> Option<Vec<Record>> {
+        let mut answers = Vec::new();
+        let mut name = name.to_lowercase();
+        for _ in 0..MAX_CNAME_CHAIN {
+            let Some(records) = self.records_for(&name) else {
+                let exists = !answers.is_empty() || self.records.keys().any(|n| name.zone_of(n));
+                return exists.then_some(answers);
+            };
+
+            let start = answers.len();
+            answers.extend(
+                records
+                    .iter()
+                    .filter(|record| {
+                        record_type == RecordType::ANY || record.record_type() == record_type
+                    })
+                    .map(|record| record_with_name(record, &name)),
+            );
+            if answers.len() > start {
+                return Some(answers);
+            }
+
+            let Some(cname) = records
+                .iter()
+                .find(|record| record.record_type() == RecordType::CNAME)
+            else {
+                return Some(answers);
+            };
+            answers.push(record_with_name(cname, &name));
+            match cname.data() {
+                Some(RData::CNAME(CNAME(target))) if self.origin.zone_of(target) => {
+                    name = target.to_lowercase();
+                }
+                _ => return Some(answers),
+            }
+        }
+        Some(answers)
+    }
+
+    /// Returns the records of the given name,
+    /// falling back to the records of the closest wildcard name.
+    fn records_for(&self, name: &Name) -> Option<&[Record]> {
+        if let Some(records) = self.records.get(name) {
+            return Some(records.as_slice());
+        }
+        let mut name = name.clone();
+        while name.num_labels() > self.origin.num_labels() {
+            if let Some(records) = self.records.get(&name.clone().into_wildcard()) {
+                return Some(records.as_slice());
+            }
+            name = name.base_name();
+        }
+        None
+    }
+}
+
+/// Returns the record with its name replaced by the given name,
+/// such that records of wildcard names answer for the queried name.
+fn record_with_name(record: &Record, name: &Name) -> Record {
+    let mut record = record.clone();
+    record.set_name(name.clone());
+    record
+}
+
+#[derive(Debug, Clone, Default)]
+/// A DNS [`Service`] which answers authoritatively for the [`Zone`]s it serves.
+///
+/// Queries for names which exist in the zone but have no records of the queried type
+/// are answered without records, while queries for names which do not exist in the zone
+/// are answered with `NXDOMAIN`. Queries outside of its zones are served by the fallback
+/// service, a [`RefuseService`] by default, e.g. a [`ForwardService`] to resolve all
+/// other domains using a [`DnsResolver`].
+///
+/// [`ForwardService`]: super::ForwardService
+/// [`DnsResolver`]: crate::DnsResolver
+pub struct ZoneService<F = RefuseService> {
+    zones: Vec<Zone>,
+    fallback: F,
+}
+
+impl ZoneService {
+    /// Create a new [`ZoneService`] without any [`Zone`]s,
+    /// which refuses to answer queries outside of its zones.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl<F> ZoneService<F> {
+    /// Add a [`Zone`] to be served by this [`ZoneService`].
+    ///
+    /// Queries are answered using the zone with the longest matching origin.
+    pub fn with_zone(mut self, zone: Zone) -> Self {
+        self.zones.push(zone);
+        self
+    }
+
+    /// Add a [`Zone`] to be served by this [`ZoneService`].
+    ///
+    /// Queries are answered using the zone with the longest matching origin.
+    pub fn add_zone(&mut self, zone: Zone) -> &mut Self {
+        self.zones.push(zone);
+        self
+    }
+
+    /// Set the [`Service`] used to serve queries outside of the [`Zone`]s of this [`ZoneService`].
+    pub fn with_fallback<G>(self, fallback: G) -> ZoneService<G> {
+        ZoneService {
+            zones: self.zones,
+            fallback,
+        }
+    }
+
+    fn zone_for(&self, name: &Name) -> Option<&Zone> {
+        self.zones
+            .iter()
+            .filter(|zone| zone.origin.zone_of(name))
+            .max_by_key(|zone| zone.origin.num_labels())
+    }
+}
+
+impl<State, F> Service<State, Message> for ZoneService<F>
+where
+    State: Clone + Send + Sync + 'static,
+    F: Service<State, Message, Response = Message>,
+{
+    type Response = Message;
+    type Error = F::Error;
+
+    async fn serve(
+        &self,
+        ctx: Context<State>,
+        query: Message,
+    ) -> Result<Self::Response, Self::Error> {
+        if query.op_code() != OpCode::Query {
+            return Ok(wire::response_message(&query, ResponseCode::NotImp));
+        }
+        let [question] = query.queries() else {
+            return Ok(wire::response_message(&query, ResponseCode::FormErr));
+        };
+        match self.zone_for(question.name()) {
+            Some(zone) => Ok(zone.answer(&query, question)),
+            None => self.fallback.serve(ctx, query).await,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+#[non_exhaustive]
+/// A DNS [`Service`] which refuses to answer any query.
+pub struct RefuseService;
+
+impl RefuseService {
+    #[inline]
+    /// Create a new [`Default`] [`RefuseService`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl<State> Service<State, Message> for RefuseService
+where
+    State: Clone + Send + Sync + 'static,
+{
+    type Response = Message;
+    type Error = Infallible;
+
+    async fn serve(
+        &self,
+        _ctx: Context<State>,
+        query: Message,
+    ) -> Result<Self::Response, Self::Error> {
+        Ok(wire::response_message(&query, ResponseCode::Refused))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hickory_resolver::proto::rr::rdata::SOA;
+    use std::{
+        net::{Ipv4Addr, Ipv6Addr},
+        str::FromStr,
+    };
+
+    fn name(s: &str) -> Name {
+        Name::from_str(s).unwrap()
+    }
+
+    fn zone() -> Zone {
+        let mut zone = Zone::new(Domain::from_static("example.com")).unwrap();
+        zone.insert_address(
+            Domain::from_static("www.example.com"),
+            Ipv4Addr::new(127, 0, 0, 1).into(),
+        )
+        .unwrap()
+        .insert_address(
+            Domain::from_static("www.example.com"),
+            Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 1).into(),
+        )
+        .unwrap()
+        .insert_address(
+            Domain::from_static("a.b.example.com"),
+            Ipv4Addr::new(127, 0, 0, 2).into(),
+        )
+        .unwrap()
+        .insert_record(Record::from_rdata(
+            name("*.wild.example.com."),
+            60,
+            RData::A(A(Ipv4Addr::new(127, 0, 0, 3))),
+        ))
+        .insert_record(Record::from_rdata(
+            name("alias.example.com."),
+            60,
+            RData::CNAME(CNAME(name("WWW.example.com."))),
+        ))
+        .insert_record(Record::from_rdata(
+            name("example.com."),
+            3600,
+            RData::SOA(SOA::new(
+                name("ns.example.com."),
+                name("hostmaster.example.com."),
+                1,
+                3600,
+                600,
+                86400,
+                60,
+            )),
+        ));
+        zone
+    }
+
+    async fn query(
+        service: &ZoneService,
+        domain: &'static str,
+        record_type: RecordType,
+    ) -> Message {
+        let query = wire::query_message(1, Domain::from_static(domain), record_type).unwrap();
+        service
+            .serve(Context::<()>::default(), query)
+            .await
+            .unwrap()
+    }
+
+    fn addresses(response: &Message) -> Vec<IpAddr> {
+        response
+            .answers()
+            .iter()
+            .filter_map(|record| match record.data() {
+                Some(RData::A(A(ip))) => Some((*ip).into()),
+                Some(RData::AAAA(AAAA(ip))) => Some((*ip).into()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_zone_service_answers() {
+        let service = ZoneService::new().with_zone(zone());
+
+        let response = query(&service, "www.example.com", RecordType::A).await;
+        assert_eq!(response.response_code(), ResponseCode::NoError);
+        assert!(response.authoritative());
+        assert_eq!(addresses(&response), vec![IpAddr::from([127, 0, 0, 1])]);
+        assert_eq!(response.answers()[0].ttl(), 300);
+
+        let response = query(&service, "WWW.Example.com", RecordType::AAAA).await;
+        assert_eq!(
+            addresses(&response),
+            vec![IpAddr::from(Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 1))]
+        );
+
+        let response = query(&service, "foo.wild.example.com", RecordType::A).await;
+        assert_eq!(addresses(&response), vec![IpAddr::from([127, 0, 0, 3])]);
+        assert_eq!(response.answers()[0].name(), &name("foo.wild.example.com."));
+
+        let response = query(&service, "alias.example.com", RecordType::A).await;
+        assert_eq!(response.answers().len(), 2);
+        assert_eq!(response.answers()[0].record_type(), RecordType::CNAME);
+        assert_eq!(addresses(&response), vec![IpAddr::from([127, 0, 0, 1])]);
+    }
+
+    #[tokio::test]
+    async fn test_zone_service_negative_answers() {
+        let service = ZoneService::new().with_zone(zone());
+
+        // existing name without records of the queried type
+        let response = query(&service, "a.b.example.com", RecordType::AAAA).await;
+        assert_eq!(response.response_code(), ResponseCode::NoError);
+        assert!(response.answers().is_empty());
+        assert_eq!(response.name_servers().len(), 1);
+
+        // empty non-terminal name
+        let response = query(&service, "b.example.com", RecordType::A).await;
+        assert_eq!(response.response_code(), ResponseCode::NoError);
+        assert!(response.answers().is_empty());
+
+        let response = query(&service, "missing.example.com", RecordType::A).await;
+        assert_eq!(response.response_code(), ResponseCode::NXDomain);
+        assert!(response.authoritative());
+        assert_eq!(response.name_servers().len(), 1);
+
+        let response = query(&service, "example.org", RecordType::A).await;
+        assert_eq!(response.response_code(), ResponseCode::Refused);
+        assert!(!response.authoritative());
+    }
+
+    #[tokio::test]
+    async fn test_zone_service_longest_origin() {
+        let mut sub = Zone::new(Domain::from_static("b.example.com")).unwrap();
+        sub.insert_address(
+            Domain::from_static("a.b.example.com"),
+            Ipv4Addr::new(10, 0, 0, 1).into(),
+        )
+        .unwrap();
+        let service = ZoneService::new().with_zone(zone()).with_zone(sub);
+
+        let response = query(&service, "a.b.example.com", RecordType::A).await;
+        assert_eq!(addresses(&response), vec![IpAddr::from([10, 0, 0, 1])]);
+
+        let response = query(&service, "www.example.com", RecordType::A).await;
+        assert_eq!(addresses(&response), vec![IpAddr::from([127, 0, 0, 1])]);
+    }
+
+    #[tokio::test]
+    async fn test_zone_service_fallback() {
+        let mut fallback_zone = Zone::new(Domain::from_static("example.org")).unwrap();
+        fallback_zone
+            .insert_address(
+                Domain::from_static("example.org"),
+                Ipv4Addr::new(10, 0, 0, 2).into(),
+            )
+            .unwrap();
+        let service = ZoneService::new()
+            .with_zone(zone())
+            .with_fallback(ZoneService::new().with_zone(fallback_zone));
+
+        let query =
+            wire::query_message(1, Domain::from_static("example.org"), RecordType::A).unwrap();
+        let response = service
+            .serve(Context::<()>::default(), query)
+            .await
+            .unwrap();
+        assert_eq!(response.response_code(), ResponseCode::NoError);
+        assert_eq!(response.answers().len(), 1);
+    }
+}
@@ -1,4 +1,4 @@
-use crate::DnsResolver;
+use crate::{DnsLookup, DnsResolver, HttpsRecord};
 use rama_net::address::Domain;
 use std::net::{Ipv4Addr, Ipv6Addr};
 
@@ -35,6 +35,58 @@ macro_rules! impl_dns_resolver_either_either {
                     )+
                 }
             }
+
+            async fn https_lookup(
+                &self,
+                domain: Domain,
+            ) -> Result<Vec<HttpsRecord>, Self::Error> {
+                match self {
+                    $(
+                        ::rama_core::combinators::$id::$param(d) => d.https_lookup(domain)
+                            .await
+                            .map_err(Into::into),
+                    )+
+                }
+            }
+
+            async fn ipv4_lookup_with_ttl(
+                &self,
+                domain: Domain,
+            ) -> Result<DnsLookup<Ipv4Addr>, Self::Error> {
+                match self {
+                    $(
+                        ::rama_core::combinators::$id::$param(d) => d.ipv4_lookup_with_ttl(domain)
+                            .await
+                            .map_err(Into::into),
+                    )+
+                }
+            }
+
+            async fn ipv6_lookup_with_ttl(
+                &self,
+                domain: Domain,
+            ) -> Result<DnsLookup<Ipv6Addr>, Self::Error> {
+                match self {
+                    $(
+                        ::rama_core::combinators::$id::$param(d) => d.ipv6_lookup_with_ttl(domain)
+                            .await
+                            .map_err(Into::into),
+                    )+
+                }
+            }
+
+            async fn https_lookup_with_ttl(
+                &self,
+                domain: Domain,
+            ) -> Result<DnsLookup<HttpsRecord>, Self::Error> {
+                match self {
+                    $(
+                        ::rama_core::combinators::$id::$param(d) => d.https_lookup_with_ttl(domain)
+                            .await
+                            .map_err(Into::into),
+                    )+
+                }
+            }
         }
     };
 }
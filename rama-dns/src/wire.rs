@@ -0,0 +1,373 @@
+//! DNS wire format support, as defined in [RFC 1035],
+//! used to exchange DNS messages with (remote) DNS servers.
+//!
+//! The message types are those of the [`hickory_resolver::proto`] crate,
+//! re-exported here for convenience.
+//!
+//! [RFC 1035]: https://datatracker.ietf.org/doc/html/rfc1035
+
+use crate::{
+    hickory::{fqdn_from_domain, https_record_from_svcb},
+    DnsLookup, HttpsRecord,
+};
+use hickory_resolver::proto::rr::rdata::{A, AAAA};
+use rama_core::error::{BoxError, ErrorContext, OpaqueError};
+use rama_net::address::Domain;
+use std::{
+    fmt,
+    future::Future,
+    net::{Ipv4Addr, Ipv6Addr},
+    sync::Arc,
+    time::Duration,
+};
+
+#[doc(inline)]
+pub use hickory_resolver::proto::{
+    op::{Message, MessageType, OpCode, Query, ResponseCode},
+    rr::{Name, RData, Record, RecordType},
+};
+
+/// Maximum size of a DNS message in wire format,
+/// as limited by the two byte length prefix used for stream transports.
+pub const MAX_MESSAGE_SIZE: usize = u16::MAX as usize;
+
+/// A transport which exchanges DNS [`Message`]s with a (remote) DNS server,
+/// e.g. over HTTPS (DoH) or TLS (DoT).
+///
+/// Use [`lookup_ipv4`], [`lookup_ipv6`] and [`lookup_https`] to resolve
+/// records via such an exchange, as used to implement a [`DnsResolver`].
+///
+/// [`DnsResolver`]: crate::DnsResolver
+pub trait DnsExchange: Send + Sync + 'static {
+    /// Error returned by the [`DnsExchange`]
+    type Error;
+
+    /// Send the query [`Message`] to the DNS server and return its response [`Message`].
+    fn exchange(
+        &self,
+        query: Message,
+    ) -> impl Future<Output = Result<Message, Self::Error>> + Send + '_;
+}
+
+impl<E: DnsExchange> DnsExchange for Arc<E> {
+    type Error = E::Error;
+
+    fn exchange(
+        &self,
+        query: Message,
+    ) -> impl Future<Output = Result<Message, Self::Error>> + Send + '_ {
+        (**self).exchange(query)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Error returned by the lookups of this module in case the DNS server
+/// replied with an error [`ResponseCode`], or without any of the requested records.
+pub struct ResponseError {
+    response_code: ResponseCode,
+}
+
+impl ResponseError {
+    pub(crate) fn new(response_code: ResponseCode) -> Self {
+        Self { response_code }
+    }
+
+    /// Returns the [`ResponseCode`] of the response,
+    /// which is `NOERROR` in case the response did not contain any of the requested records.
+    pub fn response_code(&self) -> ResponseCode {
+        self.response_code
+    }
+}
+
+impl fmt::Display for ResponseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.response_code {
+            ResponseCode::NoError => f.write_str("dns server replied without records"),
+            code => write!(f, "dns server replied with error: {code}"),
+        }
+    }
+}
+
+impl std::error::Error for ResponseError {}
+
+/// Encode a [`Message`] into its wire format.
+pub fn encode_message(message: &Message) -> Result<Vec<u8>, OpaqueError> {
+    let bytes = message.to_vec().context("encode dns message")?;
+    if bytes.len() > MAX_MESSAGE_SIZE {
+        return Err(OpaqueError::from_display("dns message too large"));
+    }
+    Ok(bytes)
+}
+
+/// Decode a [`Message`] from its wire format.
+pub fn decode_message(bytes: &[u8]) -> Result<Message, OpaqueError> {
+    Message::from_vec(bytes).context("decode dns message")
+}
+
+/// Create a recursive query [`Message`] for the records of the given type for a [`Domain`].
+pub fn query_message(
+    id: u16,
+    domain: Domain,
+    record_type: RecordType,
+) -> Result<Message, OpaqueError> {
+    let name = fqdn_from_domain(domain)?;
+    let mut message = Message::new();
+    message
+        .set_id(id)
+        .set_message_type(MessageType::Query)
+        .set_op_code(OpCode::Query)
+        .set_recursion_desired(true)
+        .add_query(Query::query(name, record_type));
+    Ok(message)
+}
+
+/// Create a response [`Message`], without any records, for the given query [`Message`].
+pub fn response_message(query: &Message, response_code: ResponseCode) -> Message {
+    let mut message = Message::new();
+    message
+        .set_id(query.id())
+        .set_message_type(MessageType::Response)
+        .set_op_code(query.op_code())
+        .set_recursion_desired(query.recursion_desired())
+        .set_checking_disabled(query.checking_disabled())
+        .set_response_code(response_code)
+        .add_queries(query.queries().to_vec());
+    message
+}
+
+/// Resolve the 'A' records for the given [`Domain`] via a [`DnsExchange`].
+pub async fn lookup_ipv4<E>(
+    exchange: &E,
+    domain: Domain,
+) -> Result<DnsLookup<Ipv4Addr>, OpaqueError>
+where
+    E: DnsExchange<Error: Into<BoxError>>,
+{
+    lookup(exchange, domain, RecordType::A, |rdata| match rdata {
+        RData::A(A(ip)) => Some(*ip),
+        _ => None,
+    })
+    .await
+}
+
+/// Resolve the 'AAAA' records for the given [`Domain`] via a [`DnsExchange`].
+pub async fn lookup_ipv6<E>(
+    exchange: &E,
+    domain: Domain,
+) -> Result<DnsLookup<Ipv6Addr>, OpaqueError>
+where
+    E: DnsExchange<Error: Into<BoxError>>,
+{
+    lookup(exchange, domain, RecordType::AAAA, |rdata| match rdata {
+        RData::AAAA(AAAA(ip)) => Some(*ip),
+        _ => None,
+    })
+    .await
+}
+
+/// Resolve the 'HTTPS' records for the given [`Domain`] via a [`DnsExchange`].
+///
+/// Unlike address lookups, a domain without HTTPS records resolves to no records.
+pub async fn lookup_https<E>(
+    exchange: &E,
+    domain: Domain,
+) -> Result<DnsLookup<HttpsRecord>, OpaqueError>
+where
+    E: DnsExchange<Error: Into<BoxError>>,
+{
+    let query = query_message(0, domain, RecordType::HTTPS)?;
+    let response = exchange_checked(exchange, query).await?;
+    Ok(records_from_response(&response, |rdata| match rdata {
+        RData::HTTPS(https) => Some(https_record_from_svcb(&https.0)),
+        _ => None,
+    }))
+}
+
+async fn lookup<E, T>(
+    exchange: &E,
+    domain: Domain,
+    record_type: RecordType,
+    extract: impl Fn(&RData) -> Option<T>,
+) -> Result<DnsLookup<T>, OpaqueError>
+where
+    E: DnsExchange<Error: Into<BoxError>>,
+{
+    let query = query_message(0, domain, record_type)?;
+    let response = exchange_checked(exchange, query).await?;
+    let lookup = records_from_response(&response, extract);
+    if lookup.records.is_empty() {
+        return Err(ResponseError::new(ResponseCode::NoError))
+            .with_context(|| format!("no {record_type} records found"));
+    }
+    Ok(lookup)
+}
+
+async fn exchange_checked<E>(exchange: &E, query: Message) -> Result<Message, OpaqueError>
+where
+    E: DnsExchange<Error: Into<BoxError>>,
+{
+    let response = exchange
+        .exchange(query)
+        .await
+        .map_err(|err| OpaqueError::from_boxed(err.into()))
+        .context("exchange dns message")?;
+    if response.message_type() != MessageType::Response {
+        return Err(OpaqueError::from_display(
+            "dns server replied with a non-response message",
+        ));
+    }
+    match response.response_code() {
+        ResponseCode::NoError => Ok(response),
+        response_code => Err(OpaqueError::from_std(ResponseError::new(response_code))),
+    }
+}
+
+/// Extract the answer records of the response,
+/// using the lowest TTL of those records as the TTL of the lookup.
+fn records_from_response<T>(
+    response: &Message,
+    extract: impl Fn(&RData) -> Option<T>,
+) -> DnsLookup<T> {
+    let mut ttl = None;
+    let records = response
+        .answers()
+        .iter()
+        .filter_map(|record| {
+            let value = record.data().and_then(&extract)?;
+            ttl = Some(ttl.unwrap_or(u32::MAX).min(record.ttl()));
+            Some(value)
+        })
+        .collect();
+    DnsLookup {
+        records,
+        ttl: ttl.map(|ttl| Duration::from_secs(ttl.into())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    struct StaticExchange(Vec<Record>, ResponseCode);
+
+    impl DnsExchange for StaticExchange {
+        type Error = OpaqueError;
+
+        async fn exchange(&self, query: Message) -> Result<Message, Self::Error> {
+            let bytes = encode_message(&query)?;
+            let query = decode_message(&bytes)?;
+
+            let mut response = Message::new();
+            response
+                .set_id(query.id())
+                .set_message_type(MessageType::Response)
+                .set_op_code(OpCode::Query)
+                .set_response_code(self.1)
+                .add_queries(query.queries().to_vec());
+            let query_type = query.queries()[0].query_type();
+            response.add_answers(
+                self.0
+                    .iter()
+                    .filter(|record| record.record_type() == query_type)
+                    .cloned(),
+            );
+            decode_message(&encode_message(&response)?)
+        }
+    }
+
+    fn name() -> Name {
+        Name::from_str("example.com.").unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_lookup_via_exchange() {
+        let exchange = StaticExchange(
+            vec![
+                Record::from_rdata(name(), 300, RData::A(A(Ipv4Addr::new(127, 0, 0, 1)))),
+                Record::from_rdata(name(), 60, RData::A(A(Ipv4Addr::new(127, 0, 0, 2)))),
+                Record::from_rdata(
+                    name(),
+                    30,
+                    RData::AAAA(AAAA(Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 1))),
+                ),
+            ],
+            ResponseCode::NoError,
+        );
+
+        let lookup = lookup_ipv4(&exchange, Domain::from_static("example.com"))
+            .await
+            .unwrap();
+        assert_eq!(
+            lookup.records,
+            vec![Ipv4Addr::new(127, 0, 0, 1), Ipv4Addr::new(127, 0, 0, 2)]
+        );
+        assert_eq!(lookup.ttl, Some(Duration::from_secs(60)));
+
+        let lookup = lookup_ipv6(&exchange, Domain::from_static("example.com"))
+            .await
+            .unwrap();
+        assert_eq!(lookup.records, vec![Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 1)]);
+        assert_eq!(lookup.ttl, Some(Duration::from_secs(30)));
+
+        let lookup = lookup_https(&exchange, Domain::from_static("example.com"))
+            .await
+            .unwrap();
+        assert!(lookup.records.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_lookup_via_exchange_errors() {
+        let exchange = StaticExchange(Vec::new(), ResponseCode::NoError);
+        let err = lookup_ipv4(&exchange, Domain::from_static("example.com"))
+            .await
+            .unwrap_err();
+        let source = std::error::Error::source(&err)
+            .and_then(|err| err.downcast_ref::<ResponseError>())
+            .unwrap();
+        assert_eq!(source.response_code(), ResponseCode::NoError);
+
+        let exchange = StaticExchange(
+            vec![Record::from_rdata(
+                name(),
+                300,
+                RData::A(A(Ipv4Addr::new(127, 0, 0, 1))),
+            )],
+            ResponseCode::NXDomain,
+        );
+        let err = lookup_ipv4(&exchange, Domain::from_static("example.com"))
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<ResponseError>().unwrap().response_code(),
+            ResponseCode::NXDomain
+        );
+    }
+
+    #[test]
+    fn test_query_message_roundtrip() {
+        let query =
+            query_message(42, Domain::from_static("example.com"), RecordType::AAAA).unwrap();
+        let query = decode_message(&encode_message(&query).unwrap()).unwrap();
+        assert_eq!(query.id(), 42);
+        assert_eq!(query.message_type(), MessageType::Query);
+        assert!(query.recursion_desired());
+        assert_eq!(query.queries().len(), 1);
+        assert_eq!(query.queries()[0].name(), &name());
+        assert_eq!(query.queries()[0].query_type(), RecordType::AAAA);
+
+        assert!(decode_message(&[0, 1, 2]).is_err());
+    }
+
+    #[test]
+    fn test_response_message() {
+        let query = query_message(7, Domain::from_static("example.com"), RecordType::A).unwrap();
+        let response = response_message(&query, ResponseCode::NXDomain);
+        assert_eq!(response.id(), 7);
+        assert_eq!(response.message_type(), MessageType::Response);
+        assert_eq!(response.response_code(), ResponseCode::NXDomain);
+        assert!(response.recursion_desired());
+        assert_eq!(response.queries(), query.queries());
+        assert!(response.answers().is_empty());
+    }
+}
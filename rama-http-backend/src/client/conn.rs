@@ -1,4 +1,8 @@
-use super::{svc::SendRequest, HttpClientService};
+use super::{
+    h2c::{self, H2cMode, H2cUpgrade},
+    svc::SendRequest,
+    HttpClientService,
+};
 use rama_core::{
     error::{BoxError, OpaqueError},
     Context, Layer, Service,
@@ -6,6 +10,7 @@ use rama_core::{
 use rama_http_types::{dep::http_body, Request, Version};
 use rama_net::{
     client::{ConnectorService, EstablishedClientConnection},
+    http::RequestContext,
     stream::Stream,
 };
 
@@ -17,6 +22,11 @@ use tracing::trace;
 use rama_net::tls::{client::NegotiatedTlsParameters, ApplicationProtocol};
 
 /// A [`Service`] which establishes an HTTP Connection.
+///
+/// The http version is based on the application protocol negotiated using tls ALPN,
+/// falling back to the version of the request. For cleartext h2 (`h2c`) connections
+/// a [`H2cMode`] can be added to the [`Context`] to use h2 with prior knowledge,
+/// or to try to upgrade the http/1.1 connection to h2.
 pub struct HttpConnector<S> {
     inner: S,
 }
@@ -65,19 +75,21 @@ where
     ) -> Result<Self::Response, Self::Error> {
         let EstablishedClientConnection {
             ctx,
-            #[cfg(any(feature = "rustls", feature = "boring"))]
             mut req,
-            #[cfg(not(any(feature = "rustls", feature = "boring")))]
-            req,
             conn,
             addr,
         } = self.inner.connect(ctx, req).await.map_err(Into::into)?;
 
+        // h2c modes only apply to connections without a negotiated application protocol
+        #[cfg_attr(not(any(feature = "rustls", feature = "boring")), allow(unused_mut))]
+        let mut h2c_mode = ctx.get::<H2cMode>().copied();
+
         #[cfg(any(feature = "rustls", feature = "boring"))]
         if let Some(proto) = ctx
             .get::<NegotiatedTlsParameters>()
             .and_then(|params| params.application_layer_protocol.as_ref())
         {
+            h2c_mode = None;
             let new_version = match proto {
                 ApplicationProtocol::HTTP_09 => rama_http_types::Version::HTTP_09,
                 ApplicationProtocol::HTTP_10 => rama_http_types::Version::HTTP_10,
@@ -99,7 +111,47 @@ where
             *req.version_mut() = new_version;
         }
 
-        let io = Box::pin(conn);
+        let mut io = Box::pin(conn);
+
+        match h2c_mode {
+            Some(H2cMode::PriorKnowledge) => {
+                trace!(uri = %req.uri(), "h2c: use h2 with prior knowledge");
+                *req.version_mut() = Version::HTTP_2;
+            }
+            Some(H2cMode::Upgrade) => {
+                trace!(uri = %req.uri(), "h2c: try to upgrade connection to h2");
+                let authority = match ctx.get::<RequestContext>() {
+                    Some(request_ctx) => request_ctx.authority.clone(),
+                    None => RequestContext::try_from((&ctx, &req))?.authority,
+                };
+                match h2c::upgrade(io, ctx.executor().clone(), &authority).await? {
+                    H2cUpgrade::Upgraded(sender, conn) => {
+                        ctx.spawn(async move {
+                            if let Err(err) = (*conn).await {
+                                tracing::debug!("connection failed: {:?}", err);
+                            }
+                        });
+
+                        *req.version_mut() = Version::HTTP_2;
+                        let svc = HttpClientService(SendRequest::Http2(sender));
+
+                        return Ok(EstablishedClientConnection {
+                            ctx,
+                            req,
+                            conn: svc,
+                            addr,
+                        });
+                    }
+                    H2cUpgrade::Declined(prev_io) => {
+                        io = prev_io;
+                        if req.version() == Version::HTTP_2 {
+                            *req.version_mut() = Version::HTTP_11;
+                        }
+                    }
+                }
+            }
+            None => (),
+        }
 
         match req.version() {
             Version::HTTP_2 => {
@@ -128,7 +180,7 @@ where
                 let (sender, conn) = rama_http_core::client::conn::http1::handshake(io).await?;
 
                 ctx.spawn(async move {
-                    if let Err(err) = conn.await {
+                    if let Err(err) = conn.with_upgrades().await {
                         tracing::debug!("connection failed: {:?}", err);
                     }
                 });
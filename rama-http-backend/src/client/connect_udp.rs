@@ -0,0 +1,107 @@
+use crate::masque::{self, UdpProxyTemplate, UdpTunnel};
+use rama_core::{
+    error::{BoxError, ErrorContext, OpaqueError},
+    Context, Service,
+};
+use rama_http_core::upgrade::{self, Upgraded};
+use rama_http_types::{
+    header::USER_AGENT, Body, HeaderValue, Method, Request, Response, StatusCode, Version,
+};
+use rama_net::address::Authority;
+use rama_utils::macros::define_inner_service_accessors;
+use std::fmt;
+
+/// A connector which establishes a [`UdpTunnel`] to a target,
+/// by sending a CONNECT-UDP request ([RFC 9298]) to a proxy using the inner http client,
+/// e.g. the [`HttpClient`].
+///
+/// The request is sent as a http/1.1 upgrade request, which the [`HttpClient`]
+/// translates into an h2 extended CONNECT request in case the connection
+/// to the proxy uses h2.
+///
+/// The [`UdpProxyTemplate`] has to be an absolute URI, pointing to the proxy.
+///
+/// [RFC 9298]: https://datatracker.ietf.org/doc/html/rfc9298
+/// [`HttpClient`]: super::HttpClient
+pub struct ConnectUdpConnector<S> {
+    inner: S,
+    template: UdpProxyTemplate,
+}
+
+impl<S> ConnectUdpConnector<S> {
+    /// Create a new [`ConnectUdpConnector`], which proxies using the given [`UdpProxyTemplate`].
+    pub const fn new(inner: S, template: UdpProxyTemplate) -> Self {
+        Self { inner, template }
+    }
+
+    define_inner_service_accessors!();
+}
+
+impl<S: fmt::Debug> fmt::Debug for ConnectUdpConnector<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ConnectUdpConnector")
+            .field("inner", &self.inner)
+            .field("template", &self.template)
+            .finish()
+    }
+}
+
+impl<S: Clone> Clone for ConnectUdpConnector<S> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            template: self.template.clone(),
+        }
+    }
+}
+
+impl<State, S> Service<State, Authority> for ConnectUdpConnector<S>
+where
+    State: Clone + Send + Sync + 'static,
+    S: Service<State, Request, Response = Response, Error: Into<BoxError>>,
+{
+    type Response = UdpTunnel<Upgraded>;
+    type Error = BoxError;
+
+    async fn serve(
+        &self,
+        ctx: Context<State>,
+        target: Authority,
+    ) -> Result<Self::Response, Self::Error> {
+        let uri = self
+            .template
+            .expand(&target)
+            .context("create connect-udp request uri")?;
+        let mut req = Request::builder()
+            .method(Method::GET)
+            .version(Version::HTTP_11)
+            .uri(uri)
+            .header(
+                USER_AGENT,
+                HeaderValue::from_static(const_format::formatcp!(
+                    "{}/{}",
+                    rama_utils::info::NAME,
+                    rama_utils::info::VERSION,
+                )),
+            )
+            .body(Body::empty())
+            .context("build connect-udp request")?;
+        masque::insert_h1_upgrade_headers(req.headers_mut());
+        masque::insert_capsule_protocol_header(req.headers_mut());
+
+        let resp = self.inner.serve(ctx, req).await.map_err(Into::into)?;
+        if resp.status() != StatusCode::SWITCHING_PROTOCOLS && !resp.status().is_success() {
+            return Err(OpaqueError::from_display(format!(
+                "connect-udp request for {target} refused: status={}",
+                resp.status()
+            ))
+            .into());
+        }
+
+        tracing::trace!(udp_target = %target, "connect-udp tunnel established");
+        let upgraded = upgrade::on(resp)
+            .await
+            .context("upgrade connect-udp response")?;
+        Ok(UdpTunnel::new(upgraded))
+    }
+}
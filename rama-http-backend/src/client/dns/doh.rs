@@ -0,0 +1,553 @@
+use super::{is_dns_message, DNS_MESSAGE};
+use crate::client::{svc::SendRequest, HttpClient, HttpClientService};
+use base64::Engine as _;
+use rama_core::{
+    error::{BoxError, ErrorContext, OpaqueError},
+    Context, Service,
+};
+use rama_dns::{
+    wire::{self, DnsExchange, Message},
+    DnsLookup, DnsResolver, HttpsRecord,
+};
+use rama_http_types::{
+    dep::http_body_util::BodyExt,
+    header::{ACCEPT, CONTENT_TYPE},
+    Body, HeaderValue, Method, Request, Response, Uri, Version,
+};
+use rama_net::{
+    address::{Domain, ProxyAddress},
+    client::EstablishedClientConnection,
+};
+use std::{
+    net::{Ipv4Addr, Ipv6Addr},
+    sync::{Arc, Mutex, PoisonError},
+};
+
+#[cfg(any(feature = "rustls", feature = "boring"))]
+use rama_net::tls::client::ClientConfig;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+/// The http method used by a [`DohResolver`] to send its DNS queries.
+pub enum DohMethod {
+    #[default]
+    /// Send the DNS query base64url-encoded as the `dns` query parameter.
+    ///
+    /// As the message id is always `0`, responses can be cached by http caches.
+    Get,
+    /// Send the DNS query as the request body.
+    Post,
+}
+
+#[derive(Debug, Clone)]
+/// A [`DnsResolver`] which resolves using DNS-over-HTTPS (DoH), as defined in [RFC 8484].
+///
+/// DNS messages are exchanged with the DoH server using an http client,
+/// the rama [`HttpClient`] by default, such that the [`ClientConfig`] of that client
+/// and any (http) proxy configured for this resolver are used to reach the server.
+///
+/// HTTP/2 is used for `https` server uris unless configured otherwise,
+/// as it is the minimum recommended version by the RFC. Using the default
+/// [`HttpClient`], the HTTP/2 connection is kept open and reused for subsequent queries.
+///
+/// [RFC 8484]: https://datatracker.ietf.org/doc/html/rfc8484
+/// [`ClientConfig`]: rama_net::tls::client::ClientConfig
+pub struct DohResolver<S = HttpClient> {
+    client: S,
+    uri: Uri,
+    method: DohMethod,
+    version: Option<Version>,
+    proxy: Option<ProxyAddress>,
+    connection: Option<Arc<DohConnection>>,
+}
+
+#[derive(Debug)]
+/// The HTTP/2 connection shared by the clones of a [`DohResolver`]
+/// which uses the default [`HttpClient`].
+struct DohConnection {
+    client: HttpClient,
+    sender: Mutex<Option<Arc<HttpClientService<Body>>>>,
+}
+
+impl DohConnection {
+    fn new(client: HttpClient) -> Self {
+        Self {
+            client,
+            sender: Mutex::new(None),
+        }
+    }
+
+    fn open_sender(&self) -> Option<Arc<HttpClientService<Body>>> {
+        let mut sender = self.sender.lock().unwrap_or_else(PoisonError::into_inner);
+        if sender
+            .as_ref()
+            .is_some_and(|svc| matches!(&svc.0, SendRequest::Http2(h2) if h2.is_closed()))
+        {
+            *sender = None;
+        }
+        sender.clone()
+    }
+
+    /// Send the request over the open connection,
+    /// or a new connection in case there is none or it failed.
+    async fn send(
+        &self,
+        ctx: Context<()>,
+        request: impl Fn() -> Result<Request, OpaqueError>,
+    ) -> Result<Response, OpaqueError> {
+        // the connection might have been closed by the server in the meantime,
+        // in which case we retry using a new connection
+        if let Some(sender) = self.open_sender() {
+            if let Ok(resp) = sender.serve(ctx.clone(), request()?).await {
+                return Ok(resp);
+            }
+        }
+
+        let EstablishedClientConnection { ctx, req, conn, .. } =
+            self.client.connect_http(ctx, request()?).await?;
+        let sender = Arc::new(conn);
+        // only HTTP/2 connections can be shared by concurrent queries
+        if matches!(sender.0, SendRequest::Http2(_)) {
+            *self.sender.lock().unwrap_or_else(PoisonError::into_inner) = Some(sender.clone());
+        }
+        sender
+            .serve(ctx, req)
+            .await
+            .map_err(OpaqueError::from_boxed)
+    }
+}
+
+impl DohResolver {
+    /// Create a new [`DohResolver`] for the DoH server at the given [`Uri`],
+    /// e.g. `https://1.1.1.1/dns-query`, using the default [`HttpClient`].
+    pub fn new(uri: Uri) -> Self {
+        let client = HttpClient::default();
+        Self {
+            connection: Some(Arc::new(DohConnection::new(client.clone()))),
+            ..Self::new_with_client(uri, client)
+        }
+    }
+
+    /// Create a new [`DohResolver`] for the public DoH server of Cloudflare.
+    pub fn cloudflare() -> Self {
+        Self::new(Uri::from_static("https://1.1.1.1/dns-query"))
+    }
+
+    /// Create a new [`DohResolver`] for the public DoH server of Google.
+    pub fn google() -> Self {
+        Self::new(Uri::from_static("https://8.8.8.8/dns-query"))
+    }
+
+    #[cfg(any(feature = "rustls", feature = "boring"))]
+    /// Set the [`ClientConfig`] used by the [`HttpClient`] to connect to the DoH server.
+    pub fn with_tls_config(mut self, cfg: ClientConfig) -> Self {
+        self.client.set_tls_config(cfg);
+        self.connection = Some(Arc::new(DohConnection::new(self.client.clone())));
+        self
+    }
+
+    #[cfg(any(feature = "rustls", feature = "boring"))]
+    /// Set the [`ClientConfig`] used by the [`HttpClient`] to connect to the DoH server.
+    pub fn set_tls_config(&mut self, cfg: ClientConfig) -> &mut Self {
+        self.client.set_tls_config(cfg);
+        self.connection = Some(Arc::new(DohConnection::new(self.client.clone())));
+        self
+    }
+}
+
+impl<S> DohResolver<S> {
+    /// Create a new [`DohResolver`] for the DoH server at the given [`Uri`],
+    /// using a custom http client, e.g. one emulating a user agent.
+    pub fn new_with_client(uri: Uri, client: S) -> Self {
+        Self {
+            client,
+            uri,
+            method: DohMethod::default(),
+            version: None,
+            proxy: None,
+            connection: None,
+        }
+    }
+
+    /// Set the [`DohMethod`] used to send DNS queries.
+    pub fn with_method(mut self, method: DohMethod) -> Self {
+        self.method = method;
+        self
+    }
+
+    /// Set the [`DohMethod`] used to send DNS queries.
+    pub fn set_method(&mut self, method: DohMethod) -> &mut Self {
+        self.method = method;
+        self
+    }
+
+    /// Set the http [`Version`] used to send DNS queries.
+    ///
+    /// Defaults to HTTP/2 for `https` uris, and HTTP/1.1 otherwise.
+    pub fn with_http_version(mut self, version: Version) -> Self {
+        self.version = Some(version);
+        self.reset_connection();
+        self
+    }
+
+    /// Set the http [`Version`] used to send DNS queries.
+    ///
+    /// Defaults to HTTP/2 for `https` uris, and HTTP/1.1 otherwise.
+    pub fn set_http_version(&mut self, version: Version) -> &mut Self {
+        self.version = Some(version);
+        self.reset_connection();
+        self
+    }
+
+    /// Connect to the DoH server via the given [`ProxyAddress`].
+    pub fn with_proxy(mut self, proxy: ProxyAddress) -> Self {
+        self.proxy = Some(proxy);
+        self.reset_connection();
+        self
+    }
+
+    /// Connect to the DoH server via the given [`ProxyAddress`].
+    pub fn set_proxy(&mut self, proxy: ProxyAddress) -> &mut Self {
+        self.proxy = Some(proxy);
+        self.reset_connection();
+        self
+    }
+
+    /// Returns the [`Uri`] of the DoH server.
+    pub fn uri(&self) -> &Uri {
+        &self.uri
+    }
+
+    /// Stop sharing the connection with clones configured differently.
+    fn reset_connection(&mut self) {
+        if let Some(connection) = &self.connection {
+            self.connection = Some(Arc::new(DohConnection::new(connection.client.clone())));
+        }
+    }
+
+    fn request(&self, query: &Message) -> Result<Request, OpaqueError> {
+        let bytes = wire::encode_message(query)?;
+        let version = self.version.unwrap_or_else(|| {
+            if self.uri.scheme_str() == Some("https") {
+                Version::HTTP_2
+            } else {
+                Version::HTTP_11
+            }
+        });
+        let builder = Request::builder()
+            .version(version)
+            .header(ACCEPT, HeaderValue::from_static(DNS_MESSAGE));
+        let req = match self.method {
+            DohMethod::Get => {
+                let dns = base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(bytes);
+                let uri = match self.uri.query() {
+                    Some(query) => format!("{}?{query}&dns={dns}", self.uri.path()),
+                    None => format!("{}?dns={dns}", self.uri.path()),
+                };
+                let mut parts = self.uri.clone().into_parts();
+                parts.path_and_query = Some(uri.parse().context("DohResolver: create query uri")?);
+                builder
+                    .method(Method::GET)
+                    .uri(Uri::from_parts(parts).context("DohResolver: create query uri")?)
+                    .body(Body::empty())
+            }
+            DohMethod::Post => builder
+                .method(Method::POST)
+                .uri(self.uri.clone())
+                .header(CONTENT_TYPE, HeaderValue::from_static(DNS_MESSAGE))
+                .body(Body::from(bytes)),
+        };
+        req.context("DohResolver: create http request")
+    }
+}
+
+impl<S> DnsExchange for DohResolver<S>
+where
+    S: Service<(), Request, Response = Response, Error: Into<BoxError>>,
+{
+    type Error = OpaqueError;
+
+    async fn exchange(&self, mut query: Message) -> Result<Message, Self::Error> {
+        // a zero message id allows responses to be cached by http caches
+        query.set_id(0);
+
+        let mut ctx = Context::default();
+        if let Some(proxy) = &self.proxy {
+            ctx.insert(proxy.clone());
+        }
+
+        let resp = match &self.connection {
+            Some(connection) => connection.send(ctx, || self.request(&query)).await,
+            None => self
+                .client
+                .serve(ctx, self.request(&query)?)
+                .await
+                .map_err(|err| OpaqueError::from_boxed(err.into())),
+        }
+        .with_context(|| format!("DohResolver: send dns query to {}", self.uri))?;
+
+        if !resp.status().is_success() {
+            return Err(OpaqueError::from_display(format!(
+                "DohResolver: unexpected http status: {}",
+                resp.status()
+            )));
+        }
+        if resp
+            .headers()
+            .get(CONTENT_TYPE)
+            .is_some_and(|content_type| !is_dns_message(content_type))
+        {
+            return Err(OpaqueError::from_display(
+                "DohResolver: unexpected content type of response",
+            ));
+        }
+
+        let bytes = resp
+            .into_body()
+            .limited(wire::MAX_MESSAGE_SIZE)
+            .collect()
+            .await
+            .context("DohResolver: read dns response")?
+            .to_bytes();
+        let response = wire::decode_message(&bytes)?;
+        // as the message id is always zero, the question is all that ties the response to the query
+        if response.queries() != query.queries() {
+            return Err(OpaqueError::from_display(
+                "DohResolver: dns response question does not match query",
+            ));
+        }
+        Ok(response)
+    }
+}
+
+impl<S> DnsResolver for DohResolver<S>
+where
+    S: Service<(), Request, Response = Response, Error: Into<BoxError>>,
+{
+    type Error = OpaqueError;
+
+    async fn ipv4_lookup(&self, domain: Domain) -> Result<Vec<Ipv4Addr>, Self::Error> {
+        Ok(wire::lookup_ipv4(self, domain).await?.records)
+    }
+
+    async fn ipv6_lookup(&self, domain: Domain) -> Result<Vec<Ipv6Addr>, Self::Error> {
+        Ok(wire::lookup_ipv6(self, domain).await?.records)
+    }
+
+    async fn https_lookup(&self, domain: Domain) -> Result<Vec<HttpsRecord>, Self::Error> {
+        Ok(wire::lookup_https(self, domain).await?.records)
+    }
+
+    async fn ipv4_lookup_with_ttl(
+        &self,
+        domain: Domain,
+    ) -> Result<DnsLookup<Ipv4Addr>, Self::Error> {
+        wire::lookup_ipv4(self, domain).await
+    }
+
+    async fn ipv6_lookup_with_ttl(
+        &self,
+        domain: Domain,
+    ) -> Result<DnsLookup<Ipv6Addr>, Self::Error> {
+        wire::lookup_ipv6(self, domain).await
+    }
+
+    async fn https_lookup_with_ttl(
+        &self,
+        domain: Domain,
+    ) -> Result<DnsLookup<HttpsRecord>, Self::Error> {
+        wire::lookup_https(self, domain).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::HttpServer;
+    use rama_core::{rt::Executor, service::service_fn};
+    use rama_dns::wire::{MessageType, Name, RData, Record, RecordType, ResponseCode};
+    use std::{
+        convert::Infallible,
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+    use tokio::net::TcpListener;
+
+    async fn doh_server(req: Request) -> Result<Response, Infallible> {
+        let bytes = match *req.method() {
+            Method::GET => {
+                let dns = req
+                    .uri()
+                    .query()
+                    .unwrap()
+                    .split('&')
+                    .find_map(|kv| kv.strip_prefix("dns="))
+                    .unwrap();
+                base64::engine::general_purpose::URL_SAFE_NO_PAD
+                    .decode(dns)
+                    .unwrap()
+            }
+            Method::POST => {
+                assert_eq!(req.headers()[CONTENT_TYPE], DNS_MESSAGE);
+                req.into_body().collect().await.unwrap().to_bytes().to_vec()
+            }
+            _ => unreachable!(),
+        };
+        let query = wire::decode_message(&bytes).unwrap();
+        assert_eq!(query.id(), 0);
+
+        let mut response = Message::new();
+        response
+            .set_id(query.id())
+            .set_message_type(MessageType::Response)
+            .set_response_code(ResponseCode::NoError)
+            .add_queries(query.queries().to_vec());
+        let q = &query.queries()[0];
+        if q.query_type() == RecordType::A {
+            response.add_answer(Record::from_rdata(
+                q.name().clone(),
+                42,
+                RData::A(Ipv4Addr::new(127, 0, 0, 1).into()),
+            ));
+        }
+
+        Ok(Response::builder()
+            .header(CONTENT_TYPE, DNS_MESSAGE)
+            .body(Body::from(wire::encode_message(&response).unwrap()))
+            .unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_doh_resolver() {
+        for method in [DohMethod::Get, DohMethod::Post] {
+            let resolver = DohResolver::new_with_client(
+                Uri::from_static("https://dns.example/dns-query?ct"),
+                service_fn(doh_server),
+            )
+            .with_method(method);
+
+            let lookup = resolver
+                .ipv4_lookup_with_ttl(Domain::from_static("example.com"))
+                .await
+                .unwrap();
+            assert_eq!(lookup.records, vec![Ipv4Addr::new(127, 0, 0, 1)]);
+            assert_eq!(lookup.ttl, Some(std::time::Duration::from_secs(42)));
+
+            assert!(resolver
+                .ipv6_lookup(Domain::from_static("example.com"))
+                .await
+                .is_err());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_doh_resolver_reuses_h2_connection() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let connections = Arc::new(AtomicUsize::new(0));
+        let server = HttpServer::auto(Executor::default()).service(service_fn(doh_server));
+        tokio::spawn({
+            let connections = connections.clone();
+            async move {
+                loop {
+                    let (stream, _) = listener.accept().await.unwrap();
+                    connections.fetch_add(1, Ordering::SeqCst);
+                    let server = server.clone();
+                    tokio::spawn(async move {
+                        let _ = server.serve(Context::<()>::default(), stream).await;
+                    });
+                }
+            }
+        });
+
+        let resolver = DohResolver::new(format!("http://{addr}/dns-query").parse().unwrap())
+            .with_http_version(Version::HTTP_2);
+        for _ in 0..3 {
+            let lookup = resolver
+                .clone()
+                .ipv4_lookup(Domain::from_static("example.com"))
+                .await
+                .unwrap();
+            assert_eq!(lookup, vec![Ipv4Addr::new(127, 0, 0, 1)]);
+        }
+        assert_eq!(connections.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_doh_resolver_response_checks() {
+        // media type parameters are allowed
+        let resolver = DohResolver::new_with_client(
+            Uri::from_static("https://dns.example/dns-query"),
+            service_fn(|req: Request| async move {
+                let mut resp = doh_server(req).await?;
+                resp.headers_mut().insert(
+                    CONTENT_TYPE,
+                    HeaderValue::from_static("application/dns-message; charset=binary"),
+                );
+                Ok::<_, Infallible>(resp)
+            }),
+        );
+        assert!(resolver
+            .ipv4_lookup(Domain::from_static("example.com"))
+            .await
+            .is_ok());
+
+        for content_type in ["application/dns-json", "text/plain"] {
+            let resolver = DohResolver::new_with_client(
+                Uri::from_static("https://dns.example/dns-query"),
+                service_fn(move |req: Request| async move {
+                    let mut resp = doh_server(req).await?;
+                    resp.headers_mut()
+                        .insert(CONTENT_TYPE, HeaderValue::from_static(content_type));
+                    Ok::<_, Infallible>(resp)
+                }),
+            );
+            assert!(resolver
+                .ipv4_lookup(Domain::from_static("example.com"))
+                .await
+                .is_err());
+        }
+
+        // the response must answer the question of the query
+        let resolver = DohResolver::new_with_client(
+            Uri::from_static("https://dns.example/dns-query"),
+            service_fn(|req: Request| async move {
+                let resp = doh_server(req).await?;
+                let bytes = resp.into_body().collect().await.unwrap().to_bytes();
+                let mut response = wire::decode_message(&bytes).unwrap();
+                let mut query = response.take_queries().remove(0);
+                query.set_name(Name::from_ascii("example.org.").unwrap());
+                response.add_query(query);
+                Ok::<_, Infallible>(
+                    Response::builder()
+                        .header(CONTENT_TYPE, DNS_MESSAGE)
+                        .body(Body::from(wire::encode_message(&response).unwrap()))
+                        .unwrap(),
+                )
+            }),
+        );
+        assert!(resolver
+            .ipv4_lookup(Domain::from_static("example.com"))
+            .await
+            .is_err());
+    }
+
+    #[test]
+    fn test_doh_request() {
+        let resolver = DohResolver::new(Uri::from_static("https://1.1.1.1/dns-query"));
+        let query =
+            wire::query_message(0, Domain::from_static("example.com"), RecordType::A).unwrap();
+        let req = resolver.request(&query).unwrap();
+        assert_eq!(req.method(), Method::GET);
+        assert_eq!(req.version(), Version::HTTP_2);
+        assert_eq!(req.headers()[ACCEPT], DNS_MESSAGE);
+        assert_eq!(req.uri().host(), Some("1.1.1.1"));
+        assert_eq!(req.uri().path(), "/dns-query");
+        assert!(req.uri().query().unwrap().starts_with("dns="));
+
+        let resolver = DohResolver::new(Uri::from_static("http://127.0.0.1:8053/dns-query"))
+            .with_method(DohMethod::Post);
+        let req = resolver.request(&query).unwrap();
+        assert_eq!(req.method(), Method::POST);
+        assert_eq!(req.version(), Version::HTTP_11);
+        assert_eq!(req.uri().query(), None);
+    }
+}
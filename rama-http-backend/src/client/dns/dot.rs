@@ -0,0 +1,351 @@
+use crate::client::proxy::layer::HttpProxyConnector;
+use rama_core::{
+    error::{ErrorContext, OpaqueError},
+    Context,
+};
+use rama_dns::{
+    wire::{self, DnsExchange, Message},
+    DnsLookup, DnsResolver, HttpsRecord,
+};
+use rama_net::{
+    address::{Authority, Domain, Host, ProxyAddress},
+    client::{ConnectorService, EstablishedClientConnection},
+    stream::Stream,
+    tls::client::{ClientConfig, ClientHelloExtension},
+};
+use rama_tcp::client::{service::TcpConnector, Request as TcpRequest};
+use rama_tls::std::client::{TlsConnector, TlsConnectorData};
+use std::{
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    sync::{
+        atomic::{AtomicU16, Ordering},
+        Arc, Mutex, PoisonError,
+    },
+    time::Duration,
+};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+/// Maximum amount of idle connections kept open per [`DotResolver`].
+const MAX_IDLE_CONNECTIONS: usize = 4;
+
+type DotConnection = Box<dyn Stream + Unpin>;
+
+#[derive(Debug, Clone)]
+/// A [`DnsResolver`] which resolves using DNS-over-TLS (DoT), as defined in [RFC 7858].
+///
+/// DNS messages are exchanged over TLS connections established using rama's
+/// own TLS connector, configured using a [`ClientConfig`], optionally via an http proxy.
+/// Connections are kept open and reused for subsequent queries.
+///
+/// [RFC 7858]: https://datatracker.ietf.org/doc/html/rfc7858
+pub struct DotResolver {
+    authority: Authority,
+    tls_config: Option<ClientConfig>,
+    server_name: Option<Domain>,
+    proxy: Option<ProxyAddress>,
+    timeout: Duration,
+    next_id: Arc<AtomicU16>,
+    idle: Arc<Mutex<Vec<IdleConnection>>>,
+}
+
+struct IdleConnection(DotConnection);
+
+impl std::fmt::Debug for IdleConnection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("IdleConnection").finish()
+    }
+}
+
+impl DotResolver {
+    /// Create a new [`DotResolver`] for the DoT server at the given [`Authority`],
+    /// e.g. `1.1.1.1:853`.
+    pub fn new(authority: Authority) -> Self {
+        Self {
+            authority,
+            tls_config: None,
+            server_name: None,
+            proxy: None,
+            timeout: Duration::from_secs(5),
+            next_id: Arc::new(AtomicU16::new(rand::random())),
+            idle: Default::default(),
+        }
+    }
+
+    /// Create a new [`DotResolver`] for the public DoT server of Cloudflare.
+    pub fn cloudflare() -> Self {
+        Self::new(Authority::new(
+            Host::Address(IpAddr::V4(Ipv4Addr::new(1, 1, 1, 1))),
+            853,
+        ))
+        .with_server_name(Domain::from_static("cloudflare-dns.com"))
+    }
+
+    /// Create a new [`DotResolver`] for the public DoT server of Google.
+    pub fn google() -> Self {
+        Self::new(Authority::new(
+            Host::Address(IpAddr::V4(Ipv4Addr::new(8, 8, 8, 8))),
+            853,
+        ))
+        .with_server_name(Domain::from_static("dns.google"))
+    }
+
+    /// Set the [`ClientConfig`] used to establish the TLS connections to the DoT server.
+    pub fn with_tls_config(mut self, cfg: ClientConfig) -> Self {
+        self.tls_config = Some(cfg);
+        self
+    }
+
+    /// Set the [`ClientConfig`] used to establish the TLS connections to the DoT server.
+    pub fn set_tls_config(&mut self, cfg: ClientConfig) -> &mut Self {
+        self.tls_config = Some(cfg);
+        self
+    }
+
+    /// Set the name of the DoT server used for SNI and to verify its certificate,
+    /// in case it is different from the host of its [`Authority`], e.g. an IP address.
+    pub fn with_server_name(mut self, name: Domain) -> Self {
+        self.server_name = Some(name);
+        self
+    }
+
+    /// Set the name of the DoT server used for SNI and to verify its certificate,
+    /// in case it is different from the host of its [`Authority`], e.g. an IP address.
+    pub fn set_server_name(&mut self, name: Domain) -> &mut Self {
+        self.server_name = Some(name);
+        self
+    }
+
+    /// Connect to the DoT server via the given (http) [`ProxyAddress`].
+    pub fn with_proxy(mut self, proxy: ProxyAddress) -> Self {
+        self.proxy = Some(proxy);
+        self
+    }
+
+    /// Connect to the DoT server via the given (http) [`ProxyAddress`].
+    pub fn set_proxy(&mut self, proxy: ProxyAddress) -> &mut Self {
+        self.proxy = Some(proxy);
+        self
+    }
+
+    /// Set the timeout of a single DNS exchange, including connection setup.
+    ///
+    /// Defaults to 5 seconds.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Set the timeout of a single DNS exchange, including connection setup.
+    ///
+    /// Defaults to 5 seconds.
+    pub fn set_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Returns the [`Authority`] of the DoT server.
+    pub fn authority(&self) -> &Authority {
+        &self.authority
+    }
+
+    fn tls_connector_data(&self) -> Result<TlsConnectorData, OpaqueError> {
+        let mut config = self.tls_config.clone().unwrap_or_default();
+        if let Some(name) = &self.server_name {
+            config
+                .extensions
+                .get_or_insert_with(Vec::new)
+                .push(ClientHelloExtension::ServerName(Some(Host::Name(
+                    name.clone(),
+                ))));
+        }
+        config
+            .try_into()
+            .context("DotResolver: create tls connector data from tls config")
+    }
+
+    async fn connect(&self) -> Result<DotConnection, OpaqueError> {
+        let mut ctx = Context::default();
+        if let Some(proxy) = &self.proxy {
+            ctx.insert(proxy.clone());
+        }
+
+        let connector = TlsConnector::secure(HttpProxyConnector::optional(TcpConnector::new()))
+            .with_connector_data(self.tls_connector_data()?);
+        let EstablishedClientConnection { conn, .. } = connector
+            .connect(ctx, TcpRequest::new(self.authority.clone()))
+            .await
+            .map_err(OpaqueError::from_boxed)
+            .with_context(|| format!("DotResolver: connect to {}", self.authority))?;
+        Ok(Box::new(conn))
+    }
+
+    fn take_idle_connection(&self) -> Option<DotConnection> {
+        self.idle
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .pop()
+            .map(|IdleConnection(conn)| conn)
+    }
+
+    fn release_connection(&self, conn: DotConnection) {
+        let mut idle = self.idle.lock().unwrap_or_else(PoisonError::into_inner);
+        if idle.len() < MAX_IDLE_CONNECTIONS {
+            idle.push(IdleConnection(conn));
+        }
+    }
+
+    async fn exchange_inner(&self, query: &Message) -> Result<Message, OpaqueError> {
+        let bytes = wire::encode_message(query)?;
+
+        // idle connections might have been closed by the server in the meantime,
+        // in which case we retry using a new connection
+        if let Some(mut conn) = self.take_idle_connection() {
+            if let Ok(response) = exchange_on(&mut conn, &bytes, query.id()).await {
+                self.release_connection(conn);
+                return Ok(response);
+            }
+        }
+
+        let mut conn = self.connect().await?;
+        let response = exchange_on(&mut conn, &bytes, query.id()).await?;
+        self.release_connection(conn);
+        Ok(response)
+    }
+}
+
+/// Exchange a single length-prefixed DNS message over the given connection.
+async fn exchange_on(
+    conn: &mut DotConnection,
+    query: &[u8],
+    id: u16,
+) -> Result<Message, OpaqueError> {
+    let len = u16::try_from(query.len()).context("DotResolver: dns query too large")?;
+    let mut buf = Vec::with_capacity(query.len() + 2);
+    buf.extend_from_slice(&len.to_be_bytes());
+    buf.extend_from_slice(query);
+    conn.write_all(&buf)
+        .await
+        .context("DotResolver: write dns query")?;
+    conn.flush().await.context("DotResolver: write dns query")?;
+
+    let len = conn
+        .read_u16()
+        .await
+        .context("DotResolver: read dns response length")?;
+    let mut buf = vec![0; len as usize];
+    conn.read_exact(&mut buf)
+        .await
+        .context("DotResolver: read dns response")?;
+
+    let response = wire::decode_message(&buf)?;
+    if response.id() != id {
+        return Err(OpaqueError::from_display(
+            "DotResolver: dns response id does not match query",
+        ));
+    }
+    Ok(response)
+}
+
+impl DnsExchange for DotResolver {
+    type Error = OpaqueError;
+
+    async fn exchange(&self, mut query: Message) -> Result<Message, Self::Error> {
+        query.set_id(self.next_id.fetch_add(1, Ordering::Relaxed));
+        tokio::time::timeout(self.timeout, self.exchange_inner(&query))
+            .await
+            .context("DotResolver: dns exchange timeout")?
+    }
+}
+
+impl DnsResolver for DotResolver {
+    type Error = OpaqueError;
+
+    async fn ipv4_lookup(&self, domain: Domain) -> Result<Vec<Ipv4Addr>, Self::Error> {
+        Ok(wire::lookup_ipv4(self, domain).await?.records)
+    }
+
+    async fn ipv6_lookup(&self, domain: Domain) -> Result<Vec<Ipv6Addr>, Self::Error> {
+        Ok(wire::lookup_ipv6(self, domain).await?.records)
+    }
+
+    async fn https_lookup(&self, domain: Domain) -> Result<Vec<HttpsRecord>, Self::Error> {
+        Ok(wire::lookup_https(self, domain).await?.records)
+    }
+
+    async fn ipv4_lookup_with_ttl(
+        &self,
+        domain: Domain,
+    ) -> Result<DnsLookup<Ipv4Addr>, Self::Error> {
+        wire::lookup_ipv4(self, domain).await
+    }
+
+    async fn ipv6_lookup_with_ttl(
+        &self,
+        domain: Domain,
+    ) -> Result<DnsLookup<Ipv6Addr>, Self::Error> {
+        wire::lookup_ipv6(self, domain).await
+    }
+
+    async fn https_lookup_with_ttl(
+        &self,
+        domain: Domain,
+    ) -> Result<DnsLookup<HttpsRecord>, Self::Error> {
+        wire::lookup_https(self, domain).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rama_dns::wire::{MessageType, RData, Record, RecordType, ResponseCode};
+
+    #[tokio::test]
+    async fn test_dot_exchange_on() {
+        let (client, mut server) = tokio::io::duplex(1024);
+        let mut conn: DotConnection = Box::new(client);
+
+        let server = tokio::spawn(async move {
+            for _ in 0..2 {
+                let len = server.read_u16().await.unwrap();
+                let mut buf = vec![0; len as usize];
+                server.read_exact(&mut buf).await.unwrap();
+                let query = wire::decode_message(&buf).unwrap();
+
+                let mut response = Message::new();
+                response
+                    .set_id(query.id())
+                    .set_message_type(MessageType::Response)
+                    .set_response_code(ResponseCode::NoError)
+                    .add_queries(query.queries().to_vec())
+                    .add_answer(Record::from_rdata(
+                        query.queries()[0].name().clone(),
+                        60,
+                        RData::A(Ipv4Addr::new(127, 0, 0, 1).into()),
+                    ));
+                let bytes = wire::encode_message(&response).unwrap();
+                server
+                    .write_all(&(bytes.len() as u16).to_be_bytes())
+                    .await
+                    .unwrap();
+                server.write_all(&bytes).await.unwrap();
+            }
+        });
+
+        for id in [1, 2] {
+            let query =
+                wire::query_message(id, Domain::from_static("example.com"), RecordType::A).unwrap();
+            let bytes = wire::encode_message(&query).unwrap();
+            let response = exchange_on(&mut conn, &bytes, id).await.unwrap();
+            assert_eq!(response.id(), id);
+            assert_eq!(response.answers().len(), 1);
+        }
+        server.await.unwrap();
+    }
+
+    #[test]
+    fn test_dot_tls_connector_data() {
+        let resolver = DotResolver::cloudflare();
+        assert_eq!(resolver.authority().port(), 853);
+        resolver.tls_connector_data().unwrap();
+    }
+}
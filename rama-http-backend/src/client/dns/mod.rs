@@ -0,0 +1,60 @@
+//! DNS resolvers which exchange DNS messages using rama's own http client
+//! and TLS connectors, such that they can share the same proxies and TLS configurations.
+//!
+//! - [`DohResolver`]: DNS-over-HTTPS (DoH), as defined in RFC 8484;
+//! - [`DotResolver`]: DNS-over-TLS (DoT), as defined in RFC 7858.
+//!
+//! Wrap them in a [`CachingDns`] to cache their lookups.
+//!
+//! Requires the `dns` feature.
+//!
+//! [`CachingDns`]: rama_dns::CachingDns
+
+use rama_http_types::{dep::mime::Mime, HeaderValue};
+
+mod doh;
+#[doc(inline)]
+pub use doh::{DohMethod, DohResolver};
+
+#[cfg(any(feature = "rustls", feature = "boring"))]
+mod dot;
+#[cfg(any(feature = "rustls", feature = "boring"))]
+#[doc(inline)]
+pub use dot::DotResolver;
+
+/// The media type of DNS messages exchanged over http, as defined in RFC 8484.
+pub(crate) const DNS_MESSAGE: &str = "application/dns-message";
+
+/// Returns `true` if the content type is that of a DNS message,
+/// ignoring any parameters of the media type.
+pub(crate) fn is_dns_message(content_type: &HeaderValue) -> bool {
+    content_type
+        .to_str()
+        .ok()
+        .and_then(|value| value.parse::<Mime>().ok())
+        .is_some_and(|mime| mime.essence_str() == DNS_MESSAGE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_dns_message() {
+        for (value, expected) in [
+            ("application/dns-message", true),
+            ("Application/DNS-Message", true),
+            ("application/dns-message; charset=binary", true),
+            ("application/dns-message;foo=bar", true),
+            ("application/dns-json", false),
+            ("application/octet-stream", false),
+            ("", false),
+        ] {
+            assert_eq!(
+                expected,
+                is_dns_message(&HeaderValue::from_static(value)),
+                "value: {value}"
+            );
+        }
+    }
+}
@@ -0,0 +1,273 @@
+//! Cleartext h2 (`h2c`) support for the [`HttpConnector`].
+//!
+//! [`HttpConnector`]: super::HttpConnector
+
+use bytes::{Buf, Bytes};
+use rama_core::{
+    error::{BoxError, OpaqueError},
+    rt::Executor,
+};
+use rama_http_core::client::conn::{http1, http2};
+use rama_http_types::{
+    dep::{http_body, http_body_util::BodyExt},
+    header::{CONNECTION, HOST, UPGRADE},
+    HeaderValue, Method, Request, StatusCode, Uri,
+};
+use rama_net::address::Authority;
+use std::{
+    future::{poll_fn, Future},
+    io,
+    pin::{pin, Pin},
+    task::{Context, Poll},
+};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// The way the [`HttpConnector`] establishes cleartext h2 (`h2c`) connections,
+/// which can be selected by adding it to the [`Context`] of the request.
+///
+/// It only applies to connections on which no application protocol
+/// was negotiated using tls ALPN, such as plain text (tcp) connections.
+///
+/// Without this extension the version of the request is respected, meaning that
+/// requests with [`Version::HTTP_2`] are sent over h2 with prior knowledge,
+/// while http/1.1 is used for other requests.
+///
+/// [`HttpConnector`]: super::HttpConnector
+/// [`Context`]: rama_core::Context
+/// [`Version::HTTP_2`]: rama_http_types::Version::HTTP_2
+pub enum H2cMode {
+    /// Speak h2 immediately, assuming the server supports it,
+    /// as described in [RFC 9113 §3.3].
+    ///
+    /// This is the mode typically used for (gRPC) services within a trusted network.
+    ///
+    /// [RFC 9113 §3.3]: https://datatracker.ietf.org/doc/html/rfc9113#section-3.3
+    PriorKnowledge,
+    /// Start as http/1.1 and try to upgrade the connection
+    /// using `Upgrade: h2c`, as described in [RFC 7540 §3.2].
+    ///
+    /// The upgrade is attempted using an `OPTIONS *` request, prior to sending
+    /// the actual request. In case the server declines the upgrade,
+    /// the connection continues to be used as a http/1.1 connection.
+    ///
+    /// [RFC 7540 §3.2]: https://datatracker.ietf.org/doc/html/rfc7540#section-3.2
+    Upgrade,
+}
+
+/// Base64url encoded SETTINGS payload sent in the `HTTP2-Settings` header,
+/// disabling server push (`SETTINGS_ENABLE_PUSH = 0`), same as the actual
+/// SETTINGS frame sent by the client after the upgrade.
+const HTTP2_SETTINGS_VALUE: &str = "AAIAAAAA";
+
+/// Outcome of an h2c upgrade attempt.
+pub(super) enum H2cUpgrade<IO, Body>
+where
+    IO: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+    Body: http_body::Body<Data: Send + 'static, Error: Into<BoxError>> + Unpin + Send + 'static,
+{
+    /// The server accepted the upgrade, and the h2 connection
+    /// (not yet driven) is ready to be used.
+    Upgraded(
+        http2::SendRequest<Body>,
+        Box<http2::Connection<RewindIo<IO>, Body>>,
+    ),
+    /// The server declined the upgrade,
+    /// returning the IO such that it can continue as a http/1.1 connection.
+    Declined(IO),
+}
+
+/// Try to upgrade the given (fresh) connection to h2c,
+/// using an `OPTIONS *` request as described in [`H2cMode::Upgrade`].
+pub(super) async fn upgrade<IO, Body>(
+    io: IO,
+    executor: Executor,
+    authority: &Authority,
+) -> Result<H2cUpgrade<IO, Body>, BoxError>
+where
+    IO: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+    Body: http_body::Body<Data: Send + 'static, Error: Into<BoxError>> + Unpin + Send + 'static,
+{
+    let (sender, mut conn) = http1::handshake::<_, rama_http_types::Body>(io).await?;
+
+    let probe = new_probe_request(authority)?;
+    let (parts, body) = probe.into_parts();
+    let upgrade_request = Request::from_parts(parts.clone(), ());
+    let mut probe = Request::from_parts(parts, body);
+    // the http/1.1 request is sent in asterisk-form
+    *probe.uri_mut() = Uri::from_static("*");
+
+    let mut conn_done = false;
+    let resp = drive_h1(&mut conn, &mut conn_done, sender.send_request(probe)).await?;
+    let status = resp.status();
+
+    if status != StatusCode::SWITCHING_PROTOCOLS {
+        tracing::trace!(%status, "h2c upgrade declined by server: continue as http/1.1");
+        drive_h1(&mut conn, &mut conn_done, resp.into_body().collect()).await?;
+        drop(sender);
+        if !conn_done {
+            poll_fn(|cx| conn.poll_without_shutdown(cx)).await?;
+        }
+        let http1::Parts { io, read_buf, .. } = conn.into_parts();
+        if !read_buf.is_empty() {
+            return Err(OpaqueError::from_display(
+                "h2c upgrade: unexpected data received after declined upgrade",
+            )
+            .into());
+        }
+        return Ok(H2cUpgrade::Declined(io));
+    }
+
+    tracing::trace!("h2c upgrade accepted by server: switch to h2");
+    drop(resp);
+    drop(sender);
+    if !conn_done {
+        poll_fn(|cx| conn.poll_without_shutdown(cx)).await?;
+    }
+    let http1::Parts { io, read_buf, .. } = conn.into_parts();
+
+    let (sender, conn, response) = http2::Builder::new(executor.clone())
+        .handshake_h2c_upgraded(RewindIo::new(io, read_buf), upgrade_request)
+        .await?;
+
+    // the response to the upgrade request is of no use to the client
+    executor.spawn_task(async move {
+        match response.await {
+            Ok(resp) => {
+                tracing::trace!(status = %resp.status(), "h2c upgrade: received stream 1 response");
+                let _ = resp.into_body().collect().await;
+            }
+            Err(err) => tracing::debug!(error = %err, "h2c upgrade: stream 1 response failed"),
+        }
+    });
+
+    Ok(H2cUpgrade::Upgraded(sender, Box::new(conn)))
+}
+
+/// Create the `OPTIONS *` request used to upgrade the connection,
+/// with the uri in absolute form, as required for the (never sent) h2 stream 1 request.
+fn new_probe_request(authority: &Authority) -> Result<Request<rama_http_types::Body>, BoxError> {
+    let authority = authority.to_string();
+    let host = HeaderValue::try_from(authority.as_str())?;
+    let uri = Uri::builder()
+        .scheme("http")
+        .authority(authority.as_str())
+        .path_and_query("*")
+        .build()?;
+    let req = Request::builder()
+        .method(Method::OPTIONS)
+        .uri(uri)
+        .header(HOST, host)
+        .header(CONNECTION, "Upgrade, HTTP2-Settings")
+        .header(UPGRADE, "h2c")
+        .header("http2-settings", HTTP2_SETTINGS_VALUE)
+        .body(rama_http_types::Body::empty())?;
+    Ok(req)
+}
+
+/// Drive the given future to completion, while driving the h1 connection
+/// (until done), without shutting down the IO in case the connection is done.
+async fn drive_h1<IO, F, T, E>(
+    conn: &mut http1::Connection<IO, rama_http_types::Body>,
+    conn_done: &mut bool,
+    fut: F,
+) -> Result<T, BoxError>
+where
+    IO: AsyncRead + AsyncWrite + Unpin,
+    F: Future<Output = Result<T, E>>,
+    E: Into<BoxError>,
+{
+    let mut fut = pin!(fut);
+    poll_fn(|cx| {
+        if let Poll::Ready(result) = fut.as_mut().poll(cx) {
+            return Poll::Ready(result.map_err(Into::into));
+        }
+        if !*conn_done {
+            match conn.poll_without_shutdown(cx) {
+                Poll::Ready(Ok(())) => *conn_done = true,
+                Poll::Ready(Err(err)) => return Poll::Ready(Err(err.into())),
+                Poll::Pending => (),
+            }
+        }
+        Poll::Pending
+    })
+    .await
+}
+
+/// IO of a connection upgraded to h2c, replaying the bytes
+/// the h1 connection already read after the upgrade response.
+pub(super) struct RewindIo<IO> {
+    pre: Bytes,
+    io: IO,
+}
+
+impl<IO> RewindIo<IO> {
+    fn new(io: IO, pre: Bytes) -> Self {
+        Self { pre, io }
+    }
+}
+
+impl<IO: AsyncRead + Unpin> AsyncRead for RewindIo<IO> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        if !self.pre.is_empty() {
+            let n = self.pre.len().min(buf.remaining());
+            buf.put_slice(&self.pre[..n]);
+            self.pre.advance(n);
+            return Poll::Ready(Ok(()));
+        }
+        Pin::new(&mut self.io).poll_read(cx, buf)
+    }
+}
+
+impl<IO: AsyncWrite + Unpin> AsyncWrite for RewindIo<IO> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.io).poll_write(cx, buf)
+    }
+
+    fn poll_write_vectored(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.io).poll_write_vectored(cx, bufs)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.io.is_write_vectored()
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.io).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.io).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_new_probe_request() {
+        let req = new_probe_request(&Authority::try_from("example.com:8080").unwrap()).unwrap();
+        assert_eq!(Method::OPTIONS, req.method());
+        assert_eq!(Some("http"), req.uri().scheme_str());
+        assert_eq!("example.com:8080", req.uri().authority().unwrap().as_str());
+        assert_eq!("example.com:8080", req.headers().get(HOST).unwrap());
+        assert_eq!("h2c", req.headers().get(UPGRADE).unwrap());
+        assert_eq!(
+            HTTP2_SETTINGS_VALUE,
+            req.headers().get("http2-settings").unwrap()
+        );
+    }
+}
@@ -26,8 +26,18 @@ pub use svc::HttpClientService;
 mod conn;
 #[doc(inline)]
 pub use conn::{HttpConnector, HttpConnectorLayer};
+
+mod h2c;
+#[doc(inline)]
+pub use h2c::H2cMode;
+
+mod connect_udp;
+#[doc(inline)]
+pub use connect_udp::ConnectUdpConnector;
 use tracing::trace;
 
+#[cfg(feature = "dns")]
+pub mod dns;
 pub mod proxy;
 
 #[derive(Debug, Clone, Default)]
@@ -93,51 +103,94 @@ impl HttpClient {
         self.proxy_tls_config = cfg;
         self
     }
-}
 
-impl<State, Body> Service<State, Request<Body>> for HttpClient
-where
-    State: Clone + Send + Sync + 'static,
-    Body: http_body::Body<Data: Send + 'static, Error: Into<BoxError>> + Unpin + Send + 'static,
-{
-    type Response = Response;
-    type Error = OpaqueError;
+    #[cfg(any(feature = "rustls", feature = "boring"))]
+    fn proxy_tls_connector_data(&self) -> Result<TlsConnectorData, OpaqueError> {
+        match &self.proxy_tls_config {
+            Some(proxy_tls_config) => {
+                trace!("create proxy tls connector using pre-defined rama tls client config");
+                proxy_tls_config
+                    .clone()
+                    .try_into()
+                    .context("HttpClient: create proxy tls connector data from tls config")
+            }
+            None => {
+                trace!("create proxy tls connector using the 'new_http_auto' constructor");
+                TlsConnectorData::new().context(
+                    "HttpClient: create proxy tls connector data with no application presets",
+                )
+            }
+        }
+    }
 
-    async fn serve(
+    /// Establish a transport connection to the target of the given request,
+    /// tunnelled through the [`ProxyChain`] or [`ProxyAddress`] found in the [`Context`], if any.
+    ///
+    /// Used to serve CONNECT requests, which require a raw stream rather than
+    /// an http connection.
+    ///
+    /// [`ProxyAddress`]: rama_net::address::ProxyAddress
+    /// [`ProxyChain`]: rama_net::address::ProxyChain
+    #[cfg(feature = "proxy")]
+    pub(crate) async fn connect_transport<State, Body>(
         &self,
         ctx: Context<State>,
         req: Request<Body>,
-    ) -> Result<Self::Response, Self::Error> {
-        let uri = req.uri().clone();
+    ) -> Result<
+        EstablishedClientConnection<impl rama_net::stream::Stream + Unpin, State, Request<Body>>,
+        OpaqueError,
+    >
+    where
+        State: Clone + Send + Sync + 'static,
+        Body: Send + 'static,
+    {
+        let tcp_connector = TcpConnector::new();
 
-        // record original req version,
-        // so we can put the response back
-        let original_req_version = req.version();
+        #[cfg(any(feature = "rustls", feature = "boring"))]
+        let connector = {
+            let proxy_tls_connector_data = self.proxy_tls_connector_data()?;
+            HttpProxyConnector::optional(
+                TlsConnector::tunnel(tcp_connector, None)
+                    .with_connector_data(proxy_tls_connector_data.clone()),
+            )
+            .with_hop_tls_connector_data(proxy_tls_connector_data)
+        };
+        #[cfg(not(any(feature = "rustls", feature = "boring")))]
+        let connector = HttpProxyConnector::optional(tcp_connector);
+
+        connector
+            .connect(ctx, req)
+            .await
+            .map_err(OpaqueError::from_boxed)
+    }
+
+    /// Establish the http connection used to send the given request,
+    /// such that it can be reused for other requests to the same server.
+    pub(crate) async fn connect_http<State, Body>(
+        &self,
+        ctx: Context<State>,
+        req: Request<Body>,
+    ) -> Result<
+        EstablishedClientConnection<HttpClientService<Body>, State, Request<Body>>,
+        OpaqueError,
+    >
+    where
+        State: Clone + Send + Sync + 'static,
+        Body: http_body::Body<Data: Send + 'static, Error: Into<BoxError>> + Unpin + Send + 'static,
+    {
+        let uri = req.uri().clone();
 
         let tcp_connector = TcpConnector::new();
 
         #[cfg(any(feature = "rustls", feature = "boring"))]
         let connector = {
-            let proxy_tls_connector_data = match &self.proxy_tls_config {
-                Some(proxy_tls_config) => {
-                    trace!("create proxy tls connector using pre-defined rama tls client config");
-                    proxy_tls_config
-                        .clone()
-                        .try_into()
-                        .context("HttpClient: create proxy tls connector data from tls config")?
-                }
-                None => {
-                    trace!("create proxy tls connector using the 'new_http_auto' constructor");
-                    TlsConnectorData::new().context(
-                        "HttpClient: create proxy tls connector data with no application presets",
-                    )?
-                }
-            };
+            let proxy_tls_connector_data = self.proxy_tls_connector_data()?;
 
             let transport_connector = HttpProxyConnector::optional(
                 TlsConnector::tunnel(tcp_connector, None)
-                    .with_connector_data(proxy_tls_connector_data),
-            );
+                    .with_connector_data(proxy_tls_connector_data.clone()),
+            )
+            .with_hop_tls_connector_data(proxy_tls_connector_data);
             let tls_connector_data = match &self.tls_config {
                 Some(tls_config) => {
                     trace!("create tls connector using pre-defined rama tls client config");
@@ -159,15 +212,39 @@ where
         #[cfg(not(any(feature = "rustls", feature = "boring")))]
         let connector = HttpConnector::new(HttpProxyConnector::optional(tcp_connector));
 
+        connector
+            .connect(ctx, req)
+            .await
+            .map_err(|err| OpaqueError::from_boxed(err).with_context(|| uri.to_string()))
+    }
+}
+
+impl<State, Body> Service<State, Request<Body>> for HttpClient
+where
+    State: Clone + Send + Sync + 'static,
+    Body: http_body::Body<Data: Send + 'static, Error: Into<BoxError>> + Unpin + Send + 'static,
+{
+    type Response = Response;
+    type Error = OpaqueError;
+
+    async fn serve(
+        &self,
+        ctx: Context<State>,
+        req: Request<Body>,
+    ) -> Result<Self::Response, Self::Error> {
+        let uri = req.uri().clone();
+
+        // record original req version,
+        // so we can put the response back
+        let original_req_version = req.version();
+
         // NOTE: stack might change request version based on connector data,
         // such as ALPN (tls), as such it is important to reset it back below,
         // so that the other end can read it... This might however give issues in
         // case switching http versions requires more work than version. If so,
         // your first place will be to check here and/or in the [`HttpConnector`].
-        let EstablishedClientConnection { ctx, req, conn, .. } = connector
-            .connect(ctx, req)
-            .await
-            .map_err(|err| OpaqueError::from_boxed(err).with_context(|| uri.to_string()))?;
+        let EstablishedClientConnection { ctx, req, conn, .. } =
+            self.connect_http(ctx, req).await?;
 
         trace!(uri = %uri, "send http req to connector stack");
         let mut resp = conn.serve(ctx, req).await.map_err(|err| {
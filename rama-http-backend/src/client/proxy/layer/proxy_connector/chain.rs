@@ -0,0 +1,288 @@
+//! Utilities to tunnel through a [`ProxyChain`], one hop at a time.
+//!
+//! [`ProxyChain`]: rama_net::address::ProxyChain
+
+use super::InnerHttpProxyConnector;
+use bytes::Bytes;
+use rama_core::{
+    error::{BoxError, ErrorExt, OpaqueError},
+    Context,
+};
+use rama_http_core::upgrade;
+use rama_net::{
+    address::{Authority, Domain, Host, ProxyAddress},
+    stream::Stream,
+    user::ProxyCredential,
+};
+use rama_socks5::Socks5Client;
+use std::{fmt, net::IpAddr};
+
+#[cfg(feature = "dns")]
+use rama_dns::{DnsOverwrite, DnsResolver, HickoryDns};
+#[cfg(feature = "dns")]
+use std::{future::Future, pin::Pin, sync::Arc};
+
+#[cfg(any(feature = "rustls", feature = "boring"))]
+use rama_core::Service;
+#[cfg(any(feature = "rustls", feature = "boring"))]
+use rama_net::client::EstablishedClientConnection;
+#[cfg(any(feature = "rustls", feature = "boring"))]
+use std::{
+    net::SocketAddr,
+    sync::{Mutex, PoisonError},
+};
+
+/// Returns `true` if the given hop can be tunnelled through using http CONNECT.
+pub(super) fn is_http_hop(hop: &ProxyAddress) -> bool {
+    hop.protocol.as_ref().map(|p| p.is_http()).unwrap_or(true)
+}
+
+/// Returns `true` if the given hop can be tunnelled through using SOCKS5.
+pub(super) fn is_socks5_hop(hop: &ProxyAddress) -> bool {
+    hop.protocol
+        .as_ref()
+        .is_some_and(|p| p.is_socks5() || p.is_socks5h())
+}
+
+/// Returns `true` if the given hop has to be connected to over tls.
+pub(super) fn is_secure_hop(hop: &ProxyAddress) -> bool {
+    hop.protocol
+        .as_ref()
+        .map(|p| p.is_secure())
+        .unwrap_or_default()
+}
+
+/// Tunnel through the given hop to the given authority,
+/// which is either the next hop or the final target.
+pub(super) async fn tunnel<State, S>(
+    ctx: &Context<State>,
+    resolver: &HopResolver,
+    stream: S,
+    hop: &ProxyAddress,
+    authority: &Authority,
+) -> Result<upgrade::Upgraded, BoxError>
+where
+    State: Clone + Send + Sync + 'static,
+    S: Stream + Unpin,
+{
+    if is_socks5_hop(hop) {
+        return socks5_tunnel(ctx, resolver, stream, hop, authority)
+            .await
+            .map(|stream| upgrade::Upgraded::new(stream, Bytes::new()))
+            .map_err(|err| {
+                OpaqueError::from_boxed(err)
+                    .context(format!(
+                        "socks5 proxy chain handshake via {} to {}",
+                        hop.authority, authority
+                    ))
+                    .into_boxed()
+            });
+    }
+
+    let mut connector = InnerHttpProxyConnector::new(authority.clone())?;
+    if let Some(credential) = hop.credential.clone() {
+        connector.with_credential(credential);
+    }
+    connector.handshake(stream).await.map_err(|err| {
+        OpaqueError::from_std(err)
+            .context(format!(
+                "http proxy chain handshake via {} to {}",
+                hop.authority, authority
+            ))
+            .into_boxed()
+    })
+}
+
+/// Establish a tunnel to the given authority using a [`Socks5Client`],
+/// authorized using the username/password of the hop, if any.
+///
+/// Domains are resolved by the client for `socks5` hops,
+/// while they are resolved by the proxy for `socks5h` hops.
+async fn socks5_tunnel<State, S>(
+    ctx: &Context<State>,
+    resolver: &HopResolver,
+    mut stream: S,
+    hop: &ProxyAddress,
+    authority: &Authority,
+) -> Result<S, BoxError>
+where
+    State: Clone + Send + Sync + 'static,
+    S: Stream + Unpin,
+{
+    let mut client = Socks5Client::new();
+    match &hop.credential {
+        None => (),
+        Some(ProxyCredential::Basic(basic)) => {
+            client.set_auth(basic.clone());
+        }
+        Some(ProxyCredential::Bearer(_)) => {
+            return Err("bearer credentials are not supported by socks5".into());
+        }
+    }
+
+    let remote_dns = hop.protocol.as_ref().is_some_and(|p| p.is_socks5h());
+    let target = match authority.host() {
+        Host::Name(domain) if !remote_dns => Authority::new(
+            resolver.resolve(ctx, domain.clone()).await?.into(),
+            authority.port(),
+        ),
+        _ => authority.clone(),
+    };
+
+    client.handshake(&mut stream, &target).await?;
+    Ok(stream)
+}
+
+#[derive(Clone)]
+/// The resolver used to resolve the target of `socks5` hops,
+/// for which domains have to be resolved by the client.
+pub(super) struct HopResolver {
+    #[cfg(feature = "dns")]
+    dns: Arc<dyn DynDnsResolver>,
+}
+
+impl HopResolver {
+    #[cfg(feature = "dns")]
+    pub(super) fn new<Dns>(dns: Dns) -> Self
+    where
+        Dns: DnsResolver,
+    {
+        Self { dns: Arc::new(dns) }
+    }
+
+    /// Resolve the given domain for a `socks5` hop,
+    /// preferring the [`DnsOverwrite`] found in the [`Context`], if any.
+    #[cfg(feature = "dns")]
+    async fn resolve<State>(&self, ctx: &Context<State>, domain: Domain) -> Result<IpAddr, BoxError>
+    where
+        State: Clone + Send + Sync + 'static,
+    {
+        if let Some(ip) = match ctx.get::<DnsOverwrite>() {
+            Some(dns_overwrite) => lookup(&**dns_overwrite, domain.clone()).await,
+            None => None,
+        } {
+            return Ok(ip);
+        }
+        self.dns
+            .lookup_ip(domain.clone())
+            .await
+            .ok_or_else(|| format!("failed to resolve {domain} for socks5 hop").into())
+    }
+
+    /// Resolving domains for `socks5` hops requires the `dns` feature,
+    /// `socks5h` hops can be used instead to let the proxy resolve them.
+    #[cfg(not(feature = "dns"))]
+    async fn resolve<State>(&self, ctx: &Context<State>, domain: Domain) -> Result<IpAddr, BoxError>
+    where
+        State: Clone + Send + Sync + 'static,
+    {
+        let _ = ctx;
+        Err(format!(
+            "dns feature required to resolve {domain} for socks5 hop: use a socks5h hop instead"
+        )
+        .into())
+    }
+}
+
+#[cfg_attr(not(feature = "dns"), allow(clippy::derivable_impls))]
+impl Default for HopResolver {
+    fn default() -> Self {
+        Self {
+            #[cfg(feature = "dns")]
+            dns: Arc::new(HickoryDns::default()),
+        }
+    }
+}
+
+impl fmt::Debug for HopResolver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HopResolver").finish_non_exhaustive()
+    }
+}
+
+#[cfg(feature = "dns")]
+/// Object safe version of [`DnsResolver`], such that the
+/// [`HttpProxyConnector`] does not have to be generic over it.
+///
+/// [`HttpProxyConnector`]: super::HttpProxyConnector
+trait DynDnsResolver: Send + Sync + 'static {
+    fn lookup_ip(
+        &self,
+        domain: Domain,
+    ) -> Pin<Box<dyn Future<Output = Option<IpAddr>> + Send + '_>>;
+}
+
+#[cfg(feature = "dns")]
+impl<Dns: DnsResolver> DynDnsResolver for Dns {
+    fn lookup_ip(
+        &self,
+        domain: Domain,
+    ) -> Pin<Box<dyn Future<Output = Option<IpAddr>> + Send + '_>> {
+        Box::pin(lookup(self, domain))
+    }
+}
+
+#[cfg(feature = "dns")]
+async fn lookup<Dns: DnsResolver>(dns: &Dns, domain: Domain) -> Option<IpAddr> {
+    if let Some(ip) = dns
+        .ipv4_lookup(domain.clone())
+        .await
+        .ok()
+        .and_then(|ips| ips.into_iter().next())
+    {
+        return Some(ip.into());
+    }
+    dns.ipv6_lookup(domain)
+        .await
+        .ok()
+        .and_then(|ips| ips.into_iter().next())
+        .map(Into::into)
+}
+
+#[cfg(any(feature = "rustls", feature = "boring"))]
+/// A connector which returns a connection established through the previous hops,
+/// such that it can be secured by a tls connector for the next hop.
+pub(super) struct EstablishedHop<T> {
+    conn: Mutex<Option<T>>,
+    addr: SocketAddr,
+}
+
+#[cfg(any(feature = "rustls", feature = "boring"))]
+impl<T> EstablishedHop<T> {
+    pub(super) fn new(conn: T, addr: SocketAddr) -> Self {
+        Self {
+            conn: Mutex::new(Some(conn)),
+            addr,
+        }
+    }
+}
+
+#[cfg(any(feature = "rustls", feature = "boring"))]
+impl<T, State, Request> Service<State, Request> for EstablishedHop<T>
+where
+    T: Send + 'static,
+    State: Clone + Send + Sync + 'static,
+    Request: Send + 'static,
+{
+    type Response = EstablishedClientConnection<T, State, Request>;
+    type Error = OpaqueError;
+
+    async fn serve(
+        &self,
+        ctx: Context<State>,
+        req: Request,
+    ) -> Result<Self::Response, Self::Error> {
+        let conn = self
+            .conn
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take()
+            .ok_or_else(|| OpaqueError::from_display("proxy chain hop connection already used"))?;
+        Ok(EstablishedClientConnection {
+            ctx,
+            req,
+            conn,
+            addr: self.addr,
+        })
+    }
+}
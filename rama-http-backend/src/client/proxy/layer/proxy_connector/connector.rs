@@ -6,10 +6,10 @@ use rama_core::error::{ErrorContext, OpaqueError};
 use rama_http_core::{client::conn::http1, upgrade};
 use rama_http_types::{
     header::{HOST, USER_AGENT},
-    headers::{Header, HeaderMapExt},
+    headers::{Header, HeaderMapExt, ProxyAuthorization},
     Body, HeaderName, HeaderValue, Method, Request, StatusCode, Version,
 };
-use rama_net::{address::Authority, stream::Stream};
+use rama_net::{address::Authority, stream::Stream, user::ProxyCredential};
 
 use super::HttpProxyError;
 
@@ -59,6 +59,14 @@ impl InnerHttpProxyConnector {
         self
     }
 
+    /// Add the [`ProxyAuthorization`] header for the given [`ProxyCredential`] to the request.
+    pub(super) fn with_credential(&mut self, credential: ProxyCredential) -> &mut Self {
+        match credential {
+            ProxyCredential::Basic(basic) => self.with_typed_header(ProxyAuthorization(basic)),
+            ProxyCredential::Bearer(bearer) => self.with_typed_header(ProxyAuthorization(bearer)),
+        }
+    }
+
     /// Connect to the proxy server.
     pub(super) async fn handshake<S: Stream + Unpin>(
         self,
@@ -2,6 +2,8 @@ mod connector;
 // internal usage only
 use connector::InnerHttpProxyConnector;
 
+mod chain;
+
 mod proxy_error;
 #[doc(inline)]
 pub use proxy_error::HttpProxyError;
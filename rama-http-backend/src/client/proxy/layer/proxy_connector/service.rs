@@ -1,19 +1,17 @@
 use crate::client::proxy::layer::HttpProxyError;
 
-use super::InnerHttpProxyConnector;
+use super::{chain, InnerHttpProxyConnector};
 use rama_core::{
     combinators::Either,
     error::{BoxError, ErrorExt, OpaqueError},
     Context, Service,
 };
 use rama_http_core::upgrade;
-use rama_http_types::headers::ProxyAuthorization;
 use rama_net::{
-    address::ProxyAddress,
+    address::{ProxyAddress, ProxyChain},
     client::{ConnectorService, EstablishedClientConnection},
     stream::Stream,
-    transport::TryRefIntoTransportContext,
-    user::ProxyCredential,
+    transport::{TransportContext, TryRefIntoTransportContext},
 };
 use rama_utils::macros::define_inner_service_accessors;
 use std::fmt;
@@ -21,21 +19,59 @@ use std::fmt;
 #[cfg(feature = "tls")]
 use rama_net::tls::TlsTunnel;
 
+#[cfg(any(feature = "rustls", feature = "boring"))]
+use rama_net::address::Authority;
+#[cfg(any(feature = "rustls", feature = "boring"))]
+use rama_tls::std::client::{TlsConnector, TlsConnectorData};
+#[cfg(any(feature = "rustls", feature = "boring"))]
+use std::collections::HashMap;
+
+#[cfg(feature = "dns")]
+use rama_dns::DnsResolver;
+
 /// A connector which can be used to establish a connection over an HTTP Proxy.
 ///
 /// This behaviour is optional and only triggered in case there
 /// is a [`ProxyAddress`] found in the [`Context`].
+///
+/// In case a non-empty [`ProxyChain`] is found in the [`Context`] instead,
+/// the connection is established through each of its hops in turn:
+/// the first hop is connected to via the inner connector, while every next hop,
+/// and finally the target itself, is tunnelled through the previous hop using
+/// an http CONNECT request, or a SOCKS5 CONNECT command for `socks5(h)` hops,
+/// authorized with the credentials of that previous hop.
+/// Secure (https) hops beyond the first are connected to over tls,
+/// using the tls connector data defined for the authority of that hop,
+/// or the hop tls connector data shared by all hops otherwise, if defined.
+///
+/// Targets of `socks5` hops are resolved by the client, using the [`DnsOverwrite`]
+/// found in the [`Context`], or the resolver of this connector otherwise,
+/// which requires the `dns` feature. `socks5h` hops resolve targets themselves.
+///
+/// [`DnsOverwrite`]: https://docs.rs/rama-dns/latest/rama_dns/struct.DnsOverwrite.html
 pub struct HttpProxyConnector<S> {
     inner: S,
     required: bool,
+    hop_resolver: chain::HopResolver,
+    #[cfg(any(feature = "rustls", feature = "boring"))]
+    hop_tls_connector_data: Option<TlsConnectorData>,
+    #[cfg(any(feature = "rustls", feature = "boring"))]
+    hop_tls_connector_data_by_authority: HashMap<Authority, TlsConnectorData>,
 }
 
 impl<S: fmt::Debug> fmt::Debug for HttpProxyConnector<S> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        f.debug_struct("HttpProxyConnector")
-            .field("inner", &self.inner)
+        let mut f = f.debug_struct("HttpProxyConnector");
+        f.field("inner", &self.inner)
             .field("required", &self.required)
-            .finish()
+            .field("hop_resolver", &self.hop_resolver);
+        #[cfg(any(feature = "rustls", feature = "boring"))]
+        f.field("hop_tls_connector_data", &self.hop_tls_connector_data)
+            .field(
+                "hop_tls_connector_data_by_authority",
+                &self.hop_tls_connector_data_by_authority,
+            );
+        f.finish()
     }
 }
 
@@ -44,6 +80,11 @@ impl<S: Clone> Clone for HttpProxyConnector<S> {
         Self {
             inner: self.inner.clone(),
             required: self.required,
+            hop_resolver: self.hop_resolver.clone(),
+            #[cfg(any(feature = "rustls", feature = "boring"))]
+            hop_tls_connector_data: self.hop_tls_connector_data.clone(),
+            #[cfg(any(feature = "rustls", feature = "boring"))]
+            hop_tls_connector_data_by_authority: self.hop_tls_connector_data_by_authority.clone(),
         }
     }
 }
@@ -51,7 +92,15 @@ impl<S: Clone> Clone for HttpProxyConnector<S> {
 impl<S> HttpProxyConnector<S> {
     /// Creates a new [`HttpProxyConnector`].
     pub(super) fn new(inner: S, required: bool) -> Self {
-        Self { inner, required }
+        Self {
+            inner,
+            required,
+            hop_resolver: chain::HopResolver::default(),
+            #[cfg(any(feature = "rustls", feature = "boring"))]
+            hop_tls_connector_data: None,
+            #[cfg(any(feature = "rustls", feature = "boring"))]
+            hop_tls_connector_data_by_authority: HashMap::new(),
+        }
     }
 
     /// Create a new [`HttpProxyConnector`]
@@ -68,6 +117,74 @@ impl<S> HttpProxyConnector<S> {
         Self::new(inner, true)
     }
 
+    #[cfg(any(feature = "rustls", feature = "boring"))]
+    /// Attach [`TlsConnectorData`] to this [`HttpProxyConnector`],
+    /// used to connect to the secure hops of a [`ProxyChain`], other than the first one.
+    pub fn with_hop_tls_connector_data(mut self, connector_data: TlsConnectorData) -> Self {
+        self.hop_tls_connector_data = Some(connector_data);
+        self
+    }
+
+    #[cfg(any(feature = "rustls", feature = "boring"))]
+    /// Attach [`TlsConnectorData`] to this [`HttpProxyConnector`],
+    /// used to connect to the secure hops of a [`ProxyChain`], other than the first one.
+    pub fn set_hop_tls_connector_data(&mut self, connector_data: TlsConnectorData) -> &mut Self {
+        self.hop_tls_connector_data = Some(connector_data);
+        self
+    }
+
+    #[cfg(any(feature = "rustls", feature = "boring"))]
+    /// Attach [`TlsConnectorData`] to this [`HttpProxyConnector`],
+    /// used to connect to the secure hop of a [`ProxyChain`] with the given [`Authority`],
+    /// other than the first one, instead of the shared hop tls connector data.
+    pub fn with_hop_tls_connector_data_for(
+        mut self,
+        authority: Authority,
+        connector_data: TlsConnectorData,
+    ) -> Self {
+        self.hop_tls_connector_data_by_authority
+            .insert(authority, connector_data);
+        self
+    }
+
+    #[cfg(any(feature = "rustls", feature = "boring"))]
+    /// Attach [`TlsConnectorData`] to this [`HttpProxyConnector`],
+    /// used to connect to the secure hop of a [`ProxyChain`] with the given [`Authority`],
+    /// other than the first one, instead of the shared hop tls connector data.
+    pub fn set_hop_tls_connector_data_for(
+        &mut self,
+        authority: Authority,
+        connector_data: TlsConnectorData,
+    ) -> &mut Self {
+        self.hop_tls_connector_data_by_authority
+            .insert(authority, connector_data);
+        self
+    }
+
+    #[cfg(feature = "dns")]
+    /// Attach a [`DnsResolver`] to this [`HttpProxyConnector`],
+    /// used to resolve the targets of the `socks5` hops of a [`ProxyChain`].
+    ///
+    /// Defaults to [`HickoryDns`], and is best set to the resolver used by the inner connector.
+    ///
+    /// [`HickoryDns`]: rama_dns::HickoryDns
+    pub fn with_dns<Dns: DnsResolver>(mut self, dns: Dns) -> Self {
+        self.hop_resolver = chain::HopResolver::new(dns);
+        self
+    }
+
+    #[cfg(feature = "dns")]
+    /// Attach a [`DnsResolver`] to this [`HttpProxyConnector`],
+    /// used to resolve the targets of the `socks5` hops of a [`ProxyChain`].
+    ///
+    /// Defaults to [`HickoryDns`], and is best set to the resolver used by the inner connector.
+    ///
+    /// [`HickoryDns`]: rama_dns::HickoryDns
+    pub fn set_dns<Dns: DnsResolver>(&mut self, dns: Dns) -> &mut Self {
+        self.hop_resolver = chain::HopResolver::new(dns);
+        self
+    }
+
     define_inner_service_accessors!();
 }
 
@@ -88,8 +205,6 @@ where
         mut ctx: Context<State>,
         req: Request,
     ) -> Result<Self::Response, Self::Error> {
-        let address = ctx.get::<ProxyAddress>().cloned();
-
         let transport_ctx = ctx
             .get_or_try_insert_with_ctx(|ctx| req.try_ref_into_transport_ctx(ctx))
             .map_err(|err| {
@@ -98,17 +213,24 @@ where
             })?
             .clone();
 
+        if let Some(proxy_chain) = ctx
+            .get::<ProxyChain>()
+            .filter(|chain| !chain.is_empty())
+            .cloned()
+        {
+            return self
+                .connect_via_chain(ctx, req, transport_ctx, proxy_chain)
+                .await;
+        }
+
+        let address = ctx.get::<ProxyAddress>().cloned();
+
         // in case the provider gave us a proxy info, we insert it into the context
         if let Some(address) = &address {
             ctx.insert(address.clone());
 
             #[cfg(feature = "tls")]
-            if address
-                .protocol
-                .as_ref()
-                .map(|p| p.is_secure())
-                .unwrap_or_default()
-            {
+            if chain::is_secure_hop(address) {
                 tracing::trace!(
                     authority = %transport_ctx.authority,
                     "http proxy connector: preparing proxy connection for tls tunnel"
@@ -195,14 +317,7 @@ where
         let mut connector = InnerHttpProxyConnector::new(transport_ctx.authority.clone())?;
 
         if let Some(credential) = address.credential.clone() {
-            match credential {
-                ProxyCredential::Basic(basic) => {
-                    connector.with_typed_header(ProxyAuthorization(basic));
-                }
-                ProxyCredential::Bearer(bearer) => {
-                    connector.with_typed_header(ProxyAuthorization(bearer));
-                }
-            }
+            connector.with_credential(credential);
         }
 
         let conn = connector
@@ -223,3 +338,533 @@ where
         })
     }
 }
+
+impl<S> HttpProxyConnector<S> {
+    async fn connect_via_chain<State, Request>(
+        &self,
+        mut ctx: Context<State>,
+        req: Request,
+        transport_ctx: TransportContext,
+        proxy_chain: ProxyChain,
+    ) -> Result<
+        EstablishedClientConnection<Either<S::Connection, upgrade::Upgraded>, State, Request>,
+        BoxError,
+    >
+    where
+        S: ConnectorService<State, Request, Connection: Stream + Unpin, Error: Into<BoxError>>,
+        State: Clone + Send + Sync + 'static,
+        Request: TryRefIntoTransportContext<State, Error: Into<BoxError> + Send + Sync + 'static>
+            + Send
+            + 'static,
+    {
+        if let Some(hop) = proxy_chain
+            .iter()
+            .find(|hop| !chain::is_http_hop(hop) && !chain::is_socks5_hop(hop))
+        {
+            return Err(OpaqueError::from_display(format!(
+                "http proxy connector: unsupported protocol {:?} for hop {} in proxy chain",
+                hop.protocol, hop.authority,
+            ))
+            .into());
+        }
+
+        let hops = &proxy_chain.hops;
+        let first_hop = &hops[0];
+
+        // the inner connector connects to the first hop as if it is the only proxy
+        ctx.insert(first_hop.clone());
+        #[cfg(feature = "tls")]
+        if chain::is_secure_hop(first_hop) {
+            ctx.insert(TlsTunnel {
+                server_host: first_hop.authority.host().clone(),
+            });
+        }
+
+        #[cfg_attr(not(any(feature = "rustls", feature = "boring")), allow(unused_mut))]
+        let EstablishedClientConnection {
+            mut ctx,
+            mut req,
+            conn,
+            addr,
+        } = self.inner.connect(ctx, req).await.map_err(|err| {
+            OpaqueError::from_std(HttpProxyError::Transport(
+                OpaqueError::from_boxed(err.into())
+                    .context(format!(
+                        "establish connection to first proxy {} (protocol: {:?}) of chain {}",
+                        first_hop.authority, first_hop.protocol, proxy_chain,
+                    ))
+                    .into_boxed(),
+            ))
+        })?;
+
+        tracing::trace!(
+            authority = %transport_ctx.authority,
+            proxy_addr = %addr,
+            %proxy_chain,
+            "http proxy connector: connected to first proxy of chain",
+        );
+
+        let next_authority = |index: usize| {
+            hops.get(index + 1)
+                .map(|hop| &hop.authority)
+                .unwrap_or(&transport_ctx.authority)
+        };
+
+        let mut conn =
+            chain::tunnel(&ctx, &self.hop_resolver, conn, first_hop, next_authority(0)).await?;
+
+        for (index, hop) in hops.iter().enumerate().skip(1) {
+            conn = if chain::is_secure_hop(hop) {
+                #[cfg(any(feature = "rustls", feature = "boring"))]
+                {
+                    ctx.insert(TlsTunnel {
+                        server_host: hop.authority.host().clone(),
+                    });
+                    let established_hop =
+                        TlsConnector::tunnel(chain::EstablishedHop::new(conn, addr), None)
+                            .maybe_with_connector_data(
+                                self.hop_tls_connector_data_by_authority
+                                    .get(&hop.authority)
+                                    .or(self.hop_tls_connector_data.as_ref())
+                                    .cloned(),
+                            )
+                            .connect(ctx, req)
+                            .await
+                            .map_err(|err| {
+                                OpaqueError::from_boxed(err).context(format!(
+                                    "establish tls connection to proxy {} of chain {}",
+                                    hop.authority, proxy_chain,
+                                ))
+                            })?;
+                    ctx = established_hop.ctx;
+                    req = established_hop.req;
+                    chain::tunnel(
+                        &ctx,
+                        &self.hop_resolver,
+                        established_hop.conn,
+                        hop,
+                        next_authority(index),
+                    )
+                    .await?
+                }
+                #[cfg(not(any(feature = "rustls", feature = "boring")))]
+                {
+                    return Err(OpaqueError::from_display(format!(
+                        "http proxy connector: tls support required for secure hop {} in proxy chain",
+                        hop.authority,
+                    ))
+                    .into());
+                }
+            } else {
+                chain::tunnel(&ctx, &self.hop_resolver, conn, hop, next_authority(index)).await?
+            };
+        }
+
+        // the target is tunnelled through the entire chain,
+        // such that requests are to be sent to it as if connected directly
+        ctx.remove::<ProxyAddress>();
+        #[cfg(feature = "tls")]
+        ctx.remove::<TlsTunnel>();
+
+        tracing::trace!(
+            authority = %transport_ctx.authority,
+            proxy_addr = %addr,
+            %proxy_chain,
+            "http proxy connector: connected to target via proxy chain",
+        );
+        Ok(EstablishedClientConnection {
+            ctx,
+            req,
+            conn: Either::B(conn),
+            addr,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rama_http_types::{Body, Request};
+    use rama_tcp::client::service::TcpConnector;
+    use std::net::SocketAddr;
+    use tokio::{
+        io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+        net::{TcpListener, TcpStream},
+    };
+
+    async fn spawn_echo_server() -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                tokio::spawn(async move {
+                    let (mut reader, mut writer) = stream.split();
+                    let _ = tokio::io::copy(&mut reader, &mut writer).await;
+                });
+            }
+        });
+        addr
+    }
+
+    async fn spawn_connect_proxy(expected_authorization: Option<&'static str>) -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                tokio::spawn(serve_connect_proxy(stream, expected_authorization));
+            }
+        });
+        addr
+    }
+
+    async fn serve_connect_proxy<IO: Stream + Unpin>(
+        stream: IO,
+        expected_authorization: Option<&'static str>,
+    ) {
+        let mut stream = BufReader::new(stream);
+        let mut request_line = String::new();
+        stream.read_line(&mut request_line).await.unwrap();
+        let target = request_line.split_whitespace().nth(1).unwrap().to_owned();
+
+        let mut authorization = None;
+        loop {
+            let mut line = String::new();
+            stream.read_line(&mut line).await.unwrap();
+            let line = line.trim_end();
+            if line.is_empty() {
+                break;
+            }
+            if let Some((name, value)) = line.split_once(':') {
+                if name.eq_ignore_ascii_case("proxy-authorization") {
+                    authorization = Some(value.trim().to_owned());
+                }
+            }
+        }
+
+        let mut stream = stream.into_inner();
+        if authorization.as_deref() != expected_authorization {
+            let _ = stream
+                .write_all(
+                    b"HTTP/1.1 407 Proxy Authentication Required\r\ncontent-length: 0\r\n\r\n",
+                )
+                .await;
+            return;
+        }
+
+        let mut upstream = TcpStream::connect(target).await.unwrap();
+        stream.write_all(b"HTTP/1.1 200 OK\r\n\r\n").await.unwrap();
+        let _ = tokio::io::copy_bidirectional(&mut stream, &mut upstream).await;
+    }
+
+    #[cfg(any(feature = "rustls", feature = "boring"))]
+    async fn spawn_tls_connect_proxy(alpn: &'static [u8]) -> SocketAddr {
+        use rama_core::service::service_fn;
+        use rama_net::tls::{
+            server::{SelfSignedData, ServerAuth, ServerConfig},
+            ApplicationProtocol,
+        };
+        use rama_tls::std::server::{TlsAcceptorData, TlsAcceptorService};
+
+        let acceptor = TlsAcceptorService::new(
+            TlsAcceptorData::try_from(ServerConfig {
+                application_layer_protocol_negotiation: Some(vec![ApplicationProtocol::from(alpn)]),
+                ..ServerConfig::new(ServerAuth::SelfSigned(SelfSignedData::default()))
+            })
+            .unwrap(),
+            service_fn(serve_tls_connect_proxy),
+            false,
+        );
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                let acceptor = acceptor.clone();
+                tokio::spawn(async move {
+                    let _ = acceptor
+                        .serve(Context::with_state(ApplicationProtocol::from(alpn)), stream)
+                        .await;
+                });
+            }
+        });
+        addr
+    }
+
+    /// Proxy only for clients which negotiated the alpn of the hop, found as state.
+    #[cfg(any(feature = "rustls", feature = "boring"))]
+    async fn serve_tls_connect_proxy<IO: Stream + Unpin>(
+        ctx: Context<rama_net::tls::ApplicationProtocol>,
+        stream: IO,
+    ) -> Result<(), std::convert::Infallible> {
+        use rama_net::tls::client::NegotiatedTlsParameters;
+
+        if ctx
+            .get::<NegotiatedTlsParameters>()
+            .and_then(|params| params.application_layer_protocol.as_ref())
+            == Some(ctx.state())
+        {
+            serve_connect_proxy(stream, None).await;
+        }
+        Ok(())
+    }
+
+    async fn spawn_socks5_proxy(
+        expected_credential: Option<(&'static str, &'static str)>,
+    ) -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                tokio::spawn(async move {
+                    let mut head = [0u8; 2];
+                    stream.read_exact(&mut head).await.unwrap();
+                    assert_eq!(5, head[0]);
+                    let mut methods = vec![0u8; head[1] as usize];
+                    stream.read_exact(&mut methods).await.unwrap();
+
+                    if let Some((username, password)) = expected_credential {
+                        assert!(methods.contains(&2));
+                        stream.write_all(&[5, 2]).await.unwrap();
+                        assert_eq!(1, stream.read_u8().await.unwrap());
+                        let mut user = vec![0u8; stream.read_u8().await.unwrap() as usize];
+                        stream.read_exact(&mut user).await.unwrap();
+                        let mut pass = vec![0u8; stream.read_u8().await.unwrap() as usize];
+                        stream.read_exact(&mut pass).await.unwrap();
+                        if user != username.as_bytes() || pass != password.as_bytes() {
+                            let _ = stream.write_all(&[1, 1]).await;
+                            return;
+                        }
+                        stream.write_all(&[1, 0]).await.unwrap();
+                    } else {
+                        stream.write_all(&[5, 0]).await.unwrap();
+                    }
+
+                    let mut request = [0u8; 4];
+                    stream.read_exact(&mut request).await.unwrap();
+                    assert_eq!([5, 1, 0], request[..3]);
+                    let target = match request[3] {
+                        1 => {
+                            let mut ip = [0u8; 4];
+                            stream.read_exact(&mut ip).await.unwrap();
+                            std::net::Ipv4Addr::from(ip).to_string()
+                        }
+                        3 => {
+                            let mut domain = vec![0u8; stream.read_u8().await.unwrap() as usize];
+                            stream.read_exact(&mut domain).await.unwrap();
+                            String::from_utf8(domain).unwrap()
+                        }
+                        atyp => panic!("unexpected address type {atyp}"),
+                    };
+                    let port = stream.read_u16().await.unwrap();
+
+                    let mut upstream = TcpStream::connect((target, port)).await.unwrap();
+                    stream
+                        .write_all(&[5, 0, 0, 1, 127, 0, 0, 1, 0, 0])
+                        .await
+                        .unwrap();
+                    let _ = tokio::io::copy_bidirectional(&mut stream, &mut upstream).await;
+                });
+            }
+        });
+        addr
+    }
+
+    #[tokio::test]
+    async fn test_http_proxy_connector_proxy_chain() {
+        let target = spawn_echo_server().await;
+        let second_hop = spawn_connect_proxy(None).await;
+        // base64 of john:secret
+        let first_hop = spawn_connect_proxy(Some("Basic am9objpzZWNyZXQ=")).await;
+
+        let connector = HttpProxyConnector::required(TcpConnector::new());
+
+        for (credential, expect_ok) in [("john:secret", true), ("john:oops", false)] {
+            let mut ctx = Context::default();
+            ctx.insert(ProxyChain::new([
+                format!("http://{credential}@{first_hop}").parse().unwrap(),
+                format!("http://{second_hop}").parse().unwrap(),
+            ]));
+            let req = Request::builder()
+                .uri(format!("http://{target}/"))
+                .body(Body::empty())
+                .unwrap();
+
+            let result = connector.connect(ctx, req).await;
+            if !expect_ok {
+                assert!(result.is_err(), "credential: {credential}");
+                continue;
+            }
+
+            let EstablishedClientConnection { ctx, mut conn, .. } = result.unwrap();
+            assert!(!ctx.contains::<ProxyAddress>());
+
+            conn.write_all(b"ping").await.unwrap();
+            let mut buf = [0u8; 4];
+            conn.read_exact(&mut buf).await.unwrap();
+            assert_eq!(b"ping", &buf);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_http_proxy_connector_proxy_chain_socks5_hop() {
+        let target = spawn_echo_server().await;
+        let target_port = target.port();
+        let socks5_hop = spawn_socks5_proxy(Some(("john", "secret"))).await;
+        let http_hop = spawn_connect_proxy(None).await;
+
+        let connector = HttpProxyConnector::required(TcpConnector::new());
+
+        for (second_hop, target, expect_ok) in [
+            (
+                format!("socks5://john:secret@{socks5_hop}"),
+                format!("127.0.0.1:{target_port}"),
+                true,
+            ),
+            (
+                format!("socks5h://john:secret@{socks5_hop}"),
+                format!("localhost:{target_port}"),
+                true,
+            ),
+            (
+                format!("socks5://john:oops@{socks5_hop}"),
+                format!("127.0.0.1:{target_port}"),
+                false,
+            ),
+        ] {
+            let mut ctx = Context::default();
+            ctx.insert(ProxyChain::new([
+                format!("http://{http_hop}").parse().unwrap(),
+                second_hop.parse().unwrap(),
+            ]));
+            let req = Request::builder()
+                .uri(format!("http://{target}/"))
+                .body(Body::empty())
+                .unwrap();
+
+            let result = connector.connect(ctx, req).await;
+            if !expect_ok {
+                assert!(result.is_err(), "hop: {second_hop}");
+                continue;
+            }
+
+            let EstablishedClientConnection { ctx, mut conn, .. } =
+                result.unwrap_or_else(|err| panic!("hop: {second_hop}: {err}"));
+            assert!(!ctx.contains::<ProxyAddress>());
+
+            conn.write_all(b"ping").await.unwrap();
+            let mut buf = [0u8; 4];
+            conn.read_exact(&mut buf).await.unwrap();
+            assert_eq!(b"ping", &buf);
+        }
+    }
+
+    #[cfg(feature = "dns")]
+    #[tokio::test]
+    async fn test_http_proxy_connector_proxy_chain_socks5_hop_dns() {
+        use rama_dns::InMemoryDns;
+        use rama_net::address::Domain;
+
+        let target = spawn_echo_server().await;
+        let socks5_hop = spawn_socks5_proxy(None).await;
+
+        let mut dns = InMemoryDns::new();
+        dns.insert_address(Domain::from_static("echo.internal"), [127, 0, 0, 1]);
+        let connector = HttpProxyConnector::required(TcpConnector::new()).with_dns(dns);
+
+        let mut ctx = Context::default();
+        ctx.insert(ProxyChain::new([format!("socks5://{socks5_hop}")
+            .parse()
+            .unwrap()]));
+        let req = Request::builder()
+            .uri(format!("http://echo.internal:{}/", target.port()))
+            .body(Body::empty())
+            .unwrap();
+
+        let EstablishedClientConnection { mut conn, .. } =
+            connector.connect(ctx, req).await.unwrap();
+        conn.write_all(b"ping").await.unwrap();
+        let mut buf = [0u8; 4];
+        conn.read_exact(&mut buf).await.unwrap();
+        assert_eq!(b"ping", &buf);
+    }
+
+    #[cfg(any(feature = "rustls", feature = "boring"))]
+    #[tokio::test]
+    async fn test_http_proxy_connector_proxy_chain_tls_hops() {
+        use rama_net::tls::{
+            client::{ClientConfig, ClientHelloExtension, ServerVerifyMode},
+            ApplicationProtocol,
+        };
+
+        let target = spawn_echo_server().await;
+        let first_hop = spawn_connect_proxy(None).await;
+        let second_hop = spawn_tls_connect_proxy(b"hop-a").await;
+        let third_hop = spawn_tls_connect_proxy(b"hop-b").await;
+
+        let connector_data = |alpn: &'static [u8]| {
+            TlsConnectorData::try_from(ClientConfig {
+                server_verify_mode: Some(ServerVerifyMode::Disable),
+                extensions: Some(vec![
+                    ClientHelloExtension::ApplicationLayerProtocolNegotiation(vec![
+                        ApplicationProtocol::from(alpn),
+                    ]),
+                ]),
+                ..Default::default()
+            })
+            .unwrap()
+        };
+
+        let shared_connector = HttpProxyConnector::required(TcpConnector::new())
+            .with_hop_tls_connector_data(connector_data(b"hop-a"));
+        let per_hop_connector = shared_connector
+            .clone()
+            .with_hop_tls_connector_data_for(Authority::from(third_hop), connector_data(b"hop-b"));
+
+        for (connector, expect_ok) in [(shared_connector, false), (per_hop_connector, true)] {
+            let mut ctx = Context::default();
+            ctx.insert(ProxyChain::new([
+                format!("http://{first_hop}").parse().unwrap(),
+                format!("https://{second_hop}").parse().unwrap(),
+                format!("https://{third_hop}").parse().unwrap(),
+            ]));
+            let req = Request::builder()
+                .uri(format!("http://{target}/"))
+                .body(Body::empty())
+                .unwrap();
+
+            let result = connector.connect(ctx, req).await;
+            if !expect_ok {
+                assert!(result.is_err());
+                continue;
+            }
+
+            let EstablishedClientConnection { mut conn, .. } = result.unwrap();
+            conn.write_all(b"ping").await.unwrap();
+            let mut buf = [0u8; 4];
+            conn.read_exact(&mut buf).await.unwrap();
+            assert_eq!(b"ping", &buf);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_http_proxy_connector_proxy_chain_unsupported_hop() {
+        let connector = HttpProxyConnector::required(TcpConnector::new());
+
+        let mut ctx = Context::default();
+        ctx.insert(ProxyChain::new([
+            "http://127.0.0.1:1".parse().unwrap(),
+            "ftp://127.0.0.1:2".parse().unwrap(),
+        ]));
+        let req = Request::builder()
+            .uri("http://example.com/")
+            .body(Body::empty())
+            .unwrap();
+
+        let err = connector.connect(ctx, req).await.unwrap_err();
+        assert!(err.to_string().contains("unsupported protocol"), "{err}");
+    }
+}
@@ -2,14 +2,17 @@ use rama_core::{
     error::{BoxError, ErrorContext, OpaqueError},
     Context, Service,
 };
+use rama_http_core::ext::{InformationalSender, OnInformational};
 use rama_http_types::{
     dep::{http::uri::PathAndQuery, http_body},
     header::{CONNECTION, HOST, KEEP_ALIVE, PROXY_CONNECTION, TRANSFER_ENCODING, UPGRADE},
     headers::HeaderMapExt,
-    Method, Request, Response, Version,
+    Method, Request, Response, StatusCode, Version,
 };
 use rama_net::{address::ProxyAddress, http::RequestContext};
 
+use crate::{masque, websocket};
+
 #[derive(Debug)]
 pub(super) enum SendRequest<Body> {
     Http1(rama_http_core::client::conn::http1::SendRequest<Body>),
@@ -31,8 +34,10 @@ where
     async fn serve(
         &self,
         mut ctx: Context<State>,
-        req: Request<Body>,
+        mut req: Request<Body>,
     ) -> Result<Self::Response, Self::Error> {
+        forward_informational_responses(&mut req);
+
         // sanitize subject line request uri
         // because Hyper (http) writes the URI as-is
         //
@@ -41,17 +46,83 @@ where
         //
         // TODO: fix this in hyper fork (embedded in rama http core)
         // directly instead of here...
-        let req = sanitize_client_req_header(&mut ctx, req)?;
-
+        //
+        // WebSocket handshakes and CONNECT-UDP requests are translated between
+        // their http/1.1 upgrade and h2 extended CONNECT (RFC 8441, RFC 9298) variants
+        // where needed, such that these can be sent over any established connection.
         let resp = match &self.0 {
-            SendRequest::Http1(sender) => sender.send_request(req).await,
-            SendRequest::Http2(sender) => sender.send_request(req).await,
-        }?;
+            SendRequest::Http1(sender) => {
+                if websocket::is_h2_websocket_handshake(&req) {
+                    tracing::trace!("translate h2 websocket handshake into http/1.1 handshake");
+                    let (req, key) = websocket::h2_into_h1_handshake(req);
+                    let req = sanitize_client_req_header(&mut ctx, req)?;
+                    let resp = sender.send_request(req).await?;
+                    websocket::h1_into_h2_handshake_response(resp, key)?
+                } else if masque::is_h2_connect_udp(&req) {
+                    tracing::trace!("translate h2 connect-udp request into http/1.1 upgrade");
+                    let req = masque::h2_into_h1_connect_udp(req);
+                    let req = sanitize_client_req_header(&mut ctx, req)?;
+                    let resp = sender.send_request(req).await?;
+                    masque::h1_into_h2_connect_udp_response(resp)
+                } else {
+                    let req = sanitize_client_req_header(&mut ctx, req)?;
+                    sender.send_request(req).await?
+                }
+            }
+            SendRequest::Http2(sender) => {
+                if websocket::is_h1_websocket_handshake(&req) {
+                    tracing::trace!(
+                        "translate http/1.1 websocket handshake into h2 extended CONNECT"
+                    );
+                    let req = sanitize_client_req_header(&mut ctx, req)?;
+                    let (req, key) = websocket::h1_into_h2_handshake(req);
+                    let resp = sender.send_request(req).await?;
+                    websocket::h2_into_h1_handshake_response(resp, key)
+                } else if masque::is_h1_connect_udp(&req) {
+                    tracing::trace!(
+                        "translate http/1.1 connect-udp upgrade into h2 extended CONNECT"
+                    );
+                    let req = sanitize_client_req_header(&mut ctx, req)?;
+                    let req = masque::h1_into_h2_connect_udp(req);
+                    let resp = sender.send_request(req).await?;
+                    masque::h2_into_h1_connect_udp_response(resp)
+                } else {
+                    let req = sanitize_client_req_header(&mut ctx, req)?;
+                    sender.send_request(req).await?
+                }
+            }
+        };
 
         Ok(resp.map(rama_http_types::Body::new))
     }
 }
 
+/// Forward the informational (1xx) responses received for a proxied request,
+/// e.g. `103 Early Hints`, to the client of the incoming request,
+/// unless the caller observes them already using its own [`OnInformational`] callback.
+///
+/// `100 Continue` is not forwarded, as expectations are handled by each hop.
+fn forward_informational_responses<B>(req: &mut Request<B>) {
+    if req.extensions().get::<OnInformational>().is_some() {
+        return;
+    }
+    let Some(sender) = req.extensions().get::<InformationalSender>().cloned() else {
+        return;
+    };
+    req.extensions_mut()
+        .insert(OnInformational::new(move |res: &Response<()>| {
+            if res.status() == StatusCode::CONTINUE {
+                return;
+            }
+            let mut informational = Response::new(());
+            *informational.status_mut() = res.status();
+            *informational.headers_mut() = res.headers().clone();
+            if let Err(err) = sender.send(informational) {
+                tracing::debug!(error = %err, "failed to forward informational response");
+            }
+        }));
+}
+
 fn sanitize_client_req_header<S, B>(
     ctx: &mut Context<S>,
     req: Request<B>,
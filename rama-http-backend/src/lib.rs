@@ -16,4 +16,8 @@
 #![cfg_attr(not(test), warn(clippy::print_stdout, clippy::dbg_macro))]
 
 pub mod client;
+pub mod masque;
+pub mod proxy;
 pub mod server;
+
+mod websocket;
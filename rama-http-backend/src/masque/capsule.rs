@@ -0,0 +1,228 @@
+//! The capsule protocol, as defined in [RFC 9297].
+//!
+//! [RFC 9297]: https://datatracker.ietf.org/doc/html/rfc9297#section-3
+
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use std::{fmt, io};
+
+/// Largest value which can be encoded as a variable-length integer.
+const MAX_VARINT: u64 = (1 << 62) - 1;
+
+/// Encode a variable-length integer, as defined in [RFC 9000].
+///
+/// # Panics
+///
+/// Panics in case the value exceeds `2^62 - 1`.
+///
+/// [RFC 9000]: https://datatracker.ietf.org/doc/html/rfc9000#section-16
+pub(super) fn encode_varint(value: u64, dst: &mut BytesMut) {
+    assert!(value <= MAX_VARINT, "varint value out of range: {value}");
+    if value < 1 << 6 {
+        dst.put_u8(value as u8);
+    } else if value < 1 << 14 {
+        dst.put_u16(0x4000 | value as u16);
+    } else if value < 1 << 30 {
+        dst.put_u32(0x8000_0000 | value as u32);
+    } else {
+        dst.put_u64(0xc000_0000_0000_0000 | value);
+    }
+}
+
+/// Decode a variable-length integer, as defined in [RFC 9000],
+/// returning the value and its encoded length, or `None`
+/// in case the given buffer does not contain the complete integer.
+///
+/// [RFC 9000]: https://datatracker.ietf.org/doc/html/rfc9000#section-16
+pub(super) fn decode_varint(src: &[u8]) -> Option<(u64, usize)> {
+    let first = *src.first()?;
+    let len = 1 << (first >> 6);
+    if src.len() < len {
+        return None;
+    }
+    let mut value = u64::from(first & 0x3f);
+    for b in &src[1..len] {
+        value = (value << 8) | u64::from(*b);
+    }
+    Some((value, len))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+/// The type of a [`Capsule`].
+///
+/// See the [IANA HTTP Capsule Types registry] for the registered types.
+///
+/// [IANA HTTP Capsule Types registry]: https://www.iana.org/assignments/masque/masque.xhtml#masque-capsule-types
+pub struct CapsuleType(u64);
+
+impl CapsuleType {
+    /// The `DATAGRAM` capsule type, carrying an HTTP datagram.
+    pub const DATAGRAM: Self = Self(0x00);
+
+    /// Create a [`CapsuleType`] from its numeric value.
+    pub const fn from_u64(value: u64) -> Self {
+        Self(value)
+    }
+
+    /// Returns the numeric value of this [`CapsuleType`].
+    pub const fn as_u64(&self) -> u64 {
+        self.0
+    }
+}
+
+impl fmt::Display for CapsuleType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:#x}", self.0)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// A capsule, as exchanged over a stream using the capsule protocol.
+///
+/// Each capsule consists of a type, a length and a payload,
+/// where both type and length are encoded as variable-length integers.
+pub struct Capsule {
+    capsule_type: CapsuleType,
+    payload: Bytes,
+}
+
+impl Capsule {
+    /// Create a new [`Capsule`] of the given type with the given payload.
+    pub fn new(capsule_type: CapsuleType, payload: impl Into<Bytes>) -> Self {
+        Self {
+            capsule_type,
+            payload: payload.into(),
+        }
+    }
+
+    /// Create a `DATAGRAM` [`Capsule`] carrying a UDP payload,
+    /// as defined for proxying UDP in HTTP, which uses context ID `0`.
+    pub fn udp_datagram(payload: &[u8]) -> Self {
+        let mut buf = BytesMut::with_capacity(1 + payload.len());
+        encode_varint(0, &mut buf);
+        buf.put_slice(payload);
+        Self::new(CapsuleType::DATAGRAM, buf.freeze())
+    }
+
+    /// Returns the [`CapsuleType`] of this [`Capsule`].
+    pub fn capsule_type(&self) -> CapsuleType {
+        self.capsule_type
+    }
+
+    /// Returns a reference to the payload of this [`Capsule`].
+    pub fn payload(&self) -> &Bytes {
+        &self.payload
+    }
+
+    /// Consumes this [`Capsule`], returning its payload.
+    pub fn into_payload(self) -> Bytes {
+        self.payload
+    }
+
+    /// Encode this [`Capsule`], appending it to `dst`.
+    pub fn encode(&self, dst: &mut BytesMut) {
+        dst.reserve(16 + self.payload.len());
+        encode_varint(self.capsule_type.0, dst);
+        encode_varint(self.payload.len() as u64, dst);
+        dst.put_slice(&self.payload);
+    }
+
+    /// Decode the next [`Capsule`] from the start of `src`,
+    /// returning `None` in case `src` does not yet contain a complete capsule.
+    ///
+    /// An error is returned in case the capsule payload exceeds `max_payload_len` bytes.
+    pub fn decode(src: &mut BytesMut, max_payload_len: usize) -> io::Result<Option<Self>> {
+        let Some((capsule_type, type_len)) = decode_varint(src) else {
+            return Ok(None);
+        };
+        let Some((len, len_len)) = decode_varint(&src[type_len..]) else {
+            return Ok(None);
+        };
+        let len = usize::try_from(len)
+            .ok()
+            .filter(|len| *len <= max_payload_len)
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("capsule of {len} bytes exceeds the limit of {max_payload_len} bytes"),
+                )
+            })?;
+        let header_len = type_len + len_len;
+        if src.len() < header_len + len {
+            src.reserve(header_len + len - src.len());
+            return Ok(None);
+        }
+        src.advance(header_len);
+        Ok(Some(Self {
+            capsule_type: CapsuleType(capsule_type),
+            payload: src.split_to(len).freeze(),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_varint_roundtrip() {
+        for (value, len) in [
+            (0, 1),
+            (63, 1),
+            (64, 2),
+            (16383, 2),
+            (16384, 4),
+            ((1 << 30) - 1, 4),
+            (1 << 30, 8),
+            (MAX_VARINT, 8),
+        ] {
+            let mut buf = BytesMut::new();
+            encode_varint(value, &mut buf);
+            assert_eq!(len, buf.len(), "{value}");
+            assert_eq!(Some((value, len)), decode_varint(&buf));
+            assert_eq!(None, decode_varint(&buf[..len - 1]));
+        }
+
+        // examples from RFC 9000, appendix A.1
+        assert_eq!(
+            Some((151_288_809_941_952_652, 8)),
+            decode_varint(&[0xc2, 0x19, 0x7c, 0x5e, 0xff, 0x14, 0xe8, 0x8c])
+        );
+        assert_eq!(
+            Some((494_878_333, 4)),
+            decode_varint(&[0x9d, 0x7f, 0x3e, 0x7d])
+        );
+        assert_eq!(Some((15_293, 2)), decode_varint(&[0x7b, 0xbd]));
+        assert_eq!(Some((37, 1)), decode_varint(&[0x25]));
+    }
+
+    #[test]
+    fn test_capsule_roundtrip() {
+        let capsule = Capsule::udp_datagram(b"hello");
+        assert_eq!(CapsuleType::DATAGRAM, capsule.capsule_type());
+        assert_eq!(&b"\x00hello"[..], &capsule.payload()[..]);
+
+        let mut buf = BytesMut::new();
+        capsule.encode(&mut buf);
+        Capsule::new(CapsuleType::from_u64(0x1234), Bytes::new()).encode(&mut buf);
+        assert_eq!(&b"\x00\x06\x00hello\x52\x34\x00"[..], &buf[..]);
+
+        let mut partial = BytesMut::from(&buf[..4]);
+        assert_eq!(None, Capsule::decode(&mut partial, 1024).unwrap());
+        assert_eq!(4, partial.len());
+
+        assert_eq!(capsule, Capsule::decode(&mut buf, 1024).unwrap().unwrap());
+        let capsule = Capsule::decode(&mut buf, 1024).unwrap().unwrap();
+        assert_eq!(0x1234, capsule.capsule_type().as_u64());
+        assert!(capsule.payload().is_empty());
+        assert!(buf.is_empty());
+        assert_eq!(None, Capsule::decode(&mut buf, 1024).unwrap());
+    }
+
+    #[test]
+    fn test_capsule_too_large() {
+        let mut buf = BytesMut::new();
+        Capsule::udp_datagram(b"hello").encode(&mut buf);
+        let err = Capsule::decode(&mut buf, 5).unwrap_err();
+        assert_eq!(io::ErrorKind::InvalidData, err.kind());
+    }
+}
@@ -0,0 +1,192 @@
+//! Internal utilities to recognise and translate CONNECT-UDP requests,
+//! bootstrapped over http/1.1 using an upgrade or over h2 using
+//! the extended CONNECT protocol ([RFC 9298]).
+//!
+//! [RFC 9298]: https://datatracker.ietf.org/doc/html/rfc9298#section-3
+
+use crate::websocket::header_contains_token;
+use rama_http_core::ext::Protocol;
+use rama_http_types::{
+    header::{CONNECTION, UPGRADE},
+    HeaderMap, HeaderName, HeaderValue, IntoResponse, Method, Request, Response, StatusCode,
+};
+
+/// Value of the `:protocol` pseudo header and `Upgrade` header for CONNECT-UDP.
+pub(crate) const CONNECT_UDP: &str = "connect-udp";
+
+/// The `Capsule-Protocol` header, indicating the use of the capsule protocol (RFC 9297).
+pub(crate) static CAPSULE_PROTOCOL: HeaderName = HeaderName::from_static("capsule-protocol");
+
+/// Returns true if the request is a http/1.1 CONNECT-UDP upgrade request.
+pub(crate) fn is_h1_connect_udp<B>(req: &Request<B>) -> bool {
+    req.method() == Method::GET
+        && header_contains_token(req.headers(), &UPGRADE, CONNECT_UDP)
+        && header_contains_token(req.headers(), &CONNECTION, "upgrade")
+        && has_capsule_protocol_header(req.headers())
+}
+
+/// Returns true if the request is an h2 extended CONNECT CONNECT-UDP request.
+pub(crate) fn is_h2_connect_udp<B>(req: &Request<B>) -> bool {
+    req.method() == Method::CONNECT
+        && req
+            .extensions()
+            .get::<Protocol>()
+            .is_some_and(|protocol| protocol.as_str().eq_ignore_ascii_case(CONNECT_UDP))
+        && has_capsule_protocol_header(req.headers())
+}
+
+/// Returns true if the headers contain a `Capsule-Protocol` header
+/// with the (structured field) boolean value true, which
+/// CONNECT-UDP requests are required to include.
+///
+/// Parameters of the value, if any, are ignored as specified in [RFC 9297].
+///
+/// [RFC 9297]: https://datatracker.ietf.org/doc/html/rfc9297#section-3.4
+fn has_capsule_protocol_header(headers: &HeaderMap) -> bool {
+    let mut values = headers.get_all(&CAPSULE_PROTOCOL).iter();
+    match (values.next(), values.next()) {
+        (Some(value), None) => value.to_str().is_ok_and(|value| {
+            let value = value.split(';').next().unwrap_or_default();
+            value.trim_matches(|c| c == ' ' || c == '\t') == "?1"
+        }),
+        _ => false,
+    }
+}
+
+/// Create the response accepting the given CONNECT-UDP request,
+/// `None` is returned in case it is not a CONNECT-UDP request.
+pub(crate) fn accept_connect_udp<B>(req: &Request<B>) -> Option<Response> {
+    let mut resp = if is_h2_connect_udp(req) {
+        StatusCode::OK.into_response()
+    } else if is_h1_connect_udp(req) {
+        let mut resp = StatusCode::SWITCHING_PROTOCOLS.into_response();
+        insert_h1_upgrade_headers(resp.headers_mut());
+        resp
+    } else {
+        return None;
+    };
+    insert_capsule_protocol_header(resp.headers_mut());
+    Some(resp)
+}
+
+/// Translate a http/1.1 CONNECT-UDP request into an extended CONNECT request.
+pub(crate) fn h1_into_h2_connect_udp<B>(mut req: Request<B>) -> Request<B> {
+    req.headers_mut().remove(UPGRADE);
+    req.headers_mut().remove(CONNECTION);
+    *req.method_mut() = Method::CONNECT;
+    req.extensions_mut()
+        .insert(Protocol::from_static(CONNECT_UDP));
+    req
+}
+
+/// Translate the (h2) response of an extended CONNECT-UDP request
+/// into the response expected for the original http/1.1 request.
+pub(crate) fn h2_into_h1_connect_udp_response<B>(mut resp: Response<B>) -> Response<B> {
+    if !resp.status().is_success() {
+        return resp;
+    }
+    *resp.status_mut() = StatusCode::SWITCHING_PROTOCOLS;
+    insert_h1_upgrade_headers(resp.headers_mut());
+    resp
+}
+
+/// Translate an extended CONNECT-UDP request into a http/1.1 upgrade request,
+/// such that it can be sent over a http/1.1 connection.
+pub(crate) fn h2_into_h1_connect_udp<B>(mut req: Request<B>) -> Request<B> {
+    req.extensions_mut().remove::<Protocol>();
+    *req.method_mut() = Method::GET;
+    insert_h1_upgrade_headers(req.headers_mut());
+    insert_capsule_protocol_header(req.headers_mut());
+    req
+}
+
+/// Translate the (http/1.1) response of a CONNECT-UDP upgrade request
+/// into the response expected for the original extended CONNECT request.
+pub(crate) fn h1_into_h2_connect_udp_response<B>(mut resp: Response<B>) -> Response<B> {
+    if resp.status() != StatusCode::SWITCHING_PROTOCOLS {
+        return resp;
+    }
+    *resp.status_mut() = StatusCode::OK;
+    resp.headers_mut().remove(UPGRADE);
+    resp.headers_mut().remove(CONNECTION);
+    resp
+}
+
+pub(crate) fn insert_h1_upgrade_headers(headers: &mut HeaderMap) {
+    headers.insert(UPGRADE, HeaderValue::from_static(CONNECT_UDP));
+    headers.insert(CONNECTION, HeaderValue::from_static("upgrade"));
+}
+
+pub(crate) fn insert_capsule_protocol_header(headers: &mut HeaderMap) {
+    headers.insert(&CAPSULE_PROTOCOL, HeaderValue::from_static("?1"));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn h1_request() -> Request<()> {
+        let mut req = Request::builder()
+            .uri("/.well-known/masque/udp/192.0.2.6/443/")
+            .body(())
+            .unwrap();
+        insert_h1_upgrade_headers(req.headers_mut());
+        insert_capsule_protocol_header(req.headers_mut());
+        req
+    }
+
+    #[test]
+    fn test_accept_connect_udp() {
+        let resp = accept_connect_udp(&h1_request()).unwrap();
+        assert_eq!(StatusCode::SWITCHING_PROTOCOLS, resp.status());
+        assert_eq!(CONNECT_UDP, resp.headers()[UPGRADE]);
+        assert_eq!("?1", resp.headers()[&CAPSULE_PROTOCOL]);
+
+        let mut req = h1_request();
+        req.headers_mut().remove(UPGRADE);
+        assert!(accept_connect_udp(&req).is_none());
+    }
+
+    #[test]
+    fn test_connect_udp_requires_capsule_protocol() {
+        for (value, expected) in [
+            (None, false),
+            (Some("?1"), true),
+            (Some(" ?1;foo=bar"), true),
+            (Some("?0"), false),
+            (Some("1"), false),
+            (Some("true"), false),
+        ] {
+            let mut req = h1_request();
+            req.headers_mut().remove(&CAPSULE_PROTOCOL);
+            if let Some(value) = value {
+                req.headers_mut()
+                    .insert(&CAPSULE_PROTOCOL, HeaderValue::from_static(value));
+            }
+            assert_eq!(expected, is_h1_connect_udp(&req), "value: {value:?}");
+            let req = h1_into_h2_connect_udp(req);
+            assert_eq!(expected, is_h2_connect_udp(&req), "value: {value:?}");
+        }
+    }
+
+    #[test]
+    fn test_h1_h2_connect_udp_roundtrip() {
+        let req = h1_into_h2_connect_udp(h1_request());
+        assert!(is_h2_connect_udp(&req));
+        assert!(!is_h1_connect_udp(&req));
+
+        let resp = accept_connect_udp(&req).unwrap();
+        assert_eq!(StatusCode::OK, resp.status());
+        assert_eq!("?1", resp.headers()[&CAPSULE_PROTOCOL]);
+        let resp = h2_into_h1_connect_udp_response(resp);
+        assert_eq!(StatusCode::SWITCHING_PROTOCOLS, resp.status());
+        assert_eq!(CONNECT_UDP, resp.headers()[UPGRADE]);
+
+        let req = h2_into_h1_connect_udp(req);
+        assert!(is_h1_connect_udp(&req));
+        assert!(!is_h2_connect_udp(&req));
+        let resp = h1_into_h2_connect_udp_response(accept_connect_udp(&req).unwrap());
+        assert_eq!(StatusCode::OK, resp.status());
+        assert!(!resp.headers().contains_key(UPGRADE));
+    }
+}
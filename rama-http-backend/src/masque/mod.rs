@@ -0,0 +1,53 @@
+//! Proxying UDP in HTTP ([RFC 9298]), also known as MASQUE CONNECT-UDP,
+//! using HTTP datagrams carried by the capsule protocol ([RFC 9297]).
+//!
+//! A client asks a proxy to relay UDP to a target using a CONNECT-UDP request,
+//! which is either a http/1.1 upgrade request (`Upgrade: connect-udp`) or an h2
+//! extended CONNECT request with `connect-udp` as its `:protocol` pseudo header.
+//! The target is encoded in the request URI using a [`UdpProxyTemplate`].
+//! Once the request is accepted, UDP payloads are exchanged as `DATAGRAM`
+//! [`Capsule`]s over the upgraded stream, see [`UdpTunnel`].
+//!
+//! Servers can accept such requests using the [`ConnectUdpMatcher`],
+//! [`ConnectUdpAcceptor`] and [`ConnectUdpRelay`] (requires the `connect-udp` feature)
+//! with an [`UpgradeLayer`],
+//! while clients can use the [`ConnectUdpConnector`] to establish a [`UdpTunnel`].
+//!
+//! [RFC 9298]: https://datatracker.ietf.org/doc/html/rfc9298
+//! [RFC 9297]: https://datatracker.ietf.org/doc/html/rfc9297
+//! [`ConnectUdpMatcher`]: crate::server::layer::upgrade::ConnectUdpMatcher
+//! [`ConnectUdpAcceptor`]: crate::server::layer::upgrade::ConnectUdpAcceptor
+//! [`ConnectUdpRelay`]: crate::server::layer::upgrade::ConnectUdpRelay
+//! [`UpgradeLayer`]: crate::server::layer::upgrade::UpgradeLayer
+//! [`ConnectUdpConnector`]: crate::client::ConnectUdpConnector
+
+mod capsule;
+#[doc(inline)]
+pub use capsule::{Capsule, CapsuleType};
+
+mod template;
+#[doc(inline)]
+pub use template::UdpProxyTemplate;
+
+mod tunnel;
+#[doc(inline)]
+pub use tunnel::UdpTunnel;
+#[cfg(feature = "connect-udp")]
+pub(crate) use tunnel::MAX_UDP_PAYLOAD_SIZE;
+
+mod handshake;
+pub(crate) use handshake::{
+    accept_connect_udp, h1_into_h2_connect_udp, h1_into_h2_connect_udp_response,
+    h2_into_h1_connect_udp, h2_into_h1_connect_udp_response, insert_capsule_protocol_header,
+    insert_h1_upgrade_headers, is_h1_connect_udp, is_h2_connect_udp,
+};
+
+use rama_net::address::Authority;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// The target of a CONNECT-UDP request,
+/// inserted in the [`Context`] by the [`ConnectUdpMatcher`].
+///
+/// [`Context`]: rama_core::Context
+/// [`ConnectUdpMatcher`]: crate::server::layer::upgrade::ConnectUdpMatcher
+pub struct ConnectUdpTarget(pub Authority);
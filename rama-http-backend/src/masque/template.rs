@@ -0,0 +1,286 @@
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use rama_core::error::{ErrorContext, OpaqueError};
+use rama_http_types::Uri;
+use rama_net::address::{Authority, Host};
+use std::{fmt, net::IpAddr};
+
+const TARGET_HOST: &str = "{target_host}";
+const TARGET_PORT: &str = "{target_port}";
+
+/// Characters which are percent-encoded in the expanded template variables,
+/// which are all characters except the unreserved ones ([RFC 3986]).
+///
+/// [RFC 3986]: https://datatracker.ietf.org/doc/html/rfc3986#section-2.3
+const VARIABLE_ENCODE_SET: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// A URI template used to proxy UDP in HTTP, as defined in [RFC 9298].
+///
+/// The template contains a `{target_host}` and `{target_port}` variable,
+/// either in its path or query, e.g. the default template
+/// `/.well-known/masque/udp/{target_host}/{target_port}/`.
+///
+/// Servers match the path (and query) of incoming requests against the template,
+/// in which case the scheme and authority of the template are ignored.
+/// Clients expand the template to create the request URI, for which the
+/// template has to be an absolute URI, e.g.
+/// `https://proxy.example.org:4443/masque?h={target_host}&p={target_port}`.
+///
+/// [RFC 9298]: https://datatracker.ietf.org/doc/html/rfc9298#section-2
+pub struct UdpProxyTemplate {
+    template: String,
+    /// Offset of the path (and query) within the template.
+    path_offset: usize,
+}
+
+impl UdpProxyTemplate {
+    /// The path of the default template, as defined in [RFC 9298].
+    ///
+    /// [RFC 9298]: https://datatracker.ietf.org/doc/html/rfc9298#section-3
+    pub const DEFAULT_PATH: &'static str = "/.well-known/masque/udp/{target_host}/{target_port}/";
+
+    /// Create a [`UdpProxyTemplate`] with the default path,
+    /// for the proxy at the given scheme and authority, e.g. `https://proxy.example.org`.
+    pub fn with_default_path(origin: &str) -> Result<Self, OpaqueError> {
+        Self::try_from(format!(
+            "{}{}",
+            origin.trim_end_matches('/'),
+            Self::DEFAULT_PATH
+        ))
+    }
+
+    /// Returns the path (and query) part of the template.
+    fn path_template(&self) -> &str {
+        &self.template[self.path_offset..]
+    }
+
+    /// Expand the template for the given target, creating the request URI.
+    pub fn expand(&self, target: &Authority) -> Result<Uri, OpaqueError> {
+        let host = match target.host() {
+            Host::Name(domain) => domain.as_str().to_owned(),
+            Host::Address(IpAddr::V4(ip)) => ip.to_string(),
+            Host::Address(IpAddr::V6(ip)) => ip.to_string(),
+        };
+        let uri = self
+            .template
+            .replace(
+                TARGET_HOST,
+                &utf8_percent_encode(&host, VARIABLE_ENCODE_SET).to_string(),
+            )
+            .replace(TARGET_PORT, &target.port().to_string());
+        uri.parse()
+            .context("parse expanded udp proxy template as uri")
+    }
+
+    /// Match the given request URI against the template,
+    /// returning the target in case it matches.
+    pub fn match_uri(&self, uri: &Uri) -> Option<Authority> {
+        self.match_path_and_query(uri.path_and_query()?.as_str())
+    }
+
+    fn match_path_and_query(&self, path_and_query: &str) -> Option<Authority> {
+        let mut input = path_and_query;
+        let mut template = self.path_template();
+        let mut host = None;
+        let mut port = None;
+
+        while let Some((pos, variable)) = next_variable(template) {
+            input = input.strip_prefix(&template[..pos])?;
+            template = &template[pos + variable.len()..];
+
+            // a variable value ends at the next literal character of the template
+            let end = match template.chars().next() {
+                Some(c) => input.find(c)?,
+                None => input.len(),
+            };
+            let value = percent_decode_str(&input[..end]).decode_utf8().ok()?;
+            input = &input[end..];
+
+            if variable == TARGET_HOST {
+                host = Some(Host::try_from(value.as_ref()).ok()?);
+            } else {
+                port = Some(value.parse::<u16>().ok().filter(|port| *port != 0)?);
+            }
+        }
+
+        if input != template {
+            return None;
+        }
+        Some(Authority::new(host?, port?))
+    }
+}
+
+/// Returns the position and name of the first variable in the given template.
+fn next_variable(template: &str) -> Option<(usize, &'static str)> {
+    [TARGET_HOST, TARGET_PORT]
+        .into_iter()
+        .filter_map(|variable| template.find(variable).map(|pos| (pos, variable)))
+        .min_by_key(|(pos, _)| *pos)
+}
+
+impl Default for UdpProxyTemplate {
+    fn default() -> Self {
+        Self {
+            template: Self::DEFAULT_PATH.to_owned(),
+            path_offset: 0,
+        }
+    }
+}
+
+impl TryFrom<String> for UdpProxyTemplate {
+    type Error = OpaqueError;
+
+    fn try_from(template: String) -> Result<Self, Self::Error> {
+        let path_offset = if template.starts_with('/') {
+            0
+        } else {
+            let authority_offset = template
+                .find("://")
+                .context("udp proxy template is neither a path nor an absolute uri")?
+                + 3;
+            template[authority_offset..]
+                .find('/')
+                .context("udp proxy template is missing a path")?
+                + authority_offset
+        };
+
+        for variable in [TARGET_HOST, TARGET_PORT] {
+            let matches: Vec<_> = template.match_indices(variable).collect();
+            if matches.len() != 1 || matches[0].0 < path_offset {
+                return Err(OpaqueError::from_display(format!(
+                    "udp proxy template has to contain {variable} exactly once in its path or query"
+                )));
+            }
+        }
+        if template.contains("{target_host}{target_port}")
+            || template.contains("{target_port}{target_host}")
+        {
+            return Err(OpaqueError::from_display(
+                "udp proxy template variables have to be separated",
+            ));
+        }
+
+        let template = Self {
+            template,
+            path_offset,
+        };
+        // ensure that the template expands into a valid uri
+        template
+            .expand(&Authority::new(Host::Address([127, 0, 0, 1].into()), 443))
+            .context("validate udp proxy template")?;
+        Ok(template)
+    }
+}
+
+impl TryFrom<&str> for UdpProxyTemplate {
+    type Error = OpaqueError;
+
+    fn try_from(template: &str) -> Result<Self, Self::Error> {
+        Self::try_from(template.to_owned())
+    }
+}
+
+impl std::str::FromStr for UdpProxyTemplate {
+    type Err = OpaqueError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::try_from(s)
+    }
+}
+
+impl fmt::Display for UdpProxyTemplate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.template)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv6Addr;
+
+    #[test]
+    fn test_default_template() {
+        let template =
+            UdpProxyTemplate::with_default_path("https://proxy.example.org:4443/").unwrap();
+        assert_eq!(
+            "https://proxy.example.org:4443/.well-known/masque/udp/{target_host}/{target_port}/",
+            template.to_string()
+        );
+
+        for (target, expected) in [
+            (
+                Authority::try_from("example.com:53").unwrap(),
+                "/.well-known/masque/udp/example.com/53/",
+            ),
+            (
+                Authority::try_from("192.0.2.6:443").unwrap(),
+                "/.well-known/masque/udp/192.0.2.6/443/",
+            ),
+            (
+                Authority::new(Host::Address(Ipv6Addr::LOCALHOST.into()), 8080),
+                "/.well-known/masque/udp/%3A%3A1/8080/",
+            ),
+        ] {
+            let uri = template.expand(&target).unwrap();
+            assert_eq!(Some("proxy.example.org"), uri.host());
+            assert_eq!(expected, uri.path());
+
+            assert_eq!(Some(target.clone()), template.match_uri(&uri));
+            assert_eq!(
+                Some(target),
+                UdpProxyTemplate::default().match_uri(&expected.parse().unwrap())
+            );
+        }
+    }
+
+    #[test]
+    fn test_query_template() {
+        let template: UdpProxyTemplate =
+            "https://proxy.example.org/masque?h={target_host}&p={target_port}"
+                .parse()
+                .unwrap();
+        let target = Authority::try_from("example.com:53").unwrap();
+        let uri = template.expand(&target).unwrap();
+        assert_eq!(
+            "https://proxy.example.org/masque?h=example.com&p=53",
+            uri.to_string()
+        );
+        assert_eq!(Some(target), template.match_uri(&uri));
+    }
+
+    #[test]
+    fn test_template_mismatch() {
+        let template = UdpProxyTemplate::default();
+        for uri in [
+            "/.well-known/masque/udp/example.com/53",
+            "/.well-known/masque/udp/example.com/53/extra",
+            "/.well-known/masque/udp/example.com/port/",
+            "/.well-known/masque/udp/example.com/0/",
+            "/.well-known/masque/udp/example.com/",
+            "/.well-known/masque/tcp/example.com/53/",
+            "/",
+        ] {
+            assert_eq!(None, template.match_uri(&uri.parse().unwrap()), "{uri}");
+        }
+    }
+
+    #[test]
+    fn test_invalid_template() {
+        for template in [
+            "",
+            "proxy.example.org/{target_host}/{target_port}",
+            "https://{target_host}/{target_port}",
+            "/masque/{target_host}",
+            "/masque/{target_host}/{target_port}/{target_port}",
+            "/masque/{target_host}{target_port}",
+            "https://proxy.example.org",
+        ] {
+            assert!(UdpProxyTemplate::try_from(template).is_err(), "{template}");
+        }
+    }
+}
@@ -0,0 +1,176 @@
+use super::capsule::{decode_varint, Capsule, CapsuleType};
+use bytes::{Bytes, BytesMut};
+use std::{fmt, io};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+/// Largest payload which can be carried in a single UDP datagram.
+pub(crate) const MAX_UDP_PAYLOAD_SIZE: usize = 65_527;
+
+/// Largest capsule payload accepted by the [`UdpTunnel`]:
+/// a UDP payload prefixed with a context ID, encoded as (at most) 8 bytes.
+const MAX_CAPSULE_PAYLOAD_SIZE: usize = MAX_UDP_PAYLOAD_SIZE + 8;
+
+/// A tunnel which proxies UDP payloads over a stream,
+/// established using a CONNECT-UDP request ([RFC 9298]).
+///
+/// The payloads are exchanged as HTTP datagrams ([RFC 9297]) in `DATAGRAM` capsules,
+/// using context ID `0`. Capsules of other types are skipped, as are
+/// datagrams with other context IDs, as defined by the specifications.
+///
+/// [RFC 9298]: https://datatracker.ietf.org/doc/html/rfc9298#section-5
+/// [RFC 9297]: https://datatracker.ietf.org/doc/html/rfc9297#section-3.5
+pub struct UdpTunnel<S> {
+    stream: S,
+    buf: BytesMut,
+}
+
+impl<S> UdpTunnel<S> {
+    /// Create a new [`UdpTunnel`] over the given (upgraded) stream.
+    pub fn new(stream: S) -> Self {
+        Self {
+            stream,
+            buf: BytesMut::new(),
+        }
+    }
+
+    /// Gets a reference to the underlying stream.
+    pub fn get_ref(&self) -> &S {
+        &self.stream
+    }
+
+    /// Gets a mutable reference to the underlying stream.
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.stream
+    }
+
+    /// Consumes the [`UdpTunnel`], returning the underlying stream.
+    ///
+    /// Note that data which was already read but not yet decoded is lost.
+    pub fn into_inner(self) -> S {
+        self.stream
+    }
+}
+
+impl<S> UdpTunnel<S>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    /// Send a UDP payload over the tunnel.
+    pub async fn send(&mut self, payload: &[u8]) -> io::Result<()> {
+        if payload.len() > MAX_UDP_PAYLOAD_SIZE {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "udp payload of {} bytes exceeds the limit of {MAX_UDP_PAYLOAD_SIZE} bytes",
+                    payload.len()
+                ),
+            ));
+        }
+        let mut buf = BytesMut::new();
+        Capsule::udp_datagram(payload).encode(&mut buf);
+        self.stream.write_all(&buf).await?;
+        self.stream.flush().await
+    }
+
+    /// Receive the next UDP payload from the tunnel,
+    /// returning `None` once the tunnel is closed by the peer.
+    ///
+    /// This method is cancel safe.
+    pub async fn recv(&mut self) -> io::Result<Option<Bytes>> {
+        loop {
+            while let Some(capsule) = Capsule::decode(&mut self.buf, MAX_CAPSULE_PAYLOAD_SIZE)? {
+                if capsule.capsule_type() != CapsuleType::DATAGRAM {
+                    tracing::trace!(
+                        capsule_type = %capsule.capsule_type(),
+                        "udp tunnel: skip unknown capsule"
+                    );
+                    continue;
+                }
+                let payload = capsule.into_payload();
+                match decode_varint(&payload) {
+                    Some((0, len)) => return Ok(Some(payload.slice(len..))),
+                    Some((context_id, _)) => {
+                        tracing::trace!(context_id, "udp tunnel: drop datagram of unknown context");
+                    }
+                    None => {
+                        return Err(io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            "datagram capsule without context id",
+                        ))
+                    }
+                }
+            }
+
+            self.buf.reserve(4096);
+            if self.stream.read_buf(&mut self.buf).await? == 0 {
+                return if self.buf.is_empty() {
+                    Ok(None)
+                } else {
+                    Err(io::ErrorKind::UnexpectedEof.into())
+                };
+            }
+        }
+    }
+}
+
+impl<S: fmt::Debug> fmt::Debug for UdpTunnel<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UdpTunnel")
+            .field("stream", &self.stream)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::masque::capsule::encode_varint;
+
+    #[tokio::test]
+    async fn test_udp_tunnel_roundtrip() {
+        let (a, b) = tokio::io::duplex(1024);
+        let mut a = UdpTunnel::new(a);
+        let mut b = UdpTunnel::new(b);
+
+        a.send(b"hello").await.unwrap();
+        a.send(b"").await.unwrap();
+        b.send(b"world").await.unwrap();
+
+        assert_eq!(&b"hello"[..], &b.recv().await.unwrap().unwrap()[..]);
+        assert!(b.recv().await.unwrap().unwrap().is_empty());
+        assert_eq!(&b"world"[..], &a.recv().await.unwrap().unwrap()[..]);
+
+        drop(a);
+        assert!(b.recv().await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_udp_tunnel_skips_unknown() {
+        let (mut a, b) = tokio::io::duplex(1024);
+        let mut b = UdpTunnel::new(b);
+
+        let mut buf = BytesMut::new();
+        Capsule::new(CapsuleType::from_u64(0x2a), &b"ignored"[..]).encode(&mut buf);
+        let mut payload = BytesMut::new();
+        encode_varint(2, &mut payload);
+        Capsule::new(CapsuleType::DATAGRAM, payload).encode(&mut buf);
+        Capsule::udp_datagram(b"hello").encode(&mut buf);
+        a.write_all(&buf).await.unwrap();
+
+        assert_eq!(&b"hello"[..], &b.recv().await.unwrap().unwrap()[..]);
+    }
+
+    #[tokio::test]
+    async fn test_udp_tunnel_truncated() {
+        let (mut a, b) = tokio::io::duplex(1024);
+        let mut b = UdpTunnel::new(b);
+
+        let mut buf = BytesMut::new();
+        Capsule::udp_datagram(b"hello").encode(&mut buf);
+        a.write_all(&buf[..4]).await.unwrap();
+        drop(a);
+
+        let err = b.recv().await.unwrap_err();
+        assert_eq!(io::ErrorKind::UnexpectedEof, err.kind());
+    }
+}
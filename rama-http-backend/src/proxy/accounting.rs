@@ -0,0 +1,275 @@
+use bytes::Bytes;
+use rama_core::{error::BoxError, Context};
+use rama_http_types::{
+    dep::http_body::{self, Frame, SizeHint},
+    Body, IntoResponse, Response, StatusCode,
+};
+use rama_proxy::accounting::{QuotaVerdict, UsageKey, UsageLedger, UsageRecord, UsageSink};
+use std::{
+    io,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    task::{ready, Poll},
+};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+/// Check the quotas of the user and upstream proxy found in the [`Context`],
+/// returning the [`UsageRecord`] to account the request on, if it is allowed.
+///
+/// Requests are delayed first in case they are to be throttled.
+pub(super) async fn check_quota<State>(
+    ledger: &UsageLedger,
+    ctx: &Context<State>,
+) -> Result<UsageRecord, Response> {
+    let record = UsageRecord::from_context(ctx);
+    let keys: Vec<_> = record.keys().collect();
+    match ledger.check(&keys) {
+        QuotaVerdict::Allow => Ok(record),
+        QuotaVerdict::Throttle(delay) => {
+            tracing::trace!(
+                ?delay,
+                "ForwardProxyService: quota exceeded, throttle request"
+            );
+            tokio::time::sleep(delay).await;
+            Ok(record)
+        }
+        QuotaVerdict::Reject(key) => {
+            tracing::debug!(?key, "ForwardProxyService: quota exceeded, reject request");
+            Err(StatusCode::TOO_MANY_REQUESTS.into_response())
+        }
+    }
+}
+
+/// The usage of a plain http request, recorded in the [`UsageLedger`]
+/// once both the request and response body are dropped.
+#[derive(Debug)]
+pub(super) struct PendingUsage {
+    ledger: UsageLedger,
+    record: UsageRecord,
+    bytes_up: AtomicU64,
+    bytes_down: AtomicU64,
+}
+
+impl PendingUsage {
+    pub(super) fn new(ledger: UsageLedger, record: UsageRecord) -> Arc<Self> {
+        Arc::new(Self {
+            ledger,
+            record,
+            bytes_up: AtomicU64::new(0),
+            bytes_down: AtomicU64::new(0),
+        })
+    }
+
+    /// Wrap the request body, counting the bytes sent by the client.
+    pub(super) fn count_up(self: &Arc<Self>, body: Body) -> Body {
+        Body::new(CountingBody {
+            inner: body,
+            usage: self.clone(),
+            up: true,
+        })
+    }
+
+    /// Wrap the response body, counting the bytes received by the client.
+    pub(super) fn count_down(self: &Arc<Self>, body: Body) -> Body {
+        Body::new(CountingBody {
+            inner: body,
+            usage: self.clone(),
+            up: false,
+        })
+    }
+}
+
+impl Drop for PendingUsage {
+    fn drop(&mut self) {
+        self.ledger.record(&UsageRecord {
+            bytes_up: *self.bytes_up.get_mut(),
+            bytes_down: *self.bytes_down.get_mut(),
+            requests: 1,
+            ..self.record.clone()
+        });
+    }
+}
+
+struct CountingBody {
+    inner: Body,
+    usage: Arc<PendingUsage>,
+    up: bool,
+}
+
+impl http_body::Body for CountingBody {
+    type Data = Bytes;
+    type Error = BoxError;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let result = ready!(Pin::new(&mut self.inner).poll_frame(cx));
+        if let Some(data) = result
+            .as_ref()
+            .and_then(|result| result.as_ref().ok())
+            .and_then(Frame::data_ref)
+        {
+            let counter = if self.up {
+                &self.usage.bytes_up
+            } else {
+                &self.usage.bytes_down
+            };
+            counter.fetch_add(data.len() as u64, Ordering::Relaxed);
+        }
+        Poll::Ready(result.map(|result| result.map_err(Into::into)))
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+/// The number of bytes sent and received by the client of a CONNECT tunnel.
+#[derive(Debug, Default)]
+pub(super) struct TunnelBytes {
+    up: AtomicU64,
+    down: AtomicU64,
+}
+
+impl TunnelBytes {
+    /// Returns the number of bytes read from the client.
+    pub(super) fn up(&self) -> u64 {
+        self.up.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of bytes written to the client.
+    pub(super) fn down(&self) -> u64 {
+        self.down.load(Ordering::Relaxed)
+    }
+}
+
+/// The number of bytes after which the [`UsageLedger`] is consulted again
+/// for the bytes left within the quota of a tunnel.
+const QUOTA_CHECK_INTERVAL: u64 = 64 * 1024;
+
+/// The client stream of a CONNECT tunnel, counting the bytes read from
+/// and written to it, such that they are known even when the tunnel fails
+/// or is handed off to a MITM service.
+///
+/// Once a byte quota is exceeded the stream fails, closing the tunnel.
+pub(super) struct CountingIo<S> {
+    inner: S,
+    bytes: Arc<TunnelBytes>,
+    quota: Option<ByteQuota>,
+}
+
+/// The bytes left within the quotas of the keys of a tunnel, refreshed from the
+/// [`UsageLedger`] every [`QUOTA_CHECK_INTERVAL`] bytes, such that the usage
+/// recorded in the meantime, e.g. by other requests, is taken into account.
+struct ByteQuota {
+    ledger: UsageLedger,
+    keys: Vec<UsageKey>,
+    remaining: Option<u64>,
+    checked_at: u64,
+}
+
+impl<S> CountingIo<S> {
+    pub(super) fn new(inner: S, bytes: Arc<TunnelBytes>) -> Self {
+        Self {
+            inner,
+            bytes,
+            quota: None,
+        }
+    }
+
+    /// Enforce the byte quotas defined in the [`UsageLedger`]
+    /// for the keys of the given [`UsageRecord`].
+    pub(super) fn with_quota(mut self, ledger: &UsageLedger, record: &UsageRecord) -> Self {
+        let keys: Vec<_> = record.keys().collect();
+        self.quota = Some(ByteQuota {
+            remaining: ledger.remaining_bytes(&keys),
+            ledger: ledger.clone(),
+            keys,
+            checked_at: 0,
+        });
+        self
+    }
+
+    fn check_quota(&mut self) -> io::Result<()> {
+        let Some(quota) = self.quota.as_mut() else {
+            return Ok(());
+        };
+        let used = self.bytes.up().saturating_add(self.bytes.down());
+        if used.saturating_sub(quota.checked_at) >= QUOTA_CHECK_INTERVAL {
+            quota.remaining = quota.ledger.remaining_bytes(&quota.keys);
+            quota.checked_at = used;
+        }
+        match quota.remaining {
+            Some(remaining) if used >= remaining => {
+                tracing::debug!(used, "ForwardProxyService: quota exceeded, close tunnel");
+                Err(io::Error::other("usage quota exceeded"))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for CountingIo<S> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        self.check_quota()?;
+        let filled = buf.filled().len();
+        ready!(Pin::new(&mut self.inner).poll_read(cx, buf))?;
+        let n = buf.filled().len() - filled;
+        self.bytes.up.fetch_add(n as u64, Ordering::Relaxed);
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for CountingIo<S> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.check_quota()?;
+        let n = ready!(Pin::new(&mut self.inner).poll_write(cx, buf))?;
+        self.bytes.down.fetch_add(n as u64, Ordering::Relaxed);
+        Poll::Ready(Ok(n))
+    }
+
+    fn poll_write_vectored(
+        mut self: Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        self.check_quota()?;
+        let n = ready!(Pin::new(&mut self.inner).poll_write_vectored(cx, bufs))?;
+        self.bytes.down.fetch_add(n as u64, Ordering::Relaxed);
+        Poll::Ready(Ok(n))
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.inner.is_write_vectored()
+    }
+
+    fn poll_flush(
+        mut self: Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(
+        mut self: Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
@@ -0,0 +1,1021 @@
+use super::{
+    accounting::{check_quota, CountingIo, PendingUsage, TunnelBytes},
+    headers::append_via,
+    health::{HealthRegistry, HealthyProxyDB},
+};
+use crate::client::HttpClient;
+use bytes::Bytes;
+use rama_core::{error::BoxError, Context, Service};
+use rama_http_core::upgrade::{self, Upgraded};
+use rama_http_types::{
+    header::PROXY_AUTHORIZATION, headers::remove_hop_by_hop_headers, HeaderValue, IntoResponse,
+    Method, Request, Response, StatusCode,
+};
+use rama_net::{
+    address::{Authority, ProxyAddress},
+    client::EstablishedClientConnection,
+    http::RequestContext,
+};
+use rama_proxy::{
+    accounting::{UsageLedger, UsageRecord, UsageSink},
+    ProxyDB, ProxyDBService, ProxyFilterMode,
+};
+use rama_tcp::utils::is_connection_error;
+use std::{
+    convert::Infallible,
+    fmt,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+/// A http forward proxy [`Service`], serving both plain http requests
+/// in absolute-form and CONNECT requests.
+///
+/// - Plain http requests are sent to their target using the [`HttpClient`],
+///   after removing the hop-by-hop headers ([RFC 9110, section 7.6.1])
+///   from both the request and the response.
+/// - CONNECT requests are tunnelled: a connection is established to the target,
+///   after which the upgraded client stream and the target stream are copied
+///   bidirectionally, recording the number of bytes in the [`ForwardProxyStats`].
+///   When a MITM service is configured using [`ForwardProxyService::mitm`],
+///   the upgraded client stream is handed off to that service instead,
+///   still recording the number of bytes sent and received by the client.
+///
+/// A `Via` header ([RFC 9110, section 7.6.3]) is added to plain requests and responses,
+/// unless disabled using [`ForwardProxyService::with_via`].
+///
+/// The upstream connection goes via the [`ProxyAddress`] found in the [`Context`], if any.
+/// When a [`ProxyDB`] is configured using [`ForwardProxyService::proxy_db`], that address
+/// is selected from the db, using the [`ProxyFilter`] in the [`Context`], e.g. as inserted
+/// by the `ProxyAuthLayer` (`rama-http`) from the username labels.
+///
+/// The usage of the proxied traffic is accounted per user, upstream proxy and pool
+/// in the [`UsageLedger`] configured using [`ForwardProxyService::with_usage_ledger`],
+/// which is also used to reject or throttle requests once a quota is exceeded.
+///
+/// Authentication, tracing and limits are left to the layers wrapping this service.
+///
+/// [RFC 9110, section 7.6.1]: https://datatracker.ietf.org/doc/html/rfc9110#section-7.6.1
+/// [RFC 9110, section 7.6.3]: https://datatracker.ietf.org/doc/html/rfc9110#section-7.6.3
+/// [`ProxyAddress`]: rama_net::address::ProxyAddress
+/// [`ProxyFilter`]: rama_proxy::ProxyFilter
+pub struct ForwardProxyService<D = (), M = ()> {
+    client: HttpClient,
+    via: Option<HeaderValue>,
+    proxy_db: Option<Arc<D>>,
+    proxy_filter_mode: ProxyFilterMode,
+    health: Option<HealthRegistry>,
+    usage: Option<UsageLedger>,
+    mitm: M,
+    stats: ForwardProxyStats,
+}
+
+impl ForwardProxyService {
+    /// Create a new [`ForwardProxyService`], using the default [`HttpClient`].
+    pub fn new() -> Self {
+        Self {
+            client: HttpClient::default(),
+            via: Some(HeaderValue::from_static(rama_utils::info::NAME)),
+            proxy_db: None,
+            proxy_filter_mode: ProxyFilterMode::Optional,
+            health: None,
+            usage: None,
+            mitm: (),
+            stats: ForwardProxyStats::default(),
+        }
+    }
+}
+
+impl Default for ForwardProxyService {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<D, M> ForwardProxyService<D, M> {
+    /// Use the given [`HttpClient`] to serve the plain http requests
+    /// and to connect to upstream proxies.
+    pub fn with_http_client(mut self, client: HttpClient) -> Self {
+        self.client = client;
+        self
+    }
+
+    /// Use the given [`HttpClient`] to serve the plain http requests
+    /// and to connect to upstream proxies.
+    pub fn set_http_client(&mut self, client: HttpClient) -> &mut Self {
+        self.client = client;
+        self
+    }
+
+    /// Define the pseudonym used in the `Via` header added by this proxy,
+    /// or `None` to not add a `Via` header at all. By default the pseudonym is `rama`.
+    pub fn with_via(mut self, pseudonym: Option<HeaderValue>) -> Self {
+        self.via = pseudonym;
+        self
+    }
+
+    /// Define the pseudonym used in the `Via` header added by this proxy,
+    /// or `None` to not add a `Via` header at all. By default the pseudonym is `rama`.
+    pub fn set_via(&mut self, pseudonym: Option<HeaderValue>) -> &mut Self {
+        self.via = pseudonym;
+        self
+    }
+
+    /// Set the [`ProxyFilterMode`] used to select an upstream proxy from the [`ProxyDB`].
+    ///
+    /// By default the [`ProxyFilterMode::Optional`] mode is used,
+    /// meaning an upstream proxy is only used in case a [`ProxyFilter`] is available.
+    ///
+    /// [`ProxyFilter`]: rama_proxy::ProxyFilter
+    pub fn with_proxy_filter_mode(mut self, mode: ProxyFilterMode) -> Self {
+        self.proxy_filter_mode = mode;
+        self
+    }
+
+    /// Set the [`ProxyFilterMode`] used to select an upstream proxy from the [`ProxyDB`].
+    ///
+    /// By default the [`ProxyFilterMode::Optional`] mode is used,
+    /// meaning an upstream proxy is only used in case a [`ProxyFilter`] is available.
+    ///
+    /// [`ProxyFilter`]: rama_proxy::ProxyFilter
+    pub fn set_proxy_filter_mode(&mut self, mode: ProxyFilterMode) -> &mut Self {
+        self.proxy_filter_mode = mode;
+        self
+    }
+
+    /// Chain all traffic via an upstream proxy selected from the given [`ProxyDB`].
+    pub fn proxy_db<T>(self, db: T) -> ForwardProxyService<T, M> {
+        ForwardProxyService {
+            client: self.client,
+            via: self.via,
+            proxy_db: Some(Arc::new(db)),
+            proxy_filter_mode: self.proxy_filter_mode,
+            health: self.health,
+            usage: self.usage,
+            mitm: self.mitm,
+            stats: self.stats,
+        }
+    }
+
+    /// Only select healthy upstream proxies from the [`ProxyDB`],
+    /// according to the given [`HealthRegistry`].
+    ///
+    /// The failures to connect via an upstream proxy are recorded
+    /// in the registry, for passive outlier detection.
+    pub fn with_health(mut self, registry: HealthRegistry) -> Self {
+        self.health = Some(registry);
+        self
+    }
+
+    /// Only select healthy upstream proxies from the [`ProxyDB`],
+    /// according to the given [`HealthRegistry`].
+    ///
+    /// The failures to connect via an upstream proxy are recorded
+    /// in the registry, for passive outlier detection.
+    pub fn set_health(&mut self, registry: HealthRegistry) -> &mut Self {
+        self.health = Some(registry);
+        self
+    }
+
+    /// Account the usage of the proxied traffic in the given [`UsageLedger`],
+    /// and enforce the quotas defined in it.
+    ///
+    /// Bytes are counted for the bodies of plain http requests and responses,
+    /// and for the CONNECT tunnels, including those handed off to a MITM service.
+    /// Quotas are checked prior to serving a request, such that the traffic of a plain
+    /// http request which exceeds a quota is still served. Rejected requests
+    /// get a `429 Too Many Requests` response. CONNECT tunnels are in addition
+    /// closed once their bytes exceed the byte quota of a rejecting [`Quota`].
+    ///
+    /// [`Quota`]: rama_proxy::accounting::Quota
+    pub fn with_usage_ledger(mut self, ledger: UsageLedger) -> Self {
+        self.usage = Some(ledger);
+        self
+    }
+
+    /// Account the usage of the proxied traffic in the given [`UsageLedger`],
+    /// and enforce the quotas defined in it.
+    ///
+    /// See [`ForwardProxyService::with_usage_ledger`] for more information.
+    pub fn set_usage_ledger(&mut self, ledger: UsageLedger) -> &mut Self {
+        self.usage = Some(ledger);
+        self
+    }
+
+    /// Hand off the upgraded client stream of CONNECT requests to the given MITM service,
+    /// instead of tunnelling it to the target.
+    ///
+    /// The [`RequestContext`] of the CONNECT request is available in the [`Context`],
+    /// and can be used by that service to define the target of the intercepted requests.
+    pub fn mitm<S>(self, service: S) -> ForwardProxyService<D, Arc<S>> {
+        ForwardProxyService {
+            client: self.client,
+            via: self.via,
+            proxy_db: self.proxy_db,
+            proxy_filter_mode: self.proxy_filter_mode,
+            health: self.health,
+            usage: self.usage,
+            mitm: Arc::new(service),
+            stats: self.stats,
+        }
+    }
+
+    /// Returns a handle to the [`ForwardProxyStats`] of this [`ForwardProxyService`],
+    /// shared by all its clones.
+    pub fn stats(&self) -> ForwardProxyStats {
+        self.stats.clone()
+    }
+}
+
+impl<D, M: fmt::Debug> fmt::Debug for ForwardProxyService<D, M> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ForwardProxyService")
+            .field("client", &self.client)
+            .field("via", &self.via)
+            .field("proxy_db", &self.proxy_db.is_some())
+            .field("proxy_filter_mode", &self.proxy_filter_mode)
+            .field("health", &self.health)
+            .field("usage", &self.usage)
+            .field("mitm", &self.mitm)
+            .field("stats", &self.stats)
+            .finish()
+    }
+}
+
+impl<D, M: Clone> Clone for ForwardProxyService<D, M> {
+    fn clone(&self) -> Self {
+        Self {
+            client: self.client.clone(),
+            via: self.via.clone(),
+            proxy_db: self.proxy_db.clone(),
+            proxy_filter_mode: self.proxy_filter_mode.clone(),
+            health: self.health.clone(),
+            usage: self.usage.clone(),
+            mitm: self.mitm.clone(),
+            stats: self.stats.clone(),
+        }
+    }
+}
+
+impl<D, M> ForwardProxyService<D, M>
+where
+    D: ProxyDB<Error: Into<BoxError> + Send + Sync + 'static>,
+{
+    /// Select the upstream proxy from the [`ProxyDB`], if one is configured,
+    /// inserting its address in the returned [`Context`].
+    async fn select_upstream<State>(
+        &self,
+        ctx: Context<State>,
+        req: Request,
+    ) -> Result<(Context<State>, Request), Response>
+    where
+        State: Clone + Send + Sync + 'static,
+    {
+        let Some(db) = self.proxy_db.clone() else {
+            return Ok((ctx, req));
+        };
+        let result = match &self.health {
+            Some(health) => {
+                ProxyDBService::new(UpstreamSelected, HealthyProxyDB::new(db, health.clone()))
+                    .filter_mode(self.proxy_filter_mode.clone())
+                    .serve(ctx, req)
+                    .await
+            }
+            None => {
+                ProxyDBService::new(UpstreamSelected, db)
+                    .filter_mode(self.proxy_filter_mode.clone())
+                    .serve(ctx, req)
+                    .await
+            }
+        };
+        result.map_err(|err| {
+            tracing::debug!(error = %err, "ForwardProxyService: failed to select upstream proxy");
+            StatusCode::BAD_GATEWAY.into_response()
+        })
+    }
+
+    /// Returns the [`Authority`] of the upstream proxy selected in the [`Context`],
+    /// in case its health is tracked.
+    fn upstream_proxy_authority<State>(&self, ctx: &Context<State>) -> Option<Authority> {
+        self.health.as_ref()?;
+        ctx.get::<ProxyAddress>()
+            .map(|address| address.authority.clone())
+    }
+
+    /// Record the outcome of a request sent via the given upstream proxy, if any.
+    fn record_upstream_outcome(&self, proxy_authority: Option<Authority>, success: bool) {
+        if let (Some(health), Some(authority)) = (&self.health, proxy_authority) {
+            health.record_outcome(&authority, success);
+        }
+    }
+
+    /// Serve a plain http request by sending it to its target.
+    async fn serve_plain<State>(&self, ctx: Context<State>, mut req: Request) -> Response
+    where
+        State: Clone + Send + Sync + 'static,
+    {
+        if req.uri().authority().is_none() {
+            tracing::debug!(uri = %req.uri(), "ForwardProxyService: request is not in absolute-form");
+            return StatusCode::BAD_REQUEST.into_response();
+        }
+
+        let version = req.version();
+        remove_hop_by_hop_headers(req.headers_mut());
+        req.headers_mut().remove(PROXY_AUTHORIZATION);
+        if let Some(pseudonym) = &self.via {
+            append_via(req.headers_mut(), version, pseudonym);
+        }
+
+        let (ctx, mut req) = match self.select_upstream(ctx, req).await {
+            Ok(selected) => selected,
+            Err(resp) => return resp,
+        };
+
+        let usage = match &self.usage {
+            Some(ledger) => match check_quota(ledger, &ctx).await {
+                Ok(record) => Some(PendingUsage::new(ledger.clone(), record)),
+                Err(resp) => return resp,
+            },
+            None => None,
+        };
+        if let Some(usage) = &usage {
+            req = req.map(|body| usage.count_up(body));
+        }
+
+        self.stats.inner.requests.fetch_add(1, Ordering::Relaxed);
+        let proxy_authority = self.upstream_proxy_authority(&ctx);
+        let mut resp = match self.client.serve(ctx, req).await {
+            Ok(resp) => resp,
+            Err(err) => {
+                tracing::debug!(error = %err, "ForwardProxyService: failed to serve plain request");
+                self.record_upstream_outcome(proxy_authority, false);
+                return StatusCode::BAD_GATEWAY.into_response();
+            }
+        };
+        self.record_upstream_outcome(proxy_authority, true);
+
+        if let Some(usage) = usage {
+            resp = resp.map(|body| usage.count_down(body));
+        }
+
+        remove_hop_by_hop_headers(resp.headers_mut());
+        if let Some(pseudonym) = &self.via {
+            let version = resp.version();
+            append_via(resp.headers_mut(), version, pseudonym);
+        }
+        resp
+    }
+
+    /// Prepare a CONNECT request, inserting its [`RequestContext`] in the [`Context`],
+    /// and selecting its upstream proxy if needed.
+    async fn accept_connect<State>(
+        &self,
+        mut ctx: Context<State>,
+        req: Request,
+    ) -> Result<(Context<State>, Request), Response>
+    where
+        State: Clone + Send + Sync + 'static,
+    {
+        if let Err(err) =
+            ctx.get_or_try_insert_with_ctx::<RequestContext, _>(|ctx| (ctx, &req).try_into())
+        {
+            tracing::debug!(error = %err, "ForwardProxyService: invalid CONNECT request");
+            return Err(StatusCode::BAD_REQUEST.into_response());
+        }
+        self.select_upstream(ctx, req).await
+    }
+
+    /// Serve a CONNECT request by tunnelling the upgraded client stream to its target.
+    async fn serve_tunnel<State>(&self, ctx: Context<State>, req: Request) -> Response
+    where
+        State: Clone + Send + Sync + 'static,
+    {
+        let (ctx, req) = match self.accept_connect(ctx, req).await {
+            Ok(accepted) => accepted,
+            Err(resp) => return resp,
+        };
+
+        let record = match &self.usage {
+            Some(ledger) => match check_quota(ledger, &ctx).await {
+                Ok(record) => Some(record),
+                Err(resp) => return resp,
+            },
+            None => None,
+        };
+
+        let proxy_authority = self.upstream_proxy_authority(&ctx);
+        let EstablishedClientConnection {
+            ctx,
+            mut req,
+            mut conn,
+            addr,
+        } = match self.client.connect_transport(ctx, req).await {
+            Ok(established) => established,
+            Err(err) => {
+                tracing::debug!(error = %err, "ForwardProxyService: failed to connect to CONNECT target");
+                self.record_upstream_outcome(proxy_authority, false);
+                if let (Some(ledger), Some(record)) = (&self.usage, record) {
+                    record_tunnel_usage(ledger, record, None, 0, 0);
+                }
+                return StatusCode::BAD_GATEWAY.into_response();
+            }
+        };
+        self.record_upstream_outcome(proxy_authority, true);
+
+        let stats = self.stats.clone();
+        let usage = self.usage.clone().zip(record);
+        ctx.executor().spawn_task(async move {
+            let upgraded = match upgrade::on(&mut req).await {
+                Ok(upgraded) => upgraded,
+                Err(err) => {
+                    tracing::debug!(error = %err, "ForwardProxyService: CONNECT upgrade failed");
+                    if let Some((ledger, record)) = usage {
+                        record_tunnel_usage(&ledger, record, None, 0, 0);
+                    }
+                    return;
+                }
+            };
+            stats.inner.tunnels.fetch_add(1, Ordering::Relaxed);
+            let start = Instant::now();
+            let bytes = Arc::new(TunnelBytes::default());
+            let mut upgraded = CountingIo::new(upgraded, bytes.clone());
+            if let Some((ledger, record)) = &usage {
+                upgraded = upgraded.with_quota(ledger, record);
+            }
+            match tokio::io::copy_bidirectional(&mut upgraded, &mut conn).await {
+                Ok(_) => tracing::debug!(
+                    %addr,
+                    bytes_up = bytes.up(),
+                    bytes_down = bytes.down(),
+                    "ForwardProxyService: CONNECT tunnel closed"
+                ),
+                Err(err) => {
+                    if !is_connection_error(&err) {
+                        tracing::debug!(%addr, error = %err, "ForwardProxyService: CONNECT tunnel failed");
+                    }
+                }
+            }
+            stats.record_tunnel_bytes(&bytes);
+            if let Some((ledger, record)) = usage {
+                record_tunnel_usage(&ledger, record, Some(start.elapsed()), bytes.up(), bytes.down());
+            }
+        });
+
+        StatusCode::OK.into_response()
+    }
+
+    /// Serve a CONNECT request by handing off the upgraded client stream to the MITM service.
+    async fn serve_mitm<State, S>(
+        &self,
+        ctx: Context<State>,
+        req: Request,
+        mitm: Arc<S>,
+    ) -> Response
+    where
+        State: Clone + Send + Sync + 'static,
+        S: Service<State, Upgraded, Response = (), Error = Infallible>,
+    {
+        let (ctx, mut req) = match self.accept_connect(ctx, req).await {
+            Ok(accepted) => accepted,
+            Err(resp) => return resp,
+        };
+
+        let usage = match &self.usage {
+            Some(ledger) => match check_quota(ledger, &ctx).await {
+                Ok(record) => Some((ledger.clone(), record)),
+                Err(resp) => return resp,
+            },
+            None => None,
+        };
+
+        let stats = self.stats.clone();
+        let exec = ctx.executor().clone();
+        exec.spawn_task(async move {
+            match upgrade::on(&mut req).await {
+                Ok(upgraded) => {
+                    stats.inner.tunnels.fetch_add(1, Ordering::Relaxed);
+                    let start = Instant::now();
+                    let bytes = Arc::new(TunnelBytes::default());
+                    let mut upgraded = CountingIo::new(upgraded, bytes.clone());
+                    if let Some((ledger, record)) = &usage {
+                        upgraded = upgraded.with_quota(ledger, record);
+                    }
+                    let upgraded = Upgraded::new(upgraded, Bytes::new());
+                    let _ = mitm.serve(ctx, upgraded).await;
+                    stats.record_tunnel_bytes(&bytes);
+                    if let Some((ledger, record)) = usage {
+                        record_tunnel_usage(
+                            &ledger,
+                            record,
+                            Some(start.elapsed()),
+                            bytes.up(),
+                            bytes.down(),
+                        );
+                    }
+                }
+                Err(err) => {
+                    tracing::debug!(error = %err, "ForwardProxyService: CONNECT upgrade failed");
+                    if let Some((ledger, record)) = usage {
+                        record_tunnel_usage(&ledger, record, None, 0, 0);
+                    }
+                }
+            }
+        });
+
+        StatusCode::OK.into_response()
+    }
+}
+
+impl<State, D> Service<State, Request> for ForwardProxyService<D, ()>
+where
+    State: Clone + Send + Sync + 'static,
+    D: ProxyDB<Error: Into<BoxError> + Send + Sync + 'static>,
+{
+    type Response = Response;
+    type Error = Infallible;
+
+    async fn serve(
+        &self,
+        ctx: Context<State>,
+        req: Request,
+    ) -> Result<Self::Response, Self::Error> {
+        if req.method() == Method::CONNECT {
+            Ok(self.serve_tunnel(ctx, req).await)
+        } else {
+            Ok(self.serve_plain(ctx, req).await)
+        }
+    }
+}
+
+impl<State, D, S> Service<State, Request> for ForwardProxyService<D, Arc<S>>
+where
+    State: Clone + Send + Sync + 'static,
+    D: ProxyDB<Error: Into<BoxError> + Send + Sync + 'static>,
+    S: Service<State, Upgraded, Response = (), Error = Infallible>,
+{
+    type Response = Response;
+    type Error = Infallible;
+
+    async fn serve(
+        &self,
+        ctx: Context<State>,
+        req: Request,
+    ) -> Result<Self::Response, Self::Error> {
+        if req.method() == Method::CONNECT {
+            Ok(self.serve_mitm(ctx, req, self.mitm.clone()).await)
+        } else {
+            Ok(self.serve_plain(ctx, req).await)
+        }
+    }
+}
+
+/// Record the usage of a CONNECT request in the [`UsageLedger`],
+/// with the duration of its tunnel, if it got established.
+fn record_tunnel_usage(
+    ledger: &UsageLedger,
+    record: UsageRecord,
+    tunnel_duration: Option<Duration>,
+    bytes_up: u64,
+    bytes_down: u64,
+) {
+    ledger.record(&UsageRecord {
+        bytes_up,
+        bytes_down,
+        requests: 1,
+        tunnels: u64::from(tunnel_duration.is_some()),
+        tunnel_duration: tunnel_duration.unwrap_or_default(),
+        ..record
+    });
+}
+
+/// Inner service of the [`ProxyDBService`] used to select the upstream proxy,
+/// returning the [`Context`] in which the proxy address got inserted.
+struct UpstreamSelected;
+
+impl<State, Request> Service<State, Request> for UpstreamSelected
+where
+    State: Clone + Send + Sync + 'static,
+    Request: Send + 'static,
+{
+    type Response = (Context<State>, Request);
+    type Error = Infallible;
+
+    async fn serve(
+        &self,
+        ctx: Context<State>,
+        req: Request,
+    ) -> Result<Self::Response, Self::Error> {
+        Ok((ctx, req))
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+/// Statistics of a [`ForwardProxyService`], shared by all its clones.
+///
+/// The bytes are accounted once a CONNECT tunnel is closed,
+/// including the tunnels which are handed off to a MITM service.
+pub struct ForwardProxyStats {
+    inner: Arc<ForwardProxyStatsInner>,
+}
+
+#[derive(Debug, Default)]
+struct ForwardProxyStatsInner {
+    requests: AtomicU64,
+    tunnels: AtomicU64,
+    bytes_up: AtomicU64,
+    bytes_down: AtomicU64,
+}
+
+impl ForwardProxyStats {
+    fn record_tunnel_bytes(&self, bytes: &TunnelBytes) {
+        self.inner.bytes_up.fetch_add(bytes.up(), Ordering::Relaxed);
+        self.inner
+            .bytes_down
+            .fetch_add(bytes.down(), Ordering::Relaxed);
+    }
+
+    /// Returns the number of plain http requests sent to their target.
+    pub fn requests(&self) -> u64 {
+        self.inner.requests.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of established CONNECT tunnels, including those handed off.
+    pub fn tunnels(&self) -> u64 {
+        self.inner.tunnels.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of bytes sent by clients over their CONNECT tunnels.
+    pub fn bytes_up(&self) -> u64 {
+        self.inner.bytes_up.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of bytes received by clients over their CONNECT tunnels.
+    pub fn bytes_down(&self) -> u64 {
+        self.inner.bytes_down.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::HttpServer;
+    use rama_core::{rt::Executor, service::service_fn};
+    use rama_http_types::{header::VIA, Body, BodyExtractExt};
+    use rama_net::user::UserId;
+    use rama_proxy::accounting::{Quota, UsageKey};
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    async fn spawn_origin() -> std::net::SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server =
+            HttpServer::auto(Executor::default()).service(service_fn(|req: Request| async move {
+                let via = req
+                    .headers()
+                    .get(VIA)
+                    .map(|value| value.to_str().unwrap().to_owned())
+                    .unwrap_or_default();
+                let hop = req.headers().contains_key("x-hop");
+                Ok::<_, Infallible>(
+                    Response::builder()
+                        .header("connection", "x-secret")
+                        .header("x-secret", "hop")
+                        .body(Body::from(format!("via={via};hop={hop}")))
+                        .unwrap(),
+                )
+            }));
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                let server = server.clone();
+                tokio::spawn(async move {
+                    let _ = server.serve(Context::<()>::default(), stream).await;
+                });
+            }
+        });
+        addr
+    }
+
+    #[tokio::test]
+    async fn test_forward_plain_request() {
+        let origin = spawn_origin().await;
+        let proxy = ForwardProxyService::new();
+        let stats = proxy.stats();
+
+        let req = Request::builder()
+            .uri(format!("http://{origin}/"))
+            .header("connection", "x-hop")
+            .header("x-hop", "1")
+            .header(PROXY_AUTHORIZATION, "Basic am9objpzZWNyZXQ=")
+            .body(Body::empty())
+            .unwrap();
+        let resp = proxy.serve(Context::<()>::default(), req).await.unwrap();
+
+        assert_eq!(StatusCode::OK, resp.status());
+        assert!(!resp.headers().contains_key("x-secret"));
+        assert_eq!("1.1 rama", resp.headers().get(VIA).unwrap());
+        assert_eq!(
+            "via=1.1 rama;hop=false",
+            resp.into_body().try_into_string().await.unwrap()
+        );
+        assert_eq!(1, stats.requests());
+    }
+
+    #[tokio::test]
+    async fn test_forward_usage_accounting_and_quota() {
+        let origin = spawn_origin().await;
+        let ledger = UsageLedger::new();
+        let user = UsageKey::User(UserId::Username("john".to_owned()));
+        ledger.set_quota(user.clone(), Quota::new().max_requests(2));
+        let proxy = ForwardProxyService::new().with_usage_ledger(ledger.clone());
+
+        let mut bytes_down = 0;
+        for i in 0..3 {
+            let mut ctx = Context::<()>::default();
+            ctx.insert(UserId::Username("john".to_owned()));
+            let req = Request::builder()
+                .uri(format!("http://{origin}/"))
+                .body(Body::from("hello"))
+                .unwrap();
+            let resp = proxy.serve(ctx, req).await.unwrap();
+            if i == 2 {
+                assert_eq!(StatusCode::TOO_MANY_REQUESTS, resp.status());
+                break;
+            }
+            assert_eq!(StatusCode::OK, resp.status());
+            bytes_down += resp.into_body().try_into_string().await.unwrap().len() as u64;
+
+            // usage is recorded once the request body is dropped by the client connection
+            for _ in 0..100 {
+                if ledger.usage(&user).requests == i + 1 {
+                    break;
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        }
+
+        let usage = ledger.usage(&user);
+        assert_eq!(2, usage.requests);
+        assert_eq!(10, usage.bytes_up);
+        assert_eq!(bytes_down, usage.bytes_down);
+        assert_eq!(0, usage.tunnels);
+    }
+
+    #[tokio::test]
+    async fn test_forward_plain_request_not_absolute_form() {
+        let proxy = ForwardProxyService::new().with_via(None);
+        let req = Request::builder().uri("/").body(Body::empty()).unwrap();
+        let resp = proxy.serve(Context::<()>::default(), req).await.unwrap();
+        assert_eq!(StatusCode::BAD_REQUEST, resp.status());
+        assert_eq!(0, proxy.stats().requests());
+    }
+
+    #[tokio::test]
+    async fn test_forward_connect_unreachable() {
+        // bind and drop a listener to get a port that is (very likely) closed
+        let addr = TcpListener::bind("127.0.0.1:0")
+            .await
+            .unwrap()
+            .local_addr()
+            .unwrap();
+
+        let proxy = ForwardProxyService::new();
+        let req = Request::builder()
+            .method(Method::CONNECT)
+            .uri(addr.to_string())
+            .body(Body::empty())
+            .unwrap();
+        let resp = proxy.serve(Context::<()>::default(), req).await.unwrap();
+        assert_eq!(StatusCode::BAD_GATEWAY, resp.status());
+    }
+
+    #[tokio::test]
+    async fn test_forward_connect_tunnel() {
+        let target = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let target_addr = target.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut stream, _) = target.accept().await.unwrap();
+            let mut buf = [0; 4];
+            stream.read_exact(&mut buf).await.unwrap();
+            assert_eq!(b"ping", &buf);
+            stream.write_all(b"pong!").await.unwrap();
+        });
+
+        let proxy = ForwardProxyService::new();
+        let stats = proxy.stats();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let proxy_addr = listener.local_addr().unwrap();
+        let server = HttpServer::http1().service(proxy);
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let _ = server.serve(Context::<()>::default(), stream).await;
+        });
+
+        let mut client = tokio::net::TcpStream::connect(proxy_addr).await.unwrap();
+        client
+            .write_all(
+                format!("CONNECT {target_addr} HTTP/1.1\r\nHost: {target_addr}\r\n\r\n").as_bytes(),
+            )
+            .await
+            .unwrap();
+
+        let mut head = Vec::new();
+        while !head.ends_with(b"\r\n\r\n") {
+            head.push(client.read_u8().await.unwrap());
+        }
+        assert!(head.starts_with(b"HTTP/1.1 200"));
+
+        client.write_all(b"ping").await.unwrap();
+        let mut buf = [0; 5];
+        client.read_exact(&mut buf).await.unwrap();
+        assert_eq!(b"pong!", &buf);
+        drop(client);
+
+        for _ in 0..100 {
+            if stats.bytes_down() > 0 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(1, stats.tunnels());
+        assert_eq!(4, stats.bytes_up());
+        assert_eq!(5, stats.bytes_down());
+    }
+
+    #[tokio::test]
+    async fn test_forward_connect_tunnel_byte_quota() {
+        let target = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let target_addr = target.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut stream, _) = target.accept().await.unwrap();
+            // keep sending until the proxy closes the tunnel
+            while stream.write_all(&[0; 1024]).await.is_ok() {}
+        });
+
+        let ledger = UsageLedger::new();
+        let user = UsageKey::User(UserId::Username("john".to_owned()));
+        ledger.set_quota(user.clone(), Quota::new().max_bytes(4096));
+        let proxy = ForwardProxyService::new().with_usage_ledger(ledger.clone());
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let proxy_addr = listener.local_addr().unwrap();
+        let server = HttpServer::http1().service(proxy);
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut ctx = Context::<()>::default();
+            ctx.insert(UserId::Username("john".to_owned()));
+            let _ = server.serve(ctx, stream).await;
+        });
+
+        let mut client = tokio::net::TcpStream::connect(proxy_addr).await.unwrap();
+        client
+            .write_all(
+                format!("CONNECT {target_addr} HTTP/1.1\r\nHost: {target_addr}\r\n\r\n").as_bytes(),
+            )
+            .await
+            .unwrap();
+
+        let mut head = Vec::new();
+        while !head.ends_with(b"\r\n\r\n") {
+            head.push(client.read_u8().await.unwrap());
+        }
+        assert!(head.starts_with(b"HTTP/1.1 200"));
+
+        // the tunnel is closed once the quota is exceeded
+        let mut received = Vec::new();
+        let _ = tokio::time::timeout(Duration::from_secs(5), client.read_to_end(&mut received))
+            .await
+            .unwrap();
+        assert!(received.len() >= 4096);
+        assert!(received.len() < 64 * 1024);
+
+        for _ in 0..100 {
+            if ledger.usage(&user).tunnels > 0 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(received.len() as u64, ledger.usage(&user).bytes_down);
+    }
+
+    #[tokio::test]
+    async fn test_forward_connect_mitm() {
+        let proxy = ForwardProxyService::new().mitm(service_fn(
+            |_ctx: Context<()>, _upgraded: Upgraded| async move { Ok::<_, Infallible>(()) },
+        ));
+        let req = Request::builder()
+            .method(Method::CONNECT)
+            .uri("example.com:443")
+            .body(Body::empty())
+            .unwrap();
+        let resp = proxy.serve(Context::<()>::default(), req).await.unwrap();
+        assert_eq!(StatusCode::OK, resp.status());
+    }
+
+    #[tokio::test]
+    async fn test_forward_connect_mitm_counts_bytes() {
+        let ledger = UsageLedger::new();
+        let user = UsageKey::User(UserId::Username("john".to_owned()));
+        let proxy = ForwardProxyService::new()
+            .with_usage_ledger(ledger.clone())
+            .mitm(service_fn(
+                |_ctx: Context<()>, mut upgraded: Upgraded| async move {
+                    let mut buf = [0; 4];
+                    upgraded.read_exact(&mut buf).await.unwrap();
+                    assert_eq!(b"ping", &buf);
+                    upgraded.write_all(b"pong!").await.unwrap();
+                    Ok::<_, Infallible>(())
+                },
+            ));
+        let stats = proxy.stats();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let proxy_addr = listener.local_addr().unwrap();
+        let server = HttpServer::http1().service(proxy);
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut ctx = Context::<()>::default();
+            ctx.insert(UserId::Username("john".to_owned()));
+            let _ = server.serve(ctx, stream).await;
+        });
+
+        let mut client = tokio::net::TcpStream::connect(proxy_addr).await.unwrap();
+        client
+            .write_all(b"CONNECT example.com:443 HTTP/1.1\r\nHost: example.com:443\r\n\r\n")
+            .await
+            .unwrap();
+
+        let mut head = Vec::new();
+        while !head.ends_with(b"\r\n\r\n") {
+            head.push(client.read_u8().await.unwrap());
+        }
+        assert!(head.starts_with(b"HTTP/1.1 200"));
+
+        client.write_all(b"ping").await.unwrap();
+        let mut buf = [0; 5];
+        client.read_exact(&mut buf).await.unwrap();
+        assert_eq!(b"pong!", &buf);
+
+        for _ in 0..100 {
+            if ledger.usage(&user).tunnels > 0 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(1, stats.tunnels());
+        assert_eq!(4, stats.bytes_up());
+        assert_eq!(5, stats.bytes_down());
+
+        let usage = ledger.usage(&user);
+        assert_eq!(4, usage.bytes_up);
+        assert_eq!(5, usage.bytes_down);
+        assert_eq!(1, usage.tunnels);
+    }
+
+    #[tokio::test]
+    async fn test_forward_upstream_proxy_db_required_filter() {
+        let proxy = ForwardProxyService::new()
+            .proxy_db(())
+            .with_proxy_filter_mode(ProxyFilterMode::Required);
+        let req = Request::builder()
+            .uri("http://example.com/")
+            .body(Body::empty())
+            .unwrap();
+        let resp = proxy.serve(Context::<()>::default(), req).await.unwrap();
+        assert_eq!(StatusCode::BAD_GATEWAY, resp.status());
+
+        // an existing proxy address is used as-is when no db is configured
+        let mut ctx = Context::<()>::default();
+        ctx.insert(ProxyAddress::try_from("http://127.0.0.1:1").unwrap());
+        let req = Request::builder()
+            .method(Method::CONNECT)
+            .uri("example.com:443")
+            .body(Body::empty())
+            .unwrap();
+        let resp = ForwardProxyService::new().serve(ctx, req).await.unwrap();
+        assert_eq!(StatusCode::BAD_GATEWAY, resp.status());
+    }
+
+    #[tokio::test]
+    async fn test_forward_upstream_proxy_outlier() {
+        let health = HealthRegistry::new().with_outlier_threshold(1);
+        let proxy = ForwardProxyService::new().with_health(health.clone());
+
+        let mut ctx = Context::<()>::default();
+        ctx.insert(ProxyAddress::try_from("http://127.0.0.1:1").unwrap());
+        let req = Request::builder()
+            .method(Method::CONNECT)
+            .uri("example.com:443")
+            .body(Body::empty())
+            .unwrap();
+        let resp = proxy.serve(ctx, req).await.unwrap();
+        assert_eq!(StatusCode::BAD_GATEWAY, resp.status());
+
+        let authority = Authority::try_from("127.0.0.1:1").unwrap();
+        assert!(!health.is_healthy(&authority));
+    }
+}
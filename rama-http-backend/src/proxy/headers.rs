@@ -0,0 +1,48 @@
+//! Header utilities shared by the proxy services.
+
+use rama_http_types::{header::VIA, HeaderMap, HeaderValue, Version};
+
+/// Append a `Via` header ([RFC 9110, section 7.6.3]) to the given headers,
+/// identifying this intermediary by the given pseudonym for a message
+/// received using the given http version.
+///
+/// [RFC 9110, section 7.6.3]: https://datatracker.ietf.org/doc/html/rfc9110#section-7.6.3
+pub(crate) fn append_via(headers: &mut HeaderMap, version: Version, pseudonym: &HeaderValue) {
+    let protocol = match version {
+        Version::HTTP_09 => "0.9",
+        Version::HTTP_10 => "1.0",
+        Version::HTTP_11 => "1.1",
+        Version::HTTP_2 => "2",
+        Version::HTTP_3 => "3",
+        _ => return,
+    };
+    let mut value = Vec::with_capacity(protocol.len() + 1 + pseudonym.len());
+    value.extend_from_slice(protocol.as_bytes());
+    value.push(b' ');
+    value.extend_from_slice(pseudonym.as_bytes());
+    match HeaderValue::from_bytes(&value) {
+        Ok(value) => {
+            headers.append(VIA, value);
+        }
+        Err(err) => {
+            tracing::debug!(error = %err, "failed to create via header value");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_append_via() {
+        let pseudonym = HeaderValue::from_static("rama");
+        let mut headers = HeaderMap::new();
+        headers.insert(VIA, HeaderValue::from_static("1.0 fred"));
+        append_via(&mut headers, Version::HTTP_11, &pseudonym);
+        append_via(&mut headers, Version::HTTP_2, &pseudonym);
+
+        let values: Vec<_> = headers.get_all(VIA).iter().collect();
+        assert_eq!(vec!["1.0 fred", "1.1 rama", "2 rama"], values);
+    }
+}
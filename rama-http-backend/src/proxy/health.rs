@@ -0,0 +1,779 @@
+use crate::client::HttpClient;
+use rama_core::{
+    error::{BoxError, ErrorContext, OpaqueError},
+    rt::Executor,
+    Context, Service,
+};
+use rama_http_types::{Body, Method, Request, Scheme, StatusCode, Uri};
+use rama_net::address::Authority;
+#[cfg(feature = "proxy")]
+use rama_proxy::{Proxy, ProxyContext, ProxyDB, ProxyFilter, ProxyQueryPredicate};
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{Arc, Mutex, PoisonError},
+    time::{Duration, Instant},
+};
+
+#[cfg(any(feature = "rustls", feature = "boring"))]
+use rama_tls::std::client::{TlsConnector, TlsConnectorData};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+/// The health of a target, as tracked by a [`HealthRegistry`].
+pub enum HealthStatus {
+    /// The target is available for selection.
+    Healthy,
+    /// The target failed its active health checks,
+    /// and is unavailable until it passes them again.
+    Unhealthy,
+    /// The target failed too many requests in a row,
+    /// and is unavailable until its ejection expires.
+    Ejected,
+}
+
+#[derive(Debug, Clone)]
+/// A registry of the health of targets (upstreams or proxies), identified by their [`Authority`].
+///
+/// The health is updated by:
+///
+/// - active health checks, run periodically by a [`HealthChecker`]:
+///   a target goes down after [`HealthRegistry::with_unhealthy_threshold`] failed probes in a row,
+///   and only comes back up after [`HealthRegistry::with_healthy_threshold`] passed probes in a row;
+/// - passive outlier detection, from the outcome of real traffic:
+///   a target is ejected for [`HealthRegistry::with_ejection_duration`]
+///   after [`HealthRegistry::with_outlier_threshold`] failed requests in a row.
+///
+/// Targets without any recorded result are considered healthy.
+///
+/// The registry can be used as a [`ProxyQueryPredicate`], to only select healthy proxies
+/// from a [`ProxyDB`] (see [`HealthyProxyDB`]), and as a filter for an [`UpstreamPool`].
+///
+/// The recorded health is shared by all its clones.
+///
+/// [`UpstreamPool`]: super::UpstreamPool
+pub struct HealthRegistry {
+    healthy_threshold: u32,
+    unhealthy_threshold: u32,
+    outlier_threshold: u32,
+    ejection_duration: Duration,
+    targets: Arc<Mutex<HashMap<Authority, TargetHealth>>>,
+}
+
+#[derive(Debug)]
+struct TargetHealth {
+    healthy: bool,
+    probe_streak: u32,
+    failure_streak: u32,
+    ejected_until: Option<Instant>,
+}
+
+impl Default for TargetHealth {
+    fn default() -> Self {
+        Self {
+            healthy: true,
+            probe_streak: 0,
+            failure_streak: 0,
+            ejected_until: None,
+        }
+    }
+}
+
+impl TargetHealth {
+    fn status(&self, now: Instant) -> HealthStatus {
+        if !self.healthy {
+            HealthStatus::Unhealthy
+        } else if self.ejected_until.is_some_and(|until| until > now) {
+            HealthStatus::Ejected
+        } else {
+            HealthStatus::Healthy
+        }
+    }
+}
+
+impl Default for HealthRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HealthRegistry {
+    /// Create a new [`HealthRegistry`], using the default thresholds.
+    pub fn new() -> Self {
+        Self {
+            healthy_threshold: 2,
+            unhealthy_threshold: 3,
+            outlier_threshold: 5,
+            ejection_duration: Duration::from_secs(30),
+            targets: Default::default(),
+        }
+    }
+
+    /// Set the number of passed probes in a row required to mark an unhealthy target healthy.
+    ///
+    /// Defaults to `2`.
+    pub fn with_healthy_threshold(mut self, threshold: u32) -> Self {
+        self.healthy_threshold = threshold.max(1);
+        self
+    }
+
+    /// Set the number of passed probes in a row required to mark an unhealthy target healthy.
+    ///
+    /// Defaults to `2`.
+    pub fn set_healthy_threshold(&mut self, threshold: u32) -> &mut Self {
+        self.healthy_threshold = threshold.max(1);
+        self
+    }
+
+    /// Set the number of failed probes in a row required to mark a healthy target unhealthy.
+    ///
+    /// Defaults to `3`.
+    pub fn with_unhealthy_threshold(mut self, threshold: u32) -> Self {
+        self.unhealthy_threshold = threshold.max(1);
+        self
+    }
+
+    /// Set the number of failed probes in a row required to mark a healthy target unhealthy.
+    ///
+    /// Defaults to `3`.
+    pub fn set_unhealthy_threshold(&mut self, threshold: u32) -> &mut Self {
+        self.unhealthy_threshold = threshold.max(1);
+        self
+    }
+
+    /// Set the number of failed requests in a row after which a target gets ejected,
+    /// or `0` to disable passive outlier detection.
+    ///
+    /// Defaults to `5`.
+    pub fn with_outlier_threshold(mut self, threshold: u32) -> Self {
+        self.outlier_threshold = threshold;
+        self
+    }
+
+    /// Set the number of failed requests in a row after which a target gets ejected,
+    /// or `0` to disable passive outlier detection.
+    ///
+    /// Defaults to `5`.
+    pub fn set_outlier_threshold(&mut self, threshold: u32) -> &mut Self {
+        self.outlier_threshold = threshold;
+        self
+    }
+
+    /// Set the duration for which an outlier is ejected.
+    ///
+    /// Defaults to 30 seconds.
+    pub fn with_ejection_duration(mut self, duration: Duration) -> Self {
+        self.ejection_duration = duration;
+        self
+    }
+
+    /// Set the duration for which an outlier is ejected.
+    ///
+    /// Defaults to 30 seconds.
+    pub fn set_ejection_duration(&mut self, duration: Duration) -> &mut Self {
+        self.ejection_duration = duration;
+        self
+    }
+
+    fn with_target<T>(&self, target: &Authority, f: impl FnOnce(&mut TargetHealth) -> T) -> T {
+        let mut targets = self.targets.lock().unwrap_or_else(PoisonError::into_inner);
+        match targets.get_mut(target) {
+            Some(health) => f(health),
+            None => f(targets.entry(target.clone()).or_default()),
+        }
+    }
+
+    /// Returns the [`HealthStatus`] of the given target.
+    pub fn status(&self, target: &Authority) -> HealthStatus {
+        self.targets
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(target)
+            .map(|health| health.status(Instant::now()))
+            .unwrap_or(HealthStatus::Healthy)
+    }
+
+    /// Returns true in case the given target is available for selection.
+    pub fn is_healthy(&self, target: &Authority) -> bool {
+        self.status(target) == HealthStatus::Healthy
+    }
+
+    /// Record the result of an active health check of the given target.
+    pub fn record_probe(&self, target: &Authority, passed: bool) {
+        let (healthy_threshold, unhealthy_threshold) =
+            (self.healthy_threshold, self.unhealthy_threshold);
+        self.with_target(target, |health| {
+            if passed == health.healthy {
+                health.probe_streak = 0;
+                return;
+            }
+            health.probe_streak += 1;
+            let threshold = if passed {
+                healthy_threshold
+            } else {
+                unhealthy_threshold
+            };
+            if health.probe_streak >= threshold {
+                health.healthy = passed;
+                health.probe_streak = 0;
+                if passed {
+                    tracing::info!(%target, "health: target is back up");
+                } else {
+                    tracing::warn!(%target, "health: target is down");
+                }
+            }
+        })
+    }
+
+    /// Record the outcome of a request sent to the given target,
+    /// used for passive outlier detection.
+    pub fn record_outcome(&self, target: &Authority, success: bool) {
+        if self.outlier_threshold == 0 {
+            return;
+        }
+        let (outlier_threshold, ejection_duration) =
+            (self.outlier_threshold, self.ejection_duration);
+        self.with_target(target, |health| {
+            if success {
+                health.failure_streak = 0;
+                return;
+            }
+            health.failure_streak += 1;
+            if health.failure_streak >= outlier_threshold {
+                health.failure_streak = 0;
+                health.ejected_until = Some(Instant::now() + ejection_duration);
+                tracing::warn!(
+                    %target,
+                    ejection_duration = ?ejection_duration,
+                    "health: outlier target ejected"
+                );
+            }
+        })
+    }
+}
+
+#[cfg(feature = "proxy")]
+impl ProxyQueryPredicate for HealthRegistry {
+    fn execute(&self, proxy: &Proxy) -> bool {
+        self.is_healthy(&proxy.address.authority)
+    }
+}
+
+#[cfg(feature = "proxy")]
+#[derive(Debug, Clone)]
+/// A [`ProxyDB`] which only returns the proxies of the inner [`ProxyDB`]
+/// that are healthy according to its [`HealthRegistry`].
+pub struct HealthyProxyDB<D> {
+    inner: D,
+    registry: HealthRegistry,
+}
+
+#[cfg(feature = "proxy")]
+impl<D> HealthyProxyDB<D> {
+    /// Create a new [`HealthyProxyDB`], filtering the proxies of the given [`ProxyDB`].
+    pub const fn new(inner: D, registry: HealthRegistry) -> Self {
+        Self { inner, registry }
+    }
+
+    /// Returns the [`HealthRegistry`] used to filter the proxies.
+    pub fn registry(&self) -> &HealthRegistry {
+        &self.registry
+    }
+}
+
+#[cfg(feature = "proxy")]
+impl<D: ProxyDB> ProxyDB for HealthyProxyDB<D> {
+    type Error = D::Error;
+
+    async fn get_proxy_if(
+        &self,
+        ctx: ProxyContext,
+        filter: ProxyFilter,
+        predicate: impl ProxyQueryPredicate,
+    ) -> Result<Proxy, Self::Error> {
+        let registry = self.registry.clone();
+        self.inner
+            .get_proxy_if(ctx, filter, move |proxy: &Proxy| {
+                registry.execute(proxy) && predicate.execute(proxy)
+            })
+            .await
+    }
+
+    async fn get_proxy(
+        &self,
+        ctx: ProxyContext,
+        filter: ProxyFilter,
+    ) -> Result<Proxy, Self::Error> {
+        self.inner
+            .get_proxy_if(ctx, filter, self.registry.clone())
+            .await
+    }
+}
+
+/// Runs an active health check probe periodically for a set of targets,
+/// recording the results in a [`HealthRegistry`].
+///
+/// The probe is a [`Service`] for the [`Authority`] of the target,
+/// which passes in case it returns `Ok` within the timeout,
+/// e.g. a [`TcpProbe`], [`HttpProbe`] or `TlsProbe`.
+pub struct HealthChecker<P> {
+    registry: HealthRegistry,
+    probe: Arc<P>,
+    interval: Duration,
+    timeout: Duration,
+}
+
+impl<P> HealthChecker<P> {
+    /// Create a new [`HealthChecker`], recording the results of the given probe in the registry.
+    pub fn new(registry: HealthRegistry, probe: P) -> Self {
+        Self {
+            registry,
+            probe: Arc::new(probe),
+            interval: Duration::from_secs(10),
+            timeout: Duration::from_secs(5),
+        }
+    }
+
+    /// Set the interval between the probes of a target. Defaults to 10 seconds.
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Set the interval between the probes of a target. Defaults to 10 seconds.
+    pub fn set_interval(&mut self, interval: Duration) -> &mut Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Set the timeout after which a probe fails. Defaults to 5 seconds.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Set the timeout after which a probe fails. Defaults to 5 seconds.
+    pub fn set_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.timeout = timeout;
+        self
+    }
+}
+
+impl<P: fmt::Debug> fmt::Debug for HealthChecker<P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HealthChecker")
+            .field("registry", &self.registry)
+            .field("probe", &self.probe)
+            .field("interval", &self.interval)
+            .field("timeout", &self.timeout)
+            .finish()
+    }
+}
+
+impl<P> Clone for HealthChecker<P> {
+    fn clone(&self) -> Self {
+        Self {
+            registry: self.registry.clone(),
+            probe: self.probe.clone(),
+            interval: self.interval,
+            timeout: self.timeout,
+        }
+    }
+}
+
+impl<P> HealthChecker<P>
+where
+    P: Service<(), Authority, Error: Into<BoxError>>,
+{
+    /// Probe the given target once, recording and returning the result.
+    pub async fn check(&self, target: &Authority) -> bool {
+        let result = tokio::time::timeout(
+            self.timeout,
+            self.probe.serve(Context::default(), target.clone()),
+        )
+        .await;
+        let passed = match result {
+            Ok(Ok(_)) => true,
+            Ok(Err(err)) => {
+                let err = err.into();
+                tracing::debug!(%target, error = %err, "health: probe failed");
+                false
+            }
+            Err(_) => {
+                tracing::debug!(%target, "health: probe timed out");
+                false
+            }
+        };
+        self.registry.record_probe(target, passed);
+        passed
+    }
+
+    /// Spawn a task for each of the given targets, probing it every interval.
+    ///
+    /// The tasks run until the graceful shutdown of the [`Executor`] is triggered,
+    /// or forever in case it has no shutdown guard.
+    pub fn spawn(&self, exec: &Executor, targets: impl IntoIterator<Item = Authority>) {
+        for target in targets {
+            let checker = self.clone();
+            let guard = exec.guard().cloned();
+            exec.spawn_task(async move {
+                let mut interval = tokio::time::interval(checker.interval);
+                interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+                loop {
+                    match &guard {
+                        Some(guard) => tokio::select! {
+                            _ = guard.cancelled() => return,
+                            _ = interval.tick() => (),
+                        },
+                        None => {
+                            interval.tick().await;
+                        }
+                    }
+                    checker.check(&target).await;
+                }
+            });
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+#[non_exhaustive]
+/// A health check probe which passes in case a TCP connection
+/// can be established to the target.
+pub struct TcpProbe;
+
+impl TcpProbe {
+    /// Create a new [`TcpProbe`].
+    pub const fn new() -> Self {
+        Self
+    }
+}
+
+impl<State> Service<State, Authority> for TcpProbe
+where
+    State: Clone + Send + Sync + 'static,
+{
+    type Response = ();
+    type Error = OpaqueError;
+
+    async fn serve(
+        &self,
+        ctx: Context<State>,
+        target: Authority,
+    ) -> Result<Self::Response, Self::Error> {
+        rama_tcp::client::default_tcp_connect(&ctx, target).await?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+/// A health check probe which passes in case a `GET` request
+/// for its path on the target returns the expected status code.
+pub struct HttpProbe {
+    client: HttpClient,
+    scheme: Scheme,
+    path: String,
+    expected_status: Option<StatusCode>,
+}
+
+impl Default for HttpProbe {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HttpProbe {
+    /// Create a new [`HttpProbe`], requesting `http://<target>/`
+    /// and expecting a success (2xx) status code.
+    pub fn new() -> Self {
+        Self {
+            client: HttpClient::default(),
+            scheme: Scheme::HTTP,
+            path: "/".to_owned(),
+            expected_status: None,
+        }
+    }
+
+    /// Use the given [`HttpClient`] to send the probe requests.
+    pub fn with_http_client(mut self, client: HttpClient) -> Self {
+        self.client = client;
+        self
+    }
+
+    /// Use the given [`HttpClient`] to send the probe requests.
+    pub fn set_http_client(&mut self, client: HttpClient) -> &mut Self {
+        self.client = client;
+        self
+    }
+
+    /// Set the [`Scheme`] used to request the target. Defaults to `http`.
+    pub fn with_scheme(mut self, scheme: Scheme) -> Self {
+        self.scheme = scheme;
+        self
+    }
+
+    /// Set the [`Scheme`] used to request the target. Defaults to `http`.
+    pub fn set_scheme(&mut self, scheme: Scheme) -> &mut Self {
+        self.scheme = scheme;
+        self
+    }
+
+    /// Set the path (and query) requested on the target, e.g. `/healthz`. Defaults to `/`.
+    pub fn with_path(mut self, path: impl Into<String>) -> Self {
+        self.path = path.into();
+        self
+    }
+
+    /// Set the path (and query) requested on the target, e.g. `/healthz`. Defaults to `/`.
+    pub fn set_path(&mut self, path: impl Into<String>) -> &mut Self {
+        self.path = path.into();
+        self
+    }
+
+    /// Expect the given status code, instead of any success (2xx) status code.
+    pub fn with_expected_status(mut self, status: StatusCode) -> Self {
+        self.expected_status = Some(status);
+        self
+    }
+
+    /// Expect the given status code, instead of any success (2xx) status code.
+    pub fn set_expected_status(&mut self, status: StatusCode) -> &mut Self {
+        self.expected_status = Some(status);
+        self
+    }
+}
+
+impl<State> Service<State, Authority> for HttpProbe
+where
+    State: Clone + Send + Sync + 'static,
+{
+    type Response = ();
+    type Error = OpaqueError;
+
+    async fn serve(
+        &self,
+        ctx: Context<State>,
+        target: Authority,
+    ) -> Result<Self::Response, Self::Error> {
+        let uri = Uri::builder()
+            .scheme(self.scheme.clone())
+            .authority(target.to_string())
+            .path_and_query(self.path.as_str())
+            .build()
+            .context("build health check uri")?;
+        let req = Request::builder()
+            .method(Method::GET)
+            .uri(uri)
+            .body(Body::empty())
+            .context("build health check request")?;
+
+        let status = self.client.serve(ctx, req).await?.status();
+        let passed = match self.expected_status {
+            Some(expected) => status == expected,
+            None => status.is_success(),
+        };
+        if passed {
+            Ok(())
+        } else {
+            Err(OpaqueError::from_display(format!(
+                "unexpected health check status: {status}"
+            )))
+        }
+    }
+}
+
+#[cfg(any(feature = "rustls", feature = "boring"))]
+#[derive(Debug, Clone, Default)]
+/// A health check probe which passes in case a TLS handshake
+/// with the target succeeds.
+pub struct TlsProbe {
+    connector_data: Option<TlsConnectorData>,
+}
+
+#[cfg(any(feature = "rustls", feature = "boring"))]
+impl TlsProbe {
+    /// Create a new [`TlsProbe`], using the default [`TlsConnectorData`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Use the given [`TlsConnectorData`] for the handshake.
+    pub fn with_connector_data(mut self, connector_data: TlsConnectorData) -> Self {
+        self.connector_data = Some(connector_data);
+        self
+    }
+
+    /// Use the given [`TlsConnectorData`] for the handshake.
+    pub fn set_connector_data(&mut self, connector_data: TlsConnectorData) -> &mut Self {
+        self.connector_data = Some(connector_data);
+        self
+    }
+}
+
+#[cfg(any(feature = "rustls", feature = "boring"))]
+impl<State> Service<State, Authority> for TlsProbe
+where
+    State: Clone + Send + Sync + 'static,
+{
+    type Response = ();
+    type Error = OpaqueError;
+
+    async fn serve(
+        &self,
+        ctx: Context<State>,
+        target: Authority,
+    ) -> Result<Self::Response, Self::Error> {
+        use rama_net::client::ConnectorService;
+
+        let connector_data = match &self.connector_data {
+            Some(connector_data) => connector_data.clone(),
+            None => TlsConnectorData::new().context("create health check tls connector data")?,
+        };
+        TlsConnector::secure(rama_tcp::client::service::TcpConnector::new())
+            .with_connector_data(connector_data)
+            .connect(ctx, rama_tcp::client::Request::new(target))
+            .await
+            .map_err(OpaqueError::from_boxed)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rama_core::service::service_fn;
+    use std::convert::Infallible;
+
+    fn target() -> Authority {
+        Authority::try_from("10.0.0.1:8080").unwrap()
+    }
+
+    #[test]
+    fn test_probe_hysteresis() {
+        let registry = HealthRegistry::new()
+            .with_unhealthy_threshold(2)
+            .with_healthy_threshold(3);
+        let target = target();
+        assert_eq!(HealthStatus::Healthy, registry.status(&target));
+
+        registry.record_probe(&target, false);
+        assert!(registry.is_healthy(&target));
+        registry.record_probe(&target, true);
+        registry.record_probe(&target, false);
+        assert!(registry.is_healthy(&target), "streak got reset");
+        registry.record_probe(&target, false);
+        assert_eq!(HealthStatus::Unhealthy, registry.status(&target));
+
+        registry.record_probe(&target, true);
+        registry.record_probe(&target, true);
+        assert!(!registry.is_healthy(&target));
+        registry.record_probe(&target, true);
+        assert!(registry.is_healthy(&target));
+    }
+
+    #[test]
+    fn test_outlier_ejection() {
+        let registry = HealthRegistry::new()
+            .with_outlier_threshold(2)
+            .with_ejection_duration(Duration::from_millis(50));
+        let target = target();
+
+        registry.record_outcome(&target, false);
+        registry.record_outcome(&target, true);
+        registry.record_outcome(&target, false);
+        assert!(registry.is_healthy(&target), "streak got reset");
+        registry.record_outcome(&target, false);
+        assert_eq!(HealthStatus::Ejected, registry.status(&target));
+
+        std::thread::sleep(Duration::from_millis(60));
+        assert!(registry.is_healthy(&target));
+    }
+
+    #[test]
+    fn test_outlier_detection_disabled() {
+        let registry = HealthRegistry::new().with_outlier_threshold(0);
+        let target = target();
+        for _ in 0..10 {
+            registry.record_outcome(&target, false);
+        }
+        assert!(registry.is_healthy(&target));
+    }
+
+    #[cfg(feature = "proxy")]
+    #[tokio::test]
+    async fn test_healthy_proxy_db() {
+        let proxy = Proxy {
+            id: "1".try_into().unwrap(),
+            address: "10.0.0.1:8080".try_into().unwrap(),
+            tcp: true,
+            udp: false,
+            http: true,
+            https: false,
+            socks5: false,
+            socks5h: false,
+            datacenter: true,
+            residential: false,
+            mobile: false,
+            pool_id: None,
+            continent: None,
+            country: None,
+            state: None,
+            city: None,
+            carrier: None,
+            asn: None,
+            weight: None,
+            via: None,
+        };
+        let registry = HealthRegistry::new().with_unhealthy_threshold(1);
+        let db = HealthyProxyDB::new(proxy, registry.clone());
+        let ctx = ProxyContext {
+            protocol: rama_net::transport::TransportProtocol::Tcp,
+            selection_strategy: None,
+            app_protocol: None,
+            authority: None,
+            user: None,
+        };
+
+        assert!(db
+            .get_proxy(ctx.clone(), ProxyFilter::default())
+            .await
+            .is_ok());
+        registry.record_probe(&target(), false);
+        assert!(db.get_proxy(ctx, ProxyFilter::default()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_health_checker() {
+        let registry = HealthRegistry::new().with_unhealthy_threshold(1);
+        let checker = HealthChecker::new(
+            registry.clone(),
+            service_fn(|target: Authority| async move {
+                if target.port() == 8080 {
+                    Ok::<_, Infallible>(())
+                } else {
+                    std::future::pending().await
+                }
+            }),
+        )
+        .with_timeout(Duration::from_millis(10));
+
+        assert!(checker.check(&target()).await);
+        let slow = Authority::try_from("10.0.0.1:9090").unwrap();
+        assert!(!checker.check(&slow).await);
+        assert_eq!(HealthStatus::Unhealthy, registry.status(&slow));
+    }
+
+    #[tokio::test]
+    async fn test_tcp_probe() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        TcpProbe::new()
+            .serve(Context::<()>::default(), addr.into())
+            .await
+            .unwrap();
+        drop(listener);
+        assert!(TcpProbe::new()
+            .serve(Context::<()>::default(), addr.into())
+            .await
+            .is_err());
+    }
+}
@@ -0,0 +1,39 @@
+//! Http proxy services, serving the requests of http clients on their behalf.
+//!
+//! See [`ForwardProxyService`] for a forward proxy, serving both plain
+//! http requests in absolute-form and CONNECT requests, and [`ReverseProxyService`]
+//! for a reverse proxy, load balancing requests over an [`UpstreamPool`].
+//!
+//! The health of upstreams and upstream proxies can be tracked using a [`HealthRegistry`],
+//! updated by the active probes of a [`HealthChecker`] and by passive outlier detection.
+//!
+//! The [`ForwardProxyService`] and [`HealthyProxyDB`] require the `proxy` feature.
+
+#[cfg(feature = "proxy")]
+mod accounting;
+#[cfg(feature = "proxy")]
+mod headers;
+
+#[cfg(feature = "proxy")]
+mod forward;
+#[cfg(feature = "proxy")]
+#[doc(inline)]
+pub use forward::{ForwardProxyService, ForwardProxyStats};
+
+mod health;
+#[cfg(feature = "proxy")]
+#[doc(inline)]
+pub use health::HealthyProxyDB;
+#[cfg(any(feature = "rustls", feature = "boring"))]
+#[doc(inline)]
+pub use health::TlsProbe;
+#[doc(inline)]
+pub use health::{HealthChecker, HealthRegistry, HealthStatus, HttpProbe, TcpProbe};
+
+mod upstream;
+#[doc(inline)]
+pub use upstream::{BalanceStrategy, HashKey, Upstream, UpstreamPool};
+
+mod reverse;
+#[doc(inline)]
+pub use reverse::ReverseProxyService;
@@ -0,0 +1,515 @@
+use super::upstream::{Upstream, UpstreamPool};
+use crate::client::HttpClient;
+use rama_core::{Context, Service};
+use rama_http_core::{ext::Protocol, upgrade::OnUpgrade};
+use rama_http_types::{
+    header::{CONNECTION, HOST, UPGRADE},
+    headers::remove_hop_by_hop_headers,
+    HeaderValue, IntoResponse, Method, Request, Response, StatusCode, Version,
+};
+use rama_net::{http::RequestContext, transport::TransportContext};
+use rama_tcp::utils::is_connection_error;
+use std::convert::Infallible;
+
+/// A http reverse proxy [`Service`], serving requests using
+/// an [`Upstream`] selected from its [`UpstreamPool`].
+///
+/// - The hop-by-hop headers ([RFC 9110, section 7.6.1]) are removed
+///   from both the request and the response.
+/// - The path of the request can be rewritten using [`ReverseProxyService::with_rewrite_path_prefix`].
+/// - The `Host` header is replaced by the authority of the selected [`Upstream`],
+///   unless [`ReverseProxyService::with_preserve_host`] is enabled.
+/// - Upgrade requests, such as WebSocket handshakes, are passed through
+///   and the upgraded streams are copied bidirectionally once switched.
+///
+/// Requests are sent to the upstream using the http version of the incoming request,
+/// unless another version is defined using [`ReverseProxyService::with_upstream_version`],
+/// or negotiated using ALPN in case of an https [`Upstream`]. The response
+/// is returned using the http version of the incoming request.
+///
+/// A `503 Service Unavailable` response is returned in case no [`Upstream`]
+/// is available, and a `502 Bad Gateway` response in case the upstream request failed.
+/// In case the [`UpstreamPool`] has a [`HealthRegistry`], these failures, as well as
+/// the `502`, `503` and `504` responses of the upstream, are recorded as outliers.
+///
+/// The `Forwarded` (or `X-Forwarded-*`) headers are not added by this service,
+/// wrap it in a `SetForwardedHeadersLayer` (`rama-http`) to do so.
+///
+/// [RFC 9110, section 7.6.1]: https://datatracker.ietf.org/doc/html/rfc9110#section-7.6.1
+/// [`HealthRegistry`]: super::HealthRegistry
+#[derive(Debug, Clone)]
+pub struct ReverseProxyService {
+    client: HttpClient,
+    pool: UpstreamPool,
+    path_prefix: Option<(String, String)>,
+    preserve_host: bool,
+    upstream_version: Option<Version>,
+}
+
+impl ReverseProxyService {
+    /// Create a new [`ReverseProxyService`], proxying to the upstreams of the given [`UpstreamPool`].
+    pub fn new(pool: UpstreamPool) -> Self {
+        Self {
+            client: HttpClient::default(),
+            pool,
+            path_prefix: None,
+            preserve_host: false,
+            upstream_version: None,
+        }
+    }
+
+    /// Use the given [`HttpClient`] to send the requests to the upstreams.
+    pub fn with_http_client(mut self, client: HttpClient) -> Self {
+        self.client = client;
+        self
+    }
+
+    /// Use the given [`HttpClient`] to send the requests to the upstreams.
+    pub fn set_http_client(&mut self, client: HttpClient) -> &mut Self {
+        self.client = client;
+        self
+    }
+
+    /// Replace the path prefix `from` of incoming requests by `to`,
+    /// e.g. `/api` by `/` to serve `/api/users` from `/users` upstream.
+    ///
+    /// The prefix only matches on a path segment boundary,
+    /// requests with a path not starting with it are proxied as-is.
+    pub fn with_rewrite_path_prefix(
+        mut self,
+        from: impl Into<String>,
+        to: impl Into<String>,
+    ) -> Self {
+        self.path_prefix = Some((from.into(), to.into()));
+        self
+    }
+
+    /// Replace the path prefix `from` of incoming requests by `to`,
+    /// e.g. `/api` by `/` to serve `/api/users` from `/users` upstream.
+    ///
+    /// The prefix only matches on a path segment boundary,
+    /// requests with a path not starting with it are proxied as-is.
+    pub fn set_rewrite_path_prefix(
+        &mut self,
+        from: impl Into<String>,
+        to: impl Into<String>,
+    ) -> &mut Self {
+        self.path_prefix = Some((from.into(), to.into()));
+        self
+    }
+
+    /// Preserve the `Host` of the incoming request, instead of
+    /// using the authority of the selected [`Upstream`]. Disabled by default.
+    pub fn with_preserve_host(mut self, preserve: bool) -> Self {
+        self.preserve_host = preserve;
+        self
+    }
+
+    /// Preserve the `Host` of the incoming request, instead of
+    /// using the authority of the selected [`Upstream`]. Disabled by default.
+    pub fn set_preserve_host(&mut self, preserve: bool) -> &mut Self {
+        self.preserve_host = preserve;
+        self
+    }
+
+    /// Send the requests to the upstreams using the given http version,
+    /// instead of the http version of the incoming request,
+    /// e.g. [`Version::HTTP_11`] for plain text upstreams which do not support h2.
+    ///
+    /// The version negotiated using ALPN still takes precedence for https upstreams.
+    pub fn with_upstream_version(mut self, version: Option<Version>) -> Self {
+        self.upstream_version = version;
+        self
+    }
+
+    /// Send the requests to the upstreams using the given http version,
+    /// instead of the http version of the incoming request,
+    /// e.g. [`Version::HTTP_11`] for plain text upstreams which do not support h2.
+    ///
+    /// The version negotiated using ALPN still takes precedence for https upstreams.
+    pub fn set_upstream_version(&mut self, version: Option<Version>) -> &mut Self {
+        self.upstream_version = version;
+        self
+    }
+
+    /// Returns the [`UpstreamPool`] of this [`ReverseProxyService`].
+    pub fn pool(&self) -> &UpstreamPool {
+        &self.pool
+    }
+
+    /// Create the path and query of the upstream request.
+    fn upstream_path_and_query<Body>(&self, req: &Request<Body>) -> String {
+        let path = req.uri().path();
+        let path = match &self.path_prefix {
+            Some((from, to)) => rewrite_path_prefix(path, from, to),
+            None => None,
+        }
+        .unwrap_or_else(|| path.to_owned());
+        match req.uri().query() {
+            Some(query) => format!("{path}?{query}"),
+            None => path,
+        }
+    }
+
+    /// Prepare the incoming request to be sent to the given [`Upstream`],
+    /// returning the upgrade of the incoming request in case it is an upgrade request.
+    fn prepare_request(
+        &self,
+        upstream: &Upstream,
+        req: &mut Request,
+    ) -> Result<Option<OnUpgrade>, StatusCode> {
+        let uri = upstream
+            .uri(&self.upstream_path_and_query(req))
+            .map_err(|err| {
+                tracing::debug!(error = %err, "ReverseProxyService: invalid upstream uri");
+                StatusCode::BAD_GATEWAY
+            })?;
+
+        let host = req.headers().get(HOST).cloned().or_else(|| {
+            req.uri()
+                .authority()
+                .and_then(|authority| HeaderValue::from_str(authority.as_str()).ok())
+        });
+
+        let h1_upgrade = h1_upgrade(req);
+        let downstream_upgrade = if h1_upgrade.is_some() || is_extended_connect(req) {
+            req.extensions_mut().remove::<OnUpgrade>()
+        } else {
+            None
+        };
+
+        remove_hop_by_hop_headers(req.headers_mut());
+        if let Some(upgrade) = h1_upgrade {
+            insert_upgrade_headers(req.headers_mut(), upgrade);
+        }
+        match host {
+            Some(host) if self.preserve_host => {
+                req.headers_mut().insert(HOST, host);
+            }
+            _ => {
+                req.headers_mut().remove(HOST);
+            }
+        }
+
+        *req.uri_mut() = uri;
+        if let Some(version) = self.upstream_version {
+            *req.version_mut() = version;
+        }
+
+        Ok(downstream_upgrade)
+    }
+}
+
+impl<State> Service<State, Request> for ReverseProxyService
+where
+    State: Clone + Send + Sync + 'static,
+{
+    type Response = Response;
+    type Error = Infallible;
+
+    async fn serve(
+        &self,
+        mut ctx: Context<State>,
+        mut req: Request,
+    ) -> Result<Self::Response, Self::Error> {
+        let Some(upstream) = self.pool.select(&req) else {
+            tracing::debug!("ReverseProxyService: no upstream available");
+            return Ok(StatusCode::SERVICE_UNAVAILABLE.into_response());
+        };
+
+        let version = req.version();
+        let downstream_upgrade = match self.prepare_request(upstream, &mut req) {
+            Ok(downstream_upgrade) => downstream_upgrade,
+            Err(status) => return Ok(status.into_response()),
+        };
+        tracing::trace!(%upstream, uri = %req.uri(), "ReverseProxyService: proxy request");
+
+        // the contexts of the incoming request do not apply to the upstream request
+        ctx.remove::<RequestContext>();
+        ctx.remove::<TransportContext>();
+        #[cfg(feature = "tls")]
+        ctx.remove::<rama_net::tls::SecureTransport>();
+
+        let exec = ctx.executor().clone();
+        let guard = upstream.start_request();
+        let health = self.pool.health_registry();
+        let mut resp = match self.client.serve(ctx, req).await {
+            Ok(resp) => resp,
+            Err(err) => {
+                tracing::debug!(%upstream, error = %err, "ReverseProxyService: upstream request failed");
+                if let Some(health) = health {
+                    health.record_outcome(upstream.authority(), false);
+                }
+                return Ok(StatusCode::BAD_GATEWAY.into_response());
+            }
+        };
+        guard.record_response();
+        if let Some(health) = health {
+            let failed = matches!(
+                resp.status(),
+                StatusCode::BAD_GATEWAY
+                    | StatusCode::SERVICE_UNAVAILABLE
+                    | StatusCode::GATEWAY_TIMEOUT
+            );
+            health.record_outcome(upstream.authority(), !failed);
+        }
+
+        let h1_upgrade = (resp.status() == StatusCode::SWITCHING_PROTOCOLS)
+            .then(|| resp.headers().get(UPGRADE).cloned())
+            .flatten();
+        if let Some(downstream_upgrade) = downstream_upgrade {
+            if resp.status() == StatusCode::SWITCHING_PROTOCOLS || resp.status().is_success() {
+                if let Some(upstream_upgrade) = resp.extensions_mut().remove::<OnUpgrade>() {
+                    exec.spawn_task(relay_upgraded(downstream_upgrade, upstream_upgrade));
+                }
+            }
+        }
+
+        remove_hop_by_hop_headers(resp.headers_mut());
+        if let Some(upgrade) = h1_upgrade {
+            insert_upgrade_headers(resp.headers_mut(), upgrade);
+        }
+        *resp.version_mut() = version;
+
+        Ok(resp)
+    }
+}
+
+/// Copy the upgraded downstream and upstream streams bidirectionally,
+/// once both are switched.
+async fn relay_upgraded(downstream: OnUpgrade, upstream: OnUpgrade) {
+    let (mut downstream, mut upstream) = match tokio::try_join!(downstream, upstream) {
+        Ok(upgraded) => upgraded,
+        Err(err) => {
+            tracing::debug!(error = %err, "ReverseProxyService: upgrade failed");
+            return;
+        }
+    };
+    if let Err(err) = tokio::io::copy_bidirectional(&mut downstream, &mut upstream).await {
+        if !is_connection_error(&err) {
+            tracing::debug!(error = %err, "ReverseProxyService: upgraded relay failed");
+        }
+    }
+}
+
+/// Returns the `Upgrade` header value of a http/1.1 upgrade request.
+fn h1_upgrade<Body>(req: &Request<Body>) -> Option<HeaderValue> {
+    let upgrade = req.headers().get(UPGRADE)?;
+    req.headers()
+        .get_all(CONNECTION)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|option| option.trim().eq_ignore_ascii_case("upgrade"))
+        .then(|| upgrade.clone())
+}
+
+/// Returns true in case the request is an h2 extended CONNECT request ([RFC 8441]).
+///
+/// [RFC 8441]: https://datatracker.ietf.org/doc/html/rfc8441
+fn is_extended_connect<Body>(req: &Request<Body>) -> bool {
+    req.method() == Method::CONNECT && req.extensions().get::<Protocol>().is_some()
+}
+
+fn insert_upgrade_headers(headers: &mut rama_http_types::HeaderMap, upgrade: HeaderValue) {
+    headers.insert(CONNECTION, HeaderValue::from_static("upgrade"));
+    headers.insert(UPGRADE, upgrade);
+}
+
+/// Replace the prefix `from` of the given path by `to`,
+/// returning `None` in case the path does not start with that prefix.
+fn rewrite_path_prefix(path: &str, from: &str, to: &str) -> Option<String> {
+    let from = from.trim_end_matches('/');
+    let rest = path.strip_prefix(from)?;
+    if !rest.is_empty() && !rest.starts_with('/') {
+        // prefix does not end on a segment boundary, e.g. `/apix` for `/api`
+        return None;
+    }
+
+    let to = to.trim_end_matches('/');
+    let path = format!("{to}{rest}");
+    Some(if path.starts_with('/') {
+        path
+    } else {
+        format!("/{path}")
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::HttpServer;
+    use rama_core::{rt::Executor, service::service_fn};
+    use rama_http_types::{Body, BodyExtractExt};
+    use tokio::net::TcpListener;
+
+    #[test]
+    fn test_rewrite_path_prefix() {
+        for (path, from, to, expected) in [
+            ("/api/users", "/api", "/", Some("/users")),
+            ("/api/users", "/api/", "/v1", Some("/v1/users")),
+            ("/api", "/api", "/", Some("/")),
+            ("/api/", "/api", "/v1/", Some("/v1/")),
+            ("/apix/users", "/api", "/", None),
+            ("/users", "/api", "/", None),
+            ("/users", "/", "/v1", Some("/v1/users")),
+        ] {
+            assert_eq!(
+                expected.map(ToOwned::to_owned),
+                rewrite_path_prefix(path, from, to),
+                "path: {path}, from: {from}, to: {to}"
+            );
+        }
+    }
+
+    async fn spawn_origin(name: &'static str) -> std::net::SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = HttpServer::auto(Executor::default()).service(service_fn(
+            move |req: Request| async move {
+                let host = req
+                    .headers()
+                    .get(HOST)
+                    .map(|value| value.to_str().unwrap().to_owned())
+                    .unwrap_or_default();
+                let hop = req.headers().contains_key("x-hop");
+                Ok::<_, Infallible>(
+                    Response::builder()
+                        .header("connection", "x-secret")
+                        .header("x-secret", "hop")
+                        .body(Body::from(format!(
+                            "{name};uri={};host={host};hop={hop}",
+                            req.uri()
+                        )))
+                        .unwrap(),
+                )
+            },
+        ));
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                let server = server.clone();
+                tokio::spawn(async move {
+                    let _ = server.serve(Context::<()>::default(), stream).await;
+                });
+            }
+        });
+        addr
+    }
+
+    fn upstream(addr: std::net::SocketAddr) -> Upstream {
+        Upstream::try_from(format!("http://{addr}")).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_reverse_proxy_round_robin() {
+        let a = spawn_origin("a").await;
+        let b = spawn_origin("b").await;
+        let proxy = ReverseProxyService::new(UpstreamPool::new([upstream(a), upstream(b)]))
+            .with_rewrite_path_prefix("/api", "/");
+
+        let mut bodies = Vec::new();
+        for _ in 0..2 {
+            let req = Request::builder()
+                .uri("http://example.com/api/users?id=1")
+                .header(HOST, "example.com")
+                .header("connection", "x-hop")
+                .header("x-hop", "1")
+                .body(Body::empty())
+                .unwrap();
+            let resp = proxy.serve(Context::<()>::default(), req).await.unwrap();
+            assert_eq!(StatusCode::OK, resp.status());
+            assert!(!resp.headers().contains_key("x-secret"));
+            bodies.push(resp.into_body().try_into_string().await.unwrap());
+        }
+        bodies.sort();
+
+        assert_eq!(
+            vec![
+                format!("a;uri=/users?id=1;host={a};hop=false"),
+                format!("b;uri=/users?id=1;host={b};hop=false"),
+            ],
+            bodies
+        );
+    }
+
+    #[tokio::test]
+    async fn test_reverse_proxy_preserve_host() {
+        let origin = spawn_origin("a").await;
+        let proxy = ReverseProxyService::new(UpstreamPool::new([upstream(origin)]))
+            .with_preserve_host(true);
+
+        let req = Request::builder()
+            .uri("/")
+            .header(HOST, "example.com")
+            .body(Body::empty())
+            .unwrap();
+        let resp = proxy.serve(Context::<()>::default(), req).await.unwrap();
+        assert_eq!(
+            "a;uri=/;host=example.com;hop=false",
+            resp.into_body().try_into_string().await.unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_reverse_proxy_upstream_version() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let origin = listener.local_addr().unwrap();
+        let server =
+            HttpServer::auto(Executor::default()).service(service_fn(|req: Request| async move {
+                Ok::<_, Infallible>(Response::new(Body::from(format!("{:?}", req.version()))))
+            }));
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                let server = server.clone();
+                tokio::spawn(async move {
+                    let _ = server.serve(Context::<()>::default(), stream).await;
+                });
+            }
+        });
+
+        for (upstream_version, version, expected) in [
+            (None, Version::HTTP_11, "HTTP/1.1"),
+            (None, Version::HTTP_2, "HTTP/2.0"),
+            (Some(Version::HTTP_11), Version::HTTP_2, "HTTP/1.1"),
+        ] {
+            let proxy = ReverseProxyService::new(UpstreamPool::new([upstream(origin)]))
+                .with_upstream_version(upstream_version);
+            let req = Request::builder()
+                .uri("http://example.com/")
+                .version(version)
+                .body(Body::empty())
+                .unwrap();
+            let resp = proxy.serve(Context::<()>::default(), req).await.unwrap();
+            assert_eq!(StatusCode::OK, resp.status());
+            assert_eq!(version, resp.version());
+            assert_eq!(
+                expected,
+                resp.into_body().try_into_string().await.unwrap(),
+                "upstream version: {upstream_version:?}, version: {version:?}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_reverse_proxy_no_upstream() {
+        let proxy = ReverseProxyService::new(UpstreamPool::new([]));
+        let req = Request::builder().uri("/").body(Body::empty()).unwrap();
+        let resp = proxy.serve(Context::<()>::default(), req).await.unwrap();
+        assert_eq!(StatusCode::SERVICE_UNAVAILABLE, resp.status());
+    }
+
+    #[tokio::test]
+    async fn test_reverse_proxy_upstream_unreachable() {
+        let addr = TcpListener::bind("127.0.0.1:0")
+            .await
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let proxy = ReverseProxyService::new(UpstreamPool::new([upstream(addr)]));
+        let req = Request::builder().uri("/").body(Body::empty()).unwrap();
+        let resp = proxy.serve(Context::<()>::default(), req).await.unwrap();
+        assert_eq!(StatusCode::BAD_GATEWAY, resp.status());
+    }
+}
@@ -0,0 +1,681 @@
+use super::health::HealthRegistry;
+use rama_core::error::{ErrorContext, OpaqueError};
+use rama_http_types::{header::COOKIE, HeaderName, Request, Scheme, Uri};
+use rama_net::address::Authority;
+use rand::Rng;
+use std::{
+    fmt,
+    hash::{DefaultHasher, Hash, Hasher},
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+/// Weight of the latest latency sample in the EWMA latency of an [`Upstream`].
+const EWMA_LATENCY_ALPHA: f64 = 0.3;
+
+/// Number of points on the hash ring per unit of weight of an [`Upstream`].
+const HASH_RING_POINTS_PER_WEIGHT: u32 = 64;
+
+/// Maximum weight of an [`Upstream`] taken into account for its points on the hash ring,
+/// such that the size of the ring remains bounded.
+const MAX_HASH_RING_WEIGHT: u32 = 256;
+
+#[derive(Clone)]
+/// An upstream server of a reverse proxy, identified by its scheme and authority,
+/// e.g. `http://10.0.0.1:8080`.
+///
+/// The load of an [`Upstream`] is shared by all its clones.
+pub struct Upstream {
+    scheme: Scheme,
+    authority: Authority,
+    weight: u32,
+    load: Arc<UpstreamLoad>,
+}
+
+#[derive(Debug, Default)]
+struct UpstreamLoad {
+    in_flight: AtomicUsize,
+    /// EWMA latency in microseconds, stored as the bits of a `f64`.
+    ewma_latency: AtomicU64,
+}
+
+impl Upstream {
+    /// Create a new [`Upstream`] for the given scheme and authority, with weight `1`.
+    pub fn new(scheme: Scheme, authority: Authority) -> Self {
+        Self {
+            scheme,
+            authority,
+            weight: 1,
+            load: Default::default(),
+        }
+    }
+
+    /// Set the weight of this [`Upstream`], used by weighted strategies,
+    /// where a weight of `0` is treated as `1`.
+    pub fn with_weight(mut self, weight: u32) -> Self {
+        self.weight = weight.max(1);
+        self
+    }
+
+    /// Set the weight of this [`Upstream`], used by weighted strategies,
+    /// where a weight of `0` is treated as `1`.
+    pub fn set_weight(&mut self, weight: u32) -> &mut Self {
+        self.weight = weight.max(1);
+        self
+    }
+
+    /// Returns the [`Scheme`] of this [`Upstream`].
+    pub fn scheme(&self) -> &Scheme {
+        &self.scheme
+    }
+
+    /// Returns the [`Authority`] of this [`Upstream`].
+    pub fn authority(&self) -> &Authority {
+        &self.authority
+    }
+
+    /// Returns the weight of this [`Upstream`].
+    pub fn weight(&self) -> u32 {
+        self.weight
+    }
+
+    /// Returns the number of requests currently in flight to this [`Upstream`],
+    /// counted until their response head is received.
+    pub fn in_flight(&self) -> usize {
+        self.load.in_flight.load(Ordering::Relaxed)
+    }
+
+    /// Returns the exponentially weighted moving average of the latency of this [`Upstream`],
+    /// or `None` in case no response has been received yet.
+    pub fn ewma_latency(&self) -> Option<Duration> {
+        let micros = f64::from_bits(self.load.ewma_latency.load(Ordering::Relaxed));
+        (micros > 0.0).then(|| Duration::from_micros(micros as u64))
+    }
+
+    /// Create the URI for this [`Upstream`] using the given path and query.
+    pub(crate) fn uri(&self, path_and_query: &str) -> Result<Uri, OpaqueError> {
+        Uri::builder()
+            .scheme(self.scheme.clone())
+            .authority(self.authority.to_string())
+            .path_and_query(path_and_query)
+            .build()
+            .context("build upstream uri")
+    }
+
+    /// Mark the start of a request to this [`Upstream`],
+    /// the returned guard records the latency once the response is received.
+    pub(crate) fn start_request(&self) -> InFlightGuard {
+        self.load.in_flight.fetch_add(1, Ordering::Relaxed);
+        InFlightGuard {
+            load: self.load.clone(),
+            start: std::time::Instant::now(),
+        }
+    }
+
+    /// Returns the load score used by the power-of-two-choices strategy.
+    fn cost(&self) -> f64 {
+        let latency = f64::from_bits(self.load.ewma_latency.load(Ordering::Relaxed));
+        (latency.max(1.0) * (self.in_flight() + 1) as f64) / f64::from(self.weight)
+    }
+}
+
+impl fmt::Debug for Upstream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Upstream")
+            .field("scheme", &self.scheme)
+            .field("authority", &self.authority)
+            .field("weight", &self.weight)
+            .field("in_flight", &self.in_flight())
+            .field("ewma_latency", &self.ewma_latency())
+            .finish()
+    }
+}
+
+impl fmt::Display for Upstream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}://{}", self.scheme, self.authority)
+    }
+}
+
+impl TryFrom<&str> for Upstream {
+    type Error = OpaqueError;
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        let uri: Uri = s.parse().context("parse upstream as uri")?;
+        let scheme = uri
+            .scheme()
+            .context("upstream is missing a scheme")?
+            .clone();
+        let default_port = if scheme == Scheme::HTTP {
+            80
+        } else if scheme == Scheme::HTTPS {
+            443
+        } else {
+            return Err(OpaqueError::from_display(format!(
+                "upstream has unsupported scheme: {scheme}"
+            )));
+        };
+        if uri.path_and_query().is_some_and(|pq| pq.as_str() != "/") {
+            return Err(OpaqueError::from_display(
+                "upstream cannot have a path or query",
+            ));
+        }
+        let authority = uri
+            .authority()
+            .context("upstream is missing an authority")?;
+        if authority.as_str().contains('@') {
+            return Err(OpaqueError::from_display("upstream cannot have user info"));
+        }
+        let authority = match authority.port_u16() {
+            Some(_) => Authority::try_from(authority.as_str()),
+            None => Authority::try_from(format!("{}:{default_port}", authority.host())),
+        }
+        .context("parse upstream authority")?;
+        Ok(Self::new(scheme, authority))
+    }
+}
+
+impl TryFrom<String> for Upstream {
+    type Error = OpaqueError;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        Self::try_from(s.as_str())
+    }
+}
+
+impl std::str::FromStr for Upstream {
+    type Err = OpaqueError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::try_from(s)
+    }
+}
+
+/// Guard tracking a request in flight to an [`Upstream`].
+pub(crate) struct InFlightGuard {
+    load: Arc<UpstreamLoad>,
+    start: std::time::Instant,
+}
+
+impl InFlightGuard {
+    /// Record the latency of the request, as its response (head) is received.
+    pub(crate) fn record_response(self) {
+        let sample = self.start.elapsed().as_secs_f64() * 1_000_000.0;
+        let _ = self
+            .load
+            .ewma_latency
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |bits| {
+                let ewma = f64::from_bits(bits);
+                let ewma = if ewma > 0.0 {
+                    EWMA_LATENCY_ALPHA.mul_add(sample - ewma, ewma)
+                } else {
+                    sample.max(1.0)
+                };
+                Some(ewma.to_bits())
+            });
+    }
+}
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        self.load.in_flight.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+/// The strategy used by an [`UpstreamPool`] to select an [`Upstream`] for a request.
+pub enum BalanceStrategy {
+    #[default]
+    /// Select the upstreams in turn.
+    RoundRobin,
+    /// Select a random upstream, proportional to its weight.
+    Weighted,
+    /// Select the upstream with the least requests in flight (relative to its weight).
+    LeastRequests,
+    /// Select the least loaded of two random upstreams, where the load is the
+    /// EWMA latency multiplied by the number of requests in flight (relative to its weight).
+    PowerOfTwoChoices,
+    /// Select the upstream using a consistent hash ring over the given [`HashKey`],
+    /// such that requests with the same key go to the same upstream,
+    /// falling back to round robin for requests without the key.
+    ConsistentHash(HashKey),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// The key of a request used by the [`BalanceStrategy::ConsistentHash`] strategy.
+pub enum HashKey {
+    /// The value of the given header.
+    Header(HeaderName),
+    /// The value of the cookie with the given name.
+    Cookie(String),
+}
+
+impl HashKey {
+    fn hash_request<Body>(&self, req: &Request<Body>) -> Option<u64> {
+        match self {
+            Self::Header(name) => req.headers().get(name).map(|value| hash(value.as_bytes())),
+            Self::Cookie(name) => req
+                .headers()
+                .get_all(COOKIE)
+                .iter()
+                .filter_map(|value| value.to_str().ok())
+                .flat_map(|value| value.split(';'))
+                .find_map(|pair| {
+                    let (key, value) = pair.trim().split_once('=')?;
+                    (key == name).then(|| hash(value.as_bytes()))
+                }),
+        }
+    }
+}
+
+fn hash(value: impl Hash) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+#[derive(Debug, Clone)]
+/// A pool of [`Upstream`]s, selecting one for each request using its [`BalanceStrategy`].
+///
+/// The state of the pool, such as the round robin position and
+/// the load of its upstreams, is shared by all its clones.
+pub struct UpstreamPool {
+    upstreams: Arc<[Upstream]>,
+    strategy: BalanceStrategy,
+    ring: Arc<[(u64, usize)]>,
+    next: Arc<AtomicUsize>,
+    health: Option<HealthRegistry>,
+}
+
+impl UpstreamPool {
+    /// Create a new [`UpstreamPool`] for the given upstreams,
+    /// using the [`BalanceStrategy::RoundRobin`] strategy.
+    pub fn new(upstreams: impl IntoIterator<Item = Upstream>) -> Self {
+        Self {
+            upstreams: upstreams.into_iter().collect(),
+            strategy: BalanceStrategy::RoundRobin,
+            ring: Arc::new([]),
+            next: Default::default(),
+            health: None,
+        }
+    }
+
+    /// Set the [`BalanceStrategy`] used to select an [`Upstream`].
+    pub fn with_strategy(mut self, strategy: BalanceStrategy) -> Self {
+        self.set_strategy(strategy);
+        self
+    }
+
+    /// Set the [`BalanceStrategy`] used to select an [`Upstream`].
+    pub fn set_strategy(&mut self, strategy: BalanceStrategy) -> &mut Self {
+        self.ring = match strategy {
+            BalanceStrategy::ConsistentHash(_) => self.build_ring(),
+            _ => Arc::new([]),
+        };
+        self.strategy = strategy;
+        self
+    }
+
+    /// Only select the upstreams which are healthy according to the given [`HealthRegistry`].
+    pub fn with_health(mut self, registry: HealthRegistry) -> Self {
+        self.health = Some(registry);
+        self
+    }
+
+    /// Only select the upstreams which are healthy according to the given [`HealthRegistry`].
+    pub fn set_health(&mut self, registry: HealthRegistry) -> &mut Self {
+        self.health = Some(registry);
+        self
+    }
+
+    /// Returns the [`HealthRegistry`] of this pool, if any.
+    pub fn health_registry(&self) -> Option<&HealthRegistry> {
+        self.health.as_ref()
+    }
+
+    /// Returns the [`Upstream`]s of this pool.
+    pub fn upstreams(&self) -> &[Upstream] {
+        &self.upstreams
+    }
+
+    fn build_ring(&self) -> Arc<[(u64, usize)]> {
+        let mut ring: Vec<_> = self
+            .upstreams
+            .iter()
+            .enumerate()
+            .flat_map(|(index, upstream)| {
+                let key = upstream.to_string();
+                let points =
+                    upstream.weight.min(MAX_HASH_RING_WEIGHT) * HASH_RING_POINTS_PER_WEIGHT;
+                (0..points).map(move |point| (hash((&key, point)), index))
+            })
+            .collect();
+        ring.sort_unstable();
+        ring.into()
+    }
+
+    /// Select an [`Upstream`] for the given request,
+    /// returning `None` in case the pool has no healthy upstream.
+    pub fn select<Body>(&self, req: &Request<Body>) -> Option<&Upstream> {
+        let healthy: Vec<bool> = self
+            .upstreams
+            .iter()
+            .map(|upstream| {
+                self.health
+                    .as_ref()
+                    .is_none_or(|health| health.is_healthy(upstream.authority()))
+            })
+            .collect();
+        let candidates: Vec<&Upstream> = self
+            .upstreams
+            .iter()
+            .zip(&healthy)
+            .filter_map(|(upstream, healthy)| healthy.then_some(upstream))
+            .collect();
+        match candidates.len() {
+            0 => return None,
+            1 => return candidates.first().copied(),
+            _ => (),
+        }
+        Some(match &self.strategy {
+            BalanceStrategy::RoundRobin => self.select_round_robin(&candidates),
+            BalanceStrategy::Weighted => select_weighted(&candidates),
+            BalanceStrategy::LeastRequests => select_least_requests(&candidates),
+            BalanceStrategy::PowerOfTwoChoices => select_power_of_two_choices(&candidates),
+            BalanceStrategy::ConsistentHash(key) => key
+                .hash_request(req)
+                .and_then(|key_hash| self.select_hashed(key_hash, &healthy))
+                .unwrap_or_else(|| self.select_round_robin(&candidates)),
+        })
+    }
+
+    fn select_round_robin<'a>(&self, candidates: &[&'a Upstream]) -> &'a Upstream {
+        let index = self.next.fetch_add(1, Ordering::Relaxed) % candidates.len();
+        candidates[index]
+    }
+
+    /// Select the [`Upstream`] for the given hash from the ring,
+    /// where `candidates` defines for each upstream (by index) whether it can be selected.
+    fn select_hashed(&self, hash: u64, candidates: &[bool]) -> Option<&Upstream> {
+        // walk the ring clockwise, skipping the upstreams which are not a candidate
+        let start = self.ring.partition_point(|(point, _)| *point < hash);
+        (0..self.ring.len())
+            .map(|i| self.ring[(start + i) % self.ring.len()].1)
+            .find(|index| candidates.get(*index).copied().unwrap_or_default())
+            .map(|index| &self.upstreams[index])
+    }
+}
+
+fn select_weighted<'a>(candidates: &[&'a Upstream]) -> &'a Upstream {
+    let total: u64 = candidates.iter().map(|u| u64::from(u.weight)).sum();
+    let mut point = rand::thread_rng().gen_range(0..total);
+    for &upstream in candidates {
+        let weight = u64::from(upstream.weight);
+        if point < weight {
+            return upstream;
+        }
+        point -= weight;
+    }
+    unreachable!("weighted point is always within the total weight")
+}
+
+fn select_least_requests<'a>(candidates: &[&'a Upstream]) -> &'a Upstream {
+    // start at a random offset, such that ties are spread over the upstreams
+    let offset = rand::thread_rng().gen_range(0..candidates.len());
+    (0..candidates.len())
+        .map(|i| candidates[(offset + i) % candidates.len()])
+        .min_by(|a, b| {
+            let a_load = a.in_flight() as f64 / f64::from(a.weight);
+            let b_load = b.in_flight() as f64 / f64::from(b.weight);
+            a_load.total_cmp(&b_load)
+        })
+        .expect("pool to contain at least two upstreams")
+}
+
+fn select_power_of_two_choices<'a>(candidates: &[&'a Upstream]) -> &'a Upstream {
+    let mut rng = rand::thread_rng();
+    let a = rng.gen_range(0..candidates.len());
+    let b = (a + rng.gen_range(1..candidates.len())) % candidates.len();
+    let (a, b) = (candidates[a], candidates[b]);
+    if b.cost() < a.cost() {
+        b
+    } else {
+        a
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rama_http_types::Body;
+    use std::collections::HashMap;
+
+    fn pool(strategy: BalanceStrategy) -> UpstreamPool {
+        UpstreamPool::new([
+            Upstream::try_from("http://10.0.0.1:8080").unwrap(),
+            Upstream::try_from("http://10.0.0.2:8080").unwrap(),
+            Upstream::try_from("https://example.com")
+                .unwrap()
+                .with_weight(2),
+        ])
+        .with_strategy(strategy)
+    }
+
+    fn request() -> Request {
+        Request::builder().uri("/").body(Body::empty()).unwrap()
+    }
+
+    #[test]
+    fn test_upstream_parse() {
+        let upstream = Upstream::try_from("https://example.com").unwrap();
+        assert_eq!("https://example.com:443", upstream.to_string());
+        assert_eq!(
+            "https://example.com:443/foo?bar",
+            upstream.uri("/foo?bar").unwrap().to_string()
+        );
+        assert_eq!(
+            "http://[::1]:8080",
+            Upstream::try_from("http://[::1]:8080/")
+                .unwrap()
+                .to_string()
+        );
+
+        for s in [
+            "example.com",
+            "ftp://example.com",
+            "http://example.com/foo",
+            "http://user@example.com",
+            "/foo",
+        ] {
+            assert!(Upstream::try_from(s).is_err(), "{s}");
+        }
+    }
+
+    #[test]
+    fn test_round_robin() {
+        let pool = pool(BalanceStrategy::RoundRobin);
+        let selected: Vec<_> = (0..6)
+            .map(|_| pool.select(&request()).unwrap().to_string())
+            .collect();
+        assert_eq!(
+            vec![
+                "http://10.0.0.1:8080",
+                "http://10.0.0.2:8080",
+                "https://example.com:443",
+                "http://10.0.0.1:8080",
+                "http://10.0.0.2:8080",
+                "https://example.com:443",
+            ],
+            selected
+        );
+    }
+
+    #[test]
+    fn test_weighted() {
+        let pool = pool(BalanceStrategy::Weighted);
+        let mut counts: HashMap<String, usize> = HashMap::new();
+        for _ in 0..4000 {
+            *counts
+                .entry(pool.select(&request()).unwrap().to_string())
+                .or_default() += 1;
+        }
+        let heavy = counts["https://example.com:443"];
+        assert!((1700..2300).contains(&heavy), "{counts:?}");
+    }
+
+    #[test]
+    fn test_least_requests() {
+        let pool = pool(BalanceStrategy::LeastRequests);
+        let guard = pool.upstreams()[0].start_request();
+        let _b = pool.upstreams()[2].start_request();
+        let _c = pool.upstreams()[2].start_request();
+        for _ in 0..10 {
+            assert_eq!(
+                "http://10.0.0.2:8080",
+                pool.select(&request()).unwrap().to_string()
+            );
+        }
+        assert_eq!(1, pool.upstreams()[0].in_flight());
+        drop(guard);
+        assert_eq!(0, pool.upstreams()[0].in_flight());
+    }
+
+    #[test]
+    fn test_power_of_two_choices() {
+        let pool = UpstreamPool::new([
+            Upstream::try_from("http://10.0.0.1:8080").unwrap(),
+            Upstream::try_from("http://10.0.0.2:8080").unwrap(),
+        ])
+        .with_strategy(BalanceStrategy::PowerOfTwoChoices);
+
+        let guard = pool.upstreams()[0].start_request();
+        std::thread::sleep(Duration::from_millis(5));
+        guard.record_response();
+        pool.upstreams()[1].start_request().record_response();
+        assert!(pool.upstreams()[0].ewma_latency() > pool.upstreams()[1].ewma_latency());
+
+        for _ in 0..10 {
+            assert_eq!(
+                "http://10.0.0.2:8080",
+                pool.select(&request()).unwrap().to_string()
+            );
+        }
+    }
+
+    #[test]
+    fn test_consistent_hash() {
+        let cookie_pool = pool(BalanceStrategy::ConsistentHash(HashKey::Cookie(
+            "session".to_owned(),
+        )));
+        let mut seen = HashMap::new();
+        for i in 0..100 {
+            let req = Request::builder()
+                .uri("/")
+                .header(COOKIE, format!("foo=bar; session=user-{i}"))
+                .body(Body::empty())
+                .unwrap();
+            let first = cookie_pool.select(&req).unwrap().to_string();
+            for _ in 0..5 {
+                assert_eq!(first, cookie_pool.select(&req).unwrap().to_string());
+            }
+            *seen.entry(first).or_insert(0) += 1;
+        }
+        assert_eq!(3, seen.len(), "{seen:?}");
+
+        // requests without the key fall back to round robin
+        assert!(cookie_pool.select(&request()).is_some());
+
+        let header_pool = pool(BalanceStrategy::ConsistentHash(HashKey::Header(
+            HeaderName::from_static("x-user"),
+        )));
+        let req = Request::builder()
+            .uri("/")
+            .header("x-user", "john")
+            .body(Body::empty())
+            .unwrap();
+        let first = header_pool.select(&req).unwrap().to_string();
+        assert_eq!(first, header_pool.select(&req).unwrap().to_string());
+
+        // only the given candidates are selected from the ring
+        let candidate = &header_pool.upstreams()[1];
+        for hash in [0, 42, u64::MAX] {
+            assert!(header_pool.select_hashed(hash, &[]).is_none());
+            assert!(header_pool
+                .select_hashed(hash, &[false, false, false])
+                .is_none());
+            assert!(std::ptr::eq(
+                candidate,
+                header_pool
+                    .select_hashed(hash, &[false, true, false])
+                    .unwrap()
+            ));
+        }
+    }
+
+    #[test]
+    fn test_consistent_hash_ring_size_bounded() {
+        let pool = UpstreamPool::new([
+            Upstream::try_from("http://10.0.0.1:8080")
+                .unwrap()
+                .with_weight(u32::MAX),
+            Upstream::try_from("http://10.0.0.2:8080").unwrap(),
+        ])
+        .with_strategy(BalanceStrategy::ConsistentHash(HashKey::Header(
+            HeaderName::from_static("x-user"),
+        )));
+        assert_eq!(
+            ((MAX_HASH_RING_WEIGHT + 1) * HASH_RING_POINTS_PER_WEIGHT) as usize,
+            pool.ring.len()
+        );
+
+        let req = Request::builder()
+            .uri("/")
+            .header("x-user", "john")
+            .body(Body::empty())
+            .unwrap();
+        assert!(pool.select(&req).is_some());
+    }
+
+    #[test]
+    fn test_empty_pool() {
+        let pool = UpstreamPool::new([]);
+        assert!(pool.select(&request()).is_none());
+    }
+
+    #[test]
+    fn test_unhealthy_upstreams_skipped() {
+        let health = HealthRegistry::new().with_unhealthy_threshold(1);
+        for strategy in [
+            BalanceStrategy::RoundRobin,
+            BalanceStrategy::Weighted,
+            BalanceStrategy::LeastRequests,
+            BalanceStrategy::PowerOfTwoChoices,
+            BalanceStrategy::ConsistentHash(HashKey::Header(HeaderName::from_static("x-user"))),
+        ] {
+            let pool = pool(strategy).with_health(health.clone());
+            health.record_probe(pool.upstreams()[0].authority(), false);
+            health.record_probe(pool.upstreams()[2].authority(), false);
+            for i in 0..20 {
+                let req = Request::builder()
+                    .uri("/")
+                    .header("x-user", format!("user-{i}"))
+                    .body(Body::empty())
+                    .unwrap();
+                assert_eq!(
+                    "http://10.0.0.2:8080",
+                    pool.select(&req).unwrap().to_string()
+                );
+            }
+
+            health.record_probe(pool.upstreams()[1].authority(), false);
+            assert!(pool.select(&request()).is_none());
+            for upstream in pool.upstreams() {
+                health.record_probe(upstream.authority(), true);
+                health.record_probe(upstream.authority(), true);
+            }
+        }
+    }
+}
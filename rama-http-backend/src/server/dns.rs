@@ -0,0 +1,243 @@
+//! DNS-over-HTTPS (DoH) server support, as defined in [RFC 8484].
+//!
+//! The [`DohService`] serves DoH requests using a DNS [`Service`],
+//! such as the services of the [`rama_dns::server`] module,
+//! and can be used as an endpoint of a `WebService` or any other http router.
+//!
+//! Requires the `dns` feature.
+//!
+//! [RFC 8484]: https://datatracker.ietf.org/doc/html/rfc8484
+
+use crate::client::dns::{is_dns_message, DNS_MESSAGE};
+use base64::Engine as _;
+use rama_core::{error::BoxError, Context, Service};
+use rama_dns::wire::{self, Message, ResponseCode};
+use rama_http_types::{
+    dep::http_body_util::BodyExt,
+    header::{CACHE_CONTROL, CONTENT_TYPE},
+    Body, HeaderValue, IntoResponse, Method, Request, Response, StatusCode,
+};
+use std::convert::Infallible;
+
+#[derive(Debug, Clone)]
+/// A [`Service`] which serves DNS-over-HTTPS (DoH) requests, as defined in [RFC 8484],
+/// using the inner DNS [`Service`].
+///
+/// Both `GET` requests, with the query base64url-encoded as the `dns` query parameter,
+/// and `POST` requests, with the query as the request body, are supported.
+/// Responses can be cached by http caches for the lowest TTL of their answer records.
+///
+/// Queries which cannot be served by the inner service are answered with `SERVFAIL`.
+///
+/// [RFC 8484]: https://datatracker.ietf.org/doc/html/rfc8484
+pub struct DohService<S> {
+    inner: S,
+}
+
+impl<S> DohService<S> {
+    /// Create a new [`DohService`] which serves DNS queries using the given [`Service`].
+    pub const fn new(inner: S) -> Self {
+        Self { inner }
+    }
+}
+
+impl<State, S> Service<State, Request> for DohService<S>
+where
+    State: Clone + Send + Sync + 'static,
+    S: Service<State, Message, Response = Message, Error: Into<BoxError>>,
+{
+    type Response = Response;
+    type Error = Infallible;
+
+    async fn serve(
+        &self,
+        ctx: Context<State>,
+        req: Request,
+    ) -> Result<Self::Response, Self::Error> {
+        let bytes = match read_query(req).await {
+            Ok(bytes) => bytes,
+            Err(status) => return Ok(status.into_response()),
+        };
+        let Ok(query) = wire::decode_message(&bytes) else {
+            return Ok(StatusCode::BAD_REQUEST.into_response());
+        };
+
+        let server_failure = wire::response_message(&query, ResponseCode::ServFail);
+        let response = self.inner.serve(ctx, query).await.unwrap_or(server_failure);
+        let Ok(bytes) = wire::encode_message(&response) else {
+            return Ok(StatusCode::INTERNAL_SERVER_ERROR.into_response());
+        };
+
+        let mut resp = Response::new(Body::from(bytes));
+        resp.headers_mut()
+            .insert(CONTENT_TYPE, HeaderValue::from_static(DNS_MESSAGE));
+        if let Some(ttl) = response.answers().iter().map(|record| record.ttl()).min() {
+            if let Ok(value) = HeaderValue::try_from(format!("max-age={ttl}")) {
+                resp.headers_mut().insert(CACHE_CONTROL, value);
+            }
+        }
+        Ok(resp)
+    }
+}
+
+/// Read the DNS query in wire format from a DoH request.
+async fn read_query(req: Request) -> Result<Vec<u8>, StatusCode> {
+    match *req.method() {
+        Method::GET => {
+            let dns = req
+                .uri()
+                .query()
+                .and_then(|query| query.split('&').find_map(|kv| kv.strip_prefix("dns=")))
+                .ok_or(StatusCode::BAD_REQUEST)?;
+            base64::engine::general_purpose::URL_SAFE_NO_PAD
+                .decode(dns)
+                .map_err(|_| StatusCode::BAD_REQUEST)
+        }
+        Method::POST => {
+            if req
+                .headers()
+                .get(CONTENT_TYPE)
+                .is_none_or(|content_type| !is_dns_message(content_type))
+            {
+                return Err(StatusCode::UNSUPPORTED_MEDIA_TYPE);
+            }
+            let bytes = req
+                .into_body()
+                .limited(wire::MAX_MESSAGE_SIZE)
+                .collect()
+                .await
+                .map_err(|_| StatusCode::BAD_REQUEST)?
+                .to_bytes();
+            Ok(bytes.to_vec())
+        }
+        _ => Err(StatusCode::METHOD_NOT_ALLOWED),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::dns::{DohMethod, DohResolver};
+    use rama_core::service::service_fn;
+    use rama_dns::{
+        server::{Zone, ZoneService},
+        wire::RecordType,
+        DnsResolver,
+    };
+    use rama_http_types::Uri;
+    use rama_net::address::Domain;
+    use std::net::Ipv4Addr;
+
+    fn doh_service() -> DohService<ZoneService> {
+        let mut zone = Zone::new(Domain::from_static("example.com")).unwrap();
+        zone.insert_address(
+            Domain::from_static("www.example.com"),
+            Ipv4Addr::new(127, 0, 0, 1).into(),
+        )
+        .unwrap();
+        DohService::new(ZoneService::new().with_zone(zone))
+    }
+
+    #[tokio::test]
+    async fn test_doh_service_with_resolver() {
+        let service = doh_service();
+        for method in [DohMethod::Get, DohMethod::Post] {
+            let service = service.clone();
+            let resolver = DohResolver::new_with_client(
+                Uri::from_static("http://dns.example/dns-query"),
+                service_fn(move |req: Request| {
+                    let service = service.clone();
+                    async move { service.serve(Context::<()>::default(), req).await }
+                }),
+            )
+            .with_method(method);
+
+            let lookup = resolver
+                .ipv4_lookup_with_ttl(Domain::from_static("www.example.com"))
+                .await
+                .unwrap();
+            assert_eq!(lookup.records, vec![Ipv4Addr::new(127, 0, 0, 1)]);
+
+            assert!(resolver
+                .ipv4_lookup(Domain::from_static("missing.example.com"))
+                .await
+                .is_err());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_doh_service_cache_control() {
+        let query =
+            wire::query_message(0, Domain::from_static("www.example.com"), RecordType::A).unwrap();
+        let req = Request::builder()
+            .method(Method::POST)
+            .uri("/dns-query")
+            .header(CONTENT_TYPE, DNS_MESSAGE)
+            .body(Body::from(wire::encode_message(&query).unwrap()))
+            .unwrap();
+        let resp = doh_service()
+            .serve(Context::<()>::default(), req)
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers()[CONTENT_TYPE], DNS_MESSAGE);
+        assert_eq!(resp.headers()[CACHE_CONTROL], "max-age=300");
+    }
+
+    #[tokio::test]
+    async fn test_doh_service_content_type_parameters() {
+        let query =
+            wire::query_message(0, Domain::from_static("www.example.com"), RecordType::A).unwrap();
+        let req = Request::builder()
+            .method(Method::POST)
+            .uri("/dns-query")
+            .header(CONTENT_TYPE, "application/dns-message; charset=binary")
+            .body(Body::from(wire::encode_message(&query).unwrap()))
+            .unwrap();
+        let resp = doh_service()
+            .serve(Context::<()>::default(), req)
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_doh_service_bad_requests() {
+        let service = doh_service();
+        for (req, status) in [
+            (
+                Request::builder()
+                    .uri("/dns-query")
+                    .body(Body::empty())
+                    .unwrap(),
+                StatusCode::BAD_REQUEST,
+            ),
+            (
+                Request::builder()
+                    .uri("/dns-query?dns=AAAA")
+                    .body(Body::empty())
+                    .unwrap(),
+                StatusCode::BAD_REQUEST,
+            ),
+            (
+                Request::builder()
+                    .method(Method::POST)
+                    .uri("/dns-query")
+                    .body(Body::empty())
+                    .unwrap(),
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            ),
+            (
+                Request::builder()
+                    .method(Method::PUT)
+                    .uri("/dns-query")
+                    .body(Body::empty())
+                    .unwrap(),
+                StatusCode::METHOD_NOT_ALLOWED,
+            ),
+        ] {
+            let resp = service.serve(Context::<()>::default(), req).await.unwrap();
+            assert_eq!(resp.status(), status);
+        }
+    }
+}
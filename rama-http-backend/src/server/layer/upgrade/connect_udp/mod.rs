@@ -0,0 +1,158 @@
+//! CONNECT-UDP support for the [`UpgradeLayer`], proxying UDP in HTTP ([RFC 9298]),
+//! both for http/1.1 upgrades and h2 extended CONNECT requests.
+//!
+//! See the [`masque`] module for more information.
+//!
+//! The [`ConnectUdpRelay`] requires the `connect-udp` feature.
+//!
+//! [`UpgradeLayer`]: super::UpgradeLayer
+//! [`masque`]: crate::masque
+//! [RFC 9298]: https://datatracker.ietf.org/doc/html/rfc9298
+
+use crate::masque::{self, ConnectUdpTarget, UdpProxyTemplate};
+use rama_core::{context::Extensions, matcher::Matcher, Context, Service};
+use rama_http_types::{IntoResponse, Request, Response, StatusCode};
+
+#[cfg(feature = "connect-udp")]
+mod relay;
+#[cfg(feature = "connect-udp")]
+#[doc(inline)]
+pub use relay::ConnectUdpRelay;
+
+#[derive(Debug, Clone, Default)]
+/// A [`Matcher`] which matches CONNECT-UDP requests, be it
+/// a http/1.1 upgrade request or an h2 extended CONNECT request
+/// with `connect-udp` as its `:protocol` pseudo header,
+/// of which the URI matches the [`UdpProxyTemplate`].
+///
+/// The target of the matched request is inserted as a [`ConnectUdpTarget`].
+///
+/// The h2 variant is only possible in case the h2 server advertises
+/// support for the extended CONNECT protocol, which the [`HttpServer`] does by default.
+///
+/// [`HttpServer`]: crate::server::HttpServer
+pub struct ConnectUdpMatcher {
+    template: UdpProxyTemplate,
+}
+
+impl ConnectUdpMatcher {
+    /// Create a new [`ConnectUdpMatcher`] using the given [`UdpProxyTemplate`].
+    pub const fn new(template: UdpProxyTemplate) -> Self {
+        Self { template }
+    }
+}
+
+impl<State, Body> Matcher<State, Request<Body>> for ConnectUdpMatcher {
+    fn matches(
+        &self,
+        ext: Option<&mut Extensions>,
+        _ctx: &Context<State>,
+        req: &Request<Body>,
+    ) -> bool {
+        if !masque::is_h1_connect_udp(req) && !masque::is_h2_connect_udp(req) {
+            return false;
+        }
+        let Some(target) = self.template.match_uri(req.uri()) else {
+            return false;
+        };
+        if let Some(ext) = ext {
+            ext.insert(ConnectUdpTarget(target));
+        }
+        true
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+#[non_exhaustive]
+/// A responder [`Service`] that can be used in an [`UpgradeLayer`]
+/// to accept CONNECT-UDP requests matched by the [`ConnectUdpMatcher`].
+///
+/// It responds with `101 Switching Protocols` to http/1.1 requests
+/// and with `200 OK` to h2 extended CONNECT requests, after which
+/// the upgraded stream is passed to the upgrade handler, e.g. the [`ConnectUdpRelay`].
+/// Requests without a [`ConnectUdpTarget`] are responded to with `400 Bad Request`.
+///
+/// [`UpgradeLayer`]: super::UpgradeLayer
+pub struct ConnectUdpAcceptor;
+
+impl ConnectUdpAcceptor {
+    /// Create a new [`ConnectUdpAcceptor`].
+    pub const fn new() -> Self {
+        Self
+    }
+}
+
+impl<State> Service<State, Request> for ConnectUdpAcceptor
+where
+    State: Clone + Send + Sync + 'static,
+{
+    type Response = (Response, Context<State>, Request);
+    type Error = Response;
+
+    async fn serve(
+        &self,
+        ctx: Context<State>,
+        req: Request,
+    ) -> Result<Self::Response, Self::Error> {
+        if !ctx.contains::<ConnectUdpTarget>() {
+            tracing::debug!(uri = %req.uri(), "ConnectUdpAcceptor: missing connect-udp target");
+            return Err(StatusCode::BAD_REQUEST.into_response());
+        }
+        match masque::accept_connect_udp(&req) {
+            Some(resp) => Ok((resp, ctx, req)),
+            None => {
+                tracing::debug!(
+                    http_version = ?req.version(),
+                    "ConnectUdpAcceptor: invalid connect-udp request"
+                );
+                Err(StatusCode::BAD_REQUEST.into_response())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rama_http_types::Body;
+    use rama_net::address::Authority;
+
+    #[test]
+    fn test_connect_udp_matcher() {
+        let matcher = ConnectUdpMatcher::default();
+        let ctx = Context::<()>::default();
+
+        let mut req = Request::builder()
+            .uri("/.well-known/masque/udp/example.com/53/")
+            .body(Body::empty())
+            .unwrap();
+        masque::insert_h1_upgrade_headers(req.headers_mut());
+        masque::insert_capsule_protocol_header(req.headers_mut());
+
+        let mut ext = Extensions::new();
+        assert!(matcher.matches(Some(&mut ext), &ctx, &req));
+        assert_eq!(
+            Some(&ConnectUdpTarget(
+                Authority::try_from("example.com:53").unwrap()
+            )),
+            ext.get::<ConnectUdpTarget>()
+        );
+
+        let req = masque::h1_into_h2_connect_udp(req);
+        assert!(matcher.matches(None, &ctx, &req));
+
+        let req = Request::builder()
+            .uri("/.well-known/masque/udp/example.com/53/")
+            .body(Body::empty())
+            .unwrap();
+        assert!(!matcher.matches(None, &ctx, &req));
+
+        let mut req = Request::builder()
+            .uri("/.well-known/masque/udp/example.com/")
+            .body(Body::empty())
+            .unwrap();
+        masque::insert_h1_upgrade_headers(req.headers_mut());
+        masque::insert_capsule_protocol_header(req.headers_mut());
+        assert!(!matcher.matches(None, &ctx, &req));
+    }
+}
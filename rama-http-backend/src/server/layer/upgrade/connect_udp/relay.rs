@@ -0,0 +1,416 @@
+//! The [`ConnectUdpRelay`] upgrade handler, relaying CONNECT-UDP tunnels to UDP sockets.
+
+use crate::masque::{self, ConnectUdpTarget, UdpTunnel};
+use crate::server::layer::upgrade::Upgraded;
+use rama_core::{
+    error::{BoxError, ErrorContext, OpaqueError},
+    Context, Service,
+};
+use rama_dns::{DnsResolver, HickoryDns};
+use rama_net::address::Host;
+use std::{
+    convert::Infallible,
+    fmt, io,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    sync::Arc,
+};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::UdpSocket;
+
+type TargetFilter = Arc<dyn Fn(&ConnectUdpTarget, SocketAddr) -> bool + Send + Sync + 'static>;
+
+/// An upgrade handler [`Service`] relaying the UDP payloads received over
+/// an accepted CONNECT-UDP request to and from its [`ConnectUdpTarget`],
+/// using a UDP socket connected to the (resolved) target.
+///
+/// Only targets allowed by the target filter are relayed to, which by default
+/// only allows globally routable addresses, such that the relay cannot be used
+/// to reach loopback, private or otherwise internal addresses.
+/// Use [`ConnectUdpRelay::with_target_filter`] to restrict or widen the allowed targets.
+pub struct ConnectUdpRelay<Dns = HickoryDns> {
+    dns: Dns,
+    target_filter: TargetFilter,
+}
+
+impl ConnectUdpRelay {
+    /// Create a new [`ConnectUdpRelay`], resolving targets using the default [`HickoryDns`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Default for ConnectUdpRelay {
+    fn default() -> Self {
+        Self {
+            dns: HickoryDns::default(),
+            target_filter: Arc::new(|_, addr| is_global_ip(addr.ip())),
+        }
+    }
+}
+
+impl<Dns> ConnectUdpRelay<Dns> {
+    /// Use the given [`DnsResolver`] to resolve the domain of targets.
+    pub fn with_dns<T>(self, dns: T) -> ConnectUdpRelay<T> {
+        ConnectUdpRelay {
+            dns,
+            target_filter: self.target_filter,
+        }
+    }
+
+    /// Only relay to the targets for which the given filter returns true,
+    /// replacing the default filter which only allows globally routable addresses.
+    ///
+    /// The filter is called with the requested target and its resolved address.
+    pub fn with_target_filter<F>(mut self, filter: F) -> Self
+    where
+        F: Fn(&ConnectUdpTarget, SocketAddr) -> bool + Send + Sync + 'static,
+    {
+        self.target_filter = Arc::new(filter);
+        self
+    }
+
+    /// Only relay to the targets for which the given filter returns true,
+    /// replacing the default filter which only allows globally routable addresses.
+    ///
+    /// The filter is called with the requested target and its resolved address.
+    pub fn set_target_filter<F>(&mut self, filter: F) -> &mut Self
+    where
+        F: Fn(&ConnectUdpTarget, SocketAddr) -> bool + Send + Sync + 'static,
+    {
+        self.target_filter = Arc::new(filter);
+        self
+    }
+}
+
+impl<Dns: fmt::Debug> fmt::Debug for ConnectUdpRelay<Dns> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ConnectUdpRelay")
+            .field("dns", &self.dns)
+            .finish()
+    }
+}
+
+impl<Dns: Clone> Clone for ConnectUdpRelay<Dns> {
+    fn clone(&self) -> Self {
+        Self {
+            dns: self.dns.clone(),
+            target_filter: self.target_filter.clone(),
+        }
+    }
+}
+
+/// Returns true if the ip address is (likely) globally routable,
+/// as opposed to e.g. a loopback, private, link-local or multicast address.
+fn is_global_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            !(ip.is_unspecified()
+                || ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_multicast()
+                || ip.is_broadcast()
+                || ip.is_documentation()
+                // "this network" (RFC 1122)
+                || a == 0
+                // shared address space (RFC 6598)
+                || (a == 100 && (b & 0b1100_0000) == 64)
+                // IETF protocol assignments (RFC 6890)
+                || (a == 192 && b == 0 && ip.octets()[2] == 0)
+                // benchmarking (RFC 2544)
+                || (a == 198 && (b & 0xfe) == 18)
+                // reserved (RFC 1112)
+                || a >= 240)
+        }
+        IpAddr::V6(ip) => {
+            if let Some(ip) = ip.to_ipv4_mapped() {
+                return is_global_ip(ip.into());
+            }
+            let first = ip.segments()[0];
+            !(ip.is_unspecified()
+                || ip.is_loopback()
+                || ip.is_multicast()
+                // unique local (RFC 4193)
+                || (first & 0xfe00) == 0xfc00
+                // link-local unicast (RFC 4291)
+                || (first & 0xffc0) == 0xfe80
+                // documentation (RFC 3849)
+                || (first == 0x2001 && ip.segments()[1] == 0x0db8))
+        }
+    }
+}
+
+impl<Dns> ConnectUdpRelay<Dns>
+where
+    Dns: DnsResolver<Error: Into<BoxError>>,
+{
+    async fn resolve(&self, host: Host) -> Result<IpAddr, OpaqueError> {
+        let domain = match host {
+            Host::Address(ip) => return Ok(ip),
+            Host::Name(domain) => domain,
+        };
+        if let Ok(ips) = self.dns.ipv4_lookup(domain.clone()).await {
+            if let Some(ip) = ips.into_iter().next() {
+                return Ok(ip.into());
+            }
+        }
+        let ipv6 = self
+            .dns
+            .ipv6_lookup(domain)
+            .await
+            .map_err(|err| OpaqueError::from_boxed(err.into()))
+            .context("resolve connect-udp target")?;
+        ipv6.into_iter()
+            .next()
+            .map(Into::into)
+            .context("resolve connect-udp target: no ip addresses found")
+    }
+
+    async fn connect(&self, target: ConnectUdpTarget) -> Result<UdpSocket, OpaqueError> {
+        let addr = SocketAddr::new(
+            self.resolve(target.0.host().clone()).await?,
+            target.0.port(),
+        );
+        if !(self.target_filter)(&target, addr) {
+            return Err(OpaqueError::from_display(format!(
+                "connect-udp target {} ({addr}) is not allowed",
+                target.0
+            )));
+        }
+        let bind_addr: IpAddr = match addr {
+            SocketAddr::V4(_) => Ipv4Addr::UNSPECIFIED.into(),
+            SocketAddr::V6(_) => Ipv6Addr::UNSPECIFIED.into(),
+        };
+        let socket = UdpSocket::bind((bind_addr, 0))
+            .await
+            .context("bind udp socket")?;
+        socket
+            .connect(addr)
+            .await
+            .context("connect udp socket to target")?;
+        Ok(socket)
+    }
+}
+
+impl<State, Dns> Service<State, Upgraded> for ConnectUdpRelay<Dns>
+where
+    State: Clone + Send + Sync + 'static,
+    Dns: DnsResolver<Error: Into<BoxError>>,
+{
+    type Response = ();
+    type Error = Infallible;
+
+    async fn serve(
+        &self,
+        ctx: Context<State>,
+        upgraded: Upgraded,
+    ) -> Result<Self::Response, Self::Error> {
+        let Some(target) = ctx.get::<ConnectUdpTarget>().cloned() else {
+            tracing::debug!("ConnectUdpRelay: missing connect-udp target");
+            return Ok(());
+        };
+        let socket = match self.connect(target.clone()).await {
+            Ok(socket) => socket,
+            Err(err) => {
+                tracing::debug!(udp_target = %target.0, error = %err, "ConnectUdpRelay: failed to connect");
+                return Ok(());
+            }
+        };
+        tracing::trace!(udp_target = %target.0, "ConnectUdpRelay: relaying udp payloads");
+        if let Err(err) = relay(UdpTunnel::new(upgraded), &socket).await {
+            tracing::debug!(udp_target = %target.0, error = %err, "ConnectUdpRelay: relay failed");
+        }
+        Ok(())
+    }
+}
+
+/// Relay the UDP payloads between the given tunnel and (connected) socket,
+/// until the tunnel is closed.
+async fn relay<S>(mut tunnel: UdpTunnel<S>, socket: &UdpSocket) -> io::Result<()>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    enum Event {
+        Tunnel(io::Result<Option<bytes::Bytes>>),
+        Socket(io::Result<usize>),
+    }
+
+    let mut buf = vec![0; masque::MAX_UDP_PAYLOAD_SIZE];
+    loop {
+        let event = tokio::select! {
+            result = tunnel.recv() => Event::Tunnel(result),
+            result = socket.recv(&mut buf) => Event::Socket(result),
+        };
+        match event {
+            Event::Tunnel(result) => match result? {
+                Some(payload) => {
+                    if let Err(err) = socket.send(&payload).await {
+                        // udp is unreliable, so a failed send only drops the payload
+                        tracing::trace!(error = %err, "ConnectUdpRelay: failed to send udp payload");
+                    }
+                }
+                None => return Ok(()),
+            },
+            Event::Socket(result) => match result {
+                Ok(n) => tunnel.send(&buf[..n]).await?,
+                Err(err) => {
+                    // e.g. an ICMP port unreachable reported for a previous payload
+                    tracing::trace!(error = %err, "ConnectUdpRelay: failed to receive udp payload");
+                }
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::{ConnectUdpConnector, H2cMode, HttpClient};
+    use crate::masque::UdpProxyTemplate;
+    use crate::server::layer::upgrade::{ConnectUdpAcceptor, ConnectUdpMatcher, UpgradeLayer};
+    use crate::server::HttpServer;
+    use rama_core::{rt::Executor, service::service_fn, Layer};
+    use rama_http_types::{IntoResponse, Request, Response, StatusCode};
+    use rama_net::address::Authority;
+
+    #[test]
+    fn test_is_global_ip() {
+        for (ip, expected) in [
+            ("1.1.1.1", true),
+            ("8.8.8.8", true),
+            ("2606:4700:4700::1111", true),
+            ("127.0.0.1", false),
+            ("10.1.2.3", false),
+            ("172.16.0.1", false),
+            ("192.168.1.1", false),
+            ("169.254.169.254", false),
+            ("100.64.0.1", false),
+            ("0.0.0.0", false),
+            ("255.255.255.255", false),
+            ("224.0.0.1", false),
+            ("::1", false),
+            ("::", false),
+            ("fd00::1", false),
+            ("fe80::1", false),
+            ("::ffff:127.0.0.1", false),
+            ("::ffff:1.1.1.1", true),
+        ] {
+            assert_eq!(expected, is_global_ip(ip.parse().unwrap()), "ip: {ip}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_connect_udp_relay_target_filter() {
+        let target = ConnectUdpTarget(Authority::try_from("127.0.0.1:53").unwrap());
+        assert!(ConnectUdpRelay::new()
+            .connect(target.clone())
+            .await
+            .is_err());
+        assert!(ConnectUdpRelay::new()
+            .with_target_filter(|_, addr| addr.ip().is_loopback())
+            .connect(target)
+            .await
+            .is_ok());
+    }
+
+    /// Spawn a CONNECT-UDP proxy serving a single (http/1.1 or h2) connection,
+    /// returning the template to use for it.
+    async fn spawn_proxy(relay: ConnectUdpRelay) -> UdpProxyTemplate {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = HttpServer::auto(Executor::default()).service(
+            UpgradeLayer::new(
+                ConnectUdpMatcher::default(),
+                ConnectUdpAcceptor::new(),
+                relay,
+            )
+            .layer(service_fn(|_req: Request| async {
+                Ok::<Response, Infallible>(StatusCode::NOT_FOUND.into_response())
+            })),
+        );
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let _ = server.serve(Context::<()>::default(), stream).await;
+        });
+        UdpProxyTemplate::with_default_path(&format!("http://{addr}")).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_connect_udp_end_to_end() {
+        let target = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let target_addr = target.local_addr().unwrap();
+
+        for h2c_mode in [None, Some(H2cMode::PriorKnowledge)] {
+            let template = spawn_proxy(
+                ConnectUdpRelay::new().with_target_filter(|_, addr| addr.ip().is_loopback()),
+            )
+            .await;
+            let connector = ConnectUdpConnector::new(HttpClient::default(), template);
+
+            let mut ctx = Context::<()>::default();
+            if let Some(mode) = h2c_mode {
+                ctx.insert(mode);
+            }
+            let mut tunnel = connector.serve(ctx, target_addr.into()).await.unwrap();
+
+            tunnel.send(b"ping").await.unwrap();
+            let mut buf = [0; 64];
+            let (n, peer) = target.recv_from(&mut buf).await.unwrap();
+            assert_eq!(b"ping", &buf[..n], "h2c mode: {h2c_mode:?}");
+
+            target.send_to(b"pong", peer).await.unwrap();
+            assert_eq!(
+                &b"pong"[..],
+                &tunnel.recv().await.unwrap().unwrap()[..],
+                "h2c mode: {h2c_mode:?}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_connect_udp_end_to_end_target_not_allowed() {
+        let target = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let target_addr = target.local_addr().unwrap();
+
+        let template = spawn_proxy(ConnectUdpRelay::new()).await;
+        let connector = ConnectUdpConnector::new(HttpClient::default(), template);
+        let mut tunnel = connector
+            .serve(Context::<()>::default(), target_addr.into())
+            .await
+            .unwrap();
+
+        // the relay refuses the (loopback) target and closes the tunnel
+        tunnel.send(b"ping").await.ok();
+        assert!(!matches!(tunnel.recv().await, Ok(Some(_))));
+    }
+
+    #[tokio::test]
+    async fn test_connect_udp_relay() {
+        let target = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let target_addr = target.local_addr().unwrap();
+
+        let relay_socket = ConnectUdpRelay::new()
+            .with_target_filter(|_, addr| addr.ip().is_loopback())
+            .connect(ConnectUdpTarget(target_addr.into()))
+            .await
+            .unwrap();
+
+        let (client, server) = tokio::io::duplex(1024);
+        let handle = tokio::spawn(async move {
+            relay(UdpTunnel::new(server), &relay_socket).await.unwrap();
+        });
+        let mut client = UdpTunnel::new(client);
+
+        client.send(b"ping").await.unwrap();
+        let mut buf = [0; 64];
+        let (n, peer) = target.recv_from(&mut buf).await.unwrap();
+        assert_eq!(b"ping", &buf[..n]);
+
+        target.send_to(b"pong", peer).await.unwrap();
+        assert_eq!(&b"pong"[..], &client.recv().await.unwrap().unwrap()[..]);
+
+        drop(client);
+        handle.await.unwrap();
+    }
+}
@@ -10,4 +10,15 @@ mod layer;
 #[doc(inline)]
 pub use layer::UpgradeLayer;
 
+pub mod websocket;
+#[doc(inline)]
+pub use websocket::{WebSocketAcceptor, WebSocketMatcher};
+
+pub mod connect_udp;
+#[doc(inline)]
+pub use connect_udp::{ConnectUdpAcceptor, ConnectUdpMatcher};
+#[cfg(feature = "connect-udp")]
+#[doc(inline)]
+pub use connect_udp::ConnectUdpRelay;
+
 pub use rama_http_core::upgrade::Upgraded;
@@ -0,0 +1,83 @@
+//! WebSocket handshake support for the [`UpgradeLayer`],
+//! both for http/1.1 upgrades and h2 extended CONNECT requests (RFC 8441).
+//!
+//! [`UpgradeLayer`]: super::UpgradeLayer
+
+use crate::websocket;
+use rama_core::{context::Extensions, matcher::Matcher, Context, Service};
+use rama_http_types::{IntoResponse, Request, Response, StatusCode};
+
+#[derive(Debug, Clone, Default)]
+#[non_exhaustive]
+/// A [`Matcher`] which matches WebSocket handshakes, be it
+/// a http/1.1 upgrade request or an h2 extended CONNECT request
+/// with `websocket` as its `:protocol` pseudo header.
+///
+/// The latter is only possible in case the h2 server advertises
+/// support for the extended CONNECT protocol, which the [`HttpServer`] does by default.
+///
+/// [`HttpServer`]: crate::server::HttpServer
+pub struct WebSocketMatcher;
+
+impl WebSocketMatcher {
+    /// Create a new [`WebSocketMatcher`].
+    pub const fn new() -> Self {
+        Self
+    }
+}
+
+impl<State, Body> Matcher<State, Request<Body>> for WebSocketMatcher {
+    fn matches(
+        &self,
+        _ext: Option<&mut Extensions>,
+        _ctx: &Context<State>,
+        req: &Request<Body>,
+    ) -> bool {
+        websocket::is_h1_websocket_handshake(req) || websocket::is_h2_websocket_handshake(req)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+#[non_exhaustive]
+/// A responder [`Service`] that can be used in an [`UpgradeLayer`]
+/// to accept WebSocket handshakes matched by the [`WebSocketMatcher`].
+///
+/// It responds with `101 Switching Protocols` to http/1.1 handshakes
+/// and with `200 OK` to h2 extended CONNECT handshakes, after which
+/// the upgraded stream is passed to the upgrade handler.
+/// Invalid handshakes are responded to with `400 Bad Request`.
+///
+/// [`UpgradeLayer`]: super::UpgradeLayer
+pub struct WebSocketAcceptor;
+
+impl WebSocketAcceptor {
+    /// Create a new [`WebSocketAcceptor`].
+    pub const fn new() -> Self {
+        Self
+    }
+}
+
+impl<State> Service<State, Request> for WebSocketAcceptor
+where
+    State: Clone + Send + Sync + 'static,
+{
+    type Response = (Response, Context<State>, Request);
+    type Error = Response;
+
+    async fn serve(
+        &self,
+        ctx: Context<State>,
+        req: Request,
+    ) -> Result<Self::Response, Self::Error> {
+        match websocket::accept_websocket_handshake(&req) {
+            Some(resp) => Ok((resp, ctx, req)),
+            None => {
+                tracing::debug!(
+                    http_version = ?req.version(),
+                    "WebSocketAcceptor: invalid websocket handshake"
+                );
+                Err(StatusCode::BAD_REQUEST.into_response())
+            }
+        }
+    }
+}
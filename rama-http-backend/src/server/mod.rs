@@ -10,3 +10,6 @@ pub use service::HttpServer;
 mod hyper_conn;
 
 pub mod layer;
+
+#[cfg(feature = "dns")]
+pub mod dns;
@@ -93,12 +93,16 @@ impl HttpServer<Http1ConnBuilder> {
 
 impl HttpServer<H2ConnBuilder> {
     /// Create a new h2 `Builder` with default settings.
+    ///
+    /// The [extended CONNECT protocol] is enabled by default,
+    /// such that WebSockets can be bootstrapped over h2.
+    ///
+    /// [extended CONNECT protocol]: https://datatracker.ietf.org/doc/html/rfc8441#section-4
     pub fn h2(exec: Executor) -> Self {
         let guard = exec.guard().cloned();
-        Self {
-            builder: H2ConnBuilder::new(exec),
-            guard,
-        }
+        let mut builder = H2ConnBuilder::new(exec);
+        builder.enable_connect_protocol();
+        Self { builder, guard }
     }
 }
 
@@ -111,12 +115,16 @@ impl HttpServer<H2ConnBuilder> {
 
 impl HttpServer<AutoConnBuilder> {
     /// Create a new dual http/1.1 + h2 `Builder` with default settings.
+    ///
+    /// The [extended CONNECT protocol] is enabled by default for h2,
+    /// such that WebSockets can be bootstrapped over h2.
+    ///
+    /// [extended CONNECT protocol]: https://datatracker.ietf.org/doc/html/rfc8441#section-4
     pub fn auto(exec: Executor) -> Self {
         let guard = exec.guard().cloned();
-        Self {
-            builder: AutoConnBuilder::new(exec),
-            guard,
-        }
+        let mut builder = AutoConnBuilder::new(exec);
+        builder.http2().enable_connect_protocol();
+        Self { builder, guard }
     }
 }
 
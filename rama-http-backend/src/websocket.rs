@@ -0,0 +1,232 @@
+//! Internal utilities to recognise and translate WebSocket handshakes,
+//! bootstrapped over http/1.1 ([RFC 6455]) or over h2 using
+//! the extended CONNECT protocol ([RFC 8441]).
+//!
+//! [RFC 6455]: https://datatracker.ietf.org/doc/html/rfc6455#section-4
+//! [RFC 8441]: https://datatracker.ietf.org/doc/html/rfc8441#section-5
+
+use base64::Engine as _;
+use rama_core::error::OpaqueError;
+use rama_http_core::ext::Protocol;
+use rama_http_types::{
+    header::{CONNECTION, SEC_WEBSOCKET_ACCEPT, SEC_WEBSOCKET_KEY, SEC_WEBSOCKET_VERSION, UPGRADE},
+    headers::{HeaderMapExt, SecWebsocketAccept, SecWebsocketKey},
+    HeaderMap, HeaderName, HeaderValue, IntoResponse, Method, Request, Response, StatusCode,
+};
+
+/// Value of the `:protocol` pseudo header and `Upgrade` header for WebSockets.
+pub(crate) const WEBSOCKET: &str = "websocket";
+
+/// Returns true if the request is a http/1.1 WebSocket handshake (RFC 6455).
+pub(crate) fn is_h1_websocket_handshake<B>(req: &Request<B>) -> bool {
+    req.method() == Method::GET
+        && header_contains_token(req.headers(), &UPGRADE, WEBSOCKET)
+        && header_contains_token(req.headers(), &CONNECTION, "upgrade")
+}
+
+/// Returns true if the request is an h2 extended CONNECT WebSocket handshake (RFC 8441).
+pub(crate) fn is_h2_websocket_handshake<B>(req: &Request<B>) -> bool {
+    req.method() == Method::CONNECT
+        && req
+            .extensions()
+            .get::<Protocol>()
+            .is_some_and(|protocol| protocol.as_str().eq_ignore_ascii_case(WEBSOCKET))
+}
+
+/// Create the response accepting the given WebSocket handshake,
+/// `None` is returned in case the handshake is invalid.
+pub(crate) fn accept_websocket_handshake<B>(req: &Request<B>) -> Option<Response> {
+    if is_h2_websocket_handshake(req) {
+        return Some(StatusCode::OK.into_response());
+    }
+    if !is_h1_websocket_handshake(req) {
+        return None;
+    }
+    let key = req.headers().typed_get::<SecWebsocketKey>()?;
+    let mut resp = StatusCode::SWITCHING_PROTOCOLS.into_response();
+    insert_h1_upgrade_headers(resp.headers_mut());
+    resp.headers_mut()
+        .typed_insert(SecWebsocketAccept::from(key));
+    Some(resp)
+}
+
+/// Translate a http/1.1 WebSocket handshake into an extended CONNECT request,
+/// returning the `Sec-WebSocket-Key` of the original request (if any),
+/// such that the response can be translated back using [`h2_into_h1_handshake_response`].
+pub(crate) fn h1_into_h2_handshake<B>(
+    mut req: Request<B>,
+) -> (Request<B>, Option<SecWebsocketKey>) {
+    let key = req.headers().typed_get::<SecWebsocketKey>();
+    req.headers_mut().remove(SEC_WEBSOCKET_KEY);
+    req.headers_mut().remove(UPGRADE);
+    req.headers_mut().remove(CONNECTION);
+    *req.method_mut() = Method::CONNECT;
+    req.extensions_mut()
+        .insert(Protocol::from_static(WEBSOCKET));
+    (req, key)
+}
+
+/// Translate the (h2) response of an extended CONNECT WebSocket handshake
+/// into the response expected for the original http/1.1 handshake.
+pub(crate) fn h2_into_h1_handshake_response<B>(
+    mut resp: Response<B>,
+    key: Option<SecWebsocketKey>,
+) -> Response<B> {
+    if resp.status() != StatusCode::OK {
+        return resp;
+    }
+    *resp.status_mut() = StatusCode::SWITCHING_PROTOCOLS;
+    insert_h1_upgrade_headers(resp.headers_mut());
+    if let Some(key) = key {
+        resp.headers_mut()
+            .typed_insert(SecWebsocketAccept::from(key));
+    }
+    resp
+}
+
+/// Translate an extended CONNECT WebSocket handshake into a http/1.1 handshake,
+/// such that it can be sent over a http/1.1 connection.
+///
+/// The generated `Sec-WebSocket-Key` is returned, such that the response
+/// can be validated and translated back using [`h1_into_h2_handshake_response`].
+pub(crate) fn h2_into_h1_handshake<B>(mut req: Request<B>) -> (Request<B>, SecWebsocketKey) {
+    req.extensions_mut().remove::<Protocol>();
+    *req.method_mut() = Method::GET;
+    insert_h1_upgrade_headers(req.headers_mut());
+    let key = base64::engine::general_purpose::STANDARD.encode(rand::random::<[u8; 16]>());
+    req.headers_mut().insert(
+        SEC_WEBSOCKET_KEY,
+        HeaderValue::try_from(key).expect("base64 encoded key to be a valid header value"),
+    );
+    if !req.headers().contains_key(SEC_WEBSOCKET_VERSION) {
+        req.headers_mut()
+            .insert(SEC_WEBSOCKET_VERSION, HeaderValue::from_static("13"));
+    }
+    let key = req
+        .headers()
+        .typed_get::<SecWebsocketKey>()
+        .expect("generated Sec-WebSocket-Key header to be valid");
+    (req, key)
+}
+
+/// Translate the (http/1.1) response of a WebSocket handshake
+/// into the response expected for the original extended CONNECT request.
+///
+/// An error is returned in case the server accepted the handshake
+/// without a `Sec-WebSocket-Accept` header matching the given key.
+pub(crate) fn h1_into_h2_handshake_response<B>(
+    mut resp: Response<B>,
+    key: SecWebsocketKey,
+) -> Result<Response<B>, OpaqueError> {
+    if resp.status() != StatusCode::SWITCHING_PROTOCOLS {
+        return Ok(resp);
+    }
+    match resp.headers().typed_get::<SecWebsocketAccept>() {
+        Some(accept) if accept == SecWebsocketAccept::from(key) => (),
+        Some(_) => {
+            return Err(OpaqueError::from_display(
+                "websocket handshake: server responded with an invalid Sec-WebSocket-Accept header",
+            ))
+        }
+        None => {
+            return Err(OpaqueError::from_display(
+                "websocket handshake: server responded without a Sec-WebSocket-Accept header",
+            ))
+        }
+    }
+    *resp.status_mut() = StatusCode::OK;
+    for name in [&UPGRADE, &CONNECTION, &SEC_WEBSOCKET_ACCEPT] {
+        resp.headers_mut().remove(name);
+    }
+    Ok(resp)
+}
+
+fn insert_h1_upgrade_headers(headers: &mut HeaderMap) {
+    headers.insert(UPGRADE, HeaderValue::from_static(WEBSOCKET));
+    headers.insert(CONNECTION, HeaderValue::from_static("upgrade"));
+}
+
+pub(crate) fn header_contains_token(headers: &HeaderMap, name: &HeaderName, token: &str) -> bool {
+    headers
+        .get_all(name)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|value| value.trim().eq_ignore_ascii_case(token))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn h1_handshake() -> Request<()> {
+        Request::builder()
+            .uri("/chat")
+            .header(UPGRADE, "websocket")
+            .header(CONNECTION, "keep-alive, Upgrade")
+            .header(SEC_WEBSOCKET_KEY, "dGhlIHNhbXBsZSBub25jZQ==")
+            .header(SEC_WEBSOCKET_VERSION, "13")
+            .body(())
+            .unwrap()
+    }
+
+    #[test]
+    fn test_accept_h1_websocket_handshake() {
+        let resp = accept_websocket_handshake(&h1_handshake()).unwrap();
+        assert_eq!(StatusCode::SWITCHING_PROTOCOLS, resp.status());
+        assert_eq!(
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=",
+            resp.headers().get(SEC_WEBSOCKET_ACCEPT).unwrap()
+        );
+
+        let mut req = h1_handshake();
+        req.headers_mut().remove(SEC_WEBSOCKET_KEY);
+        assert!(accept_websocket_handshake(&req).is_none());
+    }
+
+    #[test]
+    fn test_h1_h2_websocket_handshake_roundtrip() {
+        let (req, key) = h1_into_h2_handshake(h1_handshake());
+        assert!(is_h2_websocket_handshake(&req));
+        assert!(!is_h1_websocket_handshake(&req));
+        assert!(!req.headers().contains_key(SEC_WEBSOCKET_KEY));
+
+        let resp = accept_websocket_handshake(&req).unwrap();
+        assert_eq!(StatusCode::OK, resp.status());
+        let resp = h2_into_h1_handshake_response(resp, key);
+        assert_eq!(StatusCode::SWITCHING_PROTOCOLS, resp.status());
+        assert_eq!(
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=",
+            resp.headers().get(SEC_WEBSOCKET_ACCEPT).unwrap()
+        );
+
+        let (req, key) = h2_into_h1_handshake(req);
+        assert!(is_h1_websocket_handshake(&req));
+        assert!(req.headers().contains_key(SEC_WEBSOCKET_KEY));
+        let resp =
+            h1_into_h2_handshake_response(accept_websocket_handshake(&req).unwrap(), key).unwrap();
+        assert_eq!(StatusCode::OK, resp.status());
+        assert!(!resp.headers().contains_key(SEC_WEBSOCKET_ACCEPT));
+    }
+
+    #[test]
+    fn test_h1_into_h2_handshake_response_validates_accept() {
+        let (req, key) = h2_into_h1_handshake(h1_into_h2_handshake(h1_handshake()).0);
+
+        let mut resp = accept_websocket_handshake(&req).unwrap();
+        resp.headers_mut().insert(
+            SEC_WEBSOCKET_ACCEPT,
+            HeaderValue::from_static("s3pPLMBiTxaQ9kYGzzhZRbK+xOo="),
+        );
+        assert!(h1_into_h2_handshake_response(resp, key.clone()).is_err());
+
+        let mut resp = accept_websocket_handshake(&req).unwrap();
+        resp.headers_mut().remove(SEC_WEBSOCKET_ACCEPT);
+        assert!(h1_into_h2_handshake_response(resp, key.clone()).is_err());
+
+        // non-accepted handshakes are passed through as-is
+        let resp =
+            h1_into_h2_handshake_response(StatusCode::FORBIDDEN.into_response(), key).unwrap();
+        assert_eq!(StatusCode::FORBIDDEN, resp.status());
+    }
+}
@@ -4,6 +4,7 @@ use std::fmt;
 use std::future::Future;
 use std::pin::Pin;
 use std::task::{ready, Context, Poll};
+use std::time::Duration;
 
 use bytes::Bytes;
 use httparse::ParserConfig;
@@ -112,6 +113,7 @@ pub struct Builder {
     h1_writev: Option<bool>,
     h1_title_case_headers: bool,
     h1_max_headers: Option<usize>,
+    h1_expect_continue_timeout: Option<Duration>,
 
     h1_read_buf_exact_size: Option<usize>,
     h1_max_buf_size: Option<usize>,
@@ -310,6 +312,7 @@ impl Builder {
             h1_parser_config: Default::default(),
             h1_title_case_headers: false,
             h1_max_headers: None,
+            h1_expect_continue_timeout: None,
             h1_max_buf_size: None,
         }
     }
@@ -440,6 +443,24 @@ impl Builder {
         self
     }
 
+    /// Set a timeout to wait for a `100 Continue` response before sending the request body.
+    ///
+    /// When set, the body of a request with an `Expect: 100-continue` header is only sent
+    /// once the server responded with `100 Continue` or when this timeout elapsed.
+    /// If the server responds with a final response instead, the body is not sent at all
+    /// and the connection is closed after that response.
+    ///
+    /// Informational responses themselves can be observed using
+    /// the [`OnInformational`] request extension.
+    ///
+    /// Default is to send the request body immediately.
+    ///
+    /// [`OnInformational`]: crate::ext::OnInformational
+    pub fn expect_continue_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.h1_expect_continue_timeout = Some(timeout);
+        self
+    }
+
     /// Sets the exact size of the read buffer to *always* use.
     ///
     /// Note that setting this option unsets the `max_buf_size` option.
@@ -509,6 +530,9 @@ impl Builder {
             if opts.h09_responses {
                 conn.set_h09_responses();
             }
+            if let Some(timeout) = opts.h1_expect_continue_timeout {
+                conn.set_expect_continue_timeout(timeout);
+            }
 
             if let Some(sz) = opts.h1_read_buf_exact_size {
                 conn.set_read_buf_exact_size(sz);
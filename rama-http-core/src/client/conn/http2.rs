@@ -449,7 +449,8 @@ impl Builder {
             trace!("client handshake HTTP/2");
 
             let (tx, rx) = dispatch::channel();
-            let h2 = proto::h2::client::handshake(io, rx, &opts.h2_builder, opts.exec).await?;
+            let (h2, _) =
+                proto::h2::client::handshake(io, rx, &opts.h2_builder, opts.exec, None).await?;
             Ok((
                 SendRequest {
                     dispatch: tx.unbound(),
@@ -460,6 +461,77 @@ impl Builder {
             ))
         }
     }
+
+    /// Constructs a connection with the configured options and IO,
+    /// for a connection that was upgraded from http/1.1 to cleartext h2 (`h2c`),
+    /// as described in [RFC 7540 §3.2].
+    ///
+    /// The `upgrade_request` is the (bodiless) http/1.1 request that was used
+    /// to upgrade the connection. It is not sent again, but assigned stream 1,
+    /// on which the server sends its response. That response is resolved
+    /// by the returned [`H2cUpgradeResponse`].
+    ///
+    /// Note, if [`Connection`] is not `await`-ed, [`SendRequest`] will
+    /// do nothing, and the [`H2cUpgradeResponse`] will never resolve.
+    ///
+    /// [RFC 7540 §3.2]: https://datatracker.ietf.org/doc/html/rfc7540#section-3.2
+    pub fn handshake_h2c_upgraded<T, B>(
+        &self,
+        io: T,
+        upgrade_request: Request<()>,
+    ) -> impl Future<Output = crate::Result<(SendRequest<B>, Connection<T, B>, H2cUpgradeResponse)>>
+    where
+        T: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+        B: Body<Data: Send + 'static, Error: Into<BoxError>> + Send + 'static + Unpin,
+    {
+        let opts = self.clone();
+
+        async move {
+            trace!("client handshake HTTP/2 (h2c upgrade)");
+
+            let (tx, rx) = dispatch::channel();
+            let (h2, response) = proto::h2::client::handshake(
+                io,
+                rx,
+                &opts.h2_builder,
+                opts.exec,
+                Some(upgrade_request),
+            )
+            .await?;
+            let response = response.expect("h2c upgrade response future");
+            Ok((
+                SendRequest {
+                    dispatch: tx.unbound(),
+                },
+                Connection {
+                    inner: (PhantomData, h2),
+                },
+                H2cUpgradeResponse { inner: response },
+            ))
+        }
+    }
+}
+
+/// A future resolving to the response of the request
+/// which was used to upgrade a connection to h2c,
+/// returned by [`Builder::handshake_h2c_upgraded`].
+#[must_use = "futures do nothing unless polled"]
+pub struct H2cUpgradeResponse {
+    inner: proto::h2::client::H2cUpgradeResponse,
+}
+
+impl fmt::Debug for H2cUpgradeResponse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("H2cUpgradeResponse").finish()
+    }
+}
+
+impl Future for H2cUpgradeResponse {
+    type Output = crate::Result<Response<IncomingBody>>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.inner.as_mut().poll(cx)
+    }
 }
 
 #[cfg(test)]
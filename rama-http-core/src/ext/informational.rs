@@ -0,0 +1,157 @@
+use futures_channel::mpsc;
+use rama_http_types::{Response, StatusCode};
+use std::{fmt, sync::Arc};
+
+/// A handle to send informational (1xx) responses, such as `103 Early Hints`,
+/// prior to the final response of a request.
+///
+/// # Servers
+///
+/// The http/1.1 and h2 servers insert an `InformationalSender` in the extensions
+/// of each incoming `http::Request`. Services can use it to send any number of
+/// informational responses, as long as the final response was not yet returned.
+/// Sending informational responses after that results in an error.
+///
+/// For http/1.0 peers, informational responses are never written,
+/// as they are not supported by that version of the protocol.
+///
+/// Sending a `100 Continue` response manually to a request which expects it
+/// prevents the server from doing so automatically once the body is read.
+#[derive(Clone)]
+pub struct InformationalSender {
+    tx: mpsc::UnboundedSender<Response<()>>,
+}
+
+pub(crate) type InformationalReceiver = mpsc::UnboundedReceiver<Response<()>>;
+
+impl InformationalSender {
+    pub(crate) fn channel() -> (Self, InformationalReceiver) {
+        let (tx, rx) = mpsc::unbounded();
+        (Self { tx }, rx)
+    }
+
+    /// Send an informational response to the client.
+    ///
+    /// Only the status and headers of the response are sent,
+    /// its extensions and version are ignored.
+    ///
+    /// An error is returned in case the status code is not informational,
+    /// `101 Switching Protocols` (which has to be returned as the final response)
+    /// or when the connection no longer accepts informational responses
+    /// for this request.
+    pub fn send(&self, response: Response<()>) -> Result<(), InformationalError> {
+        let status = response.status();
+        if !status.is_informational() || status == StatusCode::SWITCHING_PROTOCOLS {
+            return Err(InformationalError::InvalidStatus(status));
+        }
+        self.tx
+            .unbounded_send(response)
+            .map_err(|_| InformationalError::Closed)
+    }
+
+    /// Returns true if informational responses can no longer be sent.
+    pub fn is_closed(&self) -> bool {
+        self.tx.is_closed()
+    }
+}
+
+impl fmt::Debug for InformationalSender {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("InformationalSender")
+            .field("closed", &self.tx.is_closed())
+            .finish()
+    }
+}
+
+/// Error returned by [`InformationalSender::send`].
+#[derive(Debug)]
+pub enum InformationalError {
+    /// The status code is not a valid informational status code.
+    InvalidStatus(StatusCode),
+    /// The final response was already sent or the connection is closed.
+    Closed,
+}
+
+impl fmt::Display for InformationalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidStatus(status) => {
+                write!(f, "invalid informational response status: {status}")
+            }
+            Self::Closed => f.write_str("informational responses no longer accepted"),
+        }
+    }
+}
+
+impl std::error::Error for InformationalError {}
+
+/// A callback invoked by the http/1.1 and h2 clients for each
+/// informational (1xx) response received prior to the final response.
+///
+/// It can be used by inserting it in the extensions
+/// of the `http::Request` to be sent.
+///
+/// `101 Switching Protocols` is not considered informational for this purpose,
+/// as it is returned as the final response.
+///
+/// The callback is invoked while the connection is being driven,
+/// and should therefore not block.
+#[derive(Clone)]
+pub struct OnInformational(Arc<dyn Fn(&Response<()>) + Send + Sync + 'static>);
+
+impl OnInformational {
+    /// Create a new [`OnInformational`] callback.
+    pub fn new<F>(callback: F) -> Self
+    where
+        F: Fn(&Response<()>) + Send + Sync + 'static,
+    {
+        Self(Arc::new(callback))
+    }
+
+    pub(crate) fn call(&self, response: &Response<()>) {
+        (self.0)(response)
+    }
+}
+
+impl fmt::Debug for OnInformational {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OnInformational").finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::StreamExt;
+
+    #[tokio::test]
+    async fn test_informational_sender() {
+        let (tx, mut rx) = InformationalSender::channel();
+
+        let early_hints = Response::builder()
+            .status(StatusCode::from_u16(103).unwrap())
+            .header("link", "</style.css>; rel=preload; as=style")
+            .body(())
+            .unwrap();
+        tx.send(early_hints).unwrap();
+
+        for status in [StatusCode::OK, StatusCode::SWITCHING_PROTOCOLS] {
+            let mut response = Response::new(());
+            *response.status_mut() = status;
+            assert!(matches!(
+                tx.send(response),
+                Err(InformationalError::InvalidStatus(s)) if s == status
+            ));
+        }
+
+        let response = rx.next().await.unwrap();
+        assert_eq!(StatusCode::from_u16(103).unwrap(), response.status());
+        assert!(response.headers().contains_key("link"));
+
+        drop(rx);
+        assert!(tx.is_closed());
+        let mut response = Response::new(());
+        *response.status_mut() = StatusCode::from_u16(103).unwrap();
+        assert!(matches!(tx.send(response), Err(InformationalError::Closed)));
+    }
+}
@@ -5,6 +5,10 @@ use std::fmt;
 mod h1_reason_phrase;
 pub use h1_reason_phrase::ReasonPhrase;
 
+mod informational;
+pub(crate) use informational::InformationalReceiver;
+pub use informational::{InformationalError, InformationalSender, OnInformational};
+
 /// Represents the `:protocol` pseudo-header used by
 /// the [Extended CONNECT Protocol].
 ///
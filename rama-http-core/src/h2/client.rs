@@ -537,6 +537,29 @@ where
             })
     }
 
+    /// Register the request that was sent as http/1.1 request
+    /// to upgrade the connection to h2c, see [RFC 7540 §3.2].
+    ///
+    /// The request is assigned stream 1 (half-closed for the client),
+    /// without sending it again, and the returned [`ResponseFuture`]
+    /// resolves to the response the server sends on that stream.
+    ///
+    /// This has to be called before any other request is sent.
+    ///
+    /// [RFC 7540 §3.2]: https://datatracker.ietf.org/doc/html/rfc7540#section-3.2
+    pub(crate) fn send_h2c_upgrade_request(
+        &mut self,
+        request: Request<()>,
+    ) -> Result<ResponseFuture, crate::h2::Error> {
+        self.inner
+            .send_upgrade_request(request)
+            .map_err(Into::into)
+            .map(|stream| ResponseFuture {
+                inner: stream.clone_to_opaque(),
+                push_promise_consumed: false,
+            })
+    }
+
     /// Returns whether the [extended CONNECT protocol][1] is enabled or not.
     ///
     /// This setting is configured by the server peer by sending the
@@ -550,6 +573,21 @@ where
         self.inner.is_extended_connect_protocol_enabled()
     }
 
+    /// Returns `Ready` once the initial `SETTINGS` frame of the server
+    /// has been received and applied.
+    ///
+    /// Settings advertised by the server, such as whether or not
+    /// the [extended CONNECT protocol][1] is enabled, are only known
+    /// once this function returned `Ready`.
+    ///
+    /// [1]: https://datatracker.ietf.org/doc/html/rfc8441#section-4
+    pub fn poll_remote_initial_settings(
+        &mut self,
+        cx: &mut Context,
+    ) -> Poll<Result<(), crate::h2::Error>> {
+        self.inner.poll_remote_initial_settings(cx)
+    }
+
     /// Returns the current max send streams
     pub fn current_max_send_streams(&self) -> usize {
         self.inner.current_max_send_streams()
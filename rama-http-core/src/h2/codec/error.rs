@@ -49,6 +49,10 @@ pub enum UserError {
 
     /// Tries to send push promise to peer who has disabled server push
     PeerDisabledServerPush,
+
+    /// Tries to send a request with the `:protocol` pseudo header
+    /// to a peer who has not enabled the extended CONNECT protocol (RFC 8441)
+    PeerDisabledExtendedConnectProtocol,
 }
 
 // ===== impl SendError =====
@@ -97,6 +101,9 @@ impl fmt::Display for UserError {
             UserError::PeerDisabledServerPush => {
                 "sending PUSH_PROMISE to peer who disabled server push"
             }
+            UserError::PeerDisabledExtendedConnectProtocol => {
+                "sending extended CONNECT request to peer who did not enable the extended CONNECT protocol"
+            }
         })
     }
 }
@@ -7,6 +7,7 @@ use crate::h2::proto::*;
 
 use bytes::Bytes;
 use futures_core::Stream;
+use rama_http_types::Request;
 use std::io;
 use std::marker::PhantomData;
 use std::pin::Pin;
@@ -578,6 +579,24 @@ where
         self.inner.streams.next_incoming()
     }
 
+    /// Serve a connection upgraded from http/1.1 to h2c, see [RFC 7540 §3.2],
+    /// applying the settings of the client decoded from the `HTTP2-Settings` header
+    /// and opening stream 1 for the upgrade request.
+    ///
+    /// [RFC 7540 §3.2]: https://datatracker.ietf.org/doc/html/rfc7540#section-3.2
+    pub(crate) fn recv_h2c_upgrade(
+        &mut self,
+        settings: &frame::Settings,
+        request: Request<()>,
+    ) -> Result<(), Error> {
+        self.inner.settings.apply_upgrade_settings(
+            settings,
+            &mut self.codec,
+            &mut self.inner.streams,
+        )?;
+        self.inner.streams.recv_upgrade_request(request)
+    }
+
     // Graceful shutdown only makes sense for server peers.
     pub(crate) fn go_away_gracefully(&mut self) {
         if self.inner.go_away.is_going_away() {
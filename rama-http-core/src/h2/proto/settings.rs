@@ -108,6 +108,55 @@ impl Settings {
         !has_received
     }
 
+    /// Apply the settings of the client decoded from the `HTTP2-Settings` header
+    /// of the request used to upgrade the connection to h2c, see [RFC 7540 §3.2.1].
+    ///
+    /// These are applied as the initial settings of the client,
+    /// and are acknowledged implicitly by the `101 Switching Protocols` response.
+    ///
+    /// [RFC 7540 §3.2.1]: https://datatracker.ietf.org/doc/html/rfc7540#section-3.2.1
+    pub(crate) fn apply_upgrade_settings<T, B, C, P>(
+        &mut self,
+        settings: &frame::Settings,
+        dst: &mut Codec<T, B>,
+        streams: &mut Streams<C, P>,
+    ) -> Result<(), Error>
+    where
+        T: AsyncWrite + Unpin,
+        B: Buf,
+        C: Buf,
+        P: Peer,
+    {
+        tracing::trace!("applying h2c upgrade settings: {:?}", settings);
+        self.apply_remote_settings(settings, dst, streams)
+    }
+
+    fn apply_remote_settings<T, B, C, P>(
+        &mut self,
+        settings: &frame::Settings,
+        dst: &mut Codec<T, B>,
+        streams: &mut Streams<C, P>,
+    ) -> Result<(), Error>
+    where
+        T: AsyncWrite + Unpin,
+        B: Buf,
+        C: Buf,
+        P: Peer,
+    {
+        let is_initial = self.mark_remote_initial_settings_as_received();
+        streams.apply_remote_settings(settings, is_initial)?;
+
+        if let Some(val) = settings.header_table_size() {
+            dst.set_send_header_table_size(val as usize);
+        }
+
+        if let Some(val) = settings.max_frame_size() {
+            dst.set_max_send_frame_size(val as usize);
+        }
+
+        Ok(())
+    }
+
     pub(crate) fn poll_send<T, B, C, P>(
         &mut self,
         cx: &mut Context,
@@ -133,16 +182,7 @@ impl Settings {
 
             tracing::trace!("ACK sent; applying settings");
 
-            let is_initial = self.mark_remote_initial_settings_as_received();
-            streams.apply_remote_settings(&settings, is_initial)?;
-
-            if let Some(val) = settings.header_table_size() {
-                dst.set_send_header_table_size(val as usize);
-            }
-
-            if let Some(val) = settings.max_frame_size() {
-                dst.set_max_send_frame_size(val as usize);
-            }
+            self.apply_remote_settings(&settings, dst, streams)?;
         }
 
         self.remote = None;
@@ -269,11 +269,46 @@ impl Recv {
                 // corresponding headers frame pushed to `stream.pending_recv`.
                 self.pending_accept.push(stream);
             }
+        } else if let Some(on_informational) = stream.on_informational.as_ref() {
+            if let Some(status) = pseudo.status {
+                let mut response = Response::new(());
+                *response.status_mut() = status;
+                *response.version_mut() = rama_http_types::Version::HTTP_2;
+                *response.headers_mut() = fields;
+                on_informational.call(&response);
+            }
         }
 
         Ok(())
     }
 
+    /// Called by the server to receive the request used to upgrade
+    /// the connection to h2c, which is served on stream 1.
+    pub(super) fn recv_upgrade_request(
+        &mut self,
+        request: Request<()>,
+        stream: &mut store::Ptr,
+        counts: &mut Counts,
+    ) -> Result<(), Error> {
+        stream.state.recv_upgrade_open()?;
+
+        if stream.id > self.last_processed_id {
+            self.last_processed_id = stream.id;
+        }
+        counts.inc_num_recv_streams(stream);
+
+        stream.pending_recv.push_back(
+            &mut self.buffer,
+            Event::Headers(peer::PollMessage::Server(request)),
+        );
+        stream.notify_recv();
+
+        // Correctness: the request is pushed to `stream.pending_recv` above.
+        self.pending_accept.push(stream);
+
+        Ok(())
+    }
+
     /// Called by the server to get the request
     ///
     /// # Panics
@@ -167,6 +167,29 @@ impl Send {
         Ok(())
     }
 
+    /// Send an informational (1xx) HEADERS frame, which does not affect the
+    /// state of the stream as it is always followed by the final headers.
+    pub(super) fn send_informational_headers<B>(
+        &mut self,
+        frame: frame::Headers,
+        buffer: &mut Buffer<Frame<B>>,
+        stream: &mut store::Ptr,
+        task: &mut Option<Waker>,
+    ) -> Result<(), UserError> {
+        tracing::trace!("send_informational_headers; frame={:?}", frame);
+
+        Self::check_headers(frame.fields())?;
+
+        if frame.is_end_stream() || !stream.state.is_send_headers() {
+            return Err(UserError::UnexpectedFrameType);
+        }
+
+        self.prioritize
+            .queue_frame(frame.into(), buffer, stream, task);
+
+        Ok(())
+    }
+
     /// Send an explicit RST_STREAM frame
     pub(super) fn send_reset<B>(
         &mut self,
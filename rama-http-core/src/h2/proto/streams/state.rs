@@ -125,6 +125,21 @@ impl State {
         Ok(())
     }
 
+    /// Opens the stream for the request used to upgrade the connection to h2c,
+    /// which is half-closed (remote) as the request was received prior to the upgrade.
+    pub(super) fn recv_upgrade_open(&mut self) -> Result<(), Error> {
+        match self.inner {
+            Inner::Idle => {
+                self.inner = Inner::HalfClosedRemote(Peer::AwaitingHeaders);
+                Ok(())
+            }
+            ref state => {
+                proto_err!(conn: "recv_upgrade_open: in unexpected state {:?}", state);
+                Err(Error::library_go_away(Reason::PROTOCOL_ERROR))
+            }
+        }
+    }
+
     /// Opens the receive-half of the stream when a HEADERS frame is received.
     ///
     /// Returns true if this transitions the state to Open.
@@ -374,6 +389,17 @@ impl State {
         }
     }
 
+    /// Returns true when the local peer has not yet sent its (final) headers.
+    pub(super) fn is_send_headers(&self) -> bool {
+        matches!(
+            self.inner,
+            Inner::Open {
+                local: Peer::AwaitingHeaders,
+                ..
+            } | Inner::HalfClosedRemote(Peer::AwaitingHeaders)
+        )
+    }
+
     pub(super) fn is_send_streaming(&self) -> bool {
         matches!(
             self.inner,
@@ -114,6 +114,9 @@ pub(super) struct Stream {
 
     /// Validate content-length headers
     pub content_length: ContentLength,
+
+    /// Callback for informational (1xx) responses received by a client
+    pub on_informational: Option<crate::ext::OnInformational>,
 }
 
 /// State related to validating a stream's content-length
@@ -198,6 +201,7 @@ impl Stream {
             push_task: None,
             pending_push_promises: store::Queue::new(),
             content_length: ContentLength::Omitted,
+            on_informational: None,
         }
     }
 
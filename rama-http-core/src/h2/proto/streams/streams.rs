@@ -96,6 +96,12 @@ struct Actions {
 
     /// If the connection errors, a copy is kept for any StreamRefs.
     conn_error: Option<proto::Error>,
+
+    /// Whether the initial SETTINGS frame of the remote peer has been applied.
+    has_received_remote_initial_settings: bool,
+
+    /// Tasks waiting for the initial SETTINGS frame of the remote peer.
+    remote_initial_settings_waiters: Vec<Waker>,
 }
 
 /// Contains the buffer of frames to be written to the wire.
@@ -226,7 +232,14 @@ where
             &mut me.store,
             &mut me.counts,
             &mut me.actions.task,
-        )
+        )?;
+
+        if is_initial {
+            me.actions.has_received_remote_initial_settings = true;
+            me.actions.wake_remote_initial_settings_waiters();
+        }
+
+        Ok(())
     }
 
     pub(crate) fn apply_local_settings(&mut self, frame: &frame::Settings) -> Result<(), Error> {
@@ -246,6 +259,9 @@ where
         use rama_http_types::Method;
 
         let protocol = request.extensions_mut().remove::<Protocol>();
+        let on_informational = request
+            .extensions_mut()
+            .remove::<crate::ext::OnInformational>();
 
         // Clear before taking lock, incase extensions contain a StreamRef.
         clear_extensions_safely(request.extensions_mut());
@@ -264,6 +280,12 @@ where
         me.actions.ensure_no_conn_error()?;
         me.actions.send.ensure_next_stream_id()?;
 
+        // RFC 8441 §4: the `:protocol` pseudo header can only be used
+        // once the peer enabled the extended CONNECT protocol
+        if protocol.is_some() && !me.actions.send.is_extended_connect_protocol_enabled() {
+            return Err(UserError::PeerDisabledExtendedConnectProtocol.into());
+        }
+
         // The `pending` argument is provided by the `Client`, and holds
         // a store `Key` of a `Stream` that may have been not been opened
         // yet.
@@ -292,6 +314,7 @@ where
         if *request.method() == Method::HEAD {
             stream.content_length = ContentLength::Head;
         }
+        stream.on_informational = on_informational;
 
         // Convert the message
         let headers =
@@ -333,6 +356,101 @@ where
         ))
     }
 
+    /// Open stream 1 for the request that was sent as http/1.1 request
+    /// to upgrade the connection to h2c, see [RFC 7540 §3.2].
+    ///
+    /// The stream is opened half-closed (local), without sending any frame,
+    /// as the request was already sent prior to the upgrade.
+    ///
+    /// [RFC 7540 §3.2]: https://datatracker.ietf.org/doc/html/rfc7540#section-3.2
+    pub(crate) fn send_upgrade_request(
+        &mut self,
+        mut request: Request<()>,
+    ) -> Result<StreamRef<B>, SendError> {
+        use super::stream::ContentLength;
+        use rama_http_types::Method;
+
+        let on_informational = request
+            .extensions_mut()
+            .remove::<crate::ext::OnInformational>();
+
+        // Clear before taking lock, incase extensions contain a StreamRef.
+        clear_extensions_safely(request.extensions_mut());
+
+        let mut me = self.inner.lock().unwrap();
+        let me = &mut *me;
+
+        me.actions.ensure_no_conn_error()?;
+
+        if me.counts.peer().is_server()
+            || me.actions.send.ensure_next_stream_id()? != StreamId::from(1)
+            || !me.counts.can_inc_num_send_streams()
+        {
+            // The upgrade request is implicitly assigned stream 1,
+            // and thus has to be the first stream opened by the client.
+            return Err(UserError::Rejected.into());
+        }
+
+        let stream_id = me.actions.send.open()?;
+
+        let mut stream = Stream::new(
+            stream_id,
+            me.actions.send.init_window_sz(),
+            me.actions.recv.init_window_sz(),
+        );
+
+        if *request.method() == Method::HEAD {
+            stream.content_length = ContentLength::Head;
+        }
+        stream.on_informational = on_informational;
+
+        let mut stream = me.store.insert(stream.id, stream);
+        stream.state.send_open(true)?;
+        me.counts.inc_num_send_streams(&mut stream);
+
+        // TODO: ideally, OpaqueStreamRefs::new would do this, but we're holding
+        // the lock, so it can't.
+        me.refs += 1;
+
+        Ok(StreamRef {
+            opaque: OpaqueStreamRef::new(self.inner.clone(), &mut stream),
+            send_buffer: self.send_buffer.clone(),
+        })
+    }
+
+    /// Open stream 1 for the request that was received as http/1.1 request
+    /// to upgrade the connection to h2c, see [RFC 7540 §3.2].
+    ///
+    /// The stream is opened half-closed (remote), and is the first
+    /// stream returned by [`Self::next_incoming`].
+    ///
+    /// [RFC 7540 §3.2]: https://datatracker.ietf.org/doc/html/rfc7540#section-3.2
+    pub(crate) fn recv_upgrade_request(&mut self, request: Request<()>) -> Result<(), Error> {
+        let mut me = self.inner.lock().unwrap();
+        let me = &mut *me;
+
+        let Some(stream_id) =
+            me.actions
+                .recv
+                .open(StreamId::from(1), Open::Headers, &mut me.counts)?
+        else {
+            proto_err!(conn: "h2c upgrade request refused: max concurrent streams is zero");
+            return Err(Error::library_go_away(Reason::REFUSED_STREAM));
+        };
+
+        let stream = Stream::new(
+            stream_id,
+            me.actions.send.init_window_sz(),
+            me.actions.recv.init_window_sz(),
+        );
+        let stream = me.store.insert(stream_id, stream);
+
+        let actions = &mut me.actions;
+        me.counts.transition(stream, |counts, stream| {
+            actions.recv.recv_upgrade_request(request, stream, counts)
+        })
+    }
+
     pub(crate) fn is_extended_connect_protocol_enabled(&self) -> bool {
         self.inner
             .lock()
@@ -429,6 +547,8 @@ impl Inner {
                 send: Send::new(&config),
                 task: None,
                 conn_error: None,
+                has_received_remote_initial_settings: false,
+                remote_initial_settings_waiters: Vec::new(),
             },
             store: Store::new(),
             refs: 1,
@@ -716,6 +836,7 @@ impl Inner {
         });
 
         actions.conn_error = Some(err);
+        actions.wake_remote_initial_settings_waiters();
 
         last_processed_id
     }
@@ -746,6 +867,7 @@ impl Inner {
         });
 
         actions.conn_error = Some(err);
+        actions.wake_remote_initial_settings_waiters();
 
         Ok(())
     }
@@ -872,6 +994,8 @@ impl Inner {
             );
         }
 
+        actions.wake_remote_initial_settings_waiters();
+
         tracing::trace!("Streams::recv_eof");
 
         self.store.for_each(|stream| {
@@ -995,6 +1119,31 @@ where
         }
         Poll::Ready(Ok(()))
     }
+
+    pub(crate) fn poll_remote_initial_settings(
+        &mut self,
+        cx: &Context,
+    ) -> Poll<Result<(), crate::h2::Error>> {
+        let mut me = self.inner.lock().unwrap();
+        let me = &mut *me;
+
+        if me.actions.has_received_remote_initial_settings {
+            return Poll::Ready(Ok(()));
+        }
+        me.actions.ensure_no_conn_error()?;
+
+        if !me
+            .actions
+            .remote_initial_settings_waiters
+            .iter()
+            .any(|waker| waker.will_wake(cx.waker()))
+        {
+            me.actions
+                .remote_initial_settings_waiters
+                .push(cx.waker().clone());
+        }
+        Poll::Pending
+    }
 }
 
 impl<B, P> Streams<B, P>
@@ -1170,6 +1319,32 @@ impl<B> StreamRef<B> {
         })
     }
 
+    pub(crate) fn send_informational(
+        &mut self,
+        mut response: Response<()>,
+    ) -> Result<(), UserError> {
+        let status = response.status();
+        if !status.is_informational() || status == rama_http_types::StatusCode::SWITCHING_PROTOCOLS
+        {
+            return Err(UserError::UnexpectedFrameType);
+        }
+
+        // Clear before taking lock, incase extensions contain a StreamRef.
+        clear_extensions_safely(response.extensions_mut());
+        let mut me = self.opaque.inner.lock().unwrap();
+        let me = &mut *me;
+
+        let mut stream = me.store.resolve(self.opaque.key);
+        let actions = &mut me.actions;
+        let mut send_buffer = self.send_buffer.inner.lock().unwrap();
+        let send_buffer = &mut *send_buffer;
+
+        let frame = server::Peer::convert_send_message(stream.id, response, false);
+        actions
+            .send
+            .send_informational_headers(frame, send_buffer, &mut stream, &mut actions.task)
+    }
+
     pub(crate) fn send_push_promise(
         &mut self,
         mut request: Request<()>,
@@ -1636,6 +1811,12 @@ impl Actions {
         }
     }
 
+    fn wake_remote_initial_settings_waiters(&mut self) {
+        for waker in self.remote_initial_settings_waiters.drain(..) {
+            waker.wake();
+        }
+    }
+
     fn ensure_no_conn_error(&self) -> Result<(), proto::Error> {
         if let Some(ref err) = self.conn_error {
             Err(err.clone())
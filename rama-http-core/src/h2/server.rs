@@ -154,6 +154,9 @@ pub struct Handshake<T, B: Buf = Bytes> {
     state: Handshaking<T, B>,
     /// Span tracking the handshake
     span: tracing::Span,
+    /// Settings and request of a connection upgraded from http/1.1 (h2c),
+    /// applied once the handshake completed.
+    h2c_upgrade: Option<(Settings, Request<()>)>,
 }
 
 /// Accepts inbound HTTP/2 streams on a connection.
@@ -408,6 +411,7 @@ where
             builder,
             state,
             span,
+            h2c_upgrade: None,
         }
     }
 
@@ -1087,6 +1091,39 @@ impl Builder {
     {
         Connection::handshake2(io, self.clone())
     }
+
+    /// Creates a new configured HTTP/2 server backed by `io`,
+    /// for a connection that was upgraded from http/1.1 to cleartext h2 (`h2c`),
+    /// as described in [RFC 7540 §3.2].
+    ///
+    /// `settings` is the (base64url decoded) payload of the `HTTP2-Settings` header
+    /// of the upgrade request, which is applied as the initial settings of the client.
+    /// The upgrade `request` itself is served on stream 1, half-closed (remote),
+    /// and is the first stream returned by [`Connection::accept`]. Its response
+    /// is sent as usual using the accompanying [`SendResponse`].
+    ///
+    /// Returns an error in case `settings` is not a valid SETTINGS frame payload.
+    ///
+    /// [RFC 7540 §3.2]: https://datatracker.ietf.org/doc/html/rfc7540#section-3.2
+    pub fn handshake_h2c_upgraded<T, B>(
+        &self,
+        io: T,
+        settings: &[u8],
+        request: Request<()>,
+    ) -> Result<Handshake<T, B>, crate::h2::Error>
+    where
+        T: AsyncRead + AsyncWrite + Unpin,
+        B: Buf,
+    {
+        let head = frame::Head::new(frame::Kind::Settings, 0, StreamId::zero());
+        let settings = Settings::load(head, settings).map_err(|err| {
+            tracing::debug!("invalid h2c upgrade settings: {:?}", err);
+            crate::h2::Error::from(Reason::PROTOCOL_ERROR)
+        })?;
+        let mut handshake = Connection::handshake2(io, self.clone());
+        handshake.h2c_upgrade = Some((settings, request));
+        Ok(handshake)
+    }
 }
 
 impl Default for Builder {
@@ -1125,6 +1162,18 @@ impl<B: Buf> SendResponse<B> {
             .map_err(Into::into)
     }
 
+    /// Send an informational (1xx) response to a client request,
+    /// such as `103 Early Hints`.
+    ///
+    /// Any number of informational responses can be sent,
+    /// as long as [`send_response`] has not been called yet.
+    /// `101 Switching Protocols` is not allowed in h2.
+    ///
+    /// [`send_response`]: #method.send_response
+    pub fn send_informational(&mut self, response: Response<()>) -> Result<(), crate::h2::Error> {
+        self.inner.send_informational(response).map_err(Into::into)
+    }
+
     /// Push a request and response to the client
     ///
     /// On success, a [`SendResponse`] instance is returned.
@@ -1394,6 +1443,9 @@ where
                     if let Some(sz) = self.builder.initial_target_connection_window_size {
                         c.set_target_window_size(sz);
                     }
+                    if let Some((settings, request)) = self.h2c_upgrade.take() {
+                        c.connection.recv_h2c_upgrade(&settings, request)?;
+                    }
 
                     return Poll::Ready(Ok(c));
                 }
@@ -12,6 +12,20 @@ pub(super) fn connection_close(value: &HeaderValue) -> bool {
     connection_has(value, "close")
 }
 
+/// Returns true if the `TE` header value contains the `trailers` token,
+/// ignoring any (invalid) parameters attached to it.
+pub(super) fn te_has_trailers(value: &HeaderValue) -> bool {
+    if let Ok(s) = value.to_str() {
+        for val in s.split(',') {
+            let token = val.split(';').next().unwrap_or_default();
+            if token.trim().eq_ignore_ascii_case("trailers") {
+                return true;
+            }
+        }
+    }
+    false
+}
+
 fn connection_has(value: &HeaderValue, needle: &str) -> bool {
     if let Ok(s) = value.to_str() {
         for val in s.split(',') {
@@ -9,8 +9,8 @@ use std::time::Duration;
 use bytes::{Buf, Bytes};
 use httparse::ParserConfig;
 use rama_http_types::dep::http_body::Frame;
-use rama_http_types::header::{CONNECTION, TE};
-use rama_http_types::{HeaderMap, HeaderValue, Method, Version};
+use rama_http_types::header::{CONNECTION, EXPECT, TE};
+use rama_http_types::{HeaderMap, HeaderValue, Method, Response, StatusCode, Version};
 use tokio::io::{AsyncRead, AsyncWrite};
 use tokio::time::{Instant, Sleep};
 use tracing::{debug, error, trace, warn};
@@ -67,6 +67,10 @@ where
                 // If they tell us otherwise, we'll downgrade in `read_head`.
                 version: Version::HTTP_11,
                 allow_trailer_fields: false,
+                on_informational: None,
+                awaiting_continue: false,
+                expect_continue_timeout: None,
+                expect_continue_fut: None,
             },
             _marker: PhantomData,
         }
@@ -112,6 +116,10 @@ where
         self.state.h1_header_read_timeout = Some(val);
     }
 
+    pub(crate) fn set_expect_continue_timeout(&mut self, val: Duration) {
+        self.state.expect_continue_timeout = Some(val);
+    }
+
     pub(crate) fn set_allow_half_close(&mut self) {
         self.state.allow_half_close = true;
     }
@@ -203,6 +211,8 @@ where
                 h1_parser_config: self.state.h1_parser_config.clone(),
                 h1_max_headers: self.state.h1_max_headers,
                 h09_responses: self.state.h09_responses,
+                on_informational: &mut self.state.on_informational,
+                awaiting_continue: &mut self.state.awaiting_continue,
             },
         ) {
             Poll::Ready(Ok(msg)) => msg,
@@ -228,6 +238,17 @@ where
         self.state.h1_header_read_timeout_running = false;
         self.state.h1_header_read_timeout_fut = None;
 
+        // A final response (for a client) ends the informational responses,
+        // and if still awaiting a 100 Continue, the request body is no longer sent.
+        self.state.on_informational = None;
+        if self.state.awaiting_continue {
+            debug!("final response received while awaiting 100 Continue, request body not sent");
+            self.state.awaiting_continue = false;
+            self.state.expect_continue_fut = None;
+            self.state.writing = Writing::Closed;
+            self.state.disable_keep_alive();
+        }
+
         // Note: don't deconstruct `msg` into local variables, it appears
         // the optimizer doesn't remove the extra copies.
 
@@ -274,8 +295,9 @@ where
         self.state.allow_trailer_fields = msg
             .head
             .headers
-            .get(TE)
-            .is_some_and(|te_header| te_header == "trailers");
+            .get_all(TE)
+            .iter()
+            .any(headers::te_has_trailers);
 
         Poll::Ready(Some(Ok((msg.head, msg.decode, wants))))
     }
@@ -537,6 +559,51 @@ where
         self.io.can_buffer()
     }
 
+    /// Returns `Pending` while a client holds back the request body,
+    /// awaiting a `100 Continue` from the server or the expect-continue timeout.
+    pub(crate) fn poll_expect_continue(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+        if self.state.awaiting_continue {
+            let Some(fut) = self.state.expect_continue_fut.as_mut() else {
+                return Poll::Pending;
+            };
+            if fut.as_mut().poll(cx).is_pending() {
+                return Poll::Pending;
+            }
+            debug!("expect-continue timeout elapsed, sending request body");
+            self.state.awaiting_continue = false;
+        }
+        self.state.expect_continue_fut = None;
+        Poll::Ready(())
+    }
+
+    /// Write the head of an informational (1xx) response,
+    /// which can only be done by a server prior to writing its final response head.
+    pub(crate) fn write_informational(&mut self, response: Response<()>) {
+        debug_assert!(T::is_server() && self.can_write_head());
+
+        if self.state.version != Version::HTTP_11 {
+            debug!(
+                "informational response ({}) dropped for {:?} peer",
+                response.status(),
+                self.state.version
+            );
+            return;
+        }
+
+        if response.status() == StatusCode::CONTINUE {
+            if let Reading::Continue(ref decoder) = self.state.reading {
+                // prevent the 100 Continue from being sent a second time
+                self.state.reading = Reading::Body(decoder.clone());
+            }
+        }
+
+        super::role::encode_informational(
+            response,
+            self.state.title_case_headers,
+            self.io.headers_buf(),
+        );
+    }
+
     pub(crate) fn write_head(&mut self, head: MessageHead<T::Outgoing>, body: Option<BodyLength>) {
         if let Some(encoder) = self.encode_head(head, body) {
             self.state.writing = if !encoder.is_eof() {
@@ -562,6 +629,17 @@ where
 
         self.enforce_version(&mut head);
 
+        let mut expect_continue = false;
+        if T::is_client() {
+            self.state.on_informational = head.extensions.remove::<crate::ext::OnInformational>();
+            expect_continue = self.state.expect_continue_timeout.is_some()
+                && head.version == Version::HTTP_11
+                && head
+                    .headers
+                    .get(EXPECT)
+                    .is_some_and(|value| value.as_bytes().eq_ignore_ascii_case(b"100-continue"));
+        }
+
         let buf = self.io.headers_buf();
         match super::role::encode_headers::<T>(
             Encode {
@@ -579,7 +657,17 @@ where
             },
             buf,
         ) {
-            Ok(encoder) => Some(encoder),
+            Ok(encoder) => {
+                if expect_continue && !encoder.is_eof() {
+                    if let Some(timeout) = self.state.expect_continue_timeout {
+                        trace!("request body held back until 100 Continue");
+                        self.state.awaiting_continue = true;
+                        self.state.expect_continue_fut =
+                            Some(Box::pin(tokio::time::sleep(timeout)));
+                    }
+                }
+                Some(encoder)
+            }
             Err(err) => {
                 self.state.error = Some(err);
                 self.state.writing = Writing::Closed;
@@ -874,6 +962,13 @@ struct State {
     version: Version,
     /// Flag to track if trailer fields are allowed to be sent
     allow_trailer_fields: bool,
+    /// Callback for informational responses received by a client.
+    on_informational: Option<crate::ext::OnInformational>,
+    /// Set to true when a client holds back the request body
+    /// until a `100 Continue` is received.
+    awaiting_continue: bool,
+    expect_continue_timeout: Option<Duration>,
+    expect_continue_fut: Option<Pin<Box<Sleep>>>,
 }
 
 #[derive(Debug)]
@@ -6,6 +6,7 @@ use std::{
 };
 
 use bytes::{Buf, Bytes};
+use futures_util::StreamExt as _;
 use rama_core::error::BoxError;
 use rama_http_types::{Request, Response, StatusCode};
 use std::task::ready;
@@ -16,6 +17,7 @@ use super::{Http1Transaction, Wants};
 use crate::body::{Body, DecodedLength, Incoming as IncomingBody};
 use crate::client::dispatch::TrySendError;
 use crate::common::task;
+use crate::ext::{InformationalReceiver, InformationalSender};
 use crate::proto::{BodyLength, Conn, Dispatched, MessageHead, RequestHead};
 use crate::upgrade::OnUpgrade;
 
@@ -40,6 +42,11 @@ pub(crate) trait Dispatch {
         -> crate::Result<()>;
     fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), ()>>;
     fn should_poll(&self) -> bool;
+    /// Poll for an informational (1xx) response to be written
+    /// prior to the message returned by `poll_msg`.
+    fn poll_informational(&mut self, _cx: &mut Context<'_>) -> Poll<Option<Response<()>>> {
+        Poll::Ready(None)
+    }
 }
 
 use crate::service::HttpService;
@@ -49,6 +56,7 @@ where
     S: HttpService<B>,
 {
     in_flight: Option<Pin<Box<dyn Future<Output = Result<Response, Infallible>> + Send + 'static>>>,
+    informational: Option<InformationalReceiver>,
     pub(crate) service: S,
     _phantom: PhantomData<fn() -> B>,
 }
@@ -331,6 +339,10 @@ where
                 && self.conn.can_write_head()
                 && self.dispatch.should_poll()
             {
+                if let Poll::Ready(Some(informational)) = self.dispatch.poll_informational(cx) {
+                    self.conn.write_informational(informational);
+                    continue;
+                }
                 if let Some(msg) = ready!(Pin::new(&mut self.dispatch).poll_msg(cx)) {
                     let (head, body) = msg.map_err(crate::Error::new_user_service)?;
 
@@ -368,6 +380,8 @@ where
                         continue;
                     }
 
+                    ready!(self.conn.poll_expect_continue(cx));
+
                     let item = ready!(body.as_mut().poll_frame(cx));
                     if let Some(item) = item {
                         let frame = item.map_err(|e| {
@@ -503,6 +517,7 @@ impl<
     pub(crate) fn new(service: S) -> Server<S, B> {
         Server {
             in_flight: None,
+            informational: None,
             service,
             _phantom: PhantomData,
         }
@@ -549,8 +564,10 @@ where
             unreachable!("poll_msg shouldn't be called if no inflight");
         };
 
-        // Since in_flight finished, remove it
+        // Since in_flight finished, remove it,
+        // no informational responses can be sent after the final one
         this.in_flight = None;
+        this.informational = None;
         ret
     }
 
@@ -565,6 +582,9 @@ where
         *req.headers_mut() = msg.headers;
         *req.version_mut() = msg.version;
         *req.extensions_mut() = msg.extensions;
+        let (informational_tx, informational_rx) = InformationalSender::channel();
+        req.extensions_mut().insert(informational_tx);
+        self.informational = Some(informational_rx);
         let fut = self.service.serve_http(req);
         self.in_flight = Some(Box::pin(fut));
         Ok(())
@@ -581,6 +601,19 @@ where
     fn should_poll(&self) -> bool {
         self.in_flight.is_some()
     }
+
+    fn poll_informational(&mut self, cx: &mut Context<'_>) -> Poll<Option<Response<()>>> {
+        let Some(rx) = self.informational.as_mut() else {
+            return Poll::Ready(None);
+        };
+        match ready!(rx.poll_next_unpin(cx)) {
+            Some(response) => Poll::Ready(Some(response)),
+            None => {
+                self.informational = None;
+                Poll::Ready(None)
+            }
+        }
+    }
 }
 
 // ===== impl Client =====
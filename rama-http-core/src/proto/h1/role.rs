@@ -8,7 +8,7 @@ use rama_http_types::dep::http;
 use rama_http_types::header::Entry;
 use rama_http_types::header::{self, HeaderMap, HeaderValue};
 use rama_http_types::proto::h1::{Http1HeaderMap, Http1HeaderName};
-use rama_http_types::{Method, StatusCode, Version};
+use rama_http_types::{Method, Response, StatusCode, Version};
 use smallvec::{smallvec, smallvec_inline, SmallVec};
 use tracing::{debug, error, trace, trace_span, warn};
 
@@ -308,10 +308,10 @@ impl Http1Transaction for Server {
 
         let mut wrote_len = false;
 
-        // hyper currently doesn't support returning 1xx status codes as a Response
-        // This is because Service only allows returning a single Response, and
-        // so if you try to reply with a e.g. 100 Continue, you have no way of
-        // replying with the latter status code response.
+        // 1xx status codes (other than 101) can't be returned as the final Response,
+        // as a Service only returns a single Response, so with e.g. a 100 Continue
+        // there would be no way of replying with the latter status code response.
+        // Informational responses are sent using the `InformationalSender` instead.
         let (ret, is_last) = if msg.head.subject == StatusCode::SWITCHING_PROTOCOLS {
             (Ok(()), true)
         } else if msg.req_method == &Some(Method::CONNECT) && msg.head.subject.is_success() {
@@ -956,6 +956,13 @@ impl Http1Transaction for Client {
                 }));
             }
 
+            if head.subject == StatusCode::CONTINUE {
+                *ctx.awaiting_continue = false;
+            }
+            if let Some(on_informational) = ctx.on_informational.as_ref() {
+                on_informational.call(&head.into_response(()));
+            }
+
             // Parsing a 1xx response could have consumed the buffer, check if
             // it is empty now...
             if buf.is_empty() {
@@ -1392,6 +1399,43 @@ fn write_h1_headers(
     }
 }
 
+/// Encode the head of an informational (1xx) response,
+/// written by a server prior to its final response.
+pub(super) fn encode_informational(
+    response: Response<()>,
+    title_case_headers: bool,
+    dst: &mut Vec<u8>,
+) {
+    let (mut parts, ()) = response.into_parts();
+    trace!("encode informational response: {}", parts.status);
+
+    extend(dst, b"HTTP/1.1 ");
+    extend(dst, parts.status.as_str().as_bytes());
+    extend(dst, b" ");
+    match parts.extensions.get::<crate::ext::ReasonPhrase>() {
+        Some(reason) => extend(dst, reason.as_bytes()),
+        None => extend(
+            dst,
+            parts
+                .status
+                .canonical_reason()
+                // not (yet) known to all versions of the http crate
+                .or_else(|| (parts.status.as_u16() == 103).then_some("Early Hints"))
+                .unwrap_or("<none>")
+                .as_bytes(),
+        ),
+    }
+    extend(dst, b"\r\n");
+
+    write_h1_headers(
+        parts.headers,
+        title_case_headers,
+        &mut parts.extensions,
+        dst,
+    );
+    extend(dst, b"\r\n");
+}
+
 struct FastWrite<'a>(&'a mut Vec<u8>);
 
 impl fmt::Write for FastWrite<'_> {
@@ -1430,6 +1474,8 @@ mod tests {
                 h1_parser_config: Default::default(),
                 h1_max_headers: None,
                 h09_responses: false,
+                on_informational: &mut None,
+                awaiting_continue: &mut false,
             },
         )
         .unwrap()
@@ -1451,6 +1497,8 @@ mod tests {
             h1_parser_config: Default::default(),
             h1_max_headers: None,
             h09_responses: false,
+            on_informational: &mut None,
+            awaiting_continue: &mut false,
         };
         let msg = Client::parse(&mut raw, ctx).unwrap().unwrap();
         assert_eq!(raw.len(), 0);
@@ -1460,6 +1508,59 @@ mod tests {
         assert_eq!(msg.head.headers["Content-Length"], "0");
     }
 
+    #[test]
+    fn test_parse_response_with_informational() {
+        let mut raw = BytesMut::from(
+            "HTTP/1.1 100 Continue\r\n\r\n\
+             HTTP/1.1 103 Early Hints\r\nLink: </style.css>; rel=preload\r\n\r\n\
+             HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n",
+        );
+        let received = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let on_informational = crate::ext::OnInformational::new({
+            let received = received.clone();
+            move |res: &Response<()>| {
+                received
+                    .lock()
+                    .unwrap()
+                    .push((res.status(), res.headers().clone()))
+            }
+        });
+        let mut awaiting_continue = true;
+        let ctx = ParseContext {
+            req_method: &mut Some(Method::POST),
+            h1_parser_config: Default::default(),
+            h1_max_headers: None,
+            h09_responses: false,
+            on_informational: &mut Some(on_informational),
+            awaiting_continue: &mut awaiting_continue,
+        };
+        let msg = Client::parse(&mut raw, ctx).unwrap().unwrap();
+        assert_eq!(raw.len(), 0);
+        assert_eq!(msg.head.subject, StatusCode::OK);
+        assert!(!awaiting_continue);
+
+        let received = received.lock().unwrap();
+        assert_eq!(received.len(), 2);
+        assert_eq!(received[0].0, StatusCode::CONTINUE);
+        assert_eq!(received[1].0, StatusCode::from_u16(103).unwrap());
+        assert_eq!(received[1].1["link"], "</style.css>; rel=preload");
+    }
+
+    #[test]
+    fn test_encode_informational() {
+        let res = Response::builder()
+            .status(StatusCode::from_u16(103).unwrap())
+            .header("link", "</style.css>; rel=preload")
+            .body(())
+            .unwrap();
+        let mut vec = Vec::new();
+        encode_informational(res, true, &mut vec);
+        assert_eq!(
+            &vec,
+            b"HTTP/1.1 103 Early Hints\r\nLink: </style.css>; rel=preload\r\n\r\n"
+        );
+    }
+
     #[test]
     fn test_parse_request_errors() {
         let mut raw = BytesMut::from("GET htt:p// HTTP/1.1\r\nHost: ramaproxy.org\r\n\r\n");
@@ -1468,6 +1569,8 @@ mod tests {
             h1_parser_config: Default::default(),
             h1_max_headers: None,
             h09_responses: false,
+            on_informational: &mut None,
+            awaiting_continue: &mut false,
         };
         Server::parse(&mut raw, ctx).unwrap_err();
     }
@@ -1482,6 +1585,8 @@ mod tests {
             h1_parser_config: Default::default(),
             h1_max_headers: None,
             h09_responses: true,
+            on_informational: &mut None,
+            awaiting_continue: &mut false,
         };
         let msg = Client::parse(&mut raw, ctx).unwrap().unwrap();
         assert_eq!(raw, H09_RESPONSE);
@@ -1498,6 +1603,8 @@ mod tests {
             h1_parser_config: Default::default(),
             h1_max_headers: None,
             h09_responses: false,
+            on_informational: &mut None,
+            awaiting_continue: &mut false,
         };
         Client::parse(&mut raw, ctx).unwrap_err();
         assert_eq!(raw, H09_RESPONSE);
@@ -1518,6 +1625,8 @@ mod tests {
             h1_parser_config,
             h1_max_headers: None,
             h09_responses: false,
+            on_informational: &mut None,
+            awaiting_continue: &mut false,
         };
         let msg = Client::parse(&mut raw, ctx).unwrap().unwrap();
         assert_eq!(raw.len(), 0);
@@ -1535,6 +1644,8 @@ mod tests {
             h1_parser_config: Default::default(),
             h1_max_headers: None,
             h09_responses: false,
+            on_informational: &mut None,
+            awaiting_continue: &mut false,
         };
         Client::parse(&mut raw, ctx).unwrap_err();
     }
@@ -1548,6 +1659,8 @@ mod tests {
             h1_parser_config: Default::default(),
             h1_max_headers: None,
             h09_responses: false,
+            on_informational: &mut None,
+            awaiting_continue: &mut false,
         };
         let parsed_message = Server::parse(&mut raw, ctx).unwrap().unwrap();
         let mut orig_headers = parsed_message
@@ -1572,6 +1685,8 @@ mod tests {
                     h1_parser_config: Default::default(),
                     h1_max_headers: None,
                     h09_responses: false,
+                    on_informational: &mut None,
+                    awaiting_continue: &mut false,
                 },
             )
             .expect("parse ok")
@@ -1587,6 +1702,8 @@ mod tests {
                     h1_parser_config: Default::default(),
                     h1_max_headers: None,
                     h09_responses: false,
+                    on_informational: &mut None,
+                    awaiting_continue: &mut false,
                 },
             )
             .expect_err(comment)
@@ -1811,6 +1928,8 @@ mod tests {
                     h1_parser_config: Default::default(),
                     h1_max_headers: None,
                     h09_responses: false,
+                    on_informational: &mut None,
+                    awaiting_continue: &mut false,
                 }
             )
             .expect("parse ok")
@@ -1826,6 +1945,8 @@ mod tests {
                     h1_parser_config: Default::default(),
                     h1_max_headers: None,
                     h09_responses: false,
+                    on_informational: &mut None,
+                    awaiting_continue: &mut false,
                 },
             )
             .expect("parse ok")
@@ -1841,6 +1962,8 @@ mod tests {
                     h1_parser_config: Default::default(),
                     h1_max_headers: None,
                     h09_responses: false,
+                    on_informational: &mut None,
+                    awaiting_continue: &mut false,
                 },
             )
             .expect_err("parse should err")
@@ -2433,6 +2556,8 @@ mod tests {
                 h1_parser_config: Default::default(),
                 h1_max_headers: None,
                 h09_responses: false,
+                on_informational: &mut None,
+                awaiting_continue: &mut false,
             },
         )
         .expect("parse ok")
@@ -2470,6 +2595,8 @@ mod tests {
                         h1_parser_config: Default::default(),
                         h1_max_headers: max_headers,
                         h09_responses: false,
+                        on_informational: &mut None,
+                        awaiting_continue: &mut false,
                     },
                 );
                 if should_success {
@@ -2488,6 +2615,8 @@ mod tests {
                         h1_parser_config: Default::default(),
                         h1_max_headers: max_headers,
                         h09_responses: false,
+                        on_informational: &mut None,
+                        awaiting_continue: &mut false,
                     },
                 );
                 if should_success {
@@ -140,17 +140,22 @@ fn new_ping_config(config: &Config) -> ping::Config {
     }
 }
 
+/// Response of the request registered as h2c upgrade request (stream 1).
+pub(crate) type H2cUpgradeResponse =
+    Pin<Box<dyn Future<Output = crate::Result<Response<IncomingBody>>> + Send + 'static>>;
+
 pub(crate) async fn handshake<T, B>(
     io: T,
     req_rx: ClientRx<B>,
     config: &Config,
     exec: Executor,
-) -> crate::Result<ClientTask<B, T>>
+    h2c_upgrade_request: Option<Request<()>>,
+) -> crate::Result<(ClientTask<B, T>, Option<H2cUpgradeResponse>)>
 where
     T: AsyncRead + AsyncWrite + Send + Unpin + 'static,
     B: Body<Data: Send + 'static, Error: Into<BoxError>> + Send + 'static + Unpin,
 {
-    let (h2_tx, mut conn) = new_builder(config)
+    let (mut h2_tx, mut conn) = new_builder(config)
         .handshake::<_, SendBuf<B::Data>>(io)
         .await
         .map_err(crate::Error::new_h2)?;
@@ -180,20 +185,45 @@ where
         is_terminated: false,
     };
 
+    // the upgrade request has to be registered prior to any other request,
+    // such that it is assigned stream 1
+    let h2c_upgrade_response = match h2c_upgrade_request {
+        Some(req) => {
+            let fut = h2_tx
+                .send_h2c_upgrade_request(req)
+                .map_err(crate::Error::new_h2)?;
+            let ping = ping.clone();
+            let response: H2cUpgradeResponse = Box::pin(async move {
+                let res = fut.await.map_err(crate::Error::new_h2)?;
+                let content_length = headers::content_length_parse_all(res.headers());
+                Ok(res.map(|stream| {
+                    let ping = ping.for_stream(&stream);
+                    IncomingBody::h2(stream, content_length.into(), ping)
+                }))
+            });
+            Some(response)
+        }
+        None => None,
+    };
+
     exec.spawn_task(H2ClientFuture::Task {
         task: ConnTask::new(conn, conn_drop_rx, cancel_tx),
     });
 
-    Ok(ClientTask {
-        ping,
-        conn_drop_ref,
-        conn_eof,
-        executor: exec,
-        h2_tx,
-        req_rx,
-        fut_ctx: None,
-        marker: PhantomData,
-    })
+    Ok((
+        ClientTask {
+            ping,
+            conn_drop_ref,
+            conn_eof,
+            executor: exec,
+            h2_tx,
+            req_rx,
+            fut_ctx: None,
+            extended_connect_ctx: None,
+            marker: PhantomData,
+        },
+        h2c_upgrade_response,
+    ))
 }
 
 pin_project! {
@@ -442,6 +472,22 @@ impl<B: Body<Data: Send + 'static, Error: Into<BoxError>> + Send + 'static + Unp
 {
 }
 
+/// Extended CONNECT request (RFC 8441) waiting on the initial
+/// `SETTINGS` frame of the server, prior to being sent.
+struct ExtendedConnectCtx<B>
+where
+    B: Body<Data: Send + 'static, Error: Into<BoxError>> + Send + 'static + Unpin,
+{
+    req: Request<()>,
+    body: B,
+    cb: Callback<Request<B>, Response<IncomingBody>>,
+}
+
+impl<B: Body<Data: Send + 'static, Error: Into<BoxError>> + Send + 'static + Unpin> Unpin
+    for ExtendedConnectCtx<B>
+{
+}
+
 pub(crate) struct ClientTask<B, T>
 where
     B: Body<Data: Send + 'static, Error: Into<BoxError>> + Send + 'static + Unpin,
@@ -454,6 +500,7 @@ where
     h2_tx: SendRequest<SendBuf<B::Data>>,
     req_rx: ClientRx<B>,
     fut_ctx: Option<FutCtx<B>>,
+    extended_connect_ctx: Option<ExtendedConnectCtx<B>>,
     marker: PhantomData<T>,
 }
 
@@ -557,6 +604,76 @@ where
         };
         self.executor.spawn_task(fut);
     }
+
+    /// Send the request, returning `Pending` in case the new stream is pending open,
+    /// in which case the task has to wait for the open to complete before accepting new requests.
+    fn send_request(
+        &mut self,
+        mut req: Request<()>,
+        body: B,
+        cb: Callback<Request<B>, Response<IncomingBody>>,
+        cx: &mut Context<'_>,
+    ) -> Poll<()> {
+        let is_connect = req.method() == Method::CONNECT;
+        let eos = body.is_end_stream();
+
+        if is_connect
+            && headers::content_length_parse_all(req.headers()).is_some_and(|len| len != 0)
+        {
+            warn!("h2 connect request with non-zero body not supported");
+            cb.send(Err(TrySendError {
+                error: crate::Error::new_h2(crate::h2::Reason::INTERNAL_ERROR.into()),
+                message: None,
+            }));
+            return Poll::Ready(());
+        }
+
+        if let Some(protocol) = req.extensions_mut().remove::<Protocol>() {
+            req.extensions_mut().insert(protocol.into_inner());
+        }
+
+        let (fut, body_tx) = match self.h2_tx.send_request(req, !is_connect && eos) {
+            Ok(ok) => ok,
+            Err(err) => {
+                debug!("client send request error: {}", err);
+                cb.send(Err(TrySendError {
+                    error: crate::Error::new_h2(err),
+                    message: None,
+                }));
+                return Poll::Ready(());
+            }
+        };
+
+        let f = FutCtx {
+            is_connect,
+            eos,
+            fut,
+            body_tx,
+            body,
+            cb,
+        };
+
+        // Check poll_ready() again.
+        // If the call to send_request() resulted in the new stream being pending open
+        // we have to wait for the open to complete before accepting new requests.
+        match self.h2_tx.poll_ready(cx) {
+            Poll::Pending => {
+                // Save Context
+                self.fut_ctx = Some(f);
+                return Poll::Pending;
+            }
+            Poll::Ready(Ok(())) => (),
+            Poll::Ready(Err(err)) => {
+                f.cb.send(Err(TrySendError {
+                    error: crate::Error::new_h2(err),
+                    message: None,
+                }));
+                return Poll::Ready(());
+            }
+        }
+        self.poll_pipe(f, cx);
+        Poll::Ready(())
+    }
 }
 
 pin_project! {
@@ -672,6 +789,30 @@ where
                 continue;
             }
 
+            // If we were waiting on the initial SETTINGS of the server
+            // prior to sending an extended CONNECT request,
+            // continue where we left off.
+            if let Some(f) = self.extended_connect_ctx.take() {
+                match self.h2_tx.poll_remote_initial_settings(cx) {
+                    Poll::Pending => {
+                        self.extended_connect_ctx = Some(f);
+                        return Poll::Pending;
+                    }
+                    Poll::Ready(Err(err)) => {
+                        f.cb.send(Err(TrySendError {
+                            error: crate::Error::new_h2(err),
+                            message: None,
+                        }));
+                    }
+                    Poll::Ready(Ok(())) => {
+                        if self.send_request(f.req, f.body, f.cb, cx).is_pending() {
+                            return Poll::Pending;
+                        }
+                    }
+                }
+                continue;
+            }
+
             match self.req_rx.poll_recv(cx) {
                 Poll::Ready(Some((req, cb))) => {
                     // check that future hasn't been canceled already
@@ -688,65 +829,16 @@ where
                         }
                     }
 
-                    let is_connect = req.method() == Method::CONNECT;
-                    let eos = body.is_end_stream();
-
-                    if is_connect
-                        && headers::content_length_parse_all(req.headers())
-                            .is_some_and(|len| len != 0)
-                    {
-                        warn!("h2 connect request with non-zero body not supported");
-                        cb.send(Err(TrySendError {
-                            error: crate::Error::new_h2(crate::h2::Reason::INTERNAL_ERROR.into()),
-                            message: None,
-                        }));
+                    // the server has to advertise support for the extended CONNECT protocol,
+                    // which is only known once its initial SETTINGS frame has been received
+                    if req.extensions().get::<Protocol>().is_some() {
+                        self.extended_connect_ctx = Some(ExtendedConnectCtx { req, body, cb });
                         continue;
                     }
 
-                    if let Some(protocol) = req.extensions_mut().remove::<Protocol>() {
-                        req.extensions_mut().insert(protocol.into_inner());
-                    }
-
-                    let (fut, body_tx) = match self.h2_tx.send_request(req, !is_connect && eos) {
-                        Ok(ok) => ok,
-                        Err(err) => {
-                            debug!("client send request error: {}", err);
-                            cb.send(Err(TrySendError {
-                                error: crate::Error::new_h2(err),
-                                message: None,
-                            }));
-                            continue;
-                        }
-                    };
-
-                    let f = FutCtx {
-                        is_connect,
-                        eos,
-                        fut,
-                        body_tx,
-                        body,
-                        cb,
-                    };
-
-                    // Check poll_ready() again.
-                    // If the call to send_request() resulted in the new stream being pending open
-                    // we have to wait for the open to complete before accepting new requests.
-                    match self.h2_tx.poll_ready(cx) {
-                        Poll::Pending => {
-                            // Save Context
-                            self.fut_ctx = Some(f);
-                            return Poll::Pending;
-                        }
-                        Poll::Ready(Ok(())) => (),
-                        Poll::Ready(Err(err)) => {
-                            f.cb.send(Err(TrySendError {
-                                error: crate::Error::new_h2(err),
-                                message: None,
-                            }));
-                            continue;
-                        }
+                    if self.send_request(req, body, cb, cx).is_pending() {
+                        return Poll::Pending;
                     }
-                    self.poll_pipe(f, cx);
                     continue;
                 }
 
@@ -13,11 +13,12 @@ use rama_http_types::header::{
     CONNECTION, KEEP_ALIVE, PROXY_CONNECTION, TE, TRANSFER_ENCODING, UPGRADE,
 };
 use rama_http_types::proto::h1::headers::original::OriginalHttp1Headers;
-use rama_http_types::{HeaderMap, HeaderName};
+use rama_http_types::{HeaderMap, HeaderName, HeaderValue};
 use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
 use tracing::{debug, trace, warn};
 
 use crate::body::Body;
+use crate::headers::te_has_trailers;
 use crate::proto::h2::ping::Recorder;
 
 pub(crate) mod ping;
@@ -50,8 +51,16 @@ fn strip_connection_headers(headers: &mut HeaderMap, is_request: bool) {
             .get(TE)
             .is_some_and(|te_header| te_header != "trailers")
         {
-            warn!("TE headers not set to \"trailers\" are illegal in HTTP/2 requests");
+            // keep accepting trailers when (proxied) http/1.1 requests
+            // combine it with other transfer codings
+            let has_trailers = headers.get_all(TE).iter().any(te_has_trailers);
             headers.remove(TE);
+            if has_trailers {
+                debug!("TE header normalized to \"trailers\" for HTTP/2 request");
+                headers.insert(TE, HeaderValue::from_static("trailers"));
+            } else {
+                warn!("TE headers not set to \"trailers\" are illegal in HTTP/2 requests");
+            }
         }
     } else if headers.remove(TE).is_some() {
         warn!("TE headers illegal in HTTP/2 responses");
@@ -6,6 +6,7 @@ use std::time::Duration;
 use crate::h2::server::{Connection, Handshake, SendResponse};
 use crate::h2::{Reason, RecvStream};
 use bytes::Bytes;
+use futures_util::StreamExt as _;
 use pin_project_lite::pin_project;
 use rama_core::error::BoxError;
 use rama_core::rt::Executor;
@@ -16,7 +17,7 @@ use tracing::{debug, trace, warn};
 use super::{ping, PipeToSendStream, SendBuf};
 use crate::body::{Body, Incoming as IncomingBody};
 use crate::common::date;
-use crate::ext::Protocol;
+use crate::ext::{InformationalReceiver, InformationalSender, Protocol};
 use crate::headers;
 use crate::proto::h2::ping::Recorder;
 use crate::proto::h2::{H2Upgraded, UpgradedSendStream};
@@ -109,25 +110,32 @@ where
     S: HttpService<IncomingBody>,
 {
     pub(crate) fn new(io: T, service: S, config: &Config, exec: Executor) -> Server<T, S> {
-        let mut builder = crate::h2::server::Builder::default();
-        builder
-            .initial_window_size(config.initial_stream_window_size)
-            .initial_connection_window_size(config.initial_conn_window_size)
-            .max_frame_size(config.max_frame_size)
-            .max_header_list_size(config.max_header_list_size)
-            .max_local_error_reset_streams(config.max_local_error_reset_streams)
-            .max_send_buffer_size(config.max_send_buffer_size);
-        if let Some(max) = config.max_concurrent_streams {
-            builder.max_concurrent_streams(max);
-        }
-        if let Some(max) = config.max_pending_accept_reset_streams {
-            builder.max_pending_accept_reset_streams(max);
-        }
-        if config.enable_connect_protocol {
-            builder.enable_connect_protocol();
-        }
-        let handshake = builder.handshake(io);
+        let handshake = new_builder(config).handshake(io);
+        Self::from_handshake(handshake, service, config, exec)
+    }
 
+    /// Serve a connection upgraded from http/1.1 to h2c, with the upgrade request
+    /// served on stream 1 and the decoded `HTTP2-Settings` applied as client settings.
+    pub(crate) fn new_h2c_upgraded(
+        io: T,
+        settings: &[u8],
+        request: Request<()>,
+        service: S,
+        config: &Config,
+        exec: Executor,
+    ) -> crate::Result<Server<T, S>> {
+        let handshake = new_builder(config)
+            .handshake_h2c_upgraded(io, settings, request)
+            .map_err(crate::Error::new_h2)?;
+        Ok(Self::from_handshake(handshake, service, config, exec))
+    }
+
+    fn from_handshake(
+        handshake: Handshake<T, SendBuf<Bytes>>,
+        service: S,
+        config: &Config,
+        exec: Executor,
+    ) -> Server<T, S> {
         let bdp = if config.adaptive_window {
             Some(config.initial_stream_window_size)
         } else {
@@ -170,6 +178,27 @@ where
     }
 }
 
+fn new_builder(config: &Config) -> crate::h2::server::Builder {
+    let mut builder = crate::h2::server::Builder::default();
+    builder
+        .initial_window_size(config.initial_stream_window_size)
+        .initial_connection_window_size(config.initial_conn_window_size)
+        .max_frame_size(config.max_frame_size)
+        .max_header_list_size(config.max_header_list_size)
+        .max_local_error_reset_streams(config.max_local_error_reset_streams)
+        .max_send_buffer_size(config.max_send_buffer_size);
+    if let Some(max) = config.max_concurrent_streams {
+        builder.max_concurrent_streams(max);
+    }
+    if let Some(max) = config.max_pending_accept_reset_streams {
+        builder.max_pending_accept_reset_streams(max);
+    }
+    if config.enable_connect_protocol {
+        builder.enable_connect_protocol();
+    }
+    builder
+}
+
 impl<T, S> Future for Server<T, S>
 where
     T: AsyncRead + AsyncWrite + Unpin,
@@ -278,10 +307,14 @@ where
                             req.extensions_mut().insert(Protocol::from_inner(protocol));
                         }
 
+                        let (informational_tx, informational_rx) = InformationalSender::channel();
+                        req.extensions_mut().insert(informational_tx);
+
                         let fut = H2Stream::new(
                             service.serve_http(req),
                             connect_parts,
                             respond,
+                            informational_rx,
                             self.date_header,
                         );
 
@@ -345,6 +378,7 @@ pin_project! {
         reply: SendResponse<SendBuf<B::Data>>,
         #[pin]
         state: H2StreamState<F, B>,
+        informational: Option<InformationalReceiver>,
         date_header: bool,
     }
 }
@@ -387,11 +421,13 @@ where
         fut: F,
         connect_parts: Option<ConnectParts>,
         respond: SendResponse<SendBuf<B::Data>>,
+        informational: InformationalReceiver,
         date_header: bool,
     ) -> H2Stream<F, B> {
         H2Stream {
             reply: respond,
             state: H2StreamState::Service { fut, connect_parts },
+            informational: Some(informational),
             date_header,
         }
     }
@@ -424,6 +460,20 @@ where
                     fut: h,
                     connect_parts,
                 } => {
+                    // send the informational responses queued so far,
+                    // prior to polling for the final response
+                    while let Some(rx) = me.informational.as_mut() {
+                        match rx.poll_next_unpin(cx) {
+                            Poll::Ready(Some(informational)) => {
+                                if let Err(e) = me.reply.send_informational(informational) {
+                                    debug!("send informational response error: {}", e);
+                                }
+                            }
+                            Poll::Ready(None) => *me.informational = None,
+                            Poll::Pending => break,
+                        }
+                    }
+
                     let res = match h.poll(cx) {
                         Poll::Ready(Ok(r)) => r,
                         Poll::Pending => {
@@ -445,6 +495,9 @@ where
                         }
                     };
 
+                    // no informational responses can be sent after the final one
+                    *me.informational = None;
+
                     let (head, body) = res.into_parts();
                     let mut res = Response::from_parts(head, ());
                     super::strip_connection_headers(res.headers_mut(), false);
@@ -20,6 +20,7 @@ use crate::service::HttpService;
 use rama_core::error::BoxError;
 use rama_core::rt::Executor;
 
+use super::h2c::H2cUpgradeService;
 use super::{http1, http2};
 
 type Result<T> = std::result::Result<T, BoxError>;
@@ -32,6 +33,7 @@ pub struct Builder {
     http1: http1::Builder,
     http2: http2::Builder,
     version: Option<Version>,
+    h2c_upgrade: bool,
 }
 
 impl Builder {
@@ -41,6 +43,7 @@ impl Builder {
             http1: http1::Builder::new(),
             http2: http2::Builder::new(executor),
             version: None,
+            h2c_upgrade: false,
         }
     }
 
@@ -370,7 +373,7 @@ pin_project! {
         },
         H1 {
             #[pin]
-            conn: Http1UpgradeableConnection<Rewind<I>, S>,
+            conn: Http1UpgradeableConnection<Rewind<I>, H2cUpgradeService<S>>,
         },
         H2 {
             #[pin]
@@ -444,6 +447,10 @@ where
                     let service = service.take().unwrap();
                     match version {
                         Version::H1 => {
+                            let service = H2cUpgradeService::new(
+                                service,
+                                builder.h2c_upgrade.then(|| builder.http2.clone()),
+                            );
                             let conn = builder.http1.serve_connection(io, service).with_upgrades();
                             this.state.set(UpgradeableConnState::H1 { conn });
                         }
@@ -724,6 +731,27 @@ impl Http2Builder<'_> {
         self
     }
 
+    /// Enables upgrading http/1.1 connections to cleartext h2 (`h2c`),
+    /// using the `Upgrade: h2c` mechanism defined in [RFC 7540 §3.2].
+    ///
+    /// Bodiless requests with `Upgrade: h2c` and a valid `HTTP2-Settings` header
+    /// are answered with `101 Switching Protocols`, after which the request
+    /// is served as h2 stream 1 on the upgraded connection, with the decoded
+    /// settings applied as the initial settings of the client. Other requests,
+    /// including those offering other protocols next to h2c,
+    /// are served as regular http/1.1 requests.
+    ///
+    /// Only has an effect for connections served using [`Builder::serve_connection_with_upgrades`].
+    /// Clients with prior knowledge of h2 are always accepted, unless [`Builder::http1_only`] is used.
+    ///
+    /// Default is false.
+    ///
+    /// [RFC 7540 §3.2]: https://datatracker.ietf.org/doc/html/rfc7540#section-3.2
+    pub fn enable_h2c_upgrade(&mut self) -> &mut Self {
+        self.inner.h2c_upgrade = true;
+        self
+    }
+
     /// Sets the max size of received header frames.
     ///
     /// Default is currently ~16MB, but may change.
@@ -0,0 +1,508 @@
+//! Server support for upgrading http/1.1 connections to cleartext h2 (`h2c`),
+//! as specified in [RFC 7540 §3.2].
+//!
+//! The upgrade request is answered with `101 Switching Protocols`, after which
+//! the connection is served as h2. As required by the RFC, the settings decoded
+//! from the `HTTP2-Settings` header are applied as the initial settings of the client,
+//! and the upgrade request itself is served on h2 stream 1.
+//!
+//! [RFC 7540 §3.2]: https://datatracker.ietf.org/doc/html/rfc7540#section-3.2
+
+use std::convert::Infallible;
+use std::future::Future;
+use std::sync::Arc;
+
+use base64::Engine as _;
+use bytes::Bytes;
+use futures_util::future::Either;
+use rama_http_types::dep::http::uri::{Authority, Scheme};
+use rama_http_types::dep::http_body::Body as _;
+use rama_http_types::header::{CONNECTION, HOST, UPGRADE};
+use rama_http_types::{
+    HeaderMap, HeaderName, HeaderValue, Request, Response, StatusCode, Uri, Version,
+};
+
+use super::http2;
+use crate::body::Incoming;
+use crate::service::HttpService;
+
+/// Name of the header containing the (base64url encoded) SETTINGS payload of the client.
+const HTTP2_SETTINGS: HeaderName = HeaderName::from_static("http2-settings");
+
+/// Token used in the `Upgrade` header for cleartext h2.
+const H2C: &str = "h2c";
+
+/// Default (and minimum) `SETTINGS_MAX_FRAME_SIZE`.
+const DEFAULT_MAX_FRAME_SIZE: u32 = 16_384;
+
+const SETTINGS_ENABLE_PUSH: u16 = 0x2;
+const SETTINGS_INITIAL_WINDOW_SIZE: u16 = 0x4;
+const SETTINGS_MAX_FRAME_SIZE: u16 = 0x5;
+const SETTINGS_ENABLE_CONNECT_PROTOCOL: u16 = 0x8;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+/// The h2 settings of the client, decoded from the `HTTP2-Settings` header
+/// of an h2c upgrade request.
+///
+/// These settings are applied as the initial settings of the client
+/// on the upgraded connection. It is also inserted as an extension
+/// in the (upgrade) request served on stream 1 of that connection.
+pub struct Http2Settings {
+    payload: Bytes,
+}
+
+impl Http2Settings {
+    /// Decode the (base64url encoded) value of an `HTTP2-Settings` header.
+    ///
+    /// Returns `None` in case the value is not a valid SETTINGS frame payload.
+    pub fn decode(value: &HeaderValue) -> Option<Self> {
+        let value = value.as_bytes();
+        let value = value
+            .strip_suffix(b"==")
+            .or_else(|| value.strip_suffix(b"="))
+            .unwrap_or(value);
+        let payload = base64::engine::general_purpose::URL_SAFE_NO_PAD
+            .decode(value)
+            .ok()?;
+        if payload.len() % 6 != 0 {
+            return None;
+        }
+        let settings = Self {
+            payload: payload.into(),
+        };
+        let valid = settings.iter().all(|(id, value)| match id {
+            SETTINGS_ENABLE_PUSH | SETTINGS_ENABLE_CONNECT_PROTOCOL => value <= 1,
+            SETTINGS_INITIAL_WINDOW_SIZE => value < (1 << 31),
+            SETTINGS_MAX_FRAME_SIZE => (DEFAULT_MAX_FRAME_SIZE..(1 << 24)).contains(&value),
+            _ => true,
+        });
+        valid.then_some(settings)
+    }
+
+    /// Get the value of the setting with the given identifier, if present.
+    pub fn get(&self, id: u16) -> Option<u32> {
+        self.iter()
+            .filter_map(|(setting, value)| (setting == id).then_some(value))
+            .last()
+    }
+
+    /// Iterate over all (identifier, value) pairs, in the order they were received.
+    pub fn iter(&self) -> impl Iterator<Item = (u16, u32)> + '_ {
+        self.payload.chunks_exact(6).map(|raw| {
+            (
+                u16::from_be_bytes([raw[0], raw[1]]),
+                u32::from_be_bytes([raw[2], raw[3], raw[4], raw[5]]),
+            )
+        })
+    }
+}
+
+/// [`HttpService`] wrapper used by the auto connection builder
+/// for http/1.1 connections, taking over h2c upgrade requests when enabled.
+pub(crate) struct H2cUpgradeService<S> {
+    inner: Arc<S>,
+    http2: Option<http2::Builder>,
+}
+
+impl<S> H2cUpgradeService<S> {
+    pub(super) fn new(inner: S, http2: Option<http2::Builder>) -> Self {
+        Self {
+            inner: Arc::new(inner),
+            http2,
+        }
+    }
+}
+
+impl<S> HttpService<Incoming> for H2cUpgradeService<S>
+where
+    S: HttpService<Incoming>,
+{
+    fn serve_http(
+        &self,
+        mut req: Request<Incoming>,
+    ) -> impl Future<Output = Result<Response, Infallible>> + Send + 'static {
+        let upgrade = self
+            .http2
+            .as_ref()
+            .and_then(|http2| Some((http2.clone(), prepare_upgrade(&req)?)));
+
+        let Some((http2, (settings, upgrade_request))) = upgrade else {
+            return Either::Right(self.inner.serve_http(req));
+        };
+
+        tracing::trace!(
+            ?settings,
+            uri = %upgrade_request.uri(),
+            "h2c upgrade: switching protocols"
+        );
+
+        let on_upgrade = crate::upgrade::on(&mut req);
+        // the upgraded connection is served as h2 only
+        let service = Self {
+            inner: self.inner.clone(),
+            http2: None,
+        };
+        let executor = http2.executor().clone();
+        executor.spawn_task(async move {
+            let upgraded = match on_upgrade.await {
+                Ok(upgraded) => upgraded,
+                Err(err) => {
+                    tracing::debug!(error = %err, "h2c upgrade: failed to upgrade connection");
+                    return;
+                }
+            };
+            let conn = match http2.serve_h2c_upgraded_connection(
+                upgraded,
+                &settings.payload,
+                upgrade_request,
+                service,
+            ) {
+                Ok(conn) => conn,
+                Err(err) => {
+                    tracing::debug!(error = %err, "h2c upgrade: failed to serve h2 connection");
+                    return;
+                }
+            };
+            if let Err(err) = conn.await {
+                tracing::debug!(error = %err, "h2c upgrade: h2 connection error");
+            }
+        });
+
+        let mut resp = Response::new(rama_http_types::Body::empty());
+        *resp.status_mut() = StatusCode::SWITCHING_PROTOCOLS;
+        resp.headers_mut()
+            .insert(CONNECTION, HeaderValue::from_static("upgrade"));
+        resp.headers_mut()
+            .insert(UPGRADE, HeaderValue::from_static(H2C));
+        Either::Left(std::future::ready(Ok(resp)))
+    }
+}
+
+/// Returns the decoded client settings and the request to serve on stream 1
+/// in case the request is a valid h2c upgrade request.
+///
+/// Only bodiless requests which ask to upgrade to h2c (and nothing else) are upgraded,
+/// as the request is served on the h2 connection, and only after the client switched protocols.
+fn prepare_upgrade(req: &Request<Incoming>) -> Option<(Http2Settings, Request<()>)> {
+    if req.version() != Version::HTTP_11
+        || !is_h2c_upgrade_only(req.headers())
+        || !header_contains_token(req.headers(), &CONNECTION, "upgrade")
+        || !header_contains_token(req.headers(), &CONNECTION, HTTP2_SETTINGS.as_str())
+        || !req.body().is_end_stream()
+        || req
+            .extensions()
+            .get::<crate::upgrade::OnUpgrade>()
+            .is_none()
+    {
+        return None;
+    }
+
+    let mut values = req.headers().get_all(HTTP2_SETTINGS).iter();
+    let settings = match (values.next(), values.next()) {
+        (Some(value), None) => Http2Settings::decode(value)?,
+        _ => return None,
+    };
+
+    let upgrade_request = stream_one_request(req, settings.clone())?;
+    Some((settings, upgrade_request))
+}
+
+/// Returns true if the `Upgrade` header consists of the single `h2c` token,
+/// as the upgrade would otherwise not be the one (only) protocol switched to.
+fn is_h2c_upgrade_only(headers: &HeaderMap) -> bool {
+    let mut values = headers.get_all(UPGRADE).iter();
+    match (values.next(), values.next()) {
+        (Some(value), None) => value
+            .to_str()
+            .is_ok_and(|value| value.trim().eq_ignore_ascii_case(H2C)),
+        _ => false,
+    }
+}
+
+/// Create the request to serve on stream 1 from the (http/1.1) upgrade request,
+/// as it would have been received over h2.
+fn stream_one_request<B>(req: &Request<B>, settings: Http2Settings) -> Option<Request<()>> {
+    let authority = match req.uri().authority() {
+        Some(authority) => authority.clone(),
+        None => Authority::try_from(req.headers().get(HOST)?.as_bytes()).ok()?,
+    };
+    let path = req
+        .uri()
+        .path_and_query()
+        .map(|path| path.as_str())
+        .filter(|path| !path.is_empty())
+        .unwrap_or("/");
+    let uri = Uri::builder()
+        .scheme(Scheme::HTTP)
+        .authority(authority)
+        .path_and_query(path)
+        .build()
+        .ok()?;
+
+    let mut request = Request::new(());
+    *request.method_mut() = req.method().clone();
+    *request.uri_mut() = uri;
+    *request.version_mut() = Version::HTTP_2;
+    for (name, value) in req.headers() {
+        if !is_connection_specific_header(req.headers(), name, value) {
+            request.headers_mut().append(name.clone(), value.clone());
+        }
+    }
+    request.extensions_mut().insert(settings);
+    Some(request)
+}
+
+fn is_connection_specific_header(
+    headers: &HeaderMap,
+    name: &HeaderName,
+    value: &HeaderValue,
+) -> bool {
+    match name.as_str() {
+        "connection" | "upgrade" | "host" | "transfer-encoding" | "http2-settings"
+        | "keep-alive" | "proxy-connection" => true,
+        "te" => value.as_bytes() != b"trailers",
+        name => header_contains_token(headers, &CONNECTION, name),
+    }
+}
+
+fn header_contains_token(headers: &HeaderMap, name: &HeaderName, token: &str) -> bool {
+    headers
+        .get_all(name)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|value| value.trim().eq_ignore_ascii_case(token))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client;
+    use crate::server::conn::auto;
+    use crate::service::RamaHttpService;
+    use rama_core::rt::Executor;
+    use rama_core::service::service_fn;
+    use rama_core::Context;
+    use rama_http_types::dep::http_body_util::{BodyExt, Empty};
+    use std::net::SocketAddr;
+    use tokio::io::AsyncWriteExt;
+    use tokio::net::{TcpListener, TcpStream};
+
+    #[test]
+    fn test_http2_settings_decode() {
+        // ENABLE_PUSH=0, INITIAL_WINDOW_SIZE=65535
+        let settings =
+            Http2Settings::decode(&HeaderValue::from_static("AAIAAAAAAAQAAP__")).unwrap();
+        assert_eq!(Some(0), settings.get(SETTINGS_ENABLE_PUSH));
+        assert_eq!(Some(65535), settings.get(SETTINGS_INITIAL_WINDOW_SIZE));
+        assert_eq!(2, settings.iter().count());
+
+        assert_eq!(
+            Some(Http2Settings::default()),
+            Http2Settings::decode(&HeaderValue::from_static(""))
+        );
+        // not base64url
+        assert!(Http2Settings::decode(&HeaderValue::from_static("AAIAAAAA+/")).is_none());
+        // not a multiple of 6 bytes
+        assert!(Http2Settings::decode(&HeaderValue::from_static("AAIAAAA")).is_none());
+        // ENABLE_PUSH=2
+        assert!(Http2Settings::decode(&HeaderValue::from_static("AAIAAAAC")).is_none());
+    }
+
+    #[test]
+    fn test_is_h2c_upgrade_only() {
+        let mut headers = HeaderMap::new();
+        assert!(!is_h2c_upgrade_only(&headers));
+        headers.insert(UPGRADE, HeaderValue::from_static("H2C"));
+        assert!(is_h2c_upgrade_only(&headers));
+        headers.insert(UPGRADE, HeaderValue::from_static("websocket, h2c"));
+        assert!(!is_h2c_upgrade_only(&headers));
+        headers.insert(UPGRADE, HeaderValue::from_static("h2c"));
+        headers.append(UPGRADE, HeaderValue::from_static("websocket"));
+        assert!(!is_h2c_upgrade_only(&headers));
+    }
+
+    #[test]
+    fn test_stream_one_request() {
+        let req = Request::builder()
+            .uri("/index.html?q=1")
+            .header(HOST, "example.com")
+            .header(CONNECTION, "Upgrade, HTTP2-Settings")
+            .header(UPGRADE, "h2c")
+            .header(HTTP2_SETTINGS, "AAIAAAAA")
+            .header("accept", "*/*")
+            .body(())
+            .unwrap();
+        let settings = Http2Settings::decode(&HeaderValue::from_static("AAIAAAAA")).unwrap();
+        let request = stream_one_request(&req, settings.clone()).unwrap();
+
+        assert_eq!(Version::HTTP_2, request.version());
+        assert_eq!("http://example.com/index.html?q=1", request.uri());
+        assert_eq!(1, request.headers().len());
+        assert_eq!("*/*", request.headers()["accept"]);
+        assert_eq!(Some(&settings), request.extensions().get::<Http2Settings>());
+    }
+
+    #[tokio::test]
+    async fn test_h2c_upgrade_applies_client_settings() {
+        let (mut client, server) = tokio::io::duplex(4096);
+
+        let request = Request::builder()
+            .uri("http://example.com/")
+            .version(Version::HTTP_2)
+            .body(())
+            .unwrap();
+        // INITIAL_WINDOW_SIZE=10
+        let settings = [0, 4, 0, 0, 0, 10];
+        let handshake = crate::h2::server::Builder::new()
+            .handshake_h2c_upgraded::<_, Bytes>(server, &settings, request)
+            .unwrap();
+
+        client
+            .write_all(b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n\0\0\0\x04\0\0\0\0\0")
+            .await
+            .unwrap();
+        let mut conn = handshake.await.unwrap();
+
+        let (req, mut respond) = conn.accept().await.unwrap().unwrap();
+        assert_eq!("http://example.com/", req.uri());
+        assert!(req.body().is_end_stream());
+
+        let mut stream = respond.send_response(Response::new(()), false).unwrap();
+        stream.reserve_capacity(100);
+        assert_eq!(10, stream.capacity());
+
+        // not a valid settings payload
+        let (_, server) = tokio::io::duplex(64);
+        assert!(crate::h2::server::Builder::new()
+            .handshake_h2c_upgraded::<_, Bytes>(server, &[0, 2, 0], Request::new(()))
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_h2c_upgrade_roundtrip() {
+        let listener = TcpListener::bind(SocketAddr::from(([127, 0, 0, 1], 0)))
+            .await
+            .unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut builder = auto::Builder::new(Executor::new());
+            builder
+                .http2()
+                .enable_h2c_upgrade()
+                .serve_connection_with_upgrades(
+                    stream,
+                    RamaHttpService::new(
+                        Context::default(),
+                        service_fn(|req: Request| async move {
+                            let body = format!(
+                                "{:?} {} {}",
+                                req.version(),
+                                req.uri().path(),
+                                req.extensions().get::<Http2Settings>().is_some()
+                            );
+                            Ok::<_, Infallible>(Response::new(rama_http_types::Body::from(body)))
+                        }),
+                    ),
+                )
+                .await
+                .unwrap();
+        });
+
+        let stream = TcpStream::connect(addr).await.unwrap();
+        let (sender, conn) = client::conn::http1::handshake(stream).await.unwrap();
+        tokio::spawn(conn.with_upgrades());
+
+        let upgrade_request = Request::builder()
+            .uri("/upgrade")
+            .header(HOST, "example.com")
+            .header(CONNECTION, "Upgrade, HTTP2-Settings")
+            .header(UPGRADE, "h2c")
+            .header(HTTP2_SETTINGS, "AAMAAABkAAQAAP__")
+            .body(Empty::<Bytes>::new())
+            .unwrap();
+        let res = sender.send_request(upgrade_request).await.unwrap();
+        assert_eq!(StatusCode::SWITCHING_PROTOCOLS, res.status());
+        let upgraded = crate::upgrade::on(res).await.unwrap();
+
+        let (sender, conn, response) = client::conn::http2::Builder::new(Executor::new())
+            .handshake_h2c_upgraded::<_, Empty<Bytes>>(
+                upgraded,
+                Request::builder()
+                    .uri("http://example.com/upgrade")
+                    .body(())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        tokio::spawn(conn);
+
+        let res = response.await.unwrap();
+        let body = res.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!("HTTP/2.0 /upgrade true", body);
+
+        let res = sender
+            .send_request(
+                Request::builder()
+                    .uri("http://example.com/next")
+                    .body(Empty::new())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let body = res.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!("HTTP/2.0 /next false", body);
+    }
+
+    #[tokio::test]
+    async fn test_no_h2c_upgrade_for_other_protocols() {
+        let listener = TcpListener::bind(SocketAddr::from(([127, 0, 0, 1], 0)))
+            .await
+            .unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut builder = auto::Builder::new(Executor::new());
+            builder
+                .http2()
+                .enable_h2c_upgrade()
+                .serve_connection_with_upgrades(
+                    stream,
+                    RamaHttpService::new(
+                        Context::default(),
+                        service_fn(|req: Request| async move {
+                            Ok::<_, Infallible>(Response::new(rama_http_types::Body::from(
+                                format!("{:?}", req.version()),
+                            )))
+                        }),
+                    ),
+                )
+                .await
+                .unwrap();
+        });
+
+        let stream = TcpStream::connect(addr).await.unwrap();
+        let (sender, conn) = client::conn::http1::handshake(stream).await.unwrap();
+        tokio::spawn(conn.with_upgrades());
+
+        let res = sender
+            .send_request(
+                Request::builder()
+                    .uri("/")
+                    .header(HOST, "example.com")
+                    .header(CONNECTION, "Upgrade, HTTP2-Settings")
+                    .header(UPGRADE, "websocket, h2c")
+                    .header(HTTP2_SETTINGS, "AAMAAABkAAQAAP__")
+                    .body(Empty::<Bytes>::new())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+        let body = res.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!("HTTP/1.1", body);
+    }
+}
@@ -14,6 +14,7 @@ use tokio::io::{AsyncRead, AsyncWrite};
 use crate::body::Incoming as IncomingBody;
 use crate::proto;
 use crate::service::HttpService;
+use rama_http_types::Request;
 
 pin_project! {
     /// A [`Future`](core::future::Future) representing an HTTP/2 connection, bound to a
@@ -258,6 +259,10 @@ impl Builder {
         self
     }
 
+    pub(super) fn executor(&self) -> &Executor {
+        &self.exec
+    }
+
     /// Bind a connection together with a [`Service`](crate::service::Service).
     ///
     /// This returns a Future that must be polled in order for HTTP to be
@@ -270,6 +275,33 @@ impl Builder {
         let proto = proto::h2::Server::new(io, service, &self.h2_builder, self.exec.clone());
         Connection { conn: proto }
     }
+
+    /// Bind a connection, upgraded from http/1.1 to cleartext h2 (`h2c`),
+    /// together with a [`Service`](crate::service::Service).
+    ///
+    /// The (bodiless) upgrade `request` is served on stream 1,
+    /// and `settings` is the decoded `HTTP2-Settings` payload of that request.
+    pub(super) fn serve_h2c_upgraded_connection<S, I>(
+        &self,
+        io: I,
+        settings: &[u8],
+        request: Request<()>,
+        service: S,
+    ) -> crate::Result<Connection<I, S>>
+    where
+        S: HttpService<IncomingBody>,
+        I: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+    {
+        let proto = proto::h2::Server::new_h2c_upgraded(
+            io,
+            settings,
+            request,
+            service,
+            &self.h2_builder,
+            self.exec.clone(),
+        )?;
+        Ok(Connection { conn: proto })
+    }
 }
 
 #[cfg(test)]
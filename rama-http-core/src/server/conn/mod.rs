@@ -19,3 +19,7 @@ pub mod http1;
 pub mod http2;
 
 pub mod auto;
+
+mod h2c;
+pub(crate) use h2c::H2cUpgradeService;
+pub use h2c::Http2Settings;
@@ -107,6 +107,11 @@ mod sealed {
     {
     }
 
+    impl<S> Sealed<crate::body::Incoming> for crate::server::conn::H2cUpgradeService<S> where
+        S: HttpService<crate::body::Incoming>
+    {
+    }
+
     impl<ReqBody> Sealed<ReqBody> for VoidHttpService where
         ReqBody: rama_http_types::dep::http_body::Body<Data = Bytes, Error: Into<BoxError>>
             + Send
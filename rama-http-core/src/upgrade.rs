@@ -124,7 +124,12 @@ pub(super) fn pending() -> (Pending, OnUpgrade) {
 // ===== impl Upgraded =====
 
 impl Upgraded {
-    pub(super) fn new<T>(io: T, read_buf: Bytes) -> Self
+    /// Create a new [`Upgraded`] from the given IO, with a buffer
+    /// of bytes which are returned by the first reads, prior to reading from the IO.
+    ///
+    /// Useful to wrap the IO of an [`Upgraded`] connection, e.g. to inspect its traffic,
+    /// while still being usable where an [`Upgraded`] connection is expected.
+    pub fn new<T>(io: T, read_buf: Bytes) -> Self
     where
         T: AsyncRead + AsyncWrite + Unpin + Send + 'static,
     {
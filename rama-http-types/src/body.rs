@@ -4,6 +4,7 @@ use crate::dep::{
     http_body::{self, Body as _, Frame},
     http_body_util::{self, BodyExt},
 };
+use crate::HeaderMap;
 use bytes::Bytes;
 use futures_core::TryStream;
 use futures_lite::stream::Stream;
@@ -78,6 +79,22 @@ impl Body {
         Self::new(crate::dep::http_body_util::Limited::new(self.0, limit))
     }
 
+    /// Append the given trailers to this body,
+    /// to be sent after all its data frames.
+    ///
+    /// In case the body itself ends with trailers as well,
+    /// the given trailers are merged into them, overwriting existing values.
+    /// Empty trailers are not sent at all.
+    pub fn with_trailers(self, trailers: HeaderMap) -> Self {
+        if trailers.is_empty() {
+            return self;
+        }
+        Self::new(WithTrailers {
+            inner: self,
+            trailers: Some(trailers),
+        })
+    }
+
     /// Convert the body into a [`Stream`] of data frames.
     ///
     /// Non-data frames (such as trailers) will be discarded. Use [`http_body_util::BodyStream`] if
@@ -194,6 +211,55 @@ impl http_body::Body for BodyDataStream {
     }
 }
 
+pin_project! {
+    struct WithTrailers<B> {
+        #[pin]
+        inner: B,
+        trailers: Option<HeaderMap>,
+    }
+}
+
+impl<B> http_body::Body for WithTrailers<B>
+where
+    B: http_body::Body<Data = Bytes, Error: Into<BoxError>>,
+{
+    type Data = Bytes;
+    type Error = BoxError;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let this = self.project();
+        if this.trailers.is_none() {
+            return this.inner.poll_frame(cx).map_err(Into::into);
+        }
+        match futures_lite::ready!(this.inner.poll_frame(cx)) {
+            Some(Ok(frame)) => match frame.into_trailers() {
+                Ok(mut trailers) => {
+                    trailers.extend(this.trailers.take().unwrap_or_default());
+                    Poll::Ready(Some(Ok(Frame::trailers(trailers))))
+                }
+                Err(frame) => Poll::Ready(Some(Ok(frame))),
+            },
+            Some(Err(err)) => Poll::Ready(Some(Err(err.into()))),
+            None => Poll::Ready(
+                this.trailers
+                    .take()
+                    .map(|trailers| Ok(Frame::trailers(trailers))),
+            ),
+        }
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.trailers.is_none() && self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> http_body::SizeHint {
+        self.inner.size_hint()
+    }
+}
+
 pin_project! {
     struct StreamBody<S> {
         #[pin]
@@ -226,3 +292,37 @@ fn test_try_downcast() {
     assert_eq!(try_downcast::<i32, _>(5_u32), Err(5_u32));
     assert_eq!(try_downcast::<i32, _>(5_i32), Ok(5_i32));
 }
+
+#[cfg(test)]
+#[tokio::test]
+async fn test_body_with_trailers() {
+    let mut trailers = HeaderMap::new();
+    trailers.insert("grpc-status", crate::HeaderValue::from_static("0"));
+
+    let collected = Body::from("hello")
+        .with_trailers(trailers.clone())
+        .collect()
+        .await
+        .unwrap();
+    assert_eq!(trailers, *collected.trailers().unwrap());
+    assert_eq!("hello", collected.to_bytes());
+
+    let mut extra = HeaderMap::new();
+    extra.insert("grpc-message", crate::HeaderValue::from_static("ok"));
+    let collected = Body::from("hello")
+        .with_trailers(trailers)
+        .with_trailers(extra)
+        .collect()
+        .await
+        .unwrap();
+    let trailers = collected.trailers().unwrap();
+    assert_eq!("0", trailers["grpc-status"]);
+    assert_eq!("ok", trailers["grpc-message"]);
+
+    let collected = Body::from("hello")
+        .with_trailers(HeaderMap::new())
+        .collect()
+        .await
+        .unwrap();
+    assert!(collected.trailers().is_none());
+}
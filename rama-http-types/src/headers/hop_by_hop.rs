@@ -0,0 +1,101 @@
+//! Utilities to remove the hop-by-hop headers of a message.
+
+use crate::{
+    header::{CONNECTION, KEEP_ALIVE, PROXY_CONNECTION, TE, TRAILER, TRANSFER_ENCODING, UPGRADE},
+    HeaderMap, HeaderName, HeaderValue,
+};
+
+/// Remove the hop-by-hop headers from the given headers,
+/// as required for intermediaries by [RFC 9110, section 7.6.1].
+///
+/// These are the `Connection` header, all headers listed by it,
+/// and the well-known hop-by-hop headers. The willingness to accept
+/// trailers (`TE: trailers`) is preserved, as it is required
+/// end-to-end by protocols such as gRPC.
+///
+/// [RFC 9110, section 7.6.1]: https://datatracker.ietf.org/doc/html/rfc9110#section-7.6.1
+pub fn remove_hop_by_hop_headers(headers: &mut HeaderMap) {
+    let connection_options: Vec<HeaderName> = headers
+        .get_all(CONNECTION)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .filter_map(|option| HeaderName::try_from(option.trim()).ok())
+        .collect();
+    for name in connection_options {
+        headers.remove(name);
+    }
+
+    let accepts_trailers = headers
+        .get_all(TE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|value| {
+            value
+                .split(';')
+                .next()
+                .is_some_and(|coding| coding.trim().eq_ignore_ascii_case("trailers"))
+        });
+
+    for name in [
+        &CONNECTION,
+        &PROXY_CONNECTION,
+        &KEEP_ALIVE,
+        &TE,
+        &TRAILER,
+        &TRANSFER_ENCODING,
+        &UPGRADE,
+    ] {
+        headers.remove(name);
+    }
+
+    if accepts_trailers {
+        headers.insert(TE, HeaderValue::from_static("trailers"));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::header::{CONTENT_TYPE, HOST, PROXY_AUTHENTICATE};
+
+    #[test]
+    fn test_remove_hop_by_hop_headers() {
+        let mut headers = HeaderMap::new();
+        headers.insert(HOST, HeaderValue::from_static("example.com"));
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("text/plain"));
+        headers.insert(CONNECTION, HeaderValue::from_static("keep-alive, X-Foo"));
+        headers.append(CONNECTION, HeaderValue::from_static("x-bar"));
+        headers.insert("x-foo", HeaderValue::from_static("foo"));
+        headers.insert("x-bar", HeaderValue::from_static("bar"));
+        headers.insert(&KEEP_ALIVE, HeaderValue::from_static("timeout=5"));
+        headers.insert(&PROXY_CONNECTION, HeaderValue::from_static("keep-alive"));
+        headers.insert(TRANSFER_ENCODING, HeaderValue::from_static("chunked"));
+        headers.insert(UPGRADE, HeaderValue::from_static("h2c"));
+        headers.insert(PROXY_AUTHENTICATE, HeaderValue::from_static("Basic"));
+
+        remove_hop_by_hop_headers(&mut headers);
+
+        let mut names: Vec<_> = headers.keys().map(|name| name.as_str()).collect();
+        names.sort_unstable();
+        assert_eq!(
+            vec!["content-type", "host", "proxy-authenticate"],
+            names,
+            "only end-to-end headers remain"
+        );
+    }
+
+    #[test]
+    fn test_remove_hop_by_hop_headers_keeps_te_trailers() {
+        let mut headers = HeaderMap::new();
+        headers.insert(TE, HeaderValue::from_static("gzip, trailers;q=1"));
+        remove_hop_by_hop_headers(&mut headers);
+        assert_eq!("trailers", headers.get(TE).unwrap());
+
+        let mut headers = HeaderMap::new();
+        headers.insert(TE, HeaderValue::from_static("gzip"));
+        remove_hop_by_hop_headers(&mut headers);
+        assert!(!headers.contains_key(TE));
+    }
+}
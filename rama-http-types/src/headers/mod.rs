@@ -90,3 +90,7 @@ pub mod authorization {
 mod ext;
 #[doc(inline)]
 pub use ext::HeaderExt;
+
+mod hop_by_hop;
+#[doc(inline)]
+pub use hop_by_hop::remove_hop_by_hop_headers;
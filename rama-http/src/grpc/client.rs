@@ -0,0 +1,645 @@
+//! gRPC client support.
+//!
+//! The [`GrpcClient`] calls gRPC methods using any http client [`Service`],
+//! such as the `HttpClient` of `rama-http-backend`, which sends the requests
+//! using h2, as required by gRPC.
+//!
+//! # Example
+//!
+//! ```
+//! use rama_core::{error::BoxError, Context, Service};
+//! use rama_http::grpc::{self, client::GrpcClient, ProstCodec};
+//! use rama_http::{Request, Response};
+//!
+//! #[derive(Clone, PartialEq, prost::Message)]
+//! struct HelloRequest {
+//!     #[prost(string, tag = "1")]
+//!     name: String,
+//! }
+//!
+//! #[derive(Clone, PartialEq, prost::Message)]
+//! struct HelloReply {
+//!     #[prost(string, tag = "1")]
+//!     message: String,
+//! }
+//!
+//! async fn say_hello<S>(client: S) -> Result<String, grpc::Status>
+//! where
+//!     S: Service<(), Request, Response = Response, Error: Into<BoxError>>,
+//! {
+//!     let client = GrpcClient::new(client, "http://127.0.0.1:50051".parse().unwrap());
+//!     let response = client
+//!         .unary(
+//!             Context::default(),
+//!             grpc::Request::new(HelloRequest {
+//!                 name: "rama".to_owned(),
+//!             }),
+//!             "/helloworld.Greeter/SayHello",
+//!             ProstCodec::<HelloRequest, HelloReply>::default(),
+//!         )
+//!         .await?;
+//!     Ok(response.into_inner().message)
+//! }
+//! ```
+
+use super::codec::{
+    Codec, EncodeBody, Role, Streaming, DEFAULT_MAX_RECV_MESSAGE_SIZE,
+    DEFAULT_MAX_SEND_MESSAGE_SIZE,
+};
+use super::timeout::{deadline_from_timeout, with_deadline, DeadlineStream};
+use super::{
+    is_grpc_content_type, sanitize_metadata, CompressionEncoding, Request, Response, Status,
+    GRPC_ACCEPT_ENCODING, GRPC_CONTENT_TYPE, GRPC_ENCODING,
+};
+use crate::{
+    header::{CONTENT_TYPE, TE},
+    Body, HeaderValue, Method, StatusCode, Uri, Version,
+};
+use futures_lite::{Stream, StreamExt};
+use rama_core::{error::BoxError, Context, Service};
+use std::fmt;
+use tokio::time::Instant;
+
+/// A client to call gRPC methods, using the inner http client [`Service`].
+///
+/// The requests are sent to the methods of the configured origin,
+/// such as `http://127.0.0.1:50051`, with the path of the method
+/// (`/{package}.{service}/{method}`) appended to it.
+///
+/// The deadline of a call can be set using [`Request::set_timeout`],
+/// in which case it is enforced by the client as well.
+/// The deadline applies to the entire call, including the streaming
+/// of request and response messages.
+pub struct GrpcClient<S> {
+    inner: S,
+    origin: Uri,
+    send_compression: Option<CompressionEncoding>,
+    accept_compression: Vec<CompressionEncoding>,
+    max_decoding_message_size: usize,
+    max_encoding_message_size: usize,
+}
+
+impl<S: fmt::Debug> fmt::Debug for GrpcClient<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("GrpcClient")
+            .field("inner", &self.inner)
+            .field("origin", &self.origin)
+            .field("send_compression", &self.send_compression)
+            .field("accept_compression", &self.accept_compression)
+            .field("max_decoding_message_size", &self.max_decoding_message_size)
+            .field("max_encoding_message_size", &self.max_encoding_message_size)
+            .finish()
+    }
+}
+
+impl<S: Clone> Clone for GrpcClient<S> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            origin: self.origin.clone(),
+            send_compression: self.send_compression,
+            accept_compression: self.accept_compression.clone(),
+            max_decoding_message_size: self.max_decoding_message_size,
+            max_encoding_message_size: self.max_encoding_message_size,
+        }
+    }
+}
+
+impl<S> GrpcClient<S> {
+    /// Create a new [`GrpcClient`] sending requests to the given origin,
+    /// using the given http client [`Service`].
+    pub fn new(inner: S, origin: Uri) -> Self {
+        Self {
+            inner,
+            origin,
+            send_compression: None,
+            accept_compression: Vec::new(),
+            max_decoding_message_size: DEFAULT_MAX_RECV_MESSAGE_SIZE,
+            max_encoding_message_size: DEFAULT_MAX_SEND_MESSAGE_SIZE,
+        }
+    }
+
+    /// Compress request messages with the given encoding.
+    ///
+    /// The server has to support the encoding,
+    /// otherwise calls fail with [`GrpcCode::Unimplemented`].
+    ///
+    /// [`GrpcCode::Unimplemented`]: super::GrpcCode::Unimplemented
+    pub fn send_compressed(mut self, encoding: CompressionEncoding) -> Self {
+        self.send_compression = Some(encoding);
+        self
+    }
+
+    /// Accept response messages compressed with the given encoding.
+    pub fn accept_compressed(mut self, encoding: CompressionEncoding) -> Self {
+        if !self.accept_compression.contains(&encoding) {
+            self.accept_compression.push(encoding);
+        }
+        self
+    }
+
+    /// Set the maximum size of a (decompressed) response message.
+    ///
+    /// Defaults to 4MiB.
+    pub fn max_decoding_message_size(mut self, limit: usize) -> Self {
+        self.max_decoding_message_size = limit;
+        self
+    }
+
+    /// Set the maximum size of an (encoded) request message.
+    ///
+    /// Defaults to `u32::MAX`, the maximum size of a gRPC message.
+    pub fn max_encoding_message_size(mut self, limit: usize) -> Self {
+        self.max_encoding_message_size = limit;
+        self
+    }
+
+    /// Returns a reference to the inner http client [`Service`].
+    pub fn get_ref(&self) -> &S {
+        &self.inner
+    }
+
+    /// Consume the [`GrpcClient`], returning the inner http client [`Service`].
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+}
+
+impl<S> GrpcClient<S> {
+    /// Call a unary method: send a single request message
+    /// and receive a single response message.
+    ///
+    /// The trailers of the response are merged into the metadata of the returned response.
+    pub async fn unary<State, C>(
+        &self,
+        ctx: Context<State>,
+        request: Request<C::Encode>,
+        path: &str,
+        codec: C,
+    ) -> Result<Response<C::Decode>, Status>
+    where
+        State: Clone + Send + Sync + 'static,
+        C: Codec,
+        S: Service<State, crate::Request, Response = crate::Response, Error: Into<BoxError>>,
+    {
+        let deadline = deadline_from_timeout(request.timeout());
+        with_deadline(deadline, async {
+            let request = request.map(futures_lite::stream::once);
+            let response = self.call(ctx, request, path, codec, deadline).await?;
+            single_message(response).await
+        })
+        .await
+    }
+
+    /// Call a server streaming method: send a single request message
+    /// and receive a stream of response messages.
+    pub async fn server_streaming<State, C>(
+        &self,
+        ctx: Context<State>,
+        request: Request<C::Encode>,
+        path: &str,
+        codec: C,
+    ) -> Result<Response<Streaming<C::Decode>>, Status>
+    where
+        State: Clone + Send + Sync + 'static,
+        C: Codec,
+        S: Service<State, crate::Request, Response = crate::Response, Error: Into<BoxError>>,
+    {
+        let deadline = deadline_from_timeout(request.timeout());
+        let request = request.map(futures_lite::stream::once);
+        with_deadline(deadline, self.call(ctx, request, path, codec, deadline)).await
+    }
+
+    /// Call a client streaming method: send a stream of request messages
+    /// and receive a single response message.
+    ///
+    /// The trailers of the response are merged into the metadata of the returned response.
+    pub async fn client_streaming<State, C, St>(
+        &self,
+        ctx: Context<State>,
+        request: Request<St>,
+        path: &str,
+        codec: C,
+    ) -> Result<Response<C::Decode>, Status>
+    where
+        State: Clone + Send + Sync + 'static,
+        C: Codec,
+        St: Stream<Item = C::Encode> + Send + 'static,
+        S: Service<State, crate::Request, Response = crate::Response, Error: Into<BoxError>>,
+    {
+        let deadline = deadline_from_timeout(request.timeout());
+        with_deadline(deadline, async {
+            let response = self.call(ctx, request, path, codec, deadline).await?;
+            single_message(response).await
+        })
+        .await
+    }
+
+    /// Call a bidirectional streaming method: send a stream of request messages
+    /// and receive a stream of response messages.
+    pub async fn streaming<State, C, St>(
+        &self,
+        ctx: Context<State>,
+        request: Request<St>,
+        path: &str,
+        codec: C,
+    ) -> Result<Response<Streaming<C::Decode>>, Status>
+    where
+        State: Clone + Send + Sync + 'static,
+        C: Codec,
+        St: Stream<Item = C::Encode> + Send + 'static,
+        S: Service<State, crate::Request, Response = crate::Response, Error: Into<BoxError>>,
+    {
+        let deadline = deadline_from_timeout(request.timeout());
+        with_deadline(deadline, self.call(ctx, request, path, codec, deadline)).await
+    }
+
+    async fn call<State, C, St>(
+        &self,
+        ctx: Context<State>,
+        request: Request<St>,
+        path: &str,
+        codec: C,
+        deadline: Option<Instant>,
+    ) -> Result<Response<Streaming<C::Decode>>, Status>
+    where
+        State: Clone + Send + Sync + 'static,
+        C: Codec,
+        St: Stream<Item = C::Encode> + Send + 'static,
+        S: Service<State, crate::Request, Response = crate::Response, Error: Into<BoxError>>,
+    {
+        let req = self.encode_request(request, path, codec.clone(), deadline)?;
+        let res = self
+            .inner
+            .serve(ctx, req)
+            .await
+            .map_err(|err| Status::from_error(err.into()))?;
+        let (parts, body) = res.into_parts();
+
+        // trailers-only responses carry the status in their headers
+        if let Some(status) = Status::from_header_map(&parts.headers) {
+            if !status.is_ok() {
+                return Err(status);
+            }
+            return Ok(Response::from_parts(
+                parts.headers.clone(),
+                parts.extensions,
+                Streaming::trailers_only(parts.headers),
+            ));
+        }
+
+        if parts.status != StatusCode::OK {
+            return Err(status_from_http_status(parts.status));
+        }
+        if !is_grpc_content_type(&parts.headers) {
+            return Err(Status::unknown(format!(
+                "invalid content-type for gRPC response: {:?}",
+                parts.headers.get(CONTENT_TYPE)
+            )));
+        }
+        let encoding =
+            CompressionEncoding::from_encoding_header(&parts.headers, &self.accept_compression)?;
+
+        let messages = Streaming::new(
+            body,
+            codec,
+            Role::Client,
+            encoding,
+            self.max_decoding_message_size,
+        )
+        .with_deadline(deadline);
+        Ok(Response::from_parts(
+            parts.headers,
+            parts.extensions,
+            messages,
+        ))
+    }
+
+    fn encode_request<C, St>(
+        &self,
+        request: Request<St>,
+        path: &str,
+        codec: C,
+        deadline: Option<Instant>,
+    ) -> Result<crate::Request, Status>
+    where
+        C: Codec,
+        St: Stream<Item = C::Encode> + Send + 'static,
+    {
+        let mut uri = self.origin.clone().into_parts();
+        uri.path_and_query = Some(
+            path.parse()
+                .map_err(|err| Status::internal(format!("invalid gRPC method path: {err}")))?,
+        );
+        let uri = Uri::from_parts(uri)
+            .map_err(|err| Status::internal(format!("invalid gRPC method uri: {err}")))?;
+
+        let (metadata, extensions, messages) = request.into_parts();
+        let body = EncodeBody::new(
+            DeadlineStream::new(messages.map(Ok), deadline),
+            codec,
+            Role::Client,
+            self.send_compression,
+            self.max_encoding_message_size,
+        );
+
+        let mut req = crate::Request::new(Body::new(body));
+        *req.method_mut() = Method::POST;
+        *req.uri_mut() = uri;
+        *req.version_mut() = Version::HTTP_2;
+        *req.extensions_mut() = extensions;
+        *req.headers_mut() = sanitize_metadata(metadata);
+
+        let headers = req.headers_mut();
+        headers.insert(CONTENT_TYPE, GRPC_CONTENT_TYPE);
+        headers.insert(TE, HeaderValue::from_static("trailers"));
+        if let Some(encoding) = self.send_compression {
+            headers.insert(GRPC_ENCODING, encoding.into_header_value());
+        }
+        if let Some(value) =
+            CompressionEncoding::accept_encoding_header_value(&self.accept_compression)
+        {
+            headers.insert(GRPC_ACCEPT_ENCODING, value);
+        }
+        Ok(req)
+    }
+}
+
+/// Read the single message of a unary or client streaming response,
+/// merging its trailers into the metadata.
+async fn single_message<M>(response: Response<Streaming<M>>) -> Result<Response<M>, Status> {
+    let (mut metadata, extensions, mut messages) = response.into_parts();
+    let message = messages
+        .message()
+        .await?
+        .ok_or_else(|| Status::internal("missing response message"))?;
+    if let Some(trailers) = messages.trailers().await? {
+        metadata.extend(sanitize_metadata(trailers));
+    }
+    Ok(Response::from_parts(metadata, extensions, message))
+}
+
+/// Map the status of a non-gRPC http response to a [`Status`],
+/// as described in the gRPC http status code mapping.
+fn status_from_http_status(status: StatusCode) -> Status {
+    let code = match status {
+        StatusCode::BAD_REQUEST => super::GrpcCode::Internal,
+        StatusCode::UNAUTHORIZED => super::GrpcCode::Unauthenticated,
+        StatusCode::FORBIDDEN => super::GrpcCode::PermissionDenied,
+        StatusCode::NOT_FOUND => super::GrpcCode::Unimplemented,
+        StatusCode::TOO_MANY_REQUESTS
+        | StatusCode::BAD_GATEWAY
+        | StatusCode::SERVICE_UNAVAILABLE
+        | StatusCode::GATEWAY_TIMEOUT => super::GrpcCode::Unavailable,
+        _ => super::GrpcCode::Unknown,
+    };
+    Status::new(
+        code,
+        format!("unexpected http status of gRPC response: {status}"),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grpc::{server::Grpc, GrpcCode, ProstCodec};
+    use crate::service::web::WebService;
+    use rama_core::service::service_fn;
+    use std::time::Duration;
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    struct Number {
+        #[prost(int64, tag = "1")]
+        value: i64,
+    }
+
+    type NumberCodec = ProstCodec<Number, Number>;
+
+    async fn double(req: Request<Number>) -> Result<Response<Number>, Status> {
+        let value = req.get_ref().value;
+        if value < 0 {
+            return Err(Status::invalid_argument("negative number"));
+        }
+        if value == 42 {
+            tokio::time::sleep(Duration::from_secs(10)).await;
+        }
+        let mut response = Response::new(Number { value: value * 2 });
+        response
+            .metadata_mut()
+            .insert("x-doubled", HeaderValue::from_static("true"));
+        Ok(response)
+    }
+
+    async fn count_to(
+        req: Request<Number>,
+    ) -> Result<Response<impl Stream<Item = Result<Number, Status>>>, Status> {
+        let n = req.into_inner().value;
+        Ok(Response::new(futures_lite::stream::iter(
+            (1..=n).map(|value| Ok(Number { value })),
+        )))
+    }
+
+    async fn sum(req: Request<Streaming<Number>>) -> Result<Response<Number>, Status> {
+        let mut messages = req.into_inner();
+        let mut value = 0;
+        while let Some(number) = messages.message().await? {
+            value += number.value;
+        }
+        Ok(Response::new(Number { value }))
+    }
+
+    async fn echo(req: Request<Streaming<Number>>) -> Result<Response<Streaming<Number>>, Status> {
+        Ok(Response::new(req.into_inner()))
+    }
+
+    fn client() -> GrpcClient<WebService<()>> {
+        let server = WebService::default()
+            .post(
+                "/test.Math/Double",
+                Grpc::new(NumberCodec::new())
+                    .accept_compressed(CompressionEncoding::Gzip)
+                    .send_compressed(CompressionEncoding::Gzip)
+                    .unary(service_fn(double)),
+            )
+            .post(
+                "/test.Math/CountTo",
+                Grpc::new(NumberCodec::new()).server_streaming(service_fn(count_to)),
+            )
+            .post(
+                "/test.Math/Sum",
+                Grpc::new(NumberCodec::new()).client_streaming(service_fn(sum)),
+            )
+            .post(
+                "/test.Math/Echo",
+                Grpc::new(NumberCodec::new()).streaming(service_fn(echo)),
+            );
+        GrpcClient::new(server, Uri::from_static("http://127.0.0.1:50051"))
+    }
+
+    #[tokio::test]
+    async fn test_grpc_unary() {
+        let client = client();
+        let response = client
+            .unary(
+                Context::default(),
+                Request::new(Number { value: 21 }),
+                "/test.Math/Double",
+                NumberCodec::new(),
+            )
+            .await
+            .unwrap();
+        assert_eq!("true", response.metadata()["x-doubled"]);
+        assert_eq!(42, response.into_inner().value);
+
+        let status = client
+            .unary(
+                Context::default(),
+                Request::new(Number { value: -1 }),
+                "/test.Math/Double",
+                NumberCodec::new(),
+            )
+            .await
+            .unwrap_err();
+        assert_eq!(GrpcCode::InvalidArgument, status.code());
+        assert_eq!("negative number", status.message());
+
+        let status = client
+            .unary(
+                Context::default(),
+                Request::new(Number { value: 1 }),
+                "/test.Math/Unknown",
+                NumberCodec::new(),
+            )
+            .await
+            .unwrap_err();
+        assert_eq!(GrpcCode::Unimplemented, status.code());
+    }
+
+    #[tokio::test]
+    async fn test_grpc_unary_compressed() {
+        let client = client()
+            .send_compressed(CompressionEncoding::Gzip)
+            .accept_compressed(CompressionEncoding::Gzip);
+        let response = client
+            .unary(
+                Context::default(),
+                Request::new(Number { value: 2 }),
+                "/test.Math/Double",
+                NumberCodec::new(),
+            )
+            .await
+            .unwrap();
+        assert_eq!("gzip", response.metadata()[GRPC_ENCODING]);
+        assert_eq!(4, response.into_inner().value);
+
+        let status = client
+            .client_streaming(
+                Context::default(),
+                Request::new(futures_lite::stream::iter([Number { value: 1 }])),
+                "/test.Math/Sum",
+                NumberCodec::new(),
+            )
+            .await
+            .unwrap_err();
+        assert_eq!(GrpcCode::Unimplemented, status.code());
+    }
+
+    #[tokio::test]
+    async fn test_grpc_deadline() {
+        let mut request = Request::new(Number { value: 42 });
+        request.set_timeout(Duration::from_millis(10));
+        let status = client()
+            .unary(
+                Context::default(),
+                request,
+                "/test.Math/Double",
+                NumberCodec::new(),
+            )
+            .await
+            .unwrap_err();
+        assert_eq!(GrpcCode::DeadlineExceeded, status.code());
+    }
+
+    #[tokio::test]
+    async fn test_grpc_streaming() {
+        let client = client();
+
+        let response = client
+            .server_streaming(
+                Context::default(),
+                Request::new(Number { value: 3 }),
+                "/test.Math/CountTo",
+                NumberCodec::new(),
+            )
+            .await
+            .unwrap();
+        let values: Vec<_> = response
+            .into_inner()
+            .map(|number| number.unwrap().value)
+            .collect()
+            .await;
+        assert_eq!(vec![1, 2, 3], values);
+
+        let response = client
+            .client_streaming(
+                Context::default(),
+                Request::new(futures_lite::stream::iter(
+                    (1..=4).map(|value| Number { value }),
+                )),
+                "/test.Math/Sum",
+                NumberCodec::new(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(10, response.into_inner().value);
+
+        let response = client
+            .streaming(
+                Context::default(),
+                Request::new(futures_lite::stream::iter(
+                    (5..=6).map(|value| Number { value }),
+                )),
+                "/test.Math/Echo",
+                NumberCodec::new(),
+            )
+            .await
+            .unwrap();
+        let mut messages = response.into_inner();
+        assert_eq!(5, messages.message().await.unwrap().unwrap().value);
+        assert_eq!(6, messages.message().await.unwrap().unwrap().value);
+        assert!(messages.message().await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_grpc_server_rejects_non_grpc_requests() {
+        let client = client();
+        let res = client
+            .get_ref()
+            .serve(
+                Context::default(),
+                crate::Request::builder()
+                    .method(Method::POST)
+                    .uri("/test.Math/Double")
+                    .header(CONTENT_TYPE, "application/json")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::UNSUPPORTED_MEDIA_TYPE, res.status());
+    }
+
+    #[test]
+    fn test_status_from_http_status() {
+        for (status, code) in [
+            (StatusCode::BAD_REQUEST, GrpcCode::Internal),
+            (StatusCode::UNAUTHORIZED, GrpcCode::Unauthenticated),
+            (StatusCode::FORBIDDEN, GrpcCode::PermissionDenied),
+            (StatusCode::NOT_FOUND, GrpcCode::Unimplemented),
+            (StatusCode::SERVICE_UNAVAILABLE, GrpcCode::Unavailable),
+            (StatusCode::INTERNAL_SERVER_ERROR, GrpcCode::Unknown),
+        ] {
+            assert_eq!(code, status_from_http_status(status).code());
+        }
+    }
+}
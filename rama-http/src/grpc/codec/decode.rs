@@ -0,0 +1,353 @@
+use super::{Codec, Role, HEADER_SIZE};
+use crate::dep::http_body::Body as _;
+use crate::grpc::timeout::DeadlineTimer;
+use crate::grpc::{CompressionEncoding, Status};
+use crate::{Body, HeaderMap};
+use bytes::{Buf, Bytes, BytesMut};
+use futures_lite::Stream;
+use std::{
+    fmt,
+    future::poll_fn,
+    pin::Pin,
+    task::{Context, Poll},
+};
+use tokio::time::Instant;
+
+type DecodeFn<T> = Box<dyn Fn(Bytes) -> Result<T, Status> + Send + Sync + 'static>;
+
+/// A stream of gRPC messages, decoded from a request or response body.
+///
+/// Servers receive it as the message of client streaming requests,
+/// while clients receive it as the message of server streaming responses.
+///
+/// For responses, the stream ends with an error in case the trailers
+/// of the response contain a non-ok [`Status`].
+///
+/// In case the call has a deadline, the stream ends with a
+/// [`GrpcCode::DeadlineExceeded`] error once it expires.
+///
+/// [`GrpcCode::DeadlineExceeded`]: crate::grpc::GrpcCode::DeadlineExceeded
+pub struct Streaming<T> {
+    body: Body,
+    decode: DecodeFn<T>,
+    role: Role,
+    state: State,
+    buf: BytesMut,
+    encoding: Option<CompressionEncoding>,
+    max_message_size: usize,
+    trailers: Option<HeaderMap>,
+    deadline: DeadlineTimer,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    ReadHeader,
+    ReadBody { compressed: bool, len: usize },
+    Done,
+}
+
+impl<T> Streaming<T> {
+    pub(crate) fn new<C>(
+        body: Body,
+        codec: C,
+        role: Role,
+        encoding: Option<CompressionEncoding>,
+        max_message_size: usize,
+    ) -> Self
+    where
+        C: Codec<Decode = T>,
+    {
+        Self {
+            body,
+            decode: Box::new(move |buf| codec.decode(buf)),
+            role,
+            state: State::ReadHeader,
+            buf: BytesMut::new(),
+            encoding,
+            max_message_size,
+            trailers: None,
+            deadline: DeadlineTimer::new(None),
+        }
+    }
+
+    /// Fail the stream once the given deadline of the call expires.
+    pub(crate) fn with_deadline(mut self, deadline: Option<Instant>) -> Self {
+        self.deadline = DeadlineTimer::new(deadline);
+        self
+    }
+
+    /// Create a [`Streaming`] for a "trailers-only" response,
+    /// which has no messages and carries its status in the given headers.
+    pub(crate) fn trailers_only(headers: HeaderMap) -> Self {
+        Self {
+            body: Body::empty(),
+            decode: Box::new(|_| Err(Status::internal("unexpected message"))),
+            role: Role::Client,
+            state: State::ReadHeader,
+            buf: BytesMut::new(),
+            encoding: None,
+            max_message_size: 0,
+            trailers: Some(headers),
+            deadline: DeadlineTimer::new(None),
+        }
+    }
+
+    /// Fetch the next message from this stream,
+    /// returning `None` once the stream has ended successfully.
+    pub async fn message(&mut self) -> Result<Option<T>, Status> {
+        poll_fn(|cx| Pin::new(&mut *self).poll_next(cx))
+            .await
+            .transpose()
+    }
+
+    /// Consume all remaining messages and return the trailers of the stream,
+    /// if any.
+    ///
+    /// For responses an error is returned in case the trailers
+    /// contain a non-ok [`Status`].
+    pub async fn trailers(&mut self) -> Result<Option<HeaderMap>, Status> {
+        while self.message().await?.is_some() {}
+        Ok(self.trailers.clone())
+    }
+
+    fn decode_chunk(&mut self) -> Result<Option<T>, Status> {
+        if let State::ReadHeader = self.state {
+            if self.buf.len() < HEADER_SIZE {
+                return Ok(None);
+            }
+            let compressed = match self.buf.get_u8() {
+                0 => false,
+                1 => true,
+                flag => {
+                    return Err(Status::internal(format!(
+                        "invalid compression flag in message header: {flag}"
+                    )))
+                }
+            };
+            let len = self.buf.get_u32() as usize;
+            if len > self.max_message_size {
+                return Err(Status::resource_exhausted(format!(
+                    "message of {len} bytes exceeds the limit of {} bytes",
+                    self.max_message_size
+                )));
+            }
+            self.buf.reserve(len);
+            self.state = State::ReadBody { compressed, len };
+        }
+
+        if let State::ReadBody { compressed, len } = self.state {
+            if self.buf.len() < len {
+                return Ok(None);
+            }
+            let msg = self.buf.split_to(len);
+            let msg = if compressed {
+                let Some(encoding) = self.encoding else {
+                    return Err(Status::internal(
+                        "compressed message received without grpc-encoding",
+                    ));
+                };
+                encoding.decompress(&msg, self.max_message_size)?
+            } else {
+                msg
+            };
+            self.state = State::ReadHeader;
+            return (self.decode)(msg.freeze()).map(Some);
+        }
+
+        Ok(None)
+    }
+
+    /// Handle the end of the body, returning the status of the stream.
+    fn on_end_of_stream(&mut self) -> Result<(), Status> {
+        self.state = State::Done;
+        if !self.buf.is_empty() {
+            return Err(Status::internal(
+                "unexpected end of stream while reading message",
+            ));
+        }
+        if self.role == Role::Server {
+            return Ok(());
+        }
+        match self.trailers.as_ref().and_then(Status::from_header_map) {
+            Some(status) if status.is_ok() => Ok(()),
+            Some(status) => Err(status),
+            None => Err(Status::internal("missing grpc-status in response trailers")),
+        }
+    }
+}
+
+impl<T> Stream for Streaming<T> {
+    type Item = Result<T, Status>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        loop {
+            if this.state == State::Done {
+                return Poll::Ready(None);
+            }
+
+            match this.decode_chunk() {
+                Ok(Some(msg)) => return Poll::Ready(Some(Ok(msg))),
+                Ok(None) => (),
+                Err(status) => {
+                    this.state = State::Done;
+                    return Poll::Ready(Some(Err(status)));
+                }
+            }
+
+            if let Poll::Ready(status) = this.deadline.poll_expired(cx) {
+                this.state = State::Done;
+                return Poll::Ready(Some(Err(status)));
+            }
+
+            match futures_lite::ready!(Pin::new(&mut this.body).poll_frame(cx)) {
+                Some(Ok(frame)) => match frame.into_data() {
+                    Ok(data) => this.buf.extend_from_slice(&data),
+                    Err(frame) => {
+                        if let Ok(trailers) = frame.into_trailers() {
+                            match this.trailers.as_mut() {
+                                Some(existing) => existing.extend(trailers),
+                                None => this.trailers = Some(trailers),
+                            }
+                        }
+                    }
+                },
+                Some(Err(err)) => {
+                    this.state = State::Done;
+                    return Poll::Ready(Some(Err(Status::from_error(err.into()))));
+                }
+                None => {
+                    return Poll::Ready(this.on_end_of_stream().err().map(Err));
+                }
+            }
+        }
+    }
+}
+
+impl<T> fmt::Debug for Streaming<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Streaming")
+            .field("body", &self.body)
+            .field("role", &self.role)
+            .field("state", &self.state)
+            .field("encoding", &self.encoding)
+            .field("max_message_size", &self.max_message_size)
+            .field("trailers", &self.trailers)
+            .field("deadline", &self.deadline)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grpc::codec::{encode::encode_message, ProstCodec};
+    use crate::grpc::{GrpcCode, GRPC_STATUS};
+    use crate::HeaderValue;
+    use futures_lite::StreamExt;
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    struct Item {
+        #[prost(uint32, tag = "1")]
+        id: u32,
+    }
+
+    fn encoded(ids: &[u32], encoding: Option<CompressionEncoding>) -> Vec<u8> {
+        let codec = ProstCodec::<Item, Item>::new();
+        ids.iter()
+            .flat_map(|id| {
+                encode_message(&codec, Item { id: *id }, encoding, usize::MAX)
+                    .unwrap()
+                    .to_vec()
+            })
+            .collect()
+    }
+
+    fn streaming(body: Body, role: Role, encoding: Option<CompressionEncoding>) -> Streaming<Item> {
+        Streaming::new(body, ProstCodec::<Item, Item>::new(), role, encoding, 1024)
+    }
+
+    fn trailers(code: &'static str) -> HeaderMap {
+        let mut trailers = HeaderMap::new();
+        trailers.insert(GRPC_STATUS, HeaderValue::from_static(code));
+        trailers
+    }
+
+    #[tokio::test]
+    async fn test_streaming_server() {
+        for encoding in [None, Some(CompressionEncoding::Gzip)] {
+            let body = Body::from(encoded(&[1, 2, 3], encoding));
+            let items: Vec<_> = streaming(body, Role::Server, encoding)
+                .map(|item| item.unwrap().id)
+                .collect()
+                .await;
+            assert_eq!(vec![1, 2, 3], items);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_streaming_byte_by_byte() {
+        let data = encoded(&[1, 300], None);
+        let body = Body::from_stream(futures_lite::stream::iter(
+            data.into_iter()
+                .map(|b| Ok::<_, std::io::Error>(Bytes::from(vec![b]))),
+        ))
+        .with_trailers(trailers("0"));
+        let mut stream = streaming(body, Role::Client, None);
+        assert_eq!(1, stream.message().await.unwrap().unwrap().id);
+        assert_eq!(300, stream.message().await.unwrap().unwrap().id);
+        assert!(stream.message().await.unwrap().is_none());
+        assert_eq!("0", stream.trailers().await.unwrap().unwrap()[GRPC_STATUS]);
+    }
+
+    #[tokio::test]
+    async fn test_streaming_client_status() {
+        let body = Body::from(encoded(&[1], None)).with_trailers(trailers("5"));
+        let mut stream = streaming(body, Role::Client, None);
+        assert_eq!(1, stream.message().await.unwrap().unwrap().id);
+        assert_eq!(
+            GrpcCode::NotFound,
+            stream.message().await.unwrap_err().code()
+        );
+
+        let body = Body::from(encoded(&[1], None));
+        let mut stream = streaming(body, Role::Client, None);
+        assert_eq!(1, stream.message().await.unwrap().unwrap().id);
+        assert_eq!(
+            GrpcCode::Internal,
+            stream.message().await.unwrap_err().code()
+        );
+
+        let mut stream = Streaming::<Item>::trailers_only(trailers("7"));
+        assert_eq!(
+            GrpcCode::PermissionDenied,
+            stream.message().await.unwrap_err().code()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_streaming_errors() {
+        let mut data = encoded(&[1], None);
+        data.truncate(data.len() - 1);
+        let mut stream = streaming(Body::from(data), Role::Server, None);
+        assert_eq!(
+            GrpcCode::Internal,
+            stream.message().await.unwrap_err().code()
+        );
+        assert!(stream.message().await.unwrap().is_none());
+
+        let body = Body::from(&b"\x00\x00\x00\x10\x00"[..]);
+        let mut stream = streaming(body, Role::Server, None);
+        assert_eq!(
+            GrpcCode::ResourceExhausted,
+            stream.message().await.unwrap_err().code()
+        );
+
+        let body = Body::from(encoded(&[1], Some(CompressionEncoding::Gzip)));
+        let mut stream = streaming(body, Role::Server, None);
+        assert_eq!(
+            GrpcCode::Internal,
+            stream.message().await.unwrap_err().code()
+        );
+    }
+}
@@ -0,0 +1,229 @@
+use super::{Codec, HEADER_SIZE};
+use crate::dep::http_body::{self, Frame};
+use crate::grpc::{CompressionEncoding, Status};
+use bytes::{BufMut, Bytes, BytesMut};
+use futures_lite::Stream;
+use pin_project_lite::pin_project;
+use rama_core::error::BoxError;
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+use sync_wrapper::SyncWrapper;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// The side of the call on which messages are encoded or decoded.
+pub(crate) enum Role {
+    /// The client, encoding requests and decoding responses.
+    Client,
+    /// The server, encoding responses and decoding requests.
+    Server,
+}
+
+/// Encode a single message, prefixed with its gRPC message header.
+pub(crate) fn encode_message<C: Codec>(
+    codec: &C,
+    item: C::Encode,
+    compression: Option<CompressionEncoding>,
+    max_message_size: usize,
+) -> Result<Bytes, Status> {
+    let mut buf = BytesMut::with_capacity(HEADER_SIZE);
+    buf.put_bytes(0, HEADER_SIZE);
+    codec.encode(item, &mut buf)?;
+
+    let compressed = match compression {
+        Some(encoding) => {
+            let mut compressed = BytesMut::with_capacity(buf.len());
+            compressed.put_bytes(0, HEADER_SIZE);
+            encoding
+                .compress(&buf[HEADER_SIZE..], &mut compressed)
+                .map_err(|err| {
+                    Status::internal(format!("failed to compress {encoding} message: {err}"))
+                })?;
+            buf = compressed;
+            true
+        }
+        None => false,
+    };
+
+    let len = buf.len() - HEADER_SIZE;
+    if len > max_message_size {
+        return Err(Status::out_of_range(format!(
+            "encoded message of {len} bytes exceeds the limit of {max_message_size} bytes"
+        )));
+    }
+    let len = u32::try_from(len).map_err(|_| {
+        Status::resource_exhausted(format!("encoded message of {len} bytes is too large"))
+    })?;
+
+    buf[0] = compressed as u8;
+    buf[1..HEADER_SIZE].copy_from_slice(&len.to_be_bytes());
+    Ok(buf.freeze())
+}
+
+type MessageStream<T> = Pin<Box<dyn Stream<Item = Result<T, Status>> + Send + 'static>>;
+
+pin_project! {
+    /// A [`http_body::Body`] encoding a stream of gRPC messages.
+    ///
+    /// On the server side the body ends with trailers containing
+    /// the [`Status`] of the call, which is an error status in case
+    /// the stream yields an error or a message fails to be encoded.
+    /// On the client side such errors abort the body instead.
+    pub(crate) struct EncodeBody<C: Codec> {
+        source: SyncWrapper<MessageStream<C::Encode>>,
+        codec: C,
+        role: Role,
+        compression: Option<CompressionEncoding>,
+        max_message_size: usize,
+        is_end_stream: bool,
+    }
+}
+
+impl<C: Codec> EncodeBody<C> {
+    pub(crate) fn new<S>(
+        source: S,
+        codec: C,
+        role: Role,
+        compression: Option<CompressionEncoding>,
+        max_message_size: usize,
+    ) -> Self
+    where
+        S: Stream<Item = Result<C::Encode, Status>> + Send + 'static,
+    {
+        Self {
+            source: SyncWrapper::new(Box::pin(source)),
+            codec,
+            role,
+            compression,
+            max_message_size,
+            is_end_stream: false,
+        }
+    }
+}
+
+impl<C: Codec> http_body::Body for EncodeBody<C> {
+    type Data = Bytes;
+    type Error = BoxError;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let this = self.project();
+        if *this.is_end_stream {
+            return Poll::Ready(None);
+        }
+
+        let result = match futures_lite::ready!(this.source.get_mut().as_mut().poll_next(cx)) {
+            Some(Ok(item)) => {
+                encode_message(this.codec, item, *this.compression, *this.max_message_size)
+            }
+            Some(Err(status)) => Err(status),
+            None => {
+                *this.is_end_stream = true;
+                return Poll::Ready(match this.role {
+                    Role::Server => Some(Ok(Frame::trailers(Status::ok().to_header_map()))),
+                    Role::Client => None,
+                });
+            }
+        };
+
+        match result {
+            Ok(buf) => Poll::Ready(Some(Ok(Frame::data(buf)))),
+            Err(status) => {
+                *this.is_end_stream = true;
+                Poll::Ready(Some(match this.role {
+                    Role::Server => Ok(Frame::trailers(status.to_header_map())),
+                    Role::Client => Err(status.into()),
+                }))
+            }
+        }
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.is_end_stream
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dep::http_body_util::BodyExt;
+    use crate::grpc::{GrpcCode, GRPC_MESSAGE, GRPC_STATUS};
+
+    #[derive(Debug, Clone)]
+    struct BytesCodec;
+
+    impl Codec for BytesCodec {
+        type Encode = Bytes;
+        type Decode = Bytes;
+
+        fn encode(&self, item: Self::Encode, dst: &mut BytesMut) -> Result<(), Status> {
+            dst.put(item);
+            Ok(())
+        }
+
+        fn decode(&self, src: Bytes) -> Result<Self::Decode, Status> {
+            Ok(src)
+        }
+    }
+
+    #[test]
+    fn test_encode_message() {
+        let buf = encode_message(&BytesCodec, Bytes::from_static(b"hello"), None, 5).unwrap();
+        assert_eq!(&b"\x00\x00\x00\x00\x05hello"[..], &buf[..]);
+
+        let status =
+            encode_message(&BytesCodec, Bytes::from_static(b"hello"), None, 4).unwrap_err();
+        assert_eq!(GrpcCode::OutOfRange, status.code());
+
+        let buf = encode_message(
+            &BytesCodec,
+            Bytes::from_static(b"hello"),
+            Some(CompressionEncoding::Gzip),
+            usize::MAX,
+        )
+        .unwrap();
+        assert_eq!(1, buf[0]);
+        let len = u32::from_be_bytes(buf[1..HEADER_SIZE].try_into().unwrap()) as usize;
+        assert_eq!(buf.len() - HEADER_SIZE, len);
+    }
+
+    #[tokio::test]
+    async fn test_encode_body_server() {
+        let source = futures_lite::stream::iter([
+            Ok(Bytes::from_static(b"a")),
+            Ok(Bytes::from_static(b"b")),
+        ]);
+        let body = EncodeBody::new(source, BytesCodec, Role::Server, None, usize::MAX);
+        let collected = body.collect().await.unwrap();
+        assert_eq!("0", collected.trailers().unwrap()[GRPC_STATUS]);
+        assert_eq!(
+            &b"\x00\x00\x00\x00\x01a\x00\x00\x00\x00\x01b"[..],
+            &collected.to_bytes()[..]
+        );
+
+        let source = futures_lite::stream::iter([
+            Ok(Bytes::from_static(b"a")),
+            Err(Status::aborted("oops")),
+        ]);
+        let body = EncodeBody::new(source, BytesCodec, Role::Server, None, usize::MAX);
+        let collected = body.collect().await.unwrap();
+        let trailers = collected.trailers().unwrap();
+        assert_eq!("10", trailers[GRPC_STATUS]);
+        assert_eq!("oops", trailers[GRPC_MESSAGE]);
+    }
+
+    #[tokio::test]
+    async fn test_encode_body_client() {
+        let source = futures_lite::stream::iter([Ok(Bytes::from_static(b"a"))]);
+        let body = EncodeBody::new(source, BytesCodec, Role::Client, None, usize::MAX);
+        let collected = body.collect().await.unwrap();
+        assert!(collected.trailers().is_none());
+
+        let source = futures_lite::stream::iter([Err(Status::aborted("oops"))]);
+        let body = EncodeBody::new(source, BytesCodec, Role::Client, None, usize::MAX);
+        assert!(body.collect().await.is_err());
+    }
+}
@@ -0,0 +1,122 @@
+//! Encoding and decoding of (length-prefixed) gRPC messages.
+
+use super::Status;
+use bytes::{Bytes, BytesMut};
+use std::{fmt, marker::PhantomData};
+
+mod encode;
+pub(crate) use encode::{EncodeBody, Role};
+
+mod decode;
+#[doc(inline)]
+pub use decode::Streaming;
+
+/// The size of the header prefixing each gRPC message:
+/// a compressed flag (1 byte) followed by the message length (4 bytes).
+pub(crate) const HEADER_SIZE: usize = 5;
+
+/// The default maximum size of a decoded message, 4MiB.
+pub(crate) const DEFAULT_MAX_RECV_MESSAGE_SIZE: usize = 4 * 1024 * 1024;
+
+/// The default maximum size of an encoded message.
+pub(crate) const DEFAULT_MAX_SEND_MESSAGE_SIZE: usize = u32::MAX as usize;
+
+/// Encodes and decodes the messages of a gRPC call.
+///
+/// A server uses the codec to decode requests and encode responses,
+/// while a client uses it to encode requests and decode responses.
+pub trait Codec: Clone + Send + Sync + 'static {
+    /// The type of the messages encoded by this codec.
+    type Encode: Send + 'static;
+    /// The type of the messages decoded by this codec.
+    type Decode: Send + 'static;
+
+    /// Encode the given message, appending it to `dst`.
+    fn encode(&self, item: Self::Encode, dst: &mut BytesMut) -> Result<(), Status>;
+
+    /// Decode a message from the given (uncompressed) bytes.
+    fn decode(&self, src: Bytes) -> Result<Self::Decode, Status>;
+}
+
+/// A [`Codec`] for protobuf messages, using [`prost`].
+///
+/// `E` is the type of the messages to encode, `D` the type of the messages to decode.
+pub struct ProstCodec<E, D> {
+    _marker: PhantomData<fn(E) -> D>,
+}
+
+impl<E, D> ProstCodec<E, D> {
+    /// Create a new [`ProstCodec`].
+    pub const fn new() -> Self {
+        Self {
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<E, D> Default for ProstCodec<E, D> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<E, D> Clone for ProstCodec<E, D> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<E, D> Copy for ProstCodec<E, D> {}
+
+impl<E, D> fmt::Debug for ProstCodec<E, D> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ProstCodec")
+            .field("encode", &std::any::type_name::<E>())
+            .field("decode", &std::any::type_name::<D>())
+            .finish()
+    }
+}
+
+impl<E, D> Codec for ProstCodec<E, D>
+where
+    E: prost::Message + Send + 'static,
+    D: prost::Message + Default + Send + 'static,
+{
+    type Encode = E;
+    type Decode = D;
+
+    fn encode(&self, item: Self::Encode, dst: &mut BytesMut) -> Result<(), Status> {
+        item.encode(dst)
+            .map_err(|err| Status::internal(format!("failed to encode message: {err}")))
+    }
+
+    fn decode(&self, src: Bytes) -> Result<Self::Decode, Status> {
+        D::decode(src).map_err(|err| Status::internal(format!("failed to decode message: {err}")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    struct Greeting {
+        #[prost(string, tag = "1")]
+        message: String,
+    }
+
+    #[test]
+    fn test_prost_codec_roundtrip() {
+        let codec = ProstCodec::<Greeting, Greeting>::new();
+        let greeting = Greeting {
+            message: "hello".to_owned(),
+        };
+
+        let mut buf = BytesMut::new();
+        codec.encode(greeting.clone(), &mut buf).unwrap();
+        assert_eq!(greeting, codec.decode(buf.freeze()).unwrap());
+
+        let status = codec.decode(Bytes::from_static(b"\xff\xff")).unwrap_err();
+        assert_eq!(crate::grpc::GrpcCode::Internal, status.code());
+    }
+}
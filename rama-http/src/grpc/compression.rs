@@ -0,0 +1,220 @@
+use super::{Status, GRPC_ACCEPT_ENCODING, GRPC_ENCODING};
+use crate::{HeaderMap, HeaderValue};
+use bytes::{BufMut, BytesMut};
+use flate2::{
+    read::{GzDecoder, ZlibDecoder},
+    write::{GzEncoder, ZlibEncoder},
+    Compression,
+};
+use std::{fmt, io};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+/// The compression encodings supported for gRPC messages.
+///
+/// Compression is negotiated per call: the `grpc-encoding` header indicates
+/// the encoding used for the (compressed) messages, while the
+/// `grpc-accept-encoding` header lists the encodings the peer accepts.
+pub enum CompressionEncoding {
+    /// The `gzip` encoding.
+    Gzip,
+    /// The `deflate` (zlib) encoding.
+    Deflate,
+}
+
+impl CompressionEncoding {
+    /// Returns the name of this encoding, as used in the gRPC headers.
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Self::Gzip => "gzip",
+            Self::Deflate => "deflate",
+        }
+    }
+
+    fn from_token(token: &str) -> Option<Self> {
+        let token = token.trim();
+        if token.eq_ignore_ascii_case("gzip") {
+            Some(Self::Gzip)
+        } else if token.eq_ignore_ascii_case("deflate") {
+            Some(Self::Deflate)
+        } else {
+            None
+        }
+    }
+
+    /// Determine the encoding of the messages from the `grpc-encoding` header.
+    ///
+    /// An [`GrpcCode::Unimplemented`] status is returned
+    /// in case the encoding is not one of the accepted encodings.
+    ///
+    /// [`GrpcCode::Unimplemented`]: super::GrpcCode::Unimplemented
+    pub(crate) fn from_encoding_header(
+        headers: &HeaderMap,
+        accepted: &[Self],
+    ) -> Result<Option<Self>, Status> {
+        let Some(value) = headers.get(GRPC_ENCODING) else {
+            return Ok(None);
+        };
+        let value = value.to_str().unwrap_or_default();
+        if value.eq_ignore_ascii_case("identity") {
+            return Ok(None);
+        }
+        match Self::from_token(value) {
+            Some(encoding) if accepted.contains(&encoding) => Ok(Some(encoding)),
+            _ => Err(Status::unimplemented(format!(
+                "message compressed with unsupported encoding: {value}"
+            ))),
+        }
+    }
+
+    /// Select the first of the given encodings accepted by the peer,
+    /// according to its `grpc-accept-encoding` header.
+    pub(crate) fn from_accept_encoding_header(
+        headers: &HeaderMap,
+        encodings: &[Self],
+    ) -> Option<Self> {
+        let accepted: Vec<_> = headers
+            .get_all(GRPC_ACCEPT_ENCODING)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .filter_map(Self::from_token)
+            .collect();
+        encodings
+            .iter()
+            .copied()
+            .find(|encoding| accepted.contains(encoding))
+    }
+
+    /// Create the value of the `grpc-accept-encoding` header for the given encodings.
+    pub(crate) fn accept_encoding_header_value(encodings: &[Self]) -> Option<HeaderValue> {
+        if encodings.is_empty() {
+            return None;
+        }
+        let value = encodings
+            .iter()
+            .map(Self::as_str)
+            .collect::<Vec<_>>()
+            .join(",");
+        HeaderValue::try_from(value).ok()
+    }
+
+    pub(crate) fn into_header_value(self) -> HeaderValue {
+        HeaderValue::from_static(self.as_str())
+    }
+
+    /// Compress the given message, appending it to `dst`.
+    pub(crate) fn compress(self, src: &[u8], dst: &mut BytesMut) -> io::Result<()> {
+        let writer = (&mut *dst).writer();
+        match self {
+            Self::Gzip => {
+                let mut encoder = GzEncoder::new(writer, Compression::default());
+                io::Write::write_all(&mut encoder, src)?;
+                encoder.finish()?;
+            }
+            Self::Deflate => {
+                let mut encoder = ZlibEncoder::new(writer, Compression::default());
+                io::Write::write_all(&mut encoder, src)?;
+                encoder.finish()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Decompress the given message, failing in case
+    /// the decompressed message exceeds the given limit.
+    pub(crate) fn decompress(self, src: &[u8], limit: usize) -> Result<BytesMut, Status> {
+        let mut dst = BytesMut::with_capacity(src.len().saturating_mul(2).min(limit));
+        let mut writer = (&mut dst).writer();
+        // read one byte more than the limit, such that we can detect exceeding it
+        let max = (limit as u64).saturating_add(1);
+        let result = match self {
+            Self::Gzip => io::copy(&mut io::Read::take(GzDecoder::new(src), max), &mut writer),
+            Self::Deflate => io::copy(&mut io::Read::take(ZlibDecoder::new(src), max), &mut writer),
+        };
+        match result {
+            Ok(n) if n > limit as u64 => Err(Status::resource_exhausted(format!(
+                "decompressed message exceeds the limit of {limit} bytes"
+            ))),
+            Ok(_) => Ok(dst),
+            Err(err) => Err(Status::internal(format!(
+                "failed to decompress {self} message: {err}"
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for CompressionEncoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grpc::GrpcCode;
+
+    #[test]
+    fn test_compression_roundtrip() {
+        let msg = b"hello hello hello hello hello hello".repeat(10);
+        for encoding in [CompressionEncoding::Gzip, CompressionEncoding::Deflate] {
+            let mut compressed = BytesMut::new();
+            encoding.compress(&msg, &mut compressed).unwrap();
+            assert!(compressed.len() < msg.len());
+
+            let decompressed = encoding.decompress(&compressed, msg.len()).unwrap();
+            assert_eq!(&msg[..], &decompressed[..]);
+
+            let err = encoding.decompress(&compressed, msg.len() - 1).unwrap_err();
+            assert_eq!(GrpcCode::ResourceExhausted, err.code());
+        }
+    }
+
+    #[test]
+    fn test_encoding_negotiation() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(
+            None,
+            CompressionEncoding::from_encoding_header(&headers, &[]).unwrap()
+        );
+
+        headers.insert(GRPC_ENCODING, HeaderValue::from_static("gzip"));
+        assert_eq!(
+            Some(CompressionEncoding::Gzip),
+            CompressionEncoding::from_encoding_header(&headers, &[CompressionEncoding::Gzip])
+                .unwrap()
+        );
+        let status =
+            CompressionEncoding::from_encoding_header(&headers, &[CompressionEncoding::Deflate])
+                .unwrap_err();
+        assert_eq!(GrpcCode::Unimplemented, status.code());
+
+        headers.insert(
+            GRPC_ACCEPT_ENCODING,
+            HeaderValue::from_static("identity, deflate,gzip"),
+        );
+        assert_eq!(
+            Some(CompressionEncoding::Deflate),
+            CompressionEncoding::from_accept_encoding_header(
+                &headers,
+                &[CompressionEncoding::Deflate, CompressionEncoding::Gzip]
+            )
+        );
+        assert_eq!(
+            None,
+            CompressionEncoding::from_accept_encoding_header(
+                &HeaderMap::new(),
+                &[CompressionEncoding::Gzip]
+            )
+        );
+        assert_eq!(
+            "gzip,deflate",
+            CompressionEncoding::accept_encoding_header_value(&[
+                CompressionEncoding::Gzip,
+                CompressionEncoding::Deflate
+            ])
+            .unwrap()
+        );
+    }
+}
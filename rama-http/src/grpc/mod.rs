@@ -0,0 +1,142 @@
+//! gRPC support for rama, built on top of rama http services and clients.
+//!
+//! It provides:
+//!
+//! - length-prefixed message framing of request and response bodies,
+//!   see [`Streaming`] for the decoding side;
+//! - mapping of a [`Status`] from and to the `grpc-status`, `grpc-message` and
+//!   `grpc-status-details-bin` (trailer) headers;
+//! - deadlines propagated using the `grpc-timeout` header;
+//! - per-message compression negotiated using the `grpc-encoding`
+//!   and `grpc-accept-encoding` headers, see [`CompressionEncoding`];
+//! - a [`Codec`] abstraction with a [`ProstCodec`] for protobuf messages;
+//! - [`server`] endpoints for unary and streaming services, which can be
+//!   routed like any other http service, e.g. using the [`WebService`];
+//! - a [`client`] to call unary and streaming methods
+//!   using any http client service, such as the `HttpClient`;
+//! - a [`web`] layer translating gRPC-Web requests from browsers into gRPC requests.
+//!
+//! gRPC services are regular rama [`Service`]s, serving a gRPC [`Request`]
+//! and returning a gRPC [`Response`] or [`Status`] error.
+//!
+//! # Example
+//!
+//! ```
+//! use rama_core::service::service_fn;
+//! use rama_http::grpc::{self, server::Grpc, ProstCodec, Status};
+//! use rama_http::service::web::WebService;
+//!
+//! #[derive(Clone, PartialEq, prost::Message)]
+//! struct HelloRequest {
+//!     #[prost(string, tag = "1")]
+//!     name: String,
+//! }
+//!
+//! #[derive(Clone, PartialEq, prost::Message)]
+//! struct HelloReply {
+//!     #[prost(string, tag = "1")]
+//!     message: String,
+//! }
+//!
+//! async fn say_hello(
+//!     req: grpc::Request<HelloRequest>,
+//! ) -> Result<grpc::Response<HelloReply>, Status> {
+//!     let name = req.into_inner().name;
+//!     if name.is_empty() {
+//!         return Err(Status::invalid_argument("name is required"));
+//!     }
+//!     Ok(grpc::Response::new(HelloReply {
+//!         message: format!("Hello {name}!"),
+//!     }))
+//! }
+//!
+//! let service = WebService::default().post(
+//!     "/helloworld.Greeter/SayHello",
+//!     Grpc::new(ProstCodec::<HelloReply, HelloRequest>::default()).unary(service_fn(say_hello)),
+//! );
+//! ```
+//!
+//! [`WebService`]: crate::service::web::WebService
+//! [`Service`]: rama_core::Service
+
+use crate::{header::CONTENT_TYPE, HeaderMap, HeaderName, HeaderValue};
+
+mod status;
+#[doc(inline)]
+pub use status::Status;
+
+#[doc(inline)]
+pub use crate::layer::classify::GrpcCode;
+
+mod codec;
+#[doc(inline)]
+pub use codec::{Codec, ProstCodec, Streaming};
+
+mod compression;
+#[doc(inline)]
+pub use compression::CompressionEncoding;
+
+mod request;
+#[doc(inline)]
+pub use request::Request;
+
+mod response;
+#[doc(inline)]
+pub use response::Response;
+
+mod timeout;
+
+pub mod client;
+pub mod server;
+pub mod web;
+
+#[allow(clippy::declare_interior_mutable_const)]
+pub(crate) const GRPC_CONTENT_TYPE: HeaderValue = HeaderValue::from_static("application/grpc");
+
+#[allow(clippy::declare_interior_mutable_const)]
+pub(crate) const GRPC_STATUS: HeaderName = HeaderName::from_static("grpc-status");
+#[allow(clippy::declare_interior_mutable_const)]
+pub(crate) const GRPC_MESSAGE: HeaderName = HeaderName::from_static("grpc-message");
+#[allow(clippy::declare_interior_mutable_const)]
+pub(crate) const GRPC_STATUS_DETAILS: HeaderName =
+    HeaderName::from_static("grpc-status-details-bin");
+#[allow(clippy::declare_interior_mutable_const)]
+pub(crate) const GRPC_TIMEOUT: HeaderName = HeaderName::from_static("grpc-timeout");
+#[allow(clippy::declare_interior_mutable_const)]
+pub(crate) const GRPC_ENCODING: HeaderName = HeaderName::from_static("grpc-encoding");
+#[allow(clippy::declare_interior_mutable_const)]
+pub(crate) const GRPC_ACCEPT_ENCODING: HeaderName = HeaderName::from_static("grpc-accept-encoding");
+
+/// Headers managed by the gRPC protocol itself,
+/// which are never copied from or into the (user) metadata.
+#[allow(clippy::declare_interior_mutable_const)]
+const RESERVED_HEADERS: [HeaderName; 7] = [
+    CONTENT_TYPE,
+    crate::header::TE,
+    GRPC_STATUS,
+    GRPC_MESSAGE,
+    GRPC_STATUS_DETAILS,
+    GRPC_ENCODING,
+    GRPC_ACCEPT_ENCODING,
+];
+
+/// Returns true if the given headers have a gRPC `content-type`,
+/// such as `application/grpc` or `application/grpc+proto`.
+pub(crate) fn is_grpc_content_type(headers: &HeaderMap) -> bool {
+    headers
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| {
+            value.strip_prefix("application/grpc").is_some_and(|rest| {
+                rest.is_empty() || rest.starts_with('+') || rest.starts_with(';')
+            })
+        })
+}
+
+/// Remove the [`RESERVED_HEADERS`] from the given metadata.
+fn sanitize_metadata(mut metadata: HeaderMap) -> HeaderMap {
+    for name in RESERVED_HEADERS {
+        metadata.remove(name);
+    }
+    metadata
+}
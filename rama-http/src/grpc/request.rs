@@ -0,0 +1,105 @@
+use super::{timeout::encode_grpc_timeout, GRPC_TIMEOUT};
+use crate::{dep::http::Extensions, HeaderMap};
+use std::time::Duration;
+
+/// A gRPC request, consisting of metadata and a message.
+///
+/// For streaming calls the message is a stream of messages,
+/// such as [`Streaming`] for requests received by a server.
+///
+/// [`Streaming`]: super::Streaming
+#[derive(Debug)]
+pub struct Request<T> {
+    metadata: HeaderMap,
+    message: T,
+    extensions: Extensions,
+}
+
+impl<T> Request<T> {
+    /// Create a new [`Request`] for the given message, without metadata.
+    pub fn new(message: T) -> Self {
+        Self {
+            metadata: HeaderMap::new(),
+            message,
+            extensions: Extensions::new(),
+        }
+    }
+
+    /// Create a new [`Request`] from its parts.
+    pub fn from_parts(metadata: HeaderMap, extensions: Extensions, message: T) -> Self {
+        Self {
+            metadata,
+            message,
+            extensions,
+        }
+    }
+
+    /// Consume the [`Request`], returning its parts.
+    pub fn into_parts(self) -> (HeaderMap, Extensions, T) {
+        (self.metadata, self.extensions, self.message)
+    }
+
+    /// Returns a reference to the metadata (headers) of this [`Request`].
+    pub fn metadata(&self) -> &HeaderMap {
+        &self.metadata
+    }
+
+    /// Returns a mutable reference to the metadata (headers) of this [`Request`].
+    pub fn metadata_mut(&mut self) -> &mut HeaderMap {
+        &mut self.metadata
+    }
+
+    /// Returns a reference to the http extensions of this [`Request`].
+    pub fn extensions(&self) -> &Extensions {
+        &self.extensions
+    }
+
+    /// Returns a mutable reference to the http extensions of this [`Request`].
+    pub fn extensions_mut(&mut self) -> &mut Extensions {
+        &mut self.extensions
+    }
+
+    /// Returns a reference to the message of this [`Request`].
+    pub fn get_ref(&self) -> &T {
+        &self.message
+    }
+
+    /// Returns a mutable reference to the message of this [`Request`].
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.message
+    }
+
+    /// Consume the [`Request`], returning its message.
+    pub fn into_inner(self) -> T {
+        self.message
+    }
+
+    /// Map the message of this [`Request`], keeping its metadata and extensions.
+    pub fn map<F, U>(self, f: F) -> Request<U>
+    where
+        F: FnOnce(T) -> U,
+    {
+        Request {
+            metadata: self.metadata,
+            message: f(self.message),
+            extensions: self.extensions,
+        }
+    }
+
+    /// Set the deadline of this [`Request`], sent to the server
+    /// in the `grpc-timeout` header and enforced by the [`GrpcClient`].
+    ///
+    /// [`GrpcClient`]: super::client::GrpcClient
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.metadata
+            .insert(GRPC_TIMEOUT, encode_grpc_timeout(timeout));
+    }
+
+    /// Returns the deadline of this [`Request`], if any,
+    /// as set by the client using the `grpc-timeout` header.
+    pub fn timeout(&self) -> Option<Duration> {
+        super::timeout::try_parse_grpc_timeout(&self.metadata)
+            .ok()
+            .flatten()
+    }
+}
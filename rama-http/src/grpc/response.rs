@@ -0,0 +1,86 @@
+use crate::{dep::http::Extensions, HeaderMap};
+
+/// A gRPC response, consisting of metadata and a message.
+///
+/// For streaming calls the message is a stream of messages,
+/// such as [`Streaming`] for responses received by a client.
+///
+/// [`Streaming`]: super::Streaming
+#[derive(Debug)]
+pub struct Response<T> {
+    metadata: HeaderMap,
+    message: T,
+    extensions: Extensions,
+}
+
+impl<T> Response<T> {
+    /// Create a new [`Response`] for the given message, without metadata.
+    pub fn new(message: T) -> Self {
+        Self {
+            metadata: HeaderMap::new(),
+            message,
+            extensions: Extensions::new(),
+        }
+    }
+
+    /// Create a new [`Response`] from its parts.
+    pub fn from_parts(metadata: HeaderMap, extensions: Extensions, message: T) -> Self {
+        Self {
+            metadata,
+            message,
+            extensions,
+        }
+    }
+
+    /// Consume the [`Response`], returning its parts.
+    pub fn into_parts(self) -> (HeaderMap, Extensions, T) {
+        (self.metadata, self.extensions, self.message)
+    }
+
+    /// Returns a reference to the metadata (headers) of this [`Response`].
+    pub fn metadata(&self) -> &HeaderMap {
+        &self.metadata
+    }
+
+    /// Returns a mutable reference to the metadata (headers) of this [`Response`].
+    pub fn metadata_mut(&mut self) -> &mut HeaderMap {
+        &mut self.metadata
+    }
+
+    /// Returns a reference to the http extensions of this [`Response`].
+    pub fn extensions(&self) -> &Extensions {
+        &self.extensions
+    }
+
+    /// Returns a mutable reference to the http extensions of this [`Response`].
+    pub fn extensions_mut(&mut self) -> &mut Extensions {
+        &mut self.extensions
+    }
+
+    /// Returns a reference to the message of this [`Response`].
+    pub fn get_ref(&self) -> &T {
+        &self.message
+    }
+
+    /// Returns a mutable reference to the message of this [`Response`].
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.message
+    }
+
+    /// Consume the [`Response`], returning its message.
+    pub fn into_inner(self) -> T {
+        self.message
+    }
+
+    /// Map the message of this [`Response`], keeping its metadata and extensions.
+    pub fn map<F, U>(self, f: F) -> Response<U>
+    where
+        F: FnOnce(T) -> U,
+    {
+        Response {
+            metadata: self.metadata,
+            message: f(self.message),
+            extensions: self.extensions,
+        }
+    }
+}
@@ -0,0 +1,714 @@
+//! gRPC server support.
+//!
+//! A gRPC method is served by an endpoint created using [`Grpc`],
+//! which translates between http and gRPC for the (gRPC) service it wraps.
+//! Endpoints are regular http [`Service`]s which can be routed using the
+//! path of the method (`/{package}.{service}/{method}`), e.g. using the [`WebService`].
+//!
+//! See the [module docs](super) for an example.
+//!
+//! [`WebService`]: crate::service::web::WebService
+
+use super::codec::{
+    Codec, EncodeBody, Role, Streaming, DEFAULT_MAX_RECV_MESSAGE_SIZE,
+    DEFAULT_MAX_SEND_MESSAGE_SIZE,
+};
+use super::timeout::{
+    deadline_from_timeout, try_parse_grpc_timeout, with_deadline, DeadlineStream,
+};
+use super::{
+    is_grpc_content_type, sanitize_metadata, CompressionEncoding, Request, Response, Status,
+    GRPC_ACCEPT_ENCODING, GRPC_CONTENT_TYPE, GRPC_ENCODING,
+};
+use crate::{header::CONTENT_TYPE, Body, IntoResponse, StatusCode};
+use futures_lite::Stream;
+use rama_core::{Context, Service};
+use std::{convert::Infallible, future::Future};
+use tokio::time::Instant;
+
+/// A gRPC service handling unary calls:
+/// a single request message results in a single response message.
+///
+/// It is implemented for any [`Service`] serving a gRPC [`Request`]
+/// and returning a gRPC [`Response`] or [`Status`] error.
+pub trait UnaryService<State, M>: Send + Sync + 'static {
+    /// The response message type.
+    type Response: Send + 'static;
+
+    /// Serve the given request.
+    fn call(
+        &self,
+        ctx: Context<State>,
+        request: Request<M>,
+    ) -> impl Future<Output = Result<Response<Self::Response>, Status>> + Send + '_;
+}
+
+impl<State, M, R, S> UnaryService<State, M> for S
+where
+    S: Service<State, Request<M>, Response = Response<R>, Error = Status>,
+    R: Send + 'static,
+{
+    type Response = R;
+
+    fn call(
+        &self,
+        ctx: Context<State>,
+        request: Request<M>,
+    ) -> impl Future<Output = Result<Response<Self::Response>, Status>> + Send + '_ {
+        self.serve(ctx, request)
+    }
+}
+
+/// A gRPC service handling server streaming calls:
+/// a single request message results in a stream of response messages.
+///
+/// It is implemented for any [`Service`] serving a gRPC [`Request`]
+/// and returning a gRPC [`Response`] wrapping a stream, or a [`Status`] error.
+pub trait ServerStreamingService<State, M>: Send + Sync + 'static {
+    /// The response message type.
+    type Response: Send + 'static;
+    /// The stream of response messages.
+    type ResponseStream: Stream<Item = Result<Self::Response, Status>> + Send + 'static;
+
+    /// Serve the given request.
+    fn call(
+        &self,
+        ctx: Context<State>,
+        request: Request<M>,
+    ) -> impl Future<Output = Result<Response<Self::ResponseStream>, Status>> + Send + '_;
+}
+
+impl<State, M, R, St, S> ServerStreamingService<State, M> for S
+where
+    S: Service<State, Request<M>, Response = Response<St>, Error = Status>,
+    St: Stream<Item = Result<R, Status>> + Send + 'static,
+    R: Send + 'static,
+{
+    type Response = R;
+    type ResponseStream = St;
+
+    fn call(
+        &self,
+        ctx: Context<State>,
+        request: Request<M>,
+    ) -> impl Future<Output = Result<Response<Self::ResponseStream>, Status>> + Send + '_ {
+        self.serve(ctx, request)
+    }
+}
+
+/// A gRPC service handling client streaming calls:
+/// a stream of request messages results in a single response message.
+///
+/// It is implemented for any [`Service`] serving a gRPC [`Request`] with
+/// a [`Streaming`] message and returning a gRPC [`Response`] or [`Status`] error.
+pub trait ClientStreamingService<State, M>: Send + Sync + 'static {
+    /// The response message type.
+    type Response: Send + 'static;
+
+    /// Serve the given request.
+    fn call(
+        &self,
+        ctx: Context<State>,
+        request: Request<Streaming<M>>,
+    ) -> impl Future<Output = Result<Response<Self::Response>, Status>> + Send + '_;
+}
+
+impl<State, M, R, S> ClientStreamingService<State, M> for S
+where
+    S: Service<State, Request<Streaming<M>>, Response = Response<R>, Error = Status>,
+    R: Send + 'static,
+{
+    type Response = R;
+
+    fn call(
+        &self,
+        ctx: Context<State>,
+        request: Request<Streaming<M>>,
+    ) -> impl Future<Output = Result<Response<Self::Response>, Status>> + Send + '_ {
+        self.serve(ctx, request)
+    }
+}
+
+/// A gRPC service handling bidirectional streaming calls:
+/// a stream of request messages results in a stream of response messages.
+///
+/// It is implemented for any [`Service`] serving a gRPC [`Request`] with a
+/// [`Streaming`] message and returning a gRPC [`Response`] wrapping a stream,
+/// or a [`Status`] error.
+pub trait StreamingService<State, M>: Send + Sync + 'static {
+    /// The response message type.
+    type Response: Send + 'static;
+    /// The stream of response messages.
+    type ResponseStream: Stream<Item = Result<Self::Response, Status>> + Send + 'static;
+
+    /// Serve the given request.
+    fn call(
+        &self,
+        ctx: Context<State>,
+        request: Request<Streaming<M>>,
+    ) -> impl Future<Output = Result<Response<Self::ResponseStream>, Status>> + Send + '_;
+}
+
+impl<State, M, R, St, S> StreamingService<State, M> for S
+where
+    S: Service<State, Request<Streaming<M>>, Response = Response<St>, Error = Status>,
+    St: Stream<Item = Result<R, Status>> + Send + 'static,
+    R: Send + 'static,
+{
+    type Response = R;
+    type ResponseStream = St;
+
+    fn call(
+        &self,
+        ctx: Context<State>,
+        request: Request<Streaming<M>>,
+    ) -> impl Future<Output = Result<Response<Self::ResponseStream>, Status>> + Send + '_ {
+        self.serve(ctx, request)
+    }
+}
+
+#[derive(Debug, Clone)]
+/// Configuration of the gRPC protocol for a server method,
+/// used to create the endpoint serving the method.
+///
+/// The [`Codec`] decodes the request messages and encodes the response messages.
+pub struct Grpc<C> {
+    codec: C,
+    accept_compression: Vec<CompressionEncoding>,
+    send_compression: Vec<CompressionEncoding>,
+    max_decoding_message_size: usize,
+    max_encoding_message_size: usize,
+}
+
+impl<C: Codec> Grpc<C> {
+    /// Create a new [`Grpc`] configuration using the given [`Codec`].
+    pub fn new(codec: C) -> Self {
+        Self {
+            codec,
+            accept_compression: Vec::new(),
+            send_compression: Vec::new(),
+            max_decoding_message_size: DEFAULT_MAX_RECV_MESSAGE_SIZE,
+            max_encoding_message_size: DEFAULT_MAX_SEND_MESSAGE_SIZE,
+        }
+    }
+
+    /// Accept request messages compressed with the given encoding.
+    pub fn accept_compressed(mut self, encoding: CompressionEncoding) -> Self {
+        if !self.accept_compression.contains(&encoding) {
+            self.accept_compression.push(encoding);
+        }
+        self
+    }
+
+    /// Compress response messages with the given encoding,
+    /// in case the client accepts it.
+    ///
+    /// If multiple encodings are configured,
+    /// the first one accepted by the client is used.
+    pub fn send_compressed(mut self, encoding: CompressionEncoding) -> Self {
+        if !self.send_compression.contains(&encoding) {
+            self.send_compression.push(encoding);
+        }
+        self
+    }
+
+    /// Set the maximum size of a (decompressed) request message.
+    ///
+    /// Defaults to 4MiB.
+    pub fn max_decoding_message_size(mut self, limit: usize) -> Self {
+        self.max_decoding_message_size = limit;
+        self
+    }
+
+    /// Set the maximum size of an (encoded) response message.
+    ///
+    /// Defaults to `u32::MAX`, the maximum size of a gRPC message.
+    pub fn max_encoding_message_size(mut self, limit: usize) -> Self {
+        self.max_encoding_message_size = limit;
+        self
+    }
+
+    /// Create an endpoint serving unary calls using the given service.
+    pub fn unary<S>(self, service: S) -> UnaryEndpoint<S, C> {
+        UnaryEndpoint {
+            inner: service,
+            grpc: self,
+        }
+    }
+
+    /// Create an endpoint serving server streaming calls using the given service.
+    pub fn server_streaming<S>(self, service: S) -> ServerStreamingEndpoint<S, C> {
+        ServerStreamingEndpoint {
+            inner: service,
+            grpc: self,
+        }
+    }
+
+    /// Create an endpoint serving client streaming calls using the given service.
+    pub fn client_streaming<S>(self, service: S) -> ClientStreamingEndpoint<S, C> {
+        ClientStreamingEndpoint {
+            inner: service,
+            grpc: self,
+        }
+    }
+
+    /// Create an endpoint serving bidirectional streaming calls using the given service.
+    pub fn streaming<S>(self, service: S) -> StreamingEndpoint<S, C> {
+        StreamingEndpoint {
+            inner: service,
+            grpc: self,
+        }
+    }
+
+    /// Translate the http request into a gRPC request with a stream of messages,
+    /// returning the deadline of the call and the encoding for the response messages.
+    ///
+    /// The deadline applies to the entire call: serving the request
+    /// as well as streaming the request and response messages.
+    #[allow(clippy::result_large_err)]
+    fn decode_request(
+        &self,
+        req: crate::Request,
+    ) -> Result<
+        (
+            Request<Streaming<C::Decode>>,
+            Option<Instant>,
+            Option<CompressionEncoding>,
+        ),
+        crate::Response,
+    > {
+        if !is_grpc_content_type(req.headers()) {
+            return Err(StatusCode::UNSUPPORTED_MEDIA_TYPE.into_response());
+        }
+        let request_encoding =
+            CompressionEncoding::from_encoding_header(req.headers(), &self.accept_compression)
+                .map_err(|status| self.status_response(status))?;
+        let response_encoding =
+            CompressionEncoding::from_accept_encoding_header(req.headers(), &self.send_compression);
+        let deadline = deadline_from_timeout(
+            try_parse_grpc_timeout(req.headers()).map_err(|status| self.status_response(status))?,
+        );
+
+        let (parts, body) = req.into_parts();
+        let messages = Streaming::new(
+            body,
+            self.codec.clone(),
+            Role::Server,
+            request_encoding,
+            self.max_decoding_message_size,
+        )
+        .with_deadline(deadline);
+        Ok((
+            Request::from_parts(parts.headers, parts.extensions, messages),
+            deadline,
+            response_encoding,
+        ))
+    }
+
+    /// Translate the gRPC response with a stream of messages into an http response,
+    /// ending the stream with a [`GrpcCode::DeadlineExceeded`] status once the deadline expires.
+    ///
+    /// [`GrpcCode::DeadlineExceeded`]: super::GrpcCode::DeadlineExceeded
+    fn encode_response<St>(
+        &self,
+        response: Response<St>,
+        encoding: Option<CompressionEncoding>,
+        deadline: Option<Instant>,
+    ) -> crate::Response
+    where
+        St: Stream<Item = Result<C::Encode, Status>> + Send + 'static,
+    {
+        let (metadata, extensions, messages) = response.into_parts();
+        let body = EncodeBody::new(
+            DeadlineStream::new(messages, deadline),
+            self.codec.clone(),
+            Role::Server,
+            encoding,
+            self.max_encoding_message_size,
+        );
+
+        let mut res = crate::Response::new(Body::new(body));
+        *res.extensions_mut() = extensions;
+        *res.headers_mut() = sanitize_metadata(metadata);
+        res.headers_mut().insert(CONTENT_TYPE, GRPC_CONTENT_TYPE);
+        if let Some(encoding) = encoding {
+            res.headers_mut()
+                .insert(GRPC_ENCODING, encoding.into_header_value());
+        }
+        self.insert_accept_encoding(&mut res);
+        res
+    }
+
+    /// Create the (trailers-only) response for the given status.
+    fn status_response(&self, status: Status) -> crate::Response {
+        let mut res = status.into_response();
+        self.insert_accept_encoding(&mut res);
+        res
+    }
+
+    fn insert_accept_encoding(&self, res: &mut crate::Response) {
+        if let Some(value) =
+            CompressionEncoding::accept_encoding_header_value(&self.accept_compression)
+        {
+            res.headers_mut().insert(GRPC_ACCEPT_ENCODING, value);
+        }
+    }
+}
+
+/// Read the single message of a unary or server streaming request.
+async fn single_message<M>(request: Request<Streaming<M>>) -> Result<Request<M>, Status> {
+    let (metadata, extensions, mut messages) = request.into_parts();
+    let message = messages
+        .message()
+        .await?
+        .ok_or_else(|| Status::internal("missing request message"))?;
+    Ok(Request::from_parts(metadata, extensions, message))
+}
+
+macro_rules! define_endpoint {
+    ($(#[$doc:meta])* $name:ident) => {
+        $(#[$doc])*
+        pub struct $name<S, C> {
+            inner: S,
+            grpc: Grpc<C>,
+        }
+
+        impl<S: std::fmt::Debug, C: std::fmt::Debug> std::fmt::Debug for $name<S, C> {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                f.debug_struct(stringify!($name))
+                    .field("inner", &self.inner)
+                    .field("grpc", &self.grpc)
+                    .finish()
+            }
+        }
+
+        impl<S: Clone, C: Clone> Clone for $name<S, C> {
+            fn clone(&self) -> Self {
+                Self {
+                    inner: self.inner.clone(),
+                    grpc: self.grpc.clone(),
+                }
+            }
+        }
+    };
+}
+
+define_endpoint! {
+    /// An http endpoint serving unary gRPC calls using an [`UnaryService`].
+    ///
+    /// Created using [`Grpc::unary`].
+    UnaryEndpoint
+}
+
+define_endpoint! {
+    /// An http endpoint serving server streaming gRPC calls using a [`ServerStreamingService`].
+    ///
+    /// Created using [`Grpc::server_streaming`].
+    ServerStreamingEndpoint
+}
+
+define_endpoint! {
+    /// An http endpoint serving client streaming gRPC calls using a [`ClientStreamingService`].
+    ///
+    /// Created using [`Grpc::client_streaming`].
+    ClientStreamingEndpoint
+}
+
+define_endpoint! {
+    /// An http endpoint serving bidirectional streaming gRPC calls using a [`StreamingService`].
+    ///
+    /// Created using [`Grpc::streaming`].
+    StreamingEndpoint
+}
+
+impl<State, S, C> Service<State, crate::Request> for UnaryEndpoint<S, C>
+where
+    State: Clone + Send + Sync + 'static,
+    C: Codec,
+    S: UnaryService<State, C::Decode, Response = C::Encode>,
+{
+    type Response = crate::Response;
+    type Error = Infallible;
+
+    async fn serve(
+        &self,
+        ctx: Context<State>,
+        req: crate::Request,
+    ) -> Result<Self::Response, Self::Error> {
+        let (request, deadline, encoding) = match self.grpc.decode_request(req) {
+            Ok(parts) => parts,
+            Err(res) => return Ok(res),
+        };
+        let result = with_deadline(deadline, async {
+            let request = single_message(request).await?;
+            self.inner.call(ctx, request).await
+        })
+        .await;
+        Ok(match result {
+            Ok(response) => self.grpc.encode_response(
+                response.map(|message| futures_lite::stream::once(Ok(message))),
+                encoding,
+                deadline,
+            ),
+            Err(status) => self.grpc.status_response(status),
+        })
+    }
+}
+
+impl<State, S, C> Service<State, crate::Request> for ServerStreamingEndpoint<S, C>
+where
+    State: Clone + Send + Sync + 'static,
+    C: Codec,
+    S: ServerStreamingService<State, C::Decode, Response = C::Encode>,
+{
+    type Response = crate::Response;
+    type Error = Infallible;
+
+    async fn serve(
+        &self,
+        ctx: Context<State>,
+        req: crate::Request,
+    ) -> Result<Self::Response, Self::Error> {
+        let (request, deadline, encoding) = match self.grpc.decode_request(req) {
+            Ok(parts) => parts,
+            Err(res) => return Ok(res),
+        };
+        let result = with_deadline(deadline, async {
+            let request = single_message(request).await?;
+            self.inner.call(ctx, request).await
+        })
+        .await;
+        Ok(match result {
+            Ok(response) => self.grpc.encode_response(response, encoding, deadline),
+            Err(status) => self.grpc.status_response(status),
+        })
+    }
+}
+
+impl<State, S, C> Service<State, crate::Request> for ClientStreamingEndpoint<S, C>
+where
+    State: Clone + Send + Sync + 'static,
+    C: Codec,
+    S: ClientStreamingService<State, C::Decode, Response = C::Encode>,
+{
+    type Response = crate::Response;
+    type Error = Infallible;
+
+    async fn serve(
+        &self,
+        ctx: Context<State>,
+        req: crate::Request,
+    ) -> Result<Self::Response, Self::Error> {
+        let (request, deadline, encoding) = match self.grpc.decode_request(req) {
+            Ok(parts) => parts,
+            Err(res) => return Ok(res),
+        };
+        let result = with_deadline(deadline, self.inner.call(ctx, request)).await;
+        Ok(match result {
+            Ok(response) => self.grpc.encode_response(
+                response.map(|message| futures_lite::stream::once(Ok(message))),
+                encoding,
+                deadline,
+            ),
+            Err(status) => self.grpc.status_response(status),
+        })
+    }
+}
+
+impl<State, S, C> Service<State, crate::Request> for StreamingEndpoint<S, C>
+where
+    State: Clone + Send + Sync + 'static,
+    C: Codec,
+    S: StreamingService<State, C::Decode, Response = C::Encode>,
+{
+    type Response = crate::Response;
+    type Error = Infallible;
+
+    async fn serve(
+        &self,
+        ctx: Context<State>,
+        req: crate::Request,
+    ) -> Result<Self::Response, Self::Error> {
+        let (request, deadline, encoding) = match self.grpc.decode_request(req) {
+            Ok(parts) => parts,
+            Err(res) => return Ok(res),
+        };
+        let result = with_deadline(deadline, self.inner.call(ctx, request)).await;
+        Ok(match result {
+            Ok(response) => self.grpc.encode_response(response, encoding, deadline),
+            Err(status) => self.grpc.status_response(status),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dep::http_body_util::BodyExt;
+    use crate::grpc::{GrpcCode, ProstCodec, GRPC_STATUS, GRPC_TIMEOUT};
+    use crate::{HeaderMap, HeaderValue, Method};
+    use bytes::{BufMut, Bytes, BytesMut};
+    use futures_lite::StreamExt;
+    use rama_core::service::service_fn;
+    use std::time::Duration;
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    struct Number {
+        #[prost(int64, tag = "1")]
+        value: i64,
+    }
+
+    type NumberCodec = ProstCodec<Number, Number>;
+
+    fn encode_numbers(values: impl IntoIterator<Item = i64>) -> Bytes {
+        let mut buf = BytesMut::new();
+        for value in values {
+            let msg = prost::Message::encode_to_vec(&Number { value });
+            buf.put_u8(0);
+            buf.put_u32(msg.len() as u32);
+            buf.put_slice(&msg);
+        }
+        buf.freeze()
+    }
+
+    fn decode_numbers(mut buf: Bytes) -> Vec<i64> {
+        let mut values = Vec::new();
+        while !buf.is_empty() {
+            let len = u32::from_be_bytes(buf[1..5].try_into().unwrap()) as usize;
+            let msg = buf.split_to(5 + len).split_off(5);
+            let number: Number = prost::Message::decode(msg).unwrap();
+            values.push(number.value);
+        }
+        values
+    }
+
+    fn grpc_request(body: impl Into<Body>) -> crate::Request {
+        crate::Request::builder()
+            .method(Method::POST)
+            .uri("/test.Math/Call")
+            .header(CONTENT_TYPE, GRPC_CONTENT_TYPE)
+            .body(body.into())
+            .unwrap()
+    }
+
+    /// Collect the messages and trailers of the given response,
+    /// which are the headers for trailers-only responses.
+    async fn collect_response(res: crate::Response) -> (Vec<i64>, HeaderMap) {
+        assert_eq!(StatusCode::OK, res.status());
+        let (parts, body) = res.into_parts();
+        let collected = body.collect().await.unwrap();
+        let trailers = collected.trailers().cloned();
+        let values = decode_numbers(collected.to_bytes());
+        (values, trailers.unwrap_or(parts.headers))
+    }
+
+    fn grpc_code(headers: &HeaderMap) -> GrpcCode {
+        Status::from_header_map(headers).unwrap().code()
+    }
+
+    async fn double(req: Request<Number>) -> Result<Response<Number>, Status> {
+        Ok(Response::new(Number {
+            value: req.into_inner().value * 2,
+        }))
+    }
+
+    async fn count_forever(
+        req: Request<Number>,
+    ) -> Result<Response<impl Stream<Item = Result<Number, Status>>>, Status> {
+        let value = req.into_inner().value;
+        Ok(Response::new(futures_lite::stream::unfold(
+            value,
+            |value| async move {
+                if value > 1 {
+                    tokio::time::sleep(Duration::from_secs(10)).await;
+                }
+                Some((Ok(Number { value }), value + 1))
+            },
+        )))
+    }
+
+    async fn sum(req: Request<Streaming<Number>>) -> Result<Response<Number>, Status> {
+        let mut messages = req.into_inner();
+        let mut value = 0;
+        while let Some(number) = messages.message().await? {
+            value += number.value;
+        }
+        Ok(Response::new(Number { value }))
+    }
+
+    #[tokio::test]
+    async fn test_unary_endpoint() {
+        let endpoint = Grpc::new(NumberCodec::new()).unary(service_fn(double));
+
+        let res = endpoint
+            .serve(Context::default(), grpc_request(encode_numbers([21])))
+            .await
+            .unwrap();
+        assert_eq!(GRPC_CONTENT_TYPE, res.headers()[CONTENT_TYPE]);
+        let (values, trailers) = collect_response(res).await;
+        assert_eq!(vec![42], values);
+        assert_eq!(GrpcCode::Ok, grpc_code(&trailers));
+
+        let res = endpoint
+            .serve(Context::default(), grpc_request(Body::empty()))
+            .await
+            .unwrap();
+        let (values, trailers) = collect_response(res).await;
+        assert!(values.is_empty());
+        assert_eq!(GrpcCode::Internal, grpc_code(&trailers));
+    }
+
+    #[tokio::test]
+    async fn test_endpoint_rejects_invalid_requests() {
+        let endpoint = Grpc::new(NumberCodec::new())
+            .accept_compressed(CompressionEncoding::Gzip)
+            .unary(service_fn(double));
+
+        let mut req = grpc_request(encode_numbers([1]));
+        req.headers_mut()
+            .insert(GRPC_TIMEOUT, HeaderValue::from_static("soon"));
+        let res = endpoint.serve(Context::default(), req).await.unwrap();
+        let (_, trailers) = collect_response(res).await;
+        assert_eq!(GrpcCode::InvalidArgument, grpc_code(&trailers));
+
+        let mut req = grpc_request(encode_numbers([1]));
+        req.headers_mut()
+            .insert(GRPC_ENCODING, HeaderValue::from_static("snappy"));
+        let res = endpoint.serve(Context::default(), req).await.unwrap();
+        assert_eq!("gzip", res.headers()[GRPC_ACCEPT_ENCODING]);
+        let (_, trailers) = collect_response(res).await;
+        assert_eq!(GrpcCode::Unimplemented, grpc_code(&trailers));
+    }
+
+    #[tokio::test]
+    async fn test_deadline_applies_to_response_stream() {
+        let endpoint = Grpc::new(NumberCodec::new()).server_streaming(service_fn(count_forever));
+
+        let mut req = grpc_request(encode_numbers([1]));
+        req.headers_mut()
+            .insert(GRPC_TIMEOUT, HeaderValue::from_static("50m"));
+        let res = endpoint.serve(Context::default(), req).await.unwrap();
+        let (values, trailers) = collect_response(res).await;
+        assert_eq!(vec![1], values);
+        assert_eq!(GrpcCode::DeadlineExceeded, grpc_code(&trailers));
+        assert_eq!("4", trailers[GRPC_STATUS]);
+    }
+
+    #[tokio::test]
+    async fn test_deadline_applies_to_request_stream() {
+        let endpoint = Grpc::new(NumberCodec::new()).client_streaming(service_fn(sum));
+
+        // a request stream which never ends
+        let body = Body::from_stream(
+            futures_lite::stream::once(Ok::<_, Infallible>(encode_numbers([1, 2])))
+                .chain(futures_lite::stream::pending()),
+        );
+        let mut req = grpc_request(body);
+        req.headers_mut()
+            .insert(GRPC_TIMEOUT, HeaderValue::from_static("50m"));
+        let res = endpoint.serve(Context::default(), req).await.unwrap();
+        let (values, trailers) = collect_response(res).await;
+        assert!(values.is_empty());
+        assert_eq!(GrpcCode::DeadlineExceeded, grpc_code(&trailers));
+    }
+}
@@ -0,0 +1,334 @@
+use super::{GrpcCode, GRPC_CONTENT_TYPE, GRPC_MESSAGE, GRPC_STATUS, GRPC_STATUS_DETAILS};
+use crate::{header::CONTENT_TYPE, HeaderMap, HeaderValue, IntoResponse, Response, StatusCode};
+use base64::Engine as _;
+use bytes::Bytes;
+use percent_encoding::{percent_decode, percent_encode, AsciiSet, CONTROLS};
+use rama_core::error::BoxError;
+use std::fmt;
+
+/// Characters percent-encoded in the `grpc-message` header,
+/// as defined by the gRPC over HTTP2 protocol.
+const GRPC_MESSAGE_ENCODE_SET: &AsciiSet = &CONTROLS.add(b'%');
+
+const BASE64: base64::engine::GeneralPurpose = base64::engine::general_purpose::STANDARD_NO_PAD;
+
+/// A gRPC status, describing the result of a gRPC call.
+///
+/// It is returned by gRPC services in case of failure,
+/// and is sent to the client in the `grpc-status`, `grpc-message`
+/// and `grpc-status-details-bin` (trailer) headers.
+#[derive(Clone)]
+pub struct Status {
+    code: GrpcCode,
+    message: String,
+    details: Bytes,
+    // boxed to keep `Result<T, Status>` small
+    metadata: Box<HeaderMap>,
+}
+
+macro_rules! status_constructors {
+    ($($(#[$doc:meta])* $name:ident => $code:ident,)+) => {
+        $(
+            $(#[$doc])*
+            pub fn $name(message: impl Into<String>) -> Self {
+                Self::new(GrpcCode::$code, message)
+            }
+        )+
+    };
+}
+
+impl Status {
+    /// Create a new [`Status`] with the given code and message.
+    pub fn new(code: GrpcCode, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            details: Bytes::new(),
+            metadata: Box::default(),
+        }
+    }
+
+    /// Create a new [`Status`] indicating success.
+    pub fn ok() -> Self {
+        Self::new(GrpcCode::Ok, String::new())
+    }
+
+    status_constructors! {
+        /// Create a new [`Status`] with [`GrpcCode::Cancelled`].
+        cancelled => Cancelled,
+        /// Create a new [`Status`] with [`GrpcCode::Unknown`].
+        unknown => Unknown,
+        /// Create a new [`Status`] with [`GrpcCode::InvalidArgument`].
+        invalid_argument => InvalidArgument,
+        /// Create a new [`Status`] with [`GrpcCode::DeadlineExceeded`].
+        deadline_exceeded => DeadlineExceeded,
+        /// Create a new [`Status`] with [`GrpcCode::NotFound`].
+        not_found => NotFound,
+        /// Create a new [`Status`] with [`GrpcCode::AlreadyExists`].
+        already_exists => AlreadyExists,
+        /// Create a new [`Status`] with [`GrpcCode::PermissionDenied`].
+        permission_denied => PermissionDenied,
+        /// Create a new [`Status`] with [`GrpcCode::ResourceExhausted`].
+        resource_exhausted => ResourceExhausted,
+        /// Create a new [`Status`] with [`GrpcCode::FailedPrecondition`].
+        failed_precondition => FailedPrecondition,
+        /// Create a new [`Status`] with [`GrpcCode::Aborted`].
+        aborted => Aborted,
+        /// Create a new [`Status`] with [`GrpcCode::OutOfRange`].
+        out_of_range => OutOfRange,
+        /// Create a new [`Status`] with [`GrpcCode::Unimplemented`].
+        unimplemented => Unimplemented,
+        /// Create a new [`Status`] with [`GrpcCode::Internal`].
+        internal => Internal,
+        /// Create a new [`Status`] with [`GrpcCode::Unavailable`].
+        unavailable => Unavailable,
+        /// Create a new [`Status`] with [`GrpcCode::DataLoss`].
+        data_loss => DataLoss,
+        /// Create a new [`Status`] with [`GrpcCode::Unauthenticated`].
+        unauthenticated => Unauthenticated,
+    }
+
+    /// Create a [`Status`] from the given error.
+    ///
+    /// In case the error is (or wraps) a [`Status`] it is returned as is,
+    /// otherwise a [`GrpcCode::Unknown`] status is created using the error's message.
+    pub fn from_error(err: BoxError) -> Self {
+        match err.downcast::<Self>() {
+            Ok(status) => *status,
+            Err(err) => {
+                let mut source = err.source();
+                while let Some(err) = source {
+                    if let Some(status) = err.downcast_ref::<Self>() {
+                        return status.clone();
+                    }
+                    source = err.source();
+                }
+                Self::unknown(err.to_string())
+            }
+        }
+    }
+
+    /// Attach the given binary details to this [`Status`],
+    /// sent in the `grpc-status-details-bin` header.
+    ///
+    /// These are typically an encoded `google.rpc.Status` protobuf message.
+    pub fn with_details(mut self, details: impl Into<Bytes>) -> Self {
+        self.details = details.into();
+        self
+    }
+
+    /// Attach the given metadata to this [`Status`],
+    /// sent alongside the status headers.
+    pub fn with_metadata(mut self, metadata: HeaderMap) -> Self {
+        *self.metadata = metadata;
+        self
+    }
+
+    /// Returns the [`GrpcCode`] of this [`Status`].
+    pub fn code(&self) -> GrpcCode {
+        self.code
+    }
+
+    /// Returns the message of this [`Status`].
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// Returns the binary details of this [`Status`].
+    pub fn details(&self) -> &[u8] {
+        &self.details
+    }
+
+    /// Returns a reference to the metadata of this [`Status`].
+    pub fn metadata(&self) -> &HeaderMap {
+        &self.metadata
+    }
+
+    /// Returns a mutable reference to the metadata of this [`Status`].
+    pub fn metadata_mut(&mut self) -> &mut HeaderMap {
+        &mut self.metadata
+    }
+
+    /// Returns true if this [`Status`] indicates success.
+    pub fn is_ok(&self) -> bool {
+        self.code == GrpcCode::Ok
+    }
+
+    /// Try to parse a [`Status`] from the given (trailer) headers,
+    /// returning `None` in case no `grpc-status` header is present.
+    ///
+    /// The remaining headers are kept as the metadata of the status.
+    pub fn from_header_map(headers: &HeaderMap) -> Option<Self> {
+        let code = headers.get(GRPC_STATUS)?;
+        let code = match code.to_str().ok().and_then(|code| code.parse::<i32>().ok()) {
+            Some(code) => GrpcCode::from_i32(code),
+            None => {
+                return Some(Self::unknown(format!(
+                    "invalid grpc-status header value: {code:?}"
+                )))
+            }
+        };
+        let message = headers
+            .get(GRPC_MESSAGE)
+            .map(|value| {
+                percent_decode(value.as_bytes())
+                    .decode_utf8_lossy()
+                    .into_owned()
+            })
+            .unwrap_or_default();
+        let details = headers
+            .get(GRPC_STATUS_DETAILS)
+            .and_then(|value| {
+                // padding is optional for binary header values
+                let value = value.as_bytes();
+                let end = value.iter().rposition(|b| *b != b'=').map_or(0, |i| i + 1);
+                BASE64.decode(&value[..end]).ok()
+            })
+            .map(Bytes::from)
+            .unwrap_or_default();
+
+        let mut metadata = headers.clone();
+        metadata.remove(GRPC_STATUS);
+        metadata.remove(GRPC_MESSAGE);
+        metadata.remove(GRPC_STATUS_DETAILS);
+
+        Some(Self {
+            code,
+            message,
+            details,
+            metadata: Box::new(metadata),
+        })
+    }
+
+    /// Add the headers describing this [`Status`], including its metadata,
+    /// to the given (trailer) headers.
+    pub fn add_header(&self, headers: &mut HeaderMap) {
+        headers.extend(super::sanitize_metadata(self.metadata.as_ref().clone()));
+        headers.insert(GRPC_STATUS, HeaderValue::from(self.code.as_i32()));
+        if !self.message.is_empty() {
+            let message =
+                percent_encode(self.message.as_bytes(), GRPC_MESSAGE_ENCODE_SET).to_string();
+            if let Ok(value) = HeaderValue::try_from(message) {
+                headers.insert(GRPC_MESSAGE, value);
+            }
+        }
+        if !self.details.is_empty() {
+            if let Ok(value) = HeaderValue::try_from(BASE64.encode(&self.details)) {
+                headers.insert(GRPC_STATUS_DETAILS, value);
+            }
+        }
+    }
+
+    /// Returns the headers describing this [`Status`], including its metadata.
+    pub fn to_header_map(&self) -> HeaderMap {
+        let mut headers = HeaderMap::with_capacity(3 + self.metadata.len());
+        self.add_header(&mut headers);
+        headers
+    }
+}
+
+impl fmt::Debug for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut builder = f.debug_struct("Status");
+        builder.field("code", &self.code);
+        if !self.message.is_empty() {
+            builder.field("message", &self.message);
+        }
+        if !self.details.is_empty() {
+            builder.field("details", &self.details);
+        }
+        if !self.metadata.is_empty() {
+            builder.field("metadata", &self.metadata);
+        }
+        builder.finish()
+    }
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "grpc status: {:?} ({}), message: {:?}",
+            self.code,
+            self.code.as_i32(),
+            self.message
+        )
+    }
+}
+
+impl std::error::Error for Status {}
+
+impl IntoResponse for Status {
+    /// Create a "trailers-only" gRPC response,
+    /// which carries the status in its headers and has an empty body.
+    fn into_response(self) -> Response {
+        let mut res = StatusCode::OK.into_response();
+        res.headers_mut().insert(CONTENT_TYPE, GRPC_CONTENT_TYPE);
+        self.add_header(res.headers_mut());
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status_header_roundtrip() {
+        let mut metadata = HeaderMap::new();
+        metadata.insert("x-request-id", HeaderValue::from_static("42"));
+        let status = Status::not_found("user 'foo' not found: 100% sure\nbye ✨")
+            .with_details(&b"\x00\x01details"[..])
+            .with_metadata(metadata);
+
+        let headers = status.to_header_map();
+        assert_eq!("5", headers[GRPC_STATUS]);
+        assert_eq!(
+            "user 'foo' not found: 100%25 sure%0Abye %E2%9C%A8",
+            headers[GRPC_MESSAGE]
+        );
+        assert_eq!("42", headers["x-request-id"]);
+
+        let parsed = Status::from_header_map(&headers).unwrap();
+        assert_eq!(GrpcCode::NotFound, parsed.code());
+        assert_eq!(status.message(), parsed.message());
+        assert_eq!(status.details(), parsed.details());
+        assert_eq!("42", parsed.metadata()["x-request-id"]);
+        assert!(!parsed.metadata().contains_key(GRPC_STATUS));
+    }
+
+    #[test]
+    fn test_status_from_header_map() {
+        assert!(Status::from_header_map(&HeaderMap::new()).is_none());
+
+        let mut headers = HeaderMap::new();
+        headers.insert(GRPC_STATUS, HeaderValue::from_static("0"));
+        assert!(Status::from_header_map(&headers).unwrap().is_ok());
+
+        headers.insert(GRPC_STATUS, HeaderValue::from_static("foo"));
+        assert_eq!(
+            GrpcCode::Unknown,
+            Status::from_header_map(&headers).unwrap().code()
+        );
+    }
+
+    #[test]
+    fn test_status_from_error() {
+        let status = Status::from_error(Status::unavailable("try again").into());
+        assert_eq!(GrpcCode::Unavailable, status.code());
+        assert_eq!("try again", status.message());
+
+        let status = Status::from_error("boom".into());
+        assert_eq!(GrpcCode::Unknown, status.code());
+        assert_eq!("boom", status.message());
+    }
+
+    #[test]
+    fn test_status_into_response() {
+        let res = Status::permission_denied("nope").into_response();
+        assert_eq!(StatusCode::OK, res.status());
+        assert_eq!(GRPC_CONTENT_TYPE, res.headers()[CONTENT_TYPE]);
+        assert_eq!("7", res.headers()[GRPC_STATUS]);
+        assert_eq!("nope", res.headers()[GRPC_MESSAGE]);
+    }
+}
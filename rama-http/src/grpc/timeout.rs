@@ -0,0 +1,251 @@
+//! Parsing and encoding of the `grpc-timeout` header.
+
+use super::{Status, GRPC_TIMEOUT};
+use crate::{HeaderMap, HeaderValue};
+use futures_lite::Stream;
+use pin_project_lite::pin_project;
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+use tokio::time::{Instant, Sleep};
+
+/// The maximum number of digits allowed in a `grpc-timeout` value.
+const MAX_TIMEOUT_DIGITS: usize = 8;
+
+/// Parse the deadline of a call from the `grpc-timeout` header, if present.
+pub(super) fn try_parse_grpc_timeout(headers: &HeaderMap) -> Result<Option<Duration>, Status> {
+    let Some(value) = headers.get(GRPC_TIMEOUT) else {
+        return Ok(None);
+    };
+    parse_grpc_timeout_value(value.as_bytes())
+        .map(Some)
+        .ok_or_else(|| Status::invalid_argument(format!("invalid grpc-timeout header: {value:?}")))
+}
+
+fn parse_grpc_timeout_value(value: &[u8]) -> Option<Duration> {
+    let (unit, digits) = value.split_last()?;
+    if digits.is_empty()
+        || digits.len() > MAX_TIMEOUT_DIGITS
+        || !digits.iter().all(u8::is_ascii_digit)
+    {
+        return None;
+    }
+    let value: u64 = std::str::from_utf8(digits).ok()?.parse().ok()?;
+    let duration = match unit {
+        b'H' => Duration::from_secs(value * 60 * 60),
+        b'M' => Duration::from_secs(value * 60),
+        b'S' => Duration::from_secs(value),
+        b'm' => Duration::from_millis(value),
+        b'u' => Duration::from_micros(value),
+        b'n' => Duration::from_nanos(value),
+        _ => return None,
+    };
+    Some(duration)
+}
+
+/// Encode the given deadline as a `grpc-timeout` header value,
+/// using the most precise unit which fits within the allowed number of digits.
+pub(super) fn encode_grpc_timeout(timeout: Duration) -> HeaderValue {
+    const MAX_VALUE: u128 = 10u128.pow(MAX_TIMEOUT_DIGITS as u32) - 1;
+
+    let nanos = timeout.as_nanos();
+    let candidates = [
+        (nanos, 'n'),
+        (nanos / 1_000, 'u'),
+        (nanos / 1_000_000, 'm'),
+        (nanos / 1_000_000_000, 'S'),
+        (nanos / 60_000_000_000, 'M'),
+        (nanos / 3_600_000_000_000, 'H'),
+    ];
+    let (value, unit) = candidates
+        .into_iter()
+        .find(|(value, _)| *value <= MAX_VALUE)
+        .unwrap_or((MAX_VALUE, 'H'));
+
+    HeaderValue::try_from(format!("{value}{unit}")).expect("valid grpc-timeout header value")
+}
+
+/// Compute the deadline of a call starting now from its timeout, if any.
+///
+/// Timeouts too large to be represented are treated as no deadline at all.
+pub(super) fn deadline_from_timeout(timeout: Option<Duration>) -> Option<Instant> {
+    timeout.and_then(|timeout| Instant::now().checked_add(timeout))
+}
+
+fn deadline_exceeded() -> Status {
+    Status::deadline_exceeded("deadline exceeded")
+}
+
+/// Run the given future within the given deadline, if any,
+/// failing with a [`GrpcCode::DeadlineExceeded`] status once it expires.
+///
+/// [`GrpcCode::DeadlineExceeded`]: super::GrpcCode::DeadlineExceeded
+pub(super) async fn with_deadline<F, T>(deadline: Option<Instant>, fut: F) -> Result<T, Status>
+where
+    F: Future<Output = Result<T, Status>>,
+{
+    match deadline {
+        Some(deadline) => tokio::time::timeout_at(deadline, fut)
+            .await
+            .unwrap_or_else(|_| Err(deadline_exceeded())),
+        None => fut.await,
+    }
+}
+
+/// A timer tracking the deadline of a call while its messages are streamed.
+pub(crate) struct DeadlineTimer(Option<Pin<Box<Sleep>>>);
+
+impl DeadlineTimer {
+    pub(crate) fn new(deadline: Option<Instant>) -> Self {
+        Self(deadline.map(|deadline| Box::pin(tokio::time::sleep_until(deadline))))
+    }
+
+    /// Poll whether the deadline expired,
+    /// returning the [`GrpcCode::DeadlineExceeded`] status to fail the call with if so.
+    ///
+    /// [`GrpcCode::DeadlineExceeded`]: super::GrpcCode::DeadlineExceeded
+    pub(crate) fn poll_expired(&mut self, cx: &mut Context<'_>) -> Poll<Status> {
+        match self.0.as_mut() {
+            Some(sleep) => sleep.as_mut().poll(cx).map(|()| deadline_exceeded()),
+            None => Poll::Pending,
+        }
+    }
+}
+
+impl std::fmt::Debug for DeadlineTimer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("DeadlineTimer")
+            .field(&self.0.as_ref().map(|sleep| sleep.deadline()))
+            .finish()
+    }
+}
+
+pin_project! {
+    /// A stream of messages which fails with a [`GrpcCode::DeadlineExceeded`]
+    /// status, and ends, once the deadline of the call expires.
+    ///
+    /// [`GrpcCode::DeadlineExceeded`]: super::GrpcCode::DeadlineExceeded
+    pub(super) struct DeadlineStream<S> {
+        #[pin]
+        inner: S,
+        timer: DeadlineTimer,
+        is_done: bool,
+    }
+}
+
+impl<S> DeadlineStream<S> {
+    pub(super) fn new(inner: S, deadline: Option<Instant>) -> Self {
+        Self {
+            inner,
+            timer: DeadlineTimer::new(deadline),
+            is_done: false,
+        }
+    }
+}
+
+impl<S, T> Stream for DeadlineStream<S>
+where
+    S: Stream<Item = Result<T, Status>>,
+{
+    type Item = Result<T, Status>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.project();
+        if *this.is_done {
+            return Poll::Ready(None);
+        }
+        if let Poll::Ready(status) = this.timer.poll_expired(cx) {
+            *this.is_done = true;
+            return Poll::Ready(Some(Err(status)));
+        }
+        let item = futures_lite::ready!(this.inner.poll_next(cx));
+        if item.is_none() {
+            *this.is_done = true;
+        }
+        Poll::Ready(item)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_grpc_timeout_value() {
+        for (input, expected) in [
+            ("1H", Some(Duration::from_secs(3600))),
+            ("3M", Some(Duration::from_secs(180))),
+            ("10S", Some(Duration::from_secs(10))),
+            ("100m", Some(Duration::from_millis(100))),
+            ("42u", Some(Duration::from_micros(42))),
+            ("7n", Some(Duration::from_nanos(7))),
+            ("99999999m", Some(Duration::from_millis(99_999_999))),
+            ("123456789m", None),
+            ("10", None),
+            ("m", None),
+            ("10x", None),
+            ("-1S", None),
+            ("", None),
+        ] {
+            assert_eq!(
+                expected,
+                parse_grpc_timeout_value(input.as_bytes()),
+                "input: {input:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_encode_grpc_timeout() {
+        for (input, expected) in [
+            (Duration::from_nanos(5), "5n"),
+            (Duration::from_millis(10), "10000000n"),
+            (Duration::from_millis(100), "100000u"),
+            (Duration::from_secs(30), "30000000u"),
+            (Duration::from_secs(60 * 60 * 24 * 365), "31536000S"),
+            (Duration::MAX, "99999999H"),
+        ] {
+            assert_eq!(expected, encode_grpc_timeout(input), "input: {input:?}");
+        }
+    }
+
+    #[test]
+    fn test_grpc_timeout_roundtrip() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(None, try_parse_grpc_timeout(&headers).unwrap());
+
+        let timeout = Duration::from_millis(1500);
+        headers.insert(GRPC_TIMEOUT, encode_grpc_timeout(timeout));
+        assert_eq!(Some(timeout), try_parse_grpc_timeout(&headers).unwrap());
+
+        headers.insert(GRPC_TIMEOUT, HeaderValue::from_static("soon"));
+        assert!(try_parse_grpc_timeout(&headers).is_err());
+    }
+
+    #[test]
+    fn test_deadline_from_timeout() {
+        assert!(deadline_from_timeout(None).is_none());
+        assert!(deadline_from_timeout(Some(Duration::from_secs(1))).is_some());
+        assert!(deadline_from_timeout(Some(Duration::MAX)).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_deadline_stream() {
+        use futures_lite::StreamExt;
+
+        let messages = futures_lite::stream::iter([Ok(1), Ok(2)])
+            .chain(futures_lite::stream::pending::<Result<u32, Status>>());
+        let mut stream = std::pin::pin!(DeadlineStream::new(
+            messages,
+            deadline_from_timeout(Some(Duration::from_millis(10))),
+        ));
+        assert_eq!(1, stream.next().await.unwrap().unwrap());
+        assert_eq!(2, stream.next().await.unwrap().unwrap());
+        let status = stream.next().await.unwrap().unwrap_err();
+        assert_eq!(super::super::GrpcCode::DeadlineExceeded, status.code());
+        assert!(stream.next().await.is_none());
+    }
+}
@@ -0,0 +1,731 @@
+//! Middleware translating [gRPC-Web] requests into regular gRPC requests.
+//!
+//! Browsers cannot make native gRPC calls, as they provide no control
+//! over http/2 framing nor access to response trailers. gRPC-Web clients
+//! instead send `application/grpc-web` (binary) or `application/grpc-web-text`
+//! (base64) requests, which this middleware turns into http/2 gRPC requests
+//! for the inner service. The responses are re-encoded for the client,
+//! carrying the trailers in a final (trailer) frame of the body.
+//!
+//! Requests which are not gRPC-Web requests are passed as-is to the inner service.
+//!
+//! The [`GrpcWebLayer`] also handles CORS (preflight) requests, using a [`CorsLayer`]
+//! which allows the headers used by gRPC-Web clients and exposes the gRPC status headers.
+//! No cross-origin requests are allowed by default: use [`GrpcWebLayer::allow_origin`]
+//! to allow the origins of the web applications calling the service.
+//!
+//! # Example
+//!
+//! ```
+//! use rama_core::{service::service_fn, Layer};
+//! use rama_http::grpc::{self, server::Grpc, web::GrpcWebLayer, ProstCodec, Status};
+//! use rama_http::service::web::WebService;
+//! use rama_http::HeaderValue;
+//!
+//! #[derive(Clone, PartialEq, prost::Message)]
+//! struct Ping {
+//!     #[prost(uint64, tag = "1")]
+//!     id: u64,
+//! }
+//!
+//! async fn ping(req: grpc::Request<Ping>) -> Result<grpc::Response<Ping>, Status> {
+//!     Ok(grpc::Response::new(req.into_inner()))
+//! }
+//!
+//! let service = GrpcWebLayer::new()
+//!     .allow_origin(HeaderValue::from_static("https://example.com"))
+//!     .layer(WebService::default().post(
+//!         "/test.Pinger/Ping",
+//!         Grpc::new(ProstCodec::<Ping, Ping>::default()).unary(service_fn(ping)),
+//!     ));
+//! ```
+//!
+//! [gRPC-Web]: https://github.com/grpc/grpc/blob/master/doc/PROTOCOL-WEB.md
+
+use super::codec::HEADER_SIZE;
+use super::{is_grpc_content_type, GRPC_MESSAGE, GRPC_STATUS, GRPC_STATUS_DETAILS, GRPC_TIMEOUT};
+use crate::dep::http_body::{self, Frame};
+use crate::header::{CONTENT_LENGTH, CONTENT_TYPE, TE};
+use crate::layer::cors::{AllowOrigin, Cors, CorsLayer};
+use crate::{
+    Body, HeaderMap, HeaderName, HeaderValue, IntoResponse, Method, Request, Response, StatusCode,
+    Version,
+};
+use base64::Engine as _;
+use bytes::{BufMut, Bytes, BytesMut};
+use rama_core::error::BoxError;
+use rama_core::{Context, Layer, Service};
+use rama_utils::macros::define_inner_service_accessors;
+use std::{
+    fmt,
+    pin::Pin,
+    task::{Context as TaskContext, Poll},
+    time::Duration,
+};
+
+const BASE64: base64::engine::GeneralPurpose = base64::engine::general_purpose::STANDARD;
+
+/// Flag of the gRPC-Web frame which carries the trailers of the response.
+const TRAILERS_FLAG: u8 = 0x80;
+
+const DEFAULT_MAX_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Layer that applies the [`GrpcWebService`] middleware,
+/// wrapped in a [`Cors`] middleware to handle CORS (preflight) requests.
+///
+/// See the [module docs](self) for more details.
+#[derive(Debug, Clone)]
+pub struct GrpcWebLayer {
+    allow_origin: Option<AllowOrigin>,
+    allow_credentials: bool,
+    cors: Option<CorsLayer>,
+}
+
+impl GrpcWebLayer {
+    /// Create a new [`GrpcWebLayer`].
+    ///
+    /// By default no CORS headers are sent, so browsers only allow same-origin requests.
+    /// Use [`Self::allow_origin`] to allow cross-origin requests.
+    pub fn new() -> Self {
+        Self {
+            allow_origin: None,
+            allow_credentials: false,
+            cors: None,
+        }
+    }
+
+    /// Allow cross-origin requests from the given origin(s),
+    /// e.g. an exact origin or a list of origins.
+    ///
+    /// The [`CorsLayer`] used for allowed origins allows the headers used by
+    /// gRPC-Web clients and exposes the gRPC status headers to the client.
+    pub fn allow_origin(mut self, origin: impl Into<AllowOrigin>) -> Self {
+        self.allow_origin = Some(origin.into());
+        self
+    }
+
+    /// Allow cross-origin requests to include credentials,
+    /// such as cookies, for the origins allowed using [`Self::allow_origin`].
+    ///
+    /// Disabled by default.
+    pub fn allow_credentials(mut self, allow_credentials: bool) -> Self {
+        self.allow_credentials = allow_credentials;
+        self
+    }
+
+    /// Use the given [`CorsLayer`] instead of the one
+    /// configured using [`Self::allow_origin`] and [`Self::allow_credentials`].
+    pub fn cors(mut self, cors: CorsLayer) -> Self {
+        self.cors = Some(cors);
+        self
+    }
+
+    fn cors_layer(&self) -> CorsLayer {
+        if let Some(cors) = &self.cors {
+            return cors.clone();
+        }
+        let Some(allow_origin) = self.allow_origin.clone() else {
+            return CorsLayer::new();
+        };
+        CorsLayer::new()
+            .allow_origin(allow_origin)
+            .allow_credentials(self.allow_credentials)
+            .allow_methods([Method::POST])
+            .allow_headers([
+                CONTENT_TYPE,
+                HeaderName::from_static("x-grpc-web"),
+                HeaderName::from_static("x-user-agent"),
+                GRPC_TIMEOUT,
+            ])
+            .expose_headers([GRPC_STATUS, GRPC_MESSAGE, GRPC_STATUS_DETAILS])
+            .max_age(DEFAULT_MAX_AGE)
+    }
+}
+
+impl Default for GrpcWebLayer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<S> Layer<S> for GrpcWebLayer {
+    type Service = Cors<GrpcWebService<S>>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        self.cors_layer().layer(GrpcWebService::new(inner))
+    }
+}
+
+/// Middleware translating gRPC-Web requests into gRPC requests.
+///
+/// Unlike the [`GrpcWebLayer`] it does not handle CORS requests,
+/// use it directly in case these are handled elsewhere or not needed.
+///
+/// See the [module docs](self) for more details.
+pub struct GrpcWebService<S> {
+    inner: S,
+}
+
+impl<S> GrpcWebService<S> {
+    /// Create a new [`GrpcWebService`].
+    pub const fn new(inner: S) -> Self {
+        Self { inner }
+    }
+
+    define_inner_service_accessors!();
+}
+
+impl<S: fmt::Debug> fmt::Debug for GrpcWebService<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("GrpcWebService")
+            .field("inner", &self.inner)
+            .finish()
+    }
+}
+
+impl<S: Clone> Clone for GrpcWebService<S> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<State, S> Service<State, Request> for GrpcWebService<S>
+where
+    State: Clone + Send + Sync + 'static,
+    S: Service<State, Request, Response = Response>,
+{
+    type Response = Response;
+    type Error = S::Error;
+
+    async fn serve(
+        &self,
+        ctx: Context<State>,
+        req: Request,
+    ) -> Result<Self::Response, Self::Error> {
+        let Some((encoding, content_type)) = grpc_web_content_type(req.headers()) else {
+            return self.inner.serve(ctx, req).await;
+        };
+        if req.method() != Method::POST {
+            return Ok(StatusCode::METHOD_NOT_ALLOWED.into_response());
+        }
+
+        let (mut parts, body) = req.into_parts();
+        parts.version = Version::HTTP_2;
+        parts.headers.insert(CONTENT_TYPE, content_type);
+        parts
+            .headers
+            .insert(TE, HeaderValue::from_static("trailers"));
+        let body = match encoding {
+            Encoding::Binary => body,
+            Encoding::Text => {
+                parts.headers.remove(CONTENT_LENGTH);
+                Body::new(DecodeTextBody {
+                    inner: body,
+                    buf: BytesMut::new(),
+                })
+            }
+        };
+
+        let res = self
+            .inner
+            .serve(ctx, Request::from_parts(parts, body))
+            .await?;
+        Ok(encode_response(res, encoding))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// The encoding of a gRPC-Web body.
+enum Encoding {
+    /// `application/grpc-web`: identical to the gRPC framing.
+    Binary,
+    /// `application/grpc-web-text`: base64 encoded gRPC framing.
+    Text,
+}
+
+impl Encoding {
+    const fn content_type_prefix(self) -> &'static str {
+        match self {
+            Self::Binary => "application/grpc-web",
+            Self::Text => "application/grpc-web-text",
+        }
+    }
+}
+
+/// Returns the [`Encoding`] of a gRPC-Web request,
+/// together with the content type of the corresponding gRPC request.
+fn grpc_web_content_type(headers: &HeaderMap) -> Option<(Encoding, HeaderValue)> {
+    let value = headers.get(CONTENT_TYPE)?.to_str().ok()?;
+    let rest = value.strip_prefix("application/grpc-web")?;
+    let (encoding, rest) = match rest.strip_prefix("-text") {
+        Some(rest) => (Encoding::Text, rest),
+        None => (Encoding::Binary, rest),
+    };
+    if !(rest.is_empty() || rest.starts_with('+') || rest.starts_with(';')) {
+        return None;
+    }
+    let content_type = HeaderValue::try_from(format!("application/grpc{rest}")).ok()?;
+    Some((encoding, content_type))
+}
+
+/// Turn a gRPC response into a gRPC-Web response of the given [`Encoding`].
+fn encode_response(res: Response, encoding: Encoding) -> Response {
+    let (mut parts, body) = res.into_parts();
+    if !is_grpc_content_type(&parts.headers) {
+        // not a gRPC response, e.g. a routing failure
+        return Response::from_parts(parts, body);
+    }
+
+    let rest = parts
+        .headers
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("application/grpc"))
+        .unwrap_or_default();
+    if let Ok(content_type) =
+        HeaderValue::try_from(format!("{}{rest}", encoding.content_type_prefix()))
+    {
+        parts.headers.insert(CONTENT_TYPE, content_type);
+    }
+    parts.headers.remove(CONTENT_LENGTH);
+
+    let body = Body::new(EncodeWebBody {
+        inner: body,
+        encoding,
+        buf: BytesMut::new(),
+        is_end_stream: false,
+    });
+    Response::from_parts(parts, body)
+}
+
+/// Encode the given trailers as a gRPC-Web trailer frame.
+fn encode_trailers(trailers: &HeaderMap, dst: &mut BytesMut) {
+    let len: usize = trailers
+        .iter()
+        .map(|(name, value)| name.as_str().len() + value.len() + 3)
+        .sum();
+    dst.reserve(HEADER_SIZE + len);
+    dst.put_u8(TRAILERS_FLAG);
+    dst.put_u32(len as u32);
+    for (name, value) in trailers {
+        dst.put_slice(name.as_str().as_bytes());
+        dst.put_u8(b':');
+        dst.put_slice(value.as_bytes());
+        dst.put_slice(b"\r\n");
+    }
+}
+
+/// Decode base64 data, which may consist of multiple padded chunks.
+fn decode_base64(mut src: &[u8]) -> Result<Bytes, base64::DecodeError> {
+    let mut dst = Vec::with_capacity(src.len() / 4 * 3);
+    while !src.is_empty() {
+        // a padded quad ends a chunk
+        let end = src
+            .iter()
+            .position(|b| *b == b'=')
+            .map_or(src.len(), |pos| ((pos / 4 + 1) * 4).min(src.len()));
+        BASE64.decode_vec(&src[..end], &mut dst)?;
+        src = &src[end..];
+    }
+    Ok(dst.into())
+}
+
+/// Request body decoding an `application/grpc-web-text` body.
+struct DecodeTextBody {
+    inner: Body,
+    buf: BytesMut,
+}
+
+impl http_body::Body for DecodeTextBody {
+    type Data = Bytes;
+    type Error = BoxError;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut TaskContext<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let this = &mut *self;
+        loop {
+            match futures_lite::ready!(Pin::new(&mut this.inner).poll_frame(cx)) {
+                Some(Ok(frame)) => match frame.into_data() {
+                    Ok(data) => {
+                        this.buf.extend_from_slice(&data);
+                        // only decode complete quads
+                        let n = this.buf.len() / 4 * 4;
+                        if n == 0 {
+                            continue;
+                        }
+                        let chunk = this.buf.split_to(n);
+                        return Poll::Ready(Some(decode_base64(&chunk).map(Frame::data).map_err(
+                            |err| {
+                                super::Status::internal(format!(
+                                    "invalid grpc-web-text request body: {err}"
+                                ))
+                                .into()
+                            },
+                        )));
+                    }
+                    Err(frame) => return Poll::Ready(Some(Ok(frame))),
+                },
+                Some(Err(err)) => return Poll::Ready(Some(Err(err.into()))),
+                None if this.buf.is_empty() => return Poll::Ready(None),
+                None => {
+                    this.buf.clear();
+                    return Poll::Ready(Some(Err(super::Status::internal(
+                        "truncated grpc-web-text request body",
+                    )
+                    .into())));
+                }
+            }
+        }
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.buf.is_empty() && self.inner.is_end_stream()
+    }
+}
+
+/// Response body encoding a gRPC response body as a gRPC-Web body.
+struct EncodeWebBody {
+    inner: Body,
+    encoding: Encoding,
+    /// Pending bytes, only used for [`Encoding::Text`], such that
+    /// base64 padding is only used at the end of the body.
+    buf: BytesMut,
+    is_end_stream: bool,
+}
+
+impl EncodeWebBody {
+    /// Base64 encode the first `n` pending bytes.
+    fn encode_text(&mut self, n: usize) -> Frame<Bytes> {
+        let chunk = self.buf.split_to(n);
+        Frame::data(BASE64.encode(chunk).into())
+    }
+}
+
+impl http_body::Body for EncodeWebBody {
+    type Data = Bytes;
+    type Error = BoxError;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut TaskContext<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let this = &mut *self;
+        loop {
+            if this.is_end_stream {
+                let n = this.buf.len();
+                return Poll::Ready((n > 0).then(|| Ok(this.encode_text(n))));
+            }
+
+            match futures_lite::ready!(Pin::new(&mut this.inner).poll_frame(cx)) {
+                Some(Ok(frame)) => {
+                    let frame = match frame.into_data() {
+                        Ok(data) if this.encoding == Encoding::Binary => {
+                            return Poll::Ready(Some(Ok(Frame::data(data))));
+                        }
+                        Ok(data) => {
+                            this.buf.extend_from_slice(&data);
+                            // only encode complete triplets, as to avoid padding
+                            let n = this.buf.len() / 3 * 3;
+                            if n == 0 {
+                                continue;
+                            }
+                            return Poll::Ready(Some(Ok(this.encode_text(n))));
+                        }
+                        Err(frame) => frame,
+                    };
+                    if let Ok(trailers) = frame.into_trailers() {
+                        this.is_end_stream = true;
+                        match this.encoding {
+                            Encoding::Binary => {
+                                let mut buf = BytesMut::new();
+                                encode_trailers(&trailers, &mut buf);
+                                return Poll::Ready(Some(Ok(Frame::data(buf.freeze()))));
+                            }
+                            Encoding::Text => encode_trailers(&trailers, &mut this.buf),
+                        }
+                    }
+                }
+                Some(Err(err)) => return Poll::Ready(Some(Err(err.into()))),
+                None => this.is_end_stream = true,
+            }
+        }
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.is_end_stream && self.buf.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dep::http_body_util::BodyExt;
+    use crate::grpc::{self, server::Grpc, ProstCodec, Status};
+    use crate::header::{
+        ACCESS_CONTROL_ALLOW_CREDENTIALS, ACCESS_CONTROL_ALLOW_ORIGIN,
+        ACCESS_CONTROL_REQUEST_METHOD, ORIGIN,
+    };
+    use crate::service::web::WebService;
+    use rama_core::service::service_fn;
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    struct Number {
+        #[prost(int64, tag = "1")]
+        value: i64,
+    }
+
+    async fn double(req: grpc::Request<Number>) -> Result<grpc::Response<Number>, Status> {
+        let value = req.into_inner().value;
+        if value < 0 {
+            return Err(Status::invalid_argument("negative number"));
+        }
+        Ok(grpc::Response::new(Number { value: value * 2 }))
+    }
+
+    fn service_with_layer(layer: GrpcWebLayer) -> Cors<GrpcWebService<WebService<()>>> {
+        layer.layer(WebService::default().post(
+            "/test.Math/Double",
+            Grpc::new(ProstCodec::<Number, Number>::new()).unary(service_fn(double)),
+        ))
+    }
+
+    fn service() -> Cors<GrpcWebService<WebService<()>>> {
+        service_with_layer(
+            GrpcWebLayer::new().allow_origin(HeaderValue::from_static("https://example.com")),
+        )
+    }
+
+    fn message(value: i64) -> Vec<u8> {
+        let msg = prost::Message::encode_to_vec(&Number { value });
+        let mut buf = vec![0];
+        buf.extend_from_slice(&(msg.len() as u32).to_be_bytes());
+        buf.extend_from_slice(&msg);
+        buf
+    }
+
+    fn request(content_type: &'static str, body: impl Into<Body>) -> Request {
+        Request::builder()
+            .method(Method::POST)
+            .uri("/test.Math/Double")
+            .header(CONTENT_TYPE, content_type)
+            .header(ORIGIN, "https://example.com")
+            .body(body.into())
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_grpc_web_binary() {
+        let res = service()
+            .serve(
+                Context::default(),
+                request("application/grpc-web+proto", message(21)),
+            )
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+        assert_eq!("application/grpc-web", res.headers()[CONTENT_TYPE]);
+        assert_eq!(
+            "https://example.com",
+            res.headers()[ACCESS_CONTROL_ALLOW_ORIGIN]
+        );
+
+        let body = res.into_body().collect().await.unwrap();
+        assert!(body.trailers().is_none());
+        let body = body.to_bytes();
+        let expected = message(42);
+        assert_eq!(&expected[..], &body[..expected.len()]);
+        assert_eq!(
+            &b"\x80\x00\x00\x00\x0fgrpc-status:0\r\n"[..],
+            &body[expected.len()..]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_grpc_web_text() {
+        // message encoded as two separately padded chunks
+        let msg = message(21);
+        let (a, b) = msg.split_at(4);
+        let body = format!("{}{}", BASE64.encode(a), BASE64.encode(b));
+        let res = service()
+            .serve(
+                Context::default(),
+                request("application/grpc-web-text", body),
+            )
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+        assert_eq!("application/grpc-web-text", res.headers()[CONTENT_TYPE]);
+
+        let body = res.into_body().collect().await.unwrap().to_bytes();
+        let body = BASE64.decode(&body).unwrap();
+        let expected = message(42);
+        assert_eq!(&expected[..], &body[..expected.len()]);
+        assert_eq!(
+            &b"\x80\x00\x00\x00\x0fgrpc-status:0\r\n"[..],
+            &body[expected.len()..]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_grpc_web_error_status() {
+        let res = service()
+            .serve(
+                Context::default(),
+                request("application/grpc-web", message(-1)),
+            )
+            .await
+            .unwrap();
+        // trailers-only responses keep their status in the headers
+        assert_eq!("3", res.headers()[GRPC_STATUS]);
+        assert_eq!("application/grpc-web", res.headers()[CONTENT_TYPE]);
+
+        let res = service()
+            .serve(
+                Context::default(),
+                request("application/grpc-web-text", "AAAA"),
+            )
+            .await
+            .unwrap();
+        assert_eq!("13", res.headers()[GRPC_STATUS]);
+    }
+
+    fn preflight_request(origin: &'static str) -> Request {
+        Request::builder()
+            .method(Method::OPTIONS)
+            .uri("/test.Math/Double")
+            .header(ORIGIN, origin)
+            .header(ACCESS_CONTROL_REQUEST_METHOD, "POST")
+            .body(Body::empty())
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_grpc_web_preflight() {
+        let res = service()
+            .serve(Context::default(), preflight_request("https://example.com"))
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+        assert_eq!(
+            "https://example.com",
+            res.headers()[ACCESS_CONTROL_ALLOW_ORIGIN]
+        );
+        assert!(res
+            .headers()
+            .get(ACCESS_CONTROL_ALLOW_CREDENTIALS)
+            .is_none());
+
+        let res = service()
+            .serve(Context::default(), preflight_request("https://evil.com"))
+            .await
+            .unwrap();
+        assert_ne!(
+            Some("https://evil.com"),
+            res.headers()
+                .get(ACCESS_CONTROL_ALLOW_ORIGIN)
+                .and_then(|v| v.to_str().ok())
+        );
+
+        let res = service_with_layer(
+            GrpcWebLayer::new()
+                .allow_origin(AllowOrigin::list([
+                    HeaderValue::from_static("https://example.com"),
+                    HeaderValue::from_static("https://example.org"),
+                ]))
+                .allow_credentials(true),
+        )
+        .serve(Context::default(), preflight_request("https://example.org"))
+        .await
+        .unwrap();
+        assert_eq!(
+            "https://example.org",
+            res.headers()[ACCESS_CONTROL_ALLOW_ORIGIN]
+        );
+        assert_eq!("true", res.headers()[ACCESS_CONTROL_ALLOW_CREDENTIALS]);
+    }
+
+    #[tokio::test]
+    async fn test_grpc_web_no_cors_by_default() {
+        let res = service_with_layer(GrpcWebLayer::new())
+            .serve(Context::default(), preflight_request("https://example.com"))
+            .await
+            .unwrap();
+        assert!(res.headers().get(ACCESS_CONTROL_ALLOW_ORIGIN).is_none());
+        assert!(res
+            .headers()
+            .get(ACCESS_CONTROL_ALLOW_CREDENTIALS)
+            .is_none());
+
+        let res = service_with_layer(GrpcWebLayer::new())
+            .serve(
+                Context::default(),
+                request("application/grpc-web", message(21)),
+            )
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+        assert!(res.headers().get(ACCESS_CONTROL_ALLOW_ORIGIN).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_grpc_web_passthrough() {
+        let res = service()
+            .serve(Context::default(), request("application/grpc", message(21)))
+            .await
+            .unwrap();
+        assert_eq!("application/grpc", res.headers()[CONTENT_TYPE]);
+
+        let req = Request::builder()
+            .method(Method::GET)
+            .uri("/test.Math/Double")
+            .header(CONTENT_TYPE, "application/grpc-web")
+            .body(Body::empty())
+            .unwrap();
+        let res = GrpcWebService::new(service_fn(|_req: Request| async {
+            Ok::<_, std::convert::Infallible>(Response::new(Body::empty()))
+        }))
+        .serve(Context::default(), req)
+        .await
+        .unwrap();
+        assert_eq!(StatusCode::METHOD_NOT_ALLOWED, res.status());
+    }
+
+    #[test]
+    fn test_grpc_web_content_type() {
+        for (content_type, expected) in [
+            (
+                "application/grpc-web",
+                Some((Encoding::Binary, "application/grpc")),
+            ),
+            (
+                "application/grpc-web+proto",
+                Some((Encoding::Binary, "application/grpc+proto")),
+            ),
+            (
+                "application/grpc-web-text",
+                Some((Encoding::Text, "application/grpc")),
+            ),
+            (
+                "application/grpc-web-text+proto",
+                Some((Encoding::Text, "application/grpc+proto")),
+            ),
+            ("application/grpc", None),
+            ("application/grpc-webx", None),
+        ] {
+            let mut headers = HeaderMap::new();
+            headers.insert(CONTENT_TYPE, HeaderValue::from_static(content_type));
+            let result = grpc_web_content_type(&headers);
+            assert_eq!(
+                expected,
+                result
+                    .as_ref()
+                    .map(|(encoding, value)| (*encoding, value.to_str().unwrap())),
+                "{content_type}"
+            );
+        }
+    }
+}
@@ -99,7 +99,7 @@ pub mod authorization {
 }
 
 #[doc(inline)]
-pub use ::rama_http_types::headers::HeaderExt;
+pub use ::rama_http_types::headers::{remove_hop_by_hop_headers, HeaderExt};
 
 pub(crate) mod util;
 pub use util::quality_value::{Quality, QualityValue};
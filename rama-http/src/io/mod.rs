@@ -1,5 +1,9 @@
 //! http I/O utilities, e.g. writing http requests/responses in std http format.
 
+use crate::HeaderMap;
+use rama_core::error::BoxError;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+
 mod request;
 #[doc(inline)]
 pub use request::write_http_request;
@@ -7,3 +11,19 @@ pub use request::write_http_request;
 mod response;
 #[doc(inline)]
 pub use response::write_http_response;
+
+/// Write the trailers of a message (if any), following its body.
+async fn write_trailers<W>(w: &mut W, trailers: &HeaderMap) -> Result<(), BoxError>
+where
+    W: AsyncWrite + Unpin,
+{
+    if trailers.is_empty() {
+        return Ok(());
+    }
+    w.write_all(b"\r\n").await?;
+    for (name, value) in trailers {
+        w.write_all(format!("{}: {}\r\n", name, value.to_str()?).as_bytes())
+            .await?;
+    }
+    Ok(())
+}
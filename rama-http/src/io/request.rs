@@ -102,12 +102,15 @@ where
     }
 
     let body = if write_body {
-        let body = body.collect().await.map_err(Into::into)?.to_bytes();
+        let collected = body.collect().await.map_err(Into::into)?;
+        let trailers = collected.trailers().cloned().unwrap_or_default();
+        let body = collected.to_bytes();
         w.write_all(b"\r\n").await?;
         if !body.is_empty() {
             w.write_all(body.as_ref()).await?;
         }
-        Body::from(body)
+        super::write_trailers(w, &trailers).await?;
+        Body::from(body).with_trailers(trailers)
     } else {
         Body::new(body)
     };
@@ -90,12 +90,15 @@ where
     }
 
     let body = if write_body {
-        let body = body.collect().await.map_err(Into::into)?.to_bytes();
+        let collected = body.collect().await.map_err(Into::into)?;
+        let trailers = collected.trailers().cloned().unwrap_or_default();
+        let body = collected.to_bytes();
         w.write_all(b"\r\n").await?;
         if !body.is_empty() {
             w.write_all(body.as_ref()).await?;
         }
-        Body::from(body)
+        super::write_trailers(w, &trailers).await?;
+        Body::from(body).with_trailers(trailers)
     } else {
         Body::new(body)
     };
@@ -162,4 +165,26 @@ mod tests {
             "HTTP/1.1 200 OK\r\ncontent-type: text/plain\r\nserver: test/0\r\n\r\nhello"
         );
     }
+
+    #[tokio::test]
+    async fn test_write_response_with_trailers() {
+        let mut buf = Vec::new();
+        let mut trailers = crate::HeaderMap::new();
+        trailers.insert("grpc-status", crate::HeaderValue::from_static("0"));
+        let res = Response::builder()
+            .status(200)
+            .body(Body::from("hello").with_trailers(trailers.clone()))
+            .unwrap();
+
+        let res = write_http_response(&mut buf, res, true, true)
+            .await
+            .unwrap();
+
+        let buf = String::from_utf8(buf).unwrap();
+        assert_eq!(buf, "HTTP/1.1 200 OK\r\n\r\nhello\r\ngrpc-status: 0\r\n");
+
+        let collected = res.into_body().collect().await.unwrap();
+        assert_eq!(Some(&trailers), collected.trailers());
+        assert_eq!("hello", collected.to_bytes());
+    }
 }
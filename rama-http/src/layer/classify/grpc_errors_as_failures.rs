@@ -8,7 +8,7 @@ use std::{fmt, num::NonZeroI32};
 /// These variants match the [gRPC status codes].
 ///
 /// [gRPC status codes]: https://github.com/grpc/grpc/blob/master/doc/statuscodes.md#status-codes-and-their-use-in-grpc
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum GrpcCode {
     /// The operation completed successfully.
     Ok,
@@ -47,6 +47,63 @@ pub enum GrpcCode {
 }
 
 impl GrpcCode {
+    /// Create a [`GrpcCode`] from its numeric value,
+    /// mapping values out of range to [`GrpcCode::Unknown`].
+    pub const fn from_i32(code: i32) -> Self {
+        match code {
+            0 => Self::Ok,
+            1 => Self::Cancelled,
+            2 => Self::Unknown,
+            3 => Self::InvalidArgument,
+            4 => Self::DeadlineExceeded,
+            5 => Self::NotFound,
+            6 => Self::AlreadyExists,
+            7 => Self::PermissionDenied,
+            8 => Self::ResourceExhausted,
+            9 => Self::FailedPrecondition,
+            10 => Self::Aborted,
+            11 => Self::OutOfRange,
+            12 => Self::Unimplemented,
+            13 => Self::Internal,
+            14 => Self::Unavailable,
+            15 => Self::DataLoss,
+            16 => Self::Unauthenticated,
+            _ => Self::Unknown,
+        }
+    }
+
+    /// Returns the numeric value of this [`GrpcCode`].
+    pub const fn as_i32(self) -> i32 {
+        self as i32
+    }
+
+    /// Returns a short description of this [`GrpcCode`].
+    pub const fn description(self) -> &'static str {
+        match self {
+            Self::Ok => "The operation completed successfully",
+            Self::Cancelled => "The operation was cancelled",
+            Self::Unknown => "Unknown error",
+            Self::InvalidArgument => "Client specified an invalid argument",
+            Self::DeadlineExceeded => "Deadline expired before operation could complete",
+            Self::NotFound => "Some requested entity was not found",
+            Self::AlreadyExists => "Some entity that we attempted to create already exists",
+            Self::PermissionDenied => {
+                "The caller does not have permission to execute the specified operation"
+            }
+            Self::ResourceExhausted => "Some resource has been exhausted",
+            Self::FailedPrecondition => {
+                "The system is not in a state required for the operation's execution"
+            }
+            Self::Aborted => "The operation was aborted",
+            Self::OutOfRange => "Operation was attempted past the valid range",
+            Self::Unimplemented => "Operation is not implemented or not supported",
+            Self::Internal => "Internal error",
+            Self::Unavailable => "The service is currently unavailable",
+            Self::DataLoss => "Unrecoverable data loss or corruption",
+            Self::Unauthenticated => "The request does not have valid authentication credentials",
+        }
+    }
+
     pub(crate) const fn into_bitmask(self) -> GrpcCodeBitmask {
         match self {
             Self::Ok => GrpcCodeBitmask::OK,
@@ -320,6 +377,15 @@ pub(crate) enum ParsedGrpcStatus {
 mod tests {
     use super::*;
 
+    #[test]
+    fn grpc_code_i32_roundtrip() {
+        for code in 0..=16 {
+            assert_eq!(code, GrpcCode::from_i32(code).as_i32());
+        }
+        assert_eq!(GrpcCode::Unknown, GrpcCode::from_i32(17));
+        assert_eq!(GrpcCode::Unknown, GrpcCode::from_i32(-1));
+    }
+
     macro_rules! classify_grpc_metadata_test {
         (
             name: $name:ident,
@@ -2,7 +2,7 @@
 //!
 //! See [request] and [response] for more details.
 
-use crate::{header, HeaderMap, HeaderName};
+use crate::{header, headers::remove_hop_by_hop_headers, HeaderMap, HeaderName};
 
 pub mod request;
 pub mod response;
@@ -34,14 +34,9 @@ fn remove_headers_by_exact_name(headers: &mut HeaderMap, name: &HeaderName) {
 }
 
 fn remove_hop_by_hop_request_headers(headers: &mut HeaderMap) {
+    remove_hop_by_hop_headers(headers);
     for header in [
-        &header::CONNECTION,
-        &header::PROXY_CONNECTION,
         &header::PROXY_AUTHORIZATION,
-        &header::TE,
-        &header::TRAILER,
-        &header::TRANSFER_ENCODING,
-        &header::UPGRADE,
         &header::X_FORWARDED_FOR,
         &header::X_FORWARDED_HOST,
         &header::X_FORWARDED_PROTO,
@@ -58,14 +53,6 @@ fn remove_hop_by_hop_request_headers(headers: &mut HeaderMap) {
 }
 
 fn remove_hop_by_hop_response_headers(headers: &mut HeaderMap) {
-    for header in [
-        &header::CONNECTION,
-        &header::KEEP_ALIVE,
-        &header::PROXY_AUTHENTICATE,
-        &header::TRAILER,
-        &header::TRANSFER_ENCODING,
-        &header::UPGRADE,
-    ] {
-        headers.remove(header);
-    }
+    remove_hop_by_hop_headers(headers);
+    headers.remove(header::PROXY_AUTHENTICATE);
 }
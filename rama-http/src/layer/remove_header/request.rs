@@ -71,6 +71,9 @@ impl RemoveRequestHeaderLayer {
     ///
     /// Removes all hop-by-hop request headers as specified in [RFC 2616](https://datatracker.ietf.org/doc/html/rfc2616#section-13.5.1).
     /// This does not support other hop-by-hop headers defined in [section-14.10](https://datatracker.ietf.org/doc/html/rfc2616#section-14.10).
+    ///
+    /// A `TE` header accepting `trailers` is replaced by `TE: trailers`,
+    /// such that trailers (e.g. `grpc-status`) can still be forwarded.
     pub fn hop_by_hop() -> Self {
         Self {
             mode: RemoveRequestHeaderMode::Hop,
@@ -114,6 +117,9 @@ impl<S> RemoveRequestHeader<S> {
     ///
     /// Removes all hop-by-hop headers as specified in [RFC 2616](https://datatracker.ietf.org/doc/html/rfc2616#section-13.5.1).
     /// This does not support other hop-by-hop headers defined in [section-14.10](https://datatracker.ietf.org/doc/html/rfc2616#section-14.10).
+    ///
+    /// A `TE` header accepting `trailers` is replaced by `TE: trailers`,
+    /// such that trailers (e.g. `grpc-status`) can still be forwarded.
     pub fn hop_by_hop(inner: S) -> Self {
         RemoveRequestHeaderLayer::hop_by_hop().layer(inner)
     }
@@ -235,4 +241,34 @@ mod test {
             .unwrap();
         let _ = svc.serve(Context::default(), req).await.unwrap();
     }
+
+    #[tokio::test]
+    async fn remove_request_header_hop_by_hop_keeps_te_trailers() {
+        let svc = RemoveRequestHeaderLayer::hop_by_hop().layer(service_fn(
+            |_ctx: Context<()>, req: Request| async move {
+                assert_eq!(
+                    req.headers().get("te").map(|v| v.to_str().unwrap()),
+                    Some("trailers")
+                );
+                Ok::<_, Infallible>(Response::new(Body::empty()))
+            },
+        ));
+        let req = Request::builder()
+            .header("te", "gzip;q=0.5, Trailers")
+            .body(Body::empty())
+            .unwrap();
+        let _ = svc.serve(Context::default(), req).await.unwrap();
+
+        let svc = RemoveRequestHeaderLayer::hop_by_hop().layer(service_fn(
+            |_ctx: Context<()>, req: Request| async move {
+                assert!(req.headers().get("te").is_none());
+                Ok::<_, Infallible>(Response::new(Body::empty()))
+            },
+        ));
+        let req = Request::builder()
+            .header("te", "gzip")
+            .body(Body::empty())
+            .unwrap();
+        let _ = svc.serve(Context::default(), req).await.unwrap();
+    }
 }
@@ -285,17 +285,18 @@ where
             Some(_) => req.map(Body::new),
             None => {
                 let (parts, body) = req.into_parts();
-                let body_bytes = body
-                    .collect()
-                    .await
-                    .map_err(|err| {
-                        OpaqueError::from_boxed(err.into())
-                            .context("printer prepare: collect request body")
-                    })?
-                    .to_bytes();
-                let req = Request::from_parts(parts.clone(), Body::from(body_bytes.clone()));
+                let collected = body.collect().await.map_err(|err| {
+                    OpaqueError::from_boxed(err.into())
+                        .context("printer prepare: collect request body")
+                })?;
+                let trailers = collected.trailers().cloned().unwrap_or_default();
+                let body_bytes = collected.to_bytes();
+                let req = Request::from_parts(
+                    parts.clone(),
+                    Body::from(body_bytes.clone()).with_trailers(trailers.clone()),
+                );
                 self.writer.write_request(req).await;
-                Request::from_parts(parts, Body::from(body_bytes))
+                Request::from_parts(parts, Body::from(body_bytes).with_trailers(trailers))
             }
         };
         self.inner.serve(ctx, req).await.map_err(Into::into)
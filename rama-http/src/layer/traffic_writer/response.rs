@@ -281,16 +281,19 @@ where
             Some(_) => resp.map(Body::new),
             None => {
                 let (parts, body) = resp.into_parts();
-                let body_bytes = body
+                let collected = body
                     .collect()
                     .await
                     .map_err(|err| OpaqueError::from_boxed(err.into()))
-                    .context("printer prepare: collect response body")?
-                    .to_bytes();
-                let resp: http::Response<Body> =
-                    Response::from_parts(parts.clone(), Body::from(body_bytes.clone()));
+                    .context("printer prepare: collect response body")?;
+                let trailers = collected.trailers().cloned().unwrap_or_default();
+                let body_bytes = collected.to_bytes();
+                let resp: http::Response<Body> = Response::from_parts(
+                    parts.clone(),
+                    Body::from(body_bytes.clone()).with_trailers(trailers.clone()),
+                );
                 self.writer.write_response(resp).await;
-                Response::from_parts(parts, Body::from(body_bytes))
+                Response::from_parts(parts, Body::from(body_bytes).with_trailers(trailers))
             }
         };
         Ok(resp)
@@ -33,6 +33,9 @@ pub mod service;
 
 pub mod io;
 
+#[cfg(feature = "grpc")]
+pub mod grpc;
+
 pub mod utils;
 
 pub mod dep {
@@ -11,6 +11,9 @@ use rama_net::{address::Domain, stream::matcher::SocketMatcher};
 use std::fmt;
 use std::sync::Arc;
 
+#[cfg(feature = "tls")]
+use rama_net::tls::server::PeerCertificateMatcher;
+
 mod method;
 #[doc(inline)]
 pub use method::MethodMatcher;
@@ -80,6 +83,9 @@ pub enum HttpMatcherKind<State, Body> {
     ///
     /// [`SocketAddr`]: std::net::SocketAddr
     Socket(SocketMatcher<State, Request<Body>>),
+    #[cfg(feature = "tls")]
+    /// [`PeerCertificateMatcher`], a matcher based on the certificate provided by the peer.
+    PeerCertificate(PeerCertificateMatcher),
     /// A custom matcher that implements [`rama_core::matcher::Matcher`].
     Custom(Arc<dyn rama_core::matcher::Matcher<State, Request<Body>>>),
 }
@@ -96,6 +102,8 @@ impl<State, Body> Clone for HttpMatcherKind<State, Body> {
             Self::Uri(inner) => Self::Uri(inner.clone()),
             Self::Header(inner) => Self::Header(inner.clone()),
             Self::Socket(inner) => Self::Socket(inner.clone()),
+            #[cfg(feature = "tls")]
+            Self::PeerCertificate(inner) => Self::PeerCertificate(inner.clone()),
             Self::Custom(inner) => Self::Custom(inner.clone()),
         }
     }
@@ -113,6 +121,8 @@ impl<State, Body> fmt::Debug for HttpMatcherKind<State, Body> {
             Self::Uri(inner) => f.debug_tuple("Uri").field(inner).finish(),
             Self::Header(inner) => f.debug_tuple("Header").field(inner).finish(),
             Self::Socket(inner) => f.debug_tuple("Socket").field(inner).finish(),
+            #[cfg(feature = "tls")]
+            Self::PeerCertificate(inner) => f.debug_tuple("PeerCertificate").field(inner).finish(),
             Self::Custom(_) => f.debug_tuple("Custom").finish(),
         }
     }
@@ -566,6 +576,31 @@ impl<State, Body> HttpMatcher<State, Body> {
         self.or(Self::socket(socket))
     }
 
+    #[cfg(feature = "tls")]
+    /// Create a [`PeerCertificateMatcher`] matcher.
+    pub fn peer_certificate(peer_cert: PeerCertificateMatcher) -> Self {
+        Self {
+            kind: HttpMatcherKind::PeerCertificate(peer_cert),
+            negate: false,
+        }
+    }
+
+    #[cfg(feature = "tls")]
+    /// Add a [`PeerCertificateMatcher`] matcher to match on top of the existing set of [`HttpMatcher`] matchers.
+    ///
+    /// See [`PeerCertificateMatcher`] for more information.
+    pub fn and_peer_certificate(self, peer_cert: PeerCertificateMatcher) -> Self {
+        self.and(Self::peer_certificate(peer_cert))
+    }
+
+    #[cfg(feature = "tls")]
+    /// Create a [`PeerCertificateMatcher`] matcher to match as an alternative to the existing set of [`HttpMatcher`] matchers.
+    ///
+    /// See [`PeerCertificateMatcher`] for more information.
+    pub fn or_peer_certificate(self, peer_cert: PeerCertificateMatcher) -> Self {
+        self.or(Self::peer_certificate(peer_cert))
+    }
+
     /// Create a [`PathMatcher`] matcher to match for a GET request.
     pub fn get(path: impl AsRef<str>) -> Self {
         Self::method_get().and_path(path)
@@ -716,6 +751,8 @@ where
             HttpMatcherKind::Uri(uri) => uri.matches(ext, ctx, req),
             HttpMatcherKind::Header(header) => header.matches(ext, ctx, req),
             HttpMatcherKind::Socket(socket) => socket.matches(ext, ctx, req),
+            #[cfg(feature = "tls")]
+            HttpMatcherKind::PeerCertificate(peer_cert) => peer_cert.matches(ext, ctx, req),
             HttpMatcherKind::Any(all) => all.iter().matches_or(ext, ctx, req),
             HttpMatcherKind::Custom(matcher) => matcher.matches(ext, ctx, req),
         }
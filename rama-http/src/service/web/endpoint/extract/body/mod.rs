@@ -19,6 +19,10 @@ mod form;
 #[doc(inline)]
 pub use form::*;
 
+mod trailers;
+#[doc(inline)]
+pub use trailers::*;
+
 /// Extractor to get the response body.
 #[derive(Debug)]
 pub struct Body(pub http::Body);
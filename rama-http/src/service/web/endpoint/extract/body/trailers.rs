@@ -0,0 +1,75 @@
+use crate::dep::http_body_util::BodyExt;
+use crate::service::web::extract::FromRequest;
+use crate::utils::macros::define_http_rejection;
+use crate::{HeaderMap, Request};
+use rama_utils::macros::impl_deref;
+
+/// Extractor to get the trailers of the request body,
+/// which is empty in case the request had no trailers.
+///
+/// The request body is collected in order to get to its trailers,
+/// and is discarded afterwards. Use the [`Body`] extractor
+/// in case you need both the data and trailers of the request body.
+///
+/// [`Body`]: super::Body
+#[derive(Debug, Clone)]
+pub struct Trailers(pub HeaderMap);
+
+impl_deref!(Trailers: HeaderMap);
+
+define_http_rejection! {
+    #[status = BAD_REQUEST]
+    #[body = "Request Body failed to be collected to get its Trailers"]
+    /// Rejection type used when the [`Trailers`] extractor fails to collect the request body.
+    pub struct TrailersRejection(Error);
+}
+
+impl FromRequest for Trailers {
+    type Rejection = TrailersRejection;
+
+    async fn from_request(req: Request) -> Result<Self, Self::Rejection> {
+        req.into_body()
+            .collect()
+            .await
+            .map_err(TrailersRejection::from_err)
+            .map(|c| Trailers(c.trailers().cloned().unwrap_or_default()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::service::web::WebService;
+    use crate::{Body, HeaderValue, Method, Request, StatusCode};
+    use rama_core::{Context, Service};
+
+    #[tokio::test]
+    async fn test_trailers() {
+        let service = WebService::default().post("/", |Trailers(trailers): Trailers| async move {
+            assert_eq!(trailers["grpc-status"], "0");
+        });
+
+        let mut trailers = HeaderMap::new();
+        trailers.insert("grpc-status", HeaderValue::from_static("0"));
+        let req = Request::builder()
+            .method(Method::POST)
+            .body(Body::from("test").with_trailers(trailers))
+            .unwrap();
+        let resp = service.serve(Context::default(), req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_trailers_missing() {
+        let service = WebService::default().post("/", |Trailers(trailers): Trailers| async move {
+            assert!(trailers.is_empty());
+        });
+
+        let req = Request::builder()
+            .method(Method::POST)
+            .body("test".into())
+            .unwrap();
+        let resp = service.serve(Context::default(), req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+}
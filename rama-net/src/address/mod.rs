@@ -30,6 +30,10 @@ mod parse_utils;
 #[doc(inline)]
 pub use proxy::ProxyAddress;
 
+mod proxy_chain;
+#[doc(inline)]
+pub use proxy_chain::ProxyChain;
+
 mod domain_address;
 #[doc(inline)]
 pub use domain_address::DomainAddress;
@@ -354,6 +354,8 @@ mod tests {
                     protocol_version: negotiated_protocol_version,
                     application_layer_protocol: None,
                     peer_certificate_chain: None,
+                    session_resumed: false,
+                    ech_accepted: false,
                 });
             }
 
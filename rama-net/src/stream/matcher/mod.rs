@@ -78,6 +78,11 @@ enum SocketMatcherKind<State, Socket> {
     /// [`IpNet`]: ipnet::IpNet
     /// [`SocketAddr`]: std::net::SocketAddr
     IpNet(IpNetMatcher),
+    #[cfg(feature = "tls")]
+    /// [`PeerCertificateMatcher`], a matcher based on the certificate provided by the peer.
+    ///
+    /// [`PeerCertificateMatcher`]: crate::tls::server::PeerCertificateMatcher
+    PeerCertificate(crate::tls::server::PeerCertificateMatcher),
     /// zero or more matchers that all need to match in order for the matcher to return `true`.
     All(Vec<SocketMatcher<State, Socket>>),
     /// `true` if no matchers are defined, or any of the defined matcher match.
@@ -94,6 +99,8 @@ impl<State, Socket> Clone for SocketMatcherKind<State, Socket> {
             Self::PrivateIpNet(matcher) => Self::PrivateIpNet(matcher.clone()),
             Self::Port(matcher) => Self::Port(matcher.clone()),
             Self::IpNet(matcher) => Self::IpNet(matcher.clone()),
+            #[cfg(feature = "tls")]
+            Self::PeerCertificate(matcher) => Self::PeerCertificate(matcher.clone()),
             Self::All(matcher) => Self::All(matcher.clone()),
             Self::Any(matcher) => Self::Any(matcher.clone()),
             Self::Custom(matcher) => Self::Custom(matcher.clone()),
@@ -109,6 +116,10 @@ impl<State, Socket> fmt::Debug for SocketMatcherKind<State, Socket> {
             Self::PrivateIpNet(matcher) => f.debug_tuple("PrivateIpNet").field(matcher).finish(),
             Self::Port(matcher) => f.debug_tuple("Port").field(matcher).finish(),
             Self::IpNet(matcher) => f.debug_tuple("IpNet").field(matcher).finish(),
+            #[cfg(feature = "tls")]
+            Self::PeerCertificate(matcher) => {
+                f.debug_tuple("PeerCertificate").field(matcher).finish()
+            }
             Self::All(matcher) => f.debug_tuple("All").field(matcher).finish(),
             Self::Any(matcher) => f.debug_tuple("Any").field(matcher).finish(),
             Self::Custom(_) => f.debug_tuple("Custom").finish(),
@@ -364,6 +375,39 @@ impl<State, Socket> SocketMatcher<State, Socket> {
         self.or(Self::optional_private_ip_net())
     }
 
+    #[cfg(feature = "tls")]
+    /// create a new peer certificate matcher to match on the certificate provided by the peer.
+    ///
+    /// See [`PeerCertificateMatcher`] for more information.
+    ///
+    /// [`PeerCertificateMatcher`]: crate::tls::server::PeerCertificateMatcher
+    pub fn peer_certificate(matcher: crate::tls::server::PeerCertificateMatcher) -> Self {
+        Self {
+            kind: SocketMatcherKind::PeerCertificate(matcher),
+            negate: false,
+        }
+    }
+
+    #[cfg(feature = "tls")]
+    /// Add a new peer certificate matcher to the existing [`SocketMatcher`] to also match on the certificate provided by the peer.
+    ///
+    /// See [`PeerCertificateMatcher`] for more information.
+    ///
+    /// [`PeerCertificateMatcher`]: crate::tls::server::PeerCertificateMatcher
+    pub fn and_peer_certificate(self, matcher: crate::tls::server::PeerCertificateMatcher) -> Self {
+        self.and(Self::peer_certificate(matcher))
+    }
+
+    #[cfg(feature = "tls")]
+    /// Add a new peer certificate matcher to the existing [`SocketMatcher`] as an alternative matcher to match on the certificate provided by the peer.
+    ///
+    /// See [`PeerCertificateMatcher`] for more information.
+    ///
+    /// [`PeerCertificateMatcher`]: crate::tls::server::PeerCertificateMatcher
+    pub fn or_peer_certificate(self, matcher: crate::tls::server::PeerCertificateMatcher) -> Self {
+        self.or(Self::peer_certificate(matcher))
+    }
+
     /// Create a matcher that matches according to a custom predicate.
     ///
     /// See [`rama_core::matcher::Matcher`] for more information.
@@ -455,6 +499,8 @@ where
             SocketMatcherKind::All(matchers) => matchers.iter().matches_and(ext, ctx, req),
             SocketMatcherKind::Any(matchers) => matchers.iter().matches_or(ext, ctx, req),
             SocketMatcherKind::Port(matcher) => matcher.matches(ext, ctx, req),
+            #[cfg(feature = "tls")]
+            SocketMatcherKind::PeerCertificate(matcher) => matcher.matches(ext, ctx, req),
             SocketMatcherKind::Custom(matcher) => matcher.matches(ext, ctx, req),
         }
     }
@@ -494,6 +540,8 @@ where
             SocketMatcherKind::Loopback(matcher) => matcher.matches(ext, ctx, stream),
             SocketMatcherKind::PrivateIpNet(matcher) => matcher.matches(ext, ctx, stream),
             SocketMatcherKind::Port(matcher) => matcher.matches(ext, ctx, stream),
+            #[cfg(feature = "tls")]
+            SocketMatcherKind::PeerCertificate(matcher) => matcher.matches(ext, ctx, stream),
             SocketMatcherKind::All(matchers) => matchers.iter().matches_and(ext, ctx, stream),
             SocketMatcherKind::Any(matchers) => matchers.iter().matches_or(ext, ctx, stream),
             SocketMatcherKind::Custom(matcher) => matcher.matches(ext, ctx, stream),
@@ -1,5 +1,8 @@
-use super::{merge_client_hello_lists, ClientHelloExtension};
-use crate::tls::{CipherSuite, CompressionAlgorithm, DataEncoding, KeyLogIntent};
+use super::{
+    merge_client_hello_lists, ClientHelloExtension, ClientSessionStore, DynamicVerifier, SpkiPinSet,
+};
+use crate::tls::{CipherSuite, CompressionAlgorithm, DataEncoding, EchConfigList, KeyLogIntent};
+use std::sync::Arc;
 
 #[derive(Debug, Clone, Default)]
 /// Common API to configure a TLS Client
@@ -23,6 +26,12 @@ pub struct ClientConfig {
     pub key_logger: Option<KeyLogIntent>,
     /// if enabled server certificates will be stored in [`NegotiatedTlsParameters`]
     pub store_server_certificate_chain: bool,
+    /// optional store used to resume sessions with previously visited servers,
+    /// shared by all connections made using this config
+    pub session_store: Option<ClientSessionStore>,
+    /// optionally offer Encrypted Client Hello (ECH),
+    /// either for real or as GREASE
+    pub ech: Option<EchMode>,
 }
 
 impl ClientConfig {
@@ -53,6 +62,14 @@ impl ClientConfig {
         if let Some(key_logger) = other.key_logger {
             self.key_logger = Some(key_logger);
         }
+
+        if let Some(session_store) = other.session_store {
+            self.session_store = Some(session_store);
+        }
+
+        if let Some(ech) = other.ech {
+            self.ech = Some(ech);
+        }
     }
 }
 
@@ -74,8 +91,11 @@ pub struct ClientAuthData {
     pub cert_chain: DataEncoding,
 }
 
-#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[derive(Debug, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
 /// Mode of server verification by a (tls) client
+///
+/// The data of the custom modes is shared (using an [`Arc`]),
+/// such that the mode remains cheap to clone.
 pub enum ServerVerifyMode {
     #[default]
     /// Use the default verification approach as defined
@@ -83,6 +103,38 @@ pub enum ServerVerifyMode {
     Auto,
     /// Explicitly disable server verification (if possible)
     Disable,
+    /// Verify the server certificate chain using the given
+    /// root certificates instead of the default root store.
+    RootStore(Arc<DataEncoding>),
+    /// Verify the server certificate chain and require
+    /// at least one of its public keys to match the given pins.
+    Pinned {
+        /// pins of which at least one has to match
+        pins: Arc<SpkiPinSet>,
+        /// optional root certificates to use instead of the default root store
+        root_store: Option<Arc<DataEncoding>>,
+    },
+    /// Verify the server certificate chain using a custom (async) verifier,
+    /// instead of the default verification of the used (tls) client.
+    ///
+    /// Not supported in combination with client auth by the rustls client,
+    /// see [`DynamicCertVerifier`] for more information.
+    ///
+    /// [`DynamicCertVerifier`]: super::DynamicCertVerifier
+    Custom(DynamicVerifier),
+}
+
+#[derive(Debug, Clone)]
+/// Mode of Encrypted Client Hello (ECH) used by a (tls) client.
+pub enum EchMode {
+    /// Offer a GREASE ECH extension, such that the client
+    /// looks like an ECH capable client, without actually using ECH.
+    Grease,
+    /// Encrypt the inner ClientHello using one of the configs in the given list.
+    ///
+    /// The list is typically resolved from the DNS HTTPS record of the server,
+    /// e.g. using `DnsResolver::https_lookup` from `rama-dns`.
+    Enable(EchConfigList),
 }
 
 impl From<super::ClientHello> for ClientConfig {
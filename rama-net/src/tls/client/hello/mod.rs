@@ -27,6 +27,31 @@ pub struct ClientHello {
 }
 
 impl ClientHello {
+    /// Try to parse a [`ClientHello`] from a raw tls handshake message,
+    /// including its 4 byte handshake header.
+    ///
+    /// This is for example useful to parse a ClientHello observed
+    /// by a message callback of a tls implementation.
+    #[cfg(any(test, feature = "boring"))]
+    pub fn try_from_handshake_message(msg: &[u8]) -> Result<Self, rama_core::error::OpaqueError> {
+        use crate::tls::client::parser::parse_client_hello;
+        use rama_core::error::OpaqueError;
+
+        match msg {
+            [1, a, b, c, body @ ..]
+                if u32::from_be_bytes([0, *a, *b, *c]) as usize == body.len() =>
+            {
+                parse_client_hello(body)
+            }
+            [1, ..] => Err(OpaqueError::from_display(
+                "parse client hello handshake message: invalid length",
+            )),
+            _ => Err(OpaqueError::from_display(
+                "parse client hello handshake message: not a client hello",
+            )),
+        }
+    }
+
     /// Return all [`ProtocolVersion`]s defined in this [`ClientHello`].
     pub fn protocol_version(&self) -> ProtocolVersion {
         self.protocol_version
@@ -20,7 +20,18 @@ pub(crate) use parser::parse_client_hello;
 
 mod config;
 #[doc(inline)]
-pub use config::{ClientAuth, ClientAuthData, ClientConfig, ServerVerifyMode};
+pub use config::{ClientAuth, ClientAuthData, ClientConfig, EchMode, ServerVerifyMode};
+
+mod session;
+#[doc(inline)]
+pub use session::{
+    ClientSession, ClientSessionKey, ClientSessionStore, MemoryClientSessionStore,
+    StoresClientSessions,
+};
+
+mod verify;
+#[doc(inline)]
+pub use verify::{DynamicCertVerifier, DynamicVerifier, SpkiPin, SpkiPinSet};
 
 use super::{ApplicationProtocol, DataEncoding, ProtocolVersion};
 
@@ -40,6 +51,12 @@ pub struct NegotiatedTlsParameters {
     pub application_layer_protocol: Option<ApplicationProtocol>,
     /// Certificate chain provided the peer (only stored if config requested this)
     pub peer_certificate_chain: Option<DataEncoding>,
+    /// `true` in case the session was resumed from a previous session,
+    /// instead of being established using a full handshake.
+    pub session_resumed: bool,
+    /// `true` in case Encrypted Client Hello (ECH) was offered and accepted,
+    /// meaning the inner (encrypted) ClientHello was used for the handshake.
+    pub ech_accepted: bool,
 }
 
 /// Merge extension lists A and B, with
@@ -323,6 +323,32 @@ mod tests {
         assert!(parse_client_hello(&[]).is_err());
     }
 
+    #[test]
+    fn test_client_hello_try_from_handshake_message() {
+        let mut body = vec![0x03, 0x03];
+        body.extend_from_slice(&[0; 32]); // random
+        body.extend_from_slice(&[0x00, 0x00, 0x02, 0x13, 0x01, 0x01, 0x00]);
+
+        let mut msg = vec![0x01, 0x00, 0x00, body.len() as u8];
+        msg.extend_from_slice(&body);
+        let client_hello = ClientHello::try_from_handshake_message(&msg).unwrap();
+        assert_eq!(
+            client_hello.cipher_suites(),
+            &[CipherSuite::TLS13_AES_128_GCM_SHA256]
+        );
+        assert!(client_hello.extensions().is_empty());
+
+        // not a client hello
+        msg[0] = 0x02;
+        assert!(ClientHello::try_from_handshake_message(&msg).is_err());
+
+        // invalid length
+        msg[0] = 0x01;
+        msg[3] += 1;
+        assert!(ClientHello::try_from_handshake_message(&msg).is_err());
+        assert!(ClientHello::try_from_handshake_message(&msg[..3]).is_err());
+    }
+
     #[test]
     fn test_parse_client_hello_pcap_dump_apple_itunes_bytes_success() {
         let client_hello = parse_client_hello(&[
@@ -0,0 +1,331 @@
+use crate::{address::Host, tls::ProtocolVersion};
+use std::{
+    any::Any,
+    collections::{HashMap, VecDeque},
+    fmt,
+    num::NonZeroUsize,
+    sync::{Arc, Mutex},
+};
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+/// Key used by a [`ClientSessionStore`] to store and lookup sessions.
+///
+/// Sessions are keyed by both the server name (SNI) and the offered ALPN protocols,
+/// as a session negotiated for one application protocol should not be used
+/// to resume a connection for which other application protocols are offered.
+pub struct ClientSessionKey {
+    server_name: Option<Host>,
+    alpn_protocols: Vec<Vec<u8>>,
+    protocol_version: Option<ProtocolVersion>,
+}
+
+impl ClientSessionKey {
+    /// Create a new [`ClientSessionKey`] for the given server name and
+    /// (raw) ALPN protocols, in the order as offered by the client.
+    pub fn new(
+        server_name: Option<Host>,
+        alpn_protocols: impl IntoIterator<Item: AsRef<[u8]>>,
+    ) -> Self {
+        Self {
+            server_name,
+            alpn_protocols: alpn_protocols
+                .into_iter()
+                .map(|p| p.as_ref().to_vec())
+                .collect(),
+            protocol_version: None,
+        }
+    }
+
+    /// Scope this key to sessions of the given [`ProtocolVersion`].
+    ///
+    /// Used by (tls) clients which store the sessions of different versions
+    /// apart, e.g. as tls 1.3 tickets are used only once, unlike tls 1.2 sessions.
+    pub fn with_protocol_version(mut self, version: ProtocolVersion) -> Self {
+        self.protocol_version = Some(version);
+        self
+    }
+
+    /// Return the server name (SNI) of this key, if any.
+    pub fn server_name(&self) -> Option<&Host> {
+        self.server_name.as_ref()
+    }
+
+    /// Return the raw ALPN protocols of this key.
+    pub fn alpn_protocols(&self) -> &[Vec<u8>] {
+        &self.alpn_protocols
+    }
+
+    /// Return the [`ProtocolVersion`] this key is scoped to, if any.
+    pub fn protocol_version(&self) -> Option<ProtocolVersion> {
+        self.protocol_version
+    }
+}
+
+#[derive(Clone)]
+/// An opaque (tls) client session, stored by a [`ClientSessionStore`].
+///
+/// The content is specific to the tls implementation which created it,
+/// and can only be used to resume a session by that same implementation.
+pub struct ClientSession(Arc<dyn Any + Send + Sync>);
+
+impl ClientSession {
+    /// Create a new [`ClientSession`] from an implementation specific value.
+    pub fn new<T: Any + Send + Sync>(value: T) -> Self {
+        Self(Arc::new(value))
+    }
+
+    /// Return a reference to the implementation specific value,
+    /// if it is of type `T`.
+    pub fn downcast_ref<T: Any + Send + Sync>(&self) -> Option<&T> {
+        self.0.downcast_ref()
+    }
+
+    /// Consume this [`ClientSession`] into the implementation specific value,
+    /// if it is of type `T` and not shared with any clones of this session.
+    pub fn try_into_inner<T: Any + Send + Sync>(self) -> Result<T, Self> {
+        match self.0.downcast::<T>() {
+            Ok(value) => match Arc::try_unwrap(value) {
+                Ok(value) => Ok(value),
+                Err(value) => Err(Self(value)),
+            },
+            Err(value) => Err(Self(value)),
+        }
+    }
+}
+
+impl fmt::Debug for ClientSession {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ClientSession").finish()
+    }
+}
+
+/// Trait that needs to be implemented by client session stores,
+/// used by the (tls) client to resume sessions with servers.
+///
+/// Sessions are stored after a handshake (or when received from the server
+/// after the handshake in case of tls 1.3), and looked up when connecting.
+pub trait StoresClientSessions: Send + Sync + 'static {
+    /// Store a session for the given key.
+    fn insert(&self, key: ClientSessionKey, session: ClientSession);
+
+    /// Return the most recent session for the given key,
+    /// without removing it from the store.
+    fn get(&self, key: &ClientSessionKey) -> Option<ClientSession>;
+
+    /// Remove and return the most recent session for the given key.
+    ///
+    /// Used for sessions which are meant to be used only once,
+    /// such as tls 1.3 tickets.
+    fn take(&self, key: &ClientSessionKey) -> Option<ClientSession>;
+
+    /// Remove all sessions for the given key.
+    fn remove(&self, key: &ClientSessionKey);
+}
+
+#[derive(Clone)]
+/// Shared store of (tls) client sessions, used for session resumption.
+///
+/// Clones of a [`ClientSessionStore`] share the same underlying store.
+pub struct ClientSessionStore {
+    store: Arc<dyn StoresClientSessions>,
+}
+
+impl ClientSessionStore {
+    /// Create a new [`ClientSessionStore`] for the given [`StoresClientSessions`] implementation.
+    pub fn new<T: StoresClientSessions>(store: T) -> Self {
+        Self {
+            store: Arc::new(store),
+        }
+    }
+
+    /// Create a new in-memory [`ClientSessionStore`],
+    /// see [`MemoryClientSessionStore`] for more information.
+    pub fn memory(max_keys: NonZeroUsize) -> Self {
+        Self::new(MemoryClientSessionStore::new(max_keys))
+    }
+
+    /// Store a session for the given key.
+    pub fn insert(&self, key: ClientSessionKey, session: ClientSession) {
+        self.store.insert(key, session)
+    }
+
+    /// Return the most recent session for the given key, without removing it.
+    pub fn get(&self, key: &ClientSessionKey) -> Option<ClientSession> {
+        self.store.get(key)
+    }
+
+    /// Remove and return the most recent session for the given key.
+    pub fn take(&self, key: &ClientSessionKey) -> Option<ClientSession> {
+        self.store.take(key)
+    }
+
+    /// Remove all sessions for the given key.
+    pub fn remove(&self, key: &ClientSessionKey) {
+        self.store.remove(key)
+    }
+}
+
+impl fmt::Debug for ClientSessionStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ClientSessionStore").finish()
+    }
+}
+
+/// In-memory [`StoresClientSessions`] implementation.
+///
+/// Keeps at most `max_keys` keys, evicting the oldest key first,
+/// and a limited number of sessions per key.
+pub struct MemoryClientSessionStore {
+    max_keys: usize,
+    max_sessions_per_key: usize,
+    state: Mutex<MemoryClientSessionState>,
+}
+
+#[derive(Default)]
+struct MemoryClientSessionState {
+    sessions: HashMap<ClientSessionKey, VecDeque<ClientSession>>,
+    insert_order: VecDeque<ClientSessionKey>,
+}
+
+impl MemoryClientSessionStore {
+    const DEFAULT_MAX_SESSIONS_PER_KEY: usize = 8;
+
+    /// Create a new [`MemoryClientSessionStore`] which stores sessions for at most `max_keys` keys.
+    pub fn new(max_keys: NonZeroUsize) -> Self {
+        Self {
+            max_keys: max_keys.get(),
+            max_sessions_per_key: Self::DEFAULT_MAX_SESSIONS_PER_KEY,
+            state: Mutex::default(),
+        }
+    }
+
+    /// Set the maximum amount of sessions stored per key (8 by default).
+    pub fn with_max_sessions_per_key(mut self, max: NonZeroUsize) -> Self {
+        self.max_sessions_per_key = max.get();
+        self
+    }
+}
+
+impl fmt::Debug for MemoryClientSessionStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MemoryClientSessionStore")
+            .field("max_keys", &self.max_keys)
+            .field("max_sessions_per_key", &self.max_sessions_per_key)
+            .finish()
+    }
+}
+
+impl StoresClientSessions for MemoryClientSessionStore {
+    fn insert(&self, key: ClientSessionKey, session: ClientSession) {
+        let mut state = self.state.lock().unwrap_or_else(|err| err.into_inner());
+        let state = &mut *state;
+
+        if !state.sessions.contains_key(&key) {
+            while state.sessions.len() >= self.max_keys {
+                let Some(oldest) = state.insert_order.pop_front() else {
+                    break;
+                };
+                state.sessions.remove(&oldest);
+            }
+            state.insert_order.push_back(key.clone());
+        }
+
+        let sessions = state.sessions.entry(key).or_default();
+        if sessions.len() >= self.max_sessions_per_key {
+            sessions.pop_front();
+        }
+        sessions.push_back(session);
+    }
+
+    fn get(&self, key: &ClientSessionKey) -> Option<ClientSession> {
+        let state = self.state.lock().unwrap_or_else(|err| err.into_inner());
+        state.sessions.get(key).and_then(|s| s.back().cloned())
+    }
+
+    fn take(&self, key: &ClientSessionKey) -> Option<ClientSession> {
+        let mut state = self.state.lock().unwrap_or_else(|err| err.into_inner());
+        state.sessions.get_mut(key).and_then(|s| s.pop_back())
+    }
+
+    fn remove(&self, key: &ClientSessionKey) {
+        let mut state = self.state.lock().unwrap_or_else(|err| err.into_inner());
+        if state.sessions.remove(key).is_some() {
+            state.insert_order.retain(|k| k != key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(name: &'static str) -> ClientSessionKey {
+        ClientSessionKey::new(Some(name.parse().unwrap()), [b"h2"])
+    }
+
+    #[test]
+    fn test_client_session_downcast() {
+        let session = ClientSession::new(42u32);
+        assert_eq!(Some(&42), session.downcast_ref::<u32>());
+        assert!(session.downcast_ref::<u64>().is_none());
+
+        let session = session.try_into_inner::<u64>().unwrap_err();
+        let clone = session.clone();
+        let session = session.try_into_inner::<u32>().unwrap_err();
+        drop(clone);
+        assert_eq!(42, session.try_into_inner::<u32>().unwrap());
+    }
+
+    #[test]
+    fn test_memory_client_session_store_keyed_by_alpn() {
+        let store = ClientSessionStore::memory(NonZeroUsize::new(4).unwrap());
+        store.insert(key("example.com"), ClientSession::new(1u8));
+
+        let http1_key = ClientSessionKey::new(Some("example.com".parse().unwrap()), [b"http/1.1"]);
+        assert!(store.get(&http1_key).is_none());
+
+        assert_eq!(
+            Some(&1u8),
+            store
+                .get(&key("example.com"))
+                .as_ref()
+                .and_then(|s| s.downcast_ref())
+        );
+        assert!(store.take(&key("example.com")).is_some());
+        assert!(store.take(&key("example.com")).is_none());
+    }
+
+    #[test]
+    fn test_memory_client_session_store_eviction() {
+        let store = MemoryClientSessionStore::new(NonZeroUsize::new(2).unwrap())
+            .with_max_sessions_per_key(NonZeroUsize::new(2).unwrap());
+
+        for i in 0..3u8 {
+            store.insert(key("a.example.com"), ClientSession::new(i));
+        }
+        assert_eq!(
+            Some(&2u8),
+            store
+                .take(&key("a.example.com"))
+                .as_ref()
+                .and_then(|s| s.downcast_ref())
+        );
+        assert_eq!(
+            Some(&1u8),
+            store
+                .take(&key("a.example.com"))
+                .as_ref()
+                .and_then(|s| s.downcast_ref())
+        );
+        assert!(store.take(&key("a.example.com")).is_none());
+
+        store.insert(key("b.example.com"), ClientSession::new(0u8));
+        store.insert(key("c.example.com"), ClientSession::new(0u8));
+        assert!(store.get(&key("a.example.com")).is_none());
+        assert!(store.get(&key("b.example.com")).is_some());
+        assert!(store.get(&key("c.example.com")).is_some());
+
+        store.remove(&key("b.example.com"));
+        assert!(store.get(&key("b.example.com")).is_none());
+    }
+}
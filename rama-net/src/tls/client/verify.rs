@@ -0,0 +1,307 @@
+use crate::address::Host;
+use crate::tls::DataEncoding;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine as _;
+use rama_core::error::{ErrorContext, OpaqueError};
+use sha2::{Digest, Sha256};
+use std::{fmt, future::Future, pin::Pin, str::FromStr, sync::Arc};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+/// SHA-256 digest of the DER-encoded SubjectPublicKeyInfo (SPKI) of a certificate.
+///
+/// Displayed and parsed in the `sha256//<base64>` format (as used by curl),
+/// the `sha256//` prefix is optional when parsing.
+pub struct SpkiPin([u8; 32]);
+
+impl SpkiPin {
+    /// Create a new [`SpkiPin`] from the raw SHA-256 digest.
+    pub const fn new(digest: [u8; 32]) -> Self {
+        Self(digest)
+    }
+
+    /// Compute the [`SpkiPin`] for the given DER-encoded SubjectPublicKeyInfo.
+    pub fn from_spki_der(spki: &[u8]) -> Self {
+        Self(Sha256::digest(spki).into())
+    }
+
+    /// Compute the [`SpkiPin`] for the given DER-encoded (x509) certificate.
+    pub fn from_cert_der(cert: &[u8]) -> Result<Self, OpaqueError> {
+        let (_, cert) = x509_parser::parse_x509_certificate(cert)
+            .map_err(|err| OpaqueError::from_display(err.to_string()))
+            .context("parse x509 certificate from DER")?;
+        Ok(Self::from_spki_der(cert.public_key().raw))
+    }
+
+    /// Return the raw SHA-256 digest of this [`SpkiPin`].
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+}
+
+impl fmt::Display for SpkiPin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "sha256//{}", BASE64.encode(self.0))
+    }
+}
+
+impl FromStr for SpkiPin {
+    type Err = OpaqueError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let encoded = s.strip_prefix("sha256//").unwrap_or(s);
+        let digest = BASE64
+            .decode(encoded)
+            .context("decode base64 spki pin")?
+            .try_into()
+            .map_err(|_| OpaqueError::from_display("spki pin is not a SHA-256 digest"))?;
+        Ok(Self(digest))
+    }
+}
+
+impl serde::Serialize for SpkiPin {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        self.to_string().serialize(serializer)
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for SpkiPin {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let s = <std::borrow::Cow<'de, str>>::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+/// A set of [`SpkiPin`]s that a server certificate chain has to match.
+///
+/// Backup pins are accepted the same as the primary pins,
+/// but are kept apart so that keys which are not (yet) in use
+/// can be pinned ahead of a key rotation.
+pub struct SpkiPinSet {
+    pins: Vec<SpkiPin>,
+    backup_pins: Vec<SpkiPin>,
+}
+
+impl SpkiPinSet {
+    /// Create a new [`SpkiPinSet`] from the given primary pins.
+    pub fn new(pins: impl IntoIterator<Item = SpkiPin>) -> Self {
+        Self {
+            pins: pins.into_iter().collect(),
+            backup_pins: Vec::new(),
+        }
+    }
+
+    /// Add backup pins to this [`SpkiPinSet`].
+    pub fn with_backup_pins(mut self, pins: impl IntoIterator<Item = SpkiPin>) -> Self {
+        self.backup_pins.extend(pins);
+        self
+    }
+
+    /// Return the primary pins of this [`SpkiPinSet`].
+    pub fn pins(&self) -> &[SpkiPin] {
+        &self.pins
+    }
+
+    /// Return the backup pins of this [`SpkiPinSet`].
+    pub fn backup_pins(&self) -> &[SpkiPin] {
+        &self.backup_pins
+    }
+
+    /// Returns `true` if there are no pins (primary or backup) in this set.
+    pub fn is_empty(&self) -> bool {
+        self.pins.is_empty() && self.backup_pins.is_empty()
+    }
+
+    /// Returns `true` if the given pin is a primary or backup pin of this set.
+    pub fn contains(&self, pin: &SpkiPin) -> bool {
+        self.pins.contains(pin) || self.backup_pins.contains(pin)
+    }
+
+    /// Verify that at least one of the given DER-encoded certificates
+    /// has a public key which is pinned by this set.
+    pub fn verify_cert_chain<'a>(
+        &self,
+        cert_chain: impl IntoIterator<Item = &'a [u8]>,
+    ) -> Result<(), OpaqueError> {
+        for cert in cert_chain {
+            let pin = SpkiPin::from_cert_der(cert).context("compute spki pin of cert in chain")?;
+            if self.contains(&pin) {
+                tracing::trace!(%pin, "spki pin set: cert chain matched pin");
+                return Ok(());
+            }
+        }
+        Err(OpaqueError::from_display(
+            "no certificate in chain matches a pinned public key",
+        ))
+    }
+}
+
+#[derive(Clone)]
+/// Dynamic verifier which internally contains the dyn verifier
+pub struct DynamicVerifier {
+    /// Verifier not public in case we want to migrate away from dyn approach to alternative (eg channels)
+    verifier: Arc<dyn DynDynamicCertVerifier + Send + Sync>,
+}
+
+impl DynamicVerifier {
+    /// Create a new [`DynamicVerifier`] for the given [`DynamicCertVerifier`].
+    pub fn new<T: DynamicCertVerifier>(verifier: T) -> Self {
+        Self {
+            verifier: Arc::new(verifier),
+        }
+    }
+
+    /// Verify the server certificate chain, see [`DynamicCertVerifier::verify_server_cert`].
+    pub async fn verify_server_cert(
+        &self,
+        cert_chain: DataEncoding,
+        server_name: Option<Host>,
+    ) -> Result<(), OpaqueError> {
+        self.verifier
+            .verify_server_cert(cert_chain, server_name)
+            .await
+    }
+}
+
+// verifiers are compared by identity, as a clone verifies the same way as its original
+
+impl PartialEq for DynamicVerifier {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.verifier, &other.verifier)
+    }
+}
+
+impl Eq for DynamicVerifier {}
+
+impl PartialOrd for DynamicVerifier {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for DynamicVerifier {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.addr().cmp(&other.addr())
+    }
+}
+
+impl std::hash::Hash for DynamicVerifier {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.addr().hash(state)
+    }
+}
+
+impl DynamicVerifier {
+    fn addr(&self) -> usize {
+        Arc::as_ptr(&self.verifier) as *const () as usize
+    }
+}
+
+impl fmt::Debug for DynamicVerifier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DynamicVerifier").finish()
+    }
+}
+
+impl<T> From<T> for DynamicVerifier
+where
+    T: DynamicCertVerifier,
+{
+    fn from(verifier: T) -> Self {
+        Self::new(verifier)
+    }
+}
+
+/// Trait that needs to be implemented by server cert verifiers to support
+/// verifying the server certificate chain (asynchronously) in a custom manner.
+///
+/// The default chain verification of the (tls) client is not applied
+/// when such a verifier is used, the handshake signatures are still verified.
+///
+/// The verifier is driven by the task establishing the connection, either during the
+/// handshake (boring) or right after it, before the connection is used (rustls).
+///
+/// In the latter case the handshake is already completed when the verifier runs,
+/// such that the server has received the client's Finished message (and possibly
+/// issued session tickets) by then. On rejection the rustls client drops the sessions
+/// stored for the server and closes the connection, and as a client certificate would
+/// already be sent, it refuses to combine such a verifier with client auth.
+pub trait DynamicCertVerifier: Send + Sync + 'static {
+    /// Verify the server certificate chain, leaf certificate first,
+    /// as a [`DataEncoding::DerStack`], together with the server name (SNI)
+    /// that was requested by the client, if any.
+    fn verify_server_cert(
+        &self,
+        cert_chain: DataEncoding,
+        server_name: Option<Host>,
+    ) -> impl Future<Output = Result<(), OpaqueError>> + Send + '_;
+}
+
+/// Internal trait to support dynamic dispatch of trait with async fn.
+/// See trait [`rama_core::service::svc::DynService`] for more info about this pattern.
+trait DynDynamicCertVerifier {
+    fn verify_server_cert(
+        &self,
+        cert_chain: DataEncoding,
+        server_name: Option<Host>,
+    ) -> Pin<Box<dyn Future<Output = Result<(), OpaqueError>> + Send + '_>>;
+}
+
+impl<T> DynDynamicCertVerifier for T
+where
+    T: DynamicCertVerifier,
+{
+    fn verify_server_cert(
+        &self,
+        cert_chain: DataEncoding,
+        server_name: Option<Host>,
+    ) -> Pin<Box<dyn Future<Output = Result<(), OpaqueError>> + Send + '_>> {
+        Box::pin(self.verify_server_cert(cert_chain, server_name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spki_pin_parse_and_display() {
+        let pin = SpkiPin::new([7; 32]);
+        let s = pin.to_string();
+        assert!(s.starts_with("sha256//"));
+        assert_eq!(pin, s.parse().unwrap());
+        assert_eq!(pin, s.trim_start_matches("sha256//").parse().unwrap());
+    }
+
+    #[test]
+    fn test_spki_pin_parse_invalid() {
+        assert!("sha256//not-base64!".parse::<SpkiPin>().is_err());
+        assert!("sha256//AAAA".parse::<SpkiPin>().is_err());
+    }
+
+    #[test]
+    fn test_spki_pin_set_contains() {
+        let primary = SpkiPin::new([1; 32]);
+        let backup = SpkiPin::new([2; 32]);
+        let set = SpkiPinSet::new([primary]).with_backup_pins([backup]);
+        assert!(set.contains(&primary));
+        assert!(set.contains(&backup));
+        assert!(!set.contains(&SpkiPin::new([3; 32])));
+        assert!(!set.is_empty());
+        assert!(SpkiPinSet::default().is_empty());
+    }
+
+    #[test]
+    fn test_spki_pin_set_verify_invalid_cert() {
+        let set = SpkiPinSet::new([SpkiPin::new([1; 32])]);
+        assert!(set.verify_cert_chain([&b"not a cert"[..]]).is_err());
+        assert!(set.verify_cert_chain(std::iter::empty()).is_err());
+    }
+}
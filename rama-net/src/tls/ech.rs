@@ -0,0 +1,252 @@
+use crate::address::Domain;
+use rama_core::error::OpaqueError;
+use std::fmt;
+
+/// ECHConfig version as defined in the ECH draft used by all current implementations.
+const ECH_CONFIG_VERSION: u16 = 0xfe0d;
+
+/// HPKE KEM identifier of `DHKEM(X25519, HKDF-SHA256)`.
+const HPKE_KEM_X25519_HKDF_SHA256: u16 = 0x0020;
+
+/// HPKE (KDF, AEAD) identifiers supported by the configs created by rama:
+/// `HKDF-SHA256` combined with `AES-128-GCM` or `ChaCha20Poly1305`.
+const HPKE_CIPHER_SUITES: [(u16, u16); 2] = [(0x0001, 0x0001), (0x0001, 0x0003)];
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+/// A single (encoded) Encrypted Client Hello (ECH) configuration,
+/// as published by a server and used by clients to encrypt their inner ClientHello.
+pub struct EchConfig {
+    config_id: u8,
+    encoded: Vec<u8>,
+}
+
+impl EchConfig {
+    /// Create a new [`EchConfig`] for an X25519 public key,
+    /// using the `DHKEM(X25519, HKDF-SHA256)` KEM.
+    ///
+    /// The `public_name` is the name used in the outer (unencrypted) ClientHello,
+    /// and for which the server has to be able to present a valid certificate.
+    pub fn new_x25519(config_id: u8, public_name: &Domain, public_key: [u8; 32]) -> Self {
+        let public_name = public_name.as_str().as_bytes();
+        let public_name_len =
+            u8::try_from(public_name.len()).expect("domain is at most 253 bytes long");
+
+        let mut contents = Vec::with_capacity(64 + public_name.len());
+        contents.push(config_id);
+        contents.extend_from_slice(&HPKE_KEM_X25519_HKDF_SHA256.to_be_bytes());
+        contents.extend_from_slice(&(public_key.len() as u16).to_be_bytes());
+        contents.extend_from_slice(&public_key);
+        contents.extend_from_slice(&((HPKE_CIPHER_SUITES.len() * 4) as u16).to_be_bytes());
+        for (kdf, aead) in HPKE_CIPHER_SUITES {
+            contents.extend_from_slice(&kdf.to_be_bytes());
+            contents.extend_from_slice(&aead.to_be_bytes());
+        }
+        contents.push(0); // maximum_name_length: let the client decide
+        contents.push(public_name_len);
+        contents.extend_from_slice(public_name);
+        contents.extend_from_slice(&0u16.to_be_bytes()); // no extensions
+
+        let mut encoded = Vec::with_capacity(4 + contents.len());
+        encoded.extend_from_slice(&ECH_CONFIG_VERSION.to_be_bytes());
+        encoded.extend_from_slice(&(contents.len() as u16).to_be_bytes());
+        encoded.extend_from_slice(&contents);
+
+        Self { config_id, encoded }
+    }
+
+    /// Try to create an [`EchConfig`] from its raw encoding.
+    pub fn try_from_bytes(encoded: Vec<u8>) -> Result<Self, OpaqueError> {
+        let (version, contents) = split_u16_length_prefixed(&encoded[..])
+            .ok_or_else(|| OpaqueError::from_display("ech config: invalid encoding"))?;
+        if version != ECH_CONFIG_VERSION {
+            return Err(OpaqueError::from_display(format!(
+                "ech config: unsupported version: {version:#06x}"
+            )));
+        }
+        let config_id = *contents
+            .first()
+            .ok_or_else(|| OpaqueError::from_display("ech config: missing config id"))?;
+        Ok(Self { config_id, encoded })
+    }
+
+    /// Return the identifier of this config.
+    pub fn config_id(&self) -> u8 {
+        self.config_id
+    }
+
+    /// Return the raw encoding of this config.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.encoded
+    }
+}
+
+impl fmt::Debug for EchConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EchConfig")
+            .field("config_id", &self.config_id)
+            .field("len", &self.encoded.len())
+            .finish()
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+/// An (encoded) `ECHConfigList`, as found in the `ech` parameter
+/// of a DNS HTTPS record, or configured manually.
+pub struct EchConfigList(Vec<u8>);
+
+impl EchConfigList {
+    /// Try to create an [`EchConfigList`] from its raw encoding,
+    /// e.g. as resolved from a DNS HTTPS record.
+    pub fn try_from_bytes(encoded: Vec<u8>) -> Result<Self, OpaqueError> {
+        let configs = parse_ech_configs(&encoded)?;
+        if configs.is_empty() {
+            return Err(OpaqueError::from_display("ech config list: empty list"));
+        }
+        Ok(Self(encoded))
+    }
+
+    /// Create an [`EchConfigList`] from the given configs.
+    pub fn from_configs<'a>(configs: impl IntoIterator<Item = &'a EchConfig>) -> Self {
+        let mut configs_encoded = Vec::new();
+        for config in configs {
+            configs_encoded.extend_from_slice(config.as_bytes());
+        }
+        let mut encoded = Vec::with_capacity(2 + configs_encoded.len());
+        encoded.extend_from_slice(&(configs_encoded.len() as u16).to_be_bytes());
+        encoded.extend_from_slice(&configs_encoded);
+        Self(encoded)
+    }
+
+    /// Return the configs found in this list.
+    pub fn configs(&self) -> Result<Vec<EchConfig>, OpaqueError> {
+        parse_ech_configs(&self.0)
+    }
+
+    /// Return the raw encoding of this list.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl fmt::Debug for EchConfigList {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("EchConfigList").field(&self.0.len()).finish()
+    }
+}
+
+impl TryFrom<Vec<u8>> for EchConfigList {
+    type Error = OpaqueError;
+
+    fn try_from(value: Vec<u8>) -> Result<Self, Self::Error> {
+        Self::try_from_bytes(value)
+    }
+}
+
+#[derive(Clone)]
+/// An [`EchConfig`] together with its (X25519) private key,
+/// used by a (tls) server to decrypt the inner ClientHello.
+pub struct EchKey {
+    /// the config published for this key
+    pub config: EchConfig,
+    /// raw (X25519) private key
+    pub private_key: Vec<u8>,
+    /// if `true` the config is sent as retry config
+    /// to clients which used an unknown or outdated config
+    pub is_retry_config: bool,
+}
+
+impl fmt::Debug for EchKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EchKey")
+            .field("config", &self.config)
+            .field("is_retry_config", &self.is_retry_config)
+            .finish()
+    }
+}
+
+fn parse_ech_configs(encoded: &[u8]) -> Result<Vec<EchConfig>, OpaqueError> {
+    let mut rest = match encoded {
+        [hi, lo, rest @ ..] if u16::from_be_bytes([*hi, *lo]) as usize == rest.len() => rest,
+        _ => {
+            return Err(OpaqueError::from_display(
+                "ech config list: invalid encoding",
+            ))
+        }
+    };
+
+    let mut configs = Vec::new();
+    while !rest.is_empty() {
+        let config_len = rest
+            .get(2..4)
+            .map(|len| 4 + u16::from_be_bytes([len[0], len[1]]) as usize)
+            .filter(|len| *len <= rest.len())
+            .ok_or_else(|| OpaqueError::from_display("ech config list: truncated config"))?;
+        let (config, tail) = rest.split_at(config_len);
+        configs.push(EchConfig::try_from_bytes(config.to_vec())?);
+        rest = tail;
+    }
+    Ok(configs)
+}
+
+fn split_u16_length_prefixed(i: &[u8]) -> Option<(u16, &[u8])> {
+    let header = u16::from_be_bytes([*i.first()?, *i.get(1)?]);
+    let len = u16::from_be_bytes([*i.get(2)?, *i.get(3)?]) as usize;
+    let contents = i.get(4..)?;
+    (contents.len() == len).then_some((header, contents))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // public key of the X25519 test vector of RFC 7748 (section 6.1)
+    const PUBLIC_KEY: [u8; 32] = [
+        0x85, 0x20, 0xf0, 0x09, 0x89, 0x30, 0xa7, 0x54, 0x74, 0x8b, 0x7d, 0xdc, 0xb4, 0x3e, 0xf7,
+        0x5a, 0x0d, 0xbf, 0x3a, 0x0d, 0x26, 0x38, 0x1a, 0xf4, 0xeb, 0xa4, 0xa9, 0x8e, 0xaa, 0x9b,
+        0x4e, 0x6a,
+    ];
+
+    #[test]
+    fn test_ech_config_encoding() {
+        let config = EchConfig::new_x25519(7, &Domain::from_static("example.com"), PUBLIC_KEY);
+        let bytes = config.as_bytes();
+        assert_eq!(&bytes[..2], &[0xfe, 0x0d]);
+        assert_eq!(
+            u16::from_be_bytes([bytes[2], bytes[3]]) as usize,
+            bytes.len() - 4
+        );
+        assert_eq!(bytes[4], 7);
+        assert_eq!(&bytes[5..7], &[0x00, 0x20]);
+        assert_eq!(&bytes[9..41], &PUBLIC_KEY);
+        assert!(bytes.ends_with(b"\x0bexample.com\x00\x00"));
+
+        let parsed = EchConfig::try_from_bytes(bytes.to_vec()).unwrap();
+        assert_eq!(config, parsed);
+        assert_eq!(7, parsed.config_id());
+    }
+
+    #[test]
+    fn test_ech_config_list_roundtrip() {
+        let a = EchConfig::new_x25519(1, &Domain::from_static("a.example.com"), PUBLIC_KEY);
+        let b = EchConfig::new_x25519(2, &Domain::from_static("b.example.com"), PUBLIC_KEY);
+
+        let list = EchConfigList::from_configs([&a, &b]);
+        let parsed = EchConfigList::try_from_bytes(list.as_bytes().to_vec()).unwrap();
+        assert_eq!(list, parsed);
+
+        assert_eq!(vec![a, b], parsed.configs().unwrap());
+    }
+
+    #[test]
+    fn test_ech_config_list_invalid() {
+        assert!(EchConfigList::try_from_bytes(vec![]).is_err());
+        assert!(EchConfigList::try_from_bytes(vec![0, 0]).is_err());
+        assert!(EchConfigList::try_from_bytes(vec![0, 3, 0xfe, 0x0d, 0]).is_err());
+
+        let config = EchConfig::new_x25519(1, &Domain::from_static("example.com"), PUBLIC_KEY);
+        let list = EchConfigList::from_configs([&config]);
+        let mut truncated = list.as_bytes().to_vec();
+        truncated.pop();
+        assert!(EchConfigList::try_from_bytes(truncated).is_err());
+    }
+}
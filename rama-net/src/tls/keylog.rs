@@ -0,0 +1,211 @@
+use rama_core::error::{ErrorContext, OpaqueError};
+use std::{
+    collections::VecDeque,
+    fmt,
+    num::NonZeroUsize,
+    sync::{Arc, Mutex},
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// A single (tls) key log entry, containing the secret
+/// logged for a connection identified by its client random.
+///
+/// Its [`Display`][`fmt::Display`] implementation produces a line
+/// in the NSS key log format (as used by `SSLKEYLOGFILE`), without trailing newline.
+pub struct KeyLogEntry {
+    /// label of the secret, e.g. `CLIENT_HANDSHAKE_TRAFFIC_SECRET`
+    pub label: String,
+    /// client random of the connection the secret belongs to
+    pub client_random: Vec<u8>,
+    /// the secret itself
+    pub secret: Vec<u8>,
+}
+
+impl KeyLogEntry {
+    /// Try to parse a [`KeyLogEntry`] from a line in the NSS key log format.
+    pub fn try_from_nss_line(line: &str) -> Result<Self, OpaqueError> {
+        let mut parts = line.trim_end().split(' ');
+        let (Some(label), Some(client_random), Some(secret), None) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            return Err(OpaqueError::from_display(
+                "key log entry: expected 3 space separated parts",
+            ));
+        };
+        Ok(Self {
+            label: label.to_owned(),
+            client_random: hex::decode(client_random)
+                .context("key log entry: decode client random")?,
+            secret: hex::decode(secret).context("key log entry: decode secret")?,
+        })
+    }
+}
+
+impl fmt::Display for KeyLogEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {}",
+            self.label,
+            hex::encode(&self.client_random),
+            hex::encode(&self.secret)
+        )
+    }
+}
+
+/// Trait that needs to be implemented by (tls) key log sinks,
+/// such as a channel, file or in-memory buffer.
+///
+/// It is implemented for any `Fn(&KeyLogEntry)` closure,
+/// e.g. to send the entries over a channel.
+pub trait WritesKeyLog: Send + Sync + 'static {
+    /// Write the given key log entry.
+    fn write_key_log(&self, entry: &KeyLogEntry);
+}
+
+impl<F> WritesKeyLog for F
+where
+    F: Fn(&KeyLogEntry) + Send + Sync + 'static,
+{
+    fn write_key_log(&self, entry: &KeyLogEntry) {
+        (self)(entry)
+    }
+}
+
+#[derive(Clone)]
+/// A shareable (tls) key log sink, used by [`KeyLogIntent::Sink`].
+///
+/// [`KeyLogIntent::Sink`]: super::KeyLogIntent::Sink
+pub struct KeyLogSink {
+    sink: Arc<dyn WritesKeyLog>,
+}
+
+impl KeyLogSink {
+    /// Create a new [`KeyLogSink`] for the given [`WritesKeyLog`] implementation.
+    pub fn new<T: WritesKeyLog>(sink: T) -> Self {
+        Self {
+            sink: Arc::new(sink),
+        }
+    }
+
+    /// Write the given key log entry to the sink.
+    pub fn write_key_log(&self, entry: &KeyLogEntry) {
+        self.sink.write_key_log(entry)
+    }
+}
+
+impl fmt::Debug for KeyLogSink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KeyLogSink").finish()
+    }
+}
+
+impl<T: WritesKeyLog> From<T> for KeyLogSink {
+    fn from(sink: T) -> Self {
+        Self::new(sink)
+    }
+}
+
+#[derive(Clone)]
+/// In-memory (ring buffer) [`WritesKeyLog`] implementation,
+/// keeping only the most recent entries.
+///
+/// Clones of a [`MemoryKeyLog`] share the same buffer.
+pub struct MemoryKeyLog {
+    capacity: usize,
+    entries: Arc<Mutex<VecDeque<KeyLogEntry>>>,
+}
+
+impl MemoryKeyLog {
+    /// Create a new [`MemoryKeyLog`] keeping at most `capacity` entries.
+    pub fn new(capacity: NonZeroUsize) -> Self {
+        Self {
+            capacity: capacity.get(),
+            entries: Default::default(),
+        }
+    }
+
+    /// Return a copy of all entries currently in the buffer.
+    pub fn entries(&self) -> Vec<KeyLogEntry> {
+        let entries = self.entries.lock().unwrap_or_else(|err| err.into_inner());
+        entries.iter().cloned().collect()
+    }
+
+    /// Remove and return all entries currently in the buffer.
+    pub fn take(&self) -> Vec<KeyLogEntry> {
+        let mut entries = self.entries.lock().unwrap_or_else(|err| err.into_inner());
+        entries.drain(..).collect()
+    }
+
+    /// Return all entries currently in the buffer in the NSS key log format,
+    /// e.g. to be used as `SSLKEYLOGFILE` content for decrypting a capture.
+    pub fn to_nss_string(&self) -> String {
+        let entries = self.entries.lock().unwrap_or_else(|err| err.into_inner());
+        entries.iter().map(|entry| format!("{entry}\n")).collect()
+    }
+}
+
+impl fmt::Debug for MemoryKeyLog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MemoryKeyLog")
+            .field("capacity", &self.capacity)
+            .finish()
+    }
+}
+
+impl WritesKeyLog for MemoryKeyLog {
+    fn write_key_log(&self, entry: &KeyLogEntry) {
+        let mut entries = self.entries.lock().unwrap_or_else(|err| err.into_inner());
+        if entries.len() >= self.capacity {
+            entries.pop_front();
+        }
+        entries.push_back(entry.clone());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LINE: &str = "CLIENT_TRAFFIC_SECRET_0 0102ff aabbcc";
+
+    #[test]
+    fn test_key_log_entry_nss_line_roundtrip() {
+        let entry = KeyLogEntry::try_from_nss_line(&format!("{LINE}\n")).unwrap();
+        assert_eq!("CLIENT_TRAFFIC_SECRET_0", entry.label);
+        assert_eq!(vec![0x01, 0x02, 0xff], entry.client_random);
+        assert_eq!(vec![0xaa, 0xbb, 0xcc], entry.secret);
+        assert_eq!(LINE, entry.to_string());
+
+        assert!(KeyLogEntry::try_from_nss_line("CLIENT_RANDOM 0102").is_err());
+        assert!(KeyLogEntry::try_from_nss_line("CLIENT_RANDOM 0102 zz").is_err());
+        assert!(KeyLogEntry::try_from_nss_line("CLIENT_RANDOM 01 02 03").is_err());
+    }
+
+    #[test]
+    fn test_memory_key_log_ring_buffer() {
+        let log = MemoryKeyLog::new(NonZeroUsize::new(2).unwrap());
+        let sink = KeyLogSink::new(log.clone());
+        for label in ["A", "B", "C"] {
+            sink.write_key_log(&KeyLogEntry {
+                label: label.to_owned(),
+                client_random: vec![1],
+                secret: vec![2],
+            });
+        }
+        assert_eq!("B 01 02\nC 01 02\n", log.to_nss_string());
+        assert_eq!(2, log.take().len());
+        assert!(log.entries().is_empty());
+    }
+
+    #[test]
+    fn test_closure_key_log_sink() {
+        let (tx, rx) = std::sync::mpsc::channel();
+        let sink = KeyLogSink::new(move |entry: &KeyLogEntry| {
+            let _ = tx.send(entry.clone());
+        });
+        let entry = KeyLogEntry::try_from_nss_line(LINE).unwrap();
+        sink.write_key_log(&entry);
+        assert_eq!(entry, rx.recv().unwrap());
+    }
+}
@@ -11,6 +11,12 @@ pub use enums::{
     ProtocolVersion, SignatureScheme, SupportedGroup,
 };
 
+mod ech;
+pub use ech::{EchConfig, EchConfigList, EchKey};
+
+mod keylog;
+pub use keylog::{KeyLogEntry, KeyLogSink, MemoryKeyLog, WritesKeyLog};
+
 pub mod client;
 pub mod server;
 
@@ -30,6 +36,7 @@ pub struct TlsTunnel {
 /// [`Context`]: rama_core::Context
 pub struct SecureTransport {
     client_hello: Option<client::ClientHello>,
+    outer_client_hello: Option<client::ClientHello>,
 }
 
 impl SecureTransport {
@@ -39,14 +46,43 @@ impl SecureTransport {
     pub fn with_client_hello(hello: client::ClientHello) -> Self {
         Self {
             client_hello: Some(hello),
+            outer_client_hello: None,
         }
     }
 
+    /// Attach the outer [`ClientHello`] to this [`SecureTransport`],
+    /// as sent in the clear by a client whose Encrypted Client Hello (ECH) was accepted.
+    pub fn with_outer_client_hello(mut self, hello: client::ClientHello) -> Self {
+        self.outer_client_hello = Some(hello);
+        self
+    }
+
+    /// Attach the outer [`ClientHello`] to this [`SecureTransport`],
+    /// as sent in the clear by a client whose Encrypted Client Hello (ECH) was accepted.
+    pub fn set_outer_client_hello(&mut self, hello: client::ClientHello) -> &mut Self {
+        self.outer_client_hello = Some(hello);
+        self
+    }
+
     /// Return the [`ClientHello`] used to establish this secure transport,
     /// only available if the tls service stored it.
+    ///
+    /// In case Encrypted Client Hello (ECH) was accepted by the server
+    /// this is the (decrypted) inner ClientHello, see
+    /// [`NegotiatedTlsParameters::ech_accepted`][`client::NegotiatedTlsParameters::ech_accepted`].
     pub fn client_hello(&self) -> Option<&client::ClientHello> {
         self.client_hello.as_ref()
     }
+
+    /// Return the outer [`ClientHello`], as sent in the clear by the client,
+    /// only available if Encrypted Client Hello (ECH) was accepted
+    /// and the tls service stored it.
+    ///
+    /// The outer ClientHello typically only reveals the public name
+    /// of the ECH config as its server name (SNI).
+    pub fn outer_client_hello(&self) -> Option<&client::ClientHello> {
+        self.outer_client_hello.as_ref()
+    }
 }
 
 #[derive(Debug, Clone, Default)]
@@ -62,13 +98,20 @@ pub enum KeyLogIntent {
     Disabled,
     /// Request a keys to be logged to the given file path.
     File(String),
+    /// Request keys to be written to the given (in-process) sink.
+    Sink(KeyLogSink),
+    /// Request the keys of each connection to be collected in a [`MemoryKeyLog`],
+    /// which is added to the [`Context`] of that connection.
+    ///
+    /// [`Context`]: rama_core::Context
+    Connection,
 }
 
 impl KeyLogIntent {
     /// get the file path if intended
     pub fn file_path(&self) -> Option<String> {
         match self {
-            KeyLogIntent::Disabled => None,
+            KeyLogIntent::Disabled | KeyLogIntent::Sink(_) | KeyLogIntent::Connection => None,
             KeyLogIntent::Environment => std::env::var("SSLKEYLOGFILE").ok().clone(),
             KeyLogIntent::File(keylog_filename) => Some(keylog_filename.clone()),
         }
@@ -77,7 +120,7 @@ impl KeyLogIntent {
     /// consume itself into the file path if intended
     pub fn into_file_path(self) -> Option<String> {
         match self {
-            KeyLogIntent::Disabled => None,
+            KeyLogIntent::Disabled | KeyLogIntent::Sink(_) | KeyLogIntent::Connection => None,
             KeyLogIntent::Environment => std::env::var("SSLKEYLOGFILE").ok().clone(),
             KeyLogIntent::File(keylog_filename) => Some(keylog_filename),
         }
@@ -1,6 +1,10 @@
+use super::ServerSessionResumption;
 use crate::{
     address::Host,
-    tls::{client::ClientHello, ApplicationProtocol, DataEncoding, KeyLogIntent, ProtocolVersion},
+    tls::{
+        client::ClientHello, ApplicationProtocol, DataEncoding, EchKey, KeyLogIntent,
+        ProtocolVersion,
+    },
 };
 use rama_core::error::OpaqueError;
 use serde::{Deserialize, Serialize};
@@ -31,7 +35,20 @@ pub struct ServerConfig {
     pub key_logger: KeyLogIntent,
 
     /// store client certificate chain
+    ///
+    /// when the client provided a certificate, its leaf certificate
+    /// is also parsed and stored as a [`PeerCertificateIdentity`]
+    ///
+    /// [`PeerCertificateIdentity`]: super::PeerCertificateIdentity
     pub store_client_certificate_chain: bool,
+
+    /// optionally enable session resumption,
+    /// using a (shared) session cache and/or session tickets
+    pub session_resumption: Option<ServerSessionResumption>,
+
+    /// optional Encrypted Client Hello (ECH) keys,
+    /// used to decrypt the inner ClientHello of ECH capable clients
+    pub ech_keys: Option<Vec<EchKey>>,
 }
 
 impl ServerConfig {
@@ -45,6 +62,8 @@ impl ServerConfig {
             client_verify_mode: ClientVerifyMode::default(),
             key_logger: KeyLogIntent::default(),
             store_client_certificate_chain: false,
+            session_resumption: None,
+            ech_keys: None,
         }
     }
 }
@@ -0,0 +1,421 @@
+use crate::tls::DataEncoding;
+use crate::user::UserId;
+use rama_core::error::{ErrorContext, OpaqueError};
+use sha2::{Digest, Sha256};
+use std::{
+    fmt,
+    net::IpAddr,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use x509_parser::{
+    certificate::X509Certificate,
+    extensions::GeneralName,
+    objects::{oid2sn, oid_registry},
+    x509::X509Name,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// Typed identity of a peer, parsed from the leaf certificate
+/// of the certificate chain it provided during the (tls) handshake.
+///
+/// Inserted in the [`Context`] by the tls acceptors (rustls and boring)
+/// in case [`ServerConfig::store_client_certificate_chain`] is enabled
+/// and the client authenticated itself using a certificate.
+///
+/// [`Context`]: rama_core::Context
+/// [`ServerConfig::store_client_certificate_chain`]: super::ServerConfig::store_client_certificate_chain
+pub struct PeerCertificateIdentity {
+    subject: DistinguishedName,
+    issuer: DistinguishedName,
+    subject_alt_names: Vec<SubjectAltName>,
+    serial_number: Vec<u8>,
+    fingerprint: CertificateFingerprint,
+    not_before: SystemTime,
+    not_after: SystemTime,
+}
+
+impl PeerCertificateIdentity {
+    /// Parse the [`PeerCertificateIdentity`] from a DER-encoded (x509) certificate.
+    pub fn from_der(cert: &[u8]) -> Result<Self, OpaqueError> {
+        let (_, parsed) = x509_parser::parse_x509_certificate(cert)
+            .map_err(|err| OpaqueError::from_display(err.to_string()))
+            .context("parse x509 certificate from DER")?;
+        Self::from_x509(&parsed, cert)
+    }
+
+    /// Parse the [`PeerCertificateIdentity`] from the leaf certificate of the given chain.
+    ///
+    /// The leaf certificate is expected to be the first certificate
+    /// in case of a [`DataEncoding::DerStack`] or [`DataEncoding::Pem`].
+    pub fn from_cert_chain(chain: &DataEncoding) -> Result<Self, OpaqueError> {
+        match chain {
+            DataEncoding::Der(cert) => Self::from_der(cert),
+            DataEncoding::DerStack(certs) => {
+                let leaf = certs
+                    .first()
+                    .ok_or_else(|| OpaqueError::from_display("peer certificate chain is empty"))?;
+                Self::from_der(leaf)
+            }
+            DataEncoding::Pem(raw_pem) => {
+                let (_, pem) = x509_parser::pem::parse_x509_pem(raw_pem.as_bytes())
+                    .map_err(|err| OpaqueError::from_display(err.to_string()))
+                    .context("parse leaf certificate from PEM")?;
+                Self::from_der(&pem.contents)
+            }
+        }
+    }
+
+    fn from_x509(cert: &X509Certificate<'_>, der: &[u8]) -> Result<Self, OpaqueError> {
+        let subject_alt_names = cert
+            .subject_alternative_name()
+            .map_err(|err| OpaqueError::from_display(err.to_string()))
+            .context("parse subject alternative name extension")?
+            .map(|ext| {
+                ext.value
+                    .general_names
+                    .iter()
+                    .filter_map(SubjectAltName::from_general_name)
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let validity = cert.validity();
+
+        Ok(Self {
+            subject: DistinguishedName::from_x509_name(cert.subject()),
+            issuer: DistinguishedName::from_x509_name(cert.issuer()),
+            subject_alt_names,
+            serial_number: cert.raw_serial().to_vec(),
+            fingerprint: CertificateFingerprint::from_cert_der(der),
+            not_before: system_time_from_timestamp(validity.not_before.timestamp()),
+            not_after: system_time_from_timestamp(validity.not_after.timestamp()),
+        })
+    }
+
+    /// Return the subject of the certificate.
+    pub fn subject(&self) -> &DistinguishedName {
+        &self.subject
+    }
+
+    /// Return the issuer of the certificate.
+    pub fn issuer(&self) -> &DistinguishedName {
+        &self.issuer
+    }
+
+    /// Return the subject alternative names (SANs) of the certificate.
+    pub fn subject_alt_names(&self) -> &[SubjectAltName] {
+        &self.subject_alt_names
+    }
+
+    /// Return the raw (big endian) serial number of the certificate.
+    pub fn serial_number(&self) -> &[u8] {
+        &self.serial_number
+    }
+
+    /// Return the SHA-256 fingerprint of the (DER-encoded) certificate.
+    pub fn fingerprint(&self) -> &CertificateFingerprint {
+        &self.fingerprint
+    }
+
+    /// Return the start of the validity period of the certificate.
+    pub fn not_before(&self) -> SystemTime {
+        self.not_before
+    }
+
+    /// Return the end of the validity period of the certificate.
+    pub fn not_after(&self) -> SystemTime {
+        self.not_after
+    }
+
+    /// Returns `true` if the certificate is valid at the given time.
+    pub fn is_valid_at(&self, time: SystemTime) -> bool {
+        self.not_before <= time && time <= self.not_after
+    }
+
+    /// Map this identity to a [`UserId`] using the given [`PeerCertificateUserIdSource`].
+    ///
+    /// Returns `None` in case the certificate has no value for the requested source.
+    pub fn user_id(&self, source: PeerCertificateUserIdSource) -> Option<UserId> {
+        match source {
+            PeerCertificateUserIdSource::SubjectCommonName => self
+                .subject
+                .common_name()
+                .map(|cn| UserId::Username(cn.to_owned())),
+            PeerCertificateUserIdSource::SubjectAltDns => {
+                self.subject_alt_names.iter().find_map(|san| match san {
+                    SubjectAltName::Dns(name) => Some(UserId::Username(name.clone())),
+                    _ => None,
+                })
+            }
+            PeerCertificateUserIdSource::SubjectAltEmail => {
+                self.subject_alt_names.iter().find_map(|san| match san {
+                    SubjectAltName::Email(email) => Some(UserId::Username(email.clone())),
+                    _ => None,
+                })
+            }
+            PeerCertificateUserIdSource::SubjectAltUri => {
+                self.subject_alt_names.iter().find_map(|san| match san {
+                    SubjectAltName::Uri(uri) => Some(UserId::Username(uri.clone())),
+                    _ => None,
+                })
+            }
+            PeerCertificateUserIdSource::Fingerprint => {
+                Some(UserId::Token(self.fingerprint.as_bytes().to_vec()))
+            }
+        }
+    }
+}
+
+fn system_time_from_timestamp(timestamp: i64) -> SystemTime {
+    if timestamp >= 0 {
+        UNIX_EPOCH + Duration::from_secs(timestamp as u64)
+    } else {
+        UNIX_EPOCH - Duration::from_secs(timestamp.unsigned_abs())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+/// The source used to map a [`PeerCertificateIdentity`] to a [`UserId`].
+pub enum PeerCertificateUserIdSource {
+    /// Use the common name (CN) of the subject as [`UserId::Username`].
+    SubjectCommonName,
+    /// Use the first DNS subject alternative name as [`UserId::Username`].
+    SubjectAltDns,
+    /// Use the first email subject alternative name as [`UserId::Username`].
+    SubjectAltEmail,
+    /// Use the first URI subject alternative name as [`UserId::Username`],
+    /// e.g. a SPIFFE ID.
+    SubjectAltUri,
+    /// Use the SHA-256 fingerprint of the certificate as [`UserId::Token`].
+    Fingerprint,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+/// A (x509) distinguished name, such as the subject or issuer of a certificate.
+pub struct DistinguishedName {
+    attributes: Vec<(String, String)>,
+}
+
+impl DistinguishedName {
+    fn from_x509_name(name: &X509Name<'_>) -> Self {
+        let attributes = name
+            .iter_attributes()
+            .filter_map(|attr| {
+                let value = attr.as_str().ok()?;
+                let key = oid2sn(attr.attr_type(), oid_registry())
+                    .map(ToOwned::to_owned)
+                    .unwrap_or_else(|_| attr.attr_type().to_id_string());
+                Some((key, value.to_owned()))
+            })
+            .collect();
+        Self { attributes }
+    }
+
+    /// Return the value of the first attribute with the given (short) name,
+    /// e.g. `CN` or `O`, or the dotted OID for attributes without a short name.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find_map(|(key, value)| key.eq_ignore_ascii_case(name).then_some(value.as_str()))
+    }
+
+    /// Return the common name (CN), if any.
+    pub fn common_name(&self) -> Option<&str> {
+        self.get("CN")
+    }
+
+    /// Return the organization (O), if any.
+    pub fn organization(&self) -> Option<&str> {
+        self.get("O")
+    }
+
+    /// Return the organizational unit (OU), if any.
+    pub fn organizational_unit(&self) -> Option<&str> {
+        self.get("OU")
+    }
+
+    /// Iterate over all (short name, value) attributes of this name, in order.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.attributes
+            .iter()
+            .map(|(key, value)| (key.as_str(), value.as_str()))
+    }
+}
+
+impl fmt::Display for DistinguishedName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, (key, value)) in self.attributes.iter().enumerate() {
+            if index > 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "{key}={value}")?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+/// A subject alternative name (SAN) of a certificate.
+pub enum SubjectAltName {
+    /// A DNS name.
+    Dns(String),
+    /// An (rfc822) email address.
+    Email(String),
+    /// A URI, e.g. a SPIFFE ID.
+    Uri(String),
+    /// An IP address.
+    Ip(IpAddr),
+}
+
+impl SubjectAltName {
+    fn from_general_name(name: &GeneralName<'_>) -> Option<Self> {
+        match name {
+            GeneralName::DNSName(name) => Some(Self::Dns((*name).to_owned())),
+            GeneralName::RFC822Name(email) => Some(Self::Email((*email).to_owned())),
+            GeneralName::URI(uri) => Some(Self::Uri((*uri).to_owned())),
+            GeneralName::IPAddress(bytes) => match bytes.len() {
+                4 => <[u8; 4]>::try_from(*bytes).ok().map(|b| Self::Ip(b.into())),
+                16 => <[u8; 16]>::try_from(*bytes)
+                    .ok()
+                    .map(|b| Self::Ip(b.into())),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for SubjectAltName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Dns(name) => write!(f, "DNS:{name}"),
+            Self::Email(email) => write!(f, "email:{email}"),
+            Self::Uri(uri) => write!(f, "URI:{uri}"),
+            Self::Ip(ip) => write!(f, "IP:{ip}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+/// SHA-256 fingerprint of a DER-encoded certificate.
+///
+/// Displayed as lowercase hex.
+pub struct CertificateFingerprint([u8; 32]);
+
+impl CertificateFingerprint {
+    /// Create a new [`CertificateFingerprint`] from the raw SHA-256 digest.
+    pub const fn new(digest: [u8; 32]) -> Self {
+        Self(digest)
+    }
+
+    /// Compute the [`CertificateFingerprint`] of the given DER-encoded certificate.
+    pub fn from_cert_der(cert: &[u8]) -> Self {
+        Self(Sha256::digest(cert).into())
+    }
+
+    /// Return the raw SHA-256 digest of this [`CertificateFingerprint`].
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+}
+
+impl fmt::Display for CertificateFingerprint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&hex::encode(self.0))
+    }
+}
+
+impl std::str::FromStr for CertificateFingerprint {
+    type Err = OpaqueError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim().replace(':', "");
+        let digest = hex::decode(s)
+            .context("decode hex certificate fingerprint")?
+            .try_into()
+            .map_err(|_| OpaqueError::from_display("fingerprint is not a SHA-256 digest"))?;
+        Ok(Self(digest))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_certificate_fingerprint_parse_and_display() {
+        let fingerprint = CertificateFingerprint::new([0xab; 32]);
+        let s = fingerprint.to_string();
+        assert_eq!(s.len(), 64);
+        assert_eq!(fingerprint, s.parse().unwrap());
+
+        let with_colons = s
+            .as_bytes()
+            .chunks(2)
+            .map(|c| std::str::from_utf8(c).unwrap())
+            .collect::<Vec<_>>()
+            .join(":");
+        assert_eq!(fingerprint, with_colons.parse().unwrap());
+
+        assert!("abcd".parse::<CertificateFingerprint>().is_err());
+        assert!("not hex".parse::<CertificateFingerprint>().is_err());
+    }
+
+    #[test]
+    fn test_distinguished_name_lookup_and_display() {
+        let name = DistinguishedName {
+            attributes: vec![
+                ("CN".to_owned(), "client.example.com".to_owned()),
+                ("O".to_owned(), "Plabayo".to_owned()),
+            ],
+        };
+        assert_eq!(Some("client.example.com"), name.common_name());
+        assert_eq!(Some("Plabayo"), name.get("o"));
+        assert_eq!(None, name.organizational_unit());
+        assert_eq!("CN=client.example.com, O=Plabayo", name.to_string());
+    }
+
+    #[test]
+    fn test_peer_certificate_identity_invalid_der() {
+        assert!(PeerCertificateIdentity::from_der(b"not a cert").is_err());
+        assert!(PeerCertificateIdentity::from_cert_chain(&DataEncoding::DerStack(vec![])).is_err());
+    }
+
+    #[test]
+    fn test_peer_certificate_identity_user_id() {
+        let identity = PeerCertificateIdentity {
+            subject: DistinguishedName {
+                attributes: vec![("CN".to_owned(), "alice".to_owned())],
+            },
+            issuer: DistinguishedName::default(),
+            subject_alt_names: vec![
+                SubjectAltName::Ip([127, 0, 0, 1].into()),
+                SubjectAltName::Uri("spiffe://example.org/alice".to_owned()),
+            ],
+            serial_number: vec![1],
+            fingerprint: CertificateFingerprint::new([1; 32]),
+            not_before: UNIX_EPOCH,
+            not_after: UNIX_EPOCH + Duration::from_secs(60),
+        };
+
+        assert_eq!(
+            Some(UserId::Username("alice".to_owned())),
+            identity.user_id(PeerCertificateUserIdSource::SubjectCommonName)
+        );
+        assert_eq!(
+            Some(UserId::Username("spiffe://example.org/alice".to_owned())),
+            identity.user_id(PeerCertificateUserIdSource::SubjectAltUri)
+        );
+        assert_eq!(
+            None,
+            identity.user_id(PeerCertificateUserIdSource::SubjectAltDns)
+        );
+        assert_eq!(
+            Some(UserId::Token(vec![1; 32])),
+            identity.user_id(PeerCertificateUserIdSource::Fingerprint)
+        );
+
+        assert!(identity.is_valid_at(UNIX_EPOCH + Duration::from_secs(30)));
+        assert!(!identity.is_valid_at(UNIX_EPOCH + Duration::from_secs(61)));
+    }
+}
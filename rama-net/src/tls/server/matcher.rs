@@ -0,0 +1,119 @@
+use super::{CertificateFingerprint, PeerCertificateIdentity, SubjectAltName};
+use rama_core::{context::Extensions, Context};
+
+#[derive(Debug, Clone)]
+/// Matcher based on the [`PeerCertificateIdentity`] found in the [`Context`].
+///
+/// This matcher will never match in case no [`PeerCertificateIdentity`]
+/// could be found, which is the case for connections where the client
+/// did not authenticate itself using a certificate.
+pub struct PeerCertificateMatcher {
+    kind: PeerCertificateMatcherKind,
+}
+
+#[derive(Debug, Clone)]
+enum PeerCertificateMatcherKind {
+    Present,
+    SubjectCommonName(String),
+    SubjectOrganization(String),
+    IssuerCommonName(String),
+    SubjectAltName(SubjectAltName),
+    Fingerprint(CertificateFingerprint),
+    SerialNumber(Vec<u8>),
+}
+
+impl PeerCertificateMatcher {
+    /// Create a new [`PeerCertificateMatcher`] which matches
+    /// as long as the peer provided a (parseable) certificate.
+    pub const fn present() -> Self {
+        Self {
+            kind: PeerCertificateMatcherKind::Present,
+        }
+    }
+
+    /// Create a new [`PeerCertificateMatcher`] which matches
+    /// on the common name (CN) of the subject of the peer certificate.
+    pub fn subject_common_name(cn: impl Into<String>) -> Self {
+        Self {
+            kind: PeerCertificateMatcherKind::SubjectCommonName(cn.into()),
+        }
+    }
+
+    /// Create a new [`PeerCertificateMatcher`] which matches
+    /// on the organization (O) of the subject of the peer certificate.
+    pub fn subject_organization(organization: impl Into<String>) -> Self {
+        Self {
+            kind: PeerCertificateMatcherKind::SubjectOrganization(organization.into()),
+        }
+    }
+
+    /// Create a new [`PeerCertificateMatcher`] which matches
+    /// on the common name (CN) of the issuer of the peer certificate.
+    pub fn issuer_common_name(cn: impl Into<String>) -> Self {
+        Self {
+            kind: PeerCertificateMatcherKind::IssuerCommonName(cn.into()),
+        }
+    }
+
+    /// Create a new [`PeerCertificateMatcher`] which matches
+    /// if the peer certificate contains the given [`SubjectAltName`].
+    ///
+    /// DNS names are compared case-insensitive.
+    pub fn subject_alt_name(san: SubjectAltName) -> Self {
+        Self {
+            kind: PeerCertificateMatcherKind::SubjectAltName(san),
+        }
+    }
+
+    /// Create a new [`PeerCertificateMatcher`] which matches
+    /// on the SHA-256 fingerprint of the peer certificate.
+    pub const fn fingerprint(fingerprint: CertificateFingerprint) -> Self {
+        Self {
+            kind: PeerCertificateMatcherKind::Fingerprint(fingerprint),
+        }
+    }
+
+    /// Create a new [`PeerCertificateMatcher`] which matches
+    /// on the raw (big endian) serial number of the peer certificate.
+    pub fn serial_number(serial: impl Into<Vec<u8>>) -> Self {
+        Self {
+            kind: PeerCertificateMatcherKind::SerialNumber(serial.into()),
+        }
+    }
+
+    fn matches_identity(&self, identity: &PeerCertificateIdentity) -> bool {
+        match &self.kind {
+            PeerCertificateMatcherKind::Present => true,
+            PeerCertificateMatcherKind::SubjectCommonName(cn) => {
+                identity.subject().common_name() == Some(cn.as_str())
+            }
+            PeerCertificateMatcherKind::SubjectOrganization(organization) => {
+                identity.subject().organization() == Some(organization.as_str())
+            }
+            PeerCertificateMatcherKind::IssuerCommonName(cn) => {
+                identity.issuer().common_name() == Some(cn.as_str())
+            }
+            PeerCertificateMatcherKind::SubjectAltName(SubjectAltName::Dns(name)) => identity
+                .subject_alt_names()
+                .iter()
+                .any(|san| matches!(san, SubjectAltName::Dns(other) if other.eq_ignore_ascii_case(name))),
+            PeerCertificateMatcherKind::SubjectAltName(san) => {
+                identity.subject_alt_names().contains(san)
+            }
+            PeerCertificateMatcherKind::Fingerprint(fingerprint) => {
+                identity.fingerprint() == fingerprint
+            }
+            PeerCertificateMatcherKind::SerialNumber(serial) => {
+                identity.serial_number() == serial.as_slice()
+            }
+        }
+    }
+}
+
+impl<State, Request> rama_core::matcher::Matcher<State, Request> for PeerCertificateMatcher {
+    fn matches(&self, _ext: Option<&mut Extensions>, ctx: &Context<State>, _req: &Request) -> bool {
+        ctx.get::<PeerCertificateIdentity>()
+            .map(|identity| self.matches_identity(identity))
+            .unwrap_or_default()
+    }
+}
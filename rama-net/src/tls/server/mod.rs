@@ -6,3 +6,26 @@ pub use config::{
     CacheKind, ClientVerifyMode, DynamicCertIssuer, DynamicIssuer, SelfSignedData, ServerAuth,
     ServerAuthData, ServerCertIssuerData, ServerCertIssuerKind, ServerConfig,
 };
+
+mod identity;
+#[doc(inline)]
+pub use identity::{
+    CertificateFingerprint, DistinguishedName, PeerCertificateIdentity,
+    PeerCertificateUserIdSource, SubjectAltName,
+};
+
+mod matcher;
+#[doc(inline)]
+pub use matcher::PeerCertificateMatcher;
+
+mod session;
+#[doc(inline)]
+pub use session::{
+    MemoryServerSessionStore, ProvidesSessionTicketKeys, RotatingSessionTicketKeyProvider,
+    ServerSessionCache, ServerSessionResumption, SessionTicketConfig, SessionTicketKey,
+    SessionTicketKeyProvider, SessionTicketKeys, StoresServerSessions,
+};
+
+mod user_id;
+#[doc(inline)]
+pub use user_id::{PeerCertificateUserIdLayer, PeerCertificateUserIdService};
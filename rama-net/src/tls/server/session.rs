@@ -0,0 +1,395 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt,
+    num::NonZeroUsize,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+#[derive(Debug, Clone, Default)]
+/// Session resumption configuration of a (tls) server.
+///
+/// Resumption can be stateful, using a [`ServerSessionCache`],
+/// and/or stateless, using session tickets configured by a [`SessionTicketConfig`].
+pub struct ServerSessionResumption {
+    /// optional cache used to store sessions by their session id (stateful resumption)
+    pub session_cache: Option<ServerSessionCache>,
+    /// optional config used to issue session tickets (stateless resumption)
+    pub session_tickets: Option<SessionTicketConfig>,
+}
+
+/// Trait that needs to be implemented by server session stores,
+/// used by the (tls) server for stateful session resumption.
+///
+/// Keys and values are opaque encoded data provided by the tls implementation.
+pub trait StoresServerSessions: Send + Sync + 'static {
+    /// Store the session value for the given key,
+    /// returning `true` if the value was stored.
+    fn put(&self, key: Vec<u8>, value: Vec<u8>) -> bool;
+
+    /// Return the session value for the given key, if any.
+    fn get(&self, key: &[u8]) -> Option<Vec<u8>>;
+
+    /// Remove and return the session value for the given key, if any.
+    fn take(&self, key: &[u8]) -> Option<Vec<u8>>;
+}
+
+#[derive(Clone)]
+/// Shared cache of (tls) server sessions, used for stateful session resumption.
+///
+/// Clones of a [`ServerSessionCache`] share the same underlying store,
+/// which allows one cache to be shared between multiple servers.
+pub struct ServerSessionCache {
+    store: Arc<dyn StoresServerSessions>,
+}
+
+impl ServerSessionCache {
+    /// Create a new [`ServerSessionCache`] for the given [`StoresServerSessions`] implementation.
+    pub fn new<T: StoresServerSessions>(store: T) -> Self {
+        Self {
+            store: Arc::new(store),
+        }
+    }
+
+    /// Create a new in-memory [`ServerSessionCache`],
+    /// see [`MemoryServerSessionStore`] for more information.
+    pub fn memory(max_size: NonZeroUsize) -> Self {
+        Self::new(MemoryServerSessionStore::new(max_size))
+    }
+
+    /// Store the session value for the given key.
+    pub fn put(&self, key: Vec<u8>, value: Vec<u8>) -> bool {
+        self.store.put(key, value)
+    }
+
+    /// Return the session value for the given key, if any.
+    pub fn get(&self, key: &[u8]) -> Option<Vec<u8>> {
+        self.store.get(key)
+    }
+
+    /// Remove and return the session value for the given key, if any.
+    pub fn take(&self, key: &[u8]) -> Option<Vec<u8>> {
+        self.store.take(key)
+    }
+}
+
+impl fmt::Debug for ServerSessionCache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ServerSessionCache").finish()
+    }
+}
+
+/// In-memory [`StoresServerSessions`] implementation,
+/// evicting the oldest session once `max_size` sessions are stored.
+pub struct MemoryServerSessionStore {
+    max_size: usize,
+    state: Mutex<MemoryServerSessionState>,
+}
+
+#[derive(Default)]
+struct MemoryServerSessionState {
+    sessions: HashMap<Vec<u8>, Vec<u8>>,
+    insert_order: VecDeque<Vec<u8>>,
+}
+
+impl MemoryServerSessionStore {
+    /// Create a new [`MemoryServerSessionStore`] which stores at most `max_size` sessions.
+    pub fn new(max_size: NonZeroUsize) -> Self {
+        Self {
+            max_size: max_size.get(),
+            state: Mutex::default(),
+        }
+    }
+}
+
+impl fmt::Debug for MemoryServerSessionStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MemoryServerSessionStore")
+            .field("max_size", &self.max_size)
+            .finish()
+    }
+}
+
+impl StoresServerSessions for MemoryServerSessionStore {
+    fn put(&self, key: Vec<u8>, value: Vec<u8>) -> bool {
+        let mut state = self.state.lock().unwrap_or_else(|err| err.into_inner());
+        let state = &mut *state;
+
+        if !state.sessions.contains_key(&key) {
+            while state.sessions.len() >= self.max_size {
+                let Some(oldest) = state.insert_order.pop_front() else {
+                    break;
+                };
+                state.sessions.remove(&oldest);
+            }
+            state.insert_order.push_back(key.clone());
+        }
+
+        state.sessions.insert(key, value);
+        true
+    }
+
+    fn get(&self, key: &[u8]) -> Option<Vec<u8>> {
+        let state = self.state.lock().unwrap_or_else(|err| err.into_inner());
+        state.sessions.get(key).cloned()
+    }
+
+    fn take(&self, key: &[u8]) -> Option<Vec<u8>> {
+        let mut state = self.state.lock().unwrap_or_else(|err| err.into_inner());
+        let value = state.sessions.remove(key)?;
+        state.insert_order.retain(|k| k != key);
+        Some(value)
+    }
+}
+
+#[derive(Debug, Clone)]
+/// Configuration used by a (tls) server to issue session tickets.
+pub struct SessionTicketConfig {
+    /// provider of the keys used to encrypt and decrypt session tickets
+    pub key_provider: SessionTicketKeyProvider,
+    /// lifetime hint of issued tickets
+    pub lifetime: Duration,
+}
+
+impl SessionTicketConfig {
+    /// Create a new [`SessionTicketConfig`] for the given key provider,
+    /// using the default ticket lifetime of 12 hours.
+    pub fn new(key_provider: impl Into<SessionTicketKeyProvider>) -> Self {
+        Self {
+            key_provider: key_provider.into(),
+            lifetime: Duration::from_secs(12 * 60 * 60),
+        }
+    }
+
+    /// Define the lifetime hint of issued tickets.
+    pub fn with_lifetime(mut self, lifetime: Duration) -> Self {
+        self.lifetime = lifetime;
+        self
+    }
+}
+
+#[derive(Clone, PartialEq, Eq)]
+/// A key used to encrypt and decrypt session tickets.
+pub struct SessionTicketKey {
+    name: [u8; 16],
+    secret: [u8; 32],
+}
+
+impl SessionTicketKey {
+    /// Create a new [`SessionTicketKey`] from its name and secret.
+    ///
+    /// The name is embedded in issued tickets, such that the server
+    /// can find the key which was used to encrypt a received ticket.
+    pub const fn new(name: [u8; 16], secret: [u8; 32]) -> Self {
+        Self { name, secret }
+    }
+
+    /// Generate a new random [`SessionTicketKey`].
+    pub fn generate() -> Self {
+        Self {
+            name: rand::random(),
+            secret: rand::random(),
+        }
+    }
+
+    /// Return the name of this key.
+    pub fn name(&self) -> &[u8; 16] {
+        &self.name
+    }
+
+    /// Return the secret of this key.
+    pub fn secret(&self) -> &[u8; 32] {
+        &self.secret
+    }
+}
+
+impl fmt::Debug for SessionTicketKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SessionTicketKey")
+            .field("name", &self.name)
+            .finish_non_exhaustive()
+    }
+}
+
+#[derive(Debug, Clone)]
+/// The [`SessionTicketKey`]s to be used by a (tls) server.
+pub struct SessionTicketKeys {
+    /// key used to encrypt new tickets (and decrypt received ones)
+    pub current: SessionTicketKey,
+    /// older keys, only used to decrypt received tickets
+    pub previous: Vec<SessionTicketKey>,
+}
+
+impl SessionTicketKeys {
+    /// Find the key with the given name, current or previous.
+    pub fn find(&self, name: &[u8]) -> Option<&SessionTicketKey> {
+        std::iter::once(&self.current)
+            .chain(self.previous.iter())
+            .find(|key| key.name() == name)
+    }
+}
+
+/// Trait that needs to be implemented by providers of [`SessionTicketKeys`].
+///
+/// Implementations can share keys between servers (e.g. fetched from a secret store),
+/// such that tickets issued by one server can be used to resume sessions on another.
+pub trait ProvidesSessionTicketKeys: Send + Sync + 'static {
+    /// Return the keys to be used for encrypting and decrypting session tickets.
+    fn ticket_keys(&self) -> SessionTicketKeys;
+}
+
+#[derive(Clone)]
+/// Provider of the [`SessionTicketKeys`] used by a (tls) server.
+pub struct SessionTicketKeyProvider {
+    provider: Arc<dyn ProvidesSessionTicketKeys>,
+}
+
+impl SessionTicketKeyProvider {
+    /// Create a new [`SessionTicketKeyProvider`] for the given [`ProvidesSessionTicketKeys`] implementation.
+    pub fn new<T: ProvidesSessionTicketKeys>(provider: T) -> Self {
+        Self {
+            provider: Arc::new(provider),
+        }
+    }
+
+    /// Return the keys to be used for encrypting and decrypting session tickets.
+    pub fn ticket_keys(&self) -> SessionTicketKeys {
+        self.provider.ticket_keys()
+    }
+}
+
+impl fmt::Debug for SessionTicketKeyProvider {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SessionTicketKeyProvider").finish()
+    }
+}
+
+impl<T> From<T> for SessionTicketKeyProvider
+where
+    T: ProvidesSessionTicketKeys,
+{
+    fn from(provider: T) -> Self {
+        Self::new(provider)
+    }
+}
+
+impl ProvidesSessionTicketKeys for SessionTicketKeys {
+    fn ticket_keys(&self) -> SessionTicketKeys {
+        self.clone()
+    }
+}
+
+#[derive(Debug, Clone)]
+/// A [`ProvidesSessionTicketKeys`] implementation which generates
+/// random keys and rotates them at a fixed interval.
+///
+/// Clones share the same keys.
+pub struct RotatingSessionTicketKeyProvider {
+    rotation_interval: Duration,
+    max_previous_keys: usize,
+    state: Arc<Mutex<RotatingSessionTicketKeyState>>,
+}
+
+#[derive(Debug)]
+struct RotatingSessionTicketKeyState {
+    keys: SessionTicketKeys,
+    rotated_at: Instant,
+}
+
+impl RotatingSessionTicketKeyProvider {
+    /// Create a new [`RotatingSessionTicketKeyProvider`] which rotates
+    /// to a new key every `rotation_interval`, keeping the previous key
+    /// around to decrypt tickets issued before the rotation.
+    pub fn new(rotation_interval: Duration) -> Self {
+        Self {
+            rotation_interval,
+            max_previous_keys: 1,
+            state: Arc::new(Mutex::new(RotatingSessionTicketKeyState {
+                keys: SessionTicketKeys {
+                    current: SessionTicketKey::generate(),
+                    previous: Vec::new(),
+                },
+                rotated_at: Instant::now(),
+            })),
+        }
+    }
+
+    /// Define how many previous keys are kept around to decrypt tickets (1 by default).
+    pub fn with_max_previous_keys(mut self, max: usize) -> Self {
+        self.max_previous_keys = max;
+        self
+    }
+
+    /// Rotate to a new key immediately.
+    pub fn rotate(&self) {
+        let mut state = self.state.lock().unwrap_or_else(|err| err.into_inner());
+        self.rotate_state(&mut state);
+    }
+
+    fn rotate_state(&self, state: &mut RotatingSessionTicketKeyState) {
+        let previous = std::mem::replace(&mut state.keys.current, SessionTicketKey::generate());
+        state.keys.previous.insert(0, previous);
+        state.keys.previous.truncate(self.max_previous_keys);
+        state.rotated_at = Instant::now();
+        tracing::trace!("rotated session ticket key");
+    }
+}
+
+impl ProvidesSessionTicketKeys for RotatingSessionTicketKeyProvider {
+    fn ticket_keys(&self) -> SessionTicketKeys {
+        let mut state = self.state.lock().unwrap_or_else(|err| err.into_inner());
+        if state.rotated_at.elapsed() >= self.rotation_interval {
+            self.rotate_state(&mut state);
+        }
+        state.keys.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_memory_server_session_store() {
+        let cache = ServerSessionCache::memory(NonZeroUsize::new(2).unwrap());
+        assert!(cache.put(b"a".to_vec(), b"1".to_vec()));
+        assert!(cache.put(b"b".to_vec(), b"2".to_vec()));
+        assert_eq!(Some(b"1".to_vec()), cache.get(b"a"));
+
+        assert!(cache.put(b"c".to_vec(), b"3".to_vec()));
+        assert!(cache.get(b"a").is_none());
+
+        assert_eq!(Some(b"2".to_vec()), cache.take(b"b"));
+        assert!(cache.get(b"b").is_none());
+        assert_eq!(Some(b"3".to_vec()), cache.get(b"c"));
+    }
+
+    #[test]
+    fn test_rotating_session_ticket_key_provider() {
+        let provider = RotatingSessionTicketKeyProvider::new(Duration::from_secs(3600));
+        let shared = provider.clone();
+
+        let keys = provider.ticket_keys();
+        assert!(keys.previous.is_empty());
+        assert_eq!(keys.current, shared.ticket_keys().current);
+
+        provider.rotate();
+        let rotated = shared.ticket_keys();
+        assert_ne!(keys.current, rotated.current);
+        assert_eq!(vec![keys.current.clone()], rotated.previous);
+        assert!(rotated.find(keys.current.name()).is_some());
+
+        provider.rotate();
+        let rotated_twice = provider.ticket_keys();
+        assert_eq!(1, rotated_twice.previous.len());
+        assert!(rotated_twice.find(keys.current.name()).is_none());
+    }
+
+    #[test]
+    fn test_rotating_session_ticket_key_provider_interval() {
+        let provider = RotatingSessionTicketKeyProvider::new(Duration::ZERO);
+        let first = provider.ticket_keys();
+        let second = provider.ticket_keys();
+        assert_ne!(first.current, second.current);
+    }
+}
@@ -0,0 +1,142 @@
+use super::{PeerCertificateIdentity, PeerCertificateUserIdSource};
+use crate::user::UserId;
+use rama_core::{
+    error::{BoxError, OpaqueError},
+    Context, Layer, Service,
+};
+use rama_utils::macros::define_inner_service_accessors;
+use std::fmt;
+
+/// A [`Service`] which maps the [`PeerCertificateIdentity`] found in the [`Context`]
+/// to a [`UserId`], such that the peer can be authorized the same way as
+/// users that are authenticated by for example the `ProxyAuthLayer`.
+///
+/// The request is refused in case no [`UserId`] could be mapped,
+/// unless anonymous users are allowed, in which case the
+/// user is identified as [`UserId::Anonymous`].
+pub struct PeerCertificateUserIdService<S> {
+    source: PeerCertificateUserIdSource,
+    allow_anonymous: bool,
+    inner: S,
+}
+
+impl<S> PeerCertificateUserIdService<S> {
+    /// Create a new [`PeerCertificateUserIdService`].
+    pub const fn new(source: PeerCertificateUserIdSource, inner: S) -> Self {
+        Self {
+            source,
+            allow_anonymous: false,
+            inner,
+        }
+    }
+
+    /// Allow anonymous requests.
+    pub fn set_allow_anonymous(&mut self, allow_anonymous: bool) -> &mut Self {
+        self.allow_anonymous = allow_anonymous;
+        self
+    }
+
+    /// Allow anonymous requests.
+    pub fn with_allow_anonymous(mut self, allow_anonymous: bool) -> Self {
+        self.allow_anonymous = allow_anonymous;
+        self
+    }
+
+    define_inner_service_accessors!();
+}
+
+impl<S: fmt::Debug> fmt::Debug for PeerCertificateUserIdService<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PeerCertificateUserIdService")
+            .field("source", &self.source)
+            .field("allow_anonymous", &self.allow_anonymous)
+            .field("inner", &self.inner)
+            .finish()
+    }
+}
+
+impl<S: Clone> Clone for PeerCertificateUserIdService<S> {
+    fn clone(&self) -> Self {
+        Self {
+            source: self.source,
+            allow_anonymous: self.allow_anonymous,
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<State, S, Request> Service<State, Request> for PeerCertificateUserIdService<S>
+where
+    State: Clone + Send + Sync + 'static,
+    S: Service<State, Request, Error: Into<BoxError>>,
+    Request: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = BoxError;
+
+    async fn serve(
+        &self,
+        mut ctx: Context<State>,
+        req: Request,
+    ) -> Result<Self::Response, Self::Error> {
+        let user_id = ctx
+            .get::<PeerCertificateIdentity>()
+            .and_then(|identity| identity.user_id(self.source));
+
+        match user_id {
+            Some(user_id) => {
+                tracing::trace!(?user_id, "peer certificate mapped to user id");
+                ctx.insert(user_id);
+            }
+            None if self.allow_anonymous => {
+                ctx.insert(UserId::Anonymous);
+            }
+            None => {
+                return Err(OpaqueError::from_display(
+                    "peer certificate user id: no user id found for peer",
+                )
+                .into_boxed());
+            }
+        }
+
+        self.inner.serve(ctx, req).await.map_err(Into::into)
+    }
+}
+
+#[derive(Debug, Clone)]
+/// A [`Layer`] that produces a [`PeerCertificateUserIdService`].
+pub struct PeerCertificateUserIdLayer {
+    source: PeerCertificateUserIdSource,
+    allow_anonymous: bool,
+}
+
+impl PeerCertificateUserIdLayer {
+    /// Create a new [`PeerCertificateUserIdLayer`].
+    pub const fn new(source: PeerCertificateUserIdSource) -> Self {
+        Self {
+            source,
+            allow_anonymous: false,
+        }
+    }
+
+    /// Allow anonymous requests.
+    pub fn set_allow_anonymous(&mut self, allow_anonymous: bool) -> &mut Self {
+        self.allow_anonymous = allow_anonymous;
+        self
+    }
+
+    /// Allow anonymous requests.
+    pub fn with_allow_anonymous(mut self, allow_anonymous: bool) -> Self {
+        self.allow_anonymous = allow_anonymous;
+        self
+    }
+}
+
+impl<S> Layer<S> for PeerCertificateUserIdLayer {
+    type Service = PeerCertificateUserIdService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        PeerCertificateUserIdService::new(self.source, inner)
+            .with_allow_anonymous(self.allow_anonymous)
+    }
+}
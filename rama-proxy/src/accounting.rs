@@ -0,0 +1,484 @@
+//! Usage accounting and quotas for proxied traffic.
+//!
+//! A [`UsageRecord`] describes the usage of a single proxied request or tunnel,
+//! attributed to the [`UserId`], [`ProxyID`] and proxy pool found in the [`Context`].
+//! Records are aggregated per [`UsageKey`] by a [`UsageLedger`], which can also
+//! forward them to a [`UsageSink`] of your choice, e.g. to export them for billing.
+//!
+//! A [`Quota`] can be defined for any [`UsageKey`] on the [`UsageLedger`], such that
+//! requests are rejected or throttled once the quota is exceeded. It is up to the
+//! proxy service to check the [`UsageLedger`] prior to serving a request, and to
+//! cut long-lived traffic such as tunnels once [`UsageLedger::remaining_bytes`] runs out,
+//! as is for example done by the `ForwardProxyService` (`rama-http-backend`).
+//!
+//! [`Context`]: rama_core::Context
+
+use crate::{Proxy, ProxyID};
+use rama_core::Context;
+use rama_net::user::UserId;
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+    time::Duration,
+};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+/// The usage of a single proxied request or tunnel.
+pub struct UsageRecord {
+    /// The user on whose behalf the traffic was proxied.
+    pub user: Option<UserId>,
+    /// The id of the upstream proxy used, if any.
+    pub proxy: Option<ProxyID>,
+    /// The pool of the upstream proxy used, if any.
+    pub pool: Option<String>,
+    /// The number of bytes sent by the client.
+    pub bytes_up: u64,
+    /// The number of bytes received by the client.
+    pub bytes_down: u64,
+    /// The number of requests, including the requests which established a tunnel.
+    pub requests: u64,
+    /// The number of tunnels.
+    pub tunnels: u64,
+    /// The time the tunnels were open.
+    pub tunnel_duration: Duration,
+}
+
+impl UsageRecord {
+    /// Create a new empty [`UsageRecord`], attributed to the
+    /// [`UserId`], [`ProxyID`] and [`Proxy`] pool found in the given [`Context`].
+    pub fn from_context<State>(ctx: &Context<State>) -> Self {
+        Self {
+            user: ctx.get::<UserId>().cloned(),
+            proxy: ctx.get::<ProxyID>().cloned(),
+            pool: ctx
+                .get::<Proxy>()
+                .and_then(|proxy| proxy.pool_id.as_ref())
+                .map(|pool_id| pool_id.inner().to_owned()),
+            ..Default::default()
+        }
+    }
+
+    /// Returns the [`UsageKey`]s to which this record is attributed.
+    pub fn keys(&self) -> impl Iterator<Item = UsageKey> + '_ {
+        self.user
+            .iter()
+            .cloned()
+            .map(UsageKey::User)
+            .chain(self.proxy.iter().cloned().map(UsageKey::Proxy))
+            .chain(self.pool.iter().cloned().map(UsageKey::Pool))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+/// The key by which usage is aggregated in a [`UsageLedger`].
+pub enum UsageKey {
+    /// Usage of a user.
+    User(UserId),
+    /// Usage of an upstream proxy.
+    Proxy(ProxyID),
+    /// Usage of a pool of upstream proxies.
+    Pool(String),
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+/// The aggregated usage for a [`UsageKey`].
+pub struct Usage {
+    /// The number of bytes sent by clients.
+    pub bytes_up: u64,
+    /// The number of bytes received by clients.
+    pub bytes_down: u64,
+    /// The number of requests, including the requests which established a tunnel.
+    pub requests: u64,
+    /// The number of tunnels.
+    pub tunnels: u64,
+    /// The time the tunnels were open.
+    pub tunnel_duration: Duration,
+}
+
+impl Usage {
+    /// Returns the number of bytes sent and received by clients.
+    pub fn bytes(&self) -> u64 {
+        self.bytes_up.saturating_add(self.bytes_down)
+    }
+
+    fn add(&mut self, record: &UsageRecord) {
+        self.bytes_up = self.bytes_up.saturating_add(record.bytes_up);
+        self.bytes_down = self.bytes_down.saturating_add(record.bytes_down);
+        self.requests = self.requests.saturating_add(record.requests);
+        self.tunnels = self.tunnels.saturating_add(record.tunnels);
+        self.tunnel_duration = self.tunnel_duration.saturating_add(record.tunnel_duration);
+    }
+}
+
+/// A sink to which [`UsageRecord`]s are reported,
+/// e.g. to export them to a billing system.
+pub trait UsageSink: Send + Sync + 'static {
+    /// Record the given usage.
+    fn record(&self, record: &UsageRecord);
+}
+
+impl UsageSink for () {
+    fn record(&self, _record: &UsageRecord) {}
+}
+
+impl<S: UsageSink> UsageSink for Arc<S> {
+    fn record(&self, record: &UsageRecord) {
+        (**self).record(record)
+    }
+}
+
+impl<F> UsageSink for F
+where
+    F: Fn(&UsageRecord) + Send + Sync + 'static,
+{
+    fn record(&self, record: &UsageRecord) {
+        (self)(record)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+/// What to do with requests once a [`Quota`] is exceeded.
+pub enum QuotaAction {
+    #[default]
+    /// Reject the requests.
+    Reject,
+    /// Delay the requests with the given duration.
+    Throttle(Duration),
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+/// A limit on the usage of a [`UsageKey`], defined on a [`UsageLedger`].
+///
+/// A quota without limits is never exceeded.
+pub struct Quota {
+    max_bytes: Option<u64>,
+    max_requests: Option<u64>,
+    action: QuotaAction,
+}
+
+impl Quota {
+    /// Create a new [`Quota`] without limits,
+    /// which rejects requests once exceeded.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Limit the number of bytes sent and received by clients.
+    pub fn max_bytes(mut self, max: u64) -> Self {
+        self.max_bytes = Some(max);
+        self
+    }
+
+    /// Limit the number of bytes sent and received by clients.
+    pub fn set_max_bytes(&mut self, max: u64) -> &mut Self {
+        self.max_bytes = Some(max);
+        self
+    }
+
+    /// Limit the number of requests, including the requests which establish a tunnel.
+    pub fn max_requests(mut self, max: u64) -> Self {
+        self.max_requests = Some(max);
+        self
+    }
+
+    /// Limit the number of requests, including the requests which establish a tunnel.
+    pub fn set_max_requests(&mut self, max: u64) -> &mut Self {
+        self.max_requests = Some(max);
+        self
+    }
+
+    /// Define what to do with requests once this quota is exceeded.
+    pub fn action(mut self, action: QuotaAction) -> Self {
+        self.action = action;
+        self
+    }
+
+    /// Define what to do with requests once this quota is exceeded.
+    pub fn set_action(&mut self, action: QuotaAction) -> &mut Self {
+        self.action = action;
+        self
+    }
+
+    /// Returns the number of bytes left before the given usage exceeds this quota,
+    /// or `None` in case the bytes are not limited or the quota only throttles.
+    pub fn remaining_bytes(&self, usage: &Usage) -> Option<u64> {
+        match self.action {
+            QuotaAction::Reject => self.max_bytes.map(|max| max.saturating_sub(usage.bytes())),
+            QuotaAction::Throttle(_) => None,
+        }
+    }
+
+    /// Returns `true` if the given usage exceeds this quota.
+    pub fn is_exceeded(&self, usage: &Usage) -> bool {
+        self.max_bytes.is_some_and(|max| usage.bytes() >= max)
+            || self.max_requests.is_some_and(|max| usage.requests >= max)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// The verdict of checking the quotas of a [`UsageLedger`].
+pub enum QuotaVerdict {
+    /// No quota is exceeded.
+    Allow,
+    /// A quota is exceeded, and requests are to be delayed with the given duration.
+    Throttle(Duration),
+    /// The quota of the given key is exceeded, and requests are to be rejected.
+    Reject(UsageKey),
+}
+
+#[derive(Clone, Default)]
+/// Aggregates [`UsageRecord`]s per [`UsageKey`], and enforces the [`Quota`]s defined for them.
+///
+/// All clones share the same usage and quotas. Records are forwarded
+/// to the [`UsageSink`] defined using [`UsageLedger::with_sink`], if any.
+///
+/// Usage is kept until reset, e.g. using [`UsageLedger::drain`] at the end of a billing period.
+pub struct UsageLedger {
+    state: Arc<Mutex<LedgerState>>,
+    sink: Option<Arc<dyn UsageSink>>,
+}
+
+#[derive(Debug, Default)]
+struct LedgerState {
+    usage: HashMap<UsageKey, Usage>,
+    quotas: HashMap<UsageKey, Quota>,
+}
+
+impl fmt::Debug for UsageLedger {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UsageLedger")
+            .field("state", &self.state)
+            .field("sink", &self.sink.is_some())
+            .finish()
+    }
+}
+
+impl UsageLedger {
+    /// Create a new empty [`UsageLedger`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Forward all records to the given [`UsageSink`],
+    /// in addition to aggregating them.
+    pub fn with_sink(mut self, sink: impl UsageSink) -> Self {
+        self.sink = Some(Arc::new(sink));
+        self
+    }
+
+    /// Forward all records to the given [`UsageSink`],
+    /// in addition to aggregating them.
+    pub fn set_sink(&mut self, sink: impl UsageSink) -> &mut Self {
+        self.sink = Some(Arc::new(sink));
+        self
+    }
+
+    fn lock(&self) -> MutexGuard<'_, LedgerState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Define the [`Quota`] for the given key, replacing the previous one.
+    pub fn set_quota(&self, key: UsageKey, quota: Quota) {
+        self.lock().quotas.insert(key, quota);
+    }
+
+    /// Remove the [`Quota`] for the given key, returning it if defined.
+    pub fn remove_quota(&self, key: &UsageKey) -> Option<Quota> {
+        self.lock().quotas.remove(key)
+    }
+
+    /// Returns the [`Quota`] for the given key, if defined.
+    pub fn quota(&self, key: &UsageKey) -> Option<Quota> {
+        self.lock().quotas.get(key).cloned()
+    }
+
+    /// Returns the aggregated usage for the given key.
+    pub fn usage(&self, key: &UsageKey) -> Usage {
+        self.lock().usage.get(key).copied().unwrap_or_default()
+    }
+
+    /// Reset the usage for the given key, returning the usage prior to the reset.
+    pub fn reset(&self, key: &UsageKey) -> Usage {
+        self.lock().usage.remove(key).unwrap_or_default()
+    }
+
+    /// Reset the usage for all keys, returning the usage prior to the reset.
+    pub fn drain(&self) -> Vec<(UsageKey, Usage)> {
+        self.lock().usage.drain().collect()
+    }
+
+    /// Check the quotas of the given keys.
+    ///
+    /// Rejection takes precedence over throttling, and in case multiple
+    /// quotas throttle, the longest delay is returned.
+    pub fn check<'a>(&self, keys: impl IntoIterator<Item = &'a UsageKey>) -> QuotaVerdict {
+        let state = self.lock();
+        let mut verdict = QuotaVerdict::Allow;
+        for key in keys {
+            let Some(quota) = state.quotas.get(key) else {
+                continue;
+            };
+            let usage = state.usage.get(key).copied().unwrap_or_default();
+            if !quota.is_exceeded(&usage) {
+                continue;
+            }
+            match quota.action {
+                QuotaAction::Reject => return QuotaVerdict::Reject(key.clone()),
+                QuotaAction::Throttle(delay) => {
+                    if !matches!(verdict, QuotaVerdict::Throttle(current) if current >= delay) {
+                        verdict = QuotaVerdict::Throttle(delay);
+                    }
+                }
+            }
+        }
+        verdict
+    }
+
+    /// Returns the smallest number of bytes left for the given keys before
+    /// a rejecting [`Quota`] is exceeded, or `None` if their bytes are not limited.
+    ///
+    /// Usage which is not yet recorded, e.g. of tunnels that are still open,
+    /// is not taken into account.
+    pub fn remaining_bytes<'a>(&self, keys: impl IntoIterator<Item = &'a UsageKey>) -> Option<u64> {
+        let state = self.lock();
+        keys.into_iter()
+            .filter_map(|key| {
+                let quota = state.quotas.get(key)?;
+                let usage = state.usage.get(key).copied().unwrap_or_default();
+                quota.remaining_bytes(&usage)
+            })
+            .min()
+    }
+}
+
+impl UsageSink for UsageLedger {
+    fn record(&self, record: &UsageRecord) {
+        {
+            let mut state = self.lock();
+            for key in record.keys() {
+                state.usage.entry(key).or_default().add(record);
+            }
+        }
+        if let Some(sink) = &self.sink {
+            sink.record(record);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rama_utils::str::NonEmptyString;
+
+    fn record(bytes: u64) -> UsageRecord {
+        UsageRecord {
+            user: Some(UserId::Username("john".to_owned())),
+            proxy: Some(ProxyID::from(NonEmptyString::from_static("1"))),
+            pool: Some("poola".to_owned()),
+            bytes_up: bytes,
+            bytes_down: bytes,
+            requests: 1,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_ledger_aggregates_per_key() {
+        let ledger = UsageLedger::new();
+        ledger.record(&record(10));
+        ledger.record(&UsageRecord {
+            proxy: Some(ProxyID::from(NonEmptyString::from_static("2"))),
+            ..record(5)
+        });
+
+        let user = UsageKey::User(UserId::Username("john".to_owned()));
+        assert_eq!(ledger.usage(&user).bytes(), 30);
+        assert_eq!(ledger.usage(&user).requests, 2);
+        assert_eq!(
+            ledger
+                .usage(&UsageKey::Proxy(ProxyID::from(
+                    NonEmptyString::from_static("1")
+                )))
+                .bytes(),
+            20
+        );
+        assert_eq!(
+            ledger.usage(&UsageKey::Pool("poola".to_owned())).requests,
+            2
+        );
+
+        assert_eq!(ledger.reset(&user).requests, 2);
+        assert_eq!(ledger.usage(&user), Usage::default());
+        assert_eq!(ledger.drain().len(), 3);
+        assert_eq!(
+            ledger.usage(&UsageKey::Pool("poola".to_owned())),
+            Usage::default()
+        );
+    }
+
+    #[test]
+    fn test_ledger_forwards_to_sink() {
+        let recorded = Arc::new(Mutex::new(Vec::new()));
+        let ledger = UsageLedger::new().with_sink({
+            let recorded = recorded.clone();
+            move |record: &UsageRecord| recorded.lock().unwrap().push(record.clone())
+        });
+        ledger.record(&record(1));
+        assert_eq!(*recorded.lock().unwrap(), vec![record(1)]);
+    }
+
+    #[test]
+    fn test_ledger_quotas() {
+        let ledger = UsageLedger::new();
+        let user = UsageKey::User(UserId::Username("john".to_owned()));
+        let pool = UsageKey::Pool("poola".to_owned());
+        ledger.set_quota(user.clone(), Quota::new().max_requests(2));
+        ledger.set_quota(
+            pool.clone(),
+            Quota::new()
+                .max_bytes(15)
+                .action(QuotaAction::Throttle(Duration::from_secs(1))),
+        );
+
+        let keys: Vec<_> = record(0).keys().collect();
+        assert_eq!(ledger.check(&keys), QuotaVerdict::Allow);
+
+        ledger.record(&record(10));
+        assert_eq!(
+            ledger.check(&keys),
+            QuotaVerdict::Throttle(Duration::from_secs(1))
+        );
+
+        ledger.record(&record(0));
+        assert_eq!(ledger.check(&keys), QuotaVerdict::Reject(user.clone()));
+
+        ledger.remove_quota(&user);
+        ledger.reset(&pool);
+        assert_eq!(ledger.check(&keys), QuotaVerdict::Allow);
+    }
+
+    #[test]
+    fn test_ledger_remaining_bytes() {
+        let ledger = UsageLedger::new();
+        let user = UsageKey::User(UserId::Username("john".to_owned()));
+        let pool = UsageKey::Pool("poola".to_owned());
+        let keys: Vec<_> = record(0).keys().collect();
+        assert_eq!(ledger.remaining_bytes(&keys), None);
+
+        ledger.set_quota(user.clone(), Quota::new().max_bytes(100));
+        ledger.set_quota(
+            pool.clone(),
+            Quota::new()
+                .max_bytes(10)
+                .action(QuotaAction::Throttle(Duration::from_secs(1))),
+        );
+        assert_eq!(ledger.remaining_bytes(&keys), Some(100));
+
+        ledger.record(&record(30));
+        assert_eq!(ledger.remaining_bytes(&keys), Some(40));
+
+        ledger.record(&record(30));
+        assert_eq!(ledger.remaining_bytes(&keys), Some(0));
+    }
+}
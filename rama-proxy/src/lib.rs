@@ -7,6 +7,10 @@
 //!
 //! See the [`ProxyFilter`] for more information on how to select a proxy,
 //! and the [`ProxyDB`] trait for how to implement a proxy database.
+//! String filters support `*` and `?` glob patterns as well as `!` negation
+//! (e.g. `john-country-!us-city-san*`), and the [`AsnFilter`] can also match
+//! ranges of AS numbers (e.g. `john-asn-64512..65534`).
+//! A label separator within a value is percent-encoded (e.g. `john-city-san%2D*`).
 //!
 //! If you wish to support proxy filters directly from the username,
 //! you can use the [`ProxyFilterUsernameParser`] to extract the proxy filter
@@ -15,6 +19,31 @@
 //! The [`ProxyDB`] is used by Connection Pools to connect via a proxy,
 //! in case a [`ProxyFilter`] is present in the [`Context`]'s [`Extensions`].
 //!
+//! Wrap a [`ProxyDB`] in a [`StickyProxyDB`] to support sticky sessions,
+//! such that all requests of a session (e.g. `john-session-abc123` as username)
+//! are proxied via the same proxy.
+//!
+//! The [`MemoryProxyDB`] selects a random proxy out of all matching proxies by default,
+//! but can be configured to use another [`ProxySelectionStrategy`] instead, such as round-robin,
+//! weighted or least-in-flight selection. A strategy can also be chosen per request,
+//! by inserting it in the [`Context`]'s [`Extensions`].
+//!
+//! Proxies which are only reachable via a gateway proxy can refer to it using [`Proxy::via`],
+//! in which case the [`ProxyDBService`] also inserts the resulting [`ProxyChain`]
+//! in the [`Context`]'s [`Extensions`], to be connected through hop by hop.
+//!
+//! Usage of proxied traffic can be accounted per user, proxy and pool,
+//! and limited using quotas, see the [`accounting`] module.
+//!
+//! Proxies can also be selected per target, as commonly done by clients:
+//! the [`EnvProxyDB`] uses the `HTTP_PROXY`, `HTTPS_PROXY`, `ALL_PROXY` and `NO_PROXY`
+//! environment variables, while the `PacProxyDB` (requires the `pac` feature)
+//! evaluates a Proxy Auto-Configuration file. Both can decide that the target
+//! is to be connected to directly, signaled by a [`DirectConnection`] error,
+//! in which case the [`ProxyDBService`] does not select a proxy at all.
+//! The `pac` feature embeds the QuickJS javascript engine,
+//! which is compiled from its C sources and thus requires a C toolchain.
+//!
 //! # DB Live Reloads
 //!
 //! [`ProxyDB`] implementations like the [`MemoryProxyDB`] feel static in nature, and they are.
@@ -31,6 +60,11 @@
 //! task and wrap the reader in a [`ProxyDB`] implementation. This way you can live reload based upon
 //! a signal, or more realistically, every x minutes.
 //!
+//! For proxy lists kept in CSV or JSON-lines files the `ProxyDBLoader` (requires the `file-loader` feature)
+//! does exactly that for a [`MemoryProxyDB`]: it reloads the file(s) each time they change
+//! and swaps the database via a `LiveUpdateProxyDBSetter`, keeping the last good database
+//! in case the updated file(s) cannot be loaded.
+//!
 //! [`Context`]: rama_core::Context
 //! [`Extensions`]: rama_core::context::Extensions
 //!
@@ -51,6 +85,7 @@
 //! or of course because the inner [`Service`][`rama_core::Service`] failed.
 //!
 //! [`ProxyAddress`]: rama_net::address::ProxyAddress
+//! [`ProxyChain`]: rama_net::address::ProxyChain
 //! [`ProxyDB`]: ProxyDB
 //! [`Context`]: rama_core::Context
 //!
@@ -94,6 +129,8 @@
 //!             city: Some("*".into()),
 //!             carrier: Some("*".into()),
 //!             asn: None,
+//!             weight: None,
+//!             via: None,
 //!         },
 //!         Proxy {
 //!             id: NonEmptyString::from_static("100"),
@@ -114,6 +151,8 @@
 //!             city: None,
 //!             carrier: None,
 //!             asn: None,
+//!             weight: None,
+//!             via: None,
 //!         },
 //!     ])
 //!     .unwrap();
@@ -190,6 +229,8 @@
 //!         city: Some("*".into()),
 //!         carrier: Some("*".into()),
 //!         asn: None,
+//!         weight: None,
+//!         via: None,
 //!     };
 //!
 //!     let service = ProxyDBLayer::new(Arc::new(proxy))
@@ -252,9 +293,13 @@ pub use username::ProxyFilterUsernameParser;
 
 mod proxydb;
 
+pub mod accounting;
+
 #[doc(inline)]
 pub use proxydb::{
-    Proxy, ProxyContext, ProxyDB, ProxyFilter, ProxyID, ProxyQueryPredicate, StringFilter,
+    AsnFilter, DirectConnection, EnvProxyDB, InvalidAsnFilter, NoProxy, Proxy, ProxyContext,
+    ProxyDB, ProxyFilter, ProxyID, ProxyQueryPredicate, ProxySelectionStrategy, ProxyUsageGuard,
+    ProxyUsageTracker, StickyProxyDB, StringFilter,
 };
 
 #[doc(inline)]
@@ -274,3 +319,14 @@ pub use proxydb::{
 #[cfg(feature = "csv")]
 #[doc(inline)]
 pub use proxydb::{ProxyCsvRowReader, ProxyCsvRowReaderError, ProxyCsvRowReaderErrorKind};
+
+#[cfg(feature = "file-loader")]
+#[doc(inline)]
+pub use proxydb::{
+    ProxyDBLoadError, ProxyDBLoadErrorKind, ProxyDBLoader, ProxyDBLoaderMetrics, ProxyFileFormat,
+    ProxyRowError,
+};
+
+#[cfg(feature = "pac")]
+#[doc(inline)]
+pub use proxydb::{PacProxyDB, PacScriptError};
@@ -0,0 +1,254 @@
+use rama_net::asn::Asn;
+use serde::{de, Deserialize, Serialize};
+use std::{fmt, str::FromStr};
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+/// A filter to select proxies by their Autonomous System Number (ASN).
+///
+/// It matches either a single [`Asn`], or an inclusive range of AS numbers,
+/// and can be negated to match all other AS numbers instead.
+///
+/// Its string representation is used for (de)serialization and username labels:
+///
+/// - `7018` (or `AS7018`): matching exactly that [`Asn`];
+/// - `64000..64495` (or `64000-64495`): matching all AS numbers within that (inclusive) range;
+/// - `!7018` or `!64000..64495`: matching all AS numbers except the given one(s).
+///
+/// An unspecified [`Asn`] (`0`) matches any value.
+/// Negated filters only match proxies with a known (specified) [`Asn`].
+pub struct AsnFilter {
+    kind: AsnFilterKind,
+    negated: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum AsnFilterKind {
+    Asn(Asn),
+    Range(u32, u32),
+}
+
+impl AsnFilter {
+    /// Create a new [`AsnFilter`] matching all AS numbers within the given inclusive range.
+    pub fn range(start: u32, end: u32) -> Self {
+        Self {
+            kind: AsnFilterKind::Range(start.min(end), start.max(end)),
+            negated: false,
+        }
+    }
+
+    /// Create the negation of this filter, matching all AS numbers not matched by this filter.
+    pub fn negated(mut self) -> Self {
+        self.negated = !self.negated;
+        self
+    }
+
+    /// Return `true` if this filter is negated, e.g. `!7018`.
+    pub fn is_negated(&self) -> bool {
+        self.negated
+    }
+
+    /// Returns the [`Asn`] matched by this filter,
+    /// in case it is neither a range nor negated.
+    pub fn as_asn(&self) -> Option<&Asn> {
+        match &self.kind {
+            AsnFilterKind::Asn(asn) if !self.negated => Some(asn),
+            _ => None,
+        }
+    }
+
+    /// Return `true` if the given (proxy) [`Asn`] is matched by this filter.
+    pub fn matches(&self, asn: &Asn) -> bool {
+        if self.negated {
+            return !asn.is_any() && !self.matches_kind(asn);
+        }
+        asn.is_any() || self.matches_kind(asn)
+    }
+
+    fn matches_kind(&self, asn: &Asn) -> bool {
+        match &self.kind {
+            AsnFilterKind::Asn(value) => value.is_any() || value == asn,
+            AsnFilterKind::Range(start, end) => (*start..=*end).contains(&asn.as_u32()),
+        }
+    }
+}
+
+impl From<Asn> for AsnFilter {
+    fn from(asn: Asn) -> Self {
+        Self {
+            kind: AsnFilterKind::Asn(asn),
+            negated: false,
+        }
+    }
+}
+
+impl fmt::Display for AsnFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.negated {
+            f.write_str("!")?;
+        }
+        match &self.kind {
+            AsnFilterKind::Asn(asn) => write!(f, "{}", asn.as_u32()),
+            AsnFilterKind::Range(start, end) => write!(f, "{start}..{end}"),
+        }
+    }
+}
+
+impl FromStr for AsnFilter {
+    type Err = InvalidAsnFilter;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        fn parse_number(s: &str) -> Result<u32, InvalidAsnFilter> {
+            let s = s.trim();
+            let s = s
+                .strip_prefix("AS")
+                .or_else(|| s.strip_prefix("as"))
+                .unwrap_or(s);
+            s.parse().map_err(|_| InvalidAsnFilter)
+        }
+
+        let s = s.trim();
+        let (s, negated) = match s.strip_prefix('!') {
+            Some(s) => (s, true),
+            None => (s, false),
+        };
+        let filter = match s.split_once("..").or_else(|| s.split_once('-')) {
+            Some((start, end)) => Self::range(parse_number(start)?, parse_number(end)?),
+            None => Asn::try_from(parse_number(s)?)
+                .map(Self::from)
+                .map_err(|_| InvalidAsnFilter)?,
+        };
+        Ok(if negated { filter.negated() } else { filter })
+    }
+}
+
+impl TryFrom<&str> for AsnFilter {
+    type Error = InvalidAsnFilter;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl Serialize for AsnFilter {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        match self.as_asn() {
+            Some(asn) => asn.serialize(serializer),
+            None => self.to_string().serialize(serializer),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for AsnFilter {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        struct Visitor;
+
+        impl de::Visitor<'_> for Visitor {
+            type Value = AsnFilter;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("an asn number, range or negation thereof")
+            }
+
+            fn visit_u64<E: de::Error>(self, v: u64) -> Result<Self::Value, E> {
+                u32::try_from(v)
+                    .ok()
+                    .and_then(|v| Asn::try_from(v).ok())
+                    .map(AsnFilter::from)
+                    .ok_or_else(|| E::custom("invalid asn"))
+            }
+
+            fn visit_i64<E: de::Error>(self, v: i64) -> Result<Self::Value, E> {
+                u64::try_from(v)
+                    .map_err(|_| E::custom("invalid asn"))
+                    .and_then(|v| self.visit_u64(v))
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+                v.parse().map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_any(Visitor)
+    }
+}
+
+rama_utils::macros::error::static_str_error! {
+    #[doc = "invalid ASN filter"]
+    pub struct InvalidAsnFilter;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_asn_filter_parse_display() {
+        for (input, expected) in [
+            ("7018", "7018"),
+            ("AS7018", "7018"),
+            (" as7018 ", "7018"),
+            ("0", "0"),
+            ("!7018", "!7018"),
+            ("64512..65534", "64512..65534"),
+            ("65534-64512", "64512..65534"),
+            ("!AS1..AS10", "!1..10"),
+        ] {
+            let filter: AsnFilter = input.parse().unwrap();
+            assert_eq!(expected, filter.to_string(), "input: {input}");
+        }
+
+        for input in ["", "!", "foo", "23456", "1..", "..2", "1..2..3"] {
+            assert!(input.parse::<AsnFilter>().is_err(), "input: {input}");
+        }
+    }
+
+    #[test]
+    fn test_asn_filter_matches() {
+        for (filter, asn, expected) in [
+            ("7018", 7018, true),
+            ("7018", 1, false),
+            ("7018", 0, true),
+            ("0", 1, true),
+            ("!7018", 7018, false),
+            ("!7018", 1, true),
+            ("!7018", 0, false),
+            ("64000..64495", 64000, true),
+            ("64000..64495", 64200, true),
+            ("64000..64495", 64495, true),
+            ("64000..64495", 63999, false),
+            ("!64000..64495", 64200, false),
+            ("!64000..64495", 1, true),
+        ] {
+            let filter: AsnFilter = filter.parse().unwrap();
+            assert_eq!(
+                expected,
+                filter.matches(&Asn::try_from(asn).unwrap()),
+                "filter: {filter}, asn: {asn}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_asn_filter_serde() {
+        for (filter, json) in [
+            ("7018", "7018"),
+            ("!7018", "\"!7018\""),
+            ("1..10", "\"1..10\""),
+        ] {
+            let filter: AsnFilter = filter.parse().unwrap();
+            assert_eq!(json, serde_json::to_string(&filter).unwrap());
+            assert_eq!(filter, serde_json::from_str::<AsnFilter>(json).unwrap());
+        }
+        assert_eq!(
+            AsnFilter::from(Asn::from_static(7018)),
+            serde_json::from_str::<AsnFilter>("\"AS7018\"").unwrap()
+        );
+        assert!(serde_json::from_str::<AsnFilter>("23456").is_err());
+    }
+}
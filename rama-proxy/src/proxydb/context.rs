@@ -1,16 +1,53 @@
-use rama_net::transport::{TransportContext, TransportProtocol};
+use super::ProxySelectionStrategy;
+use rama_net::{
+    address::Authority,
+    transport::{TransportContext, TransportProtocol},
+    user::UserId,
+    Protocol,
+};
 
 /// The context as relevant to the proxy layer.
 #[derive(Debug, Clone)]
 pub struct ProxyContext {
     /// The transport protocol used by the proxy.
     pub protocol: TransportProtocol,
+
+    /// The [`ProxySelectionStrategy`] to use for this request,
+    /// overwriting the default strategy of the [`ProxyDB`] if defined.
+    ///
+    /// [`ProxyDB`]: crate::ProxyDB
+    pub selection_strategy: Option<ProxySelectionStrategy>,
+
+    /// The application [`Protocol`] used to connect to the target, if known.
+    pub app_protocol: Option<Protocol>,
+
+    /// The [`Authority`] of the target to connect to via the proxy, if known.
+    ///
+    /// Used by [`ProxyDB`] implementations which select the proxy per target,
+    /// such as the [`EnvProxyDB`].
+    ///
+    /// [`ProxyDB`]: crate::ProxyDB
+    /// [`EnvProxyDB`]: crate::EnvProxyDB
+    pub authority: Option<Authority>,
+
+    /// The [`UserId`] of the user on whose behalf the proxy is selected, if known.
+    ///
+    /// Used by [`ProxyDB`] implementations which keep state per user,
+    /// such as the [`StickyProxyDB`].
+    ///
+    /// [`ProxyDB`]: crate::ProxyDB
+    /// [`StickyProxyDB`]: crate::StickyProxyDB
+    pub user: Option<UserId>,
 }
 
 impl From<TransportContext> for ProxyContext {
     fn from(ctx: TransportContext) -> Self {
         Self {
             protocol: ctx.protocol,
+            selection_strategy: None,
+            app_protocol: ctx.app_protocol,
+            authority: Some(ctx.authority),
+            user: None,
         }
     }
 }
@@ -19,6 +56,10 @@ impl From<&TransportContext> for ProxyContext {
     fn from(ctx: &TransportContext) -> Self {
         Self {
             protocol: ctx.protocol,
+            selection_strategy: None,
+            app_protocol: ctx.app_protocol.clone(),
+            authority: Some(ctx.authority.clone()),
+            user: None,
         }
     }
 }
@@ -42,8 +42,8 @@ impl ProxyCsvRowReader {
                 let line = lines.next_line().await?;
                 match line {
                     Some(line) => Ok(Some(match parse_csv_row(&line) {
-                        Some(proxy) => proxy,
-                        None => {
+                        Ok(proxy) => proxy,
+                        Err(_) => {
                             return Err(ProxyCsvRowReaderError {
                                 kind: ProxyCsvRowReaderErrorKind::InvalidRow(line),
                             });
@@ -54,8 +54,8 @@ impl ProxyCsvRowReader {
             }
             ProxyCsvRowReaderData::Raw(lines) => match lines.pop() {
                 Some(line) => Ok(Some(match parse_csv_row(&line) {
-                    Some(proxy) => proxy,
-                    None => {
+                    Ok(proxy) => proxy,
+                    Err(_) => {
                         return Err(ProxyCsvRowReaderError {
                             kind: ProxyCsvRowReaderErrorKind::InvalidRow(line),
                         });
@@ -73,51 +73,114 @@ fn strip_csv_quotes(p: &str) -> &str {
         .unwrap_or(p)
 }
 
-pub(crate) fn parse_csv_row(row: &str) -> Option<Proxy> {
-    let mut iter = row.split(',').map(strip_csv_quotes);
-
-    let id = iter.next().and_then(|s| s.try_into().ok())?;
-
-    let tcp = iter.next().and_then(parse_csv_bool)?;
-    let udp = iter.next().and_then(parse_csv_bool)?;
-    let http = iter.next().and_then(parse_csv_bool)?;
-    let https = iter.next().and_then(parse_csv_bool)?;
-    let socks5 = iter.next().and_then(parse_csv_bool)?;
-    let socks5h = iter.next().and_then(parse_csv_bool)?;
-    let datacenter = iter.next().and_then(parse_csv_bool)?;
-    let residential = iter.next().and_then(parse_csv_bool)?;
-    let mobile = iter.next().and_then(parse_csv_bool)?;
-    let mut address = iter.next().and_then(|s| {
-        if s.is_empty() {
-            None
-        } else {
-            ProxyAddress::try_from(s).ok()
-        }
-    })?;
-    let pool_id = parse_csv_opt_string_filter(iter.next()?);
-    let continent = parse_csv_opt_string_filter(iter.next()?);
-    let country = parse_csv_opt_string_filter(iter.next()?);
-    let state = parse_csv_opt_string_filter(iter.next()?);
-    let city = parse_csv_opt_string_filter(iter.next()?);
-    let carrier = parse_csv_opt_string_filter(iter.next()?);
-    let asn = parse_csv_opt_asn(iter.next()?).ok()?;
-
-    // support header format or cleartext format
-    if let Some(value) = iter.next() {
-        if !value.is_empty() {
-            let credential = ProxyCredential::try_from_header_str(value)
-                .or_else(|_| ProxyCredential::try_from_clear_str(value.to_owned()))
-                .ok()?;
-            address.credential = Some(credential);
-        }
+/// The columns of a proxy CSV row, in order, of which the last three are optional.
+const CSV_COLUMNS: [&str; 21] = [
+    "id",
+    "tcp",
+    "udp",
+    "http",
+    "https",
+    "socks5",
+    "socks5h",
+    "datacenter",
+    "residential",
+    "mobile",
+    "address",
+    "pool_id",
+    "continent",
+    "country",
+    "state",
+    "city",
+    "carrier",
+    "asn",
+    "credential",
+    "weight",
+    "via",
+];
+
+/// The number of columns required in a proxy CSV row.
+const CSV_REQUIRED_COLUMNS: usize = 18;
+
+/// Parse a proxy CSV row, returning a message describing
+/// the failing column and why it failed in case the row is invalid.
+pub(crate) fn parse_csv_row(row: &str) -> Result<Proxy, String> {
+    let fields: Vec<_> = row.split(',').map(strip_csv_quotes).collect();
+    if !(CSV_REQUIRED_COLUMNS..=CSV_COLUMNS.len()).contains(&fields.len()) {
+        return Err(format!(
+            "expected {CSV_REQUIRED_COLUMNS} to {} fields, found {}",
+            CSV_COLUMNS.len(),
+            fields.len()
+        ));
     }
 
-    // Ensure there are no more values in the row
-    if iter.next().is_some() {
-        return None;
+    let field = |index: usize| fields.get(index).copied().unwrap_or_default();
+    let invalid = |index: usize, reason: &str| {
+        format!("column {} ({}): {reason}", index + 1, CSV_COLUMNS[index])
+    };
+    let bool_field = |index: usize| {
+        parse_csv_bool(field(index))
+            .ok_or_else(|| invalid(index, &format!("invalid bool {:?}", field(index))))
+    };
+
+    let id = match field(0) {
+        "" => return Err(invalid(0, "missing proxy id")),
+        value => value
+            .try_into()
+            .map_err(|_| invalid(0, &format!("invalid proxy id {value:?}")))?,
+    };
+
+    let tcp = bool_field(1)?;
+    let udp = bool_field(2)?;
+    let http = bool_field(3)?;
+    let https = bool_field(4)?;
+    let socks5 = bool_field(5)?;
+    let socks5h = bool_field(6)?;
+    let datacenter = bool_field(7)?;
+    let residential = bool_field(8)?;
+    let mobile = bool_field(9)?;
+    let mut address = match field(10) {
+        "" => return Err(invalid(10, "missing proxy address")),
+        value => ProxyAddress::try_from(value)
+            .map_err(|err| invalid(10, &format!("invalid proxy address {value:?}: {err}")))?,
+    };
+    let pool_id = parse_csv_opt_string_filter(field(11));
+    let continent = parse_csv_opt_string_filter(field(12));
+    let country = parse_csv_opt_string_filter(field(13));
+    let state = parse_csv_opt_string_filter(field(14));
+    let city = parse_csv_opt_string_filter(field(15));
+    let carrier = parse_csv_opt_string_filter(field(16));
+    let asn = parse_csv_opt_asn(field(17))
+        .map_err(|_| invalid(17, &format!("invalid ASN {:?}", field(17))))?;
+
+    // support header format or cleartext format
+    let value = field(18);
+    if !value.is_empty() {
+        // the value is not reported, as it contains secrets
+        let credential = ProxyCredential::try_from_header_str(value)
+            .or_else(|_| ProxyCredential::try_from_clear_str(value.to_owned()))
+            .map_err(|_| invalid(18, "invalid credential"))?;
+        address.credential = Some(credential);
     }
 
-    Some(Proxy {
+    let weight = match field(19) {
+        "" => None,
+        value => Some(
+            value
+                .parse()
+                .map_err(|_| invalid(19, &format!("invalid weight {value:?}")))?,
+        ),
+    };
+
+    let via = match field(20) {
+        "" => None,
+        value => Some(
+            value
+                .try_into()
+                .map_err(|_| invalid(20, &format!("invalid via proxy id {value:?}")))?,
+        ),
+    };
+
+    Ok(Proxy {
         id,
         address,
         tcp,
@@ -136,6 +199,8 @@ pub(crate) fn parse_csv_row(row: &str) -> Option<Proxy> {
         city,
         carrier,
         asn,
+        weight,
+        via,
     })
 }
 
@@ -292,6 +357,8 @@ mod tests {
                     city: None,
                     carrier: None,
                     asn: None,
+                    weight: None,
+                    via: None,
                 },
             ),
             // more happy row tests
@@ -316,6 +383,8 @@ mod tests {
                     city: Some("city".into()),
                     carrier: Some("carrier".into()),
                     asn: None,
+                    weight: None,
+                    via: None,
                 },
             ),
             (
@@ -339,6 +408,8 @@ mod tests {
                     city: Some("*".into()),
                     carrier: Some("carrier".into()),
                     asn: Some(Asn::from_static(13335)),
+                    weight: None,
+                    via: None,
                 },
             ),
             (
@@ -362,6 +433,8 @@ mod tests {
                     city: Some("*".into()),
                     carrier: Some("carrier".into()),
                     asn: Some(Asn::unspecified()),
+                    weight: None,
+                    via: None,
                 },
             ),
             (
@@ -385,6 +458,33 @@ mod tests {
                     city: None,
                     carrier: None,
                     asn: None,
+                    weight: None,
+                    via: None,
+                },
+            ),
+            (
+                "foo,1,0,1,,0,,1,0,0,bar,baz,,US,,,,,,7,gateway",
+                Proxy {
+                    id: NonEmptyString::from_static("foo"),
+                    address: ProxyAddress::from_str("bar").unwrap(),
+                    tcp: true,
+                    udp: false,
+                    http: true,
+                    https: false,
+                    socks5: false,
+                    socks5h: false,
+                    datacenter: true,
+                    residential: false,
+                    mobile: false,
+                    pool_id: Some("baz".into()),
+                    continent: None,
+                    country: Some("us".into()),
+                    state: None,
+                    city: None,
+                    carrier: None,
+                    asn: None,
+                    weight: Some(7),
+                    via: Some(NonEmptyString::from_static("gateway")),
                 },
             ),
         ] {
@@ -405,6 +505,8 @@ mod tests {
             assert_eq!(proxy.city, output.city);
             assert_eq!(proxy.carrier, output.carrier);
             assert_eq!(proxy.asn, output.asn);
+            assert_eq!(proxy.weight, output.weight);
+            assert_eq!(proxy.via, output.via);
         }
     }
 
@@ -434,8 +536,43 @@ mod tests {
             "id,,,,,,,foo,authority,,,,,,,,",
             // invalid credentials
             "id,,,,,,,,authority,,,,,:foo",
+            // invalid weight
+            "foo,1,0,1,,0,,1,0,0,bar,baz,,US,,,,,,abc",
+        ] {
+            assert!(parse_csv_row(input).is_err(), "input: {}", input);
+        }
+    }
+
+    #[test]
+    fn test_parse_csv_row_error_reason() {
+        for (input, expected) in [
+            ("id,true", "expected 18 to 21 fields, found 2"),
+            (
+                "id,yes,,,,,,,,,authority,,,,,,,",
+                "column 2 (tcp): invalid bool \"yes\"",
+            ),
+            (
+                ",,,,,,,,,,authority,,,,,,,",
+                "column 1 (id): missing proxy id",
+            ),
+            (
+                "id,,,,,,,,,,,,,,,,,",
+                "column 11 (address): missing proxy address",
+            ),
+            (
+                "id,,,,,,,,,,authority,,,,,,,AS0",
+                "column 18 (asn): invalid ASN \"AS0\"",
+            ),
+            (
+                "id,,,,,,,,,,authority,,,,,,,,,abc",
+                "column 20 (weight): invalid weight \"abc\"",
+            ),
         ] {
-            assert!(parse_csv_row(input).is_none(), "input: {}", input);
+            assert_eq!(
+                expected,
+                parse_csv_row(input).unwrap_err(),
+                "input: {input}"
+            );
         }
     }
 
@@ -537,6 +674,10 @@ mod tests {
         let proxy = parse_csv_row("id,1,,1,,,,,,,authority,*,*,*,*,*,*,0").unwrap();
         let ctx = ProxyContext {
             protocol: TransportProtocol::Tcp,
+            selection_strategy: None,
+            app_protocol: None,
+            authority: None,
+            user: None,
         };
 
         for filter in [
@@ -585,6 +726,10 @@ mod tests {
                 .unwrap();
         let ctx = ProxyContext {
             protocol: TransportProtocol::Tcp,
+            selection_strategy: None,
+            app_protocol: None,
+            authority: None,
+            user: None,
         };
 
         for filter in [
@@ -620,7 +765,7 @@ mod tests {
                 state: Some(vec![StringFilter::new("state")]),
                 city: Some(vec![StringFilter::new("city")]),
                 carrier: Some(vec![StringFilter::new("carrier")]),
-                asn: Some(vec![Asn::from_static(42)]),
+                asn: Some(vec![Asn::from_static(42).into()]),
                 ..Default::default()
             },
             ProxyFilter {
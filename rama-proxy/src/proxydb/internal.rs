@@ -1,5 +1,5 @@
-use super::{ProxyContext, ProxyFilter, StringFilter};
-use rama_net::{address::ProxyAddress, asn::Asn, transport::TransportProtocol};
+use super::{matches_filters, ProxyContext, ProxyFilter, StringFilter};
+use rama_net::{address::ProxyAddress, asn::Asn, transport::TransportProtocol, Protocol};
 use rama_utils::str::NonEmptyString;
 use serde::{Deserialize, Serialize};
 
@@ -72,6 +72,22 @@ pub struct Proxy {
     #[cfg_attr(feature = "memory-db", venndb(filter, any))]
     ///  Autonomous System Number (ASN).
     pub asn: Option<Asn>,
+
+    /// Relative weight of the proxy, used by the [`ProxySelectionStrategy::Weighted`] strategy.
+    ///
+    /// A proxy without weight has a weight of `1`.
+    ///
+    /// [`ProxySelectionStrategy::Weighted`]: crate::ProxySelectionStrategy::Weighted
+    pub weight: Option<u32>,
+
+    /// Id of the gateway proxy through which this proxy is to be reached, if any.
+    ///
+    /// The gateway can itself be reachable only via another gateway,
+    /// resulting in a [`ProxyChain`] set by the [`ProxyDBService`].
+    ///
+    /// [`ProxyChain`]: rama_net::address::ProxyChain
+    /// [`ProxyDBService`]: crate::ProxyDBService
+    pub via: Option<NonEmptyString>,
 }
 
 #[cfg(feature = "memory-db")]
@@ -83,6 +99,42 @@ fn proxydb_insert_validator(proxy: &Proxy) -> bool {
 }
 
 impl Proxy {
+    /// Create a [`Proxy`] with the given id for the given [`ProxyAddress`],
+    /// supporting the protocol of the address (http if none is defined).
+    ///
+    /// Used by [`ProxyDB`] implementations which derive the proxy per request,
+    /// and thus have no metadata (e.g. location) about it.
+    ///
+    /// [`ProxyDB`]: crate::ProxyDB
+    pub(super) fn from_address(id: NonEmptyString, mut address: ProxyAddress) -> Self {
+        let protocol = address.protocol.get_or_insert(Protocol::HTTP);
+        let (socks5, socks5h) = (protocol.is_socks5(), protocol.is_socks5h());
+        let https = protocol.is_http() && protocol.is_secure();
+        let http = protocol.is_http() && !https;
+        Self {
+            id,
+            address,
+            tcp: true,
+            udp: socks5 || socks5h,
+            http,
+            https,
+            socks5,
+            socks5h,
+            datacenter: false,
+            residential: false,
+            mobile: false,
+            pool_id: None,
+            continent: None,
+            country: None,
+            state: None,
+            city: None,
+            carrier: None,
+            asn: None,
+            weight: None,
+            via: None,
+        }
+    }
+
     /// Check if the proxy is a match for the given[`ProxyContext`] and [`ProxyFilter`].
     pub fn is_match(&self, ctx: &ProxyContext, filter: &ProxyFilter) -> bool {
         if let Some(id) = &filter.id {
@@ -104,61 +156,23 @@ impl Proxy {
             }
         }
 
-        filter
-            .continent
-            .as_ref()
-            .map(|c| {
-                let continent = self.continent.as_ref();
-                c.iter().any(|c| Some(c) == continent)
-            })
-            .unwrap_or(true)
-            && filter
-                .country
-                .as_ref()
-                .map(|c| {
-                    let country = self.country.as_ref();
-                    c.iter().any(|c| Some(c) == country)
-                })
-                .unwrap_or(true)
-            && filter
-                .state
-                .as_ref()
-                .map(|s| {
-                    let state = self.state.as_ref();
-                    s.iter().any(|s| Some(s) == state)
-                })
-                .unwrap_or(true)
-            && filter
-                .city
-                .as_ref()
-                .map(|c| {
-                    let city = self.city.as_ref();
-                    c.iter().any(|c| Some(c) == city)
-                })
-                .unwrap_or(true)
-            && filter
-                .pool_id
-                .as_ref()
-                .map(|p| {
-                    let pool_id = self.pool_id.as_ref();
-                    p.iter().any(|p| Some(p) == pool_id)
-                })
-                .unwrap_or(true)
-            && filter
-                .carrier
-                .as_ref()
-                .map(|c| {
-                    let carrier = self.carrier.as_ref();
-                    c.iter().any(|c| Some(c) == carrier)
-                })
+        let matches = |filters: &Option<Vec<StringFilter>>, value: &Option<StringFilter>| {
+            filters
+                .as_deref()
+                .map(|filters| matches_filters(filters, value.as_ref()))
                 .unwrap_or(true)
+        };
+
+        matches(&filter.continent, &self.continent)
+            && matches(&filter.country, &self.country)
+            && matches(&filter.state, &self.state)
+            && matches(&filter.city, &self.city)
+            && matches(&filter.pool_id, &self.pool_id)
+            && matches(&filter.carrier, &self.carrier)
             && filter
                 .asn
-                .as_ref()
-                .map(|a| {
-                    let asn = self.asn.as_ref();
-                    a.iter().any(|a| Some(a) == asn)
-                })
+                .as_deref()
+                .map(|filters| matches_filters(filters, self.asn.as_ref()))
                 .unwrap_or(true)
             && filter
                 .datacenter
@@ -0,0 +1,756 @@
+use super::{
+    csv::parse_csv_row, LiveUpdateProxyDBSetter, MemoryProxyDB, MemoryProxyDBInsertError, Proxy,
+    ProxySelectionStrategy, ProxyUsageTracker,
+};
+use std::{
+    collections::HashMap,
+    fmt,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc, Mutex, PoisonError,
+    },
+    time::{Duration, SystemTime},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+/// The format of a file loaded by the [`ProxyDBLoader`].
+pub enum ProxyFileFormat {
+    /// One proxy per line, in the format read by the [`ProxyCsvRowReader`].
+    ///
+    /// [`ProxyCsvRowReader`]: crate::ProxyCsvRowReader
+    Csv,
+    /// One proxy per line, as a JSON object in the (serde) format of [`Proxy`].
+    JsonLines,
+}
+
+impl ProxyFileFormat {
+    /// Detect the format of a file based on its extension:
+    /// `csv` for [`ProxyFileFormat::Csv`] and `jsonl`, `ndjson`
+    /// or `json` for [`ProxyFileFormat::JsonLines`].
+    pub fn from_path(path: impl AsRef<Path>) -> Option<Self> {
+        let ext = path.as_ref().extension()?.to_str()?;
+        rama_utils::macros::match_ignore_ascii_case_str! {
+            match(ext) {
+                "csv" => Some(Self::Csv),
+                "jsonl" | "ndjson" | "json" => Some(Self::JsonLines),
+                _ => None,
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+/// A loader which (re)loads a [`MemoryProxyDB`] from a CSV or JSON-lines file,
+/// or a directory of such files, and sets it via a [`LiveUpdateProxyDBSetter`].
+///
+/// Empty lines and lines starting with `#` are ignored.
+/// Rows which cannot be parsed are reported with their file and line number,
+/// and fail the load as a whole unless [`ProxyDBLoader::with_allow_invalid_rows`] is enabled,
+/// in which case these rows are skipped instead. Loads resulting in no proxies fail as well.
+///
+/// On failure the previously loaded database is kept in use,
+/// such that a bad update of the proxy file(s) does not take down the proxy selection.
+/// The outcome of all (re)loads is recorded in the [`ProxyDBLoaderMetrics`].
+///
+/// Use [`ProxyDBLoader::watch`] to reload the database each time the file(s) change.
+/// Changes are detected by polling the size and modification time of the file(s).
+/// It is recommended to update files atomically (e.g. by renaming a temporary file),
+/// even though a change is only loaded once the file(s) remained unchanged for a poll interval.
+pub struct ProxyDBLoader {
+    path: PathBuf,
+    format: Option<ProxyFileFormat>,
+    poll_interval: Duration,
+    allow_invalid_rows: bool,
+    selection_strategy: ProxySelectionStrategy,
+    usage_tracker: ProxyUsageTracker,
+    metrics: ProxyDBLoaderMetrics,
+}
+
+impl ProxyDBLoader {
+    /// Create a new [`ProxyDBLoader`] for the given file or directory.
+    ///
+    /// The format of the file(s) is detected based on their extension,
+    /// see [`ProxyFileFormat::from_path`], unless defined explicitly.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            format: None,
+            poll_interval: Duration::from_secs(5),
+            allow_invalid_rows: false,
+            selection_strategy: ProxySelectionStrategy::default(),
+            usage_tracker: ProxyUsageTracker::default(),
+            metrics: ProxyDBLoaderMetrics::default(),
+        }
+    }
+
+    /// Define the [`ProxyFileFormat`] of the file(s) to load.
+    ///
+    /// When loading a directory only the files with an extension
+    /// matching this format are loaded.
+    pub fn with_format(mut self, format: ProxyFileFormat) -> Self {
+        self.format = Some(format);
+        self
+    }
+
+    /// Define the [`ProxyFileFormat`] of the file(s) to load.
+    ///
+    /// When loading a directory only the files with an extension
+    /// matching this format are loaded.
+    pub fn set_format(&mut self, format: ProxyFileFormat) -> &mut Self {
+        self.format = Some(format);
+        self
+    }
+
+    /// Set the interval at which [`ProxyDBLoader::watch`] checks the file(s) for changes,
+    /// 5 seconds by default.
+    pub fn with_poll_interval(mut self, interval: Duration) -> Self {
+        self.poll_interval = interval;
+        self
+    }
+
+    /// Set the interval at which [`ProxyDBLoader::watch`] checks the file(s) for changes,
+    /// 5 seconds by default.
+    pub fn set_poll_interval(&mut self, interval: Duration) -> &mut Self {
+        self.poll_interval = interval;
+        self
+    }
+
+    /// Define whether rows which cannot be parsed are skipped,
+    /// rather than failing the load as a whole (default).
+    pub fn with_allow_invalid_rows(mut self, allow: bool) -> Self {
+        self.allow_invalid_rows = allow;
+        self
+    }
+
+    /// Define whether rows which cannot be parsed are skipped,
+    /// rather than failing the load as a whole (default).
+    pub fn set_allow_invalid_rows(&mut self, allow: bool) -> &mut Self {
+        self.allow_invalid_rows = allow;
+        self
+    }
+
+    /// Set the [`ProxySelectionStrategy`] of the loaded [`MemoryProxyDB`]s.
+    pub fn with_selection_strategy(mut self, strategy: ProxySelectionStrategy) -> Self {
+        self.selection_strategy = strategy;
+        self
+    }
+
+    /// Set the [`ProxySelectionStrategy`] of the loaded [`MemoryProxyDB`]s.
+    pub fn set_selection_strategy(&mut self, strategy: ProxySelectionStrategy) -> &mut Self {
+        self.selection_strategy = strategy;
+        self
+    }
+
+    /// Set the [`ProxyUsageTracker`] shared by all loaded [`MemoryProxyDB`]s,
+    /// such that the usage of proxies is preserved across reloads.
+    pub fn with_usage_tracker(mut self, tracker: ProxyUsageTracker) -> Self {
+        self.usage_tracker = tracker;
+        self
+    }
+
+    /// Set the [`ProxyUsageTracker`] shared by all loaded [`MemoryProxyDB`]s,
+    /// such that the usage of proxies is preserved across reloads.
+    pub fn set_usage_tracker(&mut self, tracker: ProxyUsageTracker) -> &mut Self {
+        self.usage_tracker = tracker;
+        self
+    }
+
+    /// Returns the [`ProxyDBLoaderMetrics`] of this loader,
+    /// shared by all its clones.
+    pub fn metrics(&self) -> &ProxyDBLoaderMetrics {
+        &self.metrics
+    }
+
+    /// Load a new [`MemoryProxyDB`] from the file(s).
+    ///
+    /// Unlike [`ProxyDBLoader::reload`] this does not record any [`ProxyDBLoaderMetrics`].
+    pub async fn load(&self) -> Result<MemoryProxyDB, ProxyDBLoadError> {
+        self.load_proxies().await.map(|(db, _)| db)
+    }
+
+    async fn load_proxies(&self) -> Result<(MemoryProxyDB, usize), ProxyDBLoadError> {
+        let mut proxies = Vec::new();
+        let mut errors = Vec::new();
+        let mut ids: HashMap<String, (PathBuf, usize)> = HashMap::new();
+
+        for (path, format) in self.files().await? {
+            let content = tokio::fs::read_to_string(&path).await?;
+            for (index, line) in content.lines().enumerate() {
+                let line = line.trim();
+                if line.is_empty() || line.starts_with('#') {
+                    continue;
+                }
+                let row_error = |message: String| ProxyRowError {
+                    path: path.clone(),
+                    line: index + 1,
+                    message,
+                };
+
+                let proxy = match parse_row(format, line) {
+                    Ok(proxy) => proxy,
+                    Err(message) => {
+                        errors.push(row_error(message));
+                        continue;
+                    }
+                };
+                if let Some((first_path, first_line)) = ids.get(proxy.id.as_str()) {
+                    errors.push(row_error(format!(
+                        "duplicate proxy id {}, first defined at {}:{first_line}",
+                        proxy.id,
+                        first_path.display()
+                    )));
+                    continue;
+                }
+                ids.insert(proxy.id.as_str().to_owned(), (path.clone(), index + 1));
+                proxies.push(proxy);
+            }
+        }
+
+        if !errors.is_empty() {
+            if !self.allow_invalid_rows {
+                return Err(ProxyDBLoadError {
+                    kind: ProxyDBLoadErrorKind::InvalidRows(errors),
+                });
+            }
+            for err in &errors {
+                tracing::warn!(%err, "proxy db loader: skip invalid row");
+            }
+        }
+        if proxies.is_empty() {
+            return Err(ProxyDBLoadError {
+                kind: ProxyDBLoadErrorKind::Empty,
+            });
+        }
+
+        let db = MemoryProxyDB::try_from_rows(proxies)
+            .map_err(|err| ProxyDBLoadError {
+                kind: ProxyDBLoadErrorKind::InsertError(err),
+            })?
+            .with_selection_strategy(self.selection_strategy)
+            .with_usage_tracker(self.usage_tracker.clone());
+        Ok((db, errors.len()))
+    }
+
+    /// Load a new [`MemoryProxyDB`] from the file(s) and set it using the given setter,
+    /// returning the number of loaded proxies.
+    ///
+    /// On failure the error is returned and the previously set database is kept.
+    pub async fn reload(
+        &self,
+        setter: &LiveUpdateProxyDBSetter<MemoryProxyDB>,
+    ) -> Result<usize, ProxyDBLoadError> {
+        match self.load_proxies().await {
+            Ok((db, rejected_rows)) => {
+                let count = db.len();
+                setter.set(db);
+                self.metrics.record_success(count, rejected_rows);
+                tracing::debug!(
+                    path = %self.path.display(),
+                    proxies = count,
+                    rejected_rows,
+                    "proxy db loader: reloaded proxy db"
+                );
+                Ok(count)
+            }
+            Err(err) => {
+                self.metrics.record_failure(&err);
+                tracing::error!(
+                    path = %self.path.display(),
+                    %err,
+                    "proxy db loader: failed to reload proxy db, keep last good proxy db"
+                );
+                Err(err)
+            }
+        }
+    }
+
+    /// Load the [`MemoryProxyDB`] and reload it each time the file(s) change,
+    /// setting it using the given setter.
+    ///
+    /// This future runs until dropped, and is typically spawned as a (graceful) task.
+    /// Failed (re)loads are logged and recorded in the [`ProxyDBLoaderMetrics`].
+    pub async fn watch(self, setter: LiveUpdateProxyDBSetter<MemoryProxyDB>) {
+        // state of the file(s) as last loaded and as seen during the previous poll,
+        // where `Some(None)` represents the file(s) being unavailable
+        let mut loaded: Option<Option<Vec<FileState>>> = None;
+        let mut previous: Option<Option<Vec<FileState>>> = None;
+
+        loop {
+            let current = self.file_states().await.ok();
+            // only reload changes once the file(s) remained unchanged for an interval,
+            // to avoid loading files which are still being written
+            let settled = loaded.is_none() || previous.as_ref() == Some(&current);
+            if settled && loaded.as_ref() != Some(&current) {
+                let _ = self.reload(&setter).await;
+                loaded = Some(current.clone());
+            }
+            previous = Some(current);
+
+            tokio::time::sleep(self.poll_interval).await;
+        }
+    }
+
+    async fn files(&self) -> Result<Vec<(PathBuf, ProxyFileFormat)>, ProxyDBLoadError> {
+        let metadata = tokio::fs::metadata(&self.path).await?;
+        if !metadata.is_dir() {
+            let format = self
+                .format
+                .or_else(|| ProxyFileFormat::from_path(&self.path))
+                .ok_or_else(|| ProxyDBLoadError {
+                    kind: ProxyDBLoadErrorKind::UnknownFormat(self.path.clone()),
+                })?;
+            return Ok(vec![(self.path.clone(), format)]);
+        }
+
+        let mut files = Vec::new();
+        let mut entries = tokio::fs::read_dir(&self.path).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            let Some(format) = ProxyFileFormat::from_path(&path) else {
+                continue;
+            };
+            if self.format.is_some_and(|f| f != format) || !entry.file_type().await?.is_file() {
+                continue;
+            }
+            files.push((path, format));
+        }
+        files.sort_by(|(a, _), (b, _)| a.cmp(b));
+        Ok(files)
+    }
+
+    async fn file_states(&self) -> Result<Vec<FileState>, ProxyDBLoadError> {
+        let mut states = Vec::new();
+        for (path, _) in self.files().await? {
+            let metadata = tokio::fs::metadata(&path).await?;
+            states.push(FileState {
+                len: metadata.len(),
+                modified: metadata.modified().ok(),
+                path,
+            });
+        }
+        Ok(states)
+    }
+}
+
+fn parse_row(format: ProxyFileFormat, line: &str) -> Result<Proxy, String> {
+    match format {
+        ProxyFileFormat::Csv => {
+            parse_csv_row(line).map_err(|reason| format!("invalid csv row: {reason}"))
+        }
+        ProxyFileFormat::JsonLines => {
+            serde_json::from_str(line).map_err(|err| format!("invalid json row: {err}"))
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct FileState {
+    path: PathBuf,
+    len: u64,
+    modified: Option<SystemTime>,
+}
+
+#[derive(Debug, Clone, Default)]
+/// Metrics of the (re)loads performed by a [`ProxyDBLoader`].
+///
+/// The metrics are shared by all its clones.
+pub struct ProxyDBLoaderMetrics(Arc<LoaderMetrics>);
+
+#[derive(Debug, Default)]
+struct LoaderMetrics {
+    reloads: AtomicU64,
+    failed_reloads: AtomicU64,
+    proxies: AtomicUsize,
+    rejected_rows: AtomicUsize,
+    last_reload: Mutex<Option<SystemTime>>,
+    last_error: Mutex<Option<String>>,
+}
+
+impl ProxyDBLoaderMetrics {
+    /// Returns the number of successful reloads.
+    pub fn reloads(&self) -> u64 {
+        self.0.reloads.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of failed reloads.
+    pub fn failed_reloads(&self) -> u64 {
+        self.0.failed_reloads.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of proxies in the last loaded database.
+    pub fn proxies(&self) -> usize {
+        self.0.proxies.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of invalid rows skipped by the last successful reload.
+    pub fn rejected_rows(&self) -> usize {
+        self.0.rejected_rows.load(Ordering::Relaxed)
+    }
+
+    /// Returns the time of the last successful reload.
+    pub fn last_reload(&self) -> Option<SystemTime> {
+        *self
+            .0
+            .last_reload
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Returns the error of the last reload, if it failed.
+    pub fn last_error(&self) -> Option<String> {
+        self.0
+            .last_error
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    fn record_success(&self, proxies: usize, rejected_rows: usize) {
+        self.0.reloads.fetch_add(1, Ordering::Relaxed);
+        self.0.proxies.store(proxies, Ordering::Relaxed);
+        self.0.rejected_rows.store(rejected_rows, Ordering::Relaxed);
+        *self
+            .0
+            .last_reload
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = Some(SystemTime::now());
+        *self
+            .0
+            .last_error
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = None;
+    }
+
+    fn record_failure(&self, err: &ProxyDBLoadError) {
+        self.0.failed_reloads.fetch_add(1, Ordering::Relaxed);
+        *self
+            .0
+            .last_error
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = Some(err.to_string());
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// A row which could not be loaded by the [`ProxyDBLoader`].
+pub struct ProxyRowError {
+    path: PathBuf,
+    line: usize,
+    message: String,
+}
+
+impl ProxyRowError {
+    /// Returns the path of the file containing the row.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the (1-based) line number of the row.
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    /// Returns the reason why the row could not be loaded.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl fmt::Display for ProxyRowError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.path.display(), self.line, self.message)
+    }
+}
+
+impl std::error::Error for ProxyRowError {}
+
+#[derive(Debug)]
+/// An error that can occur when loading a [`MemoryProxyDB`] using the [`ProxyDBLoader`].
+pub struct ProxyDBLoadError {
+    kind: ProxyDBLoadErrorKind,
+}
+
+#[derive(Debug)]
+/// The kind of error that [`ProxyDBLoadError`] represents.
+pub enum ProxyDBLoadErrorKind {
+    /// An I/O error occurred while reading the file(s).
+    IoError(std::io::Error),
+    /// The format of the file could not be detected.
+    UnknownFormat(PathBuf),
+    /// One or more rows could not be parsed.
+    InvalidRows(Vec<ProxyRowError>),
+    /// No proxies were found in the file(s).
+    Empty,
+    /// The proxies could not be inserted in the [`MemoryProxyDB`].
+    InsertError(MemoryProxyDBInsertError),
+}
+
+impl ProxyDBLoadError {
+    /// Returns the kind of error that occurred.
+    pub fn kind(&self) -> &ProxyDBLoadErrorKind {
+        &self.kind
+    }
+
+    /// Returns the rows which could not be parsed, if any.
+    pub fn row_errors(&self) -> &[ProxyRowError] {
+        match &self.kind {
+            ProxyDBLoadErrorKind::InvalidRows(errors) => errors,
+            _ => &[],
+        }
+    }
+}
+
+impl fmt::Display for ProxyDBLoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            ProxyDBLoadErrorKind::IoError(err) => write!(f, "I/O error: {err}"),
+            ProxyDBLoadErrorKind::UnknownFormat(path) => {
+                write!(f, "unknown proxy file format: {}", path.display())
+            }
+            ProxyDBLoadErrorKind::InvalidRows(errors) => {
+                write!(f, "{} invalid row(s)", errors.len())?;
+                for (index, err) in errors.iter().enumerate() {
+                    f.write_str(if index == 0 { ": " } else { "; " })?;
+                    err.fmt(f)?;
+                }
+                Ok(())
+            }
+            ProxyDBLoadErrorKind::Empty => f.write_str("no proxies found"),
+            ProxyDBLoadErrorKind::InsertError(err) => write!(f, "insert error: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for ProxyDBLoadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match &self.kind {
+            ProxyDBLoadErrorKind::IoError(err) => Some(err),
+            ProxyDBLoadErrorKind::InsertError(err) => Some(err),
+            ProxyDBLoadErrorKind::InvalidRows(errors) => errors
+                .first()
+                .map(|err| err as &(dyn std::error::Error + 'static)),
+            ProxyDBLoadErrorKind::UnknownFormat(_) | ProxyDBLoadErrorKind::Empty => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for ProxyDBLoadError {
+    fn from(err: std::io::Error) -> Self {
+        Self {
+            kind: ProxyDBLoadErrorKind::IoError(err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{proxy_db_updater, ProxyContext, ProxyDB, ProxyFilter};
+    use rama_net::transport::TransportProtocol;
+
+    const ROW_FOO: &str = "foo,1,0,1,,0,,1,0,0,1.1.1.1:8080,,,US,,,,";
+    const ROW_BAR: &str = "bar,1,0,1,,0,,1,0,0,2.2.2.2:8080,,,BE,,,,";
+
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new() -> Self {
+            static COUNTER: AtomicUsize = AtomicUsize::new(0);
+            let path = std::env::temp_dir().join(format!(
+                "rama-proxy-loader-{}-{}",
+                std::process::id(),
+                COUNTER.fetch_add(1, Ordering::Relaxed)
+            ));
+            std::fs::create_dir_all(&path).unwrap();
+            Self(path)
+        }
+
+        fn write(&self, name: &str, content: &str) -> PathBuf {
+            let path = self.0.join(name);
+            std::fs::write(&path, content).unwrap();
+            path
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn ctx() -> ProxyContext {
+        ProxyContext {
+            protocol: TransportProtocol::Tcp,
+            selection_strategy: None,
+            app_protocol: None,
+            authority: None,
+            user: None,
+        }
+    }
+
+    fn json_row(row: &str) -> String {
+        serde_json::to_string(&parse_csv_row(row).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_proxy_file_format_from_path() {
+        for (path, expected) in [
+            ("proxies.csv", Some(ProxyFileFormat::Csv)),
+            ("/etc/proxies.CSV", Some(ProxyFileFormat::Csv)),
+            ("proxies.jsonl", Some(ProxyFileFormat::JsonLines)),
+            ("proxies.ndjson", Some(ProxyFileFormat::JsonLines)),
+            ("proxies.json", Some(ProxyFileFormat::JsonLines)),
+            ("proxies.txt", None),
+            ("proxies", None),
+        ] {
+            assert_eq!(expected, ProxyFileFormat::from_path(path), "path: {path}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_load_directory() {
+        let dir = TempDir::new();
+        dir.write("a.csv", &format!("# proxies\n{ROW_FOO}\n\n"));
+        dir.write(
+            "b.jsonl",
+            &json_row("baz,1,0,1,,0,,1,0,0,3.3.3.3:8080,,,NL,,,,"),
+        );
+        dir.write("README.txt", "not a proxy file");
+
+        let db = ProxyDBLoader::new(&dir.0).load().await.unwrap();
+        assert_eq!(2, db.len());
+
+        let db = ProxyDBLoader::new(&dir.0)
+            .with_format(ProxyFileFormat::Csv)
+            .load()
+            .await
+            .unwrap();
+        assert_eq!(1, db.len());
+    }
+
+    #[tokio::test]
+    async fn test_load_row_errors() {
+        let dir = TempDir::new();
+        let path = dir.write(
+            "proxies.csv",
+            &format!("{ROW_FOO}\n{ROW_BAR}\ninvalid,row\n# comment\n{ROW_FOO}\n"),
+        );
+
+        let err = ProxyDBLoader::new(&path).load().await.unwrap_err();
+        let errors = err.row_errors();
+        assert_eq!(2, errors.len());
+        assert_eq!(path, errors[0].path());
+        assert_eq!(3, errors[0].line());
+        assert_eq!(
+            "invalid csv row: expected 18 to 21 fields, found 2",
+            errors[0].message()
+        );
+        assert_eq!(5, errors[1].line());
+        assert!(errors[1].message().contains("duplicate proxy id foo"));
+        assert!(err.to_string().starts_with("2 invalid row(s): "));
+
+        let loader = ProxyDBLoader::new(&path).with_allow_invalid_rows(true);
+        let (_, setter) = proxy_db_updater();
+        assert_eq!(2, loader.reload(&setter).await.unwrap());
+        assert_eq!(2, loader.metrics().rejected_rows());
+
+        let path = dir.write("empty.jsonl", "# no proxies\n");
+        let err = ProxyDBLoader::new(&path).load().await.unwrap_err();
+        assert!(matches!(err.kind(), ProxyDBLoadErrorKind::Empty));
+
+        let path = dir.write("proxies.txt", ROW_FOO);
+        let err = ProxyDBLoader::new(&path).load().await.unwrap_err();
+        assert!(matches!(err.kind(), ProxyDBLoadErrorKind::UnknownFormat(_)));
+    }
+
+    #[tokio::test]
+    async fn test_reload_keeps_last_good_db() {
+        let dir = TempDir::new();
+        let path = dir.write("proxies.csv", ROW_FOO);
+
+        let loader = ProxyDBLoader::new(&path);
+        let (reader, setter) = proxy_db_updater();
+
+        assert_eq!(1, loader.reload(&setter).await.unwrap());
+        assert_eq!(1, loader.metrics().reloads());
+        assert_eq!(1, loader.metrics().proxies());
+        assert!(loader.metrics().last_reload().is_some());
+
+        dir.write("proxies.csv", "invalid,row");
+        assert!(loader.reload(&setter).await.is_err());
+        assert_eq!(1, loader.metrics().reloads());
+        assert_eq!(1, loader.metrics().failed_reloads());
+        assert!(loader
+            .metrics()
+            .last_error()
+            .unwrap()
+            .contains("proxies.csv:1: invalid csv row"));
+
+        let proxy = reader
+            .get_proxy(ctx(), ProxyFilter::default())
+            .await
+            .unwrap();
+        assert_eq!("foo", proxy.id.as_str());
+
+        std::fs::remove_file(&path).unwrap();
+        assert!(loader.reload(&setter).await.is_err());
+        assert_eq!(2, loader.metrics().failed_reloads());
+        assert!(reader
+            .get_proxy(ctx(), ProxyFilter::default())
+            .await
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_watch() {
+        let dir = TempDir::new();
+        let path = dir.write("proxies.csv", ROW_FOO);
+
+        let loader = ProxyDBLoader::new(&path).with_poll_interval(Duration::from_millis(10));
+        let metrics = loader.metrics().clone();
+        let (reader, setter) = proxy_db_updater();
+        let handle = tokio::spawn(loader.watch(setter));
+
+        let wait_for = |reloads: u64, failed_reloads: u64| {
+            let metrics = metrics.clone();
+            async move {
+                tokio::time::timeout(Duration::from_secs(5), async {
+                    while metrics.reloads() < reloads || metrics.failed_reloads() < failed_reloads {
+                        tokio::time::sleep(Duration::from_millis(5)).await;
+                    }
+                })
+                .await
+                .unwrap();
+            }
+        };
+
+        wait_for(1, 0).await;
+        assert_eq!(1, metrics.proxies());
+
+        dir.write("proxies.csv", &format!("{ROW_FOO}\n{ROW_BAR}\n"));
+        wait_for(2, 0).await;
+        assert_eq!(2, metrics.proxies());
+        let proxy = reader
+            .get_proxy(
+                ctx(),
+                ProxyFilter {
+                    country: Some(vec!["be".into()]),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!("bar", proxy.id.as_str());
+
+        dir.write("proxies.csv", "invalid,row\n");
+        wait_for(2, 1).await;
+        assert_eq!(2, metrics.proxies());
+        assert!(reader
+            .get_proxy(ctx(), ProxyFilter::default())
+            .await
+            .is_ok());
+
+        handle.abort();
+    }
+}
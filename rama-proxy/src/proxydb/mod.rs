@@ -1,5 +1,4 @@
 use rama_core::error::{BoxError, ErrorContext, OpaqueError};
-use rama_net::asn::Asn;
 use rama_utils::str::NonEmptyString;
 use serde::{Deserialize, Serialize};
 use std::{fmt, future::Future};
@@ -13,6 +12,14 @@ pub use update::{proxy_db_updater, LiveUpdateProxyDB, LiveUpdateProxyDBSetter};
 mod context;
 pub use context::ProxyContext;
 
+mod sticky;
+#[doc(inline)]
+pub use sticky::StickyProxyDB;
+
+mod selection;
+#[doc(inline)]
+pub use selection::{ProxySelectionStrategy, ProxyUsageGuard, ProxyUsageTracker};
+
 mod internal;
 #[doc(inline)]
 pub use internal::Proxy;
@@ -24,12 +31,36 @@ mod csv;
 #[doc(inline)]
 pub use csv::{ProxyCsvRowReader, ProxyCsvRowReaderError, ProxyCsvRowReaderErrorKind};
 
+#[cfg(feature = "file-loader")]
+mod loader;
+
+#[cfg(feature = "file-loader")]
+#[doc(inline)]
+pub use loader::{
+    ProxyDBLoadError, ProxyDBLoadErrorKind, ProxyDBLoader, ProxyDBLoaderMetrics, ProxyFileFormat,
+    ProxyRowError,
+};
+
 pub(super) mod layer;
 
+mod env;
+#[doc(inline)]
+pub use env::{EnvProxyDB, NoProxy};
+
+#[cfg(feature = "pac")]
+mod pac;
+#[cfg(feature = "pac")]
+#[doc(inline)]
+pub use pac::{PacProxyDB, PacScriptError};
+
 mod str;
 #[doc(inline)]
 pub use str::StringFilter;
 
+mod asn;
+#[doc(inline)]
+pub use asn::{AsnFilter, InvalidAsnFilter};
+
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 /// `ID` of the selected proxy. To be inserted into the `Context`,
 /// only if that proxy is selected.
@@ -73,6 +104,13 @@ impl From<NonEmptyString> for ProxyID {
 /// Filters can be combined to make combinations with special meaning.
 /// E.g. `datacenter:true, residential:true` is essentially an ISP proxy.
 ///
+/// The values of a list field (e.g. `country`) are combined as follows:
+/// a proxy matches if it matches any of the positive values (if any),
+/// and all of the negated values. E.g. `["!us", "!ca"]` selects a proxy
+/// from any country except the US and Canada. See [`StringFilter`] and [`AsnFilter`]
+/// for the supported patterns, such as `san-*` or `64512..65534`.
+/// Boolean fields can be set to `false` to exclude proxies, e.g. `mobile: false` for "not mobile".
+///
 /// ## Usage
 ///
 /// - Use `HeaderConfigLayer` (`rama-http`) to have this proxy filter be given by the http `Request` headers,
@@ -116,7 +154,76 @@ pub struct ProxyFilter {
     pub carrier: Option<Vec<StringFilter>>,
 
     ///  Autonomous System Number (ASN).
-    pub asn: Option<Vec<Asn>>,
+    pub asn: Option<Vec<AsnFilter>>,
+
+    /// The session to stick to, such that the same proxy is selected
+    /// for all requests of that session, see [`StickyProxyDB`].
+    pub session: Option<NonEmptyString>,
+}
+
+/// A value of a [`ProxyFilter`] list field, such as a [`StringFilter`] or [`AsnFilter`].
+trait FilterValue<T> {
+    fn is_negated(&self) -> bool;
+    fn matches(&self, value: &T) -> bool;
+}
+
+impl FilterValue<StringFilter> for StringFilter {
+    fn is_negated(&self) -> bool {
+        Self::is_negated(self)
+    }
+
+    fn matches(&self, value: &StringFilter) -> bool {
+        Self::matches(self, value)
+    }
+}
+
+impl FilterValue<rama_net::asn::Asn> for AsnFilter {
+    fn is_negated(&self) -> bool {
+        Self::is_negated(self)
+    }
+
+    fn matches(&self, value: &rama_net::asn::Asn) -> bool {
+        Self::matches(self, value)
+    }
+}
+
+/// Returns `true` if the given (proxy) value matches any of the positive filters (if any)
+/// and all of the negated filters, where a missing value matches none of the filters.
+fn matches_filters<T, F: FilterValue<T>>(filters: &[F], value: Option<&T>) -> bool {
+    let Some(value) = value else {
+        return filters.is_empty();
+    };
+    let mut positives = filters.iter().filter(|f| !f.is_negated()).peekable();
+    (positives.peek().is_none() || positives.any(|f| f.matches(value)))
+        && filters
+            .iter()
+            .filter(|f| f.is_negated())
+            .all(|f| f.matches(value))
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+/// Error returned by a [`ProxyDB`] to signal that the target
+/// is to be connected to directly, rather than via a proxy.
+///
+/// The [`ProxyDBService`] proceeds without proxy in case
+/// this error is (the source of) the error returned by its [`ProxyDB`].
+///
+/// [`ProxyDBService`]: crate::ProxyDBService
+pub struct DirectConnection;
+
+impl fmt::Display for DirectConnection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("direct connection: no proxy to be used")
+    }
+}
+
+impl std::error::Error for DirectConnection {}
+
+impl DirectConnection {
+    /// Returns `true` if the given error is or is caused by a [`DirectConnection`].
+    pub fn is_cause_of(err: &(dyn std::error::Error + 'static)) -> bool {
+        std::iter::successors(Some(err), |err| err.source()).any(|err| err.is::<Self>())
+    }
 }
 
 /// The trait to implement to provide a proxy database to other facilities,
@@ -329,19 +436,129 @@ impl ProxyDB for Proxy {
 mod memdb {
     use super::*;
     use crate::proxydb::internal::ProxyDBErrorKind;
-    use rama_net::transport::TransportProtocol;
+    use rama_net::{asn::Asn, transport::TransportProtocol};
+    use rand::Rng;
+    use std::{
+        collections::{hash_map::DefaultHasher, HashMap, HashSet},
+        hash::{Hash, Hasher},
+        sync::{Mutex, PoisonError},
+    };
 
     /// A fast in-memory ProxyDatabase that is the default choice for Rama.
+    ///
+    /// By default any of the matching proxies is selected, at random.
+    /// Use [`MemoryProxyDB::with_selection_strategy`] to select
+    /// the proxies using another [`ProxySelectionStrategy`].
     #[derive(Debug)]
     pub struct MemoryProxyDB {
         data: internal::ProxyDB,
+        values: FilterValues,
+        strategy: ProxySelectionStrategy,
+        usage: ProxyUsageTracker,
+        round_robin: Mutex<HashMap<u64, usize>>,
+    }
+
+    /// The maximum number of distinct candidate sets for which
+    /// the [`ProxySelectionStrategy::RoundRobin`] position is tracked,
+    /// after which the tracked positions are reset.
+    const ROUND_ROBIN_MAX_ENTRIES: usize = 1024;
+
+    /// The distinct (non-any) values of the filterable proxy fields,
+    /// used to expand [`ProxyFilter`] patterns into the values they match.
+    #[derive(Debug, Default)]
+    struct FilterValues {
+        pool_id: HashSet<StringFilter>,
+        continent: HashSet<StringFilter>,
+        country: HashSet<StringFilter>,
+        state: HashSet<StringFilter>,
+        city: HashSet<StringFilter>,
+        carrier: HashSet<StringFilter>,
+        asn: HashSet<Asn>,
+    }
+
+    impl FilterValues {
+        fn from_rows(proxies: &[Proxy]) -> Self {
+            let mut values = Self::default();
+            for proxy in proxies {
+                for (set, value) in [
+                    (&mut values.pool_id, &proxy.pool_id),
+                    (&mut values.continent, &proxy.continent),
+                    (&mut values.country, &proxy.country),
+                    (&mut values.state, &proxy.state),
+                    (&mut values.city, &proxy.city),
+                    (&mut values.carrier, &proxy.carrier),
+                ] {
+                    if let Some(value) = value.as_ref().filter(|value| !value.is_any()) {
+                        set.insert(value.clone());
+                    }
+                }
+                if let Some(asn) = proxy.asn.as_ref().filter(|asn| !asn.is_any()) {
+                    values.asn.insert(asn.clone());
+                }
+            }
+            values
+        }
+    }
+
+    /// Add the given filters to the query using `add`, as long as all of them are `exact` values.
+    /// Otherwise the filters are expanded into the known values they match, as patterns
+    /// cannot be queried directly. Proxies with an "any" value, or all proxies in case
+    /// no known value matches, are matched by post-filtering the query result instead.
+    fn add_filters<F: FilterValue<T>, T: Clone>(
+        filters: Option<Vec<F>>,
+        values: &HashSet<T>,
+        exact: impl Fn(&F) -> Option<T>,
+        add: impl FnMut(T),
+    ) {
+        let Some(filters) = filters else {
+            return;
+        };
+        match filters.iter().map(exact).collect::<Option<Vec<_>>>() {
+            Some(exact_values) => exact_values.into_iter().for_each(add),
+            None => values
+                .iter()
+                .filter(|value| matches_filters(&filters, Some(*value)))
+                .cloned()
+                .for_each(add),
+        }
+    }
+
+    fn exact_string(filter: &StringFilter) -> Option<StringFilter> {
+        (!filter.is_pattern()).then(|| filter.clone())
+    }
+
+    fn exact_asn(filter: &AsnFilter) -> Option<Asn> {
+        filter.as_asn().cloned()
+    }
+
+    /// Returns `true` if any of the list fields of the filter contains a pattern.
+    fn has_patterns(filter: &ProxyFilter) -> bool {
+        [
+            &filter.pool_id,
+            &filter.continent,
+            &filter.country,
+            &filter.state,
+            &filter.city,
+            &filter.carrier,
+        ]
+        .into_iter()
+        .flatten()
+        .flatten()
+        .any(StringFilter::is_pattern)
+            || filter
+                .asn
+                .iter()
+                .flatten()
+                .any(|asn| asn.as_asn().is_none())
     }
 
     impl MemoryProxyDB {
         /// Create a new in-memory proxy database with the given proxies.
         pub fn try_from_rows(proxies: Vec<Proxy>) -> Result<Self, MemoryProxyDBInsertError> {
-            Ok(MemoryProxyDB {
-                data: internal::ProxyDB::from_rows(proxies).map_err(|err| match err.kind() {
+            let values = FilterValues::from_rows(&proxies);
+            Ok(Self::from_data(
+                values,
+                internal::ProxyDB::from_rows(proxies).map_err(|err| match err.kind() {
                     ProxyDBErrorKind::DuplicateKey => {
                         MemoryProxyDBInsertError::duplicate_key(err.into_input())
                     }
@@ -349,7 +566,7 @@ mod memdb {
                         MemoryProxyDBInsertError::invalid_proxy(err.into_input())
                     }
                 })?,
-            })
+            ))
         }
 
         /// Create a new in-memory proxy database with the given proxies from an iterator.
@@ -357,16 +574,60 @@ mod memdb {
         where
             I: IntoIterator<Item = Proxy>,
         {
-            Ok(MemoryProxyDB {
-                data: internal::ProxyDB::from_iter(proxies).map_err(|err| match err.kind() {
-                    ProxyDBErrorKind::DuplicateKey => {
-                        MemoryProxyDBInsertError::duplicate_key(err.into_input())
-                    }
-                    ProxyDBErrorKind::InvalidRow => {
-                        MemoryProxyDBInsertError::invalid_proxy(err.into_input())
-                    }
-                })?,
-            })
+            Self::try_from_rows(proxies.into_iter().collect())
+        }
+
+        fn from_data(values: FilterValues, data: internal::ProxyDB) -> Self {
+            MemoryProxyDB {
+                data,
+                values,
+                strategy: ProxySelectionStrategy::default(),
+                usage: ProxyUsageTracker::default(),
+                round_robin: Mutex::new(HashMap::new()),
+            }
+        }
+
+        /// Set the default [`ProxySelectionStrategy`] used to select
+        /// a proxy out of all matching proxies.
+        ///
+        /// It can be overwritten per request using [`ProxyContext::selection_strategy`].
+        pub fn with_selection_strategy(mut self, strategy: ProxySelectionStrategy) -> Self {
+            self.strategy = strategy;
+            self
+        }
+
+        /// Set the default [`ProxySelectionStrategy`] used to select
+        /// a proxy out of all matching proxies.
+        ///
+        /// It can be overwritten per request using [`ProxyContext::selection_strategy`].
+        pub fn set_selection_strategy(&mut self, strategy: ProxySelectionStrategy) -> &mut Self {
+            self.strategy = strategy;
+            self
+        }
+
+        /// Set the [`ProxyUsageTracker`] used by this database,
+        /// e.g. to share it with the [`ProxyDBService`] which tracks
+        /// the requests in flight via the selected proxies.
+        ///
+        /// [`ProxyDBService`]: crate::ProxyDBService
+        pub fn with_usage_tracker(mut self, tracker: ProxyUsageTracker) -> Self {
+            self.usage = tracker;
+            self
+        }
+
+        /// Set the [`ProxyUsageTracker`] used by this database,
+        /// e.g. to share it with the [`ProxyDBService`] which tracks
+        /// the requests in flight via the selected proxies.
+        ///
+        /// [`ProxyDBService`]: crate::ProxyDBService
+        pub fn set_usage_tracker(&mut self, tracker: ProxyUsageTracker) -> &mut Self {
+            self.usage = tracker;
+            self
+        }
+
+        /// Returns the [`ProxyUsageTracker`] used by this database.
+        pub fn usage_tracker(&self) -> &ProxyUsageTracker {
+            &self.usage
         }
 
         /// Return the number of proxies in the database.
@@ -386,27 +647,28 @@ mod memdb {
         ) -> internal::ProxyDBQuery {
             let mut query = self.data.query();
 
-            for pool_id in filter.pool_id.into_iter().flatten() {
-                query.pool_id(pool_id);
-            }
-            for continent in filter.continent.into_iter().flatten() {
-                query.continent(continent);
-            }
-            for country in filter.country.into_iter().flatten() {
-                query.country(country);
-            }
-            for state in filter.state.into_iter().flatten() {
-                query.state(state);
-            }
-            for city in filter.city.into_iter().flatten() {
-                query.city(city);
-            }
-            for carrier in filter.carrier.into_iter().flatten() {
-                query.carrier(carrier);
-            }
-            for asn in filter.asn.into_iter().flatten() {
-                query.asn(asn);
-            }
+            let values = &self.values;
+            add_filters(filter.pool_id, &values.pool_id, exact_string, |v| {
+                query.pool_id(v);
+            });
+            add_filters(filter.continent, &values.continent, exact_string, |v| {
+                query.continent(v);
+            });
+            add_filters(filter.country, &values.country, exact_string, |v| {
+                query.country(v);
+            });
+            add_filters(filter.state, &values.state, exact_string, |v| {
+                query.state(v);
+            });
+            add_filters(filter.city, &values.city, exact_string, |v| {
+                query.city(v);
+            });
+            add_filters(filter.carrier, &values.carrier, exact_string, |v| {
+                query.carrier(v);
+            });
+            add_filters(filter.asn, &values.asn, exact_asn, |v| {
+                query.asn(v);
+            });
 
             if let Some(value) = filter.datacenter {
                 query.datacenter(value);
@@ -429,6 +691,78 @@ mod memdb {
 
             query
         }
+
+        fn select_proxy<'a>(
+            &self,
+            strategy: ProxySelectionStrategy,
+            candidates: &[&'a Proxy],
+        ) -> &'a Proxy {
+            match strategy {
+                ProxySelectionStrategy::Any => {
+                    candidates[rand::thread_rng().gen_range(0..candidates.len())]
+                }
+                ProxySelectionStrategy::RoundRobin => {
+                    // keyed on the candidates rather than the filter,
+                    // such that filters matching the same proxies share their turn
+                    let mut hasher = DefaultHasher::new();
+                    for proxy in candidates {
+                        proxy.id.hash(&mut hasher);
+                    }
+                    let key = hasher.finish();
+                    let mut round_robin = self
+                        .round_robin
+                        .lock()
+                        .unwrap_or_else(PoisonError::into_inner);
+                    if round_robin.len() >= ROUND_ROBIN_MAX_ENTRIES
+                        && !round_robin.contains_key(&key)
+                    {
+                        round_robin.clear();
+                    }
+                    let next = round_robin.entry(key).or_default();
+                    let proxy = candidates[*next % candidates.len()];
+                    *next = next.wrapping_add(1);
+                    proxy
+                }
+                ProxySelectionStrategy::Weighted => {
+                    let weight = |proxy: &Proxy| u64::from(proxy.weight.unwrap_or(1));
+                    let total: u64 = candidates.iter().map(|proxy| weight(proxy)).sum();
+                    if total == 0 {
+                        return self.select_proxy(ProxySelectionStrategy::Any, candidates);
+                    }
+                    let mut point = rand::thread_rng().gen_range(0..total);
+                    for &proxy in candidates {
+                        let weight = weight(proxy);
+                        if point < weight {
+                            return proxy;
+                        }
+                        point -= weight;
+                    }
+                    candidates[candidates.len() - 1]
+                }
+                ProxySelectionStrategy::LeastRecentlyUsed => {
+                    let usage = self
+                        .usage
+                        .snapshot(candidates.iter().map(|proxy| &proxy.id));
+                    candidates
+                        .iter()
+                        .zip(usage)
+                        .min_by_key(|(_, (_, last_used))| *last_used)
+                        .map(|(proxy, _)| *proxy)
+                        .expect("candidates to be non-empty")
+                }
+                ProxySelectionStrategy::LeastInFlight => {
+                    let usage = self
+                        .usage
+                        .snapshot(candidates.iter().map(|proxy| &proxy.id));
+                    candidates
+                        .iter()
+                        .zip(usage)
+                        .min_by_key(|(_, usage)| *usage)
+                        .map(|(proxy, _)| *proxy)
+                        .expect("candidates to be non-empty")
+                }
+            }
+        }
     }
 
     // TODO: custom query filters using ProxyQueryPredicate
@@ -451,6 +785,11 @@ mod memdb {
                     None => Err(MemoryProxyDBQueryError::not_found()),
                     Some(proxy) => {
                         if proxy.is_match(&ctx, &filter) && predicate.execute(proxy) {
+                            if ctx.selection_strategy.unwrap_or(self.strategy)
+                                != ProxySelectionStrategy::Any
+                            {
+                                self.usage.record_used(&proxy.id);
+                            }
                             Ok(proxy.clone())
                         } else {
                             Err(MemoryProxyDBQueryError::mismatch())
@@ -458,15 +797,30 @@ mod memdb {
                     }
                 },
                 None => {
-                    let query = self.query_from_filter(ctx, filter.clone());
-                    match query
-                        .execute()
-                        .and_then(|result| result.filter(|proxy| predicate.execute(proxy)))
-                        .map(|result| result.any())
-                    {
-                        None => Err(MemoryProxyDBQueryError::not_found()),
-                        Some(proxy) => Ok(proxy.clone()),
-                    }
+                    let strategy = ctx.selection_strategy.unwrap_or(self.strategy);
+                    // patterns are only partially resolved by the query itself
+                    let post_filter = has_patterns(&filter).then(|| (ctx.clone(), filter.clone()));
+                    let query = self.query_from_filter(ctx, filter);
+                    let proxy = match query.execute().and_then(|result| {
+                        result.filter(|proxy| {
+                            post_filter
+                                .as_ref()
+                                .is_none_or(|(ctx, filter)| proxy.is_match(ctx, filter))
+                                && predicate.execute(proxy)
+                        })
+                    }) {
+                        None => return Err(MemoryProxyDBQueryError::not_found()),
+                        // no usage bookkeeping is required for random selection
+                        Some(result) if strategy == ProxySelectionStrategy::Any => {
+                            return Ok(result.any().clone());
+                        }
+                        Some(result) => {
+                            let candidates: Vec<_> = result.iter().collect();
+                            self.select_proxy(strategy, &candidates)
+                        }
+                    };
+                    self.usage.record_used(&proxy.id);
+                    Ok(proxy.clone())
                 }
             }
         }
@@ -595,7 +949,7 @@ mod memdb {
         use itertools::Itertools;
         use rama_net::address::ProxyAddress;
         use rama_utils::str::NonEmptyString;
-        use std::str::FromStr;
+        use std::{collections::HashMap, str::FromStr};
 
         const RAW_CSV_DATA: &str = include_str!("./test_proxydb_rows.csv");
 
@@ -617,6 +971,10 @@ mod memdb {
         fn h2_proxy_context() -> ProxyContext {
             ProxyContext {
                 protocol: TransportProtocol::Tcp,
+                selection_strategy: None,
+                app_protocol: None,
+                authority: None,
+                user: None,
             }
         }
 
@@ -715,7 +1073,7 @@ mod memdb {
                 },
                 ProxyFilter {
                     id: Some(NonEmptyString::from_static("292096733")),
-                    asn: Some(vec![Asn::from_static(1)]),
+                    asn: Some(vec![Asn::from_static(1).into()]),
                     ..Default::default()
                 },
             ];
@@ -728,6 +1086,10 @@ mod memdb {
         fn h3_proxy_context() -> ProxyContext {
             ProxyContext {
                 protocol: TransportProtocol::Udp,
+                selection_strategy: None,
+                app_protocol: None,
+                authority: None,
+                user: None,
             }
         }
 
@@ -744,6 +1106,82 @@ mod memdb {
             assert_eq!(err.kind(), MemoryProxyDBQueryErrorKind::Mismatch);
         }
 
+        #[tokio::test]
+        async fn test_memproxydb_get_proxy_pattern_filters() {
+            let db = memproxydb().await;
+            let mut reader = ProxyCsvRowReader::raw(RAW_CSV_DATA);
+            let mut rows = Vec::new();
+            while let Some(proxy) = reader.next().await.unwrap() {
+                rows.push(proxy);
+            }
+
+            let mut ctx = h2_proxy_context();
+            ctx.selection_strategy = Some(ProxySelectionStrategy::RoundRobin);
+
+            for filter in [
+                ProxyFilter {
+                    country: Some(vec![StringFilter::new("us").negated()]),
+                    ..Default::default()
+                },
+                ProxyFilter {
+                    country: Some(vec![
+                        StringFilter::new("!us"),
+                        StringFilter::new("!gb"),
+                        StringFilter::new("!*"),
+                    ]),
+                    ..Default::default()
+                },
+                ProxyFilter {
+                    country: Some(vec![StringFilter::new("us"), StringFilter::new("!us")]),
+                    ..Default::default()
+                },
+                ProxyFilter {
+                    city: Some(vec![StringFilter::new("l*")]),
+                    ..Default::default()
+                },
+                ProxyFilter {
+                    city: Some(vec![StringFilter::new("*o*"), StringFilter::new("!lo*")]),
+                    mobile: Some(false),
+                    ..Default::default()
+                },
+                ProxyFilter {
+                    city: Some(vec![StringFilter::new("unknown-*")]),
+                    ..Default::default()
+                },
+                ProxyFilter {
+                    asn: Some(vec![AsnFilter::range(1, 41)]),
+                    ..Default::default()
+                },
+                ProxyFilter {
+                    asn: Some(vec![AsnFilter::from(Asn::from_static(42)).negated()]),
+                    ..Default::default()
+                },
+            ] {
+                let expected: Vec<_> = rows
+                    .iter()
+                    .filter(|proxy| proxy.is_match(&ctx, &filter))
+                    .map(|proxy| proxy.id.as_str())
+                    .sorted()
+                    .collect();
+
+                let mut found = Vec::new();
+                for _ in 0..expected.len().max(1) * 2 {
+                    match db.get_proxy(ctx.clone(), filter.clone()).await {
+                        Ok(proxy) => found.push(proxy.id.as_str().to_owned()),
+                        Err(err) => {
+                            assert_eq!(err.kind(), MemoryProxyDBQueryErrorKind::NotFound);
+                            break;
+                        }
+                    }
+                }
+                assert_eq!(
+                    expected.join(","),
+                    found.iter().sorted().dedup().join(","),
+                    "filter: {filter:?}"
+                );
+            }
+        }
+
         #[tokio::test]
         async fn test_memorydb_get_h3_capable_proxies() {
             let db = memproxydb().await;
@@ -842,7 +1280,7 @@ mod memdb {
             let ctx = h2_proxy_context();
             let filter = ProxyFilter {
                 // this will also work for proxies that have 'any' ASN
-                asn: Some(vec![Asn::from_static(42)]),
+                asn: Some(vec![Asn::from_static(42).into()]),
                 ..Default::default()
             };
             let mut found_ids = Vec::new();
@@ -981,6 +1419,8 @@ mod memdb {
                 city: Some("*".into()),
                 carrier: Some("*".into()),
                 asn: Some(Asn::unspecified()),
+                weight: None,
+                via: None,
             }])
             .unwrap();
 
@@ -1073,6 +1513,8 @@ mod memdb {
                 city: Some("NY".into()),
                 carrier: Some("AT&T".into()),
                 asn: Some(Asn::from_static(7018)),
+                weight: None,
+                via: None,
             }])
             .unwrap();
 
@@ -1104,7 +1546,7 @@ mod memdb {
                     ..Default::default()
                 },
                 ProxyFilter {
-                    asn: Some(vec![Asn::unspecified()]),
+                    asn: Some(vec![Asn::unspecified().into()]),
                     ..Default::default()
                 },
                 ProxyFilter {
@@ -1114,7 +1556,7 @@ mod memdb {
                     state: Some(vec![StringFilter::new("*")]),
                     city: Some(vec![StringFilter::new("*")]),
                     carrier: Some(vec![StringFilter::new("*")]),
-                    asn: Some(vec![Asn::unspecified()]),
+                    asn: Some(vec![Asn::unspecified().into()]),
                     ..Default::default()
                 },
             ] {
@@ -1158,6 +1600,8 @@ mod memdb {
                     city: Some("NY".into()),
                     carrier: Some("AT&T".into()),
                     asn: Some(Asn::from_static(7018)),
+                    weight: None,
+                    via: None,
                 },
                 Proxy {
                     id: NonEmptyString::from_static("2"),
@@ -1178,6 +1622,8 @@ mod memdb {
                     city: Some("NY".into()),
                     carrier: Some("AT&T".into()),
                     asn: Some(Asn::from_static(7018)),
+                    weight: None,
+                    via: None,
                 },
                 Proxy {
                     id: NonEmptyString::from_static("3"),
@@ -1198,6 +1644,8 @@ mod memdb {
                     city: Some("NY".into()),
                     carrier: Some("AT&T".into()),
                     asn: Some(Asn::from_static(7018)),
+                    weight: None,
+                    via: None,
                 },
                 Proxy {
                     id: NonEmptyString::from_static("4"),
@@ -1218,6 +1666,8 @@ mod memdb {
                     city: Some("NY".into()),
                     carrier: Some("AT&T".into()),
                     asn: Some(Asn::from_static(7018)),
+                    weight: None,
+                    via: None,
                 },
             ])
             .unwrap();
@@ -1243,6 +1693,216 @@ mod memdb {
             assert!(seen_4);
         }
 
+        fn weighted_proxies_db(weights: &[Option<u32>]) -> MemoryProxyDB {
+            MemoryProxyDB::try_from_iter(weights.iter().enumerate().map(|(index, weight)| Proxy {
+                id: NonEmptyString::try_from((index + 1).to_string()).unwrap(),
+                address: ProxyAddress::from_str("example.com").unwrap(),
+                tcp: true,
+                udp: false,
+                http: true,
+                https: false,
+                socks5: false,
+                socks5h: false,
+                datacenter: true,
+                residential: false,
+                mobile: false,
+                pool_id: Some(if index % 2 == 0 { "a" } else { "b" }.into()),
+                continent: None,
+                country: None,
+                state: None,
+                city: None,
+                carrier: None,
+                asn: None,
+                weight: *weight,
+                via: None,
+            }))
+            .unwrap()
+        }
+
+        #[tokio::test]
+        async fn test_memorydb_selection_round_robin() {
+            let db = weighted_proxies_db(&[None, None, None, None])
+                .with_selection_strategy(ProxySelectionStrategy::RoundRobin);
+            let ctx = h2_proxy_context();
+
+            let mut ids = Vec::new();
+            for _ in 0..8 {
+                let proxy = db
+                    .get_proxy(ctx.clone(), ProxyFilter::default())
+                    .await
+                    .unwrap();
+                ids.push(proxy.id);
+            }
+            assert_eq!(ids[..4].iter().sorted().join(","), "1,2,3,4");
+            assert_eq!(ids[..4], ids[4..]);
+
+            // round robin is tracked per set of matching proxies
+            let filter = ProxyFilter {
+                pool_id: Some(vec![StringFilter::new("b")]),
+                ..Default::default()
+            };
+            let mut ids = Vec::new();
+            for _ in 0..4 {
+                let proxy = db.get_proxy(ctx.clone(), filter.clone()).await.unwrap();
+                ids.push(proxy.id);
+            }
+            assert_eq!(ids[..2].iter().sorted().join(","), "2,4");
+            assert_eq!(ids[..2], ids[2..]);
+        }
+
+        #[tokio::test]
+        async fn test_memorydb_selection_round_robin_shared_by_candidates() {
+            let db = weighted_proxies_db(&[None, None, None, None])
+                .with_selection_strategy(ProxySelectionStrategy::RoundRobin);
+            let ctx = h2_proxy_context();
+
+            // filters only differing in their session share the same turn
+            let mut ids = Vec::new();
+            for i in 0..8 {
+                let filter = ProxyFilter {
+                    session: Some(NonEmptyString::try_from(format!("session-{i}")).unwrap()),
+                    ..Default::default()
+                };
+                let proxy = db.get_proxy(ctx.clone(), filter).await.unwrap();
+                ids.push(proxy.id);
+            }
+            assert_eq!(ids[..4].iter().sorted().join(","), "1,2,3,4");
+            assert_eq!(ids[..4], ids[4..]);
+            assert_eq!(1, db.round_robin.lock().unwrap().len());
+        }
+
+        #[tokio::test]
+        async fn test_memorydb_selection_any_skips_bookkeeping() {
+            let db = weighted_proxies_db(&[None, None, None]);
+            let ctx = h2_proxy_context();
+            for _ in 0..3 {
+                let proxy = db
+                    .get_proxy(ctx.clone(), ProxyFilter::default())
+                    .await
+                    .unwrap();
+                assert_eq!(None, db.usage.last_used(&proxy.id));
+            }
+            assert!(db.round_robin.lock().unwrap().is_empty());
+        }
+
+        #[tokio::test]
+        async fn test_memorydb_selection_weighted() {
+            let db = weighted_proxies_db(&[Some(0), Some(3), None, Some(0)])
+                .with_selection_strategy(ProxySelectionStrategy::Weighted);
+            let ctx = h2_proxy_context();
+
+            let mut counts = HashMap::new();
+            for _ in 0..1000 {
+                let proxy = db
+                    .get_proxy(ctx.clone(), ProxyFilter::default())
+                    .await
+                    .unwrap();
+                *counts.entry(proxy.id.to_string()).or_insert(0) += 1;
+            }
+            assert!(!counts.contains_key("1"));
+            assert!(!counts.contains_key("4"));
+            assert!(counts["2"] > counts["3"]);
+        }
+
+        #[tokio::test]
+        async fn test_memorydb_selection_weighted_all_zero() {
+            let db = weighted_proxies_db(&[Some(0), Some(0)])
+                .with_selection_strategy(ProxySelectionStrategy::Weighted);
+            let ctx = h2_proxy_context();
+
+            let mut ids = Vec::new();
+            for _ in 0..100 {
+                let proxy = db
+                    .get_proxy(ctx.clone(), ProxyFilter::default())
+                    .await
+                    .unwrap();
+                if !ids.contains(&proxy.id) {
+                    ids.push(proxy.id);
+                }
+            }
+            assert_eq!(ids.len(), 2);
+        }
+
+        #[tokio::test]
+        async fn test_memorydb_selection_least_recently_used() {
+            let db = weighted_proxies_db(&[None, None, None])
+                .with_selection_strategy(ProxySelectionStrategy::LeastRecentlyUsed);
+            let ctx = h2_proxy_context();
+
+            let mut ids = Vec::new();
+            for _ in 0..3 {
+                let proxy = db
+                    .get_proxy(ctx.clone(), ProxyFilter::default())
+                    .await
+                    .unwrap();
+                ids.push(proxy.id);
+            }
+            assert_eq!(ids.iter().sorted().join(","), "1,2,3");
+
+            // selecting a proxy by id also counts as usage
+            let filter = ProxyFilter {
+                id: Some(ids[0].clone()),
+                ..Default::default()
+            };
+            db.get_proxy(ctx.clone(), filter).await.unwrap();
+
+            let proxy = db.get_proxy(ctx, ProxyFilter::default()).await.unwrap();
+            assert_eq!(proxy.id, ids[1]);
+        }
+
+        #[tokio::test]
+        async fn test_memorydb_selection_least_in_flight() {
+            let tracker = ProxyUsageTracker::new();
+            let db = weighted_proxies_db(&[None, None, None])
+                .with_selection_strategy(ProxySelectionStrategy::LeastInFlight)
+                .with_usage_tracker(tracker.clone());
+            let ctx = h2_proxy_context();
+
+            let mut guards = Vec::new();
+            for _ in 0..3 {
+                let proxy = db
+                    .get_proxy(ctx.clone(), ProxyFilter::default())
+                    .await
+                    .unwrap();
+                guards.push(tracker.track(&proxy.id));
+            }
+            let busy = NonEmptyString::from_static("2");
+            let _busy_guard = tracker.track(&busy);
+            guards.clear();
+
+            for _ in 0..10 {
+                let proxy = db
+                    .get_proxy(ctx.clone(), ProxyFilter::default())
+                    .await
+                    .unwrap();
+                assert_ne!(proxy.id, busy);
+                let _guard = tracker.track(&proxy.id);
+            }
+        }
+
+        #[tokio::test]
+        async fn test_memorydb_selection_strategy_per_request() {
+            let db = weighted_proxies_db(&[None, None, None]);
+            let ctx = ProxyContext {
+                protocol: TransportProtocol::Tcp,
+                selection_strategy: Some(ProxySelectionStrategy::RoundRobin),
+                app_protocol: None,
+                authority: None,
+                user: None,
+            };
+
+            let mut ids = Vec::new();
+            for _ in 0..6 {
+                let proxy = db
+                    .get_proxy(ctx.clone(), ProxyFilter::default())
+                    .await
+                    .unwrap();
+                ids.push(proxy.id);
+            }
+            assert_eq!(ids[..3].iter().sorted().join(","), "1,2,3");
+            assert_eq!(ids[..3], ids[3..]);
+        }
+
         #[tokio::test]
         async fn test_deserialize_url_proxy_filter() {
             for (input, expected_output) in [
@@ -1295,14 +1955,27 @@ mod memdb {
                         state: Some(vec![StringFilter::new("ny")]),
                         city: Some(vec![StringFilter::new("buffalo")]),
                         carrier: Some(vec![StringFilter::new("at&t")]),
-                        asn: Some(vec![Asn::from_static(7018)]),
+                        asn: Some(vec![Asn::from_static(7018).into()]),
                         ..Default::default()
                     },
                 ),
                 (
                     "asn=1&asn=2",
                     ProxyFilter {
-                        asn: Some(vec![Asn::from_static(1), Asn::from_static(2)]),
+                        asn: Some(vec![Asn::from_static(1).into(), Asn::from_static(2).into()]),
+                        ..Default::default()
+                    },
+                ),
+                (
+                    "country=!us&city=San-*&asn=64512..65534&asn=!AS7018&mobile=false",
+                    ProxyFilter {
+                        country: Some(vec![StringFilter::new("us").negated()]),
+                        city: Some(vec![StringFilter::new("san-*")]),
+                        asn: Some(vec![
+                            AsnFilter::range(64512, 65534),
+                            AsnFilter::from(Asn::from_static(7018)).negated(),
+                        ]),
+                        mobile: Some(false),
                         ..Default::default()
                     },
                 ),
@@ -0,0 +1,425 @@
+//! Evaluation of PAC scripts using the embedded QuickJS engine.
+
+use super::{
+    functions::{self, PacEnv, PAC_FUNCTIONS},
+    PacScriptError,
+};
+use rquickjs::{
+    function::Rest, prelude::Coerced, CatchResultExt, CaughtError, Context, Function, Runtime,
+};
+use std::{
+    fmt,
+    sync::{Arc, Mutex, PoisonError},
+    time::{Duration, Instant},
+};
+use tokio::sync::Semaphore;
+
+/// Maximum duration of a single evaluation of the script,
+/// such that a misbehaving script cannot stall the proxy selection.
+const EVAL_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Maximum amount of memory used by the script engine.
+const MEMORY_LIMIT: usize = 32 * 1024 * 1024;
+
+/// Maximum number of QuickJS runtimes in which a script is loaded,
+/// bounding the number of concurrent evaluations of that script.
+const MAX_RUNTIMES: usize = 4;
+
+/// A PAC script, loaded in a small pool of QuickJS runtimes.
+///
+/// Each runtime is single threaded, such that calls are evaluated concurrently
+/// using different runtimes, which are only created once all existing ones are in use.
+/// Calls are evaluated on the blocking threads of the tokio runtime, and wait
+/// (asynchronously) for a runtime to become available once [`MAX_RUNTIMES`] are in use.
+pub(super) struct PacEngine {
+    source: String,
+    idle: Mutex<Vec<EngineState>>,
+    permits: Arc<Semaphore>,
+}
+
+struct EngineState {
+    _runtime: Runtime,
+    context: Context,
+    env: Arc<Mutex<PacEnv>>,
+    deadline: Arc<Mutex<Option<Instant>>>,
+}
+
+impl fmt::Debug for PacEngine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PacEngine").finish_non_exhaustive()
+    }
+}
+
+impl PacEngine {
+    /// Load the given script, evaluating its top-level statements,
+    /// after defining the standard PAC functions.
+    pub(super) fn new(source: &str) -> Result<Self, PacScriptError> {
+        let state = EngineState::new(source)?;
+        Ok(Self {
+            source: source.to_owned(),
+            idle: Mutex::new(vec![state]),
+            permits: Arc::new(Semaphore::new(MAX_RUNTIMES)),
+        })
+    }
+
+    /// Returns `true` if the script source mentions the given name,
+    /// e.g. to only do expensive preparations for functions used by the script.
+    pub(super) fn uses(&self, name: &str) -> bool {
+        self.source.contains(name)
+    }
+
+    /// Returns `true` if the script defines a function with the given name.
+    pub(super) fn has_function(&self, name: &str) -> bool {
+        self.with_state(|state| {
+            state.context.with(|ctx| {
+                ctx.globals()
+                    .get::<_, rquickjs::Value>(name)
+                    .is_ok_and(|value| value.is_function())
+            })
+        })
+        .unwrap_or_default()
+    }
+
+    /// Call the function with the given name and arguments in the given environment,
+    /// returning its result converted to a string.
+    ///
+    /// The call is evaluated on a blocking thread, as it can take up to [`EVAL_TIMEOUT`].
+    pub(super) async fn call(
+        self: &Arc<Self>,
+        env: PacEnv,
+        name: &'static str,
+        args: Vec<String>,
+    ) -> Result<String, PacScriptError> {
+        let permit = self
+            .permits
+            .clone()
+            .acquire_owned()
+            .await
+            .map_err(|err| PacScriptError::runtime(err.to_string()))?;
+        let engine = self.clone();
+        tokio::task::spawn_blocking(move || {
+            let _permit = permit;
+            engine.call_blocking(env, name, args)
+        })
+        .await
+        .map_err(|err| PacScriptError::runtime(err.to_string()))?
+    }
+
+    /// Call the function with the given name and arguments in the given environment,
+    /// blocking the current thread until the call is evaluated.
+    fn call_blocking(
+        &self,
+        env: PacEnv,
+        name: &str,
+        args: Vec<String>,
+    ) -> Result<String, PacScriptError> {
+        self.with_state(|state| {
+            *state.env.lock().unwrap_or_else(PoisonError::into_inner) = env;
+            state.with_deadline(|| {
+                state.context.with(|ctx| {
+                    ctx.globals()
+                        .get::<_, Function>(name)
+                        .and_then(|function| function.call::<_, Coerced<String>>((Rest(args),)))
+                        .catch(&ctx)
+                        .map(|result| result.0)
+                        .map_err(|err| PacScriptError::runtime(err.to_string()))
+                })
+            })
+        })?
+    }
+
+    /// Run the given function using an idle runtime of the pool,
+    /// creating a new one if none is idle.
+    ///
+    /// The number of runtimes is bounded by the permits, held by the callers of this function.
+    fn with_state<T>(&self, f: impl FnOnce(&EngineState) -> T) -> Result<T, PacScriptError> {
+        let idle = self
+            .idle
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .pop();
+        let state = match idle {
+            Some(state) => state,
+            None => EngineState::new(&self.source)?,
+        };
+
+        let state = PooledState {
+            engine: self,
+            state: Some(state),
+        };
+        Ok(f(state.state.as_ref().expect("pooled state to be present")))
+    }
+}
+
+/// Returns the runtime to the pool once dropped,
+/// such that it is also returned in case the evaluation panics.
+struct PooledState<'a> {
+    engine: &'a PacEngine,
+    state: Option<EngineState>,
+}
+
+impl Drop for PooledState<'_> {
+    fn drop(&mut self) {
+        if let Some(state) = self.state.take() {
+            self.engine
+                .idle
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .push(state);
+        }
+    }
+}
+
+impl EngineState {
+    fn new(source: &str) -> Result<Self, PacScriptError> {
+        let runtime = Runtime::new().map_err(|err| PacScriptError::runtime(err.to_string()))?;
+        runtime.set_memory_limit(MEMORY_LIMIT);
+
+        let deadline = Arc::new(Mutex::new(None::<Instant>));
+        runtime.set_interrupt_handler(Some(Box::new({
+            let deadline = deadline.clone();
+            move || {
+                deadline
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .is_some_and(|deadline| Instant::now() >= deadline)
+            }
+        })));
+
+        let context =
+            Context::full(&runtime).map_err(|err| PacScriptError::runtime(err.to_string()))?;
+        let env = Arc::new(Mutex::new(PacEnv::default()));
+
+        let state = Self {
+            _runtime: runtime,
+            context,
+            env,
+            deadline,
+        };
+        state.with_deadline(|| {
+            state.context.with(|ctx| {
+                let globals = ctx.globals();
+                for name in PAC_FUNCTIONS {
+                    let env = state.env.clone();
+                    Function::new(ctx.clone(), move |args: Rest<Coerced<String>>| {
+                        let args: Vec<String> = args.0.into_iter().map(|arg| arg.0).collect();
+                        functions::call(
+                            &env.lock().unwrap_or_else(PoisonError::into_inner),
+                            name,
+                            &args,
+                        )
+                    })
+                    .and_then(|function| function.with_name(name))
+                    .and_then(|function| globals.set(name, function))
+                    .map_err(|err| PacScriptError::runtime(err.to_string()))?;
+                }
+
+                ctx.eval::<(), _>(source)
+                    .catch(&ctx)
+                    .map_err(|err| match &err {
+                        CaughtError::Exception(exception)
+                            if exception.get::<_, String>("name").ok().as_deref()
+                                == Some("SyntaxError") =>
+                        {
+                            PacScriptError::syntax(err.to_string())
+                        }
+                        _ => PacScriptError::runtime(err.to_string()),
+                    })
+            })
+        })?;
+
+        Ok(state)
+    }
+
+    fn with_deadline<T>(&self, f: impl FnOnce() -> T) -> T {
+        *self.deadline.lock().unwrap_or_else(PoisonError::into_inner) =
+            Some(Instant::now() + EVAL_TIMEOUT);
+        let result = f();
+        *self.deadline.lock().unwrap_or_else(PoisonError::into_inner) = None;
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        collections::HashMap,
+        time::{SystemTime, UNIX_EPOCH},
+    };
+
+    fn eval(src: &str, args: Vec<String>) -> Result<String, PacScriptError> {
+        let engine = PacEngine::new(src)?;
+        let env = PacEnv {
+            my_ip: "192.168.1.10".parse().unwrap(),
+            resolved: HashMap::from([("intranet.example".to_owned(), "10.1.2.3".parse().unwrap())]),
+            // 2024-01-01 was a monday, 13:30 GMT
+            now: UNIX_EPOCH + Duration::from_secs(1_704_115_800),
+        };
+        engine.call_blocking(env, "f", args)
+    }
+
+    #[test]
+    fn test_eval_expressions() {
+        for (src, expected) in [
+            ("function f() { return 1 + 2 * 3; }", "7"),
+            ("function f() { return 'a' + 1 + 2; }", "a12"),
+            ("function f() { return 'San-Diego'.toLowerCase().substring(0, 4); }", "san-"),
+            ("function f() { return ['a', 'b'].map(function (x) { return x + x; }).join(';'); }", "aa;bb"),
+            ("function f() { return /^www\\./.test('www.example.com'); }", "true"),
+            ("var g = 2; function f() { g = g + 1; return g; }", "3"),
+            ("function f() { }", "undefined"),
+            ("function f() { return fib(10); } function fib(n) { if (n < 2) return n; return fib(n - 1) + fib(n - 2); }", "55"),
+        ] {
+            assert_eq!(expected, eval(src, vec![]).unwrap(), "src: {src}");
+        }
+    }
+
+    #[test]
+    fn test_eval_errors() {
+        for (src, syntax) in [
+            ("function f() {", true),
+            ("function f() { return unknown; }", false),
+            ("function f() { return f(); }", false),
+            ("function f() { return dateRange('JAN'); }", false),
+            ("function f() { while (true) {} }", false),
+            ("throw new Error('oops');", false),
+        ] {
+            let err = eval(src, vec![]).unwrap_err();
+            assert_eq!(syntax, err.is_syntax_error(), "src: {src}: {err}");
+        }
+    }
+
+    #[test]
+    fn test_eval_timeout() {
+        let start = Instant::now();
+        assert!(eval("function f() { while (true) {} }", vec![]).is_err());
+        assert!(start.elapsed() < EVAL_TIMEOUT * 5);
+
+        // the engine can still be used after an interrupted call
+        let engine =
+            PacEngine::new("function f(x) { if (x == 'loop') { while (true) {} } return 'ok'; }")
+                .unwrap();
+        let env = || PacEnv {
+            now: SystemTime::now(),
+            ..Default::default()
+        };
+        assert!(engine
+            .call_blocking(env(), "f", vec!["loop".to_owned()])
+            .is_err());
+        assert_eq!(
+            "ok",
+            engine
+                .call_blocking(env(), "f", vec!["x".to_owned()])
+                .unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_eval_concurrent_calls() {
+        let engine = Arc::new(
+            PacEngine::new(
+                "function f(x) { if (x == 'slow') { var end = Date.now() + 500; while (Date.now() < end) {} } return x; }",
+            )
+            .unwrap(),
+        );
+        let env = || PacEnv {
+            now: SystemTime::now(),
+            ..Default::default()
+        };
+
+        let slow: Vec<_> = (0..MAX_RUNTIMES + 1)
+            .map(|_| {
+                let engine = engine.clone();
+                tokio::spawn(async move { engine.call(env(), "f", vec!["slow".to_owned()]).await })
+            })
+            .collect();
+
+        // slow calls do not stall the (single threaded) tokio runtime
+        let start = Instant::now();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(start.elapsed() < Duration::from_millis(300));
+
+        for slow in slow {
+            assert_eq!("slow", slow.await.unwrap().unwrap());
+        }
+        assert_eq!(MAX_RUNTIMES, engine.idle.lock().unwrap().len());
+
+        // a slow call does not stall other calls
+        let slow = tokio::spawn({
+            let engine = engine.clone();
+            async move { engine.call(env(), "f", vec!["slow".to_owned()]).await }
+        });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let start = Instant::now();
+        assert_eq!(
+            "fast",
+            engine
+                .call(env(), "f", vec!["fast".to_owned()])
+                .await
+                .unwrap()
+        );
+        assert!(start.elapsed() < Duration::from_millis(300));
+        assert_eq!("slow", slow.await.unwrap().unwrap());
+    }
+
+    #[test]
+    fn test_eval_pac_functions() {
+        for (call, expected) in [
+            ("isPlainHostName('www')", true),
+            ("isPlainHostName('www.example.com')", false),
+            ("dnsDomainIs('www.example.com', '.example.com')", true),
+            ("dnsDomainIs('www.example.org', '.example.com')", false),
+            ("localHostOrDomainIs('www', 'www.example.com')", true),
+            (
+                "localHostOrDomainIs('www.example.com', 'www.example.com')",
+                true,
+            ),
+            (
+                "localHostOrDomainIs('www.example.org', 'www.example.com')",
+                false,
+            ),
+            ("isResolvable('intranet.example')", true),
+            ("isResolvable('unknown.example')", false),
+            ("dnsResolve('intranet.example') == '10.1.2.3'", true),
+            ("dnsResolve('unknown.example') === null", true),
+            ("isInNet('intranet.example', '10.0.0.0', '255.0.0.0')", true),
+            ("isInNet('192.168.0.1', '10.0.0.0', '255.0.0.0')", false),
+            ("isInNetEx('10.1.2.3', '10.1.0.0/16')", true),
+            ("myIpAddress() == '192.168.1.10'", true),
+            ("dnsDomainLevels('www.example.com') === 2", true),
+            ("convert_addr('10.0.0.1') === 167772161", true),
+            (
+                "shExpMatch('http://home.netscape.com/people/ari/index.html', '*/ari/*')",
+                true,
+            ),
+            (
+                "shExpMatch('http://home.netscape.com/people/montulli/index.html', '*/ari/*')",
+                false,
+            ),
+            ("shExpMatch('proxy.example.com', 'pro?y.*.com')", true),
+            ("weekdayRange('MON')", true),
+            ("weekdayRange('SAT', 'MON', 'GMT')", true),
+            ("weekdayRange('TUE', 'FRI')", false),
+            ("timeRange(13)", true),
+            ("timeRange(9, 17, 'GMT')", true),
+            ("timeRange(22, 6)", false),
+        ] {
+            let src = format!("function f() {{ return {call}; }}");
+            assert_eq!(
+                expected.to_string(),
+                eval(&src, vec![]).unwrap(),
+                "call: {call}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_eval_arguments() {
+        let src = "function f(url, host) { return url + ' ' + host + ' ' + g; } var g = 'x';";
+        assert_eq!(
+            "http://a/ a x",
+            eval(src, vec!["http://a/".to_owned(), "a".to_owned()]).unwrap()
+        );
+    }
+}
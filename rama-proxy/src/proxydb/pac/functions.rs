@@ -0,0 +1,201 @@
+//! The standard PAC functions, exposed as native functions to the script engine.
+
+use crate::proxydb::{env::ip_in_cidr, str::glob_match};
+use rquickjs::{Ctx, IntoJs};
+use std::{
+    collections::HashMap,
+    net::{IpAddr, Ipv4Addr},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+const WEEKDAYS: [&str; 7] = ["SUN", "MON", "TUE", "WED", "THU", "FRI", "SAT"];
+
+/// The names of the PAC functions defined by [`call`].
+pub(super) const PAC_FUNCTIONS: [&str; 17] = [
+    "isPlainHostName",
+    "dnsDomainIs",
+    "localHostOrDomainIs",
+    "isResolvable",
+    "isResolvableEx",
+    "dnsResolve",
+    "dnsResolveEx",
+    "myIpAddress",
+    "myIpAddressEx",
+    "isInNet",
+    "isInNetEx",
+    "convert_addr",
+    "dnsDomainLevels",
+    "shExpMatch",
+    "weekdayRange",
+    "timeRange",
+    "alert",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+/// The result of a PAC function call.
+pub(super) enum Value {
+    Undefined,
+    Null,
+    Bool(bool),
+    Num(f64),
+    Str(String),
+}
+
+impl<'js> IntoJs<'js> for Value {
+    fn into_js(self, ctx: &Ctx<'js>) -> rquickjs::Result<rquickjs::Value<'js>> {
+        match self {
+            Self::Undefined => Ok(rquickjs::Value::new_undefined(ctx.clone())),
+            Self::Null => Ok(rquickjs::Value::new_null(ctx.clone())),
+            Self::Bool(b) => b.into_js(ctx),
+            Self::Num(n) => n.into_js(ctx),
+            Self::Str(s) => s.into_js(ctx),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+/// The environment in which a PAC script is evaluated.
+pub(super) struct PacEnv {
+    /// The address returned by `myIpAddress()`.
+    pub(super) my_ip: IpAddr,
+    /// The hosts resolved prior to evaluation, used by the dns functions.
+    pub(super) resolved: HashMap<String, IpAddr>,
+    /// The time used by the time based functions, interpreted as GMT.
+    pub(super) now: SystemTime,
+}
+
+impl Default for PacEnv {
+    fn default() -> Self {
+        Self {
+            my_ip: IpAddr::V4(Ipv4Addr::LOCALHOST),
+            resolved: HashMap::new(),
+            now: UNIX_EPOCH,
+        }
+    }
+}
+
+impl PacEnv {
+    fn resolve(&self, host: &str) -> Option<IpAddr> {
+        host.parse()
+            .ok()
+            .or_else(|| self.resolved.get(&host.to_ascii_lowercase()).copied())
+    }
+}
+
+/// Call the PAC function with the given name, with its arguments converted to strings.
+///
+/// Invalid arguments result in a negative answer rather than an error,
+/// as is common for PAC implementations.
+pub(super) fn call(env: &PacEnv, name: &str, args: &[String]) -> Value {
+    let arg = |index: usize| args.get(index).map(String::as_str).unwrap_or_default();
+
+    match name {
+        "isPlainHostName" => Value::Bool(!arg(0).contains('.')),
+        "dnsDomainIs" => {
+            let (host, domain) = (arg(0).to_ascii_lowercase(), arg(1).to_ascii_lowercase());
+            Value::Bool(host.ends_with(&domain))
+        }
+        "localHostOrDomainIs" => {
+            let (host, hostdom) = (arg(0).to_ascii_lowercase(), arg(1).to_ascii_lowercase());
+            Value::Bool(
+                host == hostdom
+                    || (!host.contains('.')
+                        && hostdom
+                            .strip_prefix(&host)
+                            .is_some_and(|rest| rest.starts_with('.'))),
+            )
+        }
+        "isResolvable" | "isResolvableEx" => Value::Bool(env.resolve(arg(0)).is_some()),
+        "dnsResolve" => match env.resolve(arg(0)) {
+            Some(IpAddr::V4(ip)) => Value::Str(ip.to_string()),
+            _ => Value::Null,
+        },
+        "dnsResolveEx" => match env.resolve(arg(0)) {
+            Some(ip) => Value::Str(ip.to_string()),
+            None => Value::Str(String::new()),
+        },
+        "myIpAddress" => match env.my_ip {
+            IpAddr::V4(ip) => Value::Str(ip.to_string()),
+            IpAddr::V6(_) => Value::Str(Ipv4Addr::LOCALHOST.to_string()),
+        },
+        "myIpAddressEx" => Value::Str(env.my_ip.to_string()),
+        "isInNet" => {
+            let (Some(IpAddr::V4(ip)), Ok(pattern), Ok(mask)) = (
+                env.resolve(arg(0)),
+                arg(1).parse::<Ipv4Addr>(),
+                arg(2).parse::<Ipv4Addr>(),
+            ) else {
+                return Value::Bool(false);
+            };
+            let mask = u32::from(mask);
+            Value::Bool(u32::from(ip) & mask == u32::from(pattern) & mask)
+        }
+        "isInNetEx" => {
+            let in_net = env
+                .resolve(arg(0))
+                .zip(arg(1).split_once('/'))
+                .and_then(|(ip, (net, prefix))| {
+                    Some(ip_in_cidr(ip, net.parse().ok()?, prefix.parse().ok()?))
+                })
+                .unwrap_or_default();
+            Value::Bool(in_net)
+        }
+        "convert_addr" => match arg(0).parse::<Ipv4Addr>() {
+            Ok(ip) => Value::Num(f64::from(u32::from(ip))),
+            Err(_) => Value::Num(0.0),
+        },
+        "dnsDomainLevels" => Value::Num(arg(0).matches('.').count() as f64),
+        "shExpMatch" => Value::Bool(glob_match(arg(1), arg(0))),
+        "weekdayRange" => {
+            let today = weekday(env.now);
+            let day = |name: &str| WEEKDAYS.iter().position(|d| d.eq_ignore_ascii_case(name));
+            let mut names = args.iter().filter(|arg| !arg.eq_ignore_ascii_case("GMT"));
+            let Some(first) = names.next().and_then(|name| day(name)) else {
+                return Value::Bool(false);
+            };
+            let in_range = match names.next().and_then(|name| day(name)) {
+                None => today == first,
+                Some(last) if first <= last => (first..=last).contains(&today),
+                Some(last) => today >= first || today <= last,
+            };
+            Value::Bool(in_range)
+        }
+        "timeRange" => {
+            let Ok(hours) = args
+                .iter()
+                .filter(|arg| !arg.eq_ignore_ascii_case("GMT"))
+                .map(|arg| arg.parse())
+                .collect::<Result<Vec<u64>, _>>()
+            else {
+                return Value::Bool(false);
+            };
+            let hour = hour(env.now);
+            let in_range = match hours.as_slice() {
+                [h] => hour == *h,
+                [first, last] if first <= last => (*first..=*last).contains(&hour),
+                [first, last] => hour >= *first || hour <= *last,
+                // minute and second ranges are not supported
+                _ => false,
+            };
+            Value::Bool(in_range)
+        }
+        _ => Value::Undefined,
+    }
+}
+
+fn seconds_since_epoch(now: SystemTime) -> u64 {
+    now.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+/// The day of the week (GMT), with sunday being `0`.
+fn weekday(now: SystemTime) -> usize {
+    // 1970-01-01 was a thursday
+    ((seconds_since_epoch(now) / 86_400 + 4) % 7) as usize
+}
+
+/// The hour of the day (GMT).
+fn hour(now: SystemTime) -> u64 {
+    seconds_since_epoch(now) % 86_400 / 3_600
+}
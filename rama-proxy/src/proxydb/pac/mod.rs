@@ -0,0 +1,541 @@
+use super::{DirectConnection, Proxy, ProxyContext, ProxyDB, ProxyFilter, ProxyQueryPredicate};
+use rama_core::error::{BoxError, ErrorContext, OpaqueError};
+use rama_dns::{DnsResolver, HickoryDns};
+use rama_net::{
+    address::{Authority, Host, ProxyAddress},
+    Protocol,
+};
+use rama_utils::str::NonEmptyString;
+use std::{
+    collections::HashMap,
+    fmt,
+    net::{IpAddr, Ipv4Addr, UdpSocket},
+    sync::Arc,
+    time::SystemTime,
+};
+
+mod engine;
+mod functions;
+
+use engine::PacEngine;
+use functions::PacEnv;
+
+/// The function called by the [`PacProxyDB`] to select the proxy for a request.
+const FIND_PROXY_FOR_URL: &str = "FindProxyForURL";
+
+/// The dns functions for which the target host is resolved prior to evaluating the script.
+const DNS_FUNCTIONS: [&str; 6] = [
+    "isResolvable",
+    "isResolvableEx",
+    "dnsResolve",
+    "dnsResolveEx",
+    "isInNet",
+    "isInNetEx",
+];
+
+#[derive(Debug, Clone)]
+/// A [`ProxyDB`] which selects the proxy for a request
+/// by evaluating a Proxy Auto-Configuration (PAC) file.
+///
+/// The `FindProxyForURL(url, host)` function of the script is called for each request,
+/// where the url is derived from the target [`Authority`] and application [`Protocol`]
+/// of the [`ProxyContext`], as the path of the request is not known at this point.
+///
+/// The result is interpreted as a `;` separated list of entries, tried in order:
+///
+/// - `DIRECT`: connect to the target directly, signaled to the [`ProxyDBService`]
+///   by returning a [`DirectConnection`] error;
+/// - `PROXY host:port` or `HTTP host:port`: use the given http proxy;
+/// - `HTTPS host:port`: use the given https proxy;
+/// - `SOCKS host:port` or `SOCKS5 host:port`: use the given socks5 proxy.
+///
+/// The first proxy matching the [`ProxyFilter`] and predicate is returned.
+///
+/// The script is evaluated using an embedded javascript engine (QuickJS),
+/// which defines all standard PAC functions with the exception of `dateRange`.
+/// Time based functions use GMT. The target host is resolved prior to evaluation
+/// using the configured [`DnsResolver`], such that the dns functions can answer
+/// for the target host, as well as for hosts defined by IP address.
+/// Evaluation is bounded in both time and memory,
+/// such that a misbehaving script cannot stall the proxy selection.
+///
+/// The script is loaded in a small pool of engine runtimes, such that a few proxy
+/// selections are evaluated concurrently, on the blocking threads of the tokio runtime.
+/// Once all runtimes are in use, selections wait until one of them becomes available.
+///
+/// The `pac` feature compiles QuickJS from its C sources, requiring a C toolchain.
+///
+/// [`ProxyDBService`]: crate::ProxyDBService
+pub struct PacProxyDB<Dns = HickoryDns> {
+    engine: Arc<PacEngine>,
+    dns: Dns,
+    my_ip_address: Option<IpAddr>,
+    dns_resolution: bool,
+}
+
+impl PacProxyDB {
+    /// Create a new [`PacProxyDB`] from the given PAC script source,
+    /// resolving hosts using the default [`HickoryDns`] resolver.
+    ///
+    /// Fails in case the script cannot be evaluated
+    /// or does not define a `FindProxyForURL` function.
+    pub fn try_new(script: &str) -> Result<Self, PacScriptError> {
+        let engine = PacEngine::new(script)?;
+        if !engine.has_function(FIND_PROXY_FOR_URL) {
+            return Err(PacScriptError::syntax(format!(
+                "missing function: {FIND_PROXY_FOR_URL}"
+            )));
+        }
+        Ok(Self {
+            engine: Arc::new(engine),
+            dns: HickoryDns::default(),
+            my_ip_address: None,
+            dns_resolution: true,
+        })
+    }
+}
+
+impl<Dns> PacProxyDB<Dns> {
+    /// Consume `self` to attach the given `dns` (a [`DnsResolver`]) as a new [`PacProxyDB`],
+    /// used to resolve the target host for the dns functions used by the script.
+    pub fn with_dns<OtherDns>(self, dns: OtherDns) -> PacProxyDB<OtherDns>
+    where
+        OtherDns: DnsResolver<Error: Into<BoxError>> + Clone,
+    {
+        PacProxyDB {
+            engine: self.engine,
+            dns,
+            my_ip_address: self.my_ip_address,
+            dns_resolution: self.dns_resolution,
+        }
+    }
+
+    /// Set the address returned by `myIpAddress()`.
+    ///
+    /// By default the address of the local interface used to reach
+    /// the internet is returned, falling back to `127.0.0.1`.
+    pub fn with_my_ip_address(mut self, addr: IpAddr) -> Self {
+        self.my_ip_address = Some(addr);
+        self
+    }
+
+    /// Set the address returned by `myIpAddress()`.
+    ///
+    /// By default the address of the local interface used to reach
+    /// the internet is returned, falling back to `127.0.0.1`.
+    pub fn set_my_ip_address(&mut self, addr: IpAddr) -> &mut Self {
+        self.my_ip_address = Some(addr);
+        self
+    }
+
+    /// Define whether or not the target host is resolved for the dns functions
+    /// (e.g. `dnsResolve` and `isInNet`) used by the script, enabled by default.
+    ///
+    /// When disabled only targets defined by IP address are considered resolvable.
+    pub fn with_dns_resolution(mut self, enabled: bool) -> Self {
+        self.dns_resolution = enabled;
+        self
+    }
+
+    /// Define whether or not the target host is resolved for the dns functions
+    /// (e.g. `dnsResolve` and `isInNet`) used by the script, enabled by default.
+    ///
+    /// When disabled only targets defined by IP address are considered resolvable.
+    pub fn set_dns_resolution(&mut self, enabled: bool) -> &mut Self {
+        self.dns_resolution = enabled;
+        self
+    }
+}
+
+impl<Dns> PacProxyDB<Dns>
+where
+    Dns: DnsResolver<Error: Into<BoxError>>,
+{
+    /// Evaluate the script for the given [`ProxyContext`],
+    /// returning the raw result of `FindProxyForURL`.
+    pub async fn find_proxy_for_url(&self, ctx: &ProxyContext) -> Result<String, BoxError> {
+        let authority = ctx
+            .authority
+            .as_ref()
+            .context("pac proxy db: missing target authority")?;
+        let host = match authority.host() {
+            Host::Name(domain) => domain.to_string(),
+            Host::Address(ip) => ip.to_string(),
+        };
+        let secure = ctx
+            .app_protocol
+            .as_ref()
+            .map(|p| p.is_secure())
+            .unwrap_or_else(|| authority.port() == 443);
+        let url = format!("{}://{authority}/", if secure { "https" } else { "http" });
+
+        let my_ip = match self.my_ip_address {
+            Some(addr) => addr,
+            None if self.engine.uses("myIpAddress") => local_ip_address(),
+            None => IpAddr::V4(Ipv4Addr::LOCALHOST),
+        };
+        let env = PacEnv {
+            my_ip,
+            resolved: self.resolve(authority).await,
+            now: SystemTime::now(),
+        };
+        Ok(self
+            .engine
+            .call(env, FIND_PROXY_FOR_URL, vec![url, host])
+            .await?)
+    }
+
+    async fn resolve(&self, authority: &Authority) -> HashMap<String, IpAddr> {
+        let mut resolved = HashMap::new();
+        let Host::Name(domain) = authority.host() else {
+            return resolved;
+        };
+        if !self.dns_resolution || !DNS_FUNCTIONS.iter().any(|f| self.engine.uses(f)) {
+            return resolved;
+        }
+
+        // ipv4 addresses are preferred, as `dnsResolve` only returns those
+        let ip = match self.dns.ipv4_lookup(domain.clone()).await {
+            Ok(ips) => ips.into_iter().next().map(IpAddr::V4),
+            Err(err) => {
+                tracing::debug!(err = %err.into(), host = %domain, "pac proxy db: failed to resolve ipv4 of host");
+                None
+            }
+        };
+        let ip = match ip {
+            Some(ip) => Some(ip),
+            None => match self.dns.ipv6_lookup(domain.clone()).await {
+                Ok(ips) => ips.into_iter().next().map(IpAddr::V6),
+                Err(err) => {
+                    tracing::debug!(err = %err.into(), host = %domain, "pac proxy db: failed to resolve ipv6 of host");
+                    None
+                }
+            },
+        };
+        if let Some(ip) = ip {
+            resolved.insert(domain.as_str().to_ascii_lowercase(), ip);
+        }
+        resolved
+    }
+}
+
+/// Returns the address of the local interface used to reach the internet,
+/// found by connecting an udp socket, which does not send any packets,
+/// or `127.0.0.1` in case there is no such route.
+fn local_ip_address() -> IpAddr {
+    UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))
+        .and_then(|socket| {
+            // TEST-NET-1 (RFC 5737), such that only the default route is looked up
+            socket.connect((Ipv4Addr::new(192, 0, 2, 1), 80))?;
+            socket.local_addr()
+        })
+        .map(|addr| addr.ip())
+        .unwrap_or(IpAddr::V4(Ipv4Addr::LOCALHOST))
+}
+
+impl<Dns> ProxyDB for PacProxyDB<Dns>
+where
+    Dns: DnsResolver<Error: Into<BoxError>> + Clone,
+{
+    type Error = BoxError;
+
+    async fn get_proxy_if(
+        &self,
+        ctx: ProxyContext,
+        filter: ProxyFilter,
+        predicate: impl ProxyQueryPredicate,
+    ) -> Result<Proxy, Self::Error> {
+        let result = self.find_proxy_for_url(&ctx).await?;
+        for entry in result.split(';').map(str::trim).filter(|s| !s.is_empty()) {
+            let address = match parse_entry(entry) {
+                Ok(Some(address)) => address,
+                Ok(None) => return Err(DirectConnection.into()),
+                Err(err) => {
+                    tracing::debug!(%err, "pac proxy db: skip invalid entry");
+                    continue;
+                }
+            };
+            let id = NonEmptyString::try_from(format!("pac:{}", address.authority))
+                .context("create pac proxy id")?;
+            let proxy = Proxy::from_address(id, address);
+            if proxy.is_match(&ctx, &filter) && predicate.execute(&proxy) {
+                return Ok(proxy);
+            }
+        }
+        Err(OpaqueError::from_display(format!(
+            "pac proxy db: no matching proxy found in result: {result}"
+        ))
+        .into())
+    }
+}
+
+/// Parse a single entry of a `FindProxyForURL` result,
+/// returning `None` for `DIRECT`.
+fn parse_entry(entry: &str) -> Result<Option<ProxyAddress>, OpaqueError> {
+    let mut parts = entry.split_whitespace();
+    let kind = parts.next().unwrap_or_default();
+    if kind.eq_ignore_ascii_case("DIRECT") {
+        return Ok(None);
+    }
+
+    let scheme = match kind.to_ascii_uppercase().as_str() {
+        "PROXY" | "HTTP" => Protocol::HTTP,
+        "HTTPS" => Protocol::HTTPS,
+        "SOCKS" | "SOCKS5" => Protocol::SOCKS5,
+        _ => {
+            return Err(OpaqueError::from_display(format!(
+                "unsupported pac entry: {entry}"
+            )))
+        }
+    };
+    let authority = match (parts.next(), parts.next()) {
+        (Some(authority), None) => authority,
+        _ => {
+            return Err(OpaqueError::from_display(format!(
+                "invalid pac entry: {entry}"
+            )))
+        }
+    };
+    let address: ProxyAddress = format!("{scheme}://{authority}")
+        .parse()
+        .with_context(|| format!("invalid pac entry: {entry}"))?;
+    Ok(Some(address))
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// An error returned when parsing or evaluating a PAC script.
+pub struct PacScriptError {
+    kind: PacScriptErrorKind,
+    message: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PacScriptErrorKind {
+    Syntax,
+    Runtime,
+}
+
+impl PacScriptError {
+    fn syntax(message: impl Into<String>) -> Self {
+        Self {
+            kind: PacScriptErrorKind::Syntax,
+            message: message.into(),
+        }
+    }
+
+    fn runtime(message: impl Into<String>) -> Self {
+        Self {
+            kind: PacScriptErrorKind::Runtime,
+            message: message.into(),
+        }
+    }
+
+    /// Returns `true` if the script could not be parsed.
+    pub fn is_syntax_error(&self) -> bool {
+        self.kind == PacScriptErrorKind::Syntax
+    }
+}
+
+impl fmt::Display for PacScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            PacScriptErrorKind::Syntax => write!(f, "pac script syntax error: {}", self.message),
+            PacScriptErrorKind::Runtime => write!(f, "pac script runtime error: {}", self.message),
+        }
+    }
+}
+
+impl std::error::Error for PacScriptError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rama_dns::InMemoryDns;
+    use rama_net::{address::Domain, transport::TransportProtocol};
+
+    const SCRIPT: &str = r#"
+        // proxy selection for the example network
+        var internal = ".corp.example";
+
+        function FindProxyForURL(url, host) {
+            if (isPlainHostName(host) || dnsDomainIs(host, internal)) {
+                return "DIRECT";
+            }
+            if (isInNet(host, "10.0.0.0", "255.0.0.0")) {
+                return "DIRECT";
+            }
+            if (url.substring(0, 6) == "https:") {
+                return "HTTPS secure.example.com:443; PROXY 127.0.0.1:3128; DIRECT";
+            }
+            if (shExpMatch(host, "*.socks.example")) {
+                return "SOCKS5 127.0.0.1:1080";
+            }
+            return "PROXY 127.0.0.1:3128";
+        }
+    "#;
+
+    fn ctx(app_protocol: Protocol, authority: &str) -> ProxyContext {
+        ProxyContext {
+            protocol: TransportProtocol::Tcp,
+            selection_strategy: None,
+            app_protocol: Some(app_protocol),
+            authority: Some(authority.parse().unwrap()),
+            user: None,
+        }
+    }
+
+    #[test]
+    fn test_pac_proxy_db_invalid_script() {
+        let err = PacProxyDB::try_new("function FindProxyForURL(url, host) {").unwrap_err();
+        assert!(err.is_syntax_error());
+
+        let err =
+            PacProxyDB::try_new("function findProxy(url, host) { return 'DIRECT'; }").unwrap_err();
+        assert!(err.is_syntax_error());
+    }
+
+    #[test]
+    fn test_parse_entry() {
+        assert_eq!(None, parse_entry("DIRECT").unwrap());
+        for (entry, expected) in [
+            ("PROXY 127.0.0.1:3128", "http://127.0.0.1:3128"),
+            ("HTTP proxy.example.com:80", "http://proxy.example.com:80"),
+            (
+                "HTTPS proxy.example.com:443",
+                "https://proxy.example.com:443",
+            ),
+            ("SOCKS 127.0.0.1:1080", "socks5://127.0.0.1:1080"),
+            ("socks5  127.0.0.1:1080", "socks5://127.0.0.1:1080"),
+        ] {
+            assert_eq!(
+                expected,
+                parse_entry(entry).unwrap().unwrap().to_string(),
+                "entry: {entry}"
+            );
+        }
+        for entry in ["", "PROXY", "QUIC 127.0.0.1:443", "PROXY a:1 b:2"] {
+            assert!(parse_entry(entry).is_err(), "entry: {entry}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_pac_proxy_db() {
+        let db = PacProxyDB::try_new(SCRIPT)
+            .unwrap()
+            .with_dns_resolution(false);
+
+        let proxy = db
+            .get_proxy(
+                ctx(Protocol::HTTP, "example.com:80"),
+                ProxyFilter::default(),
+            )
+            .await
+            .unwrap();
+        assert_eq!("pac:127.0.0.1:3128", proxy.id.as_str());
+        assert!(proxy.http);
+
+        let proxy = db
+            .get_proxy(
+                ctx(Protocol::HTTPS, "example.com:443"),
+                ProxyFilter::default(),
+            )
+            .await
+            .unwrap();
+        assert_eq!("pac:secure.example.com:443", proxy.id.as_str());
+        assert!(proxy.https);
+
+        let proxy = db
+            .get_proxy(
+                ctx(Protocol::HTTP, "www.socks.example:80"),
+                ProxyFilter::default(),
+            )
+            .await
+            .unwrap();
+        assert!(proxy.socks5);
+
+        for authority in ["intranet:80", "wiki.corp.example:443", "10.1.2.3:80"] {
+            let err = db
+                .get_proxy(ctx(Protocol::HTTP, authority), ProxyFilter::default())
+                .await
+                .unwrap_err();
+            assert!(DirectConnection::is_cause_of(err.as_ref()), "{authority}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_pac_proxy_db_dns_resolver() {
+        let mut dns = InMemoryDns::new();
+        dns.insert_address(Domain::from_static("wiki.intranet.example"), [10, 1, 2, 3]);
+        let db = PacProxyDB::try_new(SCRIPT).unwrap().with_dns(dns);
+
+        let err = db
+            .get_proxy(
+                ctx(Protocol::HTTP, "wiki.intranet.example:80"),
+                ProxyFilter::default(),
+            )
+            .await
+            .unwrap_err();
+        assert!(DirectConnection::is_cause_of(err.as_ref()));
+
+        // unresolvable hosts are not in the internal network
+        let proxy = db
+            .get_proxy(
+                ctx(Protocol::HTTP, "unknown.example:80"),
+                ProxyFilter::default(),
+            )
+            .await
+            .unwrap();
+        assert_eq!("pac:127.0.0.1:3128", proxy.id.as_str());
+    }
+
+    #[tokio::test]
+    async fn test_pac_proxy_db_my_ip_address() {
+        let script =
+            "function FindProxyForURL(url, host) { return 'PROXY ' + myIpAddress() + ':3128'; }";
+        let db = PacProxyDB::try_new(script)
+            .unwrap()
+            .with_my_ip_address(IpAddr::V4(Ipv4Addr::new(192, 168, 1, 10)));
+        let result = db
+            .find_proxy_for_url(&ctx(Protocol::HTTP, "example.com:80"))
+            .await
+            .unwrap();
+        assert_eq!("PROXY 192.168.1.10:3128", result);
+
+        // by default the address of the interface with the default route is used
+        let db = PacProxyDB::try_new(script).unwrap();
+        let result = db
+            .find_proxy_for_url(&ctx(Protocol::HTTP, "example.com:80"))
+            .await
+            .unwrap();
+        assert_eq!(format!("PROXY {}:3128", local_ip_address()), result);
+    }
+
+    #[tokio::test]
+    async fn test_pac_proxy_db_filter_fallback() {
+        let db = PacProxyDB::try_new(SCRIPT)
+            .unwrap()
+            .with_dns_resolution(false);
+
+        // the https proxy is skipped as it does not match the filter,
+        // falling back to the next entry in the list
+        let proxy = db
+            .get_proxy_if(
+                ctx(Protocol::HTTPS, "example.com:443"),
+                ProxyFilter::default(),
+                |proxy: &Proxy| !proxy.https,
+            )
+            .await
+            .unwrap();
+        assert_eq!("pac:127.0.0.1:3128", proxy.id.as_str());
+
+        let err = db
+            .get_proxy_if(
+                ctx(Protocol::HTTPS, "example.com:443"),
+                ProxyFilter::default(),
+                |proxy: &Proxy| !proxy.https && !proxy.http,
+            )
+            .await
+            .unwrap_err();
+        assert!(DirectConnection::is_cause_of(err.as_ref()));
+    }
+}
@@ -0,0 +1,157 @@
+use rama_utils::str::NonEmptyString;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+    time::Instant,
+};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+/// The strategy used to select a proxy out of all proxies matching a [`ProxyFilter`].
+///
+/// Used by the [`MemoryProxyDB`], which can be configured with a default strategy,
+/// overridable per request by inserting a [`ProxySelectionStrategy`] in the [`Context`],
+/// which the [`ProxyDBService`] passes on via the [`ProxyContext`].
+///
+/// [`ProxyFilter`]: crate::ProxyFilter
+/// [`MemoryProxyDB`]: crate::MemoryProxyDB
+/// [`Context`]: rama_core::Context
+/// [`ProxyDBService`]: crate::ProxyDBService
+/// [`ProxyContext`]: crate::ProxyContext
+pub enum ProxySelectionStrategy {
+    #[default]
+    /// Select any of the matching proxies, at random.
+    Any,
+    /// Select the matching proxies in turn, tracked per set of matching proxies.
+    RoundRobin,
+    /// Select a random matching proxy, proportional to its [`Proxy::weight`].
+    ///
+    /// [`Proxy::weight`]: crate::Proxy::weight
+    Weighted,
+    /// Select the matching proxy which was selected the longest time ago,
+    /// or never at all.
+    ///
+    /// Selections made using [`ProxySelectionStrategy::Any`] are not recorded.
+    LeastRecentlyUsed,
+    /// Select the matching proxy with the least requests in flight,
+    /// as tracked by the [`ProxyUsageTracker`].
+    LeastInFlight,
+}
+
+#[derive(Debug, Clone, Default)]
+/// Tracks the usage of proxies, by their id, such as
+/// the number of requests in flight and when they were last selected.
+///
+/// The [`MemoryProxyDB`] records when its proxies are selected, while the
+/// requests in flight are tracked by the [`ProxyDBService`], when given the
+/// same tracker using [`ProxyDBService::usage_tracker`].
+///
+/// The usage is shared by all its clones.
+///
+/// [`MemoryProxyDB`]: crate::MemoryProxyDB
+/// [`ProxyDBService`]: crate::ProxyDBService
+/// [`ProxyDBService::usage_tracker`]: crate::ProxyDBService::usage_tracker
+pub struct ProxyUsageTracker {
+    usage: Arc<Mutex<HashMap<NonEmptyString, ProxyUsage>>>,
+}
+
+#[derive(Debug, Default)]
+struct ProxyUsage {
+    in_flight: usize,
+    last_used: Option<Instant>,
+}
+
+impl ProxyUsageTracker {
+    /// Create a new [`ProxyUsageTracker`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<NonEmptyString, ProxyUsage>> {
+        self.usage.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Track a request in flight via the proxy with the given id,
+    /// until the returned guard is dropped.
+    pub fn track(&self, id: &NonEmptyString) -> ProxyUsageGuard {
+        self.lock().entry(id.clone()).or_default().in_flight += 1;
+        ProxyUsageGuard {
+            tracker: self.clone(),
+            id: id.clone(),
+        }
+    }
+
+    /// Record that the proxy with the given id got selected.
+    pub fn record_used(&self, id: &NonEmptyString) {
+        self.lock().entry(id.clone()).or_default().last_used = Some(Instant::now());
+    }
+
+    /// Returns the number of requests in flight via the proxy with the given id.
+    pub fn in_flight(&self, id: &NonEmptyString) -> usize {
+        self.lock()
+            .get(id)
+            .map(|usage| usage.in_flight)
+            .unwrap_or_default()
+    }
+
+    /// Returns when the proxy with the given id was last selected, if ever.
+    pub fn last_used(&self, id: &NonEmptyString) -> Option<Instant> {
+        self.lock().get(id).and_then(|usage| usage.last_used)
+    }
+
+    /// Returns the in flight count and last used time of the given proxies,
+    /// taking the lock only once.
+    #[cfg(feature = "memory-db")]
+    pub(super) fn snapshot<'a>(
+        &self,
+        ids: impl Iterator<Item = &'a NonEmptyString>,
+    ) -> Vec<(usize, Option<Instant>)> {
+        let usage = self.lock();
+        ids.map(|id| {
+            usage
+                .get(id)
+                .map(|usage| (usage.in_flight, usage.last_used))
+                .unwrap_or_default()
+        })
+        .collect()
+    }
+}
+
+#[derive(Debug)]
+/// Guard tracking a request in flight via a proxy,
+/// created using [`ProxyUsageTracker::track`].
+pub struct ProxyUsageGuard {
+    tracker: ProxyUsageTracker,
+    id: NonEmptyString,
+}
+
+impl Drop for ProxyUsageGuard {
+    fn drop(&mut self) {
+        if let Some(usage) = self.tracker.lock().get_mut(&self.id) {
+            usage.in_flight = usage.in_flight.saturating_sub(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_usage_tracker() {
+        let tracker = ProxyUsageTracker::new();
+        let id = NonEmptyString::from_static("1");
+        assert_eq!(0, tracker.in_flight(&id));
+        assert!(tracker.last_used(&id).is_none());
+
+        let a = tracker.track(&id);
+        let b = tracker.clone().track(&id);
+        assert_eq!(2, tracker.in_flight(&id));
+        drop(a);
+        assert_eq!(1, tracker.in_flight(&id));
+        drop(b);
+        assert_eq!(0, tracker.in_flight(&id));
+
+        tracker.record_used(&id);
+        assert!(tracker.last_used(&id).is_some());
+    }
+}
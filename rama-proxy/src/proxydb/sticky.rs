@@ -0,0 +1,529 @@
+use super::{Proxy, ProxyContext, ProxyDB, ProxyFilter, ProxyQueryPredicate};
+use rama_net::{transport::TransportProtocol, user::UserId};
+use rama_utils::str::NonEmptyString;
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    fmt,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex, PoisonError,
+    },
+    time::{Duration, Instant},
+};
+
+/// Number of pinned sessions after which the expired sessions are swept.
+const SWEEP_INTERVAL: usize = 1024;
+
+/// A [`ProxyDB`] which pins each session to a single proxy of the inner [`ProxyDB`],
+/// such that all requests of a session go via the same proxy.
+///
+/// The session is given by the [`ProxyFilter::session`] field, e.g. as parsed
+/// by the [`ProxyFilterUsernameParser`] from a `session-<id>` username label.
+/// Requests without a session, or which select a proxy by id, are served by the
+/// inner [`ProxyDB`] as-is.
+///
+/// A session is pinned to the first proxy selected for it, and unpinned when:
+///
+/// - it is idle for longer than its [`StickyProxyDB::ttl`];
+/// - it is pinned for longer than its [`StickyProxyDB::max_lifetime`];
+/// - the pinned proxy is no longer available, e.g. because it got removed from the
+///   inner [`ProxyDB`], no longer matches the [`ProxyFilter`], or is ruled out
+///   by the [`ProxyQueryPredicate`] (such as an unhealthy proxy);
+///
+/// in which case the session is re-pinned to a newly selected proxy.
+/// Concurrent requests for a session which is not pinned yet all
+/// use the proxy of the request which pinned the session first.
+///
+/// Sessions are identified by their id, the [`ProxyContext::user`] and the
+/// transport protocol, such that users cannot share or hijack each other's session.
+/// Sessions of requests without a known user are shared by all such requests.
+///
+/// The pinned sessions are shared by all its clones.
+///
+/// [`ProxyFilterUsernameParser`]: crate::ProxyFilterUsernameParser
+pub struct StickyProxyDB<D> {
+    inner: D,
+    ttl: Duration,
+    max_lifetime: Duration,
+    sessions: Arc<SessionStore>,
+}
+
+#[derive(Debug, Default)]
+struct SessionStore {
+    pins: Mutex<HashMap<SessionKey, Affinity>>,
+    pin_count: AtomicUsize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct SessionKey {
+    session: NonEmptyString,
+    user: Option<UserId>,
+    protocol: TransportProtocol,
+}
+
+#[derive(Debug)]
+struct Affinity {
+    proxy_id: NonEmptyString,
+    pinned_at: Instant,
+    last_used: Instant,
+}
+
+impl<D> StickyProxyDB<D> {
+    /// Create a new [`StickyProxyDB`], pinning sessions to the proxies of the given [`ProxyDB`].
+    pub fn new(inner: D) -> Self {
+        Self {
+            inner,
+            ttl: Duration::from_secs(10 * 60),
+            max_lifetime: Duration::from_secs(60 * 60),
+            sessions: Default::default(),
+        }
+    }
+
+    /// Set the duration after which an idle session is unpinned.
+    ///
+    /// Defaults to 10 minutes.
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// Set the duration after which an idle session is unpinned.
+    ///
+    /// Defaults to 10 minutes.
+    pub fn set_ttl(&mut self, ttl: Duration) -> &mut Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// Set the duration after which a session is unpinned, even if in use.
+    ///
+    /// Defaults to 1 hour.
+    pub fn max_lifetime(mut self, max_lifetime: Duration) -> Self {
+        self.max_lifetime = max_lifetime;
+        self
+    }
+
+    /// Set the duration after which a session is unpinned, even if in use.
+    ///
+    /// Defaults to 1 hour.
+    pub fn set_max_lifetime(&mut self, max_lifetime: Duration) -> &mut Self {
+        self.max_lifetime = max_lifetime;
+        self
+    }
+
+    /// Returns the number of pinned sessions, including the expired ones not yet swept.
+    pub fn session_count(&self) -> usize {
+        self.sessions.lock().len()
+    }
+
+    /// Unpin the given session, for all users and transport protocols.
+    pub fn unpin(&self, session: &str) {
+        self.sessions
+            .lock()
+            .retain(|key, _| key.session.as_str() != session);
+    }
+
+    fn is_expired(&self, affinity: &Affinity, now: Instant) -> bool {
+        now.duration_since(affinity.last_used) > self.ttl
+            || now.duration_since(affinity.pinned_at) > self.max_lifetime
+    }
+
+    /// Returns the id of the proxy pinned to the given session, if not expired.
+    fn pinned(&self, key: &SessionKey, now: Instant) -> Option<NonEmptyString> {
+        let mut pins = self.sessions.lock();
+        match pins.get(key) {
+            Some(affinity) if self.is_expired(affinity, now) => {
+                tracing::trace!(session = %key.session, "sticky proxy db: session expired");
+                pins.remove(key);
+                None
+            }
+            Some(affinity) => Some(affinity.proxy_id.clone()),
+            None => None,
+        }
+    }
+
+    fn touch(&self, key: &SessionKey, now: Instant) {
+        if let Some(affinity) = self.sessions.lock().get_mut(key) {
+            affinity.last_used = now;
+        }
+    }
+
+    /// Pin the given session to the given proxy, unless it got pinned already
+    /// by a concurrent request, in which case that pin is kept.
+    ///
+    /// An existing pin to the `stale` proxy is replaced, as that proxy is no longer available.
+    ///
+    /// Returns the id of the proxy to which the session is pinned.
+    fn pin(
+        &self,
+        key: SessionKey,
+        proxy_id: NonEmptyString,
+        stale: Option<&NonEmptyString>,
+        now: Instant,
+    ) -> NonEmptyString {
+        let mut pins = self.sessions.lock();
+        if self.sessions.pin_count.fetch_add(1, Ordering::Relaxed) % SWEEP_INTERVAL
+            == SWEEP_INTERVAL - 1
+        {
+            pins.retain(|_, affinity| !self.is_expired(affinity, now));
+        }
+        let affinity = Affinity {
+            proxy_id: proxy_id.clone(),
+            pinned_at: now,
+            last_used: now,
+        };
+        match pins.entry(key) {
+            Entry::Occupied(mut entry)
+                if !self.is_expired(entry.get(), now) && Some(&entry.get().proxy_id) != stale =>
+            {
+                tracing::trace!(
+                    session = %entry.key().session,
+                    proxy_id = %entry.get().proxy_id,
+                    "sticky proxy db: session already pinned",
+                );
+                entry.get_mut().last_used = now;
+                entry.get().proxy_id.clone()
+            }
+            Entry::Occupied(mut entry) => {
+                tracing::trace!(session = %entry.key().session, proxy_id = %proxy_id, "sticky proxy db: re-pin session");
+                entry.insert(affinity);
+                proxy_id
+            }
+            Entry::Vacant(entry) => {
+                tracing::trace!(session = %entry.key().session, proxy_id = %proxy_id, "sticky proxy db: pin session");
+                entry.insert(affinity);
+                proxy_id
+            }
+        }
+    }
+}
+
+impl SessionStore {
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<SessionKey, Affinity>> {
+        self.pins.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl<D: fmt::Debug> fmt::Debug for StickyProxyDB<D> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StickyProxyDB")
+            .field("inner", &self.inner)
+            .field("ttl", &self.ttl)
+            .field("max_lifetime", &self.max_lifetime)
+            .field("sessions", &self.sessions)
+            .finish()
+    }
+}
+
+impl<D: Clone> Clone for StickyProxyDB<D> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            ttl: self.ttl,
+            max_lifetime: self.max_lifetime,
+            sessions: self.sessions.clone(),
+        }
+    }
+}
+
+impl<D: ProxyDB> ProxyDB for StickyProxyDB<D> {
+    type Error = D::Error;
+
+    async fn get_proxy_if(
+        &self,
+        ctx: ProxyContext,
+        filter: ProxyFilter,
+        predicate: impl ProxyQueryPredicate,
+    ) -> Result<Proxy, Self::Error> {
+        let session = match &filter.session {
+            Some(session) if filter.id.is_none() => session.clone(),
+            _ => return self.inner.get_proxy_if(ctx, filter, predicate).await,
+        };
+        let key = SessionKey {
+            session,
+            user: ctx.user.clone(),
+            protocol: ctx.protocol,
+        };
+
+        let stale = self.pinned(&key, Instant::now());
+        if let Some(proxy_id) = &stale {
+            match self
+                .get_pinned_proxy(&ctx, &filter, &predicate, proxy_id.clone())
+                .await
+            {
+                Ok(proxy) => {
+                    self.touch(&key, Instant::now());
+                    return Ok(proxy);
+                }
+                Err(_) => {
+                    tracing::debug!(
+                        session = %key.session,
+                        "sticky proxy db: pinned proxy no longer available, re-pin session"
+                    );
+                }
+            }
+        }
+
+        let proxy = self
+            .inner
+            .get_proxy_if(ctx.clone(), filter.clone(), predicate.clone())
+            .await?;
+        let proxy_id = self.pin(
+            key.clone(),
+            proxy.id.clone(),
+            stale.as_ref(),
+            Instant::now(),
+        );
+        if proxy_id == proxy.id {
+            return Ok(proxy);
+        }
+
+        // a concurrent request pinned the session first, use its proxy as well
+        match self
+            .get_pinned_proxy(&ctx, &filter, &predicate, proxy_id.clone())
+            .await
+        {
+            Ok(pinned) => Ok(pinned),
+            Err(_) => {
+                self.pin(key, proxy.id.clone(), Some(&proxy_id), Instant::now());
+                Ok(proxy)
+            }
+        }
+    }
+}
+
+impl<D: ProxyDB> StickyProxyDB<D> {
+    async fn get_pinned_proxy(
+        &self,
+        ctx: &ProxyContext,
+        filter: &ProxyFilter,
+        predicate: &impl ProxyQueryPredicate,
+        proxy_id: NonEmptyString,
+    ) -> Result<Proxy, D::Error> {
+        let pinned_filter = ProxyFilter {
+            id: Some(proxy_id),
+            ..filter.clone()
+        };
+        self.inner
+            .get_proxy_if(ctx.clone(), pinned_filter, predicate.clone())
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rama_core::error::OpaqueError;
+
+    /// Test db which returns its matching proxies in turn,
+    /// unless a proxy is selected by id.
+    ///
+    /// It yields before selecting a proxy, such that concurrent requests interleave.
+    #[derive(Debug)]
+    struct RoundRobinDB {
+        proxies: Vec<Proxy>,
+        next: AtomicUsize,
+    }
+
+    impl ProxyDB for RoundRobinDB {
+        type Error = OpaqueError;
+
+        async fn get_proxy_if(
+            &self,
+            ctx: ProxyContext,
+            filter: ProxyFilter,
+            predicate: impl ProxyQueryPredicate,
+        ) -> Result<Proxy, Self::Error> {
+            tokio::task::yield_now().await;
+            let candidates: Vec<_> = self
+                .proxies
+                .iter()
+                .filter(|proxy| proxy.is_match(&ctx, &filter) && predicate.execute(proxy))
+                .collect();
+            if candidates.is_empty() {
+                return Err(OpaqueError::from_display("no proxy found"));
+            }
+            if filter.id.is_some() {
+                return Ok(candidates[0].clone());
+            }
+            let index = self.next.fetch_add(1, Ordering::Relaxed) % candidates.len();
+            Ok(candidates[index].clone())
+        }
+    }
+
+    fn proxy(id: &'static str) -> Proxy {
+        Proxy {
+            id: NonEmptyString::from_static(id),
+            address: "127.0.0.1:8080".try_into().unwrap(),
+            tcp: true,
+            udp: false,
+            http: true,
+            https: false,
+            socks5: false,
+            socks5h: false,
+            datacenter: false,
+            residential: true,
+            mobile: false,
+            pool_id: None,
+            continent: None,
+            country: None,
+            state: None,
+            city: None,
+            carrier: None,
+            asn: None,
+            weight: None,
+            via: None,
+        }
+    }
+
+    fn sticky_db() -> StickyProxyDB<RoundRobinDB> {
+        StickyProxyDB::new(RoundRobinDB {
+            proxies: vec![proxy("1"), proxy("2"), proxy("3")],
+            next: AtomicUsize::new(0),
+        })
+    }
+
+    fn ctx() -> ProxyContext {
+        ProxyContext {
+            protocol: TransportProtocol::Tcp,
+            selection_strategy: None,
+            app_protocol: None,
+            authority: None,
+            user: None,
+        }
+    }
+
+    fn session_filter(session: &'static str) -> ProxyFilter {
+        ProxyFilter {
+            session: Some(NonEmptyString::from_static(session)),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sticky_session() {
+        let db = sticky_db();
+
+        let first = db.get_proxy(ctx(), session_filter("a")).await.unwrap();
+        for _ in 0..10 {
+            let proxy = db.get_proxy(ctx(), session_filter("a")).await.unwrap();
+            assert_eq!(first.id, proxy.id);
+        }
+
+        let other = db.get_proxy(ctx(), session_filter("b")).await.unwrap();
+        assert_ne!(first.id, other.id);
+        assert_eq!(2, db.session_count());
+
+        // no session, no stickiness
+        let a = db.get_proxy(ctx(), ProxyFilter::default()).await.unwrap();
+        let b = db.get_proxy(ctx(), ProxyFilter::default()).await.unwrap();
+        assert_ne!(a.id, b.id);
+        assert_eq!(2, db.session_count());
+    }
+
+    #[tokio::test]
+    async fn test_sticky_session_repin_unavailable() {
+        let db = sticky_db();
+
+        let first = db.get_proxy(ctx(), session_filter("a")).await.unwrap();
+        let first_id = first.id.clone();
+        let predicate = move |proxy: &Proxy| proxy.id != first_id;
+
+        let repinned = db
+            .get_proxy_if(ctx(), session_filter("a"), predicate.clone())
+            .await
+            .unwrap();
+        assert_ne!(first.id, repinned.id);
+
+        // the new pin sticks, even once the original proxy is available again
+        for _ in 0..10 {
+            let proxy = db.get_proxy(ctx(), session_filter("a")).await.unwrap();
+            assert_eq!(repinned.id, proxy.id);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sticky_session_per_user() {
+        let db = sticky_db();
+        let user_ctx = |name: &str| ProxyContext {
+            user: Some(UserId::Username(name.to_owned())),
+            ..ctx()
+        };
+
+        let john = db
+            .get_proxy(user_ctx("john"), session_filter("a"))
+            .await
+            .unwrap();
+        let jane = db
+            .get_proxy(user_ctx("jane"), session_filter("a"))
+            .await
+            .unwrap();
+        assert_ne!(john.id, jane.id, "users do not share a session");
+        assert_eq!(2, db.session_count());
+
+        for _ in 0..10 {
+            let proxy = db
+                .get_proxy(user_ctx("john"), session_filter("a"))
+                .await
+                .unwrap();
+            assert_eq!(john.id, proxy.id);
+            let proxy = db
+                .get_proxy(user_ctx("jane"), session_filter("a"))
+                .await
+                .unwrap();
+            assert_eq!(jane.id, proxy.id);
+        }
+
+        db.unpin("a");
+        assert_eq!(0, db.session_count());
+    }
+
+    #[tokio::test]
+    async fn test_sticky_session_expiry() {
+        let db = sticky_db().ttl(Duration::from_millis(20));
+        let first = db.get_proxy(ctx(), session_filter("a")).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        let proxy = db.get_proxy(ctx(), session_filter("a")).await.unwrap();
+        assert_ne!(first.id, proxy.id);
+
+        let db = sticky_db().max_lifetime(Duration::from_millis(20));
+        let first = db.get_proxy(ctx(), session_filter("a")).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        let proxy = db.get_proxy(ctx(), session_filter("a")).await.unwrap();
+        assert_eq!(first.id, proxy.id);
+        tokio::time::sleep(Duration::from_millis(15)).await;
+        let proxy = db.get_proxy(ctx(), session_filter("a")).await.unwrap();
+        assert_ne!(first.id, proxy.id);
+    }
+
+    #[tokio::test]
+    async fn test_sticky_session_unpin() {
+        let db = sticky_db();
+        db.get_proxy(ctx(), session_filter("a")).await.unwrap();
+        assert_eq!(1, db.session_count());
+        db.unpin("a");
+        assert_eq!(0, db.session_count());
+    }
+
+    #[tokio::test]
+    async fn test_sticky_session_concurrent_pin() {
+        let db = sticky_db();
+
+        let (a, b, c, d) = tokio::join!(
+            db.get_proxy(ctx(), session_filter("a")),
+            db.get_proxy(ctx(), session_filter("a")),
+            db.get_proxy(ctx(), session_filter("a")),
+            db.get_proxy(ctx(), session_filter("a")),
+        );
+        let first = a.unwrap();
+        for proxy in [b, c, d] {
+            assert_eq!(first.id, proxy.unwrap().id);
+        }
+        assert_eq!(1, db.session_count());
+
+        for _ in 0..10 {
+            let proxy = db.get_proxy(ctx(), session_filter("a")).await.unwrap();
+            assert_eq!(first.id, proxy.id);
+        }
+    }
+}
@@ -10,6 +10,22 @@ use unicode_normalization::UnicodeNormalization;
 /// - trims whitespace
 /// - case-insensitive
 /// - NFC normalizes
+///
+/// When used to filter proxies (e.g. in a [`ProxyFilter`]) the value can also be:
+///
+/// - `*`: matching any value;
+/// - a glob pattern using `*` (any sequence) and/or `?` (any character),
+///   e.g. `san-*` matching all values starting with `san-`;
+/// - prefixed with `!` to negate the (pattern) value, e.g. `!us` matching any value except `us`.
+///
+/// Negated filters only match proxies with a known value,
+/// and thus never proxies with an "any" (`*`) value.
+///
+/// In username labels the label separator within a value is percent-encoded,
+/// e.g. `city-san%2D*` for the `san-*` pattern (see [`ProxyFilterUsernameParser`]).
+///
+/// [`ProxyFilterUsernameParser`]: crate::ProxyFilterUsernameParser
+/// [`ProxyFilter`]: crate::ProxyFilter
 pub struct StringFilter(String);
 
 impl StringFilter {
@@ -37,6 +53,63 @@ impl StringFilter {
     pub fn is_any(&self) -> bool {
         self.0 == "*"
     }
+
+    /// Create the negation of this filter, matching all values not matched by this filter.
+    pub fn negated(self) -> Self {
+        match self.0.strip_prefix('!') {
+            Some(value) => Self(value.to_owned()),
+            None => Self(format!("!{}", self.0)),
+        }
+    }
+
+    /// Return `true` if this filter is negated, e.g. `!us`.
+    pub fn is_negated(&self) -> bool {
+        self.0.starts_with('!')
+    }
+
+    /// Return `true` if this filter is a glob pattern and/or negated,
+    /// and thus cannot be compared for equality.
+    pub fn is_pattern(&self) -> bool {
+        self.is_negated() || (!self.is_any() && self.0.contains(['*', '?']))
+    }
+
+    /// Return `true` if the given (proxy) value is matched by this filter.
+    pub fn matches(&self, value: &StringFilter) -> bool {
+        match self.0.strip_prefix('!') {
+            Some(pattern) => !value.is_any() && !glob_match(pattern, &value.0),
+            None => self.is_any() || value.is_any() || glob_match(&self.0, &value.0),
+        }
+    }
+}
+
+/// Match the given value against a glob pattern,
+/// supporting `*` (any sequence) and `?` (any character) wildcards.
+pub(super) fn glob_match(pattern: &str, value: &str) -> bool {
+    let value: Vec<char> = value.chars().collect();
+    let pattern: Vec<char> = pattern.chars().collect();
+    let (mut vi, mut pi) = (0, 0);
+    let mut backtrack = None;
+    while vi < value.len() {
+        match pattern.get(pi) {
+            Some('*') => {
+                backtrack = Some((pi, vi));
+                pi += 1;
+            }
+            Some(&c) if c == '?' || c == value[vi] => {
+                vi += 1;
+                pi += 1;
+            }
+            _ => match backtrack {
+                Some((star_pi, star_vi)) => {
+                    backtrack = Some((star_pi, star_vi + 1));
+                    pi = star_pi + 1;
+                    vi = star_vi + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[pi..].iter().all(|c| *c == '*')
 }
 
 impl PartialEq for StringFilter {
@@ -212,6 +285,53 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_string_filter_matches() {
+        for (filter, value, expected) in [
+            ("us", "US", true),
+            ("us", "be", false),
+            ("us", "*", true),
+            ("*", "be", true),
+            ("!us", "us", false),
+            ("!us", "be", true),
+            ("!us", "*", false),
+            ("san-*", "San-Diego", true),
+            ("san-*", "santa-fe", false),
+            ("san-*", "*", true),
+            ("!san-*", "san-jose", false),
+            ("!san-*", "tokyo", true),
+            ("?s", "us", true),
+            ("?s", "usa", false),
+            ("*o*o*", "tokyo", true),
+        ] {
+            let filter = StringFilter::from(filter);
+            assert_eq!(
+                expected,
+                filter.matches(&value.into()),
+                "filter: {filter}, value: {value}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_string_filter_patterns() {
+        for (filter, is_pattern, is_negated) in [
+            ("us", false, false),
+            ("*", false, false),
+            ("san-*", true, false),
+            ("u?", true, false),
+            ("!us", true, true),
+        ] {
+            let filter = StringFilter::from(filter);
+            assert_eq!(is_pattern, filter.is_pattern(), "filter: {filter}");
+            assert_eq!(is_negated, filter.is_negated(), "filter: {filter}");
+        }
+
+        let filter = StringFilter::from("us").negated();
+        assert_eq!("!us", filter.inner());
+        assert_eq!("us", filter.negated().inner());
+    }
+
     #[test]
     fn test_string_filter_neq() {
         for (a, b) in [("hello", "world"), ("world", "hello")] {
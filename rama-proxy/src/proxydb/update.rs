@@ -131,6 +131,10 @@ mod tests {
             .get_proxy(
                 ProxyContext {
                     protocol: TransportProtocol::Tcp,
+                    selection_strategy: None,
+                    app_protocol: None,
+                    authority: None,
+                    user: None,
                 },
                 ProxyFilter::default(),
             )
@@ -146,6 +150,10 @@ mod tests {
             .get_proxy(
                 ProxyContext {
                     protocol: TransportProtocol::Tcp,
+                    selection_strategy: None,
+                    app_protocol: None,
+                    authority: None,
+                    user: None,
                 },
                 ProxyFilter::default(),
             )
@@ -171,6 +179,8 @@ mod tests {
             city: Some("city".into()),
             carrier: Some("carrier".into()),
             asn: Some(Asn::from_static(1)),
+            weight: None,
+            via: None,
         });
 
         assert_eq!(
@@ -179,6 +189,10 @@ mod tests {
                 .get_proxy(
                     ProxyContext {
                         protocol: TransportProtocol::Tcp,
+                        selection_strategy: None,
+                        app_protocol: None,
+                        authority: None,
+                        user: None,
                     },
                     ProxyFilter::default(),
                 )
@@ -191,6 +205,10 @@ mod tests {
             .get_proxy(
                 ProxyContext {
                     protocol: TransportProtocol::Udp,
+                    selection_strategy: None,
+                    app_protocol: None,
+                    authority: None,
+                    user: None,
                 },
                 ProxyFilter::default(),
             )
@@ -203,6 +221,10 @@ mod tests {
                 .get_proxy(
                     ProxyContext {
                         protocol: TransportProtocol::Tcp,
+                        selection_strategy: None,
+                        app_protocol: None,
+                        authority: None,
+                        user: None,
                     },
                     ProxyFilter::default(),
                 )
@@ -5,12 +5,17 @@ use rama_core::{
     username::{UsernameLabelParser, UsernameLabelState, UsernameLabelWriter},
 };
 use rama_utils::macros::match_ignore_ascii_case_str;
+use std::{borrow::Cow, fmt::Write};
 
 #[derive(Debug, Clone, Default)]
 #[non_exhaustive]
 /// A parser which parses [`ProxyFilter`]s from username labels
 /// and adds it to the [`Context`]'s [`Extensions`].
 ///
+/// Values can contain the label separator (or a `%`) by percent-encoding it,
+/// e.g. `john-city-san%2D*` for cities starting with `san-`.
+/// Values are written this way when composing a username from a [`ProxyFilter`].
+///
 /// [`Context`]: rama_core::Context
 /// [`Extensions`]: rama_core::context::Extensions
 pub struct ProxyFilterUsernameParser {
@@ -28,6 +33,7 @@ enum ProxyFilterKey {
     City,
     Carrier,
     Asn,
+    Session,
 }
 
 impl ProxyFilterUsernameParser {
@@ -41,7 +47,14 @@ impl UsernameLabelParser for ProxyFilterUsernameParser {
     type Error = OpaqueError;
 
     fn parse_label(&mut self, label: &str) -> UsernameLabelState {
-        match self.key.take() {
+        let key = self.key.take();
+        let label = match key {
+            Some(_) => unescape_label_value(label),
+            None => Cow::Borrowed(label),
+        };
+        let label: &str = &label;
+
+        match key {
             Some(key) => match key {
                 ProxyFilterKey::Id => {
                     self.proxy_filter.id = Some(match label.try_into() {
@@ -122,6 +135,15 @@ impl UsernameLabelParser for ProxyFilterUsernameParser {
                         None => Some(vec![asn]),
                     }
                 }
+                ProxyFilterKey::Session => {
+                    self.proxy_filter.session = Some(match label.try_into() {
+                        Ok(session) => session,
+                        Err(err) => {
+                            tracing::trace!(err = %err, "abort username label parsing: invalid session label");
+                            return UsernameLabelState::Abort;
+                        }
+                    })
+                }
             },
             None => {
                 // allow bool-keys to be negated
@@ -144,6 +166,7 @@ impl UsernameLabelParser for ProxyFilterUsernameParser {
                         "city" => self.key = Some(ProxyFilterKey::City),
                         "carrier" => self.key = Some(ProxyFilterKey::Carrier),
                         "asn" => self.key = Some(ProxyFilterKey::Asn),
+                        "session" => self.key = Some(ProxyFilterKey::Session),
                         _ => return UsernameLabelState::Ignored,
                     }
                 }
@@ -176,41 +199,41 @@ impl<const SEPARATOR: char> UsernameLabelWriter<SEPARATOR> for ProxyFilter {
     ) -> Result<(), rama_core::username::ComposeError> {
         if let Some(id) = &self.id {
             composer.write_label("id")?;
-            composer.write_label(id.as_str())?;
+            composer.write_label(escape_label_value(id.as_ref(), SEPARATOR))?;
         }
 
         if let Some(pool_id_vec) = &self.pool_id {
             for pool_id in pool_id_vec {
                 composer.write_label("pool")?;
-                composer.write_label(pool_id.as_ref())?;
+                composer.write_label(escape_label_value(pool_id.as_ref(), SEPARATOR))?;
             }
         }
 
         if let Some(continent_vec) = &self.continent {
             for continent in continent_vec {
                 composer.write_label("continent")?;
-                composer.write_label(continent.as_ref())?;
+                composer.write_label(escape_label_value(continent.as_ref(), SEPARATOR))?;
             }
         }
 
         if let Some(country_vec) = &self.country {
             for country in country_vec {
                 composer.write_label("country")?;
-                composer.write_label(country.as_ref())?;
+                composer.write_label(escape_label_value(country.as_ref(), SEPARATOR))?;
             }
         }
 
         if let Some(state_vec) = &self.state {
             for state in state_vec {
                 composer.write_label("state")?;
-                composer.write_label(state.as_ref())?;
+                composer.write_label(escape_label_value(state.as_ref(), SEPARATOR))?;
             }
         }
 
         if let Some(city_vec) = &self.city {
             for city in city_vec {
                 composer.write_label("city")?;
-                composer.write_label(city.as_ref())?;
+                composer.write_label(escape_label_value(city.as_ref(), SEPARATOR))?;
             }
         }
 
@@ -241,25 +264,75 @@ impl<const SEPARATOR: char> UsernameLabelWriter<SEPARATOR> for ProxyFilter {
         if let Some(carrier_vec) = &self.carrier {
             for carrier in carrier_vec {
                 composer.write_label("carrier")?;
-                composer.write_label(carrier.as_ref())?;
+                composer.write_label(escape_label_value(carrier.as_ref(), SEPARATOR))?;
             }
         }
 
         if let Some(asn_vec) = &self.asn {
             for asn in asn_vec {
                 composer.write_label("asn")?;
-                composer.write_label(asn.as_u32().to_string())?;
+                composer.write_label(asn.to_string())?;
             }
         }
 
+        if let Some(session) = &self.session {
+            composer.write_label("session")?;
+            composer.write_label(escape_label_value(session.as_ref(), SEPARATOR))?;
+        }
+
         Ok(())
     }
 }
 
+/// Percent-encode the label separator and `%` in a label value,
+/// such that the value can be written as a single label.
+fn escape_label_value(value: &str, separator: char) -> Cow<'_, str> {
+    if !value.contains(['%', separator]) {
+        return Cow::Borrowed(value);
+    }
+    let mut escaped = String::with_capacity(value.len() + 4);
+    for c in value.chars() {
+        if c == '%' || c == separator {
+            for b in c.encode_utf8(&mut [0; 4]).bytes() {
+                let _ = write!(escaped, "%{b:02X}");
+            }
+        } else {
+            escaped.push(c);
+        }
+    }
+    Cow::Owned(escaped)
+}
+
+/// Decode the percent-encoded characters in a label value,
+/// keeping the value as-is in case it is not valid percent-encoded UTF-8.
+fn unescape_label_value(label: &str) -> Cow<'_, str> {
+    if !label.contains('%') {
+        return Cow::Borrowed(label);
+    }
+    let bytes = label.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if let Some([b'%', high, low]) = bytes.get(i..i + 3) {
+            if let (Some(high), Some(low)) = (
+                char::from(*high).to_digit(16),
+                char::from(*low).to_digit(16),
+            ) {
+                decoded.push((high * 16 + low) as u8);
+                i += 3;
+                continue;
+            }
+        }
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8(decoded).map_or(Cow::Borrowed(label), Cow::Owned)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::StringFilter;
+    use crate::{AsnFilter, StringFilter};
     use rama_core::username::{compose_username, parse_username};
     use rama_net::asn::Asn;
     use rama_utils::str::NonEmptyString;
@@ -490,7 +563,7 @@ mod tests {
                     country: Some(vec![StringFilter::from("us")]),
                     state: Some(vec![StringFilter::from("ny")]),
                     city: Some(vec![StringFilter::from("ny")]),
-                    asn: Some(vec![Asn::from_static(7018)]),
+                    asn: Some(vec![Asn::from_static(7018).into()]),
                     ..Default::default()
                 }),
             ),
@@ -515,6 +588,38 @@ mod tests {
                     ..Default::default()
                 }),
             ),
+            (
+                "john-country-!us-city-san*-asn-64512..65534-asn-!AS7018",
+                String::from("john"),
+                Some(ProxyFilter {
+                    country: Some(vec![StringFilter::from("!us")]),
+                    city: Some(vec![StringFilter::from("san*")]),
+                    asn: Some(vec![
+                        AsnFilter::range(64512, 65534),
+                        AsnFilter::from(Asn::from_static(7018)).negated(),
+                    ]),
+                    ..Default::default()
+                }),
+            ),
+            (
+                "john-city-san%2D*-carrier-100%-session-a%2db",
+                String::from("john"),
+                Some(ProxyFilter {
+                    city: Some(vec![StringFilter::from("san-*")]),
+                    carrier: Some(vec![StringFilter::from("100%")]),
+                    session: Some(NonEmptyString::from_static("a-b")),
+                    ..Default::default()
+                }),
+            ),
+            (
+                "john-country-us-session-abc123",
+                String::from("john"),
+                Some(ProxyFilter {
+                    country: Some(vec![StringFilter::from("us")]),
+                    session: Some(NonEmptyString::from_static("abc123")),
+                    ..Default::default()
+                }),
+            ),
         ];
 
         for (username, expected_username, expected_filter) in test_cases.into_iter() {
@@ -548,6 +653,7 @@ mod tests {
             "john-foo-country",
             "john-country",
             "john-id-", // empty id is invalid
+            "john-session",
         ] {
             let mut ext = Extensions::default();
 
@@ -630,7 +736,20 @@ mod tests {
                     StringFilter::from("at&t"),
                     StringFilter::from("orange"),
                 ]),
-                asn: Some(vec![Asn::from_static(7018), Asn::from_static(1)]),
+                asn: Some(vec![
+                    Asn::from_static(7018).into(),
+                    Asn::from_static(1).into(),
+                ]),
+                session: Some(NonEmptyString::from_static("abc123")),
+            },
+            ProxyFilter {
+                country: Some(vec![StringFilter::from("!us"), StringFilter::from("b?")]),
+                city: Some(vec![StringFilter::from("san*")]),
+                asn: Some(vec![
+                    AsnFilter::range(64512, 65534),
+                    AsnFilter::from(Asn::from_static(7018)).negated(),
+                ]),
+                ..Default::default()
             },
         ];
 
@@ -652,4 +771,29 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn test_username_compose_parser_proxy_filter_escaped_values() {
+        let filter = ProxyFilter {
+            id: Some(NonEmptyString::from_static("p-1")),
+            city: Some(vec![StringFilter::from("san-*")]),
+            carrier: Some(vec![StringFilter::from("100%2d")]),
+            session: Some(NonEmptyString::from_static("a-b")),
+            ..Default::default()
+        };
+
+        let username = compose_username("john".to_owned(), &filter).unwrap();
+        assert_eq!(
+            "john-id-p%2D1-city-san%2D*-carrier-100%252d-session-a%2Db",
+            username
+        );
+
+        let mut ext = Extensions::new();
+        let username =
+            parse_username(&mut ext, ProxyFilterUsernameParser::default(), &username).unwrap();
+        assert_eq!("john", username);
+        let result = ext.get::<ProxyFilter>().unwrap();
+        assert_eq!(filter, *result);
+        assert!(result.city.as_ref().unwrap()[0].matches(&StringFilter::from("san-francisco")));
+    }
 }
@@ -0,0 +1,298 @@
+//! SOCKS5 client support, as defined in [RFC 1928]
+//! and with username/password authentication as defined in [RFC 1929].
+//!
+//! [RFC 1928]: https://datatracker.ietf.org/doc/html/rfc1928
+//! [RFC 1929]: https://datatracker.ietf.org/doc/html/rfc1929
+
+use rama_core::error::BoxError;
+use rama_net::{
+    address::{Authority, Host},
+    user::Basic,
+};
+use std::net::IpAddr;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+const SOCKS5_VERSION: u8 = 0x05;
+const AUTH_NONE: u8 = 0x00;
+const AUTH_USERNAME_PASSWORD: u8 = 0x02;
+const AUTH_NO_ACCEPTABLE_METHODS: u8 = 0xff;
+const USERNAME_PASSWORD_VERSION: u8 = 0x01;
+const CMD_CONNECT: u8 = 0x01;
+const ATYP_IPV4: u8 = 0x01;
+const ATYP_DOMAIN: u8 = 0x03;
+const ATYP_IPV6: u8 = 0x04;
+
+#[derive(Debug, Clone, Default)]
+/// A client which establishes a tunnel via a SOCKS5 proxy,
+/// using the SOCKS5 `CONNECT` command.
+///
+/// The proxy is authorized using the username/password authentication method
+/// in case credentials are set, and without authentication otherwise.
+pub struct Socks5Client {
+    auth: Option<Basic>,
+}
+
+impl Socks5Client {
+    /// Create a new [`Socks5Client`], which does not authenticate.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Authenticate using the given username and password.
+    pub fn with_auth(mut self, auth: Basic) -> Self {
+        self.auth = Some(auth);
+        self
+    }
+
+    /// Authenticate using the given username and password.
+    pub fn set_auth(&mut self, auth: Basic) -> &mut Self {
+        self.auth = Some(auth);
+        self
+    }
+
+    /// Establish a tunnel to the given target over the given stream to a SOCKS5 proxy.
+    ///
+    /// Domains are sent as-is, to be resolved by the proxy,
+    /// resolve the target prior to calling this method to resolve it locally instead.
+    /// The stream can be used as a tunnel to the target once this method returns successfully.
+    pub async fn handshake<S>(&self, stream: &mut S, target: &Authority) -> Result<(), BoxError>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        self.authenticate(stream).await?;
+        connect(stream, target).await
+    }
+
+    async fn authenticate<S>(&self, stream: &mut S) -> Result<(), BoxError>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        let method = if self.auth.is_some() {
+            AUTH_USERNAME_PASSWORD
+        } else {
+            AUTH_NONE
+        };
+        stream.write_all(&[SOCKS5_VERSION, 1, method]).await?;
+
+        let mut reply = [0u8; 2];
+        stream.read_exact(&mut reply).await?;
+        if reply[0] != SOCKS5_VERSION {
+            return Err(format!("unexpected socks version {}", reply[0]).into());
+        }
+        match (reply[1], &self.auth) {
+            (AUTH_NONE, _) => Ok(()),
+            (AUTH_USERNAME_PASSWORD, Some(auth)) => {
+                let username = auth.username().as_bytes();
+                let password = auth.password().as_bytes();
+                let (Ok(username_len), Ok(password_len)) =
+                    (u8::try_from(username.len()), u8::try_from(password.len()))
+                else {
+                    return Err("socks5 username or password too long".into());
+                };
+                let mut request = Vec::with_capacity(3 + username.len() + password.len());
+                request.extend_from_slice(&[USERNAME_PASSWORD_VERSION, username_len]);
+                request.extend_from_slice(username);
+                request.push(password_len);
+                request.extend_from_slice(password);
+                stream.write_all(&request).await?;
+
+                stream.read_exact(&mut reply).await?;
+                if reply[0] != USERNAME_PASSWORD_VERSION {
+                    return Err(format!(
+                        "unexpected socks5 username/password auth version {}",
+                        reply[0]
+                    )
+                    .into());
+                }
+                if reply[1] != 0x00 {
+                    return Err("socks5 authentication failed".into());
+                }
+                Ok(())
+            }
+            (AUTH_NO_ACCEPTABLE_METHODS, _) => {
+                Err("socks5 proxy accepts none of the offered auth methods".into())
+            }
+            (method, _) => Err(format!("unexpected socks5 auth method {method}").into()),
+        }
+    }
+}
+
+async fn connect<S>(stream: &mut S, target: &Authority) -> Result<(), BoxError>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let mut request = vec![SOCKS5_VERSION, CMD_CONNECT, 0x00];
+    match target.host() {
+        Host::Address(IpAddr::V4(ip)) => {
+            request.push(ATYP_IPV4);
+            request.extend_from_slice(&ip.octets());
+        }
+        Host::Address(IpAddr::V6(ip)) => {
+            request.push(ATYP_IPV6);
+            request.extend_from_slice(&ip.octets());
+        }
+        Host::Name(domain) => {
+            let domain = domain.as_str().as_bytes();
+            let len = u8::try_from(domain.len()).map_err(|_| "socks5 domain too long")?;
+            request.extend_from_slice(&[ATYP_DOMAIN, len]);
+            request.extend_from_slice(domain);
+        }
+    }
+    request.extend_from_slice(&target.port().to_be_bytes());
+    stream.write_all(&request).await?;
+
+    let mut reply = [0u8; 4];
+    stream.read_exact(&mut reply).await?;
+    if reply[0] != SOCKS5_VERSION {
+        return Err(format!("unexpected socks version {}", reply[0]).into());
+    }
+    if reply[1] != 0x00 {
+        return Err(format!("socks5 connect failed: {}", reply_str(reply[1])).into());
+    }
+    // the bound address is of no use to the client
+    let addr_len = match reply[3] {
+        ATYP_IPV4 => 4,
+        ATYP_IPV6 => 16,
+        ATYP_DOMAIN => usize::from(stream.read_u8().await?),
+        atyp => return Err(format!("unexpected socks5 address type {atyp}").into()),
+    };
+    let mut bound = vec![0u8; addr_len + 2];
+    stream.read_exact(&mut bound).await?;
+
+    Ok(())
+}
+
+fn reply_str(reply: u8) -> &'static str {
+    match reply {
+        0x01 => "general failure",
+        0x02 => "connection not allowed by ruleset",
+        0x03 => "network unreachable",
+        0x04 => "host unreachable",
+        0x05 => "connection refused",
+        0x06 => "ttl expired",
+        0x07 => "command not supported",
+        0x08 => "address type not supported",
+        _ => "unknown error",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio_test::io::Builder;
+
+    const CONNECT_OK: &[u8] = &[5, 0, 0, 1, 0, 0, 0, 0, 0, 0];
+
+    fn target(s: &str) -> Authority {
+        s.parse().unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_socks5_client_no_auth_domain() {
+        let mut stream = Builder::new()
+            .write(&[5, 1, 0])
+            .read(&[5, 0])
+            .write(&[5, 1, 0, 3, 11])
+            .write(b"example.com")
+            .write(&[0, 80])
+            .read(CONNECT_OK)
+            .build();
+        Socks5Client::new()
+            .handshake(&mut stream, &target("example.com:80"))
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_socks5_client_auth_ipv4() {
+        let mut stream = Builder::new()
+            .write(&[5, 1, 2])
+            .read(&[5, 2])
+            .write(&[1, 4])
+            .write(b"john")
+            .write(&[6])
+            .write(b"secret")
+            .read(&[1, 0])
+            .write(&[5, 1, 0, 1, 127, 0, 0, 1, 0x1f, 0x90])
+            .read(&[5, 0, 0, 3, 4])
+            .read(b"host")
+            .read(&[0, 80])
+            .build();
+        Socks5Client::new()
+            .with_auth(Basic::new("john", "secret"))
+            .handshake(&mut stream, &target("127.0.0.1:8080"))
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_socks5_client_auth_failure() {
+        let mut stream = Builder::new()
+            .write(&[5, 1, 2])
+            .read(&[5, 2])
+            .write(&[1, 4])
+            .write(b"john")
+            .write(&[5])
+            .write(b"wrong")
+            .read(&[1, 1])
+            .build();
+        let err = Socks5Client::new()
+            .with_auth(Basic::new("john", "wrong"))
+            .handshake(&mut stream, &target("example.com:80"))
+            .await
+            .unwrap_err();
+        assert_eq!(err.to_string(), "socks5 authentication failed");
+    }
+
+    #[tokio::test]
+    async fn test_socks5_client_auth_invalid_version() {
+        let mut stream = Builder::new()
+            .write(&[5, 1, 2])
+            .read(&[5, 2])
+            .write(&[1, 4])
+            .write(b"john")
+            .write(&[6])
+            .write(b"secret")
+            .read(&[5, 0])
+            .build();
+        let err = Socks5Client::new()
+            .with_auth(Basic::new("john", "secret"))
+            .handshake(&mut stream, &target("example.com:80"))
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "unexpected socks5 username/password auth version 5"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_socks5_client_no_acceptable_auth_method() {
+        let mut stream = Builder::new().write(&[5, 1, 0]).read(&[5, 0xff]).build();
+        let err = Socks5Client::new()
+            .handshake(&mut stream, &target("example.com:80"))
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "socks5 proxy accepts none of the offered auth methods"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_socks5_client_connect_failure() {
+        let mut stream = Builder::new()
+            .write(&[5, 1, 0])
+            .read(&[5, 0])
+            .write(&[5, 1, 0, 4])
+            .write(&[0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1])
+            .write(&[0, 80])
+            .read(&[5, 5, 0, 1])
+            .build();
+        let err = Socks5Client::new()
+            .handshake(&mut stream, &target("[::1]:80"))
+            .await
+            .unwrap_err();
+        assert_eq!(err.to_string(), "socks5 connect failed: connection refused");
+    }
+}
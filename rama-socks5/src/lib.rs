@@ -1,5 +1,8 @@
 //! SOCKS5 support for Rama.
 //!
+//! Currently only the client side of the SOCKS5 protocol is supported,
+//! see [`Socks5Client`] to establish a tunnel via a SOCKS5 proxy.
+//!
 //! # Rama
 //!
 //! Crate used by the end-user `rama` crate and `rama` crate authors alike.
@@ -17,5 +20,6 @@
 #![cfg_attr(test, allow(clippy::float_cmp))]
 #![cfg_attr(not(test), warn(clippy::print_stdout, clippy::dbg_macro))]
 
-/// TODO
-pub const PLACEHOLDER: bool = true;
+pub mod client;
+#[doc(inline)]
+pub use client::Socks5Client;
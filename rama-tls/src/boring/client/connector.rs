@@ -8,7 +8,7 @@ use rama_net::address::Host;
 use rama_net::client::{ConnectorService, EstablishedClientConnection};
 use rama_net::stream::Stream;
 use rama_net::tls::client::NegotiatedTlsParameters;
-use rama_net::tls::ApplicationProtocol;
+use rama_net::tls::{ApplicationProtocol, MemoryKeyLog};
 use rama_net::transport::TryRefIntoTransportContext;
 use std::fmt;
 use tokio::io::{AsyncRead, AsyncWrite};
@@ -267,7 +267,8 @@ where
         let host = transport_ctx.authority.host().clone();
 
         let connector_data = ctx.get().cloned();
-        let (stream, negotiated_params) = self.handshake(connector_data, host, conn).await?;
+        let (stream, negotiated_params, key_log) =
+            self.handshake(connector_data, host, conn).await?;
 
         tracing::trace!(
             authority = %transport_ctx.authority,
@@ -275,6 +276,9 @@ where
         );
 
         ctx.insert(negotiated_params);
+        if let Some(key_log) = key_log {
+            ctx.insert(key_log);
+        }
 
         Ok(EstablishedClientConnection {
             ctx,
@@ -325,8 +329,11 @@ where
         let host = transport_ctx.authority.host().clone();
 
         let connector_data = ctx.get().cloned();
-        let (conn, negotiated_params) = self.handshake(connector_data, host, conn).await?;
+        let (conn, negotiated_params, key_log) = self.handshake(connector_data, host, conn).await?;
         ctx.insert(negotiated_params);
+        if let Some(key_log) = key_log {
+            ctx.insert(key_log);
+        }
 
         Ok(EstablishedClientConnection {
             ctx,
@@ -381,8 +388,12 @@ where
         };
 
         let connector_data = ctx.get().cloned();
-        let (stream, negotiated_params) = self.handshake(connector_data, host, conn).await?;
+        let (stream, negotiated_params, key_log) =
+            self.handshake(connector_data, host, conn).await?;
         ctx.insert(negotiated_params);
+        if let Some(key_log) = key_log {
+            ctx.insert(key_log);
+        }
 
         tracing::trace!("TlsConnector(tunnel): connection secured");
         Ok(EstablishedClientConnection {
@@ -402,16 +413,20 @@ impl<S, K> TlsConnector<S, K> {
         connector_data: Option<TlsConnectorData>,
         server_host: Host,
         stream: T,
-    ) -> Result<(SslStream<T>, NegotiatedTlsParameters), BoxError>
+    ) -> Result<(SslStream<T>, NegotiatedTlsParameters, Option<MemoryKeyLog>), BoxError>
     where
         T: Stream + Unpin,
     {
         let connector_data = connector_data.as_ref().or(self.connector_data.as_ref());
-        let client_config_data = match connector_data {
+        let mut client_config_data = match connector_data {
             Some(connector_data) => connector_data.try_to_build_config()?,
             None => TlsConnectorData::new_http_auto()?.try_to_build_config()?,
         };
-        let server_host = client_config_data.server_name.unwrap_or(server_host);
+        let server_host = client_config_data
+            .server_name
+            .clone()
+            .unwrap_or(server_host);
+        client_config_data.prepare_session_resumption(&server_host)?;
         let stream = tokio_boring::connect(
             client_config_data.config,
             server_host.to_string().as_str(),
@@ -454,6 +469,8 @@ impl<S, K> TlsConnector<S, K> {
                     protocol_version,
                     application_layer_protocol,
                     peer_certificate_chain: server_certificate_chain,
+                    session_resumed: stream.ssl().session_reused(),
+                    ech_accepted: stream.ssl().ech_accepted(),
                 }
             }
             None => {
@@ -464,7 +481,7 @@ impl<S, K> TlsConnector<S, K> {
             }
         };
 
-        Ok((stream, params))
+        Ok((stream, params, client_config_data.key_log))
     }
 }
 
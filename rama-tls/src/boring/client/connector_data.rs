@@ -1,26 +1,41 @@
 use boring::{
     asn1::Asn1Time,
     bn::{BigNum, MsbOption},
+    ex_data::Index,
     hash::MessageDigest,
     pkey::{PKey, Private},
     rsa::Rsa,
-    ssl::{ConnectConfiguration, SslCurve, SslSignatureAlgorithm, SslVerifyMode, SslVersion},
+    ssl::{
+        BoxCustomVerifyFinish, ConnectConfiguration, NameType, Ssl, SslAlert, SslCurve, SslRef,
+        SslSession, SslSessionCacheMode, SslSignatureAlgorithm, SslVerifyMode, SslVersion,
+    },
     x509::{
         extension::{BasicConstraints, KeyUsage, SubjectKeyIdentifier},
+        store::X509StoreBuilder,
         X509,
     },
 };
 use rama_core::error::{ErrorContext, ErrorExt, OpaqueError};
 use rama_net::tls::{
-    client::{ClientAuth, ClientHelloExtension},
+    client::{
+        ClientAuth, ClientHelloExtension, ClientSession, ClientSessionKey, ClientSessionStore,
+        EchMode,
+    },
     DataEncoding,
 };
-use rama_net::tls::{openssl_cipher_list_str_from_cipher_list, ApplicationProtocol, KeyLogIntent};
+use rama_net::tls::{
+    openssl_cipher_list_str_from_cipher_list, ApplicationProtocol, KeyLogEntry, KeyLogIntent,
+    MemoryKeyLog,
+};
 use rama_net::{address::Host, tls::client::ServerVerifyMode};
-use std::{fmt, sync::Arc};
+use std::{
+    fmt,
+    hash::{DefaultHasher, Hash, Hasher},
+    sync::{Arc, OnceLock},
+};
 use tracing::trace;
 
-use crate::keylog::new_key_log_file_handle;
+use crate::keylog::new_key_log_sink;
 
 #[derive(Debug, Clone)]
 /// Internal data used as configuration/input for the [`super::HttpsConnector`].
@@ -43,6 +58,8 @@ pub(super) struct ConnectConfigurationInput {
     pub(super) server_verify_mode: Option<ServerVerifyMode>,
     pub(super) client_auth: Option<ConnectorConfigClientAuth>,
     pub(super) store_server_certificate_chain: bool,
+    pub(super) session_store: Option<ClientSessionStore>,
+    pub(super) ech: Option<EchMode>,
 }
 
 #[derive(Debug, Clone)]
@@ -54,6 +71,17 @@ pub(super) struct ConnectorConfigClientAuth {
 pub(super) struct ConnectConfigData {
     pub(super) config: ConnectConfiguration,
     pub(super) server_name: Option<Host>,
+    pub(super) key_log: Option<MemoryKeyLog>,
+    session_store: Option<ClientSessionStore>,
+    session_config_tag: u64,
+    alpn_protos: Vec<Vec<u8>>,
+}
+
+/// A boring client session, as stored in a (shared) [`ClientSessionStore`],
+/// tagged with the fingerprint of the config of the connection which established it.
+struct BoringClientSession {
+    config_tag: u64,
+    session: SslSession,
 }
 
 impl fmt::Debug for ConnectConfigData {
@@ -61,27 +89,102 @@ impl fmt::Debug for ConnectConfigData {
         f.debug_struct("ConnectConfigData")
             .field("config", &"boring::ConnectConfiguration<Opaque>")
             .field("server_name", &self.server_name)
+            .field("key_log", &self.key_log)
+            .field("session_store", &self.session_store)
+            .field("session_config_tag", &self.session_config_tag)
+            .field("alpn_protos", &self.alpn_protos)
             .finish()
     }
 }
 
+impl ConnectConfigData {
+    /// Prepare the connection to `server_host` for session resumption,
+    /// in case a (shared) session store is configured.
+    ///
+    /// This offers the last stored session for the given server (and ALPN protocols),
+    /// established using the same config, and keeps track of the session key
+    /// such that new sessions can be stored.
+    pub(super) fn prepare_session_resumption(
+        &mut self,
+        server_host: &Host,
+    ) -> Result<(), OpaqueError> {
+        let Some(session_store) = self.session_store.as_ref() else {
+            return Ok(());
+        };
+
+        let key = ClientSessionKey::new(Some(server_host.clone()), &self.alpn_protos);
+
+        if let Some(session) = session_store.get(&key) {
+            match session.downcast_ref::<BoringClientSession>() {
+                Some(stored) if stored.config_tag == self.session_config_tag => {
+                    // tls 1.3 tickets are meant to be used only once
+                    if stored.session.protocol_version() == SslVersion::TLS1_3 {
+                        session_store.take(&key);
+                    }
+                    trace!(%server_host, "boring connector: offer stored client session");
+                    // SAFETY: every connection builds its own `SslContext`, so the session
+                    // cannot be associated with this one. Within boringssl a session does not
+                    // refer to the context which created it, and sessions with a version or
+                    // cipher which is not enabled for this connection are not offered.
+                    // What remains is that resumption skips the verification of the server,
+                    // hence only sessions established with the same verify mode, versions,
+                    // ciphers and client auth (= config tag) are offered.
+                    unsafe { self.config.set_session(&stored.session) }
+                        .context("boring connector: set stored client session")?;
+                }
+                Some(_) => {
+                    trace!(%server_host, "boring connector: ignore client session of other config");
+                }
+                None => {
+                    trace!(%server_host, "boring connector: ignore foreign client session");
+                }
+            }
+        }
+
+        self.config.set_ex_data(client_session_key_index()?, key);
+        Ok(())
+    }
+}
+
+fn client_session_key_index() -> Result<Index<Ssl, ClientSessionKey>, OpaqueError> {
+    static INDEX: OnceLock<Index<Ssl, ClientSessionKey>> = OnceLock::new();
+    if let Some(index) = INDEX.get() {
+        return Ok(*index);
+    }
+    let index = Ssl::new_ex_index().context("boring connector: create session key ex index")?;
+    Ok(*INDEX.get_or_init(|| index))
+}
+
+fn decode_alpn_wire_format(mut wire: &[u8]) -> Vec<Vec<u8>> {
+    let mut protocols = Vec::new();
+    while let Some((len, rest)) = wire.split_first() {
+        let Some((protocol, rest)) = rest.split_at_checked(*len as usize) else {
+            break;
+        };
+        protocols.push(protocol.to_vec());
+        wire = rest;
+    }
+    protocols
+}
+
 impl TlsConnectorData {
     pub(super) fn try_to_build_config(&self) -> Result<ConnectConfigData, OpaqueError> {
         let mut cfg_builder =
             boring::ssl::SslConnector::builder(boring::ssl::SslMethod::tls_client())
                 .context("create (boring) ssl connector builder")?;
 
-        if let Some(keylog_filename) = self
+        let keylog_intent = self
             .connect_config_input
             .keylog_intent
             .clone()
-            .unwrap_or_default()
-            .file_path()
-        {
-            let handle = new_key_log_file_handle(keylog_filename)?;
+            .unwrap_or_default();
+        let (key_log_sink, key_log) = new_key_log_sink(&keylog_intent)?;
+        if let Some(sink) = key_log_sink {
             cfg_builder.set_keylog_callback(move |_, line| {
-                let line = format!("{}\n", line);
-                handle.write_log_line(line);
+                match KeyLogEntry::try_from_nss_line(line) {
+                    Ok(entry) => sink.write_key_log(&entry),
+                    Err(err) => trace!(%err, "boring connector: ignore invalid key log line"),
+                }
             });
         }
 
@@ -133,6 +236,7 @@ impl TlsConnectorData {
         match self
             .connect_config_input
             .server_verify_mode
+            .clone()
             .unwrap_or_default()
         {
             ServerVerifyMode::Auto => {
@@ -142,6 +246,75 @@ impl TlsConnectorData {
                 trace!("boring connector: server verify mode: disable");
                 cfg_builder.set_custom_verify_callback(SslVerifyMode::NONE, |_| Ok(()));
             }
+            ServerVerifyMode::RootStore(root_store) => {
+                trace!("boring connector: server verify mode: custom root store");
+                cfg_builder.set_cert_store(x509_store_from_data_encoding(&root_store)?);
+            }
+            ServerVerifyMode::Pinned { pins, root_store } => {
+                trace!("boring connector: server verify mode: pinned");
+                if let Some(root_store) = root_store {
+                    cfg_builder.set_cert_store(x509_store_from_data_encoding(&root_store)?);
+                }
+                cfg_builder.set_verify_callback(SslVerifyMode::PEER, move |preverify_ok, ctx| {
+                    // only check the pins once the full chain is verified (leaf = depth 0)
+                    if !preverify_ok || ctx.error_depth() != 0 {
+                        return preverify_ok;
+                    }
+                    let Some(chain) = ctx.chain() else {
+                        trace!("boring connector: pinned verify: no verified chain available");
+                        return false;
+                    };
+                    let certs = match chain
+                        .into_iter()
+                        .map(|cert| cert.to_der())
+                        .collect::<Result<Vec<_>, _>>()
+                    {
+                        Ok(certs) => certs,
+                        Err(err) => {
+                            trace!(error = %err, "boring connector: pinned verify: encode chain as DER");
+                            return false;
+                        }
+                    };
+                    match pins.verify_cert_chain(certs.iter().map(Vec::as_slice)) {
+                        Ok(()) => true,
+                        Err(err) => {
+                            trace!(error = %err, "boring connector: pinned verify: no match");
+                            false
+                        }
+                    }
+                });
+            }
+            ServerVerifyMode::Custom(verifier) => {
+                trace!("boring connector: server verify mode: custom (dynamic) verifier");
+                cfg_builder.set_async_custom_verify_callback(
+                    SslVerifyMode::PEER,
+                    move |ssl: &mut SslRef| {
+                        let cert_chain: DataEncoding = ssl
+                            .peer_cert_chain()
+                            .ok_or(SslAlert::CERTIFICATE_UNKNOWN)?
+                            .try_into()
+                            .map_err(|err: OpaqueError| {
+                                trace!(error = %err, "boring connector: custom verify: encode chain");
+                                SslAlert::BAD_CERTIFICATE
+                            })?;
+                        let server_name = ssl
+                            .servername(NameType::HOST_NAME)
+                            .and_then(|name| name.parse::<Host>().ok());
+
+                        let verifier = verifier.clone();
+                        Ok(Box::pin(async move {
+                            verifier
+                                .verify_server_cert(cert_chain, server_name)
+                                .await
+                                .map_err(|err| {
+                                    trace!(error = %err, "boring connector: custom verify: rejected");
+                                    SslAlert::BAD_CERTIFICATE
+                                })?;
+                            Ok(Box::new(|_: &mut SslRef| Ok(())) as BoxCustomVerifyFinish)
+                        }))
+                    },
+                );
+            }
         }
 
         if let Some(auth) = self.connect_config_input.client_auth.as_ref() {
@@ -170,12 +343,43 @@ impl TlsConnectorData {
             }
         }
 
+        let session_config_tag = self.session_config_tag();
+        if let Some(session_store) = self.connect_config_input.session_store.clone() {
+            trace!("boring connector: enable client session resumption using shared store");
+            let key_index = client_session_key_index()?;
+            cfg_builder.set_session_cache_mode(SslSessionCacheMode::CLIENT);
+            cfg_builder.set_new_session_callback(move |ssl, session| {
+                if let Some(key) = ssl.ex_data(key_index) {
+                    session_store.insert(
+                        key.clone(),
+                        ClientSession::new(BoringClientSession {
+                            config_tag: session_config_tag,
+                            session,
+                        }),
+                    );
+                }
+            });
+        }
+
         trace!("boring connector: build SSL connector config");
-        let cfg = cfg_builder
+        let mut cfg = cfg_builder
             .build()
             .configure()
             .context("create ssl connector configuration")?;
 
+        match self.connect_config_input.ech.as_ref() {
+            None => (),
+            Some(EchMode::Grease) => {
+                trace!("boring connector: enable GREASE ECH");
+                cfg.set_enable_ech_grease(true);
+            }
+            Some(EchMode::Enable(ech_config_list)) => {
+                trace!("boring connector: enable ECH using config list");
+                cfg.set_ech_config_list(ech_config_list.as_bytes())
+                    .context("build (boring) ssl connector: set ech config list")?;
+            }
+        }
+
         trace!(
             "boring connector: return SSL connector config for server: {:?}",
             self.server_name
@@ -183,9 +387,41 @@ impl TlsConnectorData {
         Ok(ConnectConfigData {
             config: cfg,
             server_name: self.server_name.clone(),
+            key_log,
+            session_store: self.connect_config_input.session_store.clone(),
+            session_config_tag,
+            alpn_protos: self
+                .connect_config_input
+                .alpn_protos
+                .as_deref()
+                .map(decode_alpn_wire_format)
+                .unwrap_or_default(),
         })
     }
 
+    /// Fingerprint of the config which a stored session has to match in order to be resumed,
+    /// as a resumed session is not verified again.
+    fn session_config_tag(&self) -> u64 {
+        let input = &self.connect_config_input;
+        let mut hasher = DefaultHasher::new();
+        input.server_verify_mode.hash(&mut hasher);
+        input
+            .min_ssl_version
+            .map(|v| v.to_string())
+            .hash(&mut hasher);
+        input
+            .max_ssl_version
+            .map(|v| v.to_string())
+            .hash(&mut hasher);
+        input.cipher_list.hash(&mut hasher);
+        input
+            .client_auth
+            .as_ref()
+            .and_then(|auth| auth.cert_chain.first()?.to_der().ok())
+            .hash(&mut hasher);
+        hasher.finish()
+    }
+
     /// Merge `self` together with the `other`, resulting in
     /// a new [`TlsConnectorData`], where any defined properties of `other`
     /// take priority over conflicting ones in `self`.
@@ -228,7 +464,8 @@ impl TlsConnectorData {
                 server_verify_mode: other
                     .connect_config_input
                     .server_verify_mode
-                    .or_else(|| self.connect_config_input.server_verify_mode),
+                    .clone()
+                    .or_else(|| self.connect_config_input.server_verify_mode.clone()),
                 client_auth: other
                     .connect_config_input
                     .client_auth
@@ -237,6 +474,16 @@ impl TlsConnectorData {
                 store_server_certificate_chain: other
                     .connect_config_input
                     .store_server_certificate_chain,
+                session_store: other
+                    .connect_config_input
+                    .session_store
+                    .clone()
+                    .or_else(|| self.connect_config_input.session_store.clone()),
+                ech: other
+                    .connect_config_input
+                    .ech
+                    .clone()
+                    .or_else(|| self.connect_config_input.ech.clone()),
             }),
             server_name: other
                 .server_name
@@ -496,12 +743,41 @@ impl TryFrom<rama_net::tls::client::ClientConfig> for TlsConnectorData {
                 server_verify_mode: value.server_verify_mode,
                 client_auth,
                 store_server_certificate_chain: value.store_server_certificate_chain,
+                session_store: value.session_store,
+                ech: value.ech,
             }),
             server_name,
         })
     }
 }
 
+fn x509_store_from_data_encoding(
+    data: &DataEncoding,
+) -> Result<boring::x509::store::X509Store, OpaqueError> {
+    let certs = match data {
+        DataEncoding::Der(raw_data) => vec![X509::from_der(&raw_data[..])
+            .context("boring/TlsConnectorData: parse x509 root cert from DER content")?],
+        DataEncoding::DerStack(raw_data_list) => raw_data_list
+            .iter()
+            .map(|raw_data| {
+                X509::from_der(&raw_data[..])
+                    .context("boring/TlsConnectorData: parse x509 root cert from DER content")
+            })
+            .collect::<Result<Vec<_>, _>>()?,
+        DataEncoding::Pem(raw_data) => X509::stack_from_pem(raw_data.as_bytes())
+            .context("boring/TlsConnectorData: parse x509 root certs from PEM content")?,
+    };
+
+    let mut store_builder =
+        X509StoreBuilder::new().context("boring/TlsConnectorData: create x509 store builder")?;
+    for cert in certs {
+        store_builder
+            .add_cert(cert)
+            .context("boring/TlsConnectorData: add root cert to x509 store")?;
+    }
+    Ok(store_builder.build())
+}
+
 fn self_signed_client_auth() -> Result<(Vec<X509>, PKey<Private>), OpaqueError> {
     let rsa = Rsa::generate(4096).context("generate 4096 RSA key")?;
     let privkey = PKey::from_rsa(rsa).context("create private key from 4096 RSA key")?;
@@ -23,9 +23,9 @@ use rama_net::{
         client::ClientHello as RamaClientHello,
         server::{
             CacheKind, ClientVerifyMode, DynamicIssuer, SelfSignedData, ServerAuth, ServerAuthData,
-            ServerCertIssuerKind,
+            ServerCertIssuerKind, ServerSessionResumption,
         },
-        ApplicationProtocol, DataEncoding, KeyLogIntent, ProtocolVersion,
+        ApplicationProtocol, DataEncoding, EchKey, KeyLogIntent, ProtocolVersion,
     },
 };
 use std::{sync::Arc, time::Duration};
@@ -53,6 +53,10 @@ pub(super) struct TlsConfig {
     pub(super) client_cert_chain: Option<Vec<X509>>,
     /// store client certificate chain if true and client provided this
     pub store_client_certificate_chain: bool,
+    /// optionally enable session resumption
+    pub(super) session_resumption: Option<ServerSessionResumption>,
+    /// optional keys used to decrypt ECH client hellos
+    pub(super) ech_keys: Option<Vec<EchKey>>,
 }
 
 #[derive(Debug, Clone)]
@@ -342,6 +346,8 @@ impl TryFrom<rama_net::tls::server::ServerConfig> for TlsAcceptorData {
                 protocol_versions: value.protocol_versions.clone(),
                 client_cert_chain,
                 store_client_certificate_chain: value.store_client_certificate_chain,
+                session_resumption: value.session_resumption,
+                ech_keys: value.ech_keys,
             }),
         })
     }
@@ -0,0 +1,118 @@
+//! Encrypted Client Hello (ECH) support for the boring server,
+//! decrypting the inner ClientHello using configured [`EchKey`]s
+//! and recording the outer ClientHello as sent in the clear by the client.
+
+use boring::{
+    ex_data::Index,
+    hpke::HpkeKey,
+    ssl::{SslContext, SslContextBuilder, SslEchKeys},
+};
+use boring_sys as ffi;
+use parking_lot::Mutex;
+use rama_core::error::{ErrorContext, OpaqueError};
+use rama_net::tls::{client::ClientHello, EchKey};
+use std::{
+    ffi::{c_int, c_void},
+    panic::{catch_unwind, AssertUnwindSafe},
+    sync::{Arc, OnceLock},
+};
+use tracing::{debug, trace};
+
+/// Content type of a handshake record (message), as passed to the message callback.
+const SSL3_RT_HANDSHAKE: c_int = 22;
+
+/// Slot in which the outer ClientHello of a connection is recorded.
+pub(super) type OuterClientHello = Arc<Mutex<Option<ClientHello>>>;
+
+/// Decrypt ECH ClientHellos using the given [`EchKey`]s.
+pub(super) fn set_ech_keys(
+    builder: &mut SslContextBuilder,
+    ech_keys: &[EchKey],
+) -> Result<(), OpaqueError> {
+    trace!(
+        "tls boring server service: set {} ECH key(s)",
+        ech_keys.len()
+    );
+    let mut ech_keys_builder =
+        SslEchKeys::builder().context("build boring ssl acceptor: create ech keys")?;
+    for ech_key in ech_keys {
+        let hpke_key = HpkeKey::dhkem_x25519_sha256(&ech_key.private_key)
+            .context("build boring ssl acceptor: parse ech (x25519) private key")?;
+        ech_keys_builder
+            .add_key(ech_key.is_retry_config, ech_key.config.as_bytes(), hpke_key)
+            .context("build boring ssl acceptor: add ech key")?;
+    }
+    builder
+        .set_ech_keys(&ech_keys_builder.build())
+        .context("build boring ssl acceptor: set ech keys")
+}
+
+/// Record the (first) ClientHello as received on the wire.
+///
+/// In case ECH is accepted this is the outer ClientHello,
+/// as the certificate callbacks of boringssl only get to see the inner ClientHello.
+pub(super) fn record_outer_client_hello(
+    builder: &mut SslContextBuilder,
+) -> Result<OuterClientHello, OpaqueError> {
+    let outer_client_hello = OuterClientHello::default();
+    builder.set_ex_data(outer_client_hello_index()?, outer_client_hello.clone());
+
+    // SAFETY: the callback only accesses the slot stored in the ex data of the context,
+    // and the message buffer handed out by boringssl, within the length it passes along.
+    unsafe {
+        ffi::SSL_CTX_set_msg_callback(builder.as_ptr(), Some(msg_callback));
+    }
+    Ok(outer_client_hello)
+}
+
+fn outer_client_hello_index() -> Result<Index<SslContext, OuterClientHello>, OpaqueError> {
+    static INDEX: OnceLock<Index<SslContext, OuterClientHello>> = OnceLock::new();
+    if let Some(index) = INDEX.get() {
+        return Ok(*index);
+    }
+    let index = SslContext::new_ex_index()
+        .context("boring ssl acceptor: create outer client hello ex index")?;
+    Ok(*INDEX.get_or_init(|| index))
+}
+
+unsafe extern "C" fn msg_callback(
+    is_write: c_int,
+    _version: c_int,
+    content_type: c_int,
+    buf: *const c_void,
+    len: usize,
+    ssl: *mut ffi::SSL,
+    _arg: *mut c_void,
+) {
+    // only the first handshake message received from the client is of interest,
+    // all other (handshake) messages and record headers are ignored
+    if is_write != 0 || content_type != SSL3_RT_HANDSHAKE || buf.is_null() {
+        return;
+    }
+
+    // never unwind into boringssl
+    let _ = catch_unwind(AssertUnwindSafe(|| {
+        let Ok(index) = outer_client_hello_index() else {
+            return;
+        };
+        let ctx = ffi::SSL_get_SSL_CTX(ssl);
+        let slot = ffi::SSL_CTX_get_ex_data(ctx, index.as_raw()).cast::<OuterClientHello>();
+        let Some(slot) = slot.as_ref() else {
+            return;
+        };
+
+        let mut slot = slot.lock();
+        if slot.is_some() {
+            // e.g. the second ClientHello sent after a HelloRetryRequest
+            return;
+        }
+        let msg = std::slice::from_raw_parts(buf.cast::<u8>(), len);
+        if msg.first() != Some(&1) {
+            return;
+        }
+        match ClientHello::try_from_handshake_message(msg) {
+            Ok(client_hello) => *slot = Some(client_hello),
+            Err(err) => debug!(%err, "boring ssl acceptor: failed to parse outer client hello"),
+        }
+    }));
+}
@@ -21,3 +21,6 @@ pub use service::TlsAcceptorService;
 mod layer;
 #[doc(inline)]
 pub use layer::TlsAcceptorLayer;
+
+mod ech;
+mod ticket;
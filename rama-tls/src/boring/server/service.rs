@@ -1,10 +1,13 @@
-use super::TlsAcceptorData;
+use super::{ech, ticket::set_session_ticket_keys, TlsAcceptorData};
 use crate::{
     boring::dep::{
-        boring::ssl::{AlpnError, SslAcceptor, SslMethod, SslRef},
+        boring::ssl::{
+            AlpnError, GetSessionPendingError, SslAcceptor, SslMethod, SslOptions, SslRef,
+            SslSession, SslSessionCacheMode,
+        },
         tokio_boring::SslStream,
     },
-    keylog::new_key_log_file_handle,
+    keylog::new_key_log_sink,
     types::SecureTransport,
 };
 use parking_lot::Mutex;
@@ -15,7 +18,10 @@ use rama_core::{
 use rama_net::{
     http::RequestContext,
     stream::Stream,
-    tls::{client::NegotiatedTlsParameters, ApplicationProtocol, DataEncoding},
+    tls::{
+        client::NegotiatedTlsParameters, server::PeerCertificateIdentity, ApplicationProtocol,
+        DataEncoding, KeyLogEntry,
+    },
     transport::TransportContext,
 };
 use rama_utils::macros::define_inner_service_accessors;
@@ -172,12 +178,73 @@ where
             );
         }
 
-        if let Some(keylog_filename) = tls_config.keylog_intent.file_path() {
-            let handle = new_key_log_file_handle(keylog_filename)?;
-            acceptor_builder.set_keylog_callback(move |_, line| {
-                let line = format!("{}\n", line);
-                handle.write_log_line(line);
-            });
+        let (key_log_sink, key_log) = new_key_log_sink(&tls_config.keylog_intent)?;
+        if let Some(sink) = key_log_sink {
+            acceptor_builder.set_keylog_callback(
+                move |_, line| match KeyLogEntry::try_from_nss_line(line) {
+                    Ok(entry) => sink.write_key_log(&entry),
+                    Err(err) => {
+                        trace!(%err, "tls boring server service: ignore invalid key log line")
+                    }
+                },
+            );
+        }
+
+        if let Some(resumption) = tls_config.session_resumption.as_ref() {
+            // a new acceptor is created per connection, so the internal session cache
+            // and ticket keys of boring are of no use, hence the need for shared ones
+            match resumption.session_tickets.as_ref() {
+                Some(ticket_config) => {
+                    trace!("tls boring server service: enable session tickets (shared keys)");
+                    set_session_ticket_keys(&mut acceptor_builder, ticket_config)?;
+                }
+                None => {
+                    acceptor_builder.set_options(SslOptions::NO_TICKET);
+                }
+            }
+
+            if let Some(session_cache) = resumption.session_cache.clone() {
+                trace!("tls boring server service: enable (shared) session cache");
+                acceptor_builder
+                    .set_session_id_context(b"rama")
+                    .context("build boring ssl acceptor: set session id context")?;
+                acceptor_builder.set_session_cache_mode(
+                    SslSessionCacheMode::SERVER | SslSessionCacheMode::NO_INTERNAL,
+                );
+
+                let new_session_cache = session_cache.clone();
+                acceptor_builder.set_new_session_callback(move |_, session| {
+                    match session.to_der() {
+                        Ok(value) => {
+                            new_session_cache.put(session.id().to_vec(), value);
+                        }
+                        Err(err) => {
+                            debug!(%err, "tls boring server service: failed to encode session");
+                        }
+                    }
+                });
+
+                // SAFETY: the returned sessions are decoded from sessions
+                // previously encoded by this same callback chain.
+                unsafe {
+                    acceptor_builder.set_get_session_callback(
+                        move |_, id| -> Result<Option<SslSession>, GetSessionPendingError> {
+                            Ok(session_cache
+                                .get(id)
+                                .and_then(|value| SslSession::from_der(&value).ok()))
+                        },
+                    );
+                }
+            }
+        }
+
+        let mut maybe_outer_client_hello = None;
+        if let Some(ech_keys) = tls_config.ech_keys.as_deref() {
+            ech::set_ech_keys(&mut acceptor_builder, ech_keys)?;
+            if self.store_client_hello {
+                maybe_outer_client_hello =
+                    Some(ech::record_outer_client_hello(&mut acceptor_builder)?);
+            }
         }
 
         let acceptor = acceptor_builder.build();
@@ -229,10 +296,22 @@ where
                     None
                 };
 
+                if let Some(identity) = client_certificate_chain.as_ref().and_then(|chain| {
+                    PeerCertificateIdentity::from_cert_chain(chain)
+                        .inspect_err(|err| {
+                            debug!(%err, "boring ssl acceptor: failed to parse peer certificate identity");
+                        })
+                        .ok()
+                }) {
+                    ctx.insert(identity);
+                }
+
                 ctx.insert(NegotiatedTlsParameters {
                     protocol_version,
                     application_layer_protocol,
                     peer_certificate_chain: client_certificate_chain,
+                    session_resumed: stream.ssl().session_reused(),
+                    ech_accepted: stream.ssl().ech_accepted(),
                 });
             }
             None => {
@@ -243,11 +322,23 @@ where
             }
         }
 
-        let secure_transport = maybe_client_hello
+        let mut secure_transport = maybe_client_hello
             .take()
             .and_then(|maybe_client_hello| maybe_client_hello.lock().take())
             .map(SecureTransport::with_client_hello)
             .unwrap_or_default();
+        // without ECH (accepted) the outer ClientHello is the only ClientHello
+        if stream.ssl().ech_accepted() {
+            if let Some(outer_client_hello) = maybe_outer_client_hello
+                .and_then(|outer_client_hello| outer_client_hello.lock().take())
+            {
+                secure_transport.set_outer_client_hello(outer_client_hello);
+            }
+        }
+
+        if let Some(key_log) = key_log {
+            ctx.insert(key_log);
+        }
         ctx.insert(secure_transport);
 
         self.inner.serve(ctx, stream).await.map_err(|err| {
@@ -0,0 +1,153 @@
+//! Session tickets issued using (shared) [`SessionTicketKeys`],
+//! by means of the session ticket key callback of boringssl.
+//!
+//! [`SessionTicketKeys`]: rama_net::tls::server::SessionTicketKeys
+
+use boring::{
+    ex_data::Index,
+    ssl::{SslContext, SslContextBuilder},
+};
+use boring_sys as ffi;
+use rama_core::error::{ErrorContext, OpaqueError};
+use rama_net::tls::server::{SessionTicketConfig, SessionTicketKey, SessionTicketKeyProvider};
+use std::{
+    ffi::c_int,
+    panic::{catch_unwind, AssertUnwindSafe},
+    sync::OnceLock,
+};
+use tracing::{debug, trace};
+
+/// length of the key name, as expected by boringssl
+const KEY_NAME_LEN: usize = 16;
+/// length of the IV of the AES-128-CBC cipher used to encrypt tickets
+const IV_LEN: usize = 16;
+
+/// Issue and accept session tickets using the keys of the given [`SessionTicketConfig`].
+///
+/// Tickets are encrypted using AES-128-CBC and authenticated using HMAC-SHA256,
+/// using respectively the first and last 16 bytes of the secret of a [`SessionTicketKey`].
+/// Tickets encrypted with a previous key are accepted and renewed.
+pub(super) fn set_session_ticket_keys(
+    builder: &mut SslContextBuilder,
+    config: &SessionTicketConfig,
+) -> Result<(), OpaqueError> {
+    let lifetime = u32::try_from(config.lifetime.as_secs()).unwrap_or(u32::MAX);
+    builder.set_ex_data(ticket_key_provider_index()?, config.key_provider.clone());
+
+    // SAFETY: the callback only accesses the key provider stored in the ex data of the context,
+    // and the buffers handed out by boringssl, within the lengths it documents.
+    let result = unsafe {
+        ffi::SSL_CTX_set_timeout(builder.as_ptr(), lifetime);
+        ffi::SSL_CTX_set_session_psk_dhe_timeout(builder.as_ptr(), lifetime);
+        ffi::SSL_CTX_set_tlsext_ticket_key_cb(builder.as_ptr(), Some(ticket_key_callback))
+    };
+    if result != 1 {
+        return Err(OpaqueError::from_display(
+            "boring ssl acceptor: set session ticket key callback",
+        ));
+    }
+    Ok(())
+}
+
+fn ticket_key_provider_index() -> Result<Index<SslContext, SessionTicketKeyProvider>, OpaqueError> {
+    static INDEX: OnceLock<Index<SslContext, SessionTicketKeyProvider>> = OnceLock::new();
+    if let Some(index) = INDEX.get() {
+        return Ok(*index);
+    }
+    let index = SslContext::new_ex_index()
+        .context("boring ssl acceptor: create session ticket key provider ex index")?;
+    Ok(*INDEX.get_or_init(|| index))
+}
+
+unsafe extern "C" fn ticket_key_callback(
+    ssl: *mut ffi::SSL,
+    key_name: *mut u8,
+    iv: *mut u8,
+    cipher_ctx: *mut ffi::EVP_CIPHER_CTX,
+    hmac_ctx: *mut ffi::HMAC_CTX,
+    encrypt: c_int,
+) -> c_int {
+    // never unwind into boringssl, e.g. in case the (user defined) key provider panics
+    catch_unwind(AssertUnwindSafe(|| {
+        let Ok(index) = ticket_key_provider_index() else {
+            return -1;
+        };
+        let ctx = ffi::SSL_get_SSL_CTX(ssl);
+        let provider =
+            ffi::SSL_CTX_get_ex_data(ctx, index.as_raw()).cast::<SessionTicketKeyProvider>();
+        let Some(provider) = provider.as_ref() else {
+            debug!("boring ssl acceptor: session ticket callback: no key provider found");
+            return -1;
+        };
+        let keys = provider.ticket_keys();
+
+        if encrypt == 1 {
+            let key = &keys.current;
+            std::ptr::copy_nonoverlapping(key.name().as_ptr(), key_name, KEY_NAME_LEN);
+            if ffi::RAND_bytes(iv, IV_LEN) != 1 {
+                return -1;
+            }
+            if init_ticket_crypto(key, iv, cipher_ctx, hmac_ctx, true) {
+                1
+            } else {
+                -1
+            }
+        } else {
+            let name = std::slice::from_raw_parts(key_name, KEY_NAME_LEN);
+            let Some(key) = keys.find(name) else {
+                trace!("boring ssl acceptor: session ticket callback: unknown ticket key");
+                return 0;
+            };
+            if !init_ticket_crypto(key, iv, cipher_ctx, hmac_ctx, false) {
+                return -1;
+            }
+            // renew tickets issued using a previous key
+            if key == &keys.current {
+                1
+            } else {
+                2
+            }
+        }
+    }))
+    .unwrap_or(-1)
+}
+
+/// Initialize the cipher and hmac contexts of boringssl for the given key.
+///
+/// # Safety
+///
+/// The pointers have to be the ones handed to the session ticket key callback by boringssl.
+unsafe fn init_ticket_crypto(
+    key: &SessionTicketKey,
+    iv: *const u8,
+    cipher_ctx: *mut ffi::EVP_CIPHER_CTX,
+    hmac_ctx: *mut ffi::HMAC_CTX,
+    encrypt: bool,
+) -> bool {
+    let (aes_key, hmac_key) = key.secret().split_at(16);
+    let hmac_ok = ffi::HMAC_Init_ex(
+        hmac_ctx,
+        hmac_key.as_ptr().cast(),
+        hmac_key.len(),
+        ffi::EVP_sha256(),
+        std::ptr::null_mut(),
+    ) == 1;
+    let cipher_ok = if encrypt {
+        ffi::EVP_EncryptInit_ex(
+            cipher_ctx,
+            ffi::EVP_aes_128_cbc(),
+            std::ptr::null_mut(),
+            aes_key.as_ptr(),
+            iv,
+        )
+    } else {
+        ffi::EVP_DecryptInit_ex(
+            cipher_ctx,
+            ffi::EVP_aes_128_cbc(),
+            std::ptr::null_mut(),
+            aes_key.as_ptr(),
+            iv,
+        )
+    } == 1;
+    hmac_ok && cipher_ok
+}
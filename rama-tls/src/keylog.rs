@@ -2,18 +2,41 @@
 //! supported by rama, and which can be used for your owns as well.
 //!
 //! Center to thsi module is the `KeyLogger` which is a wrapper around
-//! a FS file
+//! a FS file, which can also be used as a [`KeyLogSink`], next to any other sink.
 
 use parking_lot::RwLock;
 use rama_core::error::{ErrorContext, OpaqueError};
+use rama_net::tls::{KeyLogEntry, KeyLogIntent, KeyLogSink, MemoryKeyLog, WritesKeyLog};
 use std::{
     collections::{hash_map::Entry, HashMap},
     fs::OpenOptions,
     io::Write,
+    num::NonZeroUsize,
     path::{Component, Path, PathBuf},
     sync::OnceLock,
 };
 
+/// Get a [`KeyLogSink`] for the given [`KeyLogIntent`], if any keys are to be logged.
+///
+/// In case of [`KeyLogIntent::Connection`] a new [`MemoryKeyLog`] is created
+/// for the connection, returned as well such that it can be added to its context.
+pub fn new_key_log_sink(
+    intent: &KeyLogIntent,
+) -> Result<(Option<KeyLogSink>, Option<MemoryKeyLog>), OpaqueError> {
+    match intent {
+        KeyLogIntent::Sink(sink) => Ok((Some(sink.clone()), None)),
+        KeyLogIntent::Connection => {
+            // large enough to contain all secrets of a single (tls 1.3) connection
+            let key_log = MemoryKeyLog::new(NonZeroUsize::new(16).unwrap());
+            Ok((Some(KeyLogSink::new(key_log.clone())), Some(key_log)))
+        }
+        intent => match intent.file_path() {
+            Some(path) => Ok((Some(KeyLogSink::new(new_key_log_file_handle(path)?)), None)),
+            None => Ok((None, None)),
+        },
+    }
+}
+
 /// Get a key log file handle for the given path
 /// only one file handle will be opened per unique path String.
 ///
@@ -128,6 +151,12 @@ pub struct KeyLogFileHandle {
     sender: flume::Sender<String>,
 }
 
+impl WritesKeyLog for KeyLogFileHandle {
+    fn write_key_log(&self, entry: &KeyLogEntry) {
+        self.write_log_line(format!("{entry}\n"));
+    }
+}
+
 impl KeyLogFileHandle {
     /// Write a line to the keylogger.
     pub fn write_log_line(&self, line: String) {
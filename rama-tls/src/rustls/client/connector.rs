@@ -1,4 +1,5 @@
 use super::TlsConnectorData;
+use crate::rustls::dep::rustls::{client::EchStatus, HandshakeKind};
 use crate::rustls::dep::tokio_rustls::{client::TlsStream, TlsConnector as RustlsConnector};
 use crate::types::TlsTunnel;
 use pin_project_lite::pin_project;
@@ -9,12 +10,12 @@ use rama_core::{Context, Layer, Service};
 use rama_net::address::Host;
 use rama_net::client::{ConnectorService, EstablishedClientConnection};
 use rama_net::stream::Stream;
-use rama_net::tls::client::NegotiatedTlsParameters;
-use rama_net::tls::ApplicationProtocol;
+use rama_net::tls::client::{ClientSessionKey, NegotiatedTlsParameters};
+use rama_net::tls::{ApplicationProtocol, MemoryKeyLog, ProtocolVersion};
 use rama_net::transport::TryRefIntoTransportContext;
 use std::fmt;
 use std::sync::Arc;
-use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
 
 /// A [`Layer`] which wraps the given service with a [`TlsConnector`].
 ///
@@ -274,7 +275,8 @@ where
         );
 
         let connector_data = ctx.get().cloned();
-        let (stream, negotiated_params) = self.handshake(connector_data, server_host, conn).await?;
+        let (stream, negotiated_params, key_log) =
+            self.handshake(connector_data, server_host, conn).await?;
 
         tracing::trace!(
             authority = %transport_ctx.authority,
@@ -283,6 +285,9 @@ where
         );
 
         ctx.insert(negotiated_params);
+        if let Some(key_log) = key_log {
+            ctx.insert(key_log);
+        }
 
         Ok(EstablishedClientConnection {
             ctx,
@@ -333,8 +338,12 @@ where
         let server_host = transport_ctx.authority.host().clone();
 
         let connector_data = ctx.get().cloned();
-        let (conn, negotiated_params) = self.handshake(connector_data, server_host, conn).await?;
+        let (conn, negotiated_params, key_log) =
+            self.handshake(connector_data, server_host, conn).await?;
         ctx.insert(negotiated_params);
+        if let Some(key_log) = key_log {
+            ctx.insert(key_log);
+        }
 
         Ok(EstablishedClientConnection {
             ctx,
@@ -389,8 +398,12 @@ where
         };
 
         let connector_data = ctx.get().cloned();
-        let (conn, negotiated_params) = self.handshake(connector_data, server_host, conn).await?;
+        let (conn, negotiated_params, key_log) =
+            self.handshake(connector_data, server_host, conn).await?;
         ctx.insert(negotiated_params);
+        if let Some(key_log) = key_log {
+            ctx.insert(key_log);
+        }
 
         tracing::trace!("TlsConnector(tunnel): connection secured");
         Ok(EstablishedClientConnection {
@@ -410,7 +423,7 @@ impl<S, K> TlsConnector<S, K> {
         connector_data: Option<TlsConnectorData>,
         server_host: Host,
         stream: T,
-    ) -> Result<(TlsStream<T>, NegotiatedTlsParameters), BoxError>
+    ) -> Result<(TlsStream<T>, NegotiatedTlsParameters, Option<MemoryKeyLog>), BoxError>
     where
         T: Stream + Unpin,
     {
@@ -419,16 +432,53 @@ impl<S, K> TlsConnector<S, K> {
             Some(connector_data) => connector_data.try_to_build_config()?,
             None => TlsConnectorData::new_http_auto()?.try_to_build_config()?,
         };
-        let server_name = rustls_pki_types::ServerName::try_from(
-            client_config_data.server_name.unwrap_or(server_host),
-        )?;
+        let server_host = client_config_data.server_name.unwrap_or(server_host);
+        let server_name = rustls_pki_types::ServerName::try_from(server_host.clone())?;
 
-        let connector = RustlsConnector::from(Arc::new(client_config_data.config));
+        let client_config = Arc::new(client_config_data.config);
+        let connector = RustlsConnector::from(client_config.clone());
 
         let stream = connector.connect(server_name, stream).await?;
 
         let (_, conn_data_ref) = stream.get_ref();
 
+        // verified after the handshake, as rustls cannot drive an async verifier,
+        // but still before the connection is returned and thus used
+        if let Some(verifier) =
+            connector_data.and_then(|data| data.client_config_input.dynamic_verifier.as_ref())
+        {
+            let result = match conn_data_ref.peer_certificates() {
+                Some(cert_chain) => verifier
+                    .verify_server_cert(cert_chain.into(), Some(server_host.clone()))
+                    .await
+                    .context("rustls connector: verify server cert (dynamic verifier)"),
+                None => Err(OpaqueError::from_display(
+                    "rustls connector: no server certificates to verify",
+                )),
+            };
+            if let Err(err) = result {
+                // the session was established (and possibly stored) already,
+                // so it is forgotten and the connection closed before failing
+                if let Some(session_store) =
+                    connector_data.and_then(|data| data.client_config_input.session_store.as_ref())
+                {
+                    for version in [ProtocolVersion::TLSv1_2, ProtocolVersion::TLSv1_3] {
+                        session_store.remove(
+                            &ClientSessionKey::new(
+                                Some(server_host.clone()),
+                                &client_config.alpn_protocols,
+                            )
+                            .with_protocol_version(version),
+                        );
+                    }
+                }
+                let mut stream = stream;
+                let _ = stream.shutdown().await;
+                return Err(err.into());
+            }
+            tracing::trace!("rustls connector: server cert verified by dynamic verifier");
+        }
+
         let store_server_cert_chain = connector_data
             .is_some_and(|data| data.client_config_input.store_server_certificate_chain);
 
@@ -447,9 +497,11 @@ impl<S, K> TlsConnector<S, K> {
                 .alpn_protocol()
                 .map(ApplicationProtocol::from),
             peer_certificate_chain: server_certificate_chain,
+            session_resumed: conn_data_ref.handshake_kind() == Some(HandshakeKind::Resumed),
+            ech_accepted: conn_data_ref.ech_status() == EchStatus::Accepted,
         };
 
-        Ok((stream, params))
+        Ok((stream, params, client_config_data.key_log))
     }
 }
 
@@ -590,4 +642,453 @@ mod tests {
 
         assert_sync::<TlsConnectorLayer>();
     }
+
+    use crate::rustls::dep::{
+        pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer},
+        rcgen::{BasicConstraints, CertificateParams, IsCa, KeyPair},
+        rustls::{
+            crypto::{aws_lc_rs::hpke, hpke::Hpke},
+            ServerConfig, ALL_VERSIONS,
+        },
+        tokio_rustls::TlsAcceptor,
+    };
+    use rama_net::address::Domain;
+    use rama_net::tls::{
+        client::{
+            ClientAuth, ClientConfig, ClientHelloExtension, ClientSessionStore,
+            DynamicCertVerifier, EchMode, ServerVerifyMode, SpkiPin, SpkiPinSet,
+        },
+        DataEncoding, EchConfig, EchConfigList, ProtocolVersion,
+    };
+    use std::num::NonZeroUsize;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    struct TestServer {
+        acceptor: TlsAcceptor,
+        ca_der: CertificateDer<'static>,
+        leaf_der: CertificateDer<'static>,
+    }
+
+    fn test_server() -> TestServer {
+        let ca_key = KeyPair::generate().unwrap();
+        let mut ca_params = CertificateParams::new(Vec::new()).unwrap();
+        ca_params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        let ca_cert = ca_params.self_signed(&ca_key).unwrap();
+
+        let leaf_key = KeyPair::generate().unwrap();
+        let leaf_cert = CertificateParams::new(vec!["localhost".to_owned()])
+            .unwrap()
+            .signed_by(&leaf_key, &ca_cert, &ca_key)
+            .unwrap();
+
+        let config = ServerConfig::builder_with_protocol_versions(ALL_VERSIONS)
+            .with_no_client_auth()
+            .with_single_cert(
+                vec![leaf_cert.der().clone()],
+                PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(leaf_key.serialize_der())),
+            )
+            .unwrap();
+
+        TestServer {
+            acceptor: TlsAcceptor::from(Arc::new(config)),
+            ca_der: ca_cert.der().clone(),
+            leaf_der: leaf_cert.der().clone(),
+        }
+    }
+
+    /// Handshake with the given server, returning the result of the client and the server.
+    async fn handshake(server: &TestServer, mode: ServerVerifyMode) -> (bool, bool) {
+        let (client_result, server_ok) = handshake_with_config(
+            server,
+            ClientConfig {
+                server_verify_mode: Some(mode),
+                ..Default::default()
+            },
+        )
+        .await;
+        (client_result.is_some(), server_ok)
+    }
+
+    async fn handshake_with_config(
+        server: &TestServer,
+        config: ClientConfig,
+    ) -> (Option<NegotiatedTlsParameters>, bool) {
+        let connector_data = TlsConnectorData::try_from(config).unwrap();
+
+        let (client_stream, server_stream) = tokio::io::duplex(16 * 1024);
+        let acceptor = server.acceptor.clone();
+        let server_handle = tokio::spawn(async move {
+            let mut stream = acceptor.accept(server_stream).await?;
+            // flush the (tls 1.3) session tickets, sent after the handshake
+            stream.shutdown().await
+        });
+
+        let client_result = TlsConnector::secure(())
+            .handshake(Some(connector_data), Host::LOCALHOST_NAME, client_stream)
+            .await;
+        let params = match client_result {
+            Ok((mut stream, params, _)) => {
+                // read the session tickets (if any) until the server closes the stream
+                let _ = stream.read_to_end(&mut Vec::new()).await;
+                Some(params)
+            }
+            Err(_) => None,
+        };
+        let server_ok = server_handle.await.unwrap().is_ok();
+        (params, server_ok)
+    }
+
+    struct TestVerifier {
+        accept: bool,
+        leaf_der: Vec<u8>,
+        /// Served by a task of the runtime of the test, which deadlocks
+        /// in case that runtime is blocked during the verification.
+        approvals: tokio::sync::mpsc::UnboundedSender<tokio::sync::oneshot::Sender<()>>,
+    }
+
+    impl TestVerifier {
+        fn new(accept: bool, leaf_der: Vec<u8>) -> Self {
+            let (approvals, mut rx) =
+                tokio::sync::mpsc::unbounded_channel::<tokio::sync::oneshot::Sender<()>>();
+            tokio::spawn(async move {
+                while let Some(reply) = rx.recv().await {
+                    let _ = reply.send(());
+                }
+            });
+            Self {
+                accept,
+                leaf_der,
+                approvals,
+            }
+        }
+    }
+
+    impl DynamicCertVerifier for TestVerifier {
+        async fn verify_server_cert(
+            &self,
+            cert_chain: DataEncoding,
+            server_name: Option<Host>,
+        ) -> Result<(), OpaqueError> {
+            let (reply, approved) = tokio::sync::oneshot::channel();
+            self.approvals.send(reply).unwrap();
+            approved.await.unwrap();
+
+            assert_eq!(server_name, Some(Host::LOCALHOST_NAME));
+            let DataEncoding::DerStack(chain) = cert_chain else {
+                panic!("unexpected cert chain encoding");
+            };
+            assert_eq!(chain.first(), Some(&self.leaf_der));
+            if self.accept {
+                Ok(())
+            } else {
+                Err(OpaqueError::from_display("rejected by test verifier"))
+            }
+        }
+    }
+
+    async fn test_handshake_custom_verifier() {
+        let server = test_server();
+
+        let accept =
+            ServerVerifyMode::Custom(TestVerifier::new(true, server.leaf_der.to_vec()).into());
+        assert_eq!(handshake(&server, accept).await, (true, true));
+
+        // rejected right after the handshake, such that the connection is never used
+        let reject =
+            ServerVerifyMode::Custom(TestVerifier::new(false, server.leaf_der.to_vec()).into());
+        let (client_ok, _) = handshake(&server, reject).await;
+        assert!(!client_ok);
+    }
+
+    #[tokio::test]
+    async fn test_handshake_custom_verifier_current_thread() {
+        test_handshake_custom_verifier().await;
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_handshake_custom_verifier_multi_thread() {
+        test_handshake_custom_verifier().await;
+    }
+
+    #[tokio::test]
+    async fn test_handshake_custom_verifier_resumption() {
+        let server = test_server();
+        let session_store = ClientSessionStore::memory(NonZeroUsize::new(8).unwrap());
+        let config = |accept: bool| ClientConfig {
+            server_verify_mode: Some(ServerVerifyMode::Custom(
+                TestVerifier::new(accept, server.leaf_der.to_vec()).into(),
+            )),
+            session_store: Some(session_store.clone()),
+            ..Default::default()
+        };
+
+        let (params, _) = handshake_with_config(&server, config(true)).await;
+        assert!(!params.unwrap().session_resumed);
+        let (params, _) = handshake_with_config(&server, config(true)).await;
+        assert!(params.unwrap().session_resumed);
+
+        // resumed sessions are verified as well
+        let (params, _) = handshake_with_config(&server, config(false)).await;
+        assert!(params.is_none());
+
+        // and the sessions of a rejected server are dropped
+        let (params, _) = handshake_with_config(&server, config(true)).await;
+        assert!(!params.unwrap().session_resumed);
+    }
+
+    #[tokio::test]
+    async fn test_handshake_custom_verifier_drops_rejected_session() {
+        let server = test_server();
+        let session_store = ClientSessionStore::memory(NonZeroUsize::new(8).unwrap());
+        // tls 1.2 sessions are stored during the handshake, before the dynamic verifier runs
+        let config = |accept: bool| ClientConfig {
+            server_verify_mode: Some(ServerVerifyMode::Custom(
+                TestVerifier::new(accept, server.leaf_der.to_vec()).into(),
+            )),
+            extensions: Some(vec![ClientHelloExtension::SupportedVersions(vec![
+                ProtocolVersion::TLSv1_2,
+            ])]),
+            session_store: Some(session_store.clone()),
+            ..Default::default()
+        };
+
+        let (params, _) = handshake_with_config(&server, config(false)).await;
+        assert!(params.is_none());
+        let (params, _) = handshake_with_config(&server, config(true)).await;
+        assert!(!params.unwrap().session_resumed);
+    }
+
+    #[tokio::test]
+    async fn test_handshake_custom_verifier_client_auth() {
+        let server = test_server();
+        let config = || ClientConfig {
+            server_verify_mode: Some(ServerVerifyMode::Custom(
+                TestVerifier::new(false, server.leaf_der.to_vec()).into(),
+            )),
+            client_auth: Some(ClientAuth::SelfSigned),
+            ..Default::default()
+        };
+
+        // refused before connecting, as the client certificate
+        // would be sent before the server is rejected
+        let connector_data = TlsConnectorData::try_from(config()).unwrap();
+        assert!(connector_data.try_to_build_config().is_err());
+        let (params, server_ok) = handshake_with_config(&server, config()).await;
+        assert!(params.is_none());
+        assert!(!server_ok);
+
+        // also when combined from different connector data
+        let client_auth = TlsConnectorData::try_from(ClientConfig {
+            client_auth: Some(ClientAuth::SelfSigned),
+            ..Default::default()
+        })
+        .unwrap();
+        let custom = TlsConnectorData::try_from(ClientConfig {
+            server_verify_mode: Some(ServerVerifyMode::Custom(
+                TestVerifier::new(false, server.leaf_der.to_vec()).into(),
+            )),
+            ..Default::default()
+        })
+        .unwrap();
+        assert!(client_auth.merge(&custom).try_to_build_config().is_err());
+        assert!(custom.try_to_build_config().is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_handshake_resumption_mixed_protocol_versions() {
+        let server = test_server();
+        let session_store = ClientSessionStore::memory(NonZeroUsize::new(8).unwrap());
+        let config = |version: ProtocolVersion| ClientConfig {
+            server_verify_mode: Some(ServerVerifyMode::RootStore(Arc::new(DataEncoding::Der(
+                server.ca_der.to_vec(),
+            )))),
+            extensions: Some(vec![ClientHelloExtension::SupportedVersions(vec![version])]),
+            session_store: Some(session_store.clone()),
+            ..Default::default()
+        };
+
+        for (version, resumed) in [
+            (ProtocolVersion::TLSv1_2, false),
+            (ProtocolVersion::TLSv1_2, true),
+            // rustls drops the tls 1.2 session once the server is known to support tls 1.3
+            (ProtocolVersion::TLSv1_3, false),
+            (ProtocolVersion::TLSv1_2, false),
+            // storing a tls 1.2 session does not drop (or hide) the stored tls 1.3 tickets
+            (ProtocolVersion::TLSv1_3, true),
+        ] {
+            let (params, server_ok) = handshake_with_config(&server, config(version)).await;
+            let params = params.unwrap();
+            assert!(server_ok);
+            assert_eq!(params.protocol_version, version);
+            assert_eq!(params.session_resumed, resumed, "{version:?}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_handshake_root_store_and_pins() {
+        let server = test_server();
+        let root_store = Arc::new(DataEncoding::Der(server.ca_der.to_vec()));
+
+        // not trusted by the default root store
+        assert_eq!(
+            handshake(&server, ServerVerifyMode::Auto).await,
+            (false, false)
+        );
+        assert_eq!(
+            handshake(&server, ServerVerifyMode::RootStore(root_store.clone())).await,
+            (true, true)
+        );
+
+        let pinned = |pin: SpkiPin| ServerVerifyMode::Pinned {
+            pins: Arc::new(SpkiPinSet::new([pin])),
+            root_store: Some(root_store.clone()),
+        };
+        let leaf_pin = SpkiPin::from_cert_der(&server.leaf_der).unwrap();
+        assert_eq!(handshake(&server, pinned(leaf_pin)).await, (true, true));
+        assert_eq!(
+            handshake(&server, pinned(SpkiPin::new([0; 32]))).await,
+            (false, false)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_handshake_ech_not_supported_by_server() {
+        let server = test_server();
+        let root_store = Arc::new(DataEncoding::Der(server.ca_der.to_vec()));
+        let config = |ech: EchMode| ClientConfig {
+            server_verify_mode: Some(ServerVerifyMode::RootStore(root_store.clone())),
+            ech: Some(ech),
+            ..Default::default()
+        };
+
+        // GREASE ECH is ignored by servers which do not support ECH
+        let (params, server_ok) = handshake_with_config(&server, config(EchMode::Grease)).await;
+        let params = params.unwrap();
+        assert!(server_ok);
+        assert_eq!(params.protocol_version, ProtocolVersion::TLSv1_3);
+        assert!(!params.ech_accepted);
+
+        // ECH is applied, and rejected by the (rustls) server which does not support it,
+        // instead of silently falling back to a handshake which reveals the server name
+        let (public_key, _) = hpke::DH_KEM_X25519_HKDF_SHA256_AES_128
+            .generate_key_pair()
+            .unwrap();
+        let ech_config = EchConfig::new_x25519(
+            1,
+            &Domain::from_static("localhost"),
+            public_key.0.try_into().unwrap(),
+        );
+        let connector_data =
+            TlsConnectorData::try_from(config(EchMode::Enable(EchConfigList::from_configs([
+                &ech_config,
+            ]))))
+            .unwrap();
+        let (client_stream, server_stream) = tokio::io::duplex(16 * 1024);
+        let acceptor = server.acceptor.clone();
+        let server_handle = tokio::spawn(async move { acceptor.accept(server_stream).await });
+        let err = TlsConnector::secure(())
+            .handshake(Some(connector_data), Host::LOCALHOST_NAME, client_stream)
+            .await
+            .unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("ServerRejectedEncryptedClientHello"),
+            "{err}"
+        );
+        assert!(server_handle.await.unwrap().is_err());
+    }
+
+    #[test]
+    fn test_ech_requires_tls13() {
+        let connector_data = TlsConnectorData::try_from(ClientConfig {
+            extensions: Some(vec![ClientHelloExtension::SupportedVersions(vec![
+                ProtocolVersion::TLSv1_2,
+            ])]),
+            ech: Some(EchMode::Grease),
+            ..Default::default()
+        })
+        .unwrap();
+        assert!(connector_data.try_to_build_config().is_err());
+    }
+
+    #[cfg(feature = "boring")]
+    #[tokio::test]
+    async fn test_handshake_ech_boring_server() {
+        use crate::boring::{
+            dep::tokio_boring::SslStream,
+            server::{TlsAcceptorData as BoringTlsAcceptorData, TlsAcceptorService},
+        };
+        use crate::types::SecureTransport;
+        use rama_core::service::service_fn;
+        use rama_net::tls::{
+            server::{SelfSignedData, ServerAuth, ServerConfig as TlsServerConfig},
+            EchKey,
+        };
+        use tokio::io::DuplexStream;
+
+        let (public_key, private_key) = hpke::DH_KEM_X25519_HKDF_SHA256_AES_128
+            .generate_key_pair()
+            .unwrap();
+        let ech_config = EchConfig::new_x25519(
+            1,
+            &Domain::from_static("public.example"),
+            public_key.0.try_into().unwrap(),
+        );
+
+        let acceptor = TlsAcceptorService::new(
+            BoringTlsAcceptorData::try_from(TlsServerConfig {
+                ech_keys: Some(vec![EchKey {
+                    config: ech_config.clone(),
+                    private_key: private_key.secret_bytes().to_vec(),
+                    is_retry_config: true,
+                }]),
+                ..TlsServerConfig::new(ServerAuth::SelfSigned(SelfSignedData::default()))
+            })
+            .unwrap(),
+            service_fn(
+                |ctx: Context<()>, mut stream: SslStream<DuplexStream>| async move {
+                    stream.shutdown().await?;
+                    Ok::<_, std::io::Error>((
+                        ctx.get::<SecureTransport>().cloned().unwrap(),
+                        ctx.get::<NegotiatedTlsParameters>().cloned().unwrap(),
+                    ))
+                },
+            ),
+            true,
+        );
+
+        let connector_data = TlsConnectorData::try_from(ClientConfig {
+            server_verify_mode: Some(ServerVerifyMode::Disable),
+            ech: Some(EchMode::Enable(EchConfigList::from_configs([&ech_config]))),
+            ..Default::default()
+        })
+        .unwrap();
+
+        let (client_stream, server_stream) = tokio::io::duplex(16 * 1024);
+        let server_handle =
+            tokio::spawn(async move { acceptor.serve(Context::default(), server_stream).await });
+
+        let inner_host = Host::Name(Domain::from_static("inner.example"));
+        let (mut stream, client_params, _) = TlsConnector::secure(())
+            .handshake(Some(connector_data), inner_host.clone(), client_stream)
+            .await
+            .unwrap();
+        let _ = stream.read_to_end(&mut Vec::new()).await;
+        assert!(client_params.ech_accepted);
+
+        let (secure_transport, server_params) = server_handle.await.unwrap().unwrap();
+        assert!(server_params.ech_accepted);
+        assert_eq!(
+            secure_transport
+                .client_hello()
+                .and_then(|hello| hello.ext_server_name()),
+            Some(&inner_host)
+        );
+        assert_eq!(
+            secure_transport
+                .outer_client_hello()
+                .and_then(|hello| hello.ext_server_name()),
+            Some(&Host::Name(Domain::from_static("public.example")))
+        );
+    }
 }
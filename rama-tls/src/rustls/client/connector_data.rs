@@ -1,15 +1,32 @@
+use crate::keylog::new_key_log_sink;
 use crate::rustls::dep::pemfile;
-use crate::rustls::dep::pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer};
+use crate::rustls::dep::pki_types::{
+    CertificateDer, EchConfigListBytes, PrivateKeyDer, PrivatePkcs8KeyDer,
+};
 use crate::rustls::dep::rcgen::{self, KeyPair};
 use crate::rustls::dep::rustls::client::danger::ServerCertVerifier;
-use crate::rustls::dep::rustls::RootCertStore;
+use crate::rustls::dep::rustls::client::{
+    EchConfig, EchGreaseConfig, EchMode as RustlsEchMode, Resumption, WebPkiServerVerifier,
+};
+use crate::rustls::dep::rustls::crypto::{
+    aws_lc_rs::{self, hpke},
+    hpke::Hpke,
+    CryptoProvider,
+};
+use crate::rustls::dep::rustls::{version::TLS13, RootCertStore};
 use crate::rustls::dep::rustls::{ClientConfig, SupportedProtocolVersion, ALL_VERSIONS};
-use crate::rustls::key_log::KeyLogFile;
-use crate::rustls::verify::NoServerCertVerifier;
+use crate::rustls::key_log::RustlsKeyLog;
+use crate::rustls::session::RustlsClientSessionStore;
+use crate::rustls::verify::{
+    DeferredServerCertVerifier, NoServerCertVerifier, PinnedServerCertVerifier,
+};
 use rama_core::error::{ErrorContext, OpaqueError};
 use rama_net::address::Host;
-use rama_net::tls::client::{ClientAuth, ClientHelloExtension, ServerVerifyMode};
-use rama_net::tls::{ApplicationProtocol, DataEncoding};
+use rama_net::tls::client::{
+    ClientAuth, ClientHelloExtension, ClientSessionStore, DynamicVerifier, EchMode,
+    ServerVerifyMode,
+};
+use rama_net::tls::{ApplicationProtocol, DataEncoding, KeyLogIntent, MemoryKeyLog};
 use std::io::BufReader;
 use std::sync::{Arc, OnceLock};
 use tracing::trace;
@@ -28,10 +45,15 @@ pub struct TlsConnectorData {
 pub(super) struct ClientConfigInput {
     pub(super) protocol_versions: Option<Vec<&'static SupportedProtocolVersion>>,
     pub(super) client_auth: Option<(Vec<CertificateDer<'static>>, PrivateKeyDer<'static>)>,
-    pub(super) key_logger: Option<String>,
+    pub(super) key_logger: Option<KeyLogIntent>,
     pub(super) alpn_protos: Option<Vec<Vec<u8>>>,
     pub(super) cert_verifier: Option<Arc<dyn ServerCertVerifier>>,
+    /// Verifies the server cert chain after the handshake,
+    /// as it cannot be driven by the (sync) `cert_verifier`.
+    pub(super) dynamic_verifier: Option<DynamicVerifier>,
     pub(super) store_server_certificate_chain: bool,
+    pub(super) session_store: Option<ClientSessionStore>,
+    pub(super) ech_mode: Option<RustlsEchMode>,
 }
 
 impl TlsConnectorData {
@@ -95,18 +117,45 @@ impl TlsConnectorData {
 pub(super) struct ClientConfigData {
     pub(super) config: ClientConfig,
     pub(super) server_name: Option<Host>,
+    pub(super) key_log: Option<MemoryKeyLog>,
 }
 
 impl TlsConnectorData {
     pub(super) fn try_to_build_config(&self) -> Result<ClientConfigData, OpaqueError> {
-        let builder = ClientConfig::builder_with_protocol_versions(
-            self.client_config_input
-                .protocol_versions
-                .as_deref()
-                .unwrap_or(ALL_VERSIONS),
-        )
+        let protocol_versions = self
+            .client_config_input
+            .protocol_versions
+            .as_deref()
+            .unwrap_or(ALL_VERSIONS);
+
+        let builder = match self.client_config_input.ech_mode.clone() {
+            Some(ech_mode) => {
+                if !protocol_versions.contains(&&TLS13) {
+                    return Err(OpaqueError::from_display(
+                        "rustls connector: Encrypted Client Hello (ECH) requires TLS 1.3",
+                    ));
+                }
+                let provider = CryptoProvider::get_default()
+                    .cloned()
+                    .unwrap_or_else(|| Arc::new(aws_lc_rs::default_provider()));
+                ClientConfig::builder_with_provider(provider)
+                    .with_ech(ech_mode)
+                    .context("rustls connector: create tls client config with ECH")?
+            }
+            None => ClientConfig::builder_with_protocol_versions(protocol_versions),
+        }
         .with_root_certificates(client_root_certs());
 
+        // the client certificate is sent before the dynamic verifier gets to see
+        // the server certificate chain, so the combination is refused altogether
+        if self.client_config_input.client_auth.is_some()
+            && self.client_config_input.dynamic_verifier.is_some()
+        {
+            return Err(OpaqueError::from_display(
+                "rustls connector: client auth cannot be combined with a custom (dynamic) server cert verifier",
+            ));
+        }
+
         let mut client_config = match self.client_config_input.client_auth.as_ref() {
             Some((cert_chain, key_der)) => builder
                 .with_client_auth_cert(cert_chain.clone(), key_der.clone_key())
@@ -114,9 +163,15 @@ impl TlsConnectorData {
             None => builder.with_no_client_auth(),
         };
 
-        if let Some(key_logger) = self.client_config_input.key_logger.clone() {
-            let key_log = KeyLogFile::new(key_logger)?;
-            client_config.key_log = Arc::new(key_log);
+        let key_log_intent = self
+            .client_config_input
+            .key_logger
+            .clone()
+            .unwrap_or_default();
+        let (key_log_sink, key_log) =
+            new_key_log_sink(&key_log_intent).context("rustls connector: create key log sink")?;
+        if let Some(sink) = key_log_sink {
+            client_config.key_log = Arc::new(RustlsKeyLog::new(sink));
         }
 
         if let Some(alpn_protos) = self.client_config_input.alpn_protos.clone() {
@@ -129,9 +184,17 @@ impl TlsConnectorData {
                 .set_certificate_verifier(cert_verifier);
         }
 
+        if let Some(session_store) = self.client_config_input.session_store.clone() {
+            client_config.resumption = Resumption::store(Arc::new(RustlsClientSessionStore::new(
+                session_store,
+                client_config.alpn_protocols.clone(),
+            )));
+        }
+
         Ok(ClientConfigData {
             config: client_config,
             server_name: self.server_name.clone(),
+            key_log,
         })
     }
 
@@ -139,6 +202,12 @@ impl TlsConnectorData {
     /// a new [`TlsConnectorData`], where any defined properties of `other`
     /// take priority over conflicting ones in `self`.
     pub fn merge(&self, other: &TlsConnectorData) -> TlsConnectorData {
+        // the dynamic verifier only completes the verification of its cert verifier
+        let verifiers = match other.client_config_input.cert_verifier {
+            Some(_) => &other.client_config_input,
+            None => &self.client_config_input,
+        };
+
         TlsConnectorData {
             client_config_input: Arc::new(ClientConfigInput {
                 protocol_versions: other
@@ -167,14 +236,21 @@ impl TlsConnectorData {
                     .alpn_protos
                     .clone()
                     .or_else(|| self.client_config_input.alpn_protos.clone()),
-                cert_verifier: other
-                    .client_config_input
-                    .cert_verifier
-                    .clone()
-                    .or_else(|| self.client_config_input.cert_verifier.clone()),
+                cert_verifier: verifiers.cert_verifier.clone(),
+                dynamic_verifier: verifiers.dynamic_verifier.clone(),
                 store_server_certificate_chain: other
                     .client_config_input
                     .store_server_certificate_chain,
+                session_store: other
+                    .client_config_input
+                    .session_store
+                    .clone()
+                    .or_else(|| self.client_config_input.session_store.clone()),
+                ech_mode: other
+                    .client_config_input
+                    .ech_mode
+                    .clone()
+                    .or_else(|| self.client_config_input.ech_mode.clone()),
             }),
             server_name: other
                 .server_name
@@ -226,23 +302,8 @@ impl TryFrom<rama_net::tls::client::ClientConfig> for TlsConnectorData {
             }
             Some(ClientAuth::Single(data)) => {
                 // client TLS Certs
-                let cert_chain = match data.cert_chain {
-                    DataEncoding::Der(raw_data) => vec![CertificateDer::from(raw_data)],
-                    DataEncoding::DerStack(raw_data_list) => raw_data_list
-                        .into_iter()
-                        .map(CertificateDer::from)
-                        .collect(),
-                    DataEncoding::Pem(raw_data) => {
-                        let mut pem = BufReader::new(raw_data.as_bytes());
-                        let mut cert_chain = Vec::new();
-                        for cert in pemfile::certs(&mut pem) {
-                            cert_chain.push(
-                                cert.context("rustls/TlsConnectorData: parse tls client cert")?,
-                            );
-                        }
-                        cert_chain
-                    }
-                };
+                let cert_chain = certs_from_data_encoding(data.cert_chain)
+                    .context("rustls/TlsConnectorData: parse tls client cert")?;
 
                 // client TLS key
                 let key_der = match data.private_key {
@@ -269,6 +330,7 @@ impl TryFrom<rama_net::tls::client::ClientConfig> for TlsConnectorData {
             }
         };
 
+        let mut dynamic_verifier = None;
         let cert_verifier: Option<Arc<dyn ServerCertVerifier>> =
             match value.server_verify_mode.unwrap_or_default() {
                 ServerVerifyMode::Auto => None, // = default
@@ -276,8 +338,50 @@ impl TryFrom<rama_net::tls::client::ClientConfig> for TlsConnectorData {
                     trace!("rustls: tls connector data: disable server cert verification");
                     Some(Arc::new(NoServerCertVerifier::default()))
                 }
+                ServerVerifyMode::RootStore(root_store) => {
+                    trace!("rustls: tls connector data: use custom root store");
+                    Some(webpki_server_verifier(Some(&root_store))?)
+                }
+                ServerVerifyMode::Pinned { pins, root_store } => {
+                    trace!("rustls: tls connector data: use pinned server cert verifier");
+                    Some(Arc::new(PinnedServerCertVerifier::new(
+                        webpki_server_verifier(root_store.as_deref())?,
+                        pins.as_ref().clone(),
+                    )))
+                }
+                ServerVerifyMode::Custom(verifier) => {
+                    trace!("rustls: tls connector data: use custom (dynamic) server cert verifier");
+                    dynamic_verifier = Some(verifier);
+                    Some(Arc::new(DeferredServerCertVerifier::new(
+                        webpki_server_verifier(None)?,
+                    )))
+                }
             };
 
+        let ech_mode = match value.ech {
+            None => None,
+            Some(EchMode::Grease) => {
+                trace!("rustls: tls connector data: offer GREASE ECH");
+                let suite = hpke::DH_KEM_X25519_HKDF_SHA256_AES_128;
+                let (placeholder_key, _) = suite
+                    .generate_key_pair()
+                    .context("rustls/TlsConnectorData: generate GREASE ECH placeholder key")?;
+                Some(RustlsEchMode::Grease(EchGreaseConfig::new(
+                    suite,
+                    placeholder_key,
+                )))
+            }
+            Some(EchMode::Enable(ech_config_list)) => {
+                trace!("rustls: tls connector data: offer ECH");
+                let ech_config = EchConfig::new(
+                    EchConfigListBytes::from(ech_config_list.as_bytes()),
+                    hpke::ALL_SUPPORTED_SUITES,
+                )
+                .context("rustls/TlsConnectorData: select compatible ECH config")?;
+                Some(RustlsEchMode::Enable(ech_config))
+            }
+        };
+
         let mut alpn_protos = None;
         let mut server_name = None;
 
@@ -301,14 +405,13 @@ impl TryFrom<rama_net::tls::client::ClientConfig> for TlsConnectorData {
             client_config_input: Arc::new(ClientConfigInput {
                 protocol_versions,
                 client_auth,
-                key_logger: value
-                    .key_logger
-                    .clone()
-                    .unwrap_or_default()
-                    .into_file_path(),
+                key_logger: value.key_logger.clone(),
                 alpn_protos,
                 cert_verifier,
+                dynamic_verifier,
                 store_server_certificate_chain: value.store_server_certificate_chain,
+                session_store: value.session_store,
+                ech_mode,
             }),
             server_name,
         })
@@ -326,6 +429,48 @@ pub(super) fn client_root_certs() -> Arc<RootCertStore> {
         .clone()
 }
 
+fn webpki_server_verifier(
+    root_store: Option<&DataEncoding>,
+) -> Result<Arc<WebPkiServerVerifier>, OpaqueError> {
+    let roots = match root_store {
+        Some(data) => {
+            let mut roots = RootCertStore::empty();
+            for cert in certs_from_data_encoding(data.clone())
+                .context("rustls/TlsConnectorData: parse root store cert")?
+            {
+                roots
+                    .add(cert)
+                    .context("rustls/TlsConnectorData: add cert to root store")?;
+            }
+            Arc::new(roots)
+        }
+        None => client_root_certs(),
+    };
+    WebPkiServerVerifier::builder(roots)
+        .build()
+        .context("rustls/TlsConnectorData: build webpki server verifier")
+}
+
+fn certs_from_data_encoding(
+    data: DataEncoding,
+) -> Result<Vec<CertificateDer<'static>>, OpaqueError> {
+    Ok(match data {
+        DataEncoding::Der(raw_data) => vec![CertificateDer::from(raw_data)],
+        DataEncoding::DerStack(raw_data_list) => raw_data_list
+            .into_iter()
+            .map(CertificateDer::from)
+            .collect(),
+        DataEncoding::Pem(raw_data) => {
+            let mut pem = BufReader::new(raw_data.as_bytes());
+            let mut certs = Vec::new();
+            for cert in pemfile::certs(&mut pem) {
+                certs.push(cert.context("parse PEM cert")?);
+            }
+            certs
+        }
+    })
+}
+
 fn self_signed_client_auth(
 ) -> Result<(Vec<CertificateDer<'static>>, PrivateKeyDer<'static>), OpaqueError> {
     // Create a client end entity cert.
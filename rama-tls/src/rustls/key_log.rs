@@ -1,53 +1,24 @@
-use std::fmt;
-
-use crate::keylog::{new_key_log_file_handle, KeyLogFileHandle};
 use crate::rustls::dep::rustls::KeyLog;
-use rama_core::error::OpaqueError;
+use rama_net::tls::{KeyLogEntry, KeyLogSink};
 
 #[derive(Debug, Clone)]
-/// [`KeyLog`] implementation that opens a file for the given path.
-pub(super) struct KeyLogFile(KeyLogFileHandle);
+/// [`KeyLog`] implementation that writes to a [`KeyLogSink`].
+pub(super) struct RustlsKeyLog(KeyLogSink);
 
-impl KeyLogFile {
-    /// Makes a new [`KeyLogFile`].
-    pub(super) fn new(path: String) -> Result<Self, OpaqueError> {
-        let handle = new_key_log_file_handle(path)?;
-        Ok(KeyLogFile(handle))
+impl RustlsKeyLog {
+    /// Makes a new [`RustlsKeyLog`].
+    pub(super) fn new(sink: KeyLogSink) -> Self {
+        RustlsKeyLog(sink)
     }
 }
 
-impl KeyLog for KeyLogFile {
+impl KeyLog for RustlsKeyLog {
     #[inline]
     fn log(&self, label: &str, client_random: &[u8], secret: &[u8]) {
-        let line = format!(
-            "{} {:02x} {:02x}\n",
-            label,
-            PlainHex {
-                slice: client_random
-            },
-            PlainHex { slice: secret },
-        );
-        self.0.write_log_line(line);
-    }
-}
-
-struct PlainHex<'a, T: 'a> {
-    slice: &'a [T],
-}
-
-impl<T: fmt::LowerHex> fmt::LowerHex for PlainHex<'_, T> {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        fmt_inner_hex(self.slice, f, fmt::LowerHex::fmt)
-    }
-}
-
-fn fmt_inner_hex<T, F: Fn(&T, &mut fmt::Formatter) -> fmt::Result>(
-    slice: &[T],
-    f: &mut fmt::Formatter,
-    fmt_fn: F,
-) -> fmt::Result {
-    for val in slice.iter() {
-        fmt_fn(val, f)?;
+        self.0.write_key_log(&KeyLogEntry {
+            label: label.to_owned(),
+            client_random: client_random.to_vec(),
+            secret: secret.to_vec(),
+        });
     }
-    Ok(())
 }
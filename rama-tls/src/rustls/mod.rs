@@ -5,6 +5,7 @@ pub mod server;
 pub mod verify;
 
 mod key_log;
+mod session;
 
 pub mod dep {
     //! Dependencies for rama rustls modules.
@@ -1,12 +1,18 @@
+use crate::keylog::new_key_log_sink;
 use crate::rustls::dep::pemfile;
 use crate::rustls::dep::pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer};
 use crate::rustls::dep::rcgen::{self, KeyPair};
-use crate::rustls::dep::rustls::{self, server::WebPkiClientVerifier, RootCertStore};
-use crate::rustls::key_log::KeyLogFile;
+use crate::rustls::dep::rustls::{
+    self,
+    server::{NoServerSessionStorage, WebPkiClientVerifier},
+    RootCertStore,
+};
+use crate::rustls::key_log::RustlsKeyLog;
+use crate::rustls::session::{RustlsServerSessionStore, RustlsTicketer};
 use rama_core::error::{ErrorContext, OpaqueError};
 use rama_net::address::{Domain, Host};
 use rama_net::tls::server::{ClientVerifyMode, SelfSignedData, ServerAuth};
-use rama_net::tls::DataEncoding;
+use rama_net::tls::{DataEncoding, KeyLogIntent};
 use std::io::BufReader;
 use std::sync::Arc;
 
@@ -18,6 +24,8 @@ use std::sync::Arc;
 pub struct TlsAcceptorData {
     pub(super) server_config: Arc<rustls::ServerConfig>,
     pub(super) server_cert_chain: Option<Vec<CertificateDer<'static>>>,
+    pub(super) store_client_certificate_chain: bool,
+    pub(super) connection_key_log: bool,
 }
 
 impl TlsAcceptorData {
@@ -51,6 +59,8 @@ impl From<Arc<rustls::ServerConfig>> for TlsAcceptorData {
         Self {
             server_config: value,
             server_cert_chain: None,
+            store_client_certificate_chain: false,
+            connection_key_log: false,
         }
     }
 }
@@ -59,6 +69,10 @@ impl TryFrom<rama_net::tls::server::ServerConfig> for TlsAcceptorData {
     type Error = OpaqueError;
 
     fn try_from(value: rama_net::tls::server::ServerConfig) -> Result<Self, Self::Error> {
+        if value.ech_keys.is_some() {
+            return Err(OpaqueError::from_display("Encrypted Client Hello (ECH) not supported for Rustls servers (use boring instead)"));
+        }
+
         let mut server_cert_chain = None;
 
         let v: Vec<_> = value
@@ -191,11 +205,28 @@ impl TryFrom<rama_net::tls::server::ServerConfig> for TlsAcceptorData {
             }
         };
 
-        // set key logger if one is requested
-        if let Some(path) = value.key_logger.file_path() {
-            let key_logger = KeyLogFile::new(path).context("rustls/TlsAcceptorData")?;
-            server_config.key_log = Arc::new(key_logger);
-        };
+        // set key logger if one is requested,
+        // per connection key logs are set by the acceptor service for each connection
+        let connection_key_log = matches!(value.key_logger, KeyLogIntent::Connection);
+        if !connection_key_log {
+            let (sink, _) =
+                new_key_log_sink(&value.key_logger).context("rustls/TlsAcceptorData")?;
+            if let Some(sink) = sink {
+                server_config.key_log = Arc::new(RustlsKeyLog::new(sink));
+            }
+        }
+
+        // configure session resumption if requested,
+        // only enabling the resumption mechanisms which are explicitly defined
+        if let Some(resumption) = value.session_resumption {
+            server_config.session_storage = match resumption.session_cache {
+                Some(cache) => Arc::new(RustlsServerSessionStore(cache)),
+                None => Arc::new(NoServerSessionStorage {}),
+            };
+            if let Some(session_tickets) = resumption.session_tickets {
+                server_config.ticketer = Arc::new(RustlsTicketer::new(session_tickets));
+            }
+        }
 
         // set ALPN for negotiation, resulting in the (default) empty Vec if none was defined
         server_config.alpn_protocols = value
@@ -209,6 +240,8 @@ impl TryFrom<rama_net::tls::server::ServerConfig> for TlsAcceptorData {
         Ok(TlsAcceptorData {
             server_config: Arc::new(server_config),
             server_cert_chain,
+            store_client_certificate_chain: value.store_client_certificate_chain,
+            connection_key_log,
         })
     }
 }
@@ -1,8 +1,10 @@
 use crate::{
+    keylog::new_key_log_sink,
     rustls::dep::{
-        rustls::server::Acceptor,
+        rustls::{server::Acceptor, HandshakeKind},
         tokio_rustls::{server::TlsStream, LazyConfigAcceptor},
     },
+    rustls::key_log::RustlsKeyLog,
     types::SecureTransport,
 };
 use rama_core::{
@@ -11,9 +13,13 @@ use rama_core::{
 };
 use rama_net::{
     stream::Stream,
-    tls::{client::NegotiatedTlsParameters, ApplicationProtocol},
+    tls::{
+        client::NegotiatedTlsParameters, server::PeerCertificateIdentity, ApplicationProtocol,
+        DataEncoding, KeyLogIntent,
+    },
 };
 use rama_utils::macros::define_inner_service_accessors;
+use std::sync::Arc;
 
 use super::TlsAcceptorData;
 
@@ -83,10 +89,38 @@ where
             SecureTransport::default()
         };
 
-        let stream = start
-            .into_stream(tls_acceptor_data.server_config.clone())
-            .await?;
+        // per connection key logs require a server config with its own key log sink
+        let (server_config, key_log) = if tls_acceptor_data.connection_key_log {
+            let (sink, key_log) = new_key_log_sink(&KeyLogIntent::Connection)
+                .context("rustls acceptor: create connection key log")?;
+            let mut server_config = tls_acceptor_data.server_config.as_ref().clone();
+            if let Some(sink) = sink {
+                server_config.key_log = Arc::new(RustlsKeyLog::new(sink));
+            }
+            (Arc::new(server_config), key_log)
+        } else {
+            (tls_acceptor_data.server_config.clone(), None)
+        };
+
+        let stream = start.into_stream(server_config).await?;
         let (_, conn_data_ref) = stream.get_ref();
+
+        let peer_certificate_chain = tls_acceptor_data
+            .store_client_certificate_chain
+            .then(|| conn_data_ref.peer_certificates())
+            .flatten()
+            .map(DataEncoding::from);
+
+        if let Some(identity) = peer_certificate_chain.as_ref().and_then(|chain| {
+            PeerCertificateIdentity::from_cert_chain(chain)
+                .inspect_err(|err| {
+                    tracing::debug!(%err, "rustls acceptor: failed to parse peer certificate identity");
+                })
+                .ok()
+        }) {
+            ctx.insert(identity);
+        }
+
         ctx.insert(NegotiatedTlsParameters {
             protocol_version: conn_data_ref
                 .protocol_version()
@@ -95,10 +129,15 @@ where
             application_layer_protocol: conn_data_ref
                 .alpn_protocol()
                 .map(ApplicationProtocol::from),
-            // Currently not supported as this would mean we need to wrap rustls config
-            peer_certificate_chain: None,
+            peer_certificate_chain,
+            session_resumed: conn_data_ref.handshake_kind() == Some(HandshakeKind::Resumed),
+            ech_accepted: false,
         });
 
+        if let Some(key_log) = key_log {
+            ctx.insert(key_log);
+        }
+
         ctx.insert(secure_transport);
         self.inner.serve(ctx, stream).await.map_err(|err| {
             OpaqueError::from_boxed(err.into())
@@ -0,0 +1,264 @@
+use crate::rustls::dep::pki_types::ServerName;
+use crate::rustls::dep::rustls::{
+    client::{ClientSessionStore, Tls12ClientSessionValue, Tls13ClientSessionValue},
+    server::{ProducesTickets, StoresServerSessions},
+    NamedGroup,
+};
+use aws_lc_rs::{
+    aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN},
+    rand::{SecureRandom, SystemRandom},
+};
+use rama_net::address::Host;
+use rama_net::tls::client::{ClientSession, ClientSessionKey, ClientSessionStore as RamaStore};
+use rama_net::tls::server::{ServerSessionCache, SessionTicketConfig};
+use rama_net::tls::ProtocolVersion;
+use std::fmt;
+
+/// Adapter of a rama [`ClientSessionStore`] into a rustls [`ClientSessionStore`],
+/// keying the sessions by server name _and_ the ALPN protocols of the client config.
+///
+/// [`ClientSessionStore`]: rama_net::tls::client::ClientSessionStore
+pub(crate) struct RustlsClientSessionStore {
+    store: RamaStore,
+    alpn_protocols: Vec<Vec<u8>>,
+}
+
+impl RustlsClientSessionStore {
+    pub(crate) fn new(store: RamaStore, alpn_protocols: Vec<Vec<u8>>) -> Self {
+        Self {
+            store,
+            alpn_protocols,
+        }
+    }
+
+    /// Sessions of tls 1.2 and tls 1.3 are stored apart, such that they do not replace
+    /// (or get consumed in place of) one another when both are used for the same server.
+    fn key(&self, server_name: &ServerName<'_>, version: ProtocolVersion) -> ClientSessionKey {
+        ClientSessionKey::new(Host::try_from(server_name).ok(), &self.alpn_protocols)
+            .with_protocol_version(version)
+    }
+}
+
+impl fmt::Debug for RustlsClientSessionStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RustlsClientSessionStore")
+            .field("store", &self.store)
+            .field("alpn_protocols", &self.alpn_protocols)
+            .finish()
+    }
+}
+
+impl ClientSessionStore for RustlsClientSessionStore {
+    // kx hints are only an optimisation (saving a round trip),
+    // and not worth the burden of persisting them in the shared store
+    fn set_kx_hint(&self, _server_name: ServerName<'static>, _group: NamedGroup) {}
+
+    fn kx_hint(&self, _server_name: &ServerName<'_>) -> Option<NamedGroup> {
+        None
+    }
+
+    fn set_tls12_session(&self, server_name: ServerName<'static>, value: Tls12ClientSessionValue) {
+        let key = self.key(&server_name, ProtocolVersion::TLSv1_2);
+        self.store.remove(&key);
+        self.store.insert(key, ClientSession::new(value));
+    }
+
+    fn tls12_session(&self, server_name: &ServerName<'_>) -> Option<Tls12ClientSessionValue> {
+        self.store
+            .get(&self.key(server_name, ProtocolVersion::TLSv1_2))?
+            .downcast_ref::<Tls12ClientSessionValue>()
+            .cloned()
+    }
+
+    fn remove_tls12_session(&self, server_name: &ServerName<'static>) {
+        self.store
+            .remove(&self.key(server_name, ProtocolVersion::TLSv1_2))
+    }
+
+    fn insert_tls13_ticket(
+        &self,
+        server_name: ServerName<'static>,
+        value: Tls13ClientSessionValue,
+    ) {
+        self.store.insert(
+            self.key(&server_name, ProtocolVersion::TLSv1_3),
+            ClientSession::new(value),
+        )
+    }
+
+    fn take_tls13_ticket(
+        &self,
+        server_name: &ServerName<'static>,
+    ) -> Option<Tls13ClientSessionValue> {
+        self.store
+            .take(&self.key(server_name, ProtocolVersion::TLSv1_3))?
+            .try_into_inner::<Tls13ClientSessionValue>()
+            .ok()
+    }
+}
+
+#[derive(Debug)]
+/// Adapter of a rama [`ServerSessionCache`] into a rustls [`StoresServerSessions`].
+pub(crate) struct RustlsServerSessionStore(pub(crate) ServerSessionCache);
+
+impl StoresServerSessions for RustlsServerSessionStore {
+    fn put(&self, key: Vec<u8>, value: Vec<u8>) -> bool {
+        self.0.put(key, value)
+    }
+
+    fn get(&self, key: &[u8]) -> Option<Vec<u8>> {
+        self.0.get(key)
+    }
+
+    fn take(&self, key: &[u8]) -> Option<Vec<u8>> {
+        self.0.take(key)
+    }
+
+    fn can_cache(&self) -> bool {
+        true
+    }
+}
+
+/// A rustls [`ProducesTickets`] implementation which encrypts tickets
+/// using AES-256-GCM with the keys provided by a rama [`SessionTicketConfig`].
+///
+/// Tickets are encoded as `key name (16) || nonce (12) || ciphertext || tag (16)`.
+pub(crate) struct RustlsTicketer {
+    config: SessionTicketConfig,
+    rng: SystemRandom,
+}
+
+impl RustlsTicketer {
+    const KEY_NAME_LEN: usize = 16;
+
+    pub(crate) fn new(config: SessionTicketConfig) -> Self {
+        Self {
+            config,
+            rng: SystemRandom::new(),
+        }
+    }
+}
+
+impl fmt::Debug for RustlsTicketer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RustlsTicketer")
+            .field("config", &self.config)
+            .finish()
+    }
+}
+
+impl ProducesTickets for RustlsTicketer {
+    fn enabled(&self) -> bool {
+        true
+    }
+
+    fn lifetime(&self) -> u32 {
+        self.config
+            .lifetime
+            .as_secs()
+            .try_into()
+            .unwrap_or(u32::MAX)
+    }
+
+    fn encrypt(&self, plain: &[u8]) -> Option<Vec<u8>> {
+        let keys = self.config.key_provider.ticket_keys();
+        let key = LessSafeKey::new(UnboundKey::new(&AES_256_GCM, keys.current.secret()).ok()?);
+
+        let mut nonce = [0u8; NONCE_LEN];
+        self.rng.fill(&mut nonce).ok()?;
+
+        let mut in_out = plain.to_vec();
+        key.seal_in_place_append_tag(
+            Nonce::assume_unique_for_key(nonce),
+            Aad::from(keys.current.name()),
+            &mut in_out,
+        )
+        .ok()?;
+
+        let mut ticket = Vec::with_capacity(Self::KEY_NAME_LEN + NONCE_LEN + in_out.len());
+        ticket.extend_from_slice(keys.current.name());
+        ticket.extend_from_slice(&nonce);
+        ticket.extend_from_slice(&in_out);
+        Some(ticket)
+    }
+
+    fn decrypt(&self, cipher: &[u8]) -> Option<Vec<u8>> {
+        let (name, rest) = cipher.split_at_checked(Self::KEY_NAME_LEN)?;
+        let (nonce, ciphertext) = rest.split_at_checked(NONCE_LEN)?;
+
+        let keys = self.config.key_provider.ticket_keys();
+        let ticket_key = keys.find(name)?;
+        let key = LessSafeKey::new(UnboundKey::new(&AES_256_GCM, ticket_key.secret()).ok()?);
+
+        let mut in_out = ciphertext.to_vec();
+        let plain_len = key
+            .open_in_place(
+                Nonce::try_assume_unique_for_key(nonce).ok()?,
+                Aad::from(ticket_key.name()),
+                &mut in_out,
+            )
+            .ok()?
+            .len();
+        in_out.truncate(plain_len);
+        Some(in_out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rama_net::tls::server::{
+        RotatingSessionTicketKeyProvider, SessionTicketKey, SessionTicketKeys,
+    };
+    use std::time::Duration;
+
+    #[test]
+    fn test_ticketer_roundtrip() {
+        let provider = RotatingSessionTicketKeyProvider::new(Duration::from_secs(3600));
+        let ticketer = RustlsTicketer::new(SessionTicketConfig::new(provider.clone()));
+
+        let ticket = ticketer.encrypt(b"session state").unwrap();
+        assert_eq!(
+            b"session state".to_vec(),
+            ticketer.decrypt(&ticket).unwrap()
+        );
+
+        // tickets remain valid for one rotation
+        provider.rotate();
+        assert_eq!(
+            b"session state".to_vec(),
+            ticketer.decrypt(&ticket).unwrap()
+        );
+        provider.rotate();
+        assert!(ticketer.decrypt(&ticket).is_none());
+    }
+
+    #[test]
+    fn test_ticketer_shared_keys() {
+        let keys = SessionTicketKeys {
+            current: SessionTicketKey::new([1; 16], [2; 32]),
+            previous: vec![],
+        };
+        let a = RustlsTicketer::new(SessionTicketConfig::new(keys.clone()));
+        let b = RustlsTicketer::new(SessionTicketConfig::new(keys));
+
+        let ticket = a.encrypt(b"shared").unwrap();
+        assert_eq!(b"shared".to_vec(), b.decrypt(&ticket).unwrap());
+    }
+
+    #[test]
+    fn test_ticketer_rejects_tampered_tickets() {
+        let keys = SessionTicketKeys {
+            current: SessionTicketKey::new([1; 16], [2; 32]),
+            previous: vec![],
+        };
+        let ticketer = RustlsTicketer::new(SessionTicketConfig::new(keys));
+
+        let mut ticket = ticketer.encrypt(b"state").unwrap();
+        let last = ticket.len() - 1;
+        ticket[last] ^= 0xff;
+        assert!(ticketer.decrypt(&ticket).is_none());
+        assert!(ticketer.decrypt(&[]).is_none());
+        assert!(ticketer.decrypt(&[1; 20]).is_none());
+    }
+}
@@ -1,14 +1,23 @@
 //! TLS Verify support for Rustls usage in Rama.
 //!
-//! ... or rather the lack of verification where it is not needed.
+//! ... or rather the lack of verification where it is not needed,
+//! and the verification on top of the default where it is.
 
 use crate::rustls::dep::{
     pki_types::{CertificateDer, ServerName, UnixTime},
     rustls::{
-        client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier},
-        DigitallySignedStruct, SignatureScheme,
+        client::{
+            danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier},
+            WebPkiServerVerifier,
+        },
+        CertificateError, DigitallySignedStruct, SignatureScheme,
     },
 };
+use rama_net::tls::client::SpkiPinSet;
+use std::sync::Arc;
+
+#[cfg(doc)]
+use rama_net::tls::client::DynamicVerifier;
 
 /// Cert verifier that does not verify the server certificate.
 #[derive(Debug)]
@@ -76,3 +85,132 @@ impl ServerCertVerifier for NoServerCertVerifier {
         ]
     }
 }
+
+/// Cert verifier that verifies the server certificate using
+/// the given [`WebPkiServerVerifier`], and on top of that requires
+/// at least one certificate in the chain to match one of the pinned public keys.
+#[derive(Debug)]
+pub struct PinnedServerCertVerifier {
+    inner: Arc<WebPkiServerVerifier>,
+    pins: SpkiPinSet,
+}
+
+impl PinnedServerCertVerifier {
+    /// Create a new [`PinnedServerCertVerifier`].
+    pub fn new(inner: Arc<WebPkiServerVerifier>, pins: SpkiPinSet) -> Self {
+        Self { inner, pins }
+    }
+}
+
+impl ServerCertVerifier for PinnedServerCertVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let verified = self.inner.verify_server_cert(
+            end_entity,
+            intermediates,
+            server_name,
+            ocsp_response,
+            now,
+        )?;
+        self.pins
+            .verify_cert_chain(
+                std::iter::once(end_entity)
+                    .chain(intermediates.iter())
+                    .map(|cert| cert.as_ref()),
+            )
+            .map_err(|err| {
+                tracing::debug!(error = %err, "rustls: pinned server cert verifier: no match");
+                rustls::Error::InvalidCertificate(CertificateError::ApplicationVerificationFailure)
+            })?;
+        Ok(verified)
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls12_signature(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls13_signature(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.inner.supported_verify_schemes()
+    }
+}
+
+/// Cert verifier that accepts the server certificate chain as-is,
+/// such that it can be verified after the handshake by a (async) [`DynamicVerifier`],
+/// and verifies the handshake signatures using the given [`WebPkiServerVerifier`].
+///
+/// As rustls verifies the chain synchronously, the [`DynamicVerifier`] cannot
+/// be driven from within the handshake. The rustls [`TlsConnector`] therefore
+/// uses this verifier for [`ServerVerifyMode::Custom`] and runs the [`DynamicVerifier`]
+/// once the handshake is done, before the connection is returned to the caller.
+/// A rejected server has its stored sessions dropped and its connection closed,
+/// and client auth is refused for such configs, as the client certificate
+/// would be sent before the server is verified.
+///
+/// [`TlsConnector`]: crate::rustls::client::TlsConnector
+/// [`ServerVerifyMode::Custom`]: rama_net::tls::client::ServerVerifyMode::Custom
+#[derive(Debug)]
+pub struct DeferredServerCertVerifier {
+    inner: Arc<WebPkiServerVerifier>,
+}
+
+impl DeferredServerCertVerifier {
+    /// Create a new [`DeferredServerCertVerifier`].
+    pub fn new(inner: Arc<WebPkiServerVerifier>) -> Self {
+        Self { inner }
+    }
+}
+
+impl ServerCertVerifier for DeferredServerCertVerifier {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls12_signature(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls13_signature(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.inner.supported_verify_schemes()
+    }
+}
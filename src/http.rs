@@ -11,6 +11,10 @@ pub use ::rama_http::{
     Method, Request, Scheme, StatusCode, Uri, Version,
 };
 
+#[cfg(feature = "grpc")]
+#[doc(inline)]
+pub use ::rama_http::grpc;
+
 #[cfg(feature = "http-full")]
 #[doc(inline)]
 pub use ::rama_http_core as core;
@@ -31,4 +35,4 @@ pub mod layer {
 
 #[cfg(feature = "http-full")]
 #[doc(inline)]
-pub use ::rama_http_backend::{client, server};
+pub use ::rama_http_backend::{client, masque, proxy, server};
@@ -1500,6 +1500,7 @@ mod conn {
     use rama::error::BoxError;
     use rama::http::core::body::{Body, Frame};
     use rama::http::core::client::conn;
+    use rama::http::core::ext::Protocol;
     use rama::http::core::service::RamaHttpService;
     use rama::http::core::upgrade::OnUpgrade;
     use rama::http::dep::http_body_util::{BodyExt, Empty, StreamBody};
@@ -2661,6 +2662,97 @@ mod conn {
         done_tx.send(()).unwrap();
     }
 
+    #[tokio::test]
+    async fn h2_extended_connect() {
+        let (listener, addr) = setup_tk_test_server().await;
+
+        tokio::spawn(async move {
+            let sock = listener.accept().await.unwrap().0;
+            let mut h2 = rama::http::core::h2::server::Builder::new()
+                .enable_connect_protocol()
+                .handshake::<_, Bytes>(sock)
+                .await
+                .unwrap();
+
+            let (req, mut respond) = h2.accept().await.unwrap().unwrap();
+            tokio::spawn(async move {
+                poll_fn(|cx| h2.poll_closed(cx)).await.unwrap();
+            });
+            assert_eq!(req.method(), Method::CONNECT);
+            assert_eq!(
+                req.extensions()
+                    .get::<rama::http::core::h2::ext::Protocol>()
+                    .unwrap()
+                    .as_str(),
+                "websocket"
+            );
+
+            let mut send_stream = respond.send_response(Response::new(()), false).unwrap();
+            send_stream.send_data("Bread?".into(), true).unwrap();
+        });
+
+        let io = tcp_connect(&addr).await.expect("tcp connect");
+        let (client, conn) = conn::http2::Builder::new(Executor::new())
+            .handshake(io)
+            .await
+            .expect("http handshake");
+
+        tokio::spawn(async move {
+            conn.await.expect("client conn shouldn't error");
+        });
+
+        // sent immediately, prior to having received the settings of the server
+        let req = Request::connect("http://localhost/chat")
+            .extension(Protocol::from_static("websocket"))
+            .body(Empty::<Bytes>::new())
+            .unwrap();
+        let res = client.send_request(req).await.expect("send_request");
+        assert_eq!(res.status(), StatusCode::OK);
+
+        let mut upgraded = rama::http::core::upgrade::on(res).await.unwrap();
+        let mut vec = vec![];
+        upgraded.read_to_end(&mut vec).await.unwrap();
+        assert_eq!(s(&vec), "Bread?");
+    }
+
+    #[tokio::test]
+    async fn h2_extended_connect_not_enabled_by_server() {
+        let (listener, addr) = setup_tk_test_server().await;
+        let (done_tx, done_rx) = oneshot::channel::<()>();
+
+        tokio::spawn(async move {
+            let sock = listener.accept().await.unwrap().0;
+            let mut h2 = rama::http::core::h2::server::handshake(sock).await.unwrap();
+            tokio::spawn(async move {
+                poll_fn(|cx| h2.poll_closed(cx)).await.unwrap();
+            });
+            let _ = done_rx.await;
+        });
+
+        let io = tcp_connect(&addr).await.expect("tcp connect");
+        let (client, conn) = conn::http2::Builder::new(Executor::new())
+            .handshake(io)
+            .await
+            .expect("http handshake");
+
+        tokio::spawn(async move {
+            conn.await.expect("client conn shouldn't error");
+        });
+
+        let req = Request::connect("http://localhost/chat")
+            .extension(Protocol::from_static("websocket"))
+            .body(Empty::<Bytes>::new())
+            .unwrap();
+        let err = client.send_request(req).await.unwrap_err();
+        let err = std::error::Error::source(&err).expect("h2 error source");
+        assert_eq!(
+            err.to_string(),
+            "user error: sending extended CONNECT request to peer who did not enable the extended CONNECT protocol"
+        );
+
+        done_tx.send(()).unwrap();
+    }
+
     #[tokio::test]
     async fn test_body_panics() {
         let (listener, addr) = setup_tk_test_server().await;
@@ -1653,6 +1653,12 @@ async fn extended_connect_request() {
     let h2 = async move {
         let (mut client, mut h2) = client::handshake(io).await.unwrap();
 
+        // the extended CONNECT protocol is only known to be enabled
+        // once the initial settings of the server are received
+        h2.drive(poll_fn(|cx| client.poll_remote_initial_settings(cx)))
+            .await
+            .unwrap();
+
         let request = Request::connect("http://bread/baguette")
             .extension(Protocol::from("the-bread-protocol"))
             .body(())
@@ -1664,6 +1670,41 @@ async fn extended_connect_request() {
     join(srv, h2).await;
 }
 
+#[tokio::test]
+#[ignore]
+async fn extended_connect_request_to_peer_without_connect_protocol() {
+    h2_support::trace_init!();
+
+    let (io, mut srv) = mock::new();
+
+    let srv = async move {
+        let settings = srv.assert_client_handshake().await;
+        assert_default_settings!(settings);
+        idle_ms(10).await;
+    };
+
+    let h2 = async move {
+        let (mut client, mut h2) = client::handshake(io).await.unwrap();
+
+        h2.drive(poll_fn(|cx| client.poll_remote_initial_settings(cx)))
+            .await
+            .unwrap();
+        assert!(!client.is_extended_connect_protocol_enabled());
+
+        let request = Request::connect("http://bread/baguette")
+            .extension(Protocol::from("the-bread-protocol"))
+            .body(())
+            .unwrap();
+        let err = client.send_request(request, true).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "user error: sending extended CONNECT request to peer who did not enable the extended CONNECT protocol"
+        );
+    };
+
+    join(srv, h2).await;
+}
+
 #[tokio::test]
 #[ignore]
 async fn rogue_server_odd_headers() {
@@ -0,0 +1,107 @@
+use super::utils;
+use rama::{
+    http::{
+        client::HttpClient,
+        grpc::{self, client::GrpcClient, CompressionEncoding, GrpcCode, ProstCodec},
+        Uri,
+    },
+    Context,
+};
+use std::time::Duration;
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct Number {
+    #[prost(int64, tag = "1")]
+    value: i64,
+}
+
+type NumberCodec = ProstCodec<Number, Number>;
+
+#[tokio::test]
+#[ignore]
+async fn test_http_grpc() {
+    utils::init_tracing();
+
+    let _runner = utils::ExampleRunner::<()>::interactive("http_grpc", Some("grpc"));
+
+    let client = GrpcClient::new(
+        HttpClient::default(),
+        Uri::from_static("http://127.0.0.1:62018"),
+    );
+    // only the Double method accepts compressed requests
+    let compressed_client = client
+        .clone()
+        .send_compressed(CompressionEncoding::Gzip)
+        .accept_compressed(CompressionEncoding::Gzip);
+
+    // wait for the example server to be up and running
+    let mut attempt = 0;
+    let response = loop {
+        match compressed_client
+            .unary(
+                Context::default(),
+                grpc::Request::new(Number { value: 21 }),
+                "/math.Math/Double",
+                NumberCodec::new(),
+            )
+            .await
+        {
+            Ok(response) => break response,
+            Err(status) if status.code() == GrpcCode::Unknown && attempt < 600 => {
+                attempt += 1;
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            }
+            Err(status) => panic!("unexpected status: {status:?}"),
+        }
+    };
+    assert_eq!("gzip", response.metadata()["grpc-encoding"]);
+    assert_eq!(42, response.into_inner().value);
+
+    let status = client
+        .unary(
+            Context::default(),
+            grpc::Request::new(Number { value: i64::MAX }),
+            "/math.Math/Double",
+            NumberCodec::new(),
+        )
+        .await
+        .unwrap_err();
+    assert_eq!(GrpcCode::OutOfRange, status.code());
+
+    let response = client
+        .server_streaming(
+            Context::default(),
+            grpc::Request::new(Number { value: 3 }),
+            "/math.Math/CountTo",
+            NumberCodec::new(),
+        )
+        .await
+        .unwrap();
+    let mut messages = response.into_inner();
+    for expected in 1..=3 {
+        assert_eq!(expected, messages.message().await.unwrap().unwrap().value);
+    }
+    assert!(messages.message().await.unwrap().is_none());
+
+    // the deadline applies to the entire stream of response messages
+    let mut request = grpc::Request::new(Number { value: 100 });
+    request.set_timeout(Duration::from_millis(250));
+    let response = client
+        .server_streaming(
+            Context::default(),
+            request,
+            "/math.Math/CountTo",
+            NumberCodec::new(),
+        )
+        .await
+        .unwrap();
+    let mut messages = response.into_inner();
+    let status = loop {
+        match messages.message().await {
+            Ok(Some(_)) => (),
+            Ok(None) => panic!("stream ended before the deadline expired"),
+            Err(status) => break status,
+        }
+    };
+    assert_eq!(GrpcCode::DeadlineExceeded, status.code());
+}
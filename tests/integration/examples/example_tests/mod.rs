@@ -2,10 +2,12 @@ mod utils;
 
 #[cfg(feature = "http-full")]
 mod http_conn_state;
-#[cfg(feature = "http-full")]
+#[cfg(all(feature = "http-full", feature = "proxy"))]
 mod http_connect_proxy;
 #[cfg(feature = "http-full")]
 mod http_form;
+#[cfg(all(feature = "grpc", feature = "http-full"))]
+mod http_grpc;
 #[cfg(feature = "http-full")]
 mod http_health_check;
 #[cfg(all(feature = "compression", feature = "http-full"))]
@@ -16,7 +18,7 @@ mod http_k8s_health;
 mod http_key_value_store;
 #[cfg(feature = "http-full")]
 mod http_listener_hello;
-#[cfg(all(feature = "http-full", feature = "rustls"))]
+#[cfg(all(feature = "http-full", feature = "proxy", feature = "rustls"))]
 mod http_mitm_proxy;
 #[cfg(feature = "http-full")]
 mod http_rate_limit;
@@ -32,7 +34,7 @@ mod http_telemetry;
 mod http_user_agent_classifier;
 #[cfg(all(feature = "compression", feature = "http-full"))]
 mod http_web_service_dir_and_api;
-#[cfg(all(feature = "http-full", feature = "rustls"))]
+#[cfg(all(feature = "http-full", feature = "proxy", feature = "rustls"))]
 mod https_connect_proxy;
 #[cfg(all(feature = "http-full", feature = "rustls"))]
 mod mtls_tunnel_and_service;